@@ -0,0 +1,31 @@
+#import bevy_pbr::forward_io::VertexOutput
+
+@group(2) @binding(0) var<uniform> time: f32;
+@group(2) @binding(1) var<uniform> intensity: f32;
+
+// Folded curtains drifting slowly along the band, green at the bottom fading to violet on top
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let x = in.uv.x;
+    let v = in.uv.y;
+
+    let fold = sin(x * 9.0 + time * 0.05) * 0.6 + sin(x * 23.0 - time * 0.08) * 0.3;
+    let rays = sin(x * 160.0 + fold * 6.0 + time * 0.3) * 0.5 + 0.5;
+    let curtain = mix(0.35, 1.0, rays * rays);
+
+    // Sharp lower edge that wanders up and down, soft fade towards the top
+    let edge = 0.08 + 0.06 * sin(x * 31.0 + time * 0.12);
+    let vertical = smoothstep(edge, edge + 0.06, v) * (1.0 - smoothstep(0.25, 1.0, v));
+
+    // Breaks in the band so it never looks like a full ring
+    let patches = smoothstep(-0.2, 0.6, sin(x * 5.0 + time * 0.02 + fold));
+    // Fade out at both ends of the arc
+    let ends = smoothstep(0.0, 0.15, x) * (1.0 - smoothstep(0.85, 1.0, x));
+
+    let green = vec3<f32>(0.1, 1.0, 0.45);
+    let violet = vec3<f32>(0.55, 0.2, 0.9);
+    let color = mix(green, violet, smoothstep(0.2, 0.8, v));
+
+    let strength = curtain * vertical * patches * ends * intensity * 0.8;
+    return vec4<f32>(color * strength, strength);
+}
@@ -0,0 +1,135 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::alpha_discard,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+#endif
+
+struct TerrainParams {
+    texture_scale: f32,
+    slope_start: f32,
+    slope_end: f32,
+    triplanar: u32,
+    wetness: f32,
+    snow_line: f32,
+    foliage_tint: vec3<f32>,
+    water_level: f32,
+    shore_band: f32,
+    strata_spacing: f32,
+    strata_contrast: f32,
+    sky_light: vec3<f32>,
+    ground_light: vec3<f32>,
+}
+
+@group(2) @binding(100) var<uniform> terrain: TerrainParams;
+@group(2) @binding(101) var ground_texture: texture_2d<f32>;
+@group(2) @binding(102) var ground_sampler: sampler;
+@group(2) @binding(103) var rock_texture: texture_2d<f32>;
+@group(2) @binding(104) var rock_sampler: sampler;
+
+// Project the texture along the three world axes and blend by the normal
+fn triplanar_rock(position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var weights = pow(abs(normal), vec3<f32>(4.0));
+    weights = weights / (weights.x + weights.y + weights.z);
+    let x = textureSample(rock_texture, rock_sampler, position.zy * terrain.texture_scale).rgb;
+    let y = textureSample(rock_texture, rock_sampler, position.xz * terrain.texture_scale).rgb;
+    let z = textureSample(rock_texture, rock_sampler, position.xy * terrain.texture_scale).rgb;
+    return x * weights.x + y * weights.y + z * weights.z;
+}
+
+fn band_hash(band: f32) -> f32 {
+    return fract(sin(band * 12.9898) * 43758.5453);
+}
+
+// Color multiplier for rock layers stacked by world height, each with its own shade and
+// warmth. The layers waver a little so they don't look ruled, and dark seams split them.
+fn strata(position: vec3<f32>) -> vec3<f32> {
+    let warp = sin(position.x * 0.07) * 0.35 + sin(position.z * 0.05 + 1.3) * 0.35;
+    let layer = position.y / terrain.strata_spacing + warp;
+    let band = floor(layer);
+    let tone = mix(vec3<f32>(0.86, 0.9, 0.96), vec3<f32>(1.1, 0.98, 0.84), band_hash(band + 57.0))
+        * mix(0.75, 1.15, band_hash(band));
+
+    // Seams and bands fade out once they get smaller than a pixel, instead of shimmering
+    let footprint = fwidth(layer);
+    let edge = min(fract(layer), 1.0 - fract(layer));
+    let seam = 1.0 - smoothstep(0.0, max(0.06, footprint), edge);
+    let visible = 1.0 - smoothstep(0.3, 0.8, footprint);
+    let banded = tone * mix(1.0, 0.62, seam);
+    return mix(vec3<f32>(1.0), banded, terrain.strata_contrast * visible);
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let position = in.world_position.xyz;
+    let normal = normalize(in.world_normal);
+    let planar_uv = position.xz * terrain.texture_scale;
+
+    let ground = textureSample(ground_texture, ground_sampler, planar_uv).rgb;
+    let planar_rock = textureSample(rock_texture, rock_sampler, planar_uv).rgb;
+    let projected_rock = triplanar_rock(position, normal);
+    let rock = select(planar_rock, projected_rock, terrain.triplanar != 0u);
+
+    let slope = 1.0 - normal.y;
+    let rock_amount = smoothstep(terrain.slope_start, terrain.slope_end, slope);
+    // Cliffs, steeper than where rock fully replaces ground, show layered rock instead
+    // of the vertex colors stretched down their face
+    let cliff = smoothstep(terrain.slope_end, terrain.slope_end + 0.25, slope);
+    let detail = mix(ground, rock, rock_amount) * mix(vec3<f32>(1.0), strata(position), cliff);
+
+    // Seasons tint the grass (only green colors, like the moisture tint) and cover flat
+    // ground above the snow line
+    var color = pbr_input.material.base_color.rgb;
+    let greenness = clamp((color.g - max(color.r, color.b)) * 4.0, 0.0, 1.0);
+    color = color * mix(vec3<f32>(1.0), terrain.foliage_tint, greenness);
+    let snow = smoothstep(terrain.snow_line, terrain.snow_line + 1.5, position.y) * smoothstep(0.6, 0.85, normal.y);
+    color = mix(color, vec3<f32>(0.92, 0.94, 0.97), snow);
+
+    // Wet ground gets darker and glossier, most where water can sit on flat ground
+    let wet = terrain.wetness * mix(0.5, 1.0, clamp(normal.y, 0.0, 1.0));
+    let darkening = mix(1.0, 0.6, wet);
+    pbr_input.material.perceptual_roughness = mix(pbr_input.material.perceptual_roughness, 0.3, wet);
+
+    // Waves keep the sand near the water wet: darker, more saturated and with a sheen,
+    // fading out toward the top of the band and hidden under snow
+    let shore = (1.0 - smoothstep(terrain.shore_band * 0.4, terrain.shore_band, position.y - terrain.water_level)) * (1.0 - snow);
+    let luma = dot(color, vec3<f32>(0.299, 0.587, 0.114));
+    let wet_sand = max(mix(vec3<f32>(luma), color, 1.35), vec3<f32>(0.0)) * 0.62;
+    color = mix(color, wet_sand, shore);
+    pbr_input.material.perceptual_roughness = mix(pbr_input.material.perceptual_roughness, 0.22, shore);
+    pbr_input.material.reflectance = mix(pbr_input.material.reflectance, 0.7, shore);
+
+    pbr_input.material.base_color = vec4<f32>(color * detail * darkening, pbr_input.material.base_color.a);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    // Hemisphere light: sky from above, the ground's bounce from below, so slopes turned
+    // away from a low sun keep their color instead of going black
+    let hemisphere = mix(terrain.ground_light, terrain.sky_light, normal.y * 0.5 + 0.5);
+    let diffuse = pbr_input.material.base_color.rgb * (1.0 - pbr_input.material.metallic);
+    let fill = diffuse * hemisphere * pbr_input.diffuse_occlusion;
+    pbr_input.material.emissive = vec4<f32>(pbr_input.material.emissive.rgb + fill, pbr_input.material.emissive.a);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+    return out;
+}
@@ -0,0 +1,116 @@
+#import bevy_pbr::{
+    mesh_functions,
+    mesh_view_bindings::view,
+    view_transformations::position_world_to_clip,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::prepass_io::{Vertex, VertexOutput}
+#else
+#import bevy_pbr::forward_io::{Vertex, VertexOutput}
+#endif
+
+// CHUNK_SIZE in client.rs
+const CHUNK_SIZE: f32 = 50.0;
+
+struct DisplacementParams {
+    map_cells: u32,
+    mesh_cells: u32,
+    morph_start: f32,
+    morph_end: f32,
+}
+
+@group(2) @binding(105) var<uniform> displacement: DisplacementParams;
+@group(2) @binding(106) var height_map: texture_2d<f32>;
+@group(2) @binding(107) var color_map: texture_2d<f32>;
+
+// Height of a map cell corner, the map having an extra cell around the chunk
+fn map_height(corner: vec2<i32>) -> f32 {
+    let last = i32(displacement.map_cells) + 2;
+    return textureLoad(height_map, clamp(corner + vec2<i32>(1), vec2<i32>(0), vec2<i32>(last)), 0).r;
+}
+
+// Height anywhere in the chunk, in map cells from its corner, interpolated like the
+// triangles of a mesh built at the map's resolution would be
+fn height_at(position: vec2<f32>) -> f32 {
+    let corner = vec2<i32>(floor(position));
+    let t = position - floor(position);
+    let a = map_height(corner);
+    let b = map_height(corner + vec2<i32>(1, 0));
+    let c = map_height(corner + vec2<i32>(0, 1));
+    let d = map_height(corner + vec2<i32>(1, 1));
+    // Cells are split along the diagonal from b to c
+    if t.x + t.y <= 1.0 {
+        return a + (b - a) * t.x + (c - a) * t.y;
+    }
+    return d + (c - d) * (1.0 - t.x) + (b - d) * (1.0 - t.y);
+}
+
+// Height of a vertex of the drawn grid
+fn grid_height(vertex: vec2<i32>) -> f32 {
+    return height_at(vec2<f32>(vertex) * f32(displacement.map_cells) / f32(displacement.mesh_cells));
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+    let chunk_center = world_from_local[3].xyz;
+
+    let grid = vec2<i32>(round(vertex.uv * f32(displacement.mesh_cells)));
+    var height = grid_height(grid);
+
+    // Close to the next LOD ring, vertices the coarser grid lacks slide onto its edges: the
+    // middle of the edge or diagonal they split, between its two ends
+    let offset = abs(chunk_center.xz + vertex.position.xz - view.world_position.xz);
+    let morph = smoothstep(displacement.morph_start, displacement.morph_end, max(offset.x, offset.y));
+    let odd = grid % vec2<i32>(2);
+    if morph > 0.0 && (odd.x != 0 || odd.y != 0) {
+        let along = vec2<i32>(odd.x, odd.y * (1 - 2 * odd.x));
+        let coarse = (grid_height(grid - along) + grid_height(grid + along)) * 0.5;
+        height = mix(height, coarse, morph);
+    }
+
+    // Skirt vertices are already below the grid by the skirt's depth
+    let local = vec3<f32>(vertex.position.x, vertex.position.y + height, vertex.position.z);
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(local, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+    // Normal from the map's slopes around the vertex, one map cell each way
+    let map_position = vec2<f32>(grid) * f32(displacement.map_cells) / f32(displacement.mesh_cells);
+    let cell = CHUNK_SIZE / f32(displacement.map_cells);
+    let dx = height_at(map_position + vec2<f32>(1.0, 0.0)) - height_at(map_position - vec2<f32>(1.0, 0.0));
+    let dz = height_at(map_position + vec2<f32>(0.0, 1.0)) - height_at(map_position - vec2<f32>(0.0, 1.0));
+    let normal = normalize(vec3<f32>(-dx, 2.0 * cell, -dz));
+
+#ifdef PREPASS_PIPELINE
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.position;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#endif
+#ifdef MOTION_VECTOR_PREPASS
+    let previous_world_from_local = mesh_functions::get_previous_world_from_local(vertex.instance_index);
+    out.previous_world_position = mesh_functions::mesh_position_local_to_world(previous_world_from_local, vec4<f32>(local, 1.0));
+#endif
+#else
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(normal, vertex.instance_index);
+#ifdef VISIBILITY_RANGE_DITHER
+    out.visibility_range_dither = mesh_functions::get_visibility_range_dither_level(vertex.instance_index, world_from_local[3]);
+#endif
+#endif
+
+#ifdef VERTEX_UVS_A
+    out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_COLORS
+    let color_cell = vec2<i32>(round(map_position));
+    out.color = textureLoad(color_map, clamp(color_cell, vec2<i32>(0), vec2<i32>(i32(displacement.map_cells))), 0);
+#endif
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = vertex.instance_index;
+#endif
+    return out;
+}
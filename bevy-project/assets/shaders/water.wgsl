@@ -0,0 +1,167 @@
+#import bevy_pbr::{
+    mesh_functions::{get_world_from_local, mesh_position_local_to_world},
+    mesh_view_bindings::view,
+    view_transformations::position_world_to_clip,
+}
+
+const PI: f32 = 3.14159265;
+const MAX_WAVES: u32 = 4u;
+// MAX_RIPPLES in water.rs
+const MAX_RIPPLES: u32 = 16u;
+// Rings spread at this speed in m/s, fading out over their lifetime
+const RIPPLE_SPEED: f32 = 2.0;
+const RIPPLE_LIFETIME: f32 = 3.0;
+const RIPPLE_WAVELENGTH: f32 = 0.5;
+// Width of the band of crests around the ring's front
+const RIPPLE_WIDTH: f32 = 0.7;
+const RIPPLE_HEIGHT: f32 = 0.04;
+
+struct GerstnerWave {
+    direction: vec2<f32>,
+    amplitude: f32,
+    steepness: f32,
+    wavelength: f32,
+    speed: f32,
+    padding: vec2<f32>,
+}
+
+struct WaterWaves {
+    waves: array<GerstnerWave, 4>,
+    wave_count: u32,
+}
+
+struct Ripple {
+    center: vec2<f32>,
+    start_time: f32,
+    strength: f32,
+}
+
+struct WaterRipples {
+    ripples: array<Ripple, 16>,
+}
+
+// WaterTint in water.rs, set per biome
+struct WaterTint {
+    deep_color: vec3<f32>,
+    opacity: f32,
+    shallow_color: vec3<f32>,
+    wave_intensity: f32,
+}
+
+@group(2) @binding(0) var<uniform> time: f32;
+@group(2) @binding(1) var<uniform> water: WaterWaves;
+@group(2) @binding(2) var reflection_map: texture_cube<f32>;
+@group(2) @binding(3) var reflection_sampler: sampler;
+@group(2) @binding(4) var<uniform> ripples: WaterRipples;
+@group(2) @binding(5) var<uniform> tint: WaterTint;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+// Sums every Gerstner wave at a rest world position.
+// Returns the displaced position and writes the analytic normal.
+fn gerstner(rest: vec3<f32>, normal: ptr<function, vec3<f32>>) -> vec3<f32> {
+    var position = rest;
+    var n = vec3<f32>(0.0, 1.0, 0.0);
+    let count = min(water.wave_count, MAX_WAVES);
+
+    for (var i = 0u; i < count; i = i + 1u) {
+        let wave = water.waves[i];
+        let k = 2.0 * PI / wave.wavelength;
+        let wa = k * wave.amplitude;
+        // Normalized steepness so summed waves never loop over themselves
+        let q = wave.steepness / max(wa * f32(count), 0.0001);
+        let f = k * dot(wave.direction, rest.xz) - wave.speed * k * time;
+        let s = sin(f);
+        let c = cos(f);
+
+        position.x += q * wave.amplitude * wave.direction.x * c;
+        position.z += q * wave.amplitude * wave.direction.y * c;
+        position.y += wave.amplitude * s;
+
+        n.x -= wave.direction.x * wa * c;
+        n.z -= wave.direction.y * wa * c;
+        n.y -= q * wa * s;
+    }
+
+    *normal = normalize(n);
+    return position;
+}
+
+// Height of every ripple's rings at a point of the surface, and its slope along x and z.
+// Ripples are finer than the water mesh, they only bend the normals per pixel.
+fn ripple_height(point: vec2<f32>, slope: ptr<function, vec2<f32>>) -> f32 {
+    var height = 0.0;
+    var gradient = vec2<f32>(0.0);
+    let k = 2.0 * PI / RIPPLE_WAVELENGTH;
+    for (var i = 0u; i < MAX_RIPPLES; i = i + 1u) {
+        let ripple = ripples.ripples[i];
+        let age = time - ripple.start_time;
+        if ripple.strength <= 0.0 || age < 0.0 || age > RIPPLE_LIFETIME {
+            continue;
+        }
+        let offset = point - ripple.center;
+        let distance = length(offset);
+        let front = distance - age * RIPPLE_SPEED;
+        let envelope = exp(-front * front / (RIPPLE_WIDTH * RIPPLE_WIDTH))
+            * (1.0 - age / RIPPLE_LIFETIME) * ripple.strength * RIPPLE_HEIGHT;
+        height += envelope * cos(k * front);
+        let along = envelope * (-k * sin(k * front) - 2.0 * front / (RIPPLE_WIDTH * RIPPLE_WIDTH) * cos(k * front));
+        gradient += along * offset / max(distance, 0.001);
+    }
+    *slope = gradient;
+    return height;
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    let rest = mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0)).xyz;
+
+    var normal: vec3<f32>;
+    let world_position = gerstner(rest, &normal);
+
+    out.world_position = world_position;
+    out.world_normal = normal;
+    out.clip_position = position_world_to_clip(world_position);
+    out.uv = vertex.uv;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let light_dir = normalize(vec3<f32>(0.3, 1.0, 0.2));
+
+    var slope: vec2<f32>;
+    let ripple = ripple_height(in.world_position.xz, &slope);
+    // Steeper or flatter looking waves without moving the surface
+    let wave_normal = normalize(in.world_normal);
+    let tilted = vec3<f32>(wave_normal.x * tint.wave_intensity, wave_normal.y, wave_normal.z * tint.wave_intensity);
+    let n = normalize(normalize(tilted) - vec3<f32>(slope.x, 0.0, slope.y) * tint.wave_intensity);
+    let v = normalize(view.world_position - in.world_position);
+    let fresnel = pow(1.0 - max(dot(n, v), 0.0), 5.0);
+    // Cubemaps are left-handed, z is flipped to sample them
+    let r = reflect(-v, n);
+    let sky_color = textureSample(reflection_map, reflection_sampler, vec3<f32>(r.x, r.y, -r.z)).rgb;
+    let diffuse = max(dot(n, light_dir), 0.0);
+    let h = normalize(light_dir + v);
+    let specular = pow(max(dot(n, h), 0.0), 96.0);
+
+    let base = mix(tint.deep_color, tint.shallow_color, diffuse * 0.6);
+    // Ring crests catch a little foam
+    let foam = clamp(ripple / RIPPLE_HEIGHT, 0.0, 1.0) * 0.25;
+    let color = mix(base, sky_color, fresnel * 0.6) + vec3<f32>(specular + foam);
+    return vec4<f32>(color, mix(tint.opacity, 0.95, fresnel));
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContextSettings};
+use crate::client::ChunkManager;
+use crate::palette::ActiveTerrainPalette;
+use crate::settings::{AccessibilitySettings, ColorVision, GameSettings};
+use crate::terrain_edit::TerrainEdits;
+
+// Large prompts are drawn this much bigger, this far below the screen's center
+const LARGE_PROMPT_SCALE: f32 = 1.8;
+const LARGE_PROMPT_OFFSET: f32 = 90.0;
+const LARGE_PROMPT_BACKDROP: egui::Color32 = egui::Color32::from_black_alpha(200);
+
+// Linear RGB to LMS cone responses and back
+const RGB_TO_LMS: Mat3 = Mat3::from_cols_array(&[
+    17.8824, 3.45565, 0.0299566,
+    43.5161, 27.1554, 0.184309,
+    4.11935, 3.86714, 1.46709,
+]);
+const LMS_TO_RGB: Mat3 = Mat3::from_cols_array(&[
+    0.080_944_45, -0.010_248_53, -0.000_365_297,
+    -0.130_504_4, 0.054_019_33, -0.004_121_615,
+    0.116_721_07, -0.113_614_7, 0.693_511_4,
+]);
+
+// Accessibility options from the settings: the UI scale of egui windows and the HUD, colors
+// adjusted for color blindness on the terrain, and gameplay prompts made larger.
+#[derive(Default, Clone, Debug)]
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (apply_ui_scale, apply_color_vision));
+    }
+}
+
+// LMS responses the way someone missing one kind of cone sees them
+fn simulate(lms: Vec3, vision: ColorVision) -> Vec3 {
+    match vision {
+        ColorVision::Normal => lms,
+        ColorVision::Protanopia => Vec3::new(2.02344 * lms.y - 2.52581 * lms.z, lms.y, lms.z),
+        ColorVision::Deuteranopia => Vec3::new(lms.x, 0.494207 * lms.x + 1.24827 * lms.z, lms.z),
+        ColorVision::Tritanopia => Vec3::new(lms.x, lms.y, -0.395913 * lms.x + 0.801109 * lms.y),
+    }
+}
+
+// Daltonize a color: the contrast lost to a color vision deficiency is moved to the
+// channels still seen, so colors that would look alike stay apart
+pub fn daltonize(color: [f32; 4], vision: ColorVision) -> [f32; 4] {
+    if vision == ColorVision::Normal {
+        return color;
+    }
+    let rgb = Vec3::new(color[0], color[1], color[2]);
+    let seen = LMS_TO_RGB * simulate(RGB_TO_LMS * rgb, vision);
+    let error = rgb - seen;
+    let shift = Vec3::new(0.0, 0.7 * error.x + error.y, 0.7 * error.x + error.z);
+    let [r, g, b] = (rgb + shift).clamp(Vec3::ZERO, Vec3::ONE).to_array();
+    [r, g, b, color[3]]
+}
+
+// Show a short gameplay prompt where the caller puts it, or in large text on a dark panel
+// just below the screen's center with large prompts turned on
+pub fn show_prompt(
+    ctx: &egui::Context,
+    id: &str,
+    accessibility: &AccessibilitySettings,
+    anchor: egui::Align2,
+    offset: [f32; 2],
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    if !accessibility.large_prompts {
+        egui::Area::new(egui::Id::new(id)).anchor(anchor, offset).show(ctx, add_contents);
+        return;
+    }
+    egui::Area::new(egui::Id::new(id))
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, LARGE_PROMPT_OFFSET])
+        .show(ctx, |ui| {
+            egui::Frame::NONE
+                .fill(LARGE_PROMPT_BACKDROP)
+                .corner_radius(6.0)
+                .inner_margin(12.0)
+                .show(ui, |ui| {
+                    for font in ui.style_mut().text_styles.values_mut() {
+                        font.size *= LARGE_PROMPT_SCALE;
+                    }
+                    ui.vertical_centered(add_contents);
+                });
+        });
+}
+
+fn apply_ui_scale(
+    settings: Res<GameSettings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut egui_query: Query<&mut EguiContextSettings>,
+) {
+    let scale = settings.accessibility.ui_scale;
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+    for mut egui_settings in egui_query.iter_mut() {
+        if egui_settings.scale_factor != scale {
+            egui_settings.scale_factor = scale;
+        }
+    }
+}
+
+// Recolor the terrain for the color vision picked
+fn apply_color_vision(
+    settings: Res<GameSettings>,
+    mut palette: ResMut<ActiveTerrainPalette>,
+    chunk_manager: Res<ChunkManager>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+) {
+    let vision = settings.accessibility.color_vision;
+    if palette.0.color_vision == vision {
+        return;
+    }
+    palette.0.color_vision = vision;
+    terrain_edits.remesh_all(&chunk_manager);
+    info!("Terrain colors adjusted for {} color vision", vision.label());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_vision_keeps_colors() {
+        let color = [0.8, 0.2, 0.1, 0.5];
+        assert_eq!(daltonize(color, ColorVision::Normal), color);
+    }
+
+    #[test]
+    fn red_and_green_are_pulled_apart() {
+        let (red, green) = ([0.8, 0.2, 0.2, 1.0], [0.35, 0.75, 0.3, 1.0]);
+        for vision in [ColorVision::Protanopia, ColorVision::Deuteranopia] {
+            let (red, green) = (daltonize(red, vision), daltonize(green, vision));
+            // The blue channel, seen by both, now tells them apart
+            assert!((red[2] - green[2]).abs() > 0.3, "{:?}: {:?} {:?}", vision, red, green);
+            assert_eq!(red[3], 1.0);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+use bevy::prelude::*;
+use crate::building::Structures;
+use crate::client::CHUNK_SIZE;
+use crate::console::{CommandResult, ConsoleAppExt, ConsoleCommand, ConsoleQueue, ConsoleReply, ConsoleRequest};
+use crate::day_night::TimeOfDay;
+use crate::net::ClientId;
+use crate::road::{find_road, link_sites, Road};
+use crate::save::{LoadedSave, SaveRequested, SavedTime};
+use crate::season::Season;
+use crate::server::{ClientConnections, ServerChunks, ServerWorld};
+use crate::settings::SaveSettings;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::world_border::WorldBorder;
+
+// Wait after a wrong password, so guessing it over the network is slow
+const AUTH_FAILURE_DELAY: Duration = Duration::from_secs(2);
+
+// Server administration commands (kick, time, regen, road, border, save) in the console registry.
+// With `AdminConfig` they are also read from stdin and from an admin TCP port.
+#[derive(Default, Clone, Debug)]
+pub struct AdminPlugin;
+
+impl Plugin for AdminPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AdminConfig>()
+            .register_console_command(ConsoleCommand {
+                name: "kick",
+                usage: "kick <player id>",
+                help: "disconnect a player",
+                run: kick_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "time",
+                usage: "time [hour]",
+                help: "show or set the time of day",
+                run: time_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "regen",
+                usage: "regen <chunk x> <chunk z>",
+                help: "regenerate a chunk from the world data",
+                run: regen_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "road",
+                usage: "road <x> <z> <x> <z> | link | clear",
+                help: "carve a dirt road between two points or between nearby built structures, or remove the roads; ruins get theirs when loaded",
+                run: road_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "border",
+                usage: "border [<radius in chunks> | off]",
+                help: "show, set or remove the world border",
+                run: border_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "save",
+                usage: "save",
+                help: "write the world to disk",
+                run: save_command,
+            })
+            .add_systems(Startup, start_admin_channels);
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default)]
+pub struct AdminConfig {
+    // Read commands typed in the terminal
+    pub stdin: bool,
+    // Accept admin connections on this address, logging in with `password`
+    pub remote: Option<RemoteAdmin>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RemoteAdmin {
+    pub address: SocketAddr,
+    pub password: String,
+}
+
+fn kick_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let client = match args {
+        [id] => ClientId(id.parse().map_err(|_| format!("`{}` is not a player id", id))?),
+        _ => return Err("usage: kick <player id>".to_string()),
+    };
+    let mut server_world = world.get_resource_mut::<ServerWorld>().ok_or("no server running")?;
+    if server_world.players.contains_key(&client) {
+        server_world.disconnect(client);
+    }
+    if world.resource_mut::<ClientConnections>().disconnect(client) {
+        Ok(format!("Kicked player {}", client.0))
+    } else {
+        Err(format!("no player {}", client.0))
+    }
+}
+
+fn time_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let mut time_of_day = world.get_resource_mut::<TimeOfDay>().ok_or("no clock in this world")?;
+    match args {
+        [] => {}
+        [hour] => {
+            let hour: f32 = hour.parse().ok()
+                .filter(|hour: &f32| (0.0..24.0).contains(hour))
+                .ok_or_else(|| format!("`{}` is not an hour between 0 and 24", hour))?;
+            time_of_day.hour = hour;
+        }
+        _ => return Err("usage: time [hour]".to_string()),
+    }
+    let season = Season::at(&time_of_day);
+    Ok(format!("Day {}, {:02}:{:02}, {}", time_of_day.day, time_of_day.hour as u32, (time_of_day.hour.fract() * 60.0) as u32, season.kind.name()))
+}
+
+fn border_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let mut border = world.get_resource_mut::<WorldBorder>().ok_or("no server running")?;
+    match args {
+        [] => {}
+        ["off"] => border.radius_chunks = None,
+        [radius] => {
+            let radius: i32 = radius.parse().ok()
+                .filter(|radius: &i32| *radius >= 0)
+                .ok_or_else(|| format!("`{}` is not a radius in chunks", radius))?;
+            border.radius_chunks = Some(radius);
+        }
+        _ => return Err("usage: border [<radius in chunks> | off]".to_string()),
+    }
+    Ok(match border.half_extent() {
+        Some(extent) => format!("World border {} chunks out, {} m from the center", border.radius_chunks.unwrap_or(0), extent),
+        None => "No world border".to_string(),
+    })
+}
+
+fn regen_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let coords = match args {
+        [x, z] => match (x.parse(), z.parse()) {
+            (Ok(x), Ok(z)) => (x, z),
+            _ => return Err("chunk coordinates are whole numbers".to_string()),
+        },
+        _ => return Err("usage: regen <chunk x> <chunk z>".to_string()),
+    };
+    let loaded = world.get_resource_mut::<ServerChunks>().ok_or("no server running")?.regenerate(coords);
+    // A client in the same process rebuilds its mesh as well
+    if let Some(mut terrain_edits) = world.get_resource_mut::<TerrainEdits>() {
+        terrain_edits.dirty_chunks.insert(coords);
+    }
+    if loaded {
+        Ok(format!("Regenerating chunk {:?} ({} m wide)", coords, CHUNK_SIZE))
+    } else {
+        Err(format!("chunk {:?} is not loaded", coords))
+    }
+}
+
+fn road_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let server_world = world.get_resource::<ServerWorld>().ok_or("no server running")?;
+    let ends = match args {
+        ["clear"] => {
+            let cleared = server_world.roads.clone();
+            world.resource_mut::<ServerWorld>().roads.clear();
+            publish_roads(world, &cleared);
+            return Ok(format!("Removed {} roads", cleared.len()));
+        }
+        ["link"] => {
+            // Built pieces of this process' world, or of the save on a dedicated server
+            let pieces: Vec<Vec2> = match world.get_resource::<Structures>() {
+                Some(structures) => structures.chunks.values().flatten().map(|piece| piece.position.xz()).collect(),
+                None => world.resource::<LoadedSave>().0.structures.values().flatten().map(|piece| piece.position.xz()).collect(),
+            };
+            let roads = &world.resource::<ServerWorld>().roads;
+            link_sites(pieces)
+                .into_iter()
+                .filter(|(a, b)| !roads.iter().any(|road| road.joins(*a, *b)))
+                .collect()
+        }
+        [from_x, from_z, to_x, to_z] => {
+            let parse = |value: &str| value.parse::<f32>().map_err(|_| format!("`{}` is not a coordinate", value));
+            vec![(Vec2::new(parse(from_x)?, parse(from_z)?), Vec2::new(parse(to_x)?, parse(to_z)?))]
+        }
+        _ => return Err("usage: road <x> <z> <x> <z> | link | clear".to_string()),
+    };
+
+    // Routes follow the natural terrain, the road is carved into it and edits stay on top
+    let (roads, unreachable): (Vec<Option<Road>>, Vec<_>) = ends
+        .iter()
+        .map(|(from, to)| find_road(*from, *to, terrain::height))
+        .partition(Option::is_some);
+    let roads: Vec<Road> = roads.into_iter().flatten().collect();
+    world.resource_mut::<ServerWorld>().roads.extend(roads.iter().cloned());
+    publish_roads(world, &roads);
+    match (roads.len(), unreachable.len()) {
+        (0, 0) => Ok("No structures to connect".to_string()),
+        (0, _) => Err("no route found, the ends are under water or too far apart".to_string()),
+        (carved, 0) => Ok(format!("Carved {} roads", carved)),
+        (carved, failed) => Ok(format!("Carved {} roads, no route for {}", carved, failed)),
+    }
+}
+
+// Regenerate the ground under changed roads, and send every client the new roads
+fn publish_roads(world: &mut World, changed: &[Road]) {
+    let chunks: Vec<(i32, i32)> = changed.iter().flat_map(|road| road.affected_chunks()).collect();
+    let mut server_chunks = world.resource_mut::<ServerChunks>();
+    for coords in chunks {
+        server_chunks.regenerate(coords);
+    }
+    let message = world.resource::<ServerWorld>().regions_message();
+    world.resource_mut::<ClientConnections>().broadcast(&message);
+}
+
+// With a player in this process the full game is saved at the end of the frame,
+// a dedicated server only has the world data to update in the save file
+fn save_command(world: &mut World, _args: &[&str]) -> CommandResult {
+    if let Some(mut requests) = world.get_resource_mut::<Events<SaveRequested>>() {
+        requests.send(SaveRequested { reason: "console" });
+        return Ok("Saving".to_string());
+    }
+    let mut save = world.get_resource::<LoadedSave>().ok_or("no save loaded")?.0.clone();
+    if let Some(server_world) = world.get_resource::<ServerWorld>() {
+        save.regions = Some(server_world.regions.clone());
+        save.roads = server_world.roads.clone();
+    }
+    if let Some(time_of_day) = world.get_resource::<TimeOfDay>() {
+        save.time_of_day = Some(SavedTime { hour: time_of_day.hour, day: time_of_day.day });
+    }
+    save.write(SaveSettings::default().backups);
+    world.resource_mut::<LoadedSave>().0 = save;
+    Ok("World saved".to_string())
+}
+
+fn start_admin_channels(
+    config: Res<AdminConfig>,
+    queue: Res<ConsoleQueue>,
+) {
+    if config.stdin {
+        let commands = queue.sender();
+        thread::Builder::new()
+            .name("admin-stdin".to_string())
+            .spawn(move || read_stdin(commands))
+            .expect("failed to spawn the stdin thread");
+    }
+    if let Some(remote) = config.remote.clone() {
+        let listener = match TcpListener::bind(remote.address) {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("Could not open the admin port on {}: {}", remote.address, err);
+                return;
+            }
+        };
+        info!("Admin commands accepted on {}", remote.address);
+        let commands = queue.sender();
+        thread::Builder::new()
+            .name("admin-listener".to_string())
+            .spawn(move || accept_admins(listener, remote.password, commands))
+            .expect("failed to spawn the admin listener thread");
+    }
+}
+
+fn read_stdin(commands: Sender<ConsoleRequest>) {
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if commands.send(ConsoleRequest { line, reply: ConsoleReply::Stdout }).is_err() {
+            return;
+        }
+    }
+}
+
+fn accept_admins(listener: TcpListener, password: String, commands: Sender<ConsoleRequest>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Admin connection failed: {}", err);
+                continue;
+            }
+        };
+        let (password, commands) = (password.clone(), commands.clone());
+        let _ = thread::Builder::new()
+            .name("admin-session".to_string())
+            .spawn(move || {
+                let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+                if let Err(err) = admin_session(stream, &password, commands) {
+                    info!("Admin {} disconnected: {}", peer, err);
+                }
+            });
+    }
+}
+
+// Line based protocol: the first line is the password, then one command per line.
+// Every answer ends with an empty line.
+fn admin_session(stream: TcpStream, password: &str, commands: Sender<ConsoleRequest>) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+
+    let attempt = lines.next().transpose()?.unwrap_or_default();
+    if attempt.trim_end() != password {
+        warn!("Admin login from {} refused", peer);
+        thread::sleep(AUTH_FAILURE_DELAY);
+        writeln!(writer, "error: wrong password")?;
+        return Ok(());
+    }
+    info!("Admin {} logged in", peer);
+    writeln!(writer, "ok\n")?;
+
+    for line in lines {
+        let (reply, output) = mpsc::channel();
+        if commands.send(ConsoleRequest { line: line?, reply: ConsoleReply::Channel(reply) }).is_err() {
+            return Ok(());
+        }
+        let Ok(output) = output.recv() else {
+            return Ok(());
+        };
+        writeln!(writer, "{}\n", output)?;
+    }
+    Ok(())
+}
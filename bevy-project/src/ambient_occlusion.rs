@@ -0,0 +1,51 @@
+use bevy::core_pipeline::prepass::{DepthPrepass, NormalPrepass};
+use bevy::pbr::{ScreenSpaceAmbientOcclusion, ScreenSpaceAmbientOcclusionQualityLevel};
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::settings::GameSettings;
+
+#[derive(Default, Clone, Debug)]
+pub struct AmbientOcclusionPlugin;
+
+impl Plugin for AmbientOcclusionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, apply_ambient_occlusion);
+    }
+}
+
+// Add or remove SSAO on the main camera following the graphics settings.
+// SSAO doesn't support MSAA, so it is turned off while SSAO is on.
+fn apply_ambient_occlusion(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    mut applied: Local<Option<Option<ScreenSpaceAmbientOcclusionQualityLevel>>>,
+    camera_query: Query<Entity, With<FreeCamera>>,
+    added_query: Query<(), Added<FreeCamera>>,
+) {
+    let level = settings.graphics.ambient_occlusion_level();
+    if *applied == Some(level) && added_query.is_empty() {
+        return;
+    }
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+
+    match level {
+        Some(quality_level) => {
+            commands.entity(camera).insert((
+                ScreenSpaceAmbientOcclusion {
+                    quality_level,
+                    ..default()
+                },
+                Msaa::Off,
+            ));
+        }
+        None => {
+            commands.entity(camera)
+                .remove::<(ScreenSpaceAmbientOcclusion, DepthPrepass, NormalPrepass)>()
+                .insert(Msaa::default());
+        }
+    }
+    info!("Ambient occlusion: {:?}", level);
+    *applied = Some(level);
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use bevy::asset::{LoadState, UntypedAssetId};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+// Preloads every registered asset before gameplay starts, so nothing is loaded on first
+// use (hitches, untextured or pink materials for a few frames). Features register their
+// assets with `preload_asset` and get the handles back by name from `AssetRegistry`.
+#[derive(Default, Clone, Debug)]
+pub struct AssetRegistryPlugin;
+
+impl Plugin for AssetRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_state::<GameState>()
+            .init_resource::<AssetRegistry>()
+            .add_systems(PreStartup, load_registered_assets)
+            .add_systems(Update, (wait_for_assets, loading_screen_ui).chain().run_if(in_state(GameState::Loading)));
+    }
+}
+
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    // Preloading assets, the world doesn't stream or simulate yet
+    #[default]
+    Loading,
+    Playing,
+}
+
+type LoadFn = fn(&AssetServer, &'static str) -> UntypedHandle;
+
+fn load_typed<T: Asset>(asset_server: &AssetServer, path: &'static str) -> UntypedHandle {
+    asset_server.load::<T>(path).untyped()
+}
+
+// Named handles to preloaded assets, shaders, textures, models, sounds and RON data alike
+#[derive(Resource, Default)]
+pub struct AssetRegistry {
+    queued: Vec<(&'static str, &'static str, LoadFn)>,
+    handles: HashMap<&'static str, UntypedHandle>,
+    // Assets that failed to load, reported once and not waited for
+    failed: Vec<UntypedAssetId>,
+    loaded: usize,
+}
+
+impl AssetRegistry {
+    // Handle to a registered asset, None for an unknown name or another asset type
+    pub fn get<T: Asset>(&self, name: &str) -> Option<Handle<T>> {
+        self.handles.get(name)?.clone().try_typed().ok()
+    }
+
+    // Share of the registered assets done loading, 0..1
+    pub fn progress(&self) -> f32 {
+        if self.handles.is_empty() {
+            return 1.0;
+        }
+        self.loaded as f32 / self.handles.len() as f32
+    }
+}
+
+pub trait AssetRegistryAppExt {
+    fn preload_asset<T: Asset>(&mut self, name: &'static str, path: &'static str) -> &mut Self;
+}
+
+impl AssetRegistryAppExt for App {
+    fn preload_asset<T: Asset>(&mut self, name: &'static str, path: &'static str) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(AssetRegistry::default)
+            .queued
+            .push((name, path, load_typed::<T>));
+        self
+    }
+}
+
+fn load_registered_assets(
+    asset_server: Res<AssetServer>,
+    mut registry: ResMut<AssetRegistry>,
+) {
+    let queued = std::mem::take(&mut registry.queued);
+    info!("Preloading {} assets", queued.len());
+    for (name, path, load) in queued {
+        registry.handles.insert(name, load(&asset_server, path));
+    }
+}
+
+fn wait_for_assets(
+    asset_server: Res<AssetServer>,
+    mut registry: ResMut<AssetRegistry>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let registry = &mut *registry;
+    let mut loaded = 0;
+    for (name, handle) in &registry.handles {
+        let id = handle.id();
+        if registry.failed.contains(&id) || asset_server.is_loaded_with_dependencies(id) {
+            loaded += 1;
+        } else if let Some(LoadState::Failed(err)) = asset_server.get_load_state(id) {
+            warn!("Could not preload asset {}: {}", name, err);
+            registry.failed.push(id);
+            loaded += 1;
+        }
+    }
+    registry.loaded = loaded;
+
+    if loaded == registry.handles.len() {
+        info!("Assets loaded, entering the game");
+        next_state.set(GameState::Playing);
+    }
+}
+
+fn loading_screen_ui(
+    mut contexts: EguiContexts,
+    registry: Res<AssetRegistry>,
+) {
+    let ctx = contexts.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("loading_screen")));
+    painter.rect_filled(ctx.screen_rect(), 0.0, egui::Color32::from_gray(12));
+    egui::Area::new(egui::Id::new("loading_progress"))
+        .order(egui::Order::Tooltip)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(egui::RichText::new("Loading").heading().color(egui::Color32::WHITE));
+            ui.add(egui::ProgressBar::new(registry.progress()).desired_width(240.0).show_percentage());
+        });
+}
@@ -0,0 +1,291 @@
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::audio::{AddAudioSource, CpalSample, Decodable, Source, SpatialListener};
+use bevy::prelude::*;
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::water::WaterQuery;
+
+// Cutoff of the low-pass filter when nothing muffles the sound, above what can be heard
+const OPEN_CUTOFF: f32 = 20_000.0;
+const UNDERWATER_CUTOFF: f32 = 500.0;
+const UNDERWATER_GAIN: f32 = 0.45;
+// Seconds for the muffling to set in or fade when going under or coming up
+const SUBMERGE_SECONDS: f32 = 0.25;
+// Distance between the listener's ears, in meters
+const EAR_GAP: f32 = 0.3;
+// Seconds the ears take to move to the new viewpoint when the camera mode changes
+const HANDOVER_SECONDS: f32 = 0.6;
+// Emitters are heard at full volume within 1 / EMITTER_SCALE meters, then fade with the
+// square of the distance
+pub const EMITTER_SCALE: f32 = 0.15;
+
+// Game sounds are loaded as `MixedAudio` and played through one mixer bus, filtered and
+// ducked while the listener is under water. Positional sounds are heard by a listener
+// following the active camera mode: the free camera itself, or the player's head turned
+// the way the camera looks in Player mode.
+#[derive(Default, Clone, Debug)]
+pub struct AudioMixerPlugin;
+
+impl Plugin for AudioMixerPlugin {
+    fn build(&self, app: &mut App) {
+        let mixer = AudioMixer::default();
+        app
+            .add_audio_source::<MixedAudio>()
+            .register_asset_loader(MixedAudioLoader { bus: mixer.bus.clone() })
+            .insert_resource(mixer)
+            .init_resource::<ListenerHandover>()
+            .add_systems(Startup, spawn_listener)
+            .add_systems(Update, (follow_active_camera, update_underwater_mix).chain());
+    }
+}
+
+// Settings read by every sound playing through the bus, from the audio thread
+#[derive(Debug)]
+pub struct MixerBus {
+    cutoff: AtomicU32,
+    gain: AtomicU32,
+}
+
+impl Default for MixerBus {
+    fn default() -> Self {
+        Self {
+            cutoff: AtomicU32::new(OPEN_CUTOFF.to_bits()),
+            gain: AtomicU32::new(1.0f32.to_bits()),
+        }
+    }
+}
+
+impl MixerBus {
+    pub fn set(&self, cutoff: f32, gain: f32) {
+        self.cutoff.store(cutoff.to_bits(), Ordering::Relaxed);
+        self.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    fn cutoff_bits(&self) -> u32 {
+        self.cutoff.load(Ordering::Relaxed)
+    }
+
+    fn gain(&self) -> f32 {
+        f32::from_bits(self.gain.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct AudioMixer {
+    pub bus: Arc<MixerBus>,
+    // 0 in the air, 1 fully under water
+    submerged: f32,
+}
+
+// A sound file played through the mixer bus. Same files as `AudioSource`, ask for this type
+// when loading them.
+#[derive(Asset, TypePath, Clone)]
+pub struct MixedAudio {
+    source: AudioSource,
+    bus: Arc<MixerBus>,
+}
+
+impl Decodable for MixedAudio {
+    type DecoderItem = f32;
+    type Decoder = BusFilter<<AudioSource as Decodable>::Decoder>;
+
+    fn decoder(&self) -> Self::Decoder {
+        BusFilter {
+            input: self.source.decoder(),
+            bus: self.bus.clone(),
+            cutoff_bits: 0,
+            smoothing: 1.0,
+            channel: 0,
+            filtered: Vec::new(),
+        }
+    }
+}
+
+// One-pole low-pass filter and gain over a decoded sound, following the bus
+pub struct BusFilter<S> {
+    input: S,
+    bus: Arc<MixerBus>,
+    // Cutoff `smoothing` was computed for
+    cutoff_bits: u32,
+    smoothing: f32,
+    channel: usize,
+    // Last output of each channel
+    filtered: Vec<f32>,
+}
+
+impl<S> Iterator for BusFilter<S>
+where
+    S: Source<Item = i16>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample: f32 = self.input.next()?.to_sample();
+        let channels = self.input.channels().max(1) as usize;
+        if self.filtered.len() != channels {
+            self.filtered.resize(channels, 0.0);
+            self.channel %= channels;
+        }
+        let cutoff_bits = self.bus.cutoff_bits();
+        if cutoff_bits != self.cutoff_bits {
+            self.cutoff_bits = cutoff_bits;
+            let cutoff = f32::from_bits(cutoff_bits);
+            let sample_rate = self.input.sample_rate().max(1) as f32;
+            // Fully open passes the sound through untouched
+            self.smoothing = if cutoff >= OPEN_CUTOFF { 1.0 } else { 1.0 - (-TAU * cutoff / sample_rate).exp() };
+        }
+        let filtered = &mut self.filtered[self.channel];
+        *filtered += (sample - *filtered) * self.smoothing;
+        let output = *filtered * self.bus.gain();
+        self.channel = (self.channel + 1) % channels;
+        Some(output)
+    }
+}
+
+impl<S> Source for BusFilter<S>
+where
+    S: Source<Item = i16>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+struct MixedAudioLoader {
+    bus: Arc<MixerBus>,
+}
+
+impl AssetLoader for MixedAudioLoader {
+    type Asset = MixedAudio;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<MixedAudio, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(MixedAudio {
+            source: AudioSource { bytes: bytes.into() },
+            bus: self.bus.clone(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ogg"]
+    }
+}
+
+// The ears sounds are heard with
+#[derive(Component)]
+pub struct AudioListener;
+
+// Blend of the ears from where they were when the camera mode changed to the new viewpoint
+#[derive(Resource, Default)]
+struct ListenerHandover {
+    mode: Option<CameraMode>,
+    from: Transform,
+    // 0 when the mode just changed, 1 once the ears follow the new viewpoint
+    progress: f32,
+}
+
+impl ListenerHandover {
+    fn ears(&self, target: Transform) -> Transform {
+        if self.progress >= 1.0 {
+            return target;
+        }
+        // Eased so the sound doesn't swing off abruptly, nor stop abruptly
+        let t = self.progress * self.progress * (3.0 - 2.0 * self.progress);
+        Transform {
+            translation: self.from.translation.lerp(target.translation, t),
+            rotation: self.from.rotation.slerp(target.rotation, t),
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+fn spawn_listener(mut commands: Commands) {
+    commands.spawn((AudioListener, SpatialListener::new(EAR_GAP), Transform::default()));
+}
+
+// Where the active camera mode hears from: the free camera, or the player's head turned like
+// the camera in Player mode, so sounds around the player keep their loudness as the camera orbits
+fn listener_target(
+    mode: &CameraMode,
+    camera: &GlobalTransform,
+    player: Option<&Transform>,
+    eye_height: f32,
+) -> Transform {
+    let (_, rotation, translation) = camera.to_scale_rotation_translation();
+    let translation = match (mode, player) {
+        (CameraMode::Player, Some(player)) => player.translation - Vec3::Y * PLAYER_HALF_HEIGHT + Vec3::Y * eye_height,
+        _ => translation,
+    };
+    Transform { translation, rotation, scale: Vec3::ONE }
+}
+
+fn follow_active_camera(
+    time: Res<Time>,
+    camera_settings: Res<CameraSettings>,
+    mut handover: ResMut<ListenerHandover>,
+    camera_query: Query<&GlobalTransform, With<FreeCamera>>,
+    player_query: Query<&Transform, (With<Player>, Without<AudioListener>)>,
+    mut listener_query: Query<&mut Transform, With<AudioListener>>,
+) {
+    let (Ok(camera), Ok(mut listener)) = (camera_query.get_single(), listener_query.get_single_mut()) else {
+        return;
+    };
+    let mode = &camera_settings.camera_mode;
+    let target = listener_target(mode, camera, player_query.get_single().ok(), camera_settings.eye_height);
+    match &handover.mode {
+        // The first viewpoint is taken as is
+        None => handover.progress = 1.0,
+        Some(previous) if previous != mode => {
+            handover.from = *listener;
+            handover.progress = 0.0;
+        }
+        Some(_) => handover.progress = (handover.progress + time.delta_secs() / HANDOVER_SECONDS).min(1.0),
+    }
+    handover.mode = Some(mode.clone());
+    *listener = handover.ears(target);
+}
+
+// Muffle everything while the listener is between the water surface and the floor
+fn update_underwater_mix(
+    time: Res<Time>,
+    water: WaterQuery,
+    mut mixer: ResMut<AudioMixer>,
+    listener_query: Query<&Transform, With<AudioListener>>,
+) {
+    let underwater = listener_query.get_single().is_ok_and(|listener| water.is_in_water(listener.translation));
+    let target = if underwater { 1.0 } else { 0.0 };
+    let step = time.delta_secs() / SUBMERGE_SECONDS;
+    let submerged = mixer.submerged + (target - mixer.submerged).clamp(-step, step);
+    if submerged == mixer.submerged {
+        return;
+    }
+    mixer.submerged = submerged;
+    // Fade the cutoff in octaves so the change sounds even
+    let cutoff = OPEN_CUTOFF * (UNDERWATER_CUTOFF / OPEN_CUTOFF).powf(submerged);
+    let gain = 1.0 + (UNDERWATER_GAIN - 1.0) * submerged;
+    mixer.bus.set(cutoff, gain);
+}
@@ -0,0 +1,141 @@
+use bevy::{
+    prelude::*,
+    pbr::{MaterialPipeline, MaterialPipelineKey, MaterialPlugin},
+    reflect::TypePath,
+    render::{
+        mesh::{Indices, MeshVertexBufferLayoutRef, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError},
+    },
+};
+use crate::asset_registry::AssetRegistryAppExt;
+use crate::camera::FreeCamera;
+use crate::world_events::{ActiveWorldEvents, WorldEventAppExt, WorldEventType};
+
+const AURORA: &str = "aurora";
+const AURORA_SHADER_PATH: &str = "shaders/aurora.wgsl";
+// The band is an arc of curtain hanging high around the camera, towards the north
+const BAND_RADIUS: f32 = 700.0;
+const BAND_BOTTOM: f32 = 180.0;
+const BAND_HEIGHT: f32 = 260.0;
+const BAND_ARC: f32 = std::f32::consts::PI * 1.2;
+const BAND_SEGMENTS: usize = 96;
+
+#[derive(Default, Clone, Debug)]
+pub struct AuroraPlugin;
+
+impl Plugin for AuroraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<AuroraMaterial>::default())
+            .preload_asset::<Shader>("aurora_shader", AURORA_SHADER_PATH)
+            .register_world_event(WorldEventType {
+                name: AURORA,
+                start_window: (20.0, 2.0),
+                chance: 0.06,
+                duration_hours: 4.0,
+            })
+            .add_systems(Startup, spawn_aurora)
+            .add_systems(Update, update_aurora);
+    }
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct AuroraMaterial {
+    #[uniform(0)]
+    pub time: f32,
+    #[uniform(1)]
+    pub intensity: f32,
+}
+
+impl Material for AuroraMaterial {
+    fn fragment_shader() -> ShaderRef {
+        AURORA_SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Add
+    }
+
+    // The band is seen from inside
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+#[derive(Component)]
+struct Aurora;
+
+// Vertical strip bent along an arc; uv.x runs along the arc, uv.y from the bottom up
+fn band_mesh() -> Mesh {
+    let mut positions = Vec::with_capacity((BAND_SEGMENTS + 1) * 2);
+    let mut normals = Vec::with_capacity(positions.capacity());
+    let mut uvs = Vec::with_capacity(positions.capacity());
+    for i in 0..=BAND_SEGMENTS {
+        let t = i as f32 / BAND_SEGMENTS as f32;
+        // Centred on north (-Z)
+        let angle = -std::f32::consts::FRAC_PI_2 + (t - 0.5) * BAND_ARC;
+        let (sin, cos) = angle.sin_cos();
+        for v in [0.0, 1.0] {
+            positions.push([cos * BAND_RADIUS, BAND_BOTTOM + v * BAND_HEIGHT, sin * BAND_RADIUS]);
+            normals.push([-cos, 0.0, -sin]);
+            uvs.push([t, v]);
+        }
+    }
+    let mut indices = Vec::with_capacity(BAND_SEGMENTS * 6);
+    for i in 0..BAND_SEGMENTS as u32 {
+        let base = i * 2;
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 1, base + 3, base + 2]);
+    }
+
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices))
+}
+
+fn spawn_aurora(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<AuroraMaterial>>,
+) {
+    commands.spawn((
+        Mesh3d(meshes.add(band_mesh())),
+        MeshMaterial3d(materials.add(AuroraMaterial::default())),
+        Transform::default(),
+        Visibility::Hidden,
+        Aurora,
+    ));
+}
+
+// Show the band while the event runs, keeping it around the camera
+fn update_aurora(
+    time: Res<Time>,
+    events: Res<ActiveWorldEvents>,
+    camera_query: Query<&Transform, (With<FreeCamera>, Without<Aurora>)>,
+    mut aurora_query: Query<(&mut Transform, &mut Visibility, &MeshMaterial3d<AuroraMaterial>), With<Aurora>>,
+    mut materials: ResMut<Assets<AuroraMaterial>>,
+) {
+    let intensity = events.intensity(AURORA);
+    for (mut transform, mut visibility, material) in aurora_query.iter_mut() {
+        let shown = intensity > 0.0;
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+        if !shown {
+            continue;
+        }
+        if let Ok(camera) = camera_query.get_single() {
+            transform.translation = camera.translation.with_y(0.0);
+        }
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.time = time.elapsed_secs();
+            material.intensity = intensity;
+        }
+    }
+}
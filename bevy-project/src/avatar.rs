@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+use bevy::animation::{animated_field, AnimationTarget, AnimationTargetId};
+use bevy::prelude::*;
+use crate::emote::Emote;
+use crate::net::{ClientId, ServerEvent, ServerMessage};
+use crate::player::{PLAYER_BODY_LENGTH, PLAYER_RADIUS};
+use crate::snapshot::{NetworkSettings, ServerClock, Snapshot, SnapshotBuffer};
+
+const BODY: &str = "avatar_body";
+const LEFT_ARM: &str = "avatar_left_arm";
+const RIGHT_ARM: &str = "avatar_right_arm";
+const ARM_RADIUS: f32 = 0.12;
+const ARM_LENGTH: f32 = 0.9;
+// Shoulders relative to the body's center, +x being the avatar's right
+const SHOULDER: Vec3 = Vec3::new(PLAYER_RADIUS + ARM_RADIUS, PLAYER_BODY_LENGTH * 0.35, 0.0);
+const SIT_DROP: f32 = 0.8;
+const BLEND_TIME: Duration = Duration::from_millis(250);
+// Moving faster than this stands a sitting avatar back up
+const STAND_UP_SPEED: f32 = 0.5;
+const REMOTE_COLOR: Color = Color::srgb(0.9, 0.55, 0.3);
+
+// Character rig made of primitives: a body and two arms, posed by clips on an animation
+// graph. The idle pose always plays underneath, emotes blend in over it and back out.
+// Also shows the other players on the server as avatars.
+#[derive(Default, Clone, Debug)]
+pub struct AvatarPlugin;
+
+impl Plugin for AvatarPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AvatarAnimations>()
+            .add_systems(Update, (
+                sync_remote_avatars,
+                move_remote_avatars,
+                build_avatar_rigs,
+                stand_up_moving_avatars,
+                animate_avatars,
+            ).chain());
+    }
+}
+
+// Looks of an avatar; the rig is built under the entity the frame it is added
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Transform, Visibility)]
+pub struct Avatar {
+    pub color: Color,
+}
+
+// Emote an avatar is playing, None for the idle pose
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct AvatarPose {
+    pub emote: Option<Emote>,
+    pub elapsed: f32,
+}
+
+impl AvatarPose {
+    pub fn play(&mut self, emote: Emote) {
+        self.emote = Some(emote);
+        self.elapsed = 0.0;
+    }
+}
+
+// Another player, placed from the states the server relays
+#[derive(Component, Debug, Clone)]
+pub struct RemotePlayer {
+    pub client: ClientId,
+    snapshots: SnapshotBuffer,
+}
+
+impl RemotePlayer {
+    pub fn snapshots(&self) -> &SnapshotBuffer {
+        &self.snapshots
+    }
+}
+
+// Idle pose and emote clips, shared by every avatar's graph
+#[derive(Resource)]
+pub struct AvatarAnimations {
+    graph: Handle<AnimationGraph>,
+    idle: AnimationNodeIndex,
+    emotes: HashMap<Emote, AnimationNodeIndex>,
+}
+
+impl FromWorld for AvatarAnimations {
+    fn from_world(world: &mut World) -> Self {
+        let mut clips = world.resource_mut::<Assets<AnimationClip>>();
+        let idle = clips.add(pose_clip(Pose::default()));
+        let emotes: Vec<(Emote, Handle<AnimationClip>)> = Emote::ALL
+            .iter()
+            .map(|emote| (*emote, clips.add(emote_clip(*emote))))
+            .collect();
+
+        let mut graph = AnimationGraph::new();
+        let idle = graph.add_clip(idle, 1.0, graph.root);
+        let emotes = emotes
+            .into_iter()
+            .map(|(emote, clip)| (emote, graph.add_clip(clip, 1.0, graph.root)))
+            .collect();
+        let graph = world.resource_mut::<Assets<AnimationGraph>>().add(graph);
+        Self { graph, idle, emotes }
+    }
+}
+
+// Rotations of the limbs and the body's drop, one keyframe of a clip
+#[derive(Debug, Clone, Copy)]
+struct Pose {
+    body_drop: f32,
+    body_tilt: f32,
+    left_arm: Quat,
+    right_arm: Quat,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self {
+            body_drop: 0.0,
+            body_tilt: 0.0,
+            // Arms hang slightly away from the body
+            left_arm: Quat::from_rotation_z(-0.08),
+            right_arm: Quat::from_rotation_z(0.08),
+        }
+    }
+}
+
+// Arm raised to the side at `angle` radians from hanging down
+fn arm_raised(angle: f32) -> Quat {
+    Quat::from_rotation_z(angle)
+}
+
+fn pose_clip(pose: Pose) -> AnimationClip {
+    keyframe_clip(&[(0.0, pose), (1.0, pose)])
+}
+
+fn keyframe_clip(keyframes: &[(f32, Pose)]) -> AnimationClip {
+    let mut clip = AnimationClip::default();
+    let track = |value: fn(&Pose) -> Quat| {
+        AnimatableKeyframeCurve::new(keyframes.iter().map(|(time, pose)| (*time, value(pose))))
+            .expect("emote clips have at least two keyframes")
+    };
+    clip.add_curve_to_target(target_id(LEFT_ARM), AnimatableCurve::new(animated_field!(Transform::rotation), track(|pose| pose.left_arm)));
+    clip.add_curve_to_target(target_id(RIGHT_ARM), AnimatableCurve::new(animated_field!(Transform::rotation), track(|pose| pose.right_arm)));
+    clip.add_curve_to_target(target_id(BODY), AnimatableCurve::new(animated_field!(Transform::rotation), track(|pose| Quat::from_rotation_x(pose.body_tilt))));
+    let drop = AnimatableKeyframeCurve::new(keyframes.iter().map(|(time, pose)| (*time, Vec3::NEG_Y * pose.body_drop)))
+        .expect("emote clips have at least two keyframes");
+    clip.add_curve_to_target(target_id(BODY), AnimatableCurve::new(animated_field!(Transform::translation), drop));
+    clip
+}
+
+fn emote_clip(emote: Emote) -> AnimationClip {
+    let rest = Pose::default();
+    match emote {
+        Emote::Wave => {
+            // Right hand up, swaying side to side
+            let up = |angle: f32| Pose { right_arm: arm_raised(angle), ..rest };
+            keyframe_clip(&[
+                (0.0, up(2.3)),
+                (0.3, up(2.9)),
+                (0.6, up(2.3)),
+                (0.9, up(2.9)),
+                (1.2, up(2.3)),
+            ])
+        }
+        Emote::Point => {
+            // Right arm straight ahead, the avatar faces -z
+            let point = Pose { right_arm: Quat::from_rotation_x(FRAC_PI_2), ..rest };
+            keyframe_clip(&[(0.0, point), (1.0, point)])
+        }
+        Emote::Sit => {
+            let sit = Pose {
+                body_drop: SIT_DROP,
+                body_tilt: 0.15,
+                left_arm: Quat::from_rotation_x(0.5),
+                right_arm: Quat::from_rotation_x(0.5),
+            };
+            keyframe_clip(&[(0.0, sit), (1.0, sit)])
+        }
+    }
+}
+
+fn target_id(name: &str) -> AnimationTargetId {
+    AnimationTargetId::from_name(&Name::new(name.to_string()))
+}
+
+// Spawn the body and arms of new avatars and start their idle pose
+fn build_avatar_rigs(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    animations: Res<AvatarAnimations>,
+    avatar_query: Query<(Entity, &Avatar), Added<Avatar>>,
+) {
+    for (entity, avatar) in avatar_query.iter() {
+        let material = materials.add(StandardMaterial {
+            base_color: avatar.color,
+            metallic: 0.1,
+            perceptual_roughness: 0.8,
+            ..default()
+        });
+        let body_mesh = meshes.add(Capsule3d::new(PLAYER_RADIUS, PLAYER_BODY_LENGTH));
+        let arm_mesh = meshes.add(Capsule3d::new(ARM_RADIUS, ARM_LENGTH - ARM_RADIUS * 2.0));
+
+        let mut player = AnimationPlayer::default();
+        let mut transitions = AnimationTransitions::new();
+        transitions.play(&mut player, animations.idle, Duration::ZERO).repeat();
+        commands.entity(entity).insert((
+            player,
+            transitions,
+            AnimationGraphHandle(animations.graph.clone()),
+            AvatarPose::default(),
+        ));
+
+        let target = |name: &str| (Name::new(name.to_string()), AnimationTarget { id: target_id(name), player: entity });
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn((
+                    Mesh3d(body_mesh),
+                    MeshMaterial3d(material.clone()),
+                    Transform::default(),
+                    target(BODY),
+                ))
+                .with_children(|body| {
+                    for (name, side) in [(LEFT_ARM, -1.0), (RIGHT_ARM, 1.0)] {
+                        // The arm pivots at the shoulder, its mesh hangs below
+                        body.spawn((
+                            Transform::from_translation(SHOULDER * Vec3::new(side, 1.0, 1.0)),
+                            Visibility::default(),
+                            target(name),
+                        )).with_child((
+                            Mesh3d(arm_mesh.clone()),
+                            MeshMaterial3d(material.clone()),
+                            Transform::from_xyz(0.0, -ARM_LENGTH / 2.0 + ARM_RADIUS, 0.0),
+                        ));
+                    }
+                });
+        });
+    }
+}
+
+// Timed emotes end on their own, sitting lasts until the avatar moves
+fn stand_up_moving_avatars(
+    time: Res<Time>,
+    mut avatar_query: Query<(Entity, &GlobalTransform, &mut AvatarPose)>,
+    mut last_positions: Local<HashMap<Entity, Vec3>>,
+) {
+    last_positions.retain(|entity, _| avatar_query.contains(*entity));
+    let dt = time.delta_secs();
+    for (entity, transform, mut pose) in avatar_query.iter_mut() {
+        let position = transform.translation();
+        let moved = last_positions.insert(entity, position).map_or(0.0, |last| last.distance(position));
+        let Some(emote) = pose.emote else {
+            continue;
+        };
+        pose.elapsed += dt;
+        let finished = emote.duration().is_some_and(|duration| pose.elapsed >= duration);
+        let walked_off = dt > 0.0 && moved / dt > STAND_UP_SPEED;
+        if finished || walked_off {
+            pose.emote = None;
+        }
+    }
+}
+
+// Blend each avatar's graph toward the clip of its pose
+fn animate_avatars(
+    animations: Res<AvatarAnimations>,
+    mut avatar_query: Query<(&AvatarPose, &mut AnimationPlayer, &mut AnimationTransitions), Changed<AvatarPose>>,
+) {
+    for (pose, mut player, mut transitions) in avatar_query.iter_mut() {
+        let node = pose.emote
+            .and_then(|emote| animations.emotes.get(&emote).copied())
+            .unwrap_or(animations.idle);
+        if transitions.get_main_animation() == Some(node) && pose.elapsed > 0.0 {
+            continue;
+        }
+        transitions.play(&mut player, node, BLEND_TIME).repeat();
+    }
+}
+
+// Spawn, move and remove the avatars of the other players as the server reports them
+fn sync_remote_avatars(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut clock: ResMut<ServerClock>,
+    mut server_events: EventReader<ServerEvent>,
+    mut remote_query: Query<(Entity, &mut RemotePlayer, Option<&mut AvatarPose>)>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        match message {
+            ServerMessage::PlayerMoved { client, position, yaw, time: server_time } => {
+                clock.observe(*server_time, time.elapsed_secs_f64());
+                let snapshot = Snapshot { time: *server_time, position: *position, yaw: *yaw };
+                if let Some((_, mut remote, _)) = remote_query.iter_mut().find(|(_, remote, _)| remote.client == *client) {
+                    remote.snapshots.push(snapshot);
+                } else {
+                    info!("Player {:?} is nearby", client);
+                    let mut snapshots = SnapshotBuffer::default();
+                    snapshots.push(snapshot);
+                    commands.spawn((
+                        Avatar { color: REMOTE_COLOR },
+                        RemotePlayer { client: *client, snapshots },
+                        Transform::from_translation(*position).with_rotation(Quat::from_rotation_y(*yaw)),
+                    ));
+                }
+            }
+            ServerMessage::PlayerLeft { client } => {
+                if let Some((entity, _, _)) = remote_query.iter().find(|(_, remote, _)| remote.client == *client) {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            // Players may have left while the connection was lost, the others show up again
+            // with their next move
+            ServerMessage::Joined { .. } => {
+                for (entity, _, _) in remote_query.iter() {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            ServerMessage::RemoteEmote { client, emote } => {
+                if let Some((_, _, Some(mut pose))) = remote_query.iter_mut().find(|(_, remote, _)| remote.client == *client) {
+                    pose.play(*emote);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Show remote players a little in the past, between the two states around that moment,
+// so they move smoothly whatever the spacing of the updates
+fn move_remote_avatars(
+    time: Res<Time>,
+    clock: Res<ServerClock>,
+    settings: Res<NetworkSettings>,
+    mut remote_query: Query<(&mut RemotePlayer, &mut Transform)>,
+) {
+    let view_time = clock.view_time(time.elapsed_secs_f64(), &settings);
+    for (mut remote, mut transform) in remote_query.iter_mut() {
+        remote.snapshots.trim(view_time);
+        if let Some(snapshot) = remote.snapshots.sample(view_time, settings.max_extrapolation) {
+            transform.translation = snapshot.position;
+            transform.rotation = Quat::from_rotation_y(snapshot.yaw);
+        }
+    }
+}
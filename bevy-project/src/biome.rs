@@ -0,0 +1,135 @@
+use std::sync::LazyLock;
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use crate::terrain;
+
+// Biomes are wide regions, far larger than a chunk
+const TEMPERATURE_FREQUENCY: f64 = 0.0025;
+const MOISTURE_FREQUENCY: f64 = 0.004;
+const COLD_THRESHOLD: f32 = -0.3;
+const HOT_THRESHOLD: f32 = 0.3;
+const DRY_THRESHOLD: f32 = -0.2;
+const WET_THRESHOLD: f32 = 0.25;
+// Temperature lost per meter of height, so valleys run warmer than hills
+const HEIGHT_LAPSE: f32 = 0.015;
+// Width (in climate units) of the band where two biomes blend
+const TRANSITION_WIDTH: f32 = 0.1;
+
+// Whittaker style lookup: rows are cold, temperate, hot; columns dry, medium, wet
+const BIOME_TABLE: [[Biome; 3]; 3] = [
+    [Biome::Tundra, Biome::Tundra, Biome::Tundra],
+    [Biome::Savanna, Biome::Temperate, Biome::Temperate],
+    [Biome::Desert, Biome::Savanna, Biome::Rainforest],
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Temperate,
+    Desert,
+    Tundra,
+    Savanna,
+    Rainforest,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 5] = [Biome::Temperate, Biome::Desert, Biome::Tundra, Biome::Savanna, Biome::Rainforest];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Biome::Temperate => "Temperate",
+            Biome::Desert => "Desert",
+            Biome::Tundra => "Tundra",
+            Biome::Savanna => "Savanna",
+            Biome::Rainforest => "Rainforest",
+        }
+    }
+
+    fn from_bands(temperature: usize, moisture: usize) -> Self {
+        BIOME_TABLE[temperature][moisture]
+    }
+}
+
+// Biome at a point, with the neighbouring biome it fades into near a border
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiomeSample {
+    pub biome: Biome,
+    pub neighbor: Biome,
+    // 0 inside the biome, up to 0.5 right on the border
+    pub blend: f32,
+    // Roughly -1 (dry) to 1 (wet), also used to tint vegetation inside a biome
+    pub moisture: f32,
+}
+
+static TEMPERATURE_NOISE: LazyLock<Perlin> = LazyLock::new(|| Perlin::new(3));
+static MOISTURE_NOISE: LazyLock<Perlin> = LazyLock::new(|| Perlin::new(4));
+
+// Large scale climate value in roughly -1..1, before the height is taken into account
+pub fn temperature(world_x: f32, world_z: f32) -> f32 {
+    TEMPERATURE_NOISE.get([
+        world_x as f64 * TEMPERATURE_FREQUENCY,
+        world_z as f64 * TEMPERATURE_FREQUENCY,
+    ]) as f32
+}
+
+// Second climate layer in roughly -1..1, independent of the temperature
+pub fn moisture(world_x: f32, world_z: f32) -> f32 {
+    MOISTURE_NOISE.get([
+        world_x as f64 * MOISTURE_FREQUENCY,
+        world_z as f64 * MOISTURE_FREQUENCY,
+    ]) as f32
+}
+
+// Index of the band a value falls in, split by two thresholds
+fn band(value: f32, low: f32, high: f32) -> usize {
+    if value < low {
+        0
+    } else if value > high {
+        2
+    } else {
+        1
+    }
+}
+
+// Distance to the closest threshold and the band on its other side
+fn nearest_edge(value: f32, low: f32, high: f32) -> (f32, usize) {
+    match band(value, low, high) {
+        0 => (low - value, 1),
+        2 => (value - high, 1),
+        _ if value - low < high - value => (value - low, 0),
+        _ => (high - value, 2),
+    }
+}
+
+pub fn biome_at(world_x: f32, world_z: f32) -> Biome {
+    sample(world_x, world_z).biome
+}
+
+pub fn sample(world_x: f32, world_z: f32) -> BiomeSample {
+    sample_at(world_x, world_z, terrain::cached_height(world_x, world_z))
+}
+
+// Sample for a known terrain height, avoids sampling the terrain noise again
+pub fn sample_at(world_x: f32, world_z: f32, height: f32) -> BiomeSample {
+    let temperature = temperature(world_x, world_z) - height * HEIGHT_LAPSE;
+    let moisture = moisture(world_x, world_z);
+    let temperature_band = band(temperature, COLD_THRESHOLD, HOT_THRESHOLD);
+    let moisture_band = band(moisture, DRY_THRESHOLD, WET_THRESHOLD);
+    let biome = Biome::from_bands(temperature_band, moisture_band);
+
+    // Fade into the biome across the closest climate threshold that changes the biome
+    let (temperature_distance, other_temperature) = nearest_edge(temperature, COLD_THRESHOLD, HOT_THRESHOLD);
+    let (moisture_distance, other_moisture) = nearest_edge(moisture, DRY_THRESHOLD, WET_THRESHOLD);
+    let edge = [
+        (temperature_distance, Biome::from_bands(other_temperature, moisture_band)),
+        (moisture_distance, Biome::from_bands(temperature_band, other_moisture)),
+    ]
+        .into_iter()
+        .filter(|(_, neighbor)| *neighbor != biome)
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    let (neighbor, blend) = match edge {
+        Some((distance, neighbor)) => (neighbor, (1.0 - distance / TRANSITION_WIDTH).clamp(0.0, 1.0) * 0.5),
+        None => (biome, 0.0),
+    };
+    BiomeSample { biome, neighbor, blend, moisture }
+}
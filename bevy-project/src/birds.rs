@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::biome::{self, Biome, BiomeSample};
+use crate::camera::FreeCamera;
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::player::Player;
+use crate::population::{Creature, Population, PopulationLimits};
+use crate::streaming::ChunkCoords;
+use crate::terrain_edit::TerrainEdits;
+use crate::world_rng::{RngPurpose, WorldRng};
+
+// Chance for a forest chunk to have a flock circling over it
+const FLOCK_CHANCE: f32 = 0.3;
+const MIN_FLOCK_SIZE: u32 = 8;
+const MAX_FLOCK_SIZE: u32 = 16;
+// Temperate land wetter than this counts as forest, rainforests always do
+const FOREST_MOISTURE: f32 = 0.05;
+
+const BIRD_SPEED: f32 = 7.0;
+const SCATTER_SPEED: f32 = 14.0;
+// Radius of the circle a flock flies around its home
+const CIRCLE_RADIUS: f32 = 22.0;
+// Height above the ground a flock circles at, and the lowest and highest it goes
+const CRUISE_HEIGHT: f32 = 20.0;
+const MIN_HEIGHT: f32 = 8.0;
+const MAX_HEIGHT: f32 = 45.0;
+// A bird scatters when the player comes this close, and its flockmates after it
+const SCATTER_RADIUS: f32 = 18.0;
+// Seconds a scattered bird keeps fleeing before circling again
+const SCATTER_SECONDS: f32 = 6.0;
+// Ground is checked this far ahead of a bird to climb over hills in time
+const LOOK_AHEAD: f32 = 6.0;
+const WING_BEATS_PER_SECOND: f32 = 3.0;
+
+const NEIGHBOR_RADIUS: f32 = 6.0;
+const SEPARATION_RADIUS: f32 = 1.5;
+const SEPARATION_WEIGHT: f32 = 3.0;
+const ALIGNMENT_WEIGHT: f32 = 1.0;
+const COHESION_WEIGHT: f32 = 0.4;
+const ORBIT_WEIGHT: f32 = 1.5;
+const HEIGHT_WEIGHT: f32 = 0.8;
+const FLEE_WEIGHT: f32 = 10.0;
+const MAX_STEER: f32 = 12.0;
+
+// Flocks of birds circling over forests, scattering when the player comes near. Spawned for
+// the forest chunks loaded near the camera and despawned with them, like fish schools.
+#[derive(Default, Clone, Debug)]
+pub struct BirdsPlugin;
+
+impl Plugin for BirdsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<BirdFlocks>()
+            .add_systems(Startup, setup_bird_assets)
+            .add_systems(Update, (
+                forget_despawned_birds,
+                stream_bird_flocks,
+                scatter_birds,
+                update_birds,
+            ).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct Bird {
+    pub velocity: Vec3,
+    pub flock: u32,
+    // Point on the ground the flock circles around
+    home: Vec2,
+    // Seconds left fleeing, 0 while circling
+    scattered: f32,
+    // Offset of the wing beat, so a flock doesn't flap in step
+    phase: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct BirdFlocks {
+    chunks: HashMap<ChunkCoords, Vec<Entity>>,
+    next_flock: u32,
+}
+
+#[derive(Resource)]
+struct BirdAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+// Wet temperate land and rainforests are the forests, where trees grow thick
+pub fn is_forest(sample: &BiomeSample) -> bool {
+    match sample.biome {
+        Biome::Rainforest => true,
+        Biome::Temperate => sample.moisture > FOREST_MOISTURE,
+        _ => false,
+    }
+}
+
+// Horizontal direction keeping a bird on the circle around home: along it, leaning in from
+// outside and out from inside. Counterclockwise seen from above.
+pub fn orbit_direction(position: Vec2, home: Vec2, radius: f32) -> Vec2 {
+    let offset = position - home;
+    let distance = offset.length();
+    if distance < 0.01 {
+        return Vec2::X;
+    }
+    let outward = offset / distance;
+    let along = Vec2::new(-outward.y, outward.x);
+    (along - outward * ((distance - radius) / radius).clamp(-1.0, 1.0)).normalize()
+}
+
+fn setup_bird_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // A flat plank for the wings, which is all that shows from the ground
+    commands.insert_resource(BirdAssets {
+        mesh: meshes.add(Cuboid::new(0.9, 0.06, 0.3)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.12, 0.11, 0.1),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_flock(
+    commands: &mut Commands,
+    assets: &BirdAssets,
+    terrain_edits: &TerrainEdits,
+    chunk: ChunkCoords,
+    flock: u32,
+    creature: Creature,
+    // Most birds the population caps leave room for
+    limit: u32,
+) -> Vec<Entity> {
+    // Seeded by the world and chunk so a chunk always gets the same flock
+    let mut rng = WorldRng::for_chunk(chunk, RngPurpose::Birds);
+    if !rng.chance(FLOCK_CHANCE) {
+        return Vec::new();
+    }
+    let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+    let home = center + (Vec2::new(rng.unit(), rng.unit()) - 0.5) * CHUNK_SIZE * 0.5;
+    let size = (MIN_FLOCK_SIZE + rng.index((MAX_FLOCK_SIZE - MIN_FLOCK_SIZE + 1) as usize) as u32).min(limit);
+    (0..size)
+        .map(|_| {
+            let angle = rng.unit() * std::f32::consts::TAU;
+            let point = home + Vec2::new(angle.cos(), angle.sin()) * CIRCLE_RADIUS * rng.range(0.8, 1.2);
+            let y = terrain_edits.height(point.x, point.y) + CRUISE_HEIGHT + rng.range(-2.0, 2.0);
+            let position = Vec3::new(point.x, y, point.y);
+            let direction = orbit_direction(point, home, CIRCLE_RADIUS);
+            let velocity = Vec3::new(direction.x, 0.0, direction.y) * BIRD_SPEED;
+            commands.spawn((
+                Mesh3d(assets.mesh.clone()),
+                MeshMaterial3d(assets.material.clone()),
+                Transform::from_translation(position).looking_to(velocity, Vec3::Y),
+                Bird { velocity, flock, home, scattered: 0.0, phase: rng.unit() },
+                creature,
+            )).id()
+        })
+        .collect()
+}
+
+// Spawn flocks over the forest chunks loaded near the camera as far as the population caps
+// allow, and despawn those of chunks unloaded or left out of the simulation range
+#[allow(clippy::too_many_arguments)]
+fn stream_bird_flocks(
+    mut commands: Commands,
+    mut flocks: ResMut<BirdFlocks>,
+    mut population: ResMut<Population>,
+    mut camera_chunk: Local<Option<ChunkCoords>>,
+    chunk_manager: Res<ChunkManager>,
+    limits: Res<PopulationLimits>,
+    terrain_edits: Res<TerrainEdits>,
+    time: Res<Time>,
+    assets: Option<Res<BirdAssets>>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+) {
+    let (Ok(camera), Some(assets)) = (camera_query.get_single(), assets) else {
+        return;
+    };
+    let camera = camera.translation.xz();
+    let current_chunk = ((camera.x / CHUNK_SIZE).round() as i32, (camera.y / CHUNK_SIZE).round() as i32);
+    let moved = camera_chunk.replace(current_chunk) != Some(current_chunk);
+    if !chunk_manager.is_changed() && !moved && !limits.is_changed() {
+        return;
+    }
+
+    // Flocks fly a little past their chunk, new ones are kept a chunk's width inside the range
+    let in_range = |chunk: &ChunkCoords, margin: f32| {
+        let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+        center.distance(camera) <= limits.simulation_range - margin
+    };
+    flocks.chunks.retain(|chunk, birds| {
+        if chunk_manager.loaded_chunks.contains_key(chunk) && in_range(chunk, 0.0) {
+            return true;
+        }
+        for entity in birds.drain(..) {
+            commands.entity(entity).despawn_recursive();
+        }
+        false
+    });
+
+    let now = time.elapsed_secs();
+    for &chunk in chunk_manager.loaded_chunks.keys() {
+        if flocks.chunks.contains_key(&chunk) || !in_range(&chunk, CHUNK_SIZE) {
+            continue;
+        }
+        let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+        let sample = biome::sample(center.x, center.y);
+        if !is_forest(&sample) {
+            flocks.chunks.insert(chunk, Vec::new());
+            continue;
+        }
+        // A chunk skipped for lack of room is tried again once creatures despawn
+        let room = population.room(sample.biome, &limits);
+        if room == 0 {
+            continue;
+        }
+        let flock = flocks.next_flock;
+        flocks.next_flock = flocks.next_flock.wrapping_add(1);
+        let birds = spawn_flock(&mut commands, &assets, &terrain_edits, chunk, flock, Creature::new(sample.biome, now), room);
+        population.add(sample.biome, birds.len() as u32);
+        flocks.chunks.insert(chunk, birds);
+    }
+}
+
+// Birds despawned elsewhere, culled by the population caps, leave their flock
+fn forget_despawned_birds(
+    mut removed: RemovedComponents<Bird>,
+    mut flocks: ResMut<BirdFlocks>,
+) {
+    let removed: Vec<Entity> = removed.read().collect();
+    if removed.is_empty() {
+        return;
+    }
+    for birds in flocks.chunks.values_mut() {
+        birds.retain(|entity| !removed.contains(entity));
+    }
+}
+
+// The player coming close startles a bird, and a startled bird its whole flock
+fn scatter_birds(
+    time: Res<Time>,
+    player_query: Query<&Transform, With<Player>>,
+    mut bird_query: Query<(&mut Bird, &Transform)>,
+) {
+    let dt = time.delta_secs();
+    let player = player_query.get_single().ok().map(|transform| transform.translation);
+    let mut startled = Vec::new();
+    for (mut bird, transform) in bird_query.iter_mut() {
+        bird.scattered = (bird.scattered - dt).max(0.0);
+        if player.is_some_and(|player| player.distance(transform.translation) < SCATTER_RADIUS) {
+            startled.push(bird.flock);
+        }
+    }
+    if startled.is_empty() {
+        return;
+    }
+    for (mut bird, _) in bird_query.iter_mut() {
+        if startled.contains(&bird.flock) {
+            bird.scattered = SCATTER_SECONDS;
+        }
+    }
+}
+
+// Boids: separation, alignment and cohesion within a flock, circling home at a height over
+// the ground, or fleeing up and away from the player once scattered
+fn update_birds(
+    time: Res<Time>,
+    terrain_edits: Res<TerrainEdits>,
+    player_query: Query<&Transform, (With<Player>, Without<Bird>)>,
+    mut bird_query: Query<(&mut Bird, &mut Transform)>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    let player = player_query.get_single().ok().map(|transform| transform.translation);
+    let now = time.elapsed_secs();
+
+    let snapshot: Vec<(u32, Vec3, Vec3)> = bird_query.iter()
+        .map(|(bird, transform)| (bird.flock, transform.translation, bird.velocity))
+        .collect();
+
+    for (mut bird, mut transform) in bird_query.iter_mut() {
+        let position = transform.translation;
+        let mut separation = Vec3::ZERO;
+        let mut alignment = Vec3::ZERO;
+        let mut center = Vec3::ZERO;
+        let mut neighbors = 0;
+
+        for &(flock, other_position, other_velocity) in &snapshot {
+            if flock != bird.flock || other_position == position {
+                continue;
+            }
+            let offset = position - other_position;
+            let distance = offset.length();
+            if distance > NEIGHBOR_RADIUS {
+                continue;
+            }
+            if distance < SEPARATION_RADIUS {
+                separation += offset / distance.max(0.01);
+            }
+            alignment += other_velocity;
+            center += other_position;
+            neighbors += 1;
+        }
+
+        let mut steer = separation * SEPARATION_WEIGHT;
+        if neighbors > 0 {
+            let count = neighbors as f32;
+            steer += (alignment / count - bird.velocity) * ALIGNMENT_WEIGHT;
+            steer += (center / count - position) * COHESION_WEIGHT;
+        }
+
+        let ahead = position + bird.velocity.normalize_or_zero() * LOOK_AHEAD;
+        let ground = terrain_edits.height(position.x, position.z).max(terrain_edits.height(ahead.x, ahead.z));
+        let (speed, cruise) = match player.filter(|_| bird.scattered > 0.0) {
+            Some(player) => {
+                let away = Vec3::new(position.x - player.x, 0.0, position.z - player.z).normalize_or_zero();
+                steer += (away + Vec3::Y * 0.5) * FLEE_WEIGHT;
+                (SCATTER_SPEED, MAX_HEIGHT)
+            }
+            None => {
+                let orbit = orbit_direction(position.xz(), bird.home, CIRCLE_RADIUS) * BIRD_SPEED;
+                steer += (Vec3::new(orbit.x, 0.0, orbit.y) - Vec3::new(bird.velocity.x, 0.0, bird.velocity.z)) * ORBIT_WEIGHT;
+                (BIRD_SPEED, CRUISE_HEIGHT)
+            }
+        };
+        steer.y += (ground + cruise - position.y) * HEIGHT_WEIGHT;
+
+        let velocity = bird.velocity + steer.clamp_length_max(MAX_STEER) * dt;
+        bird.velocity = velocity.clamp_length(speed * 0.6, speed);
+
+        let mut next = position + bird.velocity * dt;
+        next.y = next.y.clamp(ground + MIN_HEIGHT, ground + MAX_HEIGHT);
+        transform.translation = next;
+        if bird.velocity.length_squared() > 0.0001 {
+            let target = next + bird.velocity;
+            transform.look_at(target, Vec3::Y);
+        }
+        // Seen from the ground, the wings beating is the plank narrowing and widening
+        let beat = ((now * WING_BEATS_PER_SECOND + bird.phase) * std::f32::consts::TAU).sin();
+        transform.scale = Vec3::new(0.75 + 0.25 * beat, 1.0, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn birds_are_steered_onto_the_circle() {
+        let home = Vec2::new(10.0, -4.0);
+        // On the circle, straight along it
+        let on = orbit_direction(home + Vec2::X * CIRCLE_RADIUS, home, CIRCLE_RADIUS);
+        assert!((on - Vec2::Y).length() < 1e-4);
+        // Outside it leans back in, inside it leans out
+        assert!(orbit_direction(home + Vec2::X * CIRCLE_RADIUS * 2.0, home, CIRCLE_RADIUS).x < 0.0);
+        assert!(orbit_direction(home + Vec2::X * CIRCLE_RADIUS * 0.5, home, CIRCLE_RADIUS).x > 0.0);
+        assert_eq!(orbit_direction(home, home, CIRCLE_RADIUS), Vec2::X);
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+use crate::camera::{CameraMode, CameraSettings};
+use crate::interaction::{InteractEvent, Interactable};
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::terrain;
+use crate::water::{sea_level, WaterQuery};
+
+// Minimum water depth under the hull
+const BOAT_DRAFT: f32 = 0.6;
+const BOAT_LENGTH: f32 = 4.0;
+const BOAT_WIDTH: f32 = 1.8;
+const DECK_HEIGHT: f32 = 0.4;
+const SHORE_SEARCH_RADIUS: f32 = 8.0;
+const BOAT_SEARCH_RADIUS: f32 = 300.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct BoatPlugin;
+
+impl Plugin for BoatPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, spawn_boat)
+            .add_systems(Update, (
+                board_or_leave_boat,
+                drive_boat,
+                float_boats,
+                carry_riders,
+            ).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct Boat {
+    pub heading: f32,
+    pub speed: f32,
+    pub max_speed: f32,
+    pub acceleration: f32,
+    pub turn_rate: f32,
+}
+
+impl Default for Boat {
+    fn default() -> Self {
+        Self {
+            heading: 0.0,
+            speed: 0.0,
+            max_speed: 9.0,
+            acceleration: 4.0,
+            turn_rate: 1.2,
+        }
+    }
+}
+
+// Put on the player while riding a boat
+#[derive(Component)]
+pub struct Aboard {
+    pub boat: Entity,
+}
+
+fn is_navigable(water: &WaterQuery, position: Vec2) -> bool {
+    water.depth(position.x, position.y) > BOAT_DRAFT
+}
+
+fn spawn_boat(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(position) = terrain::find_nearest(Vec2::ZERO, BOAT_SEARCH_RADIUS, 4.0, |x, z| {
+        terrain::height(x, z) < sea_level(x, z) - BOAT_DRAFT * 2.0
+    }) else {
+        info!("No water deep enough near spawn, no boat spawned");
+        return;
+    };
+    info!("Spawning boat at ({:.1}, {:.1})", position.x, position.y);
+
+    let wood = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.45, 0.3, 0.18),
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(BOAT_WIDTH, 0.5, BOAT_LENGTH))),
+        MeshMaterial3d(wood.clone()),
+        Transform::from_xyz(position.x, sea_level(position.x, position.y), position.y),
+        Boat::default(),
+        Interactable {
+            prompt: "Board boat".to_string(),
+            radius: 4.0,
+        },
+    )).with_children(|parent| {
+        // Bow and a small bench so the heading reads at a glance
+        parent.spawn((
+            Mesh3d(meshes.add(Cuboid::new(BOAT_WIDTH * 0.6, 0.4, 0.8))),
+            MeshMaterial3d(wood.clone()),
+            Transform::from_xyz(0.0, 0.2, -BOAT_LENGTH / 2.0 - 0.2),
+        ));
+        parent.spawn((
+            Mesh3d(meshes.add(Cuboid::new(BOAT_WIDTH, 0.15, 0.4))),
+            MeshMaterial3d(wood),
+            Transform::from_xyz(0.0, 0.35, 0.8),
+        ));
+    });
+}
+
+fn board_or_leave_boat(
+    mut commands: Commands,
+    mut events: EventReader<InteractEvent>,
+    boats: Query<&Transform, (With<Boat>, Without<Player>)>,
+    mut players: Query<(&mut Transform, Option<&Aboard>), With<Player>>,
+    water: WaterQuery,
+) {
+    for event in events.read() {
+        let Ok(boat_transform) = boats.get(event.target) else {
+            continue;
+        };
+        let Ok((mut player_transform, aboard)) = players.get_mut(event.actor) else {
+            continue;
+        };
+
+        if aboard.is_none() {
+            info!("Boarding boat");
+            commands.entity(event.actor).insert(Aboard { boat: event.target });
+            continue;
+        }
+
+        // Step off onto the closest dry land, otherwise stay on deck
+        let boat_position = boat_transform.translation.xz();
+        let shore = terrain::find_nearest(boat_position, SHORE_SEARCH_RADIUS, 1.0, |x, z| {
+            !water.has_water(x, z)
+        });
+        match shore {
+            Some(shore) => {
+                info!("Leaving boat");
+                let ground = water.floor_height(shore.x, shore.y);
+                player_transform.translation = Vec3::new(shore.x, ground + PLAYER_HALF_HEIGHT, shore.y);
+                commands.entity(event.actor).remove::<Aboard>();
+            }
+            None => info!("No shore close enough to leave the boat"),
+        }
+    }
+}
+
+// While aboard, WASD throttles and steers the boat instead of moving the player
+fn drive_boat(
+    riders: Query<&Aboard, With<Player>>,
+    mut boats: Query<(&mut Boat, &mut Transform)>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    camera_settings: Res<CameraSettings>,
+    water: WaterQuery,
+    time: Res<Time>,
+) {
+    let Ok(aboard) = riders.get_single() else {
+        return;
+    };
+    let Ok((mut boat, mut transform)) = boats.get_mut(aboard.boat) else {
+        return;
+    };
+    let dt = time.delta_secs();
+    let driving = camera_settings.camera_mode == CameraMode::Player;
+
+    let mut throttle = 0.0;
+    let mut steering = 0.0;
+    if driving {
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            throttle += 1.0;
+        }
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            throttle -= 0.5;
+        }
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            steering += 1.0;
+        }
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            steering -= 1.0;
+        }
+    }
+
+    let target_speed = throttle * boat.max_speed;
+    boat.speed = boat.speed.lerp(target_speed, (boat.acceleration * dt / boat.max_speed).min(1.0));
+    // Steering needs water flowing past the rudder
+    let steer_factor = (boat.speed.abs() / boat.max_speed).clamp(0.2, 1.0);
+    boat.heading += steering * boat.turn_rate * steer_factor * dt;
+
+    let forward = Vec2::new(-boat.heading.sin(), -boat.heading.cos());
+    let next = transform.translation.xz() + forward * boat.speed * dt;
+    if is_navigable(&water, next) {
+        transform.translation.x = next.x;
+        transform.translation.z = next.y;
+    } else {
+        boat.speed = 0.0;
+    }
+}
+
+// Buoyancy: follow the wave surface and tilt with it
+fn float_boats(
+    mut boats: Query<(&Boat, &mut Transform)>,
+    water: WaterQuery,
+) {
+    for (boat, mut transform) in &mut boats {
+        let rotation = Quat::from_rotation_y(boat.heading);
+        let forward = (rotation * Vec3::NEG_Z).xz();
+        let right = (rotation * Vec3::X).xz();
+        let center = transform.translation.xz();
+
+        let bow = water.wave_surface_height(center + forward * BOAT_LENGTH / 2.0);
+        let stern = water.wave_surface_height(center - forward * BOAT_LENGTH / 2.0);
+        let port = water.wave_surface_height(center - right * BOAT_WIDTH / 2.0);
+        let starboard = water.wave_surface_height(center + right * BOAT_WIDTH / 2.0);
+
+        let pitch = ((bow - stern) / BOAT_LENGTH).atan();
+        let roll = ((port - starboard) / BOAT_WIDTH).atan();
+
+        transform.translation.y = (bow + stern + port + starboard) / 4.0;
+        transform.rotation = rotation * Quat::from_euler(EulerRot::XYZ, pitch, 0.0, roll);
+    }
+}
+
+fn carry_riders(
+    mut riders: Query<(&Aboard, &mut Transform), With<Player>>,
+    boats: Query<(&Boat, &Transform), Without<Player>>,
+) {
+    for (aboard, mut player_transform) in &mut riders {
+        let Ok((boat, boat_transform)) = boats.get(aboard.boat) else {
+            continue;
+        };
+        player_transform.translation = boat_transform.translation + Vec3::Y * (DECK_HEIGHT + PLAYER_HALF_HEIGHT);
+        player_transform.rotation = Quat::from_rotation_y(boat.heading);
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI};
+use bevy::prelude::*;
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::collider::{BoxCollider, WalkableSurface};
+use crate::placement::{ObjectPlaced, PlaceableKind, PlacedObject};
+use crate::ruins::ruin_in_chunk;
+use crate::save::{LoadedSave, SavedPiece, SaveRequested};
+use crate::streaming::{mesh_chunk_coords, ChunkCoords};
+use crate::water::WaterQuery;
+
+// Floors and ramps cover a square this wide, walls are this long
+pub const PIECE_SIZE: f32 = 3.0;
+const FLOOR_THICKNESS: f32 = 0.4;
+const WALL_HEIGHT: f32 = 3.0;
+const WALL_THICKNESS: f32 = 0.2;
+// Ramps climb half a wall over one piece
+const RAMP_RISE: f32 = WALL_HEIGHT / 2.0;
+// Aiming this close to a free socket of a built piece snaps to it
+const SNAP_DISTANCE: f32 = 1.5;
+// Terrain may rise this far through a floor or ramp before it counts as intersecting
+const TERRAIN_TOLERANCE: f32 = 0.05;
+
+// Modular floors, walls and ramps placed with the placement tool. A new piece snaps to
+// the sockets of built ones, or stands on the terrain when none is close. Pieces are kept
+// by chunk, spawned while their chunk is loaded and saved with the world. The world's ruins
+// are built from the same pieces.
+#[derive(Default, Clone, Debug)]
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Structures>()
+            .add_systems(Startup, (setup_building_assets, restore_structures).chain())
+            .add_systems(Update, (
+                record_placed_pieces,
+                stream_structures,
+            ).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct StructurePiece;
+
+#[derive(Resource, Default)]
+pub struct Structures {
+    // Every piece built, by the chunk containing it
+    pub chunks: HashMap<ChunkCoords, Vec<SavedPiece>>,
+    // Piece entities of the loaded chunks
+    spawned: HashMap<ChunkCoords, Vec<Entity>>,
+}
+
+#[derive(Resource)]
+struct BuildingAssets {
+    floor_mesh: Handle<Mesh>,
+    wall_mesh: Handle<Mesh>,
+    ramp_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    // Weathered stone of the ruins found in the world
+    ruin_material: Handle<StandardMaterial>,
+}
+
+// Where a piece would go and whether it can be built there
+#[derive(Debug, Clone, Copy)]
+pub struct PiecePlacement {
+    pub transform: Transform,
+    pub valid: bool,
+    // Attached to another piece rather than standing on the terrain
+    pub snapped: bool,
+}
+
+// Piece shapes, with the origin where things attach: the walking surface's center for
+// floors, the footprint's center at the low edge's height for ramps, the base's center for walls
+pub fn piece_mesh(kind: PlaceableKind) -> Mesh {
+    match kind {
+        PlaceableKind::Wall => Mesh::from(Cuboid::new(PIECE_SIZE, WALL_HEIGHT, WALL_THICKNESS))
+            .translated_by(Vec3::Y * WALL_HEIGHT / 2.0),
+        PlaceableKind::Ramp => {
+            // A slab tilted to rise toward -z, its top on the walking surface
+            let angle = (RAMP_RISE / PIECE_SIZE).atan();
+            let length = PIECE_SIZE.hypot(RAMP_RISE);
+            Mesh::from(Cuboid::new(PIECE_SIZE, FLOOR_THICKNESS, length))
+                .translated_by(Vec3::NEG_Y * FLOOR_THICKNESS / 2.0)
+                .rotated_by(Quat::from_rotation_x(angle))
+                .translated_by(Vec3::Y * RAMP_RISE / 2.0)
+        }
+        _ => Mesh::from(Cuboid::new(PIECE_SIZE, FLOOR_THICKNESS, PIECE_SIZE))
+            .translated_by(Vec3::NEG_Y * FLOOR_THICKNESS / 2.0),
+    }
+}
+
+fn walkable_surface(kind: PlaceableKind) -> Option<WalkableSurface> {
+    let half_size = Vec2::splat(PIECE_SIZE / 2.0);
+    match kind {
+        PlaceableKind::Floor => Some(WalkableSurface { half_size, rise: 0.0 }),
+        PlaceableKind::Ramp => Some(WalkableSurface { half_size, rise: RAMP_RISE }),
+        _ => None,
+    }
+}
+
+// Sides of a square piece: local direction and the yaw turning -z toward it
+pub const SIDES: [(Vec3, f32); 4] = [
+    (Vec3::NEG_Z, 0.0),
+    (Vec3::Z, PI),
+    (Vec3::NEG_X, FRAC_PI_2),
+    (Vec3::X, -FRAC_PI_2),
+];
+
+// Spots a new piece can attach to a built one, as offsets and yaws in the built piece's space
+fn sockets(built: PlaceableKind, new: PlaceableKind) -> Vec<(Vec3, f32)> {
+    let (size, half) = (PIECE_SIZE, PIECE_SIZE / 2.0);
+    match (built, new) {
+        (PlaceableKind::Floor, PlaceableKind::Floor) => SIDES.iter().map(|(side, _)| (*side * size, 0.0)).collect(),
+        (PlaceableKind::Floor, PlaceableKind::Wall) => SIDES.iter().map(|(side, yaw)| (*side * half, *yaw)).collect(),
+        // Climbing away from the floor's side, or coming up to it
+        (PlaceableKind::Floor, PlaceableKind::Ramp) => SIDES.iter()
+            .flat_map(|(side, yaw)| [(*side * size, *yaw), (*side * size - Vec3::Y * RAMP_RISE, yaw + PI)])
+            .collect(),
+        (PlaceableKind::Wall, PlaceableKind::Wall) => vec![
+            (Vec3::X * size, 0.0),
+            (Vec3::NEG_X * size, 0.0),
+            (Vec3::Y * WALL_HEIGHT, 0.0),
+        ],
+        // Resting on the wall's top, on either side
+        (PlaceableKind::Wall, PlaceableKind::Floor) => vec![
+            (Vec3::new(0.0, WALL_HEIGHT, half), 0.0),
+            (Vec3::new(0.0, WALL_HEIGHT, -half), 0.0),
+        ],
+        (PlaceableKind::Ramp, PlaceableKind::Floor) => vec![
+            (Vec3::new(0.0, RAMP_RISE, -size), 0.0),
+            (Vec3::Z * size, 0.0),
+        ],
+        (PlaceableKind::Ramp, PlaceableKind::Ramp) => vec![
+            (Vec3::new(0.0, RAMP_RISE, -size), 0.0),
+            (Vec3::new(0.0, -RAMP_RISE, size), 0.0),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+// Nearest free socket of the built pieces around the aimed spot
+fn snap(kind: PlaceableKind, target: Vec3, placed: &[(&Transform, &PlacedObject)]) -> Option<Transform> {
+    let occupied = |transform: &Transform| {
+        placed.iter().any(|(other, object)| object.kind == kind && other.translation.distance(transform.translation) < 0.1)
+    };
+    placed.iter()
+        .filter(|(built, object)| object.kind.is_structure() && built.translation.xz().distance(target.xz()) < PIECE_SIZE * 2.0)
+        .flat_map(|(built, object)| {
+            sockets(object.kind, kind).into_iter().map(move |(offset, yaw)| {
+                Transform::from_translation(built.translation + built.rotation * offset)
+                    .with_rotation(built.rotation * Quat::from_rotation_y(yaw))
+            })
+        })
+        .filter(|candidate| candidate.translation.xz().distance(target.xz()) < SNAP_DISTANCE)
+        .filter(|candidate| !occupied(candidate))
+        .min_by(|a, b| a.translation.distance(target).total_cmp(&b.translation.distance(target)))
+}
+
+// Points along the bottom of a piece, in world space before its height is known
+fn base_samples(kind: PlaceableKind, transform: &Transform) -> Vec<Vec3> {
+    let half = PIECE_SIZE / 2.0 * 0.95;
+    let local: Vec<Vec3> = match kind {
+        PlaceableKind::Wall => (-2..=2).map(|i| Vec3::X * half * i as f32 / 2.0).collect(),
+        _ => (-1..=1).flat_map(|z| (-1..=1).map(move |x| Vec3::new(x as f32, 0.0, z as f32) * half)).collect(),
+    };
+    local.into_iter().map(|point| transform.transform_point(point)).collect()
+}
+
+// Height of a floor's or ramp's walking surface above a world point
+fn surface_at(kind: PlaceableKind, transform: &Transform, point: Vec3) -> f32 {
+    let local = transform.rotation.inverse() * (point - transform.translation);
+    let rise = walkable_surface(kind).map_or(0.0, |surface| surface.rise);
+    transform.translation.y + rise * (PIECE_SIZE / 2.0 - local.z) / PIECE_SIZE
+}
+
+// Snap to a built piece when one is close, otherwise stand on the terrain. Pieces on the
+// terrain need dry, even enough ground; no piece may have terrain poking through its
+// surface or overlap other objects.
+pub fn place_piece(
+    kind: PlaceableKind,
+    target: Vec3,
+    yaw: f32,
+    placed: &[(&Transform, &PlacedObject)],
+    water: &WaterQuery,
+) -> PiecePlacement {
+    let snapped = snap(kind, target, placed);
+    let mut transform = snapped.unwrap_or_else(|| Transform::from_translation(target).with_rotation(Quat::from_rotation_y(yaw)));
+    let samples = base_samples(kind, &transform);
+    let ground: Vec<f32> = samples.iter().map(|point| water.floor_height(point.x, point.z)).collect();
+
+    let mut valid = true;
+    if snapped.is_none() {
+        let lowest = ground.iter().copied().fold(f32::MAX, f32::min);
+        let highest = ground.iter().copied().fold(f32::MIN, f32::max);
+        // Floors sit on the highest point and walls reach down to the lowest, ramps start at their low edge
+        transform.translation.y = match kind {
+            PlaceableKind::Floor => highest,
+            PlaceableKind::Wall => lowest,
+            _ => {
+                let low_edge = transform.transform_point(Vec3::Z * PIECE_SIZE / 2.0);
+                water.floor_height(low_edge.x, low_edge.z)
+            }
+        };
+        let dry = samples.iter().all(|point| !water.has_water(point.x, point.z));
+        valid &= dry && highest - lowest <= kind.max_unevenness();
+    }
+
+    if kind != PlaceableKind::Wall {
+        valid &= samples.iter().zip(&ground)
+            .all(|(point, ground)| *ground <= surface_at(kind, &transform, *point) + TERRAIN_TOLERANCE);
+    }
+
+    valid &= placed.iter().all(|(other, object)| {
+        if object.kind.is_structure() {
+            // Pieces of a kind only share a spot when stacked apart
+            object.kind != kind
+                || other.translation.xz().distance(transform.translation.xz()) > PIECE_SIZE * 0.9
+                || (other.translation.y - transform.translation.y).abs() > 0.5
+        } else {
+            other.translation.xz().distance(transform.translation.xz()) > kind.footprint() + object.kind.footprint()
+        }
+    });
+
+    PiecePlacement { transform, valid, snapped: snapped.is_some() }
+}
+
+fn setup_building_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(BuildingAssets {
+        floor_mesh: meshes.add(piece_mesh(PlaceableKind::Floor)),
+        wall_mesh: meshes.add(piece_mesh(PlaceableKind::Wall)),
+        ramp_mesh: meshes.add(piece_mesh(PlaceableKind::Ramp)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.55, 0.4, 0.26),
+            perceptual_roughness: 0.85,
+            ..default()
+        }),
+        ruin_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.5, 0.5, 0.47),
+            perceptual_roughness: 0.95,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_piece(commands: &mut Commands, assets: &BuildingAssets, piece: &SavedPiece, material: &Handle<StandardMaterial>) -> Entity {
+    let mesh = match piece.kind {
+        PlaceableKind::Wall => assets.wall_mesh.clone(),
+        PlaceableKind::Ramp => assets.ramp_mesh.clone(),
+        _ => assets.floor_mesh.clone(),
+    };
+    let mut entity = commands.spawn((
+        Mesh3d(mesh),
+        MeshMaterial3d(material.clone()),
+        Transform::from_translation(piece.position).with_rotation(piece.rotation),
+        PlacedObject { kind: piece.kind },
+        StructurePiece,
+    ));
+    if let Some(surface) = walkable_surface(piece.kind) {
+        entity.insert(surface);
+    }
+    if piece.kind == PlaceableKind::Wall {
+        entity.with_child((
+            Transform::from_xyz(0.0, WALL_HEIGHT / 2.0, 0.0),
+            BoxCollider { half_size: Vec3::new(PIECE_SIZE, WALL_HEIGHT, WALL_THICKNESS) / 2.0 },
+        ));
+    }
+    entity.id()
+}
+
+fn restore_structures(
+    save: Res<LoadedSave>,
+    mut structures: ResMut<Structures>,
+) {
+    structures.chunks = save.0.structures.clone();
+}
+
+// New pieces join their chunk's data and the world is saved with them
+fn record_placed_pieces(
+    mut commands: Commands,
+    mut placed: EventReader<ObjectPlaced>,
+    assets: Res<BuildingAssets>,
+    mut structures: ResMut<Structures>,
+    mut save_requests: EventWriter<SaveRequested>,
+) {
+    for event in placed.read() {
+        if !event.kind.is_structure() {
+            continue;
+        }
+        let piece = SavedPiece { kind: event.kind, position: event.position, rotation: event.rotation };
+        let coords = mesh_chunk_coords(piece.position.x, piece.position.z, CHUNK_SIZE);
+        if structures.spawned.contains_key(&coords) {
+            let entity = spawn_piece(&mut commands, &assets, &piece, &assets.material);
+            structures.spawned.entry(coords).or_default().push(entity);
+        }
+        structures.chunks.entry(coords).or_default().push(piece);
+        save_requests.send(SaveRequested { reason: "building" });
+    }
+}
+
+// Pieces and the chunk's ruin come and go with the terrain chunks they stand in
+fn stream_structures(
+    mut commands: Commands,
+    chunk_manager: Res<ChunkManager>,
+    assets: Res<BuildingAssets>,
+    mut structures: ResMut<Structures>,
+) {
+    if !chunk_manager.is_changed() {
+        return;
+    }
+    let Structures { chunks, spawned } = &mut *structures;
+    spawned.retain(|coords, entities| {
+        let loaded = chunk_manager.loaded_chunks.contains_key(coords);
+        if !loaded {
+            for entity in entities.drain(..) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        loaded
+    });
+    for coords in chunk_manager.loaded_chunks.keys() {
+        if spawned.contains_key(coords) {
+            continue;
+        }
+        let built = chunks.get(coords).into_iter().flatten().map(|piece| (piece, &assets.material));
+        let ruin = ruin_in_chunk(*coords).map(|ruin| ruin.pieces).unwrap_or_default();
+        let entities = built
+            .chain(ruin.iter().map(|piece| (piece, &assets.ruin_material)))
+            .map(|(piece, material)| spawn_piece(&mut commands, &assets, piece, material))
+            .collect();
+        spawned.insert(*coords, entities);
+    }
+}
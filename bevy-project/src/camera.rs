@@ -2,13 +2,64 @@ use bevy::input::keyboard::KeyCode;
 use bevy::input::mouse::MouseButton;
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
+use bevy::render::camera::{RenderTarget, ScalingMode};
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
 use bevy_atmosphere::prelude::*;
-use crate::player::Player;
+use bevy_egui::{egui, EguiContexts};
+use crate::camera_shake::CameraShake;
+use crate::streaming::StreamingAnchor;
+use crate::player::{Player, PlayerInput, PlayerStance, PLAYER_HALF_HEIGHT};
 
 
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct CameraSettings {
     pub camera_mode: CameraMode,
+    pub eye_heights: EyeHeights,
+    // How fast the eye moves to a new stance height, higher is snappier
+    pub eye_transition_speed: f32,
+    // Current eye height above the player's feet, eased toward the stance's height
+    pub eye_height: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        let eye_heights = EyeHeights::default();
+        Self {
+            camera_mode: CameraMode::default(),
+            eye_transition_speed: 8.0,
+            eye_height: eye_heights.standing,
+            eye_heights,
+        }
+    }
+}
+
+// Eye height above the feet for each player stance
+#[derive(Clone, Debug)]
+pub struct EyeHeights {
+    pub standing: f32,
+    pub crouching: f32,
+    pub swimming: f32,
+}
+
+impl Default for EyeHeights {
+    fn default() -> Self {
+        Self {
+            standing: 1.9,
+            crouching: 1.1,
+            swimming: 1.6,
+        }
+    }
+}
+
+impl EyeHeights {
+    pub fn for_stance(&self, stance: PlayerStance) -> f32 {
+        match stance {
+            PlayerStance::Standing => self.standing,
+            PlayerStance::Crouching => self.crouching,
+            PlayerStance::Swimming => self.swimming,
+        }
+    }
 }
 
 #[derive(Default, Clone, Debug, PartialEq)]
@@ -29,8 +80,13 @@ impl Plugin for CameraPlugin {
             .add_systems(Startup, spawn_camera)
             .add_systems(Update, free_camera_system)
             .add_systems(Update, camera_look)
-            .add_systems(Update, camera_follow_player)
-            .add_systems(Update, camera_mouse_look);
+            .add_systems(Update, (update_eye_height, camera_follow_player).chain())
+            .add_systems(Update, camera_mouse_look)
+            .add_systems(Update, (
+                toggle_overview_camera,
+                follow_overview_camera,
+                texture_camera_ui,
+            ).chain());
     }
 }
 
@@ -84,7 +140,9 @@ fn spawn_camera(
     commands.spawn((
         Camera3d::default(),
         FreeCamera,
+        StreamingAnchor::default(),
         CameraPlayer::default(),
+        CameraShake::default(),
         AtmosphereCamera::default(),
         Transform::from_xyz(0.0, 1.0, 0.0).looking_at(Vec3::ZERO, Vec3::Y),
     ));
@@ -103,17 +161,17 @@ pub fn camera_look(
         return;
     }
 
-    if mouse_button_input.pressed(MouseButton::Right) {
-        if let Ok(mut transform) = query.get_single_mut() {
-            for motion in mouse_motion.read() {
-                let sensitivity : f32 = 0.002;
-                
-                transform.rotate_y(-motion.delta.x * sensitivity);
+    if mouse_button_input.pressed(MouseButton::Right)
+        && let Ok(mut transform) = query.get_single_mut()
+    {
+        for motion in mouse_motion.read() {
+            let sensitivity : f32 = 0.002;
+            
+            transform.rotate_y(-motion.delta.x * sensitivity);
 
-                let right = transform.right();
-                transform.rotate_around(Vec3::ZERO, Quat::from_axis_angle(*right, -motion.delta.y * sensitivity));
+            let right = transform.right();
+            transform.rotate_around(Vec3::ZERO, Quat::from_axis_angle(*right, -motion.delta.y * sensitivity));
 
-            }
         }
     }
 }
@@ -121,6 +179,7 @@ pub fn camera_look(
 
 #[derive(Component)]
 pub struct CameraPlayer {
+    #[allow(dead_code)]
     pub player_id: i32,
     pub distance: f32,
     pub height: f32,
@@ -134,7 +193,8 @@ impl Default for CameraPlayer {
         Self {
             player_id : 0,
             distance: 10.0,
-            height: 2.0,
+            // Above the eye
+            height: 1.5,
             sensitivity: 0.01,
             yaw: 0.0,
             pitch: -0.3
@@ -143,13 +203,32 @@ impl Default for CameraPlayer {
 }
 
 
+// Ease the eye height toward the one of the player's stance instead of jumping
+fn update_eye_height(
+    mut camera_settings: ResMut<CameraSettings>,
+    player_query: Query<&PlayerStance, With<Player>>,
+    time: Res<Time>,
+) {
+    let Ok(stance) = player_query.get_single() else {
+        return;
+    };
+    let target = camera_settings.eye_heights.for_stance(*stance);
+    if camera_settings.eye_height == target {
+        return;
+    }
+    let blend = 1.0 - (-camera_settings.eye_transition_speed * time.delta_secs()).exp();
+    let eye_height = camera_settings.eye_height.lerp(target, blend);
+    camera_settings.eye_height = if (eye_height - target).abs() < 0.001 { target } else { eye_height };
+}
+
+#[allow(clippy::type_complexity)]
 pub fn camera_follow_player(
     mut camera_query: Query<(&mut Transform, &CameraPlayer), (With<CameraPlayer>, Without<Player>)>,
     player_query: Query<&Transform, (With<Player>, Without<CameraPlayer>)>,
     time: Res<Time>,
-    camera_settings: Res<CameraSettings>,
+    settings: Res<CameraSettings>,
 ) {
-    if camera_settings.camera_mode != CameraMode::Player {
+    if settings.camera_mode != CameraMode::Player {
         return;
     }
 
@@ -163,23 +242,23 @@ pub fn camera_follow_player(
             0.0
         );
         
+        let feet = player_transform.translation - Vec3::Y * PLAYER_HALF_HEIGHT;
+        let eye = feet + Vec3::Y * settings.eye_height;
         let offset = rot * Vec3::new(0.0, 0.0, camera_settings.distance);
-        let target_position = player_transform.translation + offset + Vec3::Y * camera_settings.height;
+        let target_position = eye + offset + Vec3::Y * camera_settings.height;
         
         let lerp_factor = 8.0 * time.delta_secs();
         camera_transform.translation = camera_transform.translation.lerp(target_position, lerp_factor);
         
-        camera_transform.look_at(
-            player_transform.translation + Vec3::Y * 0.5,
-            Vec3::Y
-        );
+        camera_transform.look_at(eye, Vec3::Y);
     }
 }
 
 
+// The camera turns right away, the player turns by the same amount on its next fixed tick
 pub fn camera_mouse_look(
     mut camera_query: Query<&mut CameraPlayer>,
-    mut player_query: Query<&mut Transform, With<Player>>,
+    mut player_input: ResMut<PlayerInput>,
     mut mouse_motion: EventReader<MouseMotion>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
     camera_settings: Res<CameraSettings>,
@@ -189,21 +268,148 @@ pub fn camera_mouse_look(
         return;
     }
 
-    if let (Ok(mut camera_player), Ok(mut player_transform)) = 
-        (camera_query.get_single_mut(), player_query.get_single_mut()) 
+    if let Ok(mut camera_player) = camera_query.get_single_mut()
+        && mouse_button_input.pressed(MouseButton::Right)
     {
-        if mouse_button_input.pressed(MouseButton::Right) {
-            for motion in mouse_motion.read() {
-
-                camera_player.yaw -= motion.delta.x * camera_player.sensitivity;
-                
-                player_transform.rotation = Quat::from_rotation_y(camera_player.yaw);
-                
-                camera_player.pitch -= motion.delta.y * camera_player.sensitivity;
-                camera_player.pitch = camera_player.pitch.clamp(-1.2, 0.8);
-                
-                camera_player.yaw = camera_player.yaw.rem_euclid(std::f32::consts::TAU);
-            }
+        for motion in mouse_motion.read() {
+
+            let yaw_delta = -motion.delta.x * camera_player.sensitivity;
+            camera_player.yaw += yaw_delta;
+            player_input.yaw_delta += yaw_delta;
+            
+            camera_player.pitch -= motion.delta.y * camera_player.sensitivity;
+            camera_player.pitch = camera_player.pitch.clamp(-1.2, 0.8);
+            
+            camera_player.yaw = camera_player.yaw.rem_euclid(std::f32::consts::TAU);
         }
     }
-}
\ No newline at end of file
+}
+
+
+// Overview camera looking straight down, for watching chunk streaming from above
+const OVERVIEW_HEIGHT: f32 = 300.0;
+const OVERVIEW_VIEW_SIZE: f32 = 400.0;
+const OVERVIEW_RESOLUTION: UVec2 = UVec2::new(320, 320);
+
+// Extra camera rendering into `image` instead of the window. Register it with
+// `register_texture_camera` and remove it by despawning (`remove_texture_camera`).
+#[derive(Component, Clone, Debug)]
+pub struct TextureCamera {
+    pub name: String,
+    pub image: Handle<Image>,
+    pub size: UVec2,
+    // Show the image in its own egui window
+    pub show_in_ui: bool,
+}
+
+#[derive(Component)]
+struct OverviewCamera;
+
+// Image a camera can render into and egui or materials can sample
+pub fn render_target_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d { width: size.x.max(1), height: size.y.max(1), depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Bgra8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+// Spawn a camera rendering the world into a new image of `size` pixels.
+// Texture cameras render before the window so their images show the current frame.
+pub fn register_texture_camera(
+    commands: &mut Commands,
+    images: &mut Assets<Image>,
+    name: impl Into<String>,
+    size: UVec2,
+    transform: Transform,
+    show_in_ui: bool,
+) -> Entity {
+    let image = images.add(render_target_image(size));
+    commands.spawn((
+        Camera3d::default(),
+        Camera {
+            target: RenderTarget::Image(image.clone()),
+            order: -1,
+            ..default()
+        },
+        AtmosphereCamera::default(),
+        transform,
+        TextureCamera {
+            name: name.into(),
+            image,
+            size,
+            show_in_ui,
+        },
+    )).id()
+}
+
+// The image is freed with the camera, unless something else still holds it
+pub fn remove_texture_camera(commands: &mut Commands, camera: Entity) {
+    commands.entity(camera).despawn_recursive();
+}
+
+// F7 shows or hides the overview camera
+fn toggle_overview_camera(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    input: Res<ButtonInput<KeyCode>>,
+    overview_query: Query<Entity, With<OverviewCamera>>,
+) {
+    if !input.just_pressed(KeyCode::F7) {
+        return;
+    }
+    if let Ok(camera) = overview_query.get_single() {
+        remove_texture_camera(&mut commands, camera);
+        return;
+    }
+
+    let camera = register_texture_camera(
+        &mut commands,
+        &mut images,
+        "Overview",
+        OVERVIEW_RESOLUTION,
+        Transform::from_xyz(0.0, OVERVIEW_HEIGHT, 0.0).looking_to(Vec3::NEG_Y, Vec3::NEG_Z),
+        true,
+    );
+    commands.entity(camera).insert((
+        OverviewCamera,
+        Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical { viewport_height: OVERVIEW_VIEW_SIZE },
+            ..OrthographicProjection::default_3d()
+        }),
+    ));
+}
+
+fn follow_overview_camera(
+    player_query: Query<&Transform, (With<Player>, Without<OverviewCamera>)>,
+    mut overview_query: Query<&mut Transform, With<OverviewCamera>>,
+) {
+    let (Ok(player), Ok(mut transform)) = (player_query.get_single(), overview_query.get_single_mut()) else {
+        return;
+    };
+    transform.translation = Vec3::new(player.translation.x, OVERVIEW_HEIGHT, player.translation.z);
+}
+
+// A window per texture camera that asks for one
+fn texture_camera_ui(
+    mut contexts: EguiContexts,
+    camera_query: Query<(Entity, &TextureCamera)>,
+) {
+    for (entity, camera) in camera_query.iter() {
+        if !camera.show_in_ui {
+            continue;
+        }
+        // Weak handle: egui drops the texture on its own once the camera is removed
+        let texture = contexts.add_image(camera.image.clone_weak());
+        egui::Window::new(format!("Camera: {}", camera.name))
+            .id(egui::Id::new(("texture_camera", entity)))
+            .resizable(false)
+            .show(contexts.ctx_mut(), |ui| {
+                ui.image((texture, egui::vec2(camera.size.x as f32, camera.size.y as f32)));
+            });
+    }
+}
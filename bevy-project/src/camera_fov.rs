@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::player::{Player, SPRINT_SPEED, WALK_SPEED};
+use crate::settings::GameSettings;
+
+// Degrees added at full sprint and when falling fast
+const SPRINT_KICK: f32 = 8.0;
+const FALL_KICK: f32 = 12.0;
+// Falling widens the view from FALL_KICK_START m/s, fully at FALL_KICK_FULL
+const FALL_KICK_START: f32 = 6.0;
+const FALL_KICK_FULL: f32 = 20.0;
+// How fast the kick follows the player's speed, higher is snappier
+const KICK_SMOOTHING: f32 = 6.0;
+// Moving more than this in a frame is a teleport (respawn, boarding), not speed
+const TELEPORT_DISTANCE: f32 = 5.0;
+
+// Field of view of the world camera: the angle from the settings, widened while sprinting or
+// falling fast. Every camera mode renders through the same projection, so all get it.
+#[derive(Default, Clone, Debug)]
+pub struct CameraFovPlugin;
+
+impl Plugin for CameraFovPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CameraFov>()
+            .add_systems(Update, (sync_base_fov, update_fov_kick).chain())
+            .add_systems(PostUpdate, apply_camera_fov);
+    }
+}
+
+// Vertical angles, in degrees
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct CameraFov {
+    pub base: f32,
+    pub kick: f32,
+}
+
+impl Default for CameraFov {
+    fn default() -> Self {
+        Self { base: 45.0, kick: 0.0 }
+    }
+}
+
+impl CameraFov {
+    pub fn degrees(&self) -> f32 {
+        self.base + self.kick
+    }
+}
+
+fn sync_base_fov(
+    settings: Res<GameSettings>,
+    mut fov: ResMut<CameraFov>,
+) {
+    if settings.is_changed() {
+        fov.base = settings.graphics.fov;
+    }
+}
+
+// Ease the kick toward how fast the player moves, off when walking or in the free camera
+fn update_fov_kick(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    camera_settings: Res<CameraSettings>,
+    mut fov: ResMut<CameraFov>,
+    mut last_position: Local<Option<Vec3>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let dt = time.delta_secs();
+    let position = player_query.get_single().ok().map(|transform| transform.translation);
+    let velocity = match (*last_position, position) {
+        (Some(last), Some(position)) if dt > 0.0 && last.distance(position) < TELEPORT_DISTANCE => (position - last) / dt,
+        _ => Vec3::ZERO,
+    };
+    *last_position = position;
+
+    let target = if settings.graphics.fov_kick && camera_settings.camera_mode == CameraMode::Player {
+        let sprint = ((velocity.xz().length() - WALK_SPEED) / (SPRINT_SPEED - WALK_SPEED)).clamp(0.0, 1.0);
+        let fall = ((-velocity.y - FALL_KICK_START) / (FALL_KICK_FULL - FALL_KICK_START)).clamp(0.0, 1.0);
+        SPRINT_KICK * sprint + FALL_KICK * fall
+    } else {
+        0.0
+    };
+    let mut kick = fov.kick + (target - fov.kick) * (1.0 - (-KICK_SMOOTHING * dt).exp());
+    // Snap when close, so a settled kick stops changing the projection
+    if (kick - target).abs() < 0.01 {
+        kick = target;
+    }
+    if kick != fov.kick {
+        fov.kick = kick;
+    }
+}
+
+fn apply_camera_fov(
+    fov: Res<CameraFov>,
+    mut camera_query: Query<&mut Projection, With<FreeCamera>>,
+) {
+    if !fov.is_changed() {
+        return;
+    }
+    for mut projection in camera_query.iter_mut() {
+        if let Projection::Perspective(perspective) = &mut *projection {
+            perspective.fov = fov.degrees().to_radians();
+        }
+    }
+}
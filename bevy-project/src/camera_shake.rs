@@ -0,0 +1,132 @@
+use std::sync::LazyLock;
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use noise::{NoiseFn, Perlin};
+use crate::player::{move_player, Player};
+
+// Trauma lost per second, a full shake settles in a second
+const TRAUMA_DECAY: f32 = 1.0;
+// How fast the shake wobbles, in noise cycles per second
+const SHAKE_FREQUENCY: f64 = 18.0;
+// Offsets at full trauma
+const MAX_OFFSET: Vec3 = Vec3::new(0.25, 0.2, 0.1);
+// Yaw, pitch and roll at full trauma, in radians
+const MAX_ANGLES: Vec3 = Vec3::new(0.04, 0.04, 0.07);
+// Shakes from a point in the world fade out over this distance
+const SHAKE_FALLOFF_DISTANCE: f32 = 80.0;
+// Dropping more than this in one tick counts as a landing
+const LANDING_DROP: f32 = 0.35;
+// Trauma per meter dropped
+const LANDING_TRAUMA: f32 = 0.25;
+// More than this in one tick is a teleport (respawn, boarding), not a landing
+const TELEPORT_DISTANCE: f32 = 5.0;
+
+static SHAKE_NOISE: LazyLock<Perlin> = LazyLock::new(|| Perlin::new(11));
+
+#[derive(Default, Clone, Debug)]
+pub struct CameraShakePlugin;
+
+impl Plugin for CameraShakePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<CameraShakeEvent>()
+            .add_systems(FixedUpdate, shake_on_landing.after(move_player))
+            .add_systems(PreUpdate, remove_camera_shake)
+            .add_systems(Update, receive_shake_events)
+            .add_systems(PostUpdate, apply_camera_shake.before(TransformSystem::TransformPropagate));
+    }
+}
+
+// Shake of a camera using the trauma model: effects add trauma, which decays over time,
+// and the shake grows with its square so small hits stay subtle. The offset is added on
+// top of whatever the camera mode placed the camera at, and taken off again next frame.
+#[derive(Component, Default, Debug)]
+pub struct CameraShake {
+    trauma: f32,
+    time: f32,
+    applied_offset: Vec3,
+    applied_rotation: Quat,
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    // Smooth noise in -1..1 for one of the six shake channels
+    fn noise(&self, channel: u32) -> f32 {
+        SHAKE_NOISE.get([self.time as f64 * SHAKE_FREQUENCY, channel as f64 * 7.3]) as f32
+    }
+}
+
+// Shake for effects that don't hold the camera, like thunder or explosions.
+// With an `origin` the trauma fades with the distance to the camera.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CameraShakeEvent {
+    pub trauma: f32,
+    pub origin: Option<Vec3>,
+}
+
+fn receive_shake_events(
+    mut events: EventReader<CameraShakeEvent>,
+    mut camera_query: Query<(&Transform, &mut CameraShake)>,
+) {
+    for event in events.read() {
+        for (transform, mut shake) in camera_query.iter_mut() {
+            let falloff = event.origin.map_or(1.0, |origin| {
+                1.0 - (transform.translation.distance(origin) / SHAKE_FALLOFF_DISTANCE).min(1.0)
+            });
+            shake.add_trauma(event.trauma * falloff);
+        }
+    }
+}
+
+// Dropping onto lower ground in one tick (walking off a dug edge, a ledge, ending a flight)
+fn shake_on_landing(
+    mut last_position: Local<Option<Vec3>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut events: EventWriter<CameraShakeEvent>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let position = player.translation;
+    if let Some(last) = last_position.replace(position) {
+        let drop = last.y - position.y;
+        if drop > LANDING_DROP && last.distance(position) < TELEPORT_DISTANCE {
+            events.send(CameraShakeEvent { trauma: drop * LANDING_TRAUMA, origin: None });
+        }
+    }
+}
+
+// Take last frame's shake off so the camera modes work from the unshaken transform
+fn remove_camera_shake(mut camera_query: Query<(&mut Transform, &mut CameraShake)>) {
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        transform.translation -= shake.applied_offset;
+        transform.rotation *= shake.applied_rotation.inverse();
+        shake.applied_offset = Vec3::ZERO;
+        shake.applied_rotation = Quat::IDENTITY;
+    }
+}
+
+fn apply_camera_shake(
+    time: Res<Time>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShake)>,
+) {
+    for (mut transform, mut shake) in camera_query.iter_mut() {
+        shake.trauma = (shake.trauma - TRAUMA_DECAY * time.delta_secs()).max(0.0);
+        if shake.trauma <= 0.0 {
+            continue;
+        }
+        shake.time += time.delta_secs();
+
+        let amount = shake.trauma * shake.trauma;
+        let offset = MAX_OFFSET * Vec3::new(shake.noise(0), shake.noise(1), shake.noise(2)) * amount;
+        let angles = MAX_ANGLES * Vec3::new(shake.noise(3), shake.noise(4), shake.noise(5)) * amount;
+        // Offset in the camera's own frame so it reads the same whichever way it faces
+        shake.applied_offset = transform.rotation * offset;
+        shake.applied_rotation = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+        transform.translation += shake.applied_offset;
+        transform.rotation *= shake.applied_rotation;
+    }
+}
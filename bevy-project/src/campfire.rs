@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use crate::creative::CreativeMode;
+use crate::hotbar::Hotbar;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::inventory::Inventory;
+use crate::placement::{ObjectPlaced, PlaceableKind, PlacedObject};
+use crate::projectile::Hittable;
+use crate::player::RespawnPoint;
+use crate::save::{LoadedSave, SaveRequested};
+
+const CAMPFIRE_INTERACT_RADIUS: f32 = 2.5;
+const FLAME_HEIGHT: f32 = 0.6;
+const FLAME_EMISSIVE: LinearRgba = LinearRgba::rgb(12.0, 4.0, 0.8);
+const FIRE_LIGHT_INTENSITY: f32 = 400_000.0;
+const FIRE_LIGHT_RANGE: f32 = 30.0;
+// Held in hand to light a fire, one is burnt
+const KINDLING_ITEM: &str = "wood";
+
+#[derive(Default, Clone, Debug)]
+pub struct CampfirePlugin;
+
+impl Plugin for CampfirePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, (setup_campfire_assets, spawn_saved_campfires).chain())
+            .add_systems(Update, (
+                spawn_placed_campfires,
+                use_campfires,
+                animate_campfires,
+            ).chain());
+    }
+}
+
+// A lit campfire is the player's respawn point
+#[derive(Component)]
+pub struct Campfire {
+    pub lit: bool,
+    flame: Entity,
+    light: Entity,
+    // Each fire owns its flame material so it can flicker on its own
+    flame_material: Handle<StandardMaterial>,
+}
+
+#[derive(Resource)]
+struct CampfireAssets {
+    log_mesh: Handle<Mesh>,
+    stone_mesh: Handle<Mesh>,
+    flame_mesh: Handle<Mesh>,
+    log_material: Handle<StandardMaterial>,
+    stone_material: Handle<StandardMaterial>,
+}
+
+fn setup_campfire_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(CampfireAssets {
+        log_mesh: meshes.add(Cylinder::new(0.08, 0.9)),
+        stone_mesh: meshes.add(Sphere::new(0.12).mesh().ico(1).unwrap()),
+        flame_mesh: meshes.add(Cone { radius: 0.25, height: FLAME_HEIGHT }),
+        log_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.3, 0.2, 0.12),
+            perceptual_roughness: 0.95,
+            ..default()
+        }),
+        stone_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.45, 0.45, 0.45),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+    });
+}
+
+fn prompt(lit: bool) -> String {
+    if lit { "Rest at campfire (save, sleep at night)" } else { "Light campfire (hold wood)" }.to_string()
+}
+
+fn spawn_campfire(
+    commands: &mut Commands,
+    assets: &CampfireAssets,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+    rotation: Quat,
+    lit: bool,
+) -> Entity {
+    let flame_material = materials.add(StandardMaterial {
+        base_color: Color::srgba(1.0, 0.55, 0.15, 0.85),
+        emissive: FLAME_EMISSIVE,
+        alpha_mode: AlphaMode::Add,
+        unlit: true,
+        ..default()
+    });
+    let visibility = if lit { Visibility::Inherited } else { Visibility::Hidden };
+
+    let mut flame = Entity::PLACEHOLDER;
+    let mut light = Entity::PLACEHOLDER;
+    let campfire = commands.spawn((
+        Transform::from_translation(position).with_rotation(rotation),
+        Visibility::Inherited,
+        PlacedObject { kind: PlaceableKind::Campfire },
+        Hittable { radius: PlaceableKind::Campfire.footprint() },
+        Interactable {
+            prompt: prompt(lit),
+            radius: CAMPFIRE_INTERACT_RADIUS,
+        },
+    )).with_children(|parent| {
+        // Crossed logs
+        for angle in [0.0, std::f32::consts::FRAC_PI_3, -std::f32::consts::FRAC_PI_3] {
+            parent.spawn((
+                Mesh3d(assets.log_mesh.clone()),
+                MeshMaterial3d(assets.log_material.clone()),
+                Transform::from_xyz(0.0, 0.1, 0.0)
+                    .with_rotation(Quat::from_rotation_y(angle) * Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)),
+            ));
+        }
+        // Ring of stones
+        for i in 0..8 {
+            let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+            parent.spawn((
+                Mesh3d(assets.stone_mesh.clone()),
+                MeshMaterial3d(assets.stone_material.clone()),
+                Transform::from_xyz(angle.cos() * 0.6, 0.05, angle.sin() * 0.6),
+            ));
+        }
+        flame = parent.spawn((
+            Mesh3d(assets.flame_mesh.clone()),
+            MeshMaterial3d(flame_material.clone()),
+            Transform::from_xyz(0.0, 0.15 + FLAME_HEIGHT / 2.0, 0.0),
+            visibility,
+        )).id();
+        light = parent.spawn((
+            PointLight {
+                color: Color::srgb(1.0, 0.6, 0.3),
+                intensity: 0.0,
+                range: FIRE_LIGHT_RANGE,
+                shadows_enabled: true,
+                ..default()
+            },
+            Transform::from_xyz(0.0, 0.8, 0.0),
+        )).id();
+    }).id();
+
+    commands.entity(campfire).insert(Campfire { lit, flame, light, flame_material });
+    campfire
+}
+
+fn spawn_saved_campfires(
+    mut commands: Commands,
+    assets: Res<CampfireAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    save: Res<LoadedSave>,
+) {
+    for campfire in &save.0.campfires {
+        spawn_campfire(&mut commands, &assets, &mut materials, campfire.position, campfire.rotation, campfire.lit);
+    }
+}
+
+fn spawn_placed_campfires(
+    mut commands: Commands,
+    mut placed: EventReader<ObjectPlaced>,
+    assets: Res<CampfireAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for event in placed.read() {
+        if event.kind == PlaceableKind::Campfire {
+            spawn_campfire(&mut commands, &assets, &mut materials, event.position, event.rotation, false);
+        }
+    }
+}
+
+// Lighting a campfire (or resting at a lit one) sets the respawn point and autosaves.
+// Lighting burns the wood held in hand, a held fish gets cooked on a lit fire.
+#[allow(clippy::too_many_arguments)]
+fn use_campfires(
+    mut interactions: EventReader<InteractEvent>,
+    mut campfire_query: Query<(&mut Campfire, &mut Interactable, &Transform)>,
+    mut visibility_query: Query<&mut Visibility>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut save_requests: EventWriter<SaveRequested>,
+    hotbar: Res<Hotbar>,
+    mut inventory: ResMut<Inventory>,
+    creative: Res<CreativeMode>,
+) {
+    for event in interactions.read() {
+        let Ok((mut campfire, mut interactable, transform)) = campfire_query.get_mut(event.target) else {
+            continue;
+        };
+
+        if campfire.lit && hotbar.is_holding(&inventory, "fish") && inventory.take_all(&[("fish", 1)]) {
+            *inventory.items.entry("cooked_fish".to_string()).or_insert(0) += 1;
+            info!("Cooked a fish");
+        }
+
+        if !campfire.lit {
+            let has_kindling = creative.free_building()
+                || (hotbar.is_holding(&inventory, KINDLING_ITEM) && inventory.take_all(&[(KINDLING_ITEM, 1)]));
+            if !has_kindling {
+                info!("Hold {} to light the campfire", KINDLING_ITEM);
+                continue;
+            }
+            campfire.lit = true;
+            interactable.prompt = prompt(true);
+            if let Ok(mut visibility) = visibility_query.get_mut(campfire.flame) {
+                *visibility = Visibility::Inherited;
+            }
+            info!("Campfire lit");
+        }
+
+        respawn_point.0 = Some(transform.translation);
+        save_requests.send(SaveRequested { reason: "campfire" });
+    }
+}
+
+// Flicker the flame glow, size and light of lit campfires
+fn animate_campfires(
+    time: Res<Time>,
+    campfire_query: Query<(Entity, &Campfire)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut light_query: Query<&mut PointLight>,
+    mut flame_query: Query<&mut Transform, Without<Campfire>>,
+) {
+    let t = time.elapsed_secs();
+    for (entity, campfire) in campfire_query.iter() {
+        // Offset per fire so they don't flicker in sync
+        let phase = entity.index() as f32 * 1.7;
+        let flicker = if campfire.lit {
+            0.8 + 0.12 * (t * 9.0 + phase).sin() + 0.08 * (t * 17.3 + phase * 2.0).sin()
+        } else {
+            0.0
+        };
+
+        if let Ok(mut light) = light_query.get_mut(campfire.light) {
+            light.intensity = FIRE_LIGHT_INTENSITY * flicker;
+        }
+        if !campfire.lit {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(&campfire.flame_material) {
+            material.emissive = FLAME_EMISSIVE * flicker;
+        }
+        if let Ok(mut transform) = flame_query.get_mut(campfire.flame) {
+            transform.scale = Vec3::new(1.0, 0.8 + flicker * 0.4, 1.0);
+        }
+    }
+}
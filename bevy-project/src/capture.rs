@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{save_to_disk, Screenshot, ScreenshotCaptured};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{imageops, Delay, Frame, RgbaImage};
+use crate::settings::GameSettings;
+
+#[derive(Default, Clone, Debug)]
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ClipRecorder>()
+            .add_systems(Update, (
+                take_screenshot,
+                toggle_clip_recording,
+                record_clip_frames,
+                save_clip,
+            ));
+    }
+}
+
+// Rolling buffer of the last few seconds, kept while clip recording is on
+#[derive(Resource, Default)]
+pub struct ClipRecorder {
+    pub recording: bool,
+    pub frames: VecDeque<RgbaImage>,
+    timer: Timer,
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn output_path(output_dir: &str, prefix: &str, extension: &str) -> Option<PathBuf> {
+    let dir = Path::new(output_dir);
+    if let Err(err) = fs::create_dir_all(dir) {
+        warn!("Could not create capture directory {}: {}", dir.display(), err);
+        return None;
+    }
+    Some(dir.join(format!("{}-{}.{}", prefix, timestamp(), extension)))
+}
+
+fn take_screenshot(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+) {
+    if !input.just_pressed(KeyCode::F12) {
+        return;
+    }
+    if let Some(path) = output_path(&settings.capture.output_dir, "screenshot", "png") {
+        info!("Saving screenshot to {}", path.display());
+        commands
+            .spawn(Screenshot::primary_window())
+            .observe(save_to_disk(path));
+    }
+}
+
+// F9 starts/stops filling the clip ring buffer
+fn toggle_clip_recording(
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    mut recorder: ResMut<ClipRecorder>,
+) {
+    if !input.just_pressed(KeyCode::F9) {
+        return;
+    }
+    recorder.recording = !recorder.recording;
+    recorder.frames.clear();
+    let interval = 1.0 / settings.capture.clip_fps.max(1) as f32;
+    recorder.timer = Timer::from_seconds(interval, TimerMode::Repeating);
+    info!("Clip recording: {}", recorder.recording);
+}
+
+fn record_clip_frames(
+    mut commands: Commands,
+    mut recorder: ResMut<ClipRecorder>,
+    time: Res<Time>,
+) {
+    if !recorder.recording {
+        return;
+    }
+    if recorder.timer.tick(time.delta()).just_finished() {
+        commands
+            .spawn(Screenshot::primary_window())
+            .observe(push_clip_frame);
+    }
+}
+
+fn push_clip_frame(
+    trigger: Trigger<ScreenshotCaptured>,
+    mut recorder: ResMut<ClipRecorder>,
+    settings: Res<GameSettings>,
+) {
+    if !recorder.recording {
+        return;
+    }
+    let Ok(image) = trigger.event().0.clone().try_into_dynamic() else {
+        return;
+    };
+
+    let capture = &settings.capture;
+    let mut frame = image.to_rgba8();
+    if frame.width() > capture.clip_max_width {
+        let height = frame.height() * capture.clip_max_width / frame.width();
+        frame = imageops::resize(&frame, capture.clip_max_width, height, imageops::FilterType::Triangle);
+    }
+
+    let max_frames = (capture.clip_seconds * capture.clip_fps as f32).ceil() as usize;
+    recorder.frames.push_back(frame);
+    while recorder.frames.len() > max_frames.max(1) {
+        recorder.frames.pop_front();
+    }
+}
+
+// F10 writes the buffered frames as an animated GIF on a background thread
+fn save_clip(
+    input: Res<ButtonInput<KeyCode>>,
+    settings: Res<GameSettings>,
+    recorder: Res<ClipRecorder>,
+) {
+    if !input.just_pressed(KeyCode::F10) {
+        return;
+    }
+    if recorder.frames.is_empty() {
+        warn!("No clip frames buffered, press F9 to start recording");
+        return;
+    }
+    let Some(path) = output_path(&settings.capture.output_dir, "clip", "gif") else {
+        return;
+    };
+
+    let frames: Vec<RgbaImage> = recorder.frames.iter().cloned().collect();
+    let delay = Delay::from_saturating_duration(Duration::from_secs_f32(1.0 / settings.capture.clip_fps.max(1) as f32));
+    info!("Saving {} frame clip to {}", frames.len(), path.display());
+
+    std::thread::spawn(move || {
+        let result = File::create(&path)
+            .map_err(image::ImageError::IoError)
+            .and_then(|file| {
+                let mut encoder = GifEncoder::new(file);
+                encoder.set_repeat(Repeat::Infinite)?;
+                encoder.encode_frames(frames.into_iter().map(|frame| Frame::from_parts(frame, 0, 0, delay)))
+            });
+        match result {
+            Ok(()) => info!("Clip saved to {}", path.display()),
+            Err(err) => warn!("Could not save clip {}: {}", path.display(), err),
+        }
+    });
+}
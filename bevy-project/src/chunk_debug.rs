@@ -0,0 +1,324 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy_egui::EguiContexts;
+use crate::client::{ChunkBounds, ChunkManager, TerrainChunk, WorldPosition, TERRAIN_SUBDIVISIONS};
+use crate::terrain;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+
+// Small lift so lines don't z-fight with the terrain they follow
+const LINE_OFFSET: f32 = 0.05;
+const BORDER_SAMPLES: u32 = 25;
+const POST_HEIGHT: f32 = 30.0;
+// Chunks around the camera whose shared edges are checked for seams
+const SEAM_RADIUS: i32 = 2;
+// Height of the marker column above the higher of the two edges
+const SEAM_COLUMN_HEIGHT: f32 = 1.5;
+// Edge heights closer than this count as matching
+const SEAM_TOLERANCE: f32 = 0.001;
+// Gap drawn fully red
+const SEAM_BAD_GAP: f32 = 0.25;
+const DEBUG_WINDOW: &str = "debug";
+
+#[derive(Default, Clone, Debug)]
+pub struct ChunkDebugPlugin;
+
+impl Plugin for ChunkDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ChunkDebugSettings>()
+            .init_gizmo_group::<ChunkDebugGizmos>()
+            .register_ui_window(UiWindow {
+                id: DEBUG_WINDOW,
+                title: "Debug",
+                shortcut: None,
+                open: false,
+            })
+            .add_systems(Startup, configure_chunk_gizmos)
+            .add_systems(Update, (
+                toggle_chunk_debug,
+                chunk_debug_ui,
+                draw_chunk_borders,
+                draw_vertex_grid,
+                draw_chunk_culling_bounds,
+                draw_edge_seams,
+            ).chain());
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ChunkDebugSettings {
+    pub show_borders: bool,
+    pub show_grid: bool,
+    pub show_bounds: bool,
+    pub show_seams: bool,
+    pub show_navigation: bool,
+    pub show_network: bool,
+}
+
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct ChunkDebugGizmos;
+
+fn configure_chunk_gizmos(mut config_store: ResMut<GizmoConfigStore>) {
+    let (config, _) = config_store.config_mut::<ChunkDebugGizmos>();
+    config.line_width = 2.0;
+    config.depth_bias = -0.1;
+}
+
+fn toggle_chunk_debug(
+    mut settings: ResMut<ChunkDebugSettings>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if input.just_pressed(KeyCode::F3) {
+        settings.show_borders = !settings.show_borders;
+        info!("Chunk borders: {}", settings.show_borders);
+    }
+    if input.just_pressed(KeyCode::F4) {
+        settings.show_grid = !settings.show_grid;
+        info!("Chunk vertex grid: {}", settings.show_grid);
+    }
+    if input.just_pressed(KeyCode::F8) {
+        settings.show_bounds = !settings.show_bounds;
+        info!("Chunk culling bounds: {}", settings.show_bounds);
+    }
+    if input.just_pressed(KeyCode::F5) {
+        settings.show_seams = !settings.show_seams;
+        info!("Chunk edge seams: {}", settings.show_seams);
+    }
+    if input.just_pressed(KeyCode::F2) {
+        settings.show_network = !settings.show_network;
+        info!("Network labels: {}", settings.show_network);
+    }
+    if input.just_pressed(KeyCode::F11) {
+        settings.show_navigation = !settings.show_navigation;
+        info!("Navigation grid: {}", settings.show_navigation);
+    }
+}
+
+// Same toggles as the function keys, for finding them without knowing the keys
+fn chunk_debug_ui(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut settings: ResMut<ChunkDebugSettings>,
+    chunk_manager: Res<ChunkManager>,
+) {
+    let Some(window) = windows.window(DEBUG_WINDOW) else {
+        return;
+    };
+    window.show(contexts.ctx_mut(), |ui| {
+        ui.checkbox(&mut settings.show_borders, "Chunk borders [F3]");
+        ui.checkbox(&mut settings.show_grid, "Vertex grid [F4]");
+        ui.checkbox(&mut settings.show_seams, "Edge seams [F5]");
+        ui.checkbox(&mut settings.show_bounds, "Culling bounds [F8]");
+        ui.checkbox(&mut settings.show_navigation, "Navigation grid [F11]");
+        ui.checkbox(&mut settings.show_network, "Network labels [F2]");
+        ui.separator();
+        let (hits, misses, points) = terrain::height_cache_stats();
+        let rate = hits as f32 / (hits + misses).max(1) as f32 * 100.0;
+        ui.label(format!("Height cache: {:.1}% hits, {} points", rate, points));
+        ui.label(format!(
+            "Chunks: {} loaded, {} generating, {} unloading",
+            chunk_manager.loaded_chunks.len(),
+            chunk_manager.pending_chunks.len(),
+            chunk_manager.unloading_chunks.len(),
+        ));
+    });
+}
+
+// World-space bounds (min corner, max corner) of a chunk's mesh, which is centered on its offset
+fn chunk_bounds(chunk_x: i32, chunk_z: i32, chunk_size: f32) -> (Vec2, Vec2) {
+    let center = Vec2::new(chunk_x as f32, chunk_z as f32) * chunk_size;
+    let half = Vec2::splat(chunk_size / 2.0);
+    (center - half, center + half)
+}
+
+fn surface_point(x: f32, z: f32) -> Vec3 {
+    Vec3::new(x, terrain::cached_height(x, z) + LINE_OFFSET, z)
+}
+
+// Polyline hugging the terrain between two points on the ground
+fn surface_line(from: Vec2, to: Vec2, samples: u32) -> impl Iterator<Item = Vec3> {
+    (0..=samples).map(move |i| {
+        let point = from.lerp(to, i as f32 / samples as f32);
+        surface_point(point.x, point.y)
+    })
+}
+
+fn draw_chunk_borders(
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    settings: Res<ChunkDebugSettings>,
+    chunk_manager: Res<ChunkManager>,
+    world_pos: Res<WorldPosition>,
+) {
+    if !settings.show_borders {
+        return;
+    }
+
+    let chunk_size = chunk_manager.chunk_size;
+    for &(chunk_x, chunk_z) in chunk_manager.loaded_chunks.keys() {
+        let current = chunk_x == world_pos.chunk_x && chunk_z == world_pos.chunk_z;
+        let color = if current {
+            Color::srgb(1.0, 0.2, 0.2)
+        } else if chunk_manager.unloading_chunks.contains_key(&(chunk_x, chunk_z)) {
+            Color::srgb(0.5, 0.5, 0.5)
+        } else {
+            Color::srgb(1.0, 1.0, 0.0)
+        };
+
+        let (min, max) = chunk_bounds(chunk_x, chunk_z, chunk_size);
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+
+        for i in 0..corners.len() {
+            let from = corners[i];
+            let to = corners[(i + 1) % corners.len()];
+            gizmos.linestrip(surface_line(from, to, BORDER_SAMPLES), color);
+        }
+
+        if current {
+            for corner in corners {
+                let base = surface_point(corner.x, corner.y);
+                gizmos.line(base, base + Vec3::Y * POST_HEIGHT, color);
+            }
+        }
+    }
+}
+
+// Overlay the mesh vertex grid of the chunk the camera is in
+fn draw_vertex_grid(
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    settings: Res<ChunkDebugSettings>,
+    chunk_manager: Res<ChunkManager>,
+    world_pos: Res<WorldPosition>,
+) {
+    if !settings.show_grid {
+        return;
+    }
+
+    let color = Color::srgba(0.2, 0.8, 1.0, 0.6);
+    let (min, max) = chunk_bounds(world_pos.chunk_x, world_pos.chunk_z, chunk_manager.chunk_size);
+    let step = (max - min) / TERRAIN_SUBDIVISIONS as f32;
+
+    for i in 0..=TERRAIN_SUBDIVISIONS {
+        let offset = i as f32;
+        let x = min.x + step.x * offset;
+        let z = min.y + step.y * offset;
+        gizmos.linestrip(surface_line(Vec2::new(x, min.y), Vec2::new(x, max.y), TERRAIN_SUBDIVISIONS), color);
+        gizmos.linestrip(surface_line(Vec2::new(min.x, z), Vec2::new(max.x, z), TERRAIN_SUBDIVISIONS), color);
+    }
+}
+
+// Boxes used for frustum culling of each terrain chunk
+fn draw_chunk_culling_bounds(
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    settings: Res<ChunkDebugSettings>,
+    chunk_query: Query<(&ChunkBounds, &GlobalTransform), With<TerrainChunk>>,
+) {
+    if !settings.show_bounds {
+        return;
+    }
+
+    let color = Color::srgb(1.0, 0.5, 0.0);
+    for (bounds, transform) in chunk_query.iter() {
+        let aabb = bounds.aabb();
+        let center = transform.transform_point(Vec3::from(aabb.center));
+        let size = Vec3::from(aabb.half_extents) * 2.0;
+        gizmos.cuboid(Transform::from_translation(center).with_scale(size), color);
+    }
+}
+
+// Heights of a chunk mesh's grid as it was built, without the skirts
+fn mesh_grid_heights(mesh: &Mesh, side: usize) -> Option<Vec<f32>> {
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) if positions.len() >= side * side => {
+            Some(positions[..side * side].iter().map(|position| position[1]).collect())
+        }
+        _ => None,
+    }
+}
+
+// Height along a polyline of evenly spaced edge vertices, `t` in 0..1, the way the mesh
+// triangles interpolate it
+fn edge_height(edge: &[f32], t: f32) -> f32 {
+    let position = t * (edge.len() - 1) as f32;
+    let i = (position.floor() as usize).min(edge.len() - 2);
+    edge[i].lerp(edge[i + 1], position - i as f32)
+}
+
+// Columns along every edge shared by two loaded chunks near the camera, sampled at full
+// resolution from both meshes: green where both agree, through yellow to red as the gap
+// grows. Mismatching edges also get a tick at each chunk's height.
+fn draw_edge_seams(
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    settings: Res<ChunkDebugSettings>,
+    chunk_manager: Res<ChunkManager>,
+    world_pos: Res<WorldPosition>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_query: Query<(&Mesh3d, &ChunkBounds), With<TerrainChunk>>,
+) {
+    if !settings.show_seams {
+        return;
+    }
+
+    // Grid heights and side of each loaded chunk near the camera
+    let grid = |coords: (i32, i32)| {
+        let (entity, _) = chunk_manager.loaded_chunks.get(&coords)?;
+        let (mesh, bounds) = mesh_query.get(*entity).ok()?;
+        let side = bounds.cells as usize + 1;
+        let mesh = meshes.get(&mesh.0)?;
+        Some((mesh_grid_heights(mesh, side)?, side))
+    };
+
+    let chunk_size = chunk_manager.chunk_size;
+    for dz in -SEAM_RADIUS..=SEAM_RADIUS {
+        for dx in -SEAM_RADIUS..=SEAM_RADIUS {
+            let coords = (world_pos.chunk_x + dx, world_pos.chunk_z + dz);
+            let Some((heights, side)) = grid(coords) else {
+                continue;
+            };
+            let (min, max) = chunk_bounds(coords.0, coords.1, chunk_size);
+
+            // East edge against the west edge of the next chunk, north edge against the south edge
+            for (neighbor, east) in [((coords.0 + 1, coords.1), true), ((coords.0, coords.1 + 1), false)] {
+                let Some((neighbor_heights, neighbor_side)) = grid(neighbor) else {
+                    continue;
+                };
+                let (edge, neighbor_edge): (Vec<f32>, Vec<f32>) = if east {
+                    (
+                        (0..side).map(|z| heights[z * side + side - 1]).collect(),
+                        (0..neighbor_side).map(|z| neighbor_heights[z * neighbor_side]).collect(),
+                    )
+                } else {
+                    (
+                        heights[(side - 1) * side..].to_vec(),
+                        neighbor_heights[..neighbor_side].to_vec(),
+                    )
+                };
+
+                for i in 0..=TERRAIN_SUBDIVISIONS {
+                    let t = i as f32 / TERRAIN_SUBDIVISIONS as f32;
+                    let point = if east {
+                        Vec2::new(max.x, min.y.lerp(max.y, t))
+                    } else {
+                        Vec2::new(min.x.lerp(max.x, t), max.y)
+                    };
+                    let (a, b) = (edge_height(&edge, t), edge_height(&neighbor_edge, t));
+                    let gap = (a - b).abs();
+                    let color = if gap <= SEAM_TOLERANCE {
+                        Color::srgb(0.2, 1.0, 0.3)
+                    } else {
+                        let severity = (gap / SEAM_BAD_GAP).min(1.0);
+                        Color::srgb(1.0, 1.0 - severity, 0.0)
+                    };
+                    let base = Vec3::new(point.x, a.min(b), point.y);
+                    gizmos.line(base, base.with_y(a.max(b) + SEAM_COLUMN_HEIGHT), color);
+                    if gap > SEAM_TOLERANCE {
+                        let across = if east { Vec3::X } else { Vec3::Z } * 0.3;
+                        for height in [a, b] {
+                            let tick = base.with_y(height);
+                            gizmos.line(tick - across, tick + across, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::fs;
+use std::path::Path;
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use serde::{Deserialize, Serialize};
+use crate::client::build_terrain_mesh;
+use crate::palette::TerrainPalette;
+use crate::server::{generate_server_chunk, ServerWorld};
+use crate::streaming::ChunkCoords;
+use crate::terrain_edit::TerrainEdits;
+
+const GOLDEN_PATH: &str = "golden/chunks.ron";
+// Chunks kept as golden snapshots, at the coarsest LOD to keep the file small
+const GOLDEN_CHUNKS: [ChunkCoords; 3] = [(0, 0), (3, -2), (-7, 5)];
+const GOLDEN_LOD: u32 = 2;
+// Chunks generated twice when verifying, around the spawn and far from it
+const VERIFY_CHUNKS: [ChunkCoords; 5] = [(0, 0), (1, 0), (-1, -1), (12, -30), (-250, 180)];
+
+// Heights and colors of a chunk's terrain grid, without the skirts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkSnapshot {
+    pub coords: ChunkCoords,
+    pub lod: u32,
+    pub heights: Vec<f32>,
+    pub colors: Vec<[f32; 4]>,
+}
+
+// First place two snapshots of the same chunk disagree
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkMismatch {
+    Size { expected: usize, actual: usize },
+    Height { index: usize, expected: f32, actual: f32 },
+    Color { index: usize, expected: [f32; 4], actual: [f32; 4] },
+}
+
+impl std::fmt::Display for ChunkMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChunkMismatch::Size { expected, actual } => write!(f, "{} vertices instead of {}", actual, expected),
+            ChunkMismatch::Height { index, expected, actual } => write!(f, "height {} is {} instead of {}", index, actual, expected),
+            ChunkMismatch::Color { index, expected, actual } => write!(f, "color {} is {:?} instead of {:?}", index, actual, expected),
+        }
+    }
+}
+
+impl ChunkSnapshot {
+    // Client path: the render mesh
+    pub fn from_mesh(coords: ChunkCoords, lod: u32, edits: &TerrainEdits, palette: &TerrainPalette) -> Self {
+        let (mesh, bounds) = build_terrain_mesh(coords.0, coords.1, lod, edits, palette);
+        let side = bounds.cells + 1;
+        let grid = (side * side) as usize;
+        let heights = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions[..grid].iter().map(|position| position[1]).collect(),
+            _ => Vec::new(),
+        };
+        let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => colors[..grid].to_vec(),
+            _ => Vec::new(),
+        };
+        Self { coords, lod, heights, colors }
+    }
+
+    // Vertices along each side of the height grid
+    #[cfg(test)]
+    pub fn side(&self) -> usize {
+        (self.heights.len() as f64).sqrt() as usize
+    }
+
+    // Compared bit for bit, so -0.0 and 0.0 or two NaNs count as different
+    pub fn first_difference(&self, other: &Self) -> Option<ChunkMismatch> {
+        if self.heights.len() != other.heights.len() || self.colors.len() != other.colors.len() {
+            return Some(ChunkMismatch::Size { expected: self.heights.len(), actual: other.heights.len() });
+        }
+        if let Some(index) = first_different_bits(&self.heights, &other.heights) {
+            return Some(ChunkMismatch::Height { index, expected: self.heights[index], actual: other.heights[index] });
+        }
+        let flatten = |colors: &[[f32; 4]]| colors.iter().flatten().copied().collect::<Vec<f32>>();
+        first_different_bits(&flatten(&self.colors), &flatten(&other.colors))
+            .map(|index| index / 4)
+            .map(|index| ChunkMismatch::Color { index, expected: self.colors[index], actual: other.colors[index] })
+    }
+}
+
+fn first_different_bits(expected: &[f32], actual: &[f32]) -> Option<usize> {
+    expected.iter().zip(actual).position(|(a, b)| a.to_bits() != b.to_bits())
+}
+
+// Server path: the collision heightfield, which must match the full resolution mesh
+pub fn server_heights(coords: ChunkCoords, server_world: &ServerWorld) -> Vec<f32> {
+    generate_server_chunk(server_world, coords).collider.heights
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GoldenChunks {
+    pub chunks: Vec<ChunkSnapshot>,
+}
+
+impl GoldenChunks {
+    pub fn generate() -> Self {
+        let (edits, palette) = (TerrainEdits::default(), TerrainPalette::default());
+        Self {
+            chunks: GOLDEN_CHUNKS.iter()
+                .map(|coords| ChunkSnapshot::from_mesh(*coords, GOLDEN_LOD, &edits, &palette))
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        ron::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+// Every way chunk generation disagrees with itself or with the golden snapshots
+pub fn verify(golden: &GoldenChunks) -> Vec<String> {
+    let mut failures = Vec::new();
+    let (edits, palette) = (TerrainEdits::default(), TerrainPalette::default());
+    let server_world = ServerWorld::default();
+
+    for coords in VERIFY_CHUNKS {
+        // Same chunk twice, once on another thread as background generation would
+        let first = ChunkSnapshot::from_mesh(coords, 0, &edits, &palette);
+        let second = std::thread::spawn(move || {
+            ChunkSnapshot::from_mesh(coords, 0, &TerrainEdits::default(), &TerrainPalette::default())
+        }).join();
+        match second {
+            Ok(second) => {
+                if let Some(mismatch) = first.first_difference(&second) {
+                    failures.push(format!("chunk {:?} differs between threads: {}", coords, mismatch));
+                }
+            }
+            Err(_) => failures.push(format!("chunk {:?} panicked on the worker thread", coords)),
+        }
+
+        let server = ChunkSnapshot { heights: server_heights(coords, &server_world), colors: first.colors.clone(), ..first.clone() };
+        if let Some(mismatch) = first.first_difference(&server) {
+            failures.push(format!("chunk {:?} differs between client and server: {}", coords, mismatch));
+        }
+    }
+
+    for expected in &golden.chunks {
+        let actual = ChunkSnapshot::from_mesh(expected.coords, expected.lod, &edits, &palette);
+        if let Some(mismatch) = expected.first_difference(&actual) {
+            failures.push(format!("chunk {:?} differs from its golden snapshot: {}", expected.coords, mismatch));
+        }
+    }
+    failures
+}
+
+// `verify-chunks` mode: checks world generation is deterministic and unchanged.
+// With `update_golden` the snapshots are regenerated instead, after an intended change.
+pub fn run(update_golden: bool) -> bool {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_PATH);
+    if update_golden {
+        return match GoldenChunks::generate().write(&path) {
+            Ok(()) => {
+                println!("Wrote golden chunks to {}", path.display());
+                true
+            }
+            Err(err) => {
+                println!("Could not write {}: {}", path.display(), err);
+                false
+            }
+        };
+    }
+
+    let golden = match GoldenChunks::load(&path) {
+        Ok(golden) => golden,
+        Err(err) => {
+            println!("Could not read {}: {}", path.display(), err);
+            return false;
+        }
+    };
+    let failures = verify(&golden);
+    for failure in &failures {
+        println!("FAIL {}", failure);
+    }
+    if failures.is_empty() {
+        println!("All chunks match ({} golden)", golden.chunks.len());
+    }
+    failures.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+    use crate::client::{chunk_subdivisions, lod_subdivisions, MAX_TERRAIN_LOD};
+
+    #[test]
+    fn generation_is_deterministic() {
+        let golden = GoldenChunks::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_PATH))
+            .expect("golden chunks, regenerate with `verify-chunks --update-golden`");
+        let failures = verify(&golden);
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    // Edge row or column of a chunk's height grid, from the low to the high coordinate
+    fn edge(snapshot: &ChunkSnapshot, side: Edge) -> Vec<f32> {
+        let size = snapshot.side();
+        (0..size)
+            .map(|i| match side {
+                Edge::MinX => snapshot.heights[i * size],
+                Edge::MaxX => snapshot.heights[i * size + size - 1],
+                Edge::MinZ => snapshot.heights[i],
+                Edge::MaxZ => snapshot.heights[(size - 1) * size + i],
+            })
+            .collect()
+    }
+
+    #[derive(Clone, Copy)]
+    enum Edge {
+        MinX,
+        MaxX,
+        MinZ,
+        MaxZ,
+    }
+
+    // Vertices of the coarser edge lie on the finer one, the skirts hide the rest.
+    // Grids that don't nest would leave cracks only the skirts cover, so they fail too.
+    fn assert_shared_vertices(a: &[f32], b: &[f32], seam: &str) {
+        let (fine, coarse) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+        assert!(
+            (fine.len() - 1).is_multiple_of(coarse.len() - 1),
+            "{} and {} cells don't nest along the {}", fine.len() - 1, coarse.len() - 1, seam,
+        );
+        let ratio = (fine.len() - 1) / (coarse.len() - 1);
+        for (i, height) in coarse.iter().enumerate() {
+            assert_eq!(fine[i * ratio], *height, "vertex {} of the {}", i, seam);
+        }
+    }
+
+    // Neighbours may pick different grids for their roughness, shared vertices still match
+    #[test]
+    fn neighbouring_chunks_share_their_edges() {
+        let palette = TerrainPalette::default();
+        let edits = TerrainEdits::default();
+        for &(x, z) in &VERIFY_CHUNKS {
+            for lod in 0..=MAX_TERRAIN_LOD {
+                let chunk = ChunkSnapshot::from_mesh((x, z), lod, &edits, &palette);
+                let right = ChunkSnapshot::from_mesh((x + 1, z), lod, &edits, &palette);
+                let below = ChunkSnapshot::from_mesh((x, z + 1), lod, &edits, &palette);
+                let seam = |axis: &str| format!("seam between {:?} and its {} neighbour at lod {}", (x, z), axis, lod);
+                assert_shared_vertices(&edge(&chunk, Edge::MaxX), &edge(&right, Edge::MinX), &seam("+x"));
+                assert_shared_vertices(&edge(&chunk, Edge::MaxZ), &edge(&below, Edge::MinZ), &seam("+z"));
+            }
+        }
+    }
+
+    #[test]
+    fn lod_borders_share_the_coarse_vertices() {
+        let palette = TerrainPalette::default();
+        let edits = TerrainEdits::default();
+        for &(x, z) in &VERIFY_CHUNKS {
+            for lod in 0..MAX_TERRAIN_LOD {
+                let fine = edge(&ChunkSnapshot::from_mesh((x, z), lod, &edits, &palette), Edge::MaxX);
+                let coarse = edge(&ChunkSnapshot::from_mesh((x + 1, z), lod + 1, &edits, &palette), Edge::MinX);
+                let seam = format!("border of {:?} between lod {} and {}", (x, z), lod, lod + 1);
+                assert_shared_vertices(&fine, &coarse, &seam);
+            }
+        }
+    }
+
+    proptest! {
+        // Meshing is slow in debug builds, a few dozen seams per run is plenty
+        #![proptest_config(ProptestConfig::with_cases(24))]
+
+        // Any two neighbours, at the same LOD or one apart, agree on their shared vertices
+        #[test]
+        fn mesh_edges_meet_between_any_neighbours(
+            x in -5000i32..5000,
+            z in -5000i32..5000,
+            lod in 0..=MAX_TERRAIN_LOD,
+            coarser: bool,
+            across_x: bool,
+        ) {
+            let (palette, edits) = (TerrainPalette::default(), TerrainEdits::default());
+            let neighbour_lod = if coarser { (lod + 1).min(MAX_TERRAIN_LOD) } else { lod };
+            let chunk = ChunkSnapshot::from_mesh((x, z), lod, &edits, &palette);
+            let (neighbour, own_edge, neighbour_edge) = if across_x {
+                ((x + 1, z), Edge::MaxX, Edge::MinX)
+            } else {
+                ((x, z + 1), Edge::MaxZ, Edge::MinZ)
+            };
+            let other = ChunkSnapshot::from_mesh(neighbour, neighbour_lod, &edits, &palette);
+            let seam = format!("seam between {:?} at lod {} and {:?} at lod {}", (x, z), lod, neighbour, neighbour_lod);
+            assert_shared_vertices(&edge(&chunk, own_edge), &edge(&other, neighbour_edge), &seam);
+        }
+    }
+
+    // Smooth ground gets a coarser grid than jagged ground, at every LOD
+    #[test]
+    fn rougher_chunks_get_finer_grids() {
+        for lod in 0..=MAX_TERRAIN_LOD {
+            let flat = chunk_subdivisions(0.0, lod);
+            let rough = chunk_subdivisions(10.0, lod);
+            assert!(flat < lod_subdivisions(lod) && lod_subdivisions(lod) < rough, "lod {}: {} and {} cells", lod, flat, rough);
+        }
+    }
+
+    #[test]
+    fn edits_change_the_snapshot() {
+        let palette = TerrainPalette::default();
+        let mut edits = TerrainEdits::default();
+        let before = ChunkSnapshot::from_mesh((0, 0), 0, &edits, &palette);
+        edits.apply_remote(crate::terrain_edit::TerrainEdit { center: Vec2::ZERO, radius: 4.0, delta: 1.0 });
+        let after = ChunkSnapshot::from_mesh((0, 0), 0, &edits, &palette);
+        assert!(matches!(before.first_difference(&after), Some(ChunkMismatch::Height { .. })));
+    }
+}
@@ -1,14 +1,106 @@
 use bevy::prelude::*;
 use bevy::pbr::wireframe::WireframePlugin;
 use bevy_atmosphere::prelude::*;
-use bevy::render::mesh::VertexAttributeValues;
-use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::render::primitives::Aabb;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::RenderPlugin;
+use bevy_egui::{EguiContexts, EguiPlugin};
 use crate::player::PlayerPlugin;
-use crate::camera::{CameraPlugin, CameraSettings, CameraMode};
+use crate::camera::{CameraPlugin, CameraSettings, CameraMode, FreeCamera};
 use crate::ground::{Ground, toggle_wireframe};
-use crate::water::{WaterPlugin, WaterMaterial, Water};
-use noise::{BasicMulti, MultiFractal, NoiseFn, Perlin};
+use crate::water::{sea_level, water_lod, WaterPlugin, WaterMaterials, WaterMeshes, WaterLod, Water};
+use crate::terrain;
+use crate::biome::{self, Biome};
+use crate::road::paint_roads;
+use crate::quest::QuestPlugin;
+use crate::chunk_debug::ChunkDebugPlugin;
+use crate::pool::{EntityPool, EntityPoolPlugin};
+use bevy::ecs::system::EntityCommands;
+use crate::terrain_edit::{TerrainEdits, TerrainEditPlugin};
+use crate::server::LocalServerPlugin;
+use crate::net::{NetClientPlugin, ServerConnection};
+use crate::transport::{UdpConnector, UdpTransport};
+use crate::diagnostics::{self, GameDiagnosticsPlugin, CHUNK_GENERATION_MS};
+use bevy::diagnostic::Diagnostics;
+use bevy::log::LogPlugin;
+use crate::settings::{AvailableBackends, GameSettings, GpuBackend, SettingsPlugin};
+use crate::capture::CapturePlugin;
+use crate::interaction::InteractionPlugin;
+use crate::boat::BoatPlugin;
+use crate::fish::FishPlugin;
+use crate::day_night::{DayNightPlugin, Sun};
+use crate::torch::TorchPlugin;
+use crate::display::DisplayPlugin;
+use crate::render_scale::RenderScalePlugin;
+use crate::ambient_occlusion::AmbientOcclusionPlugin;
+use crate::placement::PlacementPlugin;
+use crate::save::SavePlugin;
+use crate::campfire::CampfirePlugin;
+use crate::inventory::InventoryPlugin;
+use crate::creative::CreativePlugin;
+use crate::map::MapPlugin;
+use crate::footprints::FootprintPlugin;
+use crate::map_bridge::{MapBridgeConfig, MapBridgePlugin};
+use crate::projectile::ProjectilePlugin;
+use crate::door::DoorPlugin;
+use crate::region::RegionPlugin;
+use crate::hotbar::HotbarPlugin;
+use crate::world_events::WorldEventsPlugin;
+use crate::aurora::AuroraPlugin;
+use crate::meteors::MeteorPlugin;
+use crate::fog_bank::FogBankPlugin;
+use crate::sleep::SleepPlugin;
+use crate::camera_shake::CameraShakePlugin;
+use crate::camera_fov::CameraFovPlugin;
+use crate::population::PopulationPlugin;
+use crate::worldgen::WorldGenPlugin;
+use crate::drowning::DrowningPlugin;
+use crate::thirst::ThirstPlugin;
+use crate::dialogue::DialoguePlugin;
+use crate::vendor::VendorPlugin;
+use crate::world_border::{WorldBorder, WorldBorderPlugin};
+use crate::accessibility::AccessibilityPlugin;
+use crate::wolf::WolfPlugin;
+use crate::network_debug::NetworkDebugPlugin;
+use crate::race::RacePlugin;
+use crate::ripple::RipplePlugin;
+use crate::items::ItemsPlugin;
+use crate::reconnect::ReconnectPlugin;
+use crate::toast::{Toast, ToastKind, ToastPlugin};
+use crate::birds::BirdsPlugin;
+use crate::vegetation::VegetationPlugin;
+use crate::console::{ConsolePlugin, ConsoleWindowPlugin};
+use crate::admin::AdminPlugin;
+use crate::rain::RainPlugin;
+use crate::navigation::NavigationPlugin;
+use crate::fishing::FishingPlugin;
+use crate::avatar::AvatarPlugin;
+use crate::emote::EmotePlugin;
+use crate::building::BuildingPlugin;
+use crate::input_map::InputMapPlugin;
+use crate::music::MusicPlugin;
+use crate::impact::ImpactPlugin;
+use crate::hud::HudPlugin;
+use crate::audio_mixer::AudioMixerPlugin;
+use crate::locate::LocatePlugin;
+use crate::season::SeasonPlugin;
+use crate::reflection_probe::ReflectionProbePlugin;
+use crate::sky_light::SkyLightPlugin;
+use crate::asset_registry::{AssetRegistryPlugin, GameState};
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindowPlugin, UiWindows};
+use crate::palette::{ActiveTerrainPalette, PalettePlugin, TerrainPalette};
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle, TerrainMaterialPlugin};
+use crate::terrain_displacement::{build_displacement_maps, DisplacedTerrain, DisplacementMaps, TerrainDisplacementPlugin};
+use crate::streaming::{chunk_coords, AnchorId, ChunkChanges, ChunkCoords, ChunkStreamer, StreamingAnchor};
+use bevy::tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task};
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::heightmap::Heightmap;
 
 // Chunk system for infinite terrain
 #[derive(Resource, Default)]
@@ -22,6 +114,50 @@ pub struct ChunkManager {
     pub loaded_chunks: HashMap<(i32, i32), (Entity, Option<Entity>)>, // (terrain_entity, optional_water_entity)
     pub chunk_size: f32,
     pub render_distance: i32,
+    pub streamer: ChunkStreamer,
+    // Mesh LOD each loaded terrain chunk was built with
+    pub chunk_lods: HashMap<(i32, i32), u32>,
+    // Chunks whose meshes are being generated in the background
+    pub pending_chunks: HashMap<(i32, i32), PendingChunk>,
+    // Loaded or pending chunks no anchor needs anymore, and seconds left before they go.
+    // Coming back into range in the meantime keeps them.
+    pub unloading_chunks: HashMap<(i32, i32), f32>,
+}
+
+// Shared between a pending chunk and its generation task, which checks it between steps
+// and gives up once the chunk is no longer wanted
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct PendingChunk {
+    // Err with the panic's message if generating the chunk failed
+    task: Task<Result<Option<GeneratedChunk>, String>>,
+    cancellation: CancellationToken,
+    lod: u32,
+}
+
+// Meshes of a chunk, ready to be spawned
+struct GeneratedChunk {
+    terrain: ChunkSurface,
+    // Biome the chunk's water takes its look from, None when none of it is under water
+    water: Option<Biome>,
+    elapsed_ms: f64,
+}
+
+// A chunk's ground: a mesh built on the CPU, or maps a flat grid is displaced by on the GPU
+enum ChunkSurface {
+    Mesh(Mesh, ChunkBounds),
+    Displaced(Box<DisplacementMaps>),
 }
 
 #[derive(Component)]
@@ -30,60 +166,184 @@ pub struct TerrainChunk {
     pub chunk_z: i32,
 }
 
-const CHUNK_SIZE: f32 = 50.0;
-const RENDER_DISTANCE: i32 = 3; // 3 chunks dans chaque direction
-const WATER_LEVEL: f32 = 1.0; // Niveau de l'eau (remonté pour une meilleure visibilité)
-
-// Linear interpolation between two colors
-fn lerp_color(color1: [f32; 4], color2: [f32; 4], t: f32) -> [f32; 4] {
-    let t = t.clamp(0.0, 1.0);
-    [
-        color1[0] + (color2[0] - color1[0]) * t,
-        color1[1] + (color2[1] - color1[1]) * t,
-        color1[2] + (color2[2] - color1[2]) * t,
-        color1[3] + (color2[3] - color1[3]) * t,
-    ]
-}
-
-// Get smooth terrain color based on height (without water)
-fn get_terrain_color(height: f32) -> [f32; 4] {
-    // Define color stops (no water colors since water is separate)
-    let sand_color = [0.8, 0.7, 0.4, 1.0];     // Sandy color for beach
-    let grass_color = [0.3, 0.6, 0.2, 1.0];    // Green for grass
-    let rock_color = [0.5, 0.4, 0.3, 1.0];     // Brown for rocks
-    let snow_color = [0.9, 0.9, 0.9, 1.0];     // White for snow
-    
-    // Define height thresholds
-    let sand_level = 0.3;
-    let grass_level = 1.5;
-    let rock_level = 3.0;
-    let snow_level = 4.0;
-    
-    if height < sand_level {
-        sand_color
-    } else if height < grass_level {
-        let t = (height - sand_level) / (grass_level - sand_level);
-        lerp_color(sand_color, grass_color, t)
-    } else if height < rock_level {
-        let t = (height - grass_level) / (rock_level - grass_level);
-        lerp_color(grass_color, rock_color, t)
-    } else if height < snow_level {
-        let t = (height - rock_level) / (snow_level - rock_level);
-        lerp_color(rock_color, snow_color, t)
-    } else {
-        snow_color
+// Height range of a chunk's mesh, skirts included. Kept in sync with the mesh so frustum
+// culling stays tight after edits, and usable for occlusion or streaming heuristics.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ChunkBounds {
+    pub min_height: f32,
+    pub max_height: f32,
+    // Grid cells along each side of the mesh, picked from the LOD and the terrain's roughness
+    pub cells: u32,
+}
+
+impl ChunkBounds {
+    // Local space box of the chunk mesh, which is centered on the chunk's origin
+    pub fn aabb(&self) -> Aabb {
+        let half_size = CHUNK_SIZE / 2.0;
+        Aabb::from_min_max(
+            Vec3::new(-half_size, self.min_height, -half_size),
+            Vec3::new(half_size, self.max_height, half_size),
+        )
     }
 }
 
-pub fn run() {
+pub const CHUNK_SIZE: f32 = 50.0;
+pub const TERRAIN_SUBDIVISIONS: u32 = 64;
+const RENDER_DISTANCE: i32 = 3; // 3 chunks dans chaque direction
+const WATER_POOL_CAPACITY: usize = 64;
+// Seconds a chunk out of range stays before unloading, so walking back and forth over a
+// chunk boundary doesn't rebuild the same chunks over and over
+const CHUNK_UNLOAD_GRACE: f32 = 5.0;
+const CAMERA_WINDOW: &str = "camera";
+
+#[derive(Default)]
+pub struct ClientOptions {
+    // Write a chrome trace of the session to disk
+    pub trace: bool,
+    // Grayscale image or heightmap descriptor used instead of the noise terrain
+    pub heightmap: Option<PathBuf>,
+    // Serve the world to external map viewers over WebSocket on this address
+    pub map_bridge: Option<SocketAddr>,
+    // Join the dedicated server at this address instead of running the world locally
+    pub connect: Option<SocketAddr>,
+}
+
+pub fn run(options: ClientOptions) {
+    if options.trace {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        diagnostics::enable_trace_output(format!("trace-{}.json", timestamp).into());
+    }
+
+    if let Some(path) = &options.heightmap {
+        match Heightmap::load(path) {
+            Ok(heightmap) => {
+                terrain::use_heightmap(heightmap);
+            }
+            Err(err) => eprintln!("Could not load heightmap {}: {}, using noise terrain", path.display(), err),
+        }
+    }
+
+    // Read before the renderer starts, it can't switch backend or GPU afterwards
+    let mut settings = GameSettings::load();
+    let backends = AvailableBackends::detect();
+    if !backends.has(settings.graphics.backend) {
+        eprintln!("No GPU supports {} here, letting the renderer pick", settings.graphics.backend.label());
+        settings.graphics.backend = GpuBackend::Auto;
+    }
+
     let mut app = App::new();
-    app.add_plugins(DefaultPlugins);
+    app.add_plugins(DefaultPlugins
+        .set(LogPlugin {
+            custom_layer: diagnostics::trace_layer,
+            ..default()
+        })
+        .set(RenderPlugin {
+            render_creation: settings.graphics.wgpu_settings(&backends).into(),
+            ..default()
+        }));
+    app.insert_resource(settings);
+    app.insert_resource(backends);
     app.add_plugins(EguiPlugin);
+    app.add_plugins(UiWindowPlugin);
+    app.add_plugins(AssetRegistryPlugin);
+    app.add_plugins(SettingsPlugin);
+    app.add_plugins(InputMapPlugin);
+    app.add_plugins(DisplayPlugin);
+    app.add_plugins(RenderScalePlugin);
+    app.add_plugins(AmbientOcclusionPlugin);
     app.add_plugins(PlayerPlugin);
     app.add_plugins(WireframePlugin);
     app.add_plugins(WaterPlugin);
+    app.add_plugins(ReflectionProbePlugin);
+    app.add_plugins(SkyLightPlugin);
     app.add_plugins(CameraPlugin);
     app.add_plugins(AtmospherePlugin);
+    app.add_plugins(QuestPlugin);
+    app.add_plugins(ChunkDebugPlugin);
+    app.add_plugins(EntityPoolPlugin::<Water>::new(WATER_POOL_CAPACITY, reset_water_entity));
+    app.add_plugins(TerrainEditPlugin);
+    app.add_plugins(NetClientPlugin);
+    match options.connect {
+        Some(server) => match UdpTransport::to_server(server) {
+            Ok(transport) => {
+                app.insert_resource(ServerConnection::new(transport).with_connector(UdpConnector { server }));
+            }
+            Err(err) => {
+                eprintln!("Could not open a connection to {}: {}", server, err);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            app.add_plugins(LocalServerPlugin);
+        }
+    }
+    app.add_plugins(CapturePlugin);
+    app.add_plugins(InteractionPlugin);
+    app.add_plugins(BoatPlugin);
+    app.add_plugins(TerrainMaterialPlugin);
+    app.add_plugins(TerrainDisplacementPlugin);
+    app.add_plugins(GameDiagnosticsPlugin);
+    app.add_plugins(FishPlugin);
+    app.add_plugins(PopulationPlugin);
+    app.add_plugins(DayNightPlugin);
+    app.add_plugins(SeasonPlugin);
+    app.add_plugins(TorchPlugin);
+    app.add_plugins(PalettePlugin);
+    app.add_plugins(WorldGenPlugin);
+    app.add_plugins(PlacementPlugin);
+    app.add_plugins(SavePlugin);
+    app.add_plugins(CampfirePlugin);
+    app.add_plugins(InventoryPlugin);
+    app.add_plugins(CreativePlugin);
+    app.add_plugins(MapPlugin);
+    app.add_plugins(FootprintPlugin);
+    app.add_plugins(ProjectilePlugin);
+    app.add_plugins(DoorPlugin);
+    app.add_plugins(RegionPlugin);
+    app.add_plugins(HotbarPlugin);
+    app.add_plugins(WorldEventsPlugin);
+    app.add_plugins(AuroraPlugin);
+    app.add_plugins(MeteorPlugin);
+    app.add_plugins(FogBankPlugin);
+    app.add_plugins(RainPlugin);
+    app.add_plugins(SleepPlugin);
+    app.add_plugins(NavigationPlugin);
+    app.add_plugins(FishingPlugin);
+    app.add_plugins(AvatarPlugin);
+    app.add_plugins(EmotePlugin);
+    app.add_plugins(BuildingPlugin);
+    app.add_plugins(AudioMixerPlugin);
+    app.add_plugins(MusicPlugin);
+    app.add_plugins(ImpactPlugin);
+    app.add_plugins(DrowningPlugin);
+    app.add_plugins(ThirstPlugin);
+    app.add_plugins(DialoguePlugin);
+    app.add_plugins(VendorPlugin);
+    app.add_plugins(WorldBorderPlugin);
+    app.add_plugins(AccessibilityPlugin);
+    app.add_plugins(WolfPlugin);
+    app.add_plugins(NetworkDebugPlugin);
+    app.add_plugins(RacePlugin);
+    app.add_plugins(RipplePlugin);
+    app.add_plugins(ItemsPlugin);
+    app.add_plugins(ReconnectPlugin);
+    app.add_plugins(ToastPlugin);
+    app.add_plugins(BirdsPlugin);
+    app.add_plugins(VegetationPlugin);
+    app.add_plugins(HudPlugin);
+    app.add_plugins(LocatePlugin);
+    app.add_plugins(CameraShakePlugin);
+    app.add_plugins(CameraFovPlugin);
+    app.add_plugins(ConsolePlugin);
+    app.add_plugins(ConsoleWindowPlugin);
+    app.add_plugins(AdminPlugin);
+    if let Some(address) = options.map_bridge {
+        app.insert_resource(MapBridgeConfig { address });
+        app.add_plugins(MapBridgePlugin);
+    }
     
     // Initialize chunk system resources
     app.insert_resource(WorldPosition::default());
@@ -91,12 +351,23 @@ pub fn run() {
         loaded_chunks: HashMap::new(),
         chunk_size: CHUNK_SIZE,
         render_distance: RENDER_DISTANCE,
+        streamer: ChunkStreamer::default(),
+        chunk_lods: HashMap::new(),
+        pending_chunks: HashMap::new(),
+        unloading_chunks: HashMap::new(),
     });
     
+    app.register_ui_window(UiWindow {
+        id: CAMERA_WINDOW,
+        title: "Camera",
+        shortcut: None,
+        open: true,
+    });
     app.add_systems(Startup, setup);
     app.add_systems(Update, (
         update_world_position,
-        manage_chunks,
+        expire_streaming_anchors,
+        (manage_chunks, spawn_generated_chunks).chain().run_if(in_state(GameState::Playing)),
         camera_ui_system,
         toggle_wireframe,
     ));
@@ -106,11 +377,10 @@ pub fn run() {
 // Update world position based on player/camera position
 fn update_world_position(
     mut world_pos: ResMut<WorldPosition>,
-    camera_query: Query<&Transform, (With<Camera>, Without<TerrainChunk>)>,
+    camera_query: Query<&Transform, (With<FreeCamera>, Without<TerrainChunk>)>,
 ) {
     if let Ok(camera_transform) = camera_query.get_single() {
-        let new_chunk_x = (camera_transform.translation.x / CHUNK_SIZE).floor() as i32;
-        let new_chunk_z = (camera_transform.translation.z / CHUNK_SIZE).floor() as i32;
+        let (new_chunk_x, new_chunk_z) = chunk_coords(camera_transform.translation, CHUNK_SIZE);
         
         if world_pos.chunk_x != new_chunk_x || world_pos.chunk_z != new_chunk_z {
             world_pos.chunk_x = new_chunk_x;
@@ -120,89 +390,294 @@ fn update_world_position(
     }
 }
 
-// Manage chunk loading and unloading
+// Despawn preloading anchors once their time is up
+fn expire_streaming_anchors(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut anchor_query: Query<(Entity, &mut StreamingAnchor)>,
+) {
+    for (entity, mut anchor) in anchor_query.iter_mut() {
+        if let Some(lifetime) = anchor.lifetime.as_mut()
+            && lifetime.tick(time.delta()).finished()
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// LOD of a chunk given the chunks the streaming anchors are in, from the nearest one
+fn anchored_lod(chunk: ChunkCoords, centers: &[ChunkCoords]) -> u32 {
+    centers.iter().map(|center| chunk_lod(chunk, *center)).min().unwrap_or(MAX_TERRAIN_LOD)
+}
+
+// Remove a chunk's terrain and water, or stop generating it
+fn unload_chunk(
+    commands: &mut Commands,
+    chunk_manager: &mut ChunkManager,
+    water_pool: &mut EntityPool<Water>,
+    chunk_pos: ChunkCoords,
+) {
+    // Not generated yet, stop the task instead of spawning a chunk only to remove it
+    if let Some(pending) = chunk_manager.pending_chunks.remove(&chunk_pos) {
+        pending.cancellation.cancel();
+        info!("Cancelled chunk at ({}, {})", chunk_pos.0, chunk_pos.1);
+        return;
+    }
+    let Some((terrain_entity, water_entity_opt)) = chunk_manager.loaded_chunks.remove(&chunk_pos) else {
+        return;
+    };
+    chunk_manager.chunk_lods.remove(&chunk_pos);
+    // Supprimer le terrain
+    commands.entity(terrain_entity).despawn_recursive();
+    // Rendre l'eau au pool si elle existe
+    if let Some(water_entity) = water_entity_opt {
+        water_pool.release(commands, water_entity);
+    }
+    info!("Removed chunk at ({}, {}) - terrain and water", chunk_pos.0, chunk_pos.1);
+}
+
+// Loaded chunks past a new world border and needed ones inside a wider one
+fn border_changes(chunk_manager: &ChunkManager, border: &WorldBorder) -> ChunkChanges {
+    let present = chunk_manager.loaded_chunks.keys().chain(chunk_manager.pending_chunks.keys());
+    ChunkChanges {
+        load: chunk_manager.streamer.required()
+            .filter(|chunk| border.contains_chunk(*chunk))
+            .filter(|chunk| !chunk_manager.loaded_chunks.contains_key(chunk) && !chunk_manager.pending_chunks.contains_key(chunk))
+            .collect(),
+        unload: present.copied().filter(|chunk| !border.contains_chunk(*chunk)).collect(),
+    }
+}
+
+// Manage chunk loading and unloading around every streaming anchor.
+// Chunks past the world border are never generated. New chunks are
+// generated in the background and spawned by spawn_generated_chunks once ready.
+#[allow(clippy::too_many_arguments)]
 fn manage_chunks(
     mut commands: Commands,
+    time: Res<Time>,
     mut chunk_manager: ResMut<ChunkManager>,
-    world_pos: Res<WorldPosition>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut water_materials: ResMut<Assets<WaterMaterial>>,
-    terrain_chunks: Query<Entity, With<TerrainChunk>>,
-    water_chunks: Query<Entity, With<Water>>,
+    anchor_query: Query<(Entity, &GlobalTransform, &StreamingAnchor)>,
+    mut removed_anchors: RemovedComponents<StreamingAnchor>,
+    mut last_centers: Local<Vec<ChunkCoords>>,
+    border: Res<WorldBorder>,
+    mut water_pool: ResMut<EntityPool<Water>>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    palette: Res<ActiveTerrainPalette>,
+    settings: Res<GameSettings>,
 ) {
-    if !world_pos.is_changed() {
+    let render_distance = chunk_manager.render_distance;
+    let mut changes = ChunkChanges::default();
+    for entity in removed_anchors.read() {
+        changes.merge(chunk_manager.streamer.remove_anchor(AnchorId::Entity(entity)));
+    }
+    for (entity, transform, anchor) in anchor_query.iter() {
+        let center = chunk_coords(transform.translation(), CHUNK_SIZE);
+        let radius = anchor.radius.unwrap_or(render_distance);
+        changes.merge(chunk_manager.streamer.update_anchor(AnchorId::Entity(entity), center, radius));
+    }
+    if border.is_changed() {
+        changes.merge(border_changes(&chunk_manager, &border));
+    }
+
+    // Chunks out of range for the whole grace period go now
+    let dt = time.delta_secs();
+    let mut expired = Vec::new();
+    chunk_manager.unloading_chunks.retain(|chunk_pos, remaining| {
+        *remaining -= dt;
+        if *remaining > 0.0 {
+            return true;
+        }
+        expired.push(*chunk_pos);
+        false
+    });
+    for chunk_pos in expired {
+        if !chunk_manager.streamer.is_required(chunk_pos) {
+            unload_chunk(&mut commands, &mut chunk_manager, &mut water_pool, chunk_pos);
+        }
+    }
+
+    let mut centers: Vec<ChunkCoords> = chunk_manager.streamer.centers().collect();
+    centers.sort();
+    if changes.is_empty() && centers == *last_centers {
         return;
     }
+    let _span = info_span!("manage_chunks").entered();
     
-    let player_chunk_x = world_pos.chunk_x;
-    let player_chunk_z = world_pos.chunk_z;
-    let render_distance = chunk_manager.render_distance;
-    
-    // Collect chunks that should be loaded
-    let mut required_chunks = std::collections::HashSet::new();
-    for x in (player_chunk_x - render_distance)..=(player_chunk_x + render_distance) {
-        for z in (player_chunk_z - render_distance)..=(player_chunk_z + render_distance) {
-            required_chunks.insert((x, z));
+    // Chunks no longer needed wait out the grace period, those past the border go right away
+    for chunk_pos in changes.unload {
+        if border.contains_chunk(chunk_pos) {
+            chunk_manager.unloading_chunks.entry(chunk_pos).or_insert(CHUNK_UNLOAD_GRACE);
+        } else {
+            chunk_manager.unloading_chunks.remove(&chunk_pos);
+            unload_chunk(&mut commands, &mut chunk_manager, &mut water_pool, chunk_pos);
         }
     }
+    // Back in range before their time was up, they stay as they are
+    for chunk_pos in &changes.load {
+        chunk_manager.unloading_chunks.remove(chunk_pos);
+    }
     
-    // Remove chunks that are too far (both terrain and water)
-    let mut chunks_to_remove = Vec::new();
-    for (chunk_pos, (terrain_entity, water_entity_opt)) in chunk_manager.loaded_chunks.iter() {
-        if !required_chunks.contains(chunk_pos) {
-            chunks_to_remove.push(*chunk_pos);
-            // Supprimer le terrain
-            commands.entity(*terrain_entity).despawn_recursive();
-            // Supprimer l'eau si elle existe
-            if let Some(water_entity) = water_entity_opt {
-                commands.entity(*water_entity).despawn_recursive();
+    // Rebuild chunks that crossed a LOD ring; displaced ones only swap their grid
+    let gpu_terrain = settings.graphics.gpu_terrain;
+    for (chunk_pos, lod) in chunk_manager.chunk_lods.iter_mut() {
+        let wanted = anchored_lod(*chunk_pos, &centers);
+        if *lod != wanted {
+            *lod = wanted;
+            if !gpu_terrain {
+                terrain_edits.dirty_chunks.insert(*chunk_pos);
             }
-            info!("Removed chunk at ({}, {}) - terrain and water", chunk_pos.0, chunk_pos.1);
         }
     }
-    for chunk_pos in chunks_to_remove {
-        chunk_manager.loaded_chunks.remove(&chunk_pos);
-    }
+    last_centers.clone_from(&centers);
     
-    // Add new chunks that need to be loaded
-    for chunk_pos in required_chunks {
-        if !chunk_manager.loaded_chunks.contains_key(&chunk_pos) {
-            let (terrain_entity, water_entity_opt) = spawn_chunk(
-                &mut commands,
-                &mut meshes,
-                &mut materials,
-                &mut water_materials,
-                chunk_pos.0,
-                chunk_pos.1,
-            );
-            chunk_manager.loaded_chunks.insert(chunk_pos, (terrain_entity, water_entity_opt));
-            info!("Created chunk at ({}, {}) - terrain and water", chunk_pos.0, chunk_pos.1);
+    // Start generating new chunks that need to be loaded
+    if changes.load.is_empty() {
+        return;
+    }
+    let edits = Arc::new(terrain_edits.clone());
+    let palette = Arc::new(palette.0.clone());
+    let task_pool = AsyncComputeTaskPool::get();
+    for chunk_pos in changes.load {
+        if !border.contains_chunk(chunk_pos) {
+            continue;
+        }
+        if chunk_manager.loaded_chunks.contains_key(&chunk_pos) || chunk_manager.pending_chunks.contains_key(&chunk_pos) {
+            continue;
+        }
+        let lod = anchored_lod(chunk_pos, &centers);
+        let cancellation = CancellationToken::default();
+        let task = task_pool.spawn({
+            let (edits, palette, cancellation) = (edits.clone(), palette.clone(), cancellation.clone());
+            async move {
+                std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    generate_chunk(chunk_pos.0, chunk_pos.1, lod, gpu_terrain, &edits, &palette, &cancellation)
+                }))
+                .map_err(|panic| panic_message(panic.as_ref()))
+            }
+        });
+        chunk_manager.pending_chunks.insert(chunk_pos, PendingChunk { task, cancellation, lod });
+    }
+}
+
+// Spawn the chunks whose generation finished
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn spawn_generated_chunks(
+    mut commands: Commands,
+    mut chunk_manager: ResMut<ChunkManager>,
+    world_pos: Res<WorldPosition>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    terrain_material: Res<TerrainMaterialHandle>,
+    water_materials: Res<WaterMaterials>,
+    water_meshes: Res<WaterMeshes>,
+    mut water_pool: ResMut<EntityPool<Water>>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    mut displaced: DisplacedTerrain,
+    settings: Res<GameSettings>,
+    mut diagnostics: Diagnostics,
+    mut toasts: EventWriter<Toast>,
+) {
+    if chunk_manager.pending_chunks.is_empty() {
+        return;
+    }
+    let finished: Vec<((i32, i32), Result<Option<GeneratedChunk>, String>)> = chunk_manager.pending_chunks
+        .iter_mut()
+        .filter_map(|(chunk_pos, pending)| block_on(future::poll_once(&mut pending.task)).map(|chunk| (*chunk_pos, chunk)))
+        .collect();
+    let center = (world_pos.chunk_x, world_pos.chunk_z);
+    let centers: Vec<ChunkCoords> = chunk_manager.streamer.centers().collect();
+    for (chunk_pos, generated) in finished {
+        let Some(pending) = chunk_manager.pending_chunks.remove(&chunk_pos) else {
+            continue;
+        };
+        // The chunk stays missing until it streams in again
+        let generated = match generated {
+            Ok(Some(generated)) => generated,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!("Chunk ({}, {}) failed to generate: {}", chunk_pos.0, chunk_pos.1, err);
+                toasts.send(Toast::new(ToastKind::Error, format!("Chunk ({}, {}) failed to generate", chunk_pos.0, chunk_pos.1)));
+                continue;
+            }
+        };
+        diagnostics.add_measurement(&CHUNK_GENERATION_MS, || generated.elapsed_ms);
+        // Refined from the camera's exact position once spawned
+        let water_distance = Vec2::new((chunk_pos.0 - center.0) as f32, (chunk_pos.1 - center.1) as f32).length() * CHUNK_SIZE;
+        let (terrain_entity, water_entity_opt) = spawn_chunk(
+            &mut commands,
+            &mut meshes,
+            &terrain_material.0,
+            &mut displaced,
+            pending.lod,
+            &water_materials,
+            &water_meshes,
+            water_lod(water_distance, None),
+            &mut water_pool,
+            chunk_pos.0,
+            chunk_pos.1,
+            generated,
+        );
+        chunk_manager.loaded_chunks.insert(chunk_pos, (terrain_entity, water_entity_opt));
+        chunk_manager.chunk_lods.insert(chunk_pos, pending.lod);
+        // An anchor may have crossed a LOD ring while it was generated
+        if pending.lod != anchored_lod(chunk_pos, &centers) && !settings.graphics.gpu_terrain {
+            terrain_edits.dirty_chunks.insert(chunk_pos);
         }
+        info!("Created chunk at ({}, {}) - terrain and water", chunk_pos.0, chunk_pos.1);
     }
 }
 
-// Generate water mesh for areas below water level
-fn generate_water_mesh(
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+// Build a chunk's meshes, or its maps for GPU terrain, None if it was cancelled meanwhile.
+// Runs off the main thread.
+fn generate_chunk(
+    chunk_x: i32,
+    chunk_z: i32,
+    lod: u32,
+    gpu_terrain: bool,
+    terrain_edits: &TerrainEdits,
+    palette: &TerrainPalette,
+    cancellation: &CancellationToken,
+) -> Option<GeneratedChunk> {
+    if cancellation.is_cancelled() {
+        return None;
+    }
+    let start = std::time::Instant::now();
+    let terrain = if gpu_terrain {
+        ChunkSurface::Displaced(Box::new(build_displacement_maps(chunk_x, chunk_z, terrain_edits, palette)))
+    } else {
+        let (mesh, bounds) = build_terrain_mesh(chunk_x, chunk_z, lod, terrain_edits, palette);
+        ChunkSurface::Mesh(mesh, bounds)
+    };
+    if cancellation.is_cancelled() {
+        return None;
+    }
+    let (world_x, world_z) = (chunk_x as f32 * CHUNK_SIZE, chunk_z as f32 * CHUNK_SIZE);
+    // Taken at the chunk's center on the surface, the whole plane gets one look
+    let water = chunk_has_water(world_x, world_z).then(|| biome::sample_at(world_x, world_z, sea_level(world_x, world_z)).biome);
+    Some(GeneratedChunk {
+        terrain,
+        water,
+        elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+    })
+}
+
+// Terrain is sampled on a grid this many cells a side to find water in a chunk
+const WATER_SAMPLES: u32 = 20;
+
+// Whether a chunk needs a water plane, some of its terrain being below the water level
+fn chunk_has_water(
     world_offset_x: f32,
     world_offset_z: f32,
-    subdivisions: u32,
-) -> Option<Mesh> {
-    info!("Generating water mesh for offset ({}, {})", world_offset_x, world_offset_z);
+) -> bool {
+    let _span = info_span!("chunk_has_water", world_offset_x, world_offset_z).entered();
+    let subdivisions = WATER_SAMPLES;
     
     // Check if this chunk needs water by sampling terrain heights
-    let main_noise = BasicMulti::<Perlin>::new(1)
-        .set_octaves(8)           
-        .set_frequency(0.05)
-        .set_persistence(0.6)     
-        .set_lacunarity(2.0);
-        
-    let detail_noise = BasicMulti::<Perlin>::new(2)
-        .set_octaves(3)
-        .set_frequency(0.03)
-        .set_persistence(0.4)
-        .set_lacunarity(2.0);
-
     let mut has_water = false;
     let step = CHUNK_SIZE / subdivisions as f32;
     let half_size = CHUNK_SIZE / 2.0;
@@ -217,12 +692,10 @@ fn generate_water_mesh(
             let world_z = local_z + world_offset_z;
             
             // Calculate terrain height at this point
-            let main_val = main_noise.get([world_x as f64, world_z as f64, 42.0]) * 22.0;
-            let detail_val = detail_noise.get([world_x as f64, world_z as f64, 100.0]) * 3.0;
-            let terrain_height = (main_val + detail_val) as f32;
+            let terrain_height = terrain::height(world_x, world_z);
             
             // If any point is below water level, we need water for this chunk
-            if terrain_height < WATER_LEVEL {
+            if terrain_height < sea_level(world_x, world_z) {
                 has_water = true;
                 break;
             }
@@ -232,104 +705,408 @@ fn generate_water_mesh(
         }
     }
     
-    if !has_water {
-        return None; // No water needed for this chunk
+    has_water
+}
+
+// Terrain LOD: chunk rings further than this from the camera chunk get coarser meshes
+pub const LOD_RING_STEP: i32 = 2;
+pub const MAX_TERRAIN_LOD: u32 = 2;
+// Skirts hang below chunk edges to hide cracks against neighbours of another LOD
+const SKIRT_DEPTH_FACTOR: f32 = 1.5;
+
+// LOD of a chunk given the chunk the camera is in, 0 being full resolution
+pub fn chunk_lod(chunk: (i32, i32), center: (i32, i32)) -> u32 {
+    let ring = (chunk.0 - center.0).abs().max((chunk.1 - center.1).abs());
+    ((ring / LOD_RING_STEP) as u32).min(MAX_TERRAIN_LOD)
+}
+
+// Each LOD halves the grid resolution
+pub fn lod_subdivisions(lod: u32) -> u32 {
+    (TERRAIN_SUBDIVISIONS >> lod).max(2)
+}
+
+// Roughness is sampled on a grid this many cells a side over the chunk
+const ROUGHNESS_SAMPLES: u32 = 16;
+// Grid cells a side at full resolution for chunks up to each roughness in meters, rougher
+// ones get ROUGH_SUBDIVISIONS. Every size is a power of two, so after any LOD halving the
+// coarser of two neighbouring grids still has its edge vertices on the finer one.
+const DETAIL_LEVELS: [(f32, u32); 2] = [(0.44, TERRAIN_SUBDIVISIONS / 2), (0.54, TERRAIN_SUBDIVISIONS)];
+const ROUGH_SUBDIVISIONS: u32 = TERRAIN_SUBDIVISIONS * 2;
+
+// How far the ground bends away from straight lines between samples, the root mean square
+// over the chunk in meters. Flat ground and even slopes are 0, so a coarse grid fits them.
+pub fn chunk_roughness(chunk_x: i32, chunk_z: i32, height: impl Fn(f32, f32) -> f32) -> f32 {
+    let side = ROUGHNESS_SAMPLES + 1;
+    let step = CHUNK_SIZE / ROUGHNESS_SAMPLES as f32;
+    let origin = Vec2::new(chunk_x as f32, chunk_z as f32) * CHUNK_SIZE - CHUNK_SIZE / 2.0;
+    let heights: Vec<f32> = (0..side * side)
+        .map(|i| {
+            let point = origin + Vec2::new((i % side) as f32, (i / side) as f32) * step;
+            height(point.x, point.y)
+        })
+        .collect();
+    let at = |x: u32, z: u32| heights[(z * side + x) as usize];
+
+    let mut sum = 0.0;
+    for z in 1..side - 1 {
+        for x in 1..side - 1 {
+            let center = at(x, z);
+            let along_x = (at(x - 1, z) + at(x + 1, z)) / 2.0 - center;
+            let along_z = (at(x, z - 1) + at(x, z + 1)) / 2.0 - center;
+            sum += along_x * along_x + along_z * along_z;
+        }
+    }
+    (sum / (2 * (side - 2) * (side - 2)) as f32).sqrt()
+}
+
+// Grid cells a side of a chunk mesh: flat chunks get fewer, jagged ones more, each LOD halves them
+pub fn chunk_subdivisions(roughness: f32, lod: u32) -> u32 {
+    let full = DETAIL_LEVELS
+        .iter()
+        .find(|(max_roughness, _)| roughness <= *max_roughness)
+        .map_or(ROUGH_SUBDIVISIONS, |(_, cells)| *cells);
+    (full >> lod).max(2)
+}
+
+// Build the deformed and colored terrain mesh of a chunk, including terrain edits
+pub fn build_terrain_mesh(
+    chunk_x: i32,
+    chunk_z: i32,
+    lod: u32,
+    terrain_edits: &TerrainEdits,
+    palette: &TerrainPalette,
+) -> (Mesh, ChunkBounds) {
+    let _span = info_span!("build_terrain_mesh", chunk_x, chunk_z, lod).entered();
+    let roughness = chunk_roughness(chunk_x, chunk_z, |x, z| terrain::height(x, z) + terrain_edits.height_offset(x, z));
+    let cells = chunk_subdivisions(roughness, lod);
+    let side = cells + 1;
+    let step = CHUNK_SIZE / cells as f32;
+    let half_size = CHUNK_SIZE / 2.0;
+    
+    // Calculate world offset for this chunk
+    let world_offset_x = chunk_x as f32 * CHUNK_SIZE;
+    let world_offset_z = chunk_z as f32 * CHUNK_SIZE;
+    
+    let mut positions = Vec::with_capacity((side * side) as usize);
+    let mut uvs = Vec::with_capacity((side * side) as usize);
+    let mut colors = Vec::with_capacity((side * side) as usize);
+    let mut min_height = f32::MAX;
+    let mut max_height = f32::MIN;
+    for z in 0..side {
+        for x in 0..side {
+            let local_x = x as f32 * step - half_size;
+            let local_z = z as f32 * step - half_size;
+            // World coordinates keep neighbouring chunks seamless
+            let world_x = local_x + world_offset_x;
+            let world_z = local_z + world_offset_z;
+            let height = terrain::height(world_x, world_z) + terrain_edits.height_offset(world_x, world_z);
+            min_height = min_height.min(height);
+            max_height = max_height.max(height);
+            
+            positions.push([local_x, height, local_z]);
+            uvs.push([x as f32 / cells as f32, z as f32 / cells as f32]);
+            // Get color based on height and biome
+            colors.push(paint_roads(&terrain_edits.roads, world_x, world_z, palette.color(world_x, world_z, height)));
+        }
+    }
+    
+    let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+    for z in 0..cells {
+        for x in 0..cells {
+            let a = z * side + x;
+            let b = a + 1;
+            let c = a + side;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+    
+    let mut terrain = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_indices(Indices::U32(indices));
+    terrain.compute_normals();
+    let skirt_depth = step * SKIRT_DEPTH_FACTOR;
+    add_skirts(&mut terrain, side, skirt_depth);
+    
+    let bounds = ChunkBounds {
+        min_height: min_height - skirt_depth,
+        max_height,
+        cells,
+    };
+    (terrain, bounds)
+}
+
+// Flat grid of a chunk with its skirts, for chunks displaced on the GPU by their height
+// map. Grid vertices sit at 0 and skirt vertices a skirt depth below, added to the height.
+pub fn build_grid_mesh(cells: u32) -> Mesh {
+    let side = cells + 1;
+    let step = CHUNK_SIZE / cells as f32;
+    let half_size = CHUNK_SIZE / 2.0;
+    let mut positions = Vec::with_capacity((side * side) as usize);
+    let mut uvs = Vec::with_capacity((side * side) as usize);
+    for z in 0..side {
+        for x in 0..side {
+            positions.push([x as f32 * step - half_size, 0.0, z as f32 * step - half_size]);
+            uvs.push([x as f32 / cells as f32, z as f32 / cells as f32]);
+        }
+    }
+    let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+    for z in 0..cells {
+        for x in 0..cells {
+            let a = z * side + x;
+            let b = a + 1;
+            let c = a + side;
+            let d = c + 1;
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+    // Normals and colors are read from the maps in the vertex shader, these only keep the
+    // attributes the terrain shader expects
+    let mut grid = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; (side * side) as usize])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, vec![[1.0; 4]; (side * side) as usize])
+        .with_inserted_indices(Indices::U32(indices));
+    add_skirts(&mut grid, side, step * SKIRT_DEPTH_FACTOR);
+    grid
+}
+
+// Depth of the skirts of a chunk mesh with this many grid cells a side
+pub fn skirt_depth(cells: u32) -> f32 {
+    CHUNK_SIZE / cells as f32 * SKIRT_DEPTH_FACTOR
+}
+
+// Move the vertices of a chunk mesh lying in `area` (world space) to the edited terrain, in
+// place, along with their colors, the normals around them and their skirt copies. Returns
+// None when the edits change the chunk's grid, which needs a full rebuild then.
+#[allow(clippy::too_many_arguments)]
+pub fn update_terrain_mesh(
+    mesh: &mut Mesh,
+    chunk_x: i32,
+    chunk_z: i32,
+    lod: u32,
+    cells: u32,
+    area: Rect,
+    terrain_edits: &TerrainEdits,
+    palette: &TerrainPalette,
+) -> Option<ChunkBounds> {
+    let _span = info_span!("update_terrain_mesh", chunk_x, chunk_z, lod).entered();
+    let roughness = chunk_roughness(chunk_x, chunk_z, |x, z| terrain::height(x, z) + terrain_edits.height_offset(x, z));
+    if chunk_subdivisions(roughness, lod) != cells {
+        return None;
+    }
+    let side = cells + 1;
+    let step = CHUNK_SIZE / cells as f32;
+    let center = Vec2::new(chunk_x as f32, chunk_z as f32) * CHUNK_SIZE;
+    let origin = center - CHUNK_SIZE / 2.0;
+    // Grid lines covering the area, and one more around it for the normals
+    let first = ((area.min - origin) / step).floor().max(Vec2::ZERO).as_uvec2().min(UVec2::splat(cells));
+    let last = ((area.max - origin) / step).ceil().max(Vec2::ZERO).as_uvec2().min(UVec2::splat(cells));
+    let around = (first.saturating_sub(UVec2::ONE), (last + UVec2::ONE).min(UVec2::splat(cells)));
+    let skirt_depth = step * SKIRT_DEPTH_FACTOR;
+
+    let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION) else {
+        return None;
+    };
+    if positions.len() < (side * side) as usize {
+        return None;
+    }
+    let mut colors = Vec::new();
+    for z in first.y..=last.y {
+        for x in first.x..=last.x {
+            let vertex = (z * side + x) as usize;
+            let [local_x, _, local_z] = positions[vertex];
+            let (world_x, world_z) = (local_x + center.x, local_z + center.y);
+            let height = terrain::height(world_x, world_z) + terrain_edits.height_offset(world_x, world_z);
+            positions[vertex][1] = height;
+            for skirt in skirt_copies(side, x, z) {
+                positions[skirt][1] = height - skirt_depth;
+            }
+            colors.push((x, z, paint_roads(&terrain_edits.roads, world_x, world_z, palette.color(world_x, world_z, height))));
+        }
     }
+    let surface: Vec<[f32; 3]> = positions[..(side * side) as usize].to_vec();
+
+    if let Some(VertexAttributeValues::Float32x4(mesh_colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR) {
+        for (x, z, color) in colors {
+            mesh_colors[(z * side + x) as usize] = color;
+            for skirt in skirt_copies(side, x, z) {
+                mesh_colors[skirt] = color;
+            }
+        }
+    }
+    if let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) {
+        for z in around.0.y..=around.1.y {
+            for x in around.0.x..=around.1.x {
+                let normal = grid_normal(&surface, side, x, z).to_array();
+                normals[(z * side + x) as usize] = normal;
+                for skirt in skirt_copies(side, x, z) {
+                    normals[skirt] = normal;
+                }
+            }
+        }
+    }
+
+    let (min_height, max_height) = surface
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), [_, height, _]| (min.min(*height), max.max(*height)));
+    Some(ChunkBounds {
+        min_height: min_height - skirt_depth,
+        max_height,
+        cells,
+    })
+}
+
+// Normal of a grid vertex the way `Mesh::compute_normals` gives it, the sum of the unit
+// normals of the triangles around it, split as in `build_terrain_mesh`
+fn grid_normal(positions: &[[f32; 3]], side: u32, x: u32, z: u32) -> Vec3 {
+    let at = |(x, z): (u32, u32)| Vec3::from(positions[(z * side + x) as usize]);
+    let mut normal = Vec3::ZERO;
+    // Cells having the vertex as a corner, by their first corner
+    let cells = [(x.wrapping_sub(1), z.wrapping_sub(1)), (x, z.wrapping_sub(1)), (x.wrapping_sub(1), z), (x, z)];
+    for (cell_x, cell_z) in cells.into_iter().filter(|(cell_x, cell_z)| *cell_x < side - 1 && *cell_z < side - 1) {
+        let a = (cell_x, cell_z);
+        let b = (cell_x + 1, cell_z);
+        let c = (cell_x, cell_z + 1);
+        let d = (cell_x + 1, cell_z + 1);
+        for [p, q, r] in [[a, c, b], [b, c, d]] {
+            if [p, q, r].contains(&(x, z)) {
+                normal += (at(q) - at(p)).cross(at(r) - at(p)).normalize();
+            }
+        }
+    }
+    normal.normalize_or_zero()
+}
+
+// Skirt copies of a grid vertex on the border, where `add_skirts` put them
+fn skirt_copies(side: u32, x: u32, z: u32) -> impl Iterator<Item = usize> {
+    let last = side - 1;
+    [(z == 0, x), (z == last, x), (x == 0, z), (x == last, z)]
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (on_edge, _))| *on_edge)
+        .map(move |(edge, (_, along))| (side * side + edge as u32 * side + along) as usize)
+}
+
+// Extrude the border vertices of a grid mesh downward, facing outward.
+// Skirt vertices are copies, so they reuse the surface normals and leave its shading untouched.
+fn add_skirts(mesh: &mut Mesh, side: u32, depth: f32) {
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32x3(normals)),
+        Some(VertexAttributeValues::Float32x2(uvs)),
+        Some(VertexAttributeValues::Float32x4(colors)),
+    ) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION).cloned(),
+        mesh.attribute(Mesh::ATTRIBUTE_NORMAL).cloned(),
+        mesh.attribute(Mesh::ATTRIBUTE_UV_0).cloned(),
+        mesh.attribute(Mesh::ATTRIBUTE_COLOR).cloned(),
+    ) else {
+        return;
+    };
+    let (mut positions, mut normals, mut uvs, mut colors) = (positions, normals, uvs, colors);
+    let last = side - 1;
+    let index = |x: u32, z: u32| z * side + x;
+    
+    // Border vertices of each side with its outward direction
+    let edges: [(Vec<u32>, Vec3); 4] = [
+        ((0..side).map(|x| index(x, 0)).collect(), Vec3::NEG_Z),
+        ((0..side).map(|x| index(x, last)).collect(), Vec3::Z),
+        ((0..side).map(|z| index(0, z)).collect(), Vec3::NEG_X),
+        ((0..side).map(|z| index(last, z)).collect(), Vec3::X),
+    ];
     
-    // Create a simple water plane for this chunk
-    let mesh = Mesh::from(
-        Plane3d::default()
-            .mesh()
-            .size(CHUNK_SIZE, CHUNK_SIZE)
-            .subdivisions(subdivisions)
-    );
+    let mut skirt_indices = Vec::new();
+    for (edge, outward) in edges {
+        let base = positions.len() as u32;
+        for &vertex in &edge {
+            let [x, y, z] = positions[vertex as usize];
+            positions.push([x, y - depth, z]);
+            normals.push(normals[vertex as usize]);
+            uvs.push(uvs[vertex as usize]);
+            colors.push(colors[vertex as usize]);
+        }
+        for i in 0..edge.len() as u32 - 1 {
+            let (top0, top1) = (edge[i as usize], edge[i as usize + 1]);
+            let (bottom0, bottom1) = (base + i, base + i + 1);
+            let along = Vec3::from(positions[top1 as usize]) - Vec3::from(positions[top0 as usize]);
+            // Counter-clockwise seen from outside
+            if Vec3::NEG_Y.cross(along).dot(outward) > 0.0 {
+                skirt_indices.extend_from_slice(&[top0, bottom0, top1, top1, bottom0, bottom1]);
+            } else {
+                skirt_indices.extend_from_slice(&[top0, top1, bottom0, top1, bottom1, bottom0]);
+            }
+        }
+    }
     
-    Some(mesh)
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    if let Some(Indices::U32(indices)) = mesh.indices_mut() {
+        indices.extend(skirt_indices);
+    }
 }
 
 // Spawn a single terrain chunk at the given coordinates
+#[allow(clippy::too_many_arguments)]
 fn spawn_chunk(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
-    water_materials: &mut ResMut<Assets<WaterMaterial>>,
+    terrain_material: &Handle<TerrainMaterial>,
+    displaced: &mut DisplacedTerrain,
+    lod: u32,
+    water_materials: &WaterMaterials,
+    water_meshes: &WaterMeshes,
+    water_lod: u32,
+    water_pool: &mut EntityPool<Water>,
     chunk_x: i32,
     chunk_z: i32,
+    generated: GeneratedChunk,
 ) -> (Entity, Option<Entity>) { // Retourne (terrain_entity, optional_water_entity)
-    // Create terrain mesh
-    let mut terrain = Mesh::from(
-        Plane3d::default()
-            .mesh()
-            .size(CHUNK_SIZE, CHUNK_SIZE)
-            .subdivisions(50)  // Good balance between detail and performance
-    );
-    
-    let terrain_material = StandardMaterial {
-        base_color: Color::WHITE,
-        ..default()
-    };
+    let GeneratedChunk { terrain, water, .. } = generated;
     
     // Calculate world offset for this chunk
     let world_offset_x = chunk_x as f32 * CHUNK_SIZE;
     let world_offset_z = chunk_z as f32 * CHUNK_SIZE;
     
-    // Deform the terrain
-    if let Some(VertexAttributeValues::Float32x3(positions)) = terrain.attribute_mut(Mesh::ATTRIBUTE_POSITION) {
-        let main_noise = BasicMulti::<Perlin>::new(1)
-            .set_octaves(8)           
-            .set_frequency(0.05)
-            .set_persistence(0.6)     
-            .set_lacunarity(2.0);
-            
-        let detail_noise = BasicMulti::<Perlin>::new(2)
-            .set_octaves(3)
-            .set_frequency(0.03)
-            .set_persistence(0.4)
-            .set_lacunarity(2.0);
-        
-        let mut colors = Vec::new();
-        
-        for pos in positions.iter_mut() {
-            // Apply world offset to get correct world coordinates
-            let world_x = pos[0] + world_offset_x;
-            let world_z = pos[2] + world_offset_z;
-            
-            // Generate height using world coordinates for seamless chunks
-            let main_val = main_noise.get([world_x as f64, world_z as f64, 42.0]) * 22.0;
-            let detail_val = detail_noise.get([world_x as f64, world_z as f64, 100.0]) * 3.0;
-            
-            let height = main_val + detail_val;
-            pos[1] = height as f32;
-            
-            // Get color based on height
-            let color = get_terrain_color(pos[1]);
-            colors.push(color);
-        }
-        
-        terrain.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
-        terrain.compute_normals();
-    }
-    
     // Spawn terrain chunk
-    let terrain_entity = commands.spawn((
-        Mesh3d(meshes.add(terrain)),
-        MeshMaterial3d(materials.add(terrain_material)),
+    let mut terrain_entity = commands.spawn((
         Transform::from_translation(Vec3::new(world_offset_x, 0.0, world_offset_z)),
         TerrainChunk { chunk_x, chunk_z },
         Ground,
-    )).id();
+    ));
+    match terrain {
+        ChunkSurface::Mesh(mesh, bounds) => {
+            terrain_entity.insert((
+                Mesh3d(meshes.add(mesh)),
+                MeshMaterial3d(terrain_material.clone()),
+                bounds,
+                // Set up front, Bevy would otherwise compute it once and never refresh it after edits
+                bounds.aabb(),
+            ));
+        }
+        ChunkSurface::Displaced(maps) => {
+            terrain_entity.insert(displaced.chunk(meshes, *maps, lod));
+        }
+    }
+    let terrain_entity = terrain_entity.id();
     
     // Generate water mesh only for areas below water level
-    let water_entity = if let Some(water_mesh) = generate_water_mesh(world_offset_x, world_offset_z, 20) {
+    let water_entity = if let Some(biome) = water {
         info!("Creating water for chunk ({}, {})", chunk_x, chunk_z);
         
-        Some(commands.spawn((
-            Mesh3d(meshes.add(water_mesh)),
-            MeshMaterial3d(water_materials.add(WaterMaterial::default())),
-            Transform::from_translation(Vec3::new(world_offset_x, WATER_LEVEL, world_offset_z)),
+        Some(water_pool.acquire(commands, (
+            Mesh3d(water_meshes.get(water_lod)),
+            WaterLod(water_lod),
+            MeshMaterial3d(water_materials.get(biome)),
+            Transform::from_translation(Vec3::new(world_offset_x, sea_level(world_offset_x, world_offset_z), world_offset_z)),
             Water,
             TerrainChunk { chunk_x, chunk_z },
-        )).id())
+        )))
     } else {
         info!("No water needed for chunk ({}, {})", chunk_x, chunk_z);
         None
@@ -338,20 +1115,31 @@ fn spawn_chunk(
     (terrain_entity, water_entity)
 }
 
+// Drop the per-chunk parts of a water entity when it goes back to the pool, the shared
+// material stays
+fn reset_water_entity(entity: &mut EntityCommands) {
+    entity.remove::<(Mesh3d, WaterLod, TerrainChunk)>();
+}
+
 fn setup(mut commands: Commands) {
     // Only spawn lighting, chunks will be managed by the chunk system
     commands.spawn((
         DirectionalLight::default(),
         Transform::from_translation(Vec3::ONE).looking_at(Vec3::ZERO, Vec3::Y),
+        Sun,
     ));
 
 }
 
 fn camera_ui_system(
     mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
     mut camera_settings: ResMut<CameraSettings>,
 ) {
-    egui::Window::new("Camera")
+    let Some(window) = windows.window(CAMERA_WINDOW) else {
+        return;
+    };
+    window
         .default_size([200.0, 200.0])
         .show(contexts.ctx_mut(), |ui| {
             ui.heading("Camera Mode");
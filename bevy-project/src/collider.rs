@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+// Solid box the player can't walk through, centred and oriented on the entity's global transform
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BoxCollider {
+    pub half_size: Vec3,
+}
+
+// Push an upright body (a circle of `radius` spanning `bottom..top`) out of the boxes it overlaps.
+// Boxes are expected to stay upright, only their yaw is taken into account.
+pub fn push_out<'a>(
+    position: Vec3,
+    radius: f32,
+    bottom: f32,
+    top: f32,
+    colliders: impl Iterator<Item = (&'a GlobalTransform, &'a BoxCollider)>,
+) -> Vec3 {
+    let mut position = position;
+    for (transform, collider) in colliders {
+        let center = transform.translation();
+        if top < center.y - collider.half_size.y || bottom > center.y + collider.half_size.y {
+            continue;
+        }
+
+        let rotation = transform.to_scale_rotation_translation().1;
+        let local = rotation.inverse() * (position - center);
+        let half = collider.half_size.xz();
+        let point = local.xz();
+        let closest = point.clamp(-half, half);
+        let offset = point - closest;
+        let distance = offset.length();
+        if distance >= radius {
+            continue;
+        }
+
+        let push = if distance > f32::EPSILON {
+            offset / distance * (radius - distance)
+        } else {
+            // Centre inside the box, leave through the nearest side
+            let depth = half - point.abs();
+            if depth.x < depth.y {
+                Vec2::new(point.x.signum() * (depth.x + radius), 0.0)
+            } else {
+                Vec2::new(0.0, point.y.signum() * (depth.y + radius))
+            }
+        };
+        position += rotation * Vec3::new(push.x, 0.0, push.y);
+    }
+    position
+}
+
+// Top of a floor or ramp the player can stand on: a rectangle of `half_size` in the entity's
+// local xz at its origin's height, rising by `rise` from its +z edge to its -z edge
+#[derive(Component, Clone, Copy, Debug)]
+pub struct WalkableSurface {
+    pub half_size: Vec2,
+    pub rise: f32,
+}
+
+impl WalkableSurface {
+    // Surface height above a world point, None outside the rectangle
+    pub fn height_at(&self, transform: &GlobalTransform, x: f32, z: f32) -> Option<f32> {
+        let (_, rotation, center) = transform.to_scale_rotation_translation();
+        let local = rotation.inverse() * Vec3::new(x - center.x, 0.0, z - center.z);
+        if local.x.abs() > self.half_size.x || local.z.abs() > self.half_size.y {
+            return None;
+        }
+        let along = (self.half_size.y - local.z) / (2.0 * self.half_size.y);
+        Some(center.y + self.rise * along)
+    }
+}
+
+// Highest surface under the feet that they can step onto, surfaces further above are walked under
+pub fn surface_height<'a>(
+    feet: Vec3,
+    max_step: f32,
+    surfaces: impl Iterator<Item = (&'a GlobalTransform, &'a WalkableSurface)>,
+) -> Option<f32> {
+    surfaces
+        .filter_map(|(transform, surface)| surface.height_at(transform, feet.x, feet.z))
+        .filter(|height| *height <= feet.y + max_step)
+        .reduce(f32::max)
+}
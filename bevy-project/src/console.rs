@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use bevy::prelude::*;
+use bevy::utils::synccell::SyncCell;
+use bevy_egui::{egui, EguiContexts};
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+
+// Lines kept in the in-game console window
+const CONSOLE_HISTORY: usize = 200;
+const CONSOLE_WINDOW: &str = "console";
+
+// Command registry shared by the in-game console and the server admin channels.
+// Commands run with the whole world, between the other systems of the frame.
+#[derive(Default, Clone, Debug)]
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = mpsc::channel();
+        app
+            .insert_resource(ConsoleQueue {
+                sender,
+                receiver: SyncCell::new(receiver),
+            })
+            .register_console_command(ConsoleCommand {
+                name: "help",
+                usage: "help",
+                help: "list every command",
+                run: help_command,
+            })
+            .add_systems(Update, run_console_commands);
+    }
+}
+
+// In-game console window, toggled with the backquote key
+#[derive(Default, Clone, Debug)]
+pub struct ConsoleWindowPlugin;
+
+impl Plugin for ConsoleWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ConsoleWindow>()
+            .register_ui_window(UiWindow {
+                id: CONSOLE_WINDOW,
+                title: "Console",
+                shortcut: Some(KeyCode::Backquote),
+                open: false,
+            })
+            .add_systems(Update, console_window_ui);
+    }
+}
+
+// What a command prints on success, or why it failed
+pub type CommandResult = Result<String, String>;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    // Gets the arguments after the command name
+    pub run: fn(&mut World, &[&str]) -> CommandResult,
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    pub fn get(&self, name: &str) -> Option<ConsoleCommand> {
+        self.commands.get(name).copied()
+    }
+}
+
+pub trait ConsoleAppExt {
+    fn register_console_command(&mut self, command: ConsoleCommand) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn register_console_command(&mut self, command: ConsoleCommand) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .commands
+            .insert(command.name, command);
+        self
+    }
+}
+
+// Where the output of a command goes
+#[derive(Debug, Clone)]
+pub enum ConsoleReply {
+    Window,
+    Stdout,
+    Channel(Sender<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsoleRequest {
+    pub line: String,
+    pub reply: ConsoleReply,
+}
+
+// Commands waiting to run. Other threads (stdin, admin connections) keep a clone of `sender`.
+#[derive(Resource)]
+pub struct ConsoleQueue {
+    sender: Sender<ConsoleRequest>,
+    receiver: SyncCell<Receiver<ConsoleRequest>>,
+}
+
+impl ConsoleQueue {
+    pub fn sender(&self) -> Sender<ConsoleRequest> {
+        self.sender.clone()
+    }
+
+    pub fn submit(&self, line: impl Into<String>, reply: ConsoleReply) {
+        let _ = self.sender.send(ConsoleRequest { line: line.into(), reply });
+    }
+}
+
+// Parse and run one command line
+pub fn execute(world: &mut World, line: &str) -> CommandResult {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((name, args)) = words.split_first() else {
+        return Ok(String::new());
+    };
+    let command = world.get_resource::<ConsoleCommands>().and_then(|commands| commands.get(name));
+    match command {
+        Some(command) => (command.run)(world, args),
+        None => Err(format!("unknown command `{}`, try `help`", name)),
+    }
+}
+
+fn help_command(world: &mut World, _args: &[&str]) -> CommandResult {
+    let commands = world.resource::<ConsoleCommands>();
+    Ok(commands.commands.values()
+        .map(|command| format!("{:<26} {}", command.usage, command.help))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn run_console_commands(world: &mut World) {
+    let requests: Vec<ConsoleRequest> = world.resource_mut::<ConsoleQueue>().receiver.get().try_iter().collect();
+    for request in requests {
+        info!("Console: {}", request.line);
+        let output = match execute(world, &request.line) {
+            Ok(output) => output,
+            Err(err) => format!("error: {}", err),
+        };
+        match request.reply {
+            ConsoleReply::Window => {
+                if let Some(mut window) = world.get_resource_mut::<ConsoleWindow>() {
+                    window.push(format!("> {}", request.line));
+                    window.push(output);
+                }
+            }
+            ConsoleReply::Stdout => println!("{}", output),
+            ConsoleReply::Channel(sender) => {
+                let _ = sender.send(output);
+            }
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleWindow {
+    input: String,
+    history: Vec<String>,
+}
+
+impl ConsoleWindow {
+    fn push(&mut self, output: String) {
+        self.history.extend(output.lines().map(str::to_string));
+        let excess = self.history.len().saturating_sub(CONSOLE_HISTORY);
+        self.history.drain(..excess);
+    }
+}
+
+fn console_window_ui(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut console: ResMut<ConsoleWindow>,
+    queue: Res<ConsoleQueue>,
+) {
+    let Some(window) = windows.window(CONSOLE_WINDOW) else {
+        return;
+    };
+    let console = &mut *console;
+    window
+        .default_width(460.0)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &console.history {
+                        ui.monospace(line);
+                    }
+                });
+            let response = ui.add(egui::TextEdit::singleline(&mut console.input)
+                .desired_width(f32::INFINITY)
+                .hint_text("help"));
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                let line = std::mem::take(&mut console.input);
+                if !line.trim().is_empty() {
+                    queue.submit(line, ConsoleReply::Window);
+                }
+                response.request_focus();
+            }
+        });
+}
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+
+const CREATIVE_WINDOW: &str = "creative";
+
+#[derive(Default, Clone, Debug)]
+pub struct CreativePlugin;
+
+impl Plugin for CreativePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<CreativeMode>()
+            .register_ui_window(UiWindow {
+                id: CREATIVE_WINDOW,
+                title: "Creative",
+                shortcut: None,
+                open: false,
+            })
+            .add_systems(Update, (toggle_creative, creative_ui_system));
+    }
+}
+
+// Cheats for building and exploring; a world where it was ever turned on is saved as creative
+#[derive(Resource, Debug, Clone)]
+pub struct CreativeMode {
+    pub enabled: bool,
+    // Space / Left Ctrl move the player up and down instead of walking on the ground
+    pub fly: bool,
+    // Flying goes through the terrain
+    pub noclip: bool,
+    // Placing objects costs no items
+    pub instant_build: bool,
+    // Set once creative was enabled, kept in the save
+    pub used: bool,
+}
+
+impl Default for CreativeMode {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fly: true,
+            noclip: false,
+            instant_build: true,
+            used: false,
+        }
+    }
+}
+
+impl CreativeMode {
+    pub fn flying(&self) -> bool {
+        self.enabled && self.fly
+    }
+
+    pub fn noclip(&self) -> bool {
+        self.enabled && self.fly && self.noclip
+    }
+
+    pub fn free_building(&self) -> bool {
+        self.enabled && self.instant_build
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if enabled && !self.used {
+            self.used = true;
+            info!("Creative mode enabled, this world is now marked as creative");
+        }
+    }
+}
+
+// F6 toggles creative mode
+fn toggle_creative(
+    input: Res<ButtonInput<KeyCode>>,
+    mut creative: ResMut<CreativeMode>,
+) {
+    if input.just_pressed(KeyCode::F6) {
+        let enabled = !creative.enabled;
+        creative.set_enabled(enabled);
+        info!("Creative mode {}", if enabled { "on" } else { "off" });
+    }
+}
+
+fn creative_ui_system(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut creative: ResMut<CreativeMode>,
+) {
+    let Some(window) = windows.window(CREATIVE_WINDOW) else {
+        return;
+    };
+    let current = creative.bypass_change_detection();
+    let mut enabled = current.enabled;
+    let mut changed = false;
+
+    window.show(contexts.ctx_mut(), |ui| {
+        changed |= ui.checkbox(&mut enabled, "Enabled [F6]").changed();
+        ui.add_enabled_ui(enabled, |ui| {
+            changed |= ui.checkbox(&mut current.fly, "Fly").changed();
+            ui.add_enabled_ui(current.fly, |ui| {
+                changed |= ui.checkbox(&mut current.noclip, "Noclip").changed();
+            });
+            changed |= ui.checkbox(&mut current.instant_build, "Instant build (no costs)").changed();
+        });
+        if current.used {
+            ui.label("This world is marked as creative");
+        }
+    });
+
+    if changed {
+        current.set_enabled(enabled);
+        creative.set_changed();
+    }
+}
@@ -0,0 +1,50 @@
+use bevy::math::FloatExt;
+use crate::biome::{self, Biome};
+
+// Distance from the world origin where the distance part of the danger peaks
+const DANGER_RADIUS: f32 = 1500.0;
+// Share of the danger coming from distance, the rest comes from the biome
+const DISTANCE_WEIGHT: f32 = 0.7;
+
+fn biome_danger(biome: Biome) -> f32 {
+    match biome {
+        Biome::Temperate => 0.0,
+        Biome::Desert => 0.6,
+        Biome::Tundra => 1.0,
+        Biome::Savanna => 0.4,
+        Biome::Rainforest => 0.7,
+    }
+}
+
+// How hostile a region is, 0 around the spawn up to 1 far away in harsh biomes
+pub fn danger_level(world_x: f32, world_z: f32) -> f32 {
+    let distance = (world_x * world_x + world_z * world_z).sqrt();
+    let distance_danger = (distance / DANGER_RADIUS).min(1.0);
+
+    // Fade between biomes like the terrain colors do
+    let sample = biome::sample(world_x, world_z);
+    let biome_danger = biome_danger(sample.biome).lerp(biome_danger(sample.neighbor), sample.blend);
+
+    // Harsh biomes near the spawn stay mild, the biome part grows with distance
+    let danger = distance_danger * DISTANCE_WEIGHT + biome_danger * (1.0 - DISTANCE_WEIGHT) * distance_danger.sqrt();
+    danger.clamp(0.0, 1.0)
+}
+
+pub fn danger_label(danger: f32) -> &'static str {
+    match danger {
+        d if d < 0.25 => "Safe",
+        d if d < 0.5 => "Moderate",
+        d if d < 0.75 => "Dangerous",
+        _ => "Deadly",
+    }
+}
+
+// Multiplier on how boldly wildlife behaves, 1 at the spawn up to 2
+pub fn wildlife_aggressiveness(danger: f32) -> f32 {
+    1.0 + danger
+}
+
+// Multiplier on how much there is to find, 1 at the spawn up to 2.5
+pub fn resource_richness(danger: f32) -> f32 {
+    1.0 + danger * 1.5
+}
@@ -0,0 +1,145 @@
+use bevy::pbr::light_consts::lux;
+use bevy::prelude::*;
+use bevy_atmosphere::prelude::*;
+use crate::asset_registry::GameState;
+
+// Real seconds for a full in-game day
+const DAY_LENGTH_SECONDS: f32 = 600.0;
+const START_HOUR: f32 = 8.0;
+// Hours between sunrise and sunset, and how high the sun gets at noon, until the season changes them
+const DAYLIGHT_HOURS: f32 = 12.0;
+const SUN_ELEVATION_DEGREES: f32 = 70.0;
+// Horizontal direction the sun is seen in at noon, tilted off the east-west arc
+const NOON_TILT: f32 = 0.3;
+// Updating the sky every frame is slow, it is refreshed at this interval instead
+const SKY_UPDATE_INTERVAL: f32 = 0.1;
+const DAY_AMBIENT_BRIGHTNESS: f32 = 400.0;
+const NIGHT_AMBIENT_BRIGHTNESS: f32 = 15.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct DayNightPlugin;
+
+impl Plugin for DayNightPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TimeOfDay>()
+            .init_resource::<AtmosphereModel>()
+            .insert_resource(SkyUpdateTimer(Timer::from_seconds(SKY_UPDATE_INTERVAL, TimerMode::Repeating)))
+            .add_systems(Update, (
+                advance_time_of_day.run_if(in_state(GameState::Playing)),
+                update_sun,
+            ).chain());
+    }
+}
+
+// Marks the directional light following the time of day
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Resource)]
+pub struct TimeOfDay {
+    // 0..24
+    pub hour: f32,
+    pub day: u32,
+    pub day_length_seconds: f32,
+    pub paused: bool,
+    // Set by the season: hours of daylight centered on noon, and the sun's height at noon
+    pub daylight_hours: f32,
+    pub sun_elevation: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            hour: START_HOUR,
+            day: 1,
+            day_length_seconds: DAY_LENGTH_SECONDS,
+            paused: false,
+            daylight_hours: DAYLIGHT_HOURS,
+            sun_elevation: SUN_ELEVATION_DEGREES.to_radians(),
+        }
+    }
+}
+
+impl TimeOfDay {
+    pub fn sunrise(&self) -> f32 {
+        12.0 - self.daylight_hours / 2.0
+    }
+
+    pub fn sunset(&self) -> f32 {
+        12.0 + self.daylight_hours / 2.0
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.hour < self.sunrise() || self.hour >= self.sunset()
+    }
+
+    // Direction pointing toward the sun; it rises in the east at sunrise, peaks at noon at the
+    // season's elevation and sets in the west. Night hours run the same arc under the horizon.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = if self.is_night() {
+            let night_hours = 24.0 - self.daylight_hours;
+            std::f32::consts::PI * (1.0 + (self.hour - self.sunset()).rem_euclid(24.0) / night_hours)
+        } else {
+            std::f32::consts::PI * (self.hour - self.sunrise()) / self.daylight_hours
+        };
+        let elevation = angle.sin() * self.sun_elevation;
+        let horizontal = Vec2::new(angle.cos(), NOON_TILT).normalize() * elevation.cos();
+        Vec3::new(horizontal.x, elevation.sin(), horizontal.y)
+    }
+
+    // 0 at night, 1 with the sun high in the sky
+    pub fn daylight(&self) -> f32 {
+        self.sun_direction().y.max(0.0).sqrt()
+    }
+
+    // Move time forward, rolling over to the next day
+    pub fn advance_hours(&mut self, hours: f32) {
+        self.hour += hours;
+        while self.hour >= 24.0 {
+            self.hour -= 24.0;
+            self.day += 1;
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SkyUpdateTimer(Timer);
+
+pub fn advance_time_of_day(
+    time: Res<Time>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) {
+    if time_of_day.paused {
+        return;
+    }
+    let hours = time.delta_secs() / time_of_day.day_length_seconds * 24.0;
+    time_of_day.advance_hours(hours);
+}
+
+fn update_sun(
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    mut timer: ResMut<SkyUpdateTimer>,
+    mut atmosphere: ResMut<AtmosphereModel>,
+    mut ambient: ResMut<AmbientLight>,
+    mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    timer.0.tick(time.delta());
+    if !timer.0.finished() {
+        return;
+    }
+
+    let direction = time_of_day.sun_direction();
+    let daylight = time_of_day.daylight();
+
+    if let Some(nishita) = atmosphere.to_mut::<Nishita>() {
+        nishita.sun_position = direction;
+    }
+    ambient.brightness = NIGHT_AMBIENT_BRIGHTNESS.lerp(DAY_AMBIENT_BRIGHTNESS, daylight);
+
+    for (mut transform, mut light) in sun_query.iter_mut() {
+        *transform = Transform::from_translation(direction).looking_at(Vec3::ZERO, Vec3::Y);
+        light.illuminance = daylight * daylight * lux::AMBIENT_DAYLIGHT;
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin, RegisterDiagnostic,
+};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::BoxedLayer;
+use bevy::prelude::*;
+use bevy::utils::synccell::SyncCell;
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use crate::client::ChunkManager;
+use crate::server::ServerChunks;
+
+pub const LOADED_CHUNKS: DiagnosticPath = DiagnosticPath::const_new("chunks/loaded");
+pub const CHUNK_GENERATION_MS: DiagnosticPath = DiagnosticPath::const_new("chunks/generation_ms");
+pub const SERVER_CHUNKS: DiagnosticPath = DiagnosticPath::const_new("server/chunks");
+
+// Set before the app is built when `--trace` is passed
+static TRACE_OUTPUT: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn enable_trace_output(path: PathBuf) {
+    let _ = TRACE_OUTPUT.set(path);
+}
+
+pub fn trace_enabled() -> bool {
+    TRACE_OUTPUT.get().is_some()
+}
+
+// Flushes the chrome trace file when the app is dropped
+#[derive(Resource)]
+struct TraceFlushGuard(#[allow(dead_code)] SyncCell<FlushGuard>);
+
+// `LogPlugin::custom_layer` hook writing spans to a chrome://tracing / Perfetto json file
+pub fn trace_layer(app: &mut App) -> Option<BoxedLayer> {
+    let path = TRACE_OUTPUT.get()?;
+    let (layer, guard) = ChromeLayerBuilder::new()
+        .file(path)
+        .include_args(true)
+        .build();
+    app.insert_resource(TraceFlushGuard(SyncCell::new(guard)));
+    eprintln!("Writing trace to {}", path.display());
+    Some(layer.boxed())
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct GameDiagnosticsPlugin;
+
+impl Plugin for GameDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(FrameTimeDiagnosticsPlugin)
+            .register_diagnostic(Diagnostic::new(LOADED_CHUNKS))
+            .register_diagnostic(Diagnostic::new(CHUNK_GENERATION_MS).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(SERVER_CHUNKS))
+            .add_systems(Update, record_chunk_counts);
+
+        if trace_enabled() {
+            app.add_plugins(LogDiagnosticsPlugin::default());
+        }
+    }
+}
+
+fn record_chunk_counts(
+    mut diagnostics: Diagnostics,
+    chunk_manager: Res<ChunkManager>,
+    server_chunks: Option<Res<ServerChunks>>,
+) {
+    diagnostics.add_measurement(&LOADED_CHUNKS, || chunk_manager.loaded_chunks.len() as f64);
+    if let Some(server_chunks) = server_chunks {
+        diagnostics.add_measurement(&SERVER_CHUNKS, || server_chunks.chunks.len() as f64);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::avatar::Avatar;
+use crate::biome;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::inventory::Inventory;
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::quest::{ItemCollected, QuestLog};
+use crate::ron_asset::RonAssetPlugin;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::vendor::Vendor;
+use crate::water::sea_level;
+
+const VILLAGES_PATH: &str = "npcs/villages.npcs.ron";
+const VILLAGES: &str = "villages";
+const TALK_RADIUS: f32 = 2.5;
+// Walking further than this from the NPC ends the conversation
+const LEAVE_DISTANCE: f32 = 5.0;
+// Villages are moved to the nearest dry land within this distance of their center
+const VILLAGE_SEARCH_RADIUS: f32 = 200.0;
+const DRY_LAND_MARGIN: f32 = 0.5;
+
+// Villagers standing around the villages listed in `npcs/villages.npcs.ron`. Talking to one
+// opens a dialogue box walking their RON dialogue tree, whose choices can trade items and
+// hand out quests.
+#[derive(Default, Clone, Debug)]
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<Villages>::new(&["npcs.ron"]))
+            .add_plugins(RonAssetPlugin::<DialogueTree>::new(&["dialogue.ron"]))
+            .preload_asset::<Villages>(VILLAGES, VILLAGES_PATH)
+            .init_resource::<Conversation>()
+            .add_event::<DialogueActionTaken>()
+            .add_systems(Update, (
+                spawn_villages,
+                start_conversations,
+                end_distant_conversations,
+                dialogue_ui,
+                apply_dialogue_actions,
+            ).chain());
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VillagerDefinition {
+    pub name: String,
+    // From the village's center, in meters
+    pub offset: (f32, f32),
+    // Dialogue tree asset, like "dialogue/maren.dialogue.ron"
+    pub dialogue: String,
+    pub color: (f32, f32, f32),
+    // Sells and buys goods from the stock table of the village's biome
+    #[serde(default)]
+    pub vendor: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VillageDefinition {
+    pub name: String,
+    // World (x, z) the village is built around
+    pub center: (f32, f32),
+    pub villagers: Vec<VillagerDefinition>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct Villages {
+    pub villages: Vec<VillageDefinition>,
+}
+
+// Something a dialogue choice does besides moving the conversation along
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum DialogueAction {
+    // Give the NPC items for others; the choice is greyed out without the items to give
+    Trade { give: Vec<(String, u32)>, receive: Vec<(String, u32)> },
+    // Accept a quest from the quest book offered by this NPC
+    AcceptQuest(String),
+    // Open the shop of a vendor
+    OpenShop,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueChoice {
+    pub text: String,
+    // Node the conversation goes to, None ends it
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub action: Option<DialogueAction>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DialogueNode {
+    pub text: String,
+    pub choices: Vec<DialogueChoice>,
+}
+
+// What an NPC can say, as nodes of text linked by the player's choices
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct DialogueTree {
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Npc {
+    pub name: String,
+    pub dialogue: Handle<DialogueTree>,
+}
+
+// Sent when a choice with an action is picked, for the systems hooking into dialogues
+#[derive(Event, Debug, Clone)]
+pub struct DialogueActionTaken {
+    pub npc: Entity,
+    pub action: DialogueAction,
+}
+
+// The dialogue box: the NPC being talked to and the node shown
+#[derive(Resource, Default, Debug)]
+struct Conversation {
+    npc: Option<Entity>,
+    node: String,
+}
+
+fn spawn_villages(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Villages>>,
+    registry: Res<AssetRegistry>,
+    asset_server: Res<AssetServer>,
+    villages: Res<Assets<Villages>>,
+    terrain_edits: Res<TerrainEdits>,
+    npc_query: Query<Entity, With<Npc>>,
+) {
+    let Some(handle) = registry.get::<Villages>(VILLAGES) else {
+        return;
+    };
+    let loaded = events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } if *id == handle.id())
+    });
+    let Some(villages) = loaded.then(|| villages.get(&handle)).flatten() else {
+        return;
+    };
+
+    // Edited villages are spawned again from scratch
+    for entity in npc_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for village in &villages.villages {
+        let wanted = Vec2::new(village.center.0, village.center.1);
+        let center = terrain::find_dry_land(wanted, sea_level(wanted.x, wanted.y) + DRY_LAND_MARGIN, VILLAGE_SEARCH_RADIUS)
+            .unwrap_or(wanted);
+        for villager in &village.villagers {
+            let position = center + Vec2::new(villager.offset.0, villager.offset.1);
+            let ground = terrain_edits.height(position.x, position.y);
+            let (r, g, b) = villager.color;
+            let mut npc = commands.spawn((
+                Npc { name: villager.name.clone(), dialogue: asset_server.load(&villager.dialogue) },
+                Avatar { color: Color::srgb(r, g, b) },
+                Interactable { prompt: format!("Talk to {}", villager.name), radius: TALK_RADIUS },
+                Transform::from_xyz(position.x, ground + PLAYER_HALF_HEIGHT, position.y),
+            ));
+            if villager.vendor {
+                npc.insert(Vendor::new(biome::biome_at(center.x, center.y)));
+            }
+        }
+        info!("Village {} with {} villagers at ({:.0}, {:.0})", village.name, village.villagers.len(), center.x, center.y);
+    }
+}
+
+fn start_conversations(
+    mut interactions: EventReader<InteractEvent>,
+    mut conversation: ResMut<Conversation>,
+    trees: Res<Assets<DialogueTree>>,
+    npc_query: Query<&Npc>,
+) {
+    for event in interactions.read() {
+        let Ok(npc) = npc_query.get(event.target) else {
+            continue;
+        };
+        let Some(tree) = trees.get(&npc.dialogue) else {
+            warn!("{} has nothing to say, their dialogue isn't loaded", npc.name);
+            continue;
+        };
+        conversation.npc = Some(event.target);
+        conversation.node = tree.start.clone();
+    }
+}
+
+fn end_distant_conversations(
+    mut conversation: ResMut<Conversation>,
+    player_query: Query<&Transform, With<Player>>,
+    npc_query: Query<&Transform, With<Npc>>,
+) {
+    let Some(npc) = conversation.npc else {
+        return;
+    };
+    let near = match (player_query.get_single(), npc_query.get(npc)) {
+        (Ok(player), Ok(npc)) => player.translation.distance(npc.translation) <= LEAVE_DISTANCE,
+        _ => false,
+    };
+    if !near {
+        conversation.npc = None;
+    }
+}
+
+fn dialogue_ui(
+    mut contexts: EguiContexts,
+    mut conversation: ResMut<Conversation>,
+    mut actions: EventWriter<DialogueActionTaken>,
+    inventory: Res<Inventory>,
+    trees: Res<Assets<DialogueTree>>,
+    npc_query: Query<&Npc>,
+) {
+    let Some(entity) = conversation.npc else {
+        return;
+    };
+    let Ok(npc) = npc_query.get(entity) else {
+        conversation.npc = None;
+        return;
+    };
+    let Some(node) = trees.get(&npc.dialogue).and_then(|tree| tree.nodes.get(&conversation.node)) else {
+        warn!("{}'s dialogue has no node {}", npc.name, conversation.node);
+        conversation.npc = None;
+        return;
+    };
+
+    let mut picked = None;
+    egui::Window::new(&npc.name)
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -120.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(&node.text);
+            ui.separator();
+            for choice in &node.choices {
+                let affordable = match &choice.action {
+                    Some(DialogueAction::Trade { give, .. }) => {
+                        inventory.has_all(&give.iter().map(|(item, count)| (item.as_str(), *count)).collect::<Vec<_>>())
+                    }
+                    _ => true,
+                };
+                if ui.add_enabled(affordable, egui::Button::new(&choice.text)).clicked() {
+                    picked = Some(choice.clone());
+                }
+            }
+        });
+
+    let Some(choice) = picked else {
+        return;
+    };
+    if let Some(action) = choice.action {
+        actions.send(DialogueActionTaken { npc: entity, action });
+    }
+    match choice.next {
+        Some(next) => conversation.node = next,
+        None => conversation.npc = None,
+    }
+}
+
+fn apply_dialogue_actions(
+    mut actions: EventReader<DialogueActionTaken>,
+    mut inventory: ResMut<Inventory>,
+    mut quest_log: ResMut<QuestLog>,
+    mut collected: EventWriter<ItemCollected>,
+) {
+    for DialogueActionTaken { action, .. } in actions.read() {
+        match action {
+            DialogueAction::Trade { give, receive } => {
+                let give: Vec<(&str, u32)> = give.iter().map(|(item, count)| (item.as_str(), *count)).collect();
+                if !inventory.take_all(&give) {
+                    continue;
+                }
+                for (item, count) in receive {
+                    collected.send(ItemCollected { item: item.clone(), count: *count });
+                }
+            }
+            DialogueAction::AcceptQuest(quest_id) => {
+                if quest_log.accept(quest_id) {
+                    info!("Quest accepted: {}", quest_id);
+                }
+            }
+            // Handled by the vendors
+            DialogueAction::OpenShop => {}
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, MonitorSelection, PresentMode, PrimaryWindow, WindowFocused, WindowMode, WindowResized};
+use bevy::winit::{UpdateMode, WinitSettings};
+use crate::settings::{DisplayMode, DisplaySettings, GameSettings};
+
+#[derive(Default, Clone, Debug)]
+pub struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<FrameLimiter>()
+            .init_resource::<BackgroundState>()
+            .add_systems(Update, (
+                apply_display_settings,
+                apply_background_fps,
+                pause_on_focus_loss,
+                stop_rendering_when_minimized,
+            ))
+            .add_systems(Last, limit_frame_rate);
+    }
+}
+
+// What was stopped while the window was in the background, to be started again
+#[derive(Resource, Default)]
+struct BackgroundState {
+    paused: bool,
+    // Cameras turned off while the window is minimized
+    cameras: Vec<Entity>,
+}
+
+// Time the last frame ended, used to sleep out the rest of the frame when the FPS is capped
+#[derive(Resource)]
+struct FrameLimiter {
+    last_frame: Instant,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self { last_frame: Instant::now() }
+    }
+}
+
+// Push the display settings to the primary window whenever they change
+fn apply_display_settings(
+    settings: Res<GameSettings>,
+    mut applied: Local<Option<DisplaySettings>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() || applied.as_ref() == Some(&settings.display) {
+        return;
+    }
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+
+    let target = &settings.display;
+    let (width, height) = target.resolution;
+    window.mode = match target.mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Borderless => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        DisplayMode::Fullscreen => WindowMode::SizedFullscreen(MonitorSelection::Current),
+    };
+    if target.mode != DisplayMode::Borderless {
+        window.resolution.set_physical_resolution(width, height);
+    }
+    window.present_mode = if target.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync };
+
+    info!(
+        "Display: {} {}x{}, vsync {}, fps cap {}",
+        target.mode.label(), width, height, target.vsync, target.fps_cap
+    );
+    *applied = Some(target.clone());
+}
+
+// Frames slow down to the background rate while another window has focus
+fn apply_background_fps(
+    settings: Res<GameSettings>,
+    winit_settings: Option<ResMut<WinitSettings>>,
+) {
+    let Some(mut winit_settings) = winit_settings else {
+        return;
+    };
+    if !settings.is_changed() {
+        return;
+    }
+    let fps = settings.display.background_fps;
+    let mode = if fps > 0 {
+        UpdateMode::reactive_low_power(Duration::from_secs_f64(1.0 / fps as f64))
+    } else {
+        UpdateMode::Continuous
+    };
+    if winit_settings.unfocused_mode != mode {
+        winit_settings.unfocused_mode = mode;
+    }
+}
+
+// Losing focus frees the cursor and lets go of held mouse buttons, so the camera doesn't keep
+// turning, and pauses the game when the setting asks for it until focus comes back
+fn pause_on_focus_loss(
+    settings: Res<GameSettings>,
+    mut focus_events: EventReader<WindowFocused>,
+    mut state: ResMut<BackgroundState>,
+    mut time: ResMut<Time<Virtual>>,
+    mut mouse_buttons: ResMut<ButtonInput<MouseButton>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Some(focused) = focus_events.read()
+        .filter(|event| window_query.contains(event.window))
+        .last()
+        .map(|event| event.focused)
+    else {
+        return;
+    };
+    if focused {
+        if state.paused {
+            state.paused = false;
+            time.unpause();
+            info!("Window focused, game resumed");
+        }
+        return;
+    }
+
+    mouse_buttons.reset_all();
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.cursor_options.grab_mode = CursorGrabMode::None;
+        window.cursor_options.visible = true;
+    }
+    if settings.display.pause_on_unfocus && !time.is_paused() {
+        state.paused = true;
+        time.pause();
+        info!("Window lost focus, game paused");
+    }
+}
+
+// A minimized window has no size; its cameras stop rendering until it is restored
+fn stop_rendering_when_minimized(
+    mut resize_events: EventReader<WindowResized>,
+    mut state: ResMut<BackgroundState>,
+    window_query: Query<(), With<PrimaryWindow>>,
+    mut camera_query: Query<(Entity, &mut Camera)>,
+) {
+    let Some(minimized) = resize_events.read()
+        .filter(|event| window_query.contains(event.window))
+        .last()
+        .map(|event| event.width <= 0.0 || event.height <= 0.0)
+    else {
+        return;
+    };
+    if minimized && state.cameras.is_empty() {
+        for (entity, mut camera) in camera_query.iter_mut() {
+            if camera.is_active {
+                camera.is_active = false;
+                state.cameras.push(entity);
+            }
+        }
+        debug!("Window minimized, {} cameras stopped", state.cameras.len());
+    } else if !minimized {
+        for entity in std::mem::take(&mut state.cameras) {
+            if let Ok((_, mut camera)) = camera_query.get_mut(entity) {
+                camera.is_active = true;
+            }
+        }
+    }
+}
+
+fn limit_frame_rate(
+    settings: Res<GameSettings>,
+    mut limiter: ResMut<FrameLimiter>,
+) {
+    let fps_cap = settings.display.fps_cap;
+    if fps_cap > 0 {
+        let target = Duration::from_secs_f64(1.0 / fps_cap as f64);
+        let elapsed = limiter.last_frame.elapsed();
+        if elapsed < target {
+            std::thread::sleep(target - elapsed);
+        }
+    }
+    limiter.last_frame = Instant::now();
+}
@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use crate::collider::BoxCollider;
+use crate::interaction::{InteractEvent, Interactable};
+use crate::placement::{door_size, ObjectPlaced, PlaceableKind, PlacedObject};
+use crate::projectile::Hittable;
+use crate::save::{LoadedSave, SaveRequested};
+
+const DOOR_INTERACT_RADIUS: f32 = 2.0;
+const OPEN_ANGLE: f32 = std::f32::consts::FRAC_PI_2;
+// Radians per second while swinging
+const SWING_SPEED: f32 = 2.5;
+const POST_SIZE: f32 = 0.15;
+
+#[derive(Default, Clone, Debug)]
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, (setup_door_assets, spawn_saved_doors).chain())
+            .add_systems(Update, (
+                spawn_placed_doors,
+                use_doors,
+                swing_doors,
+            ).chain());
+    }
+}
+
+// Door or gate placed in the world. The entity sits at the middle of the doorway,
+// the panel turns around a hinge on its left side.
+#[derive(Component)]
+pub struct Door {
+    pub kind: PlaceableKind,
+    pub open: bool,
+    // Current swing, eased towards open or closed
+    angle: f32,
+    hinge: Entity,
+}
+
+#[derive(Resource)]
+struct DoorAssets {
+    post_mesh: Handle<Mesh>,
+    wood_material: Handle<StandardMaterial>,
+}
+
+fn setup_door_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(DoorAssets {
+        post_mesh: meshes.add(Cuboid::from_length(1.0)),
+        wood_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.45, 0.3, 0.18),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+    });
+}
+
+fn prompt(kind: PlaceableKind, open: bool) -> String {
+    format!("{} {}", if open { "Close" } else { "Open" }, kind.label().to_lowercase())
+}
+
+fn target_angle(open: bool) -> f32 {
+    if open { OPEN_ANGLE } else { 0.0 }
+}
+
+fn spawn_door(
+    commands: &mut Commands,
+    assets: &DoorAssets,
+    meshes: &mut Assets<Mesh>,
+    kind: PlaceableKind,
+    position: Vec3,
+    rotation: Quat,
+    open: bool,
+) -> Entity {
+    let size = door_size(kind);
+    let angle = target_angle(open);
+    let post_height = size.y + 0.1;
+
+    let mut hinge = Entity::PLACEHOLDER;
+    let door = commands.spawn((
+        Transform::from_translation(position).with_rotation(rotation),
+        Visibility::Inherited,
+        PlacedObject { kind },
+        Hittable { radius: kind.footprint() },
+        Interactable {
+            prompt: prompt(kind, open),
+            radius: DOOR_INTERACT_RADIUS,
+        },
+    )).with_children(|parent| {
+        // Posts on both sides of the doorway
+        for side in [-1.0, 1.0] {
+            let x = side * (size.x / 2.0 + POST_SIZE / 2.0);
+            parent.spawn((
+                Mesh3d(assets.post_mesh.clone()),
+                MeshMaterial3d(assets.wood_material.clone()),
+                Transform::from_xyz(x, post_height / 2.0, 0.0)
+                    .with_scale(Vec3::new(POST_SIZE, post_height, POST_SIZE)),
+                BoxCollider { half_size: Vec3::new(POST_SIZE, post_height, POST_SIZE) / 2.0 },
+            ));
+        }
+        hinge = parent.spawn((
+            Transform::from_xyz(-size.x / 2.0, 0.0, 0.0).with_rotation(Quat::from_rotation_y(angle)),
+            Visibility::Inherited,
+        )).with_children(|hinge| {
+            // The collider sits on the panel so it swings along with it
+            hinge.spawn((
+                Mesh3d(meshes.add(Cuboid::from_size(size))),
+                MeshMaterial3d(assets.wood_material.clone()),
+                Transform::from_xyz(size.x / 2.0, size.y / 2.0, 0.0),
+                BoxCollider { half_size: size / 2.0 },
+            ));
+        }).id();
+    }).id();
+
+    commands.entity(door).insert(Door { kind, open, angle, hinge });
+    door
+}
+
+fn spawn_saved_doors(
+    mut commands: Commands,
+    assets: Res<DoorAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    save: Res<LoadedSave>,
+) {
+    for door in &save.0.doors {
+        spawn_door(&mut commands, &assets, &mut meshes, door.kind, door.position, door.rotation, door.open);
+    }
+}
+
+fn spawn_placed_doors(
+    mut commands: Commands,
+    mut placed: EventReader<ObjectPlaced>,
+    assets: Res<DoorAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for event in placed.read() {
+        if matches!(event.kind, PlaceableKind::Door | PlaceableKind::Gate) {
+            spawn_door(&mut commands, &assets, &mut meshes, event.kind, event.position, event.rotation, false);
+        }
+    }
+}
+
+// Opening or closing a door is saved right away so the world keeps its state
+fn use_doors(
+    mut interactions: EventReader<InteractEvent>,
+    mut door_query: Query<(&mut Door, &mut Interactable)>,
+    mut save_requests: EventWriter<SaveRequested>,
+) {
+    for event in interactions.read() {
+        let Ok((mut door, mut interactable)) = door_query.get_mut(event.target) else {
+            continue;
+        };
+        door.open = !door.open;
+        interactable.prompt = prompt(door.kind, door.open);
+        save_requests.send(SaveRequested { reason: "door" });
+    }
+}
+
+fn swing_doors(
+    time: Res<Time>,
+    mut door_query: Query<&mut Door>,
+    mut hinge_query: Query<&mut Transform>,
+) {
+    for mut door in door_query.iter_mut() {
+        let target = target_angle(door.open);
+        if door.angle == target {
+            continue;
+        }
+        let step = SWING_SPEED * time.delta_secs();
+        door.angle += (target - door.angle).clamp(-step, step);
+        if let Ok(mut transform) = hinge_query.get_mut(door.hinge) {
+            transform.rotation = Quat::from_rotation_y(door.angle);
+        }
+    }
+}
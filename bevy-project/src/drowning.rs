@@ -0,0 +1,107 @@
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use crate::audio_mixer::MixedAudio;
+use crate::camera::CameraSettings;
+use crate::creative::CreativeMode;
+use crate::player::{Health, Oxygen, Player, PlayerStance, RespawnPlayer, PLAYER_HALF_HEIGHT};
+use crate::settings::GameSettings;
+use crate::water::WaterQuery;
+
+// Seconds of breath regained per second with the head above water
+const OXYGEN_RECOVERY: f32 = 10.0;
+// Health lost each DROWNING_INTERVAL seconds once out of breath
+const DROWNING_DAMAGE: f32 = 10.0;
+const DROWNING_INTERVAL: f32 = 1.0;
+// Share of the breath left when the warning plays
+const LOW_OXYGEN: f32 = 0.25;
+const LOW_OXYGEN_SOUND: &str = "sounds/low_oxygen.ogg";
+const DROWNING_SOUND: &str = "sounds/drowning.ogg";
+const GASP_SOUND: &str = "sounds/gasp.ogg";
+
+// Breath runs out while the player's head is under water, comes back at the surface, and
+// running out of it hurts until they come up or drown
+#[derive(Default, Clone, Debug)]
+pub struct DrowningPlugin;
+
+impl Plugin for DrowningPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Breath>()
+            .add_systems(Update, update_oxygen);
+    }
+}
+
+#[derive(Resource, Default, Debug)]
+struct Breath {
+    submerged: bool,
+    warned: bool,
+    // Seconds until the next drowning damage
+    drowning_timer: f32,
+}
+
+fn play_cue(commands: &mut Commands, asset_server: &AssetServer, settings: &GameSettings, sound: &'static str) {
+    let volume = settings.audio.master_volume * settings.audio.effects_volume;
+    if volume > 0.0 {
+        commands.spawn((
+            AudioPlayer::<MixedAudio>(asset_server.load(sound)),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)),
+        ));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_oxygen(
+    mut commands: Commands,
+    time: Res<Time>,
+    water: WaterQuery,
+    camera_settings: Res<CameraSettings>,
+    creative: Res<CreativeMode>,
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+    mut breath: ResMut<Breath>,
+    mut respawn_events: EventWriter<RespawnPlayer>,
+    mut player_query: Query<(&Transform, &PlayerStance, &mut Oxygen, &mut Health), With<Player>>,
+) {
+    let Ok((transform, stance, mut oxygen, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    let dt = time.delta_secs();
+    let head = transform.translation - Vec3::Y * PLAYER_HALF_HEIGHT + Vec3::Y * camera_settings.eye_heights.for_stance(*stance);
+    let submerged = water.is_in_water(head) && !creative.enabled;
+
+    if !submerged {
+        // Coming up short of breath
+        if breath.submerged && breath.warned {
+            play_cue(&mut commands, &asset_server, &settings, GASP_SOUND);
+        }
+        *breath = Breath::default();
+        if !oxygen.is_full() {
+            oxygen.change(OXYGEN_RECOVERY * dt);
+        }
+        return;
+    }
+
+    breath.submerged = true;
+    oxygen.change(-dt);
+    if !breath.warned && oxygen.fraction() <= LOW_OXYGEN {
+        breath.warned = true;
+        play_cue(&mut commands, &asset_server, &settings, LOW_OXYGEN_SOUND);
+    }
+    if oxygen.current > 0.0 {
+        breath.drowning_timer = 0.0;
+        return;
+    }
+
+    breath.drowning_timer -= dt;
+    if breath.drowning_timer > 0.0 {
+        return;
+    }
+    breath.drowning_timer = DROWNING_INTERVAL;
+    health.change(-DROWNING_DAMAGE);
+    play_cue(&mut commands, &asset_server, &settings, DROWNING_SOUND);
+    if health.current <= 0.0 {
+        info!("Player drowned");
+        *breath = Breath::default();
+        respawn_events.send(RespawnPlayer);
+    }
+}
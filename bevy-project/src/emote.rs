@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use crate::avatar::AvatarPose;
+use crate::input_map::{Action, Actions};
+use crate::console::{CommandResult, ConsoleAppExt, ConsoleCommand};
+use crate::net::{ClientMessage, ServerConnection};
+use crate::player::Player;
+
+const WHEEL_RADIUS: f32 = 110.0;
+// The pointer must leave the center this far to pick an emote
+const WHEEL_DEADZONE: f32 = 24.0;
+
+// Hold G for a wheel of emotes, release over one to play it. The local avatar plays it
+// right away and the server relays it so other players see it too.
+#[derive(Default, Clone, Debug)]
+pub struct EmotePlugin;
+
+impl Plugin for EmotePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<PlayEmote>()
+            .init_resource::<EmoteWheel>()
+            .register_console_command(ConsoleCommand {
+                name: "emote",
+                usage: "emote <wave|sit|point>",
+                help: "play an emote on the player",
+                run: emote_command,
+            })
+            .add_systems(Update, (
+                emote_wheel,
+                play_local_emotes,
+            ).chain());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Emote {
+    Wave,
+    Sit,
+    Point,
+}
+
+impl Emote {
+    // Clockwise around the wheel, from the top
+    pub const ALL: [Emote; 3] = [Emote::Wave, Emote::Point, Emote::Sit];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Emote::Wave => "wave",
+            Emote::Sit => "sit",
+            Emote::Point => "point",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|emote| emote.name() == name)
+    }
+
+    // Seconds the emote plays for, None if it lasts until the player moves
+    pub fn duration(&self) -> Option<f32> {
+        match self {
+            Emote::Wave => Some(2.4),
+            Emote::Point => Some(2.0),
+            Emote::Sit => None,
+        }
+    }
+}
+
+// Emote for the local player
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayEmote(pub Emote);
+
+#[derive(Resource, Default)]
+struct EmoteWheel {
+    open: bool,
+    selected: Option<Emote>,
+}
+
+fn emote_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let [name] = args else {
+        return Err("usage: emote <wave|sit|point>".to_string());
+    };
+    let emote = Emote::from_name(name).ok_or_else(|| format!("unknown emote '{}'", name))?;
+    world.send_event(PlayEmote(emote));
+    Ok(format!("playing {}", emote.name()))
+}
+
+fn emote_wheel(
+    mut contexts: EguiContexts,
+    actions: Actions,
+    mut wheel: ResMut<EmoteWheel>,
+    mut emotes: EventWriter<PlayEmote>,
+) {
+    if actions.just_pressed(Action::EmoteWheel) {
+        wheel.open = true;
+        wheel.selected = None;
+    }
+    if !wheel.open {
+        return;
+    }
+    if !actions.pressed(Action::EmoteWheel) {
+        wheel.open = false;
+        if let Some(emote) = wheel.selected.take() {
+            emotes.send(PlayEmote(emote));
+        }
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let center = ctx.screen_rect().center();
+    // Emote in the direction of the pointer from the center, each owning an equal slice
+    wheel.selected = ctx.pointer_hover_pos()
+        .map(|pointer| pointer - center)
+        .filter(|offset| offset.length() > WHEEL_DEADZONE)
+        .map(|offset| {
+            let slice = std::f32::consts::TAU / Emote::ALL.len() as f32;
+            let angle = (offset.x.atan2(-offset.y) + slice / 2.0).rem_euclid(std::f32::consts::TAU);
+            Emote::ALL[(angle / slice) as usize % Emote::ALL.len()]
+        });
+
+    egui::Area::new(egui::Id::new("emote_wheel"))
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .interactable(false)
+        .show(ctx, |ui| {
+            let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(WHEEL_RADIUS * 2.0), egui::Sense::hover());
+            let painter = ui.painter();
+            painter.circle_filled(rect.center(), WHEEL_RADIUS, egui::Color32::from_black_alpha(150));
+            for (index, emote) in Emote::ALL.iter().enumerate() {
+                let angle = index as f32 / Emote::ALL.len() as f32 * std::f32::consts::TAU;
+                let position = rect.center() + egui::vec2(angle.sin(), -angle.cos()) * WHEEL_RADIUS * 0.62;
+                let selected = wheel.selected == Some(*emote);
+                if selected {
+                    painter.circle_filled(position, 30.0, egui::Color32::from_rgba_unmultiplied(255, 255, 255, 60));
+                }
+                painter.text(
+                    position,
+                    egui::Align2::CENTER_CENTER,
+                    emote.name(),
+                    egui::FontId::proportional(if selected { 18.0 } else { 15.0 }),
+                    egui::Color32::WHITE,
+                );
+            }
+        });
+}
+
+fn play_local_emotes(
+    mut emotes: EventReader<PlayEmote>,
+    mut connection: ResMut<ServerConnection>,
+    mut player_query: Query<&mut AvatarPose, With<Player>>,
+) {
+    for PlayEmote(emote) in emotes.read() {
+        if let Ok(mut pose) = player_query.get_single_mut() {
+            pose.play(*emote);
+        }
+        connection.send(&ClientMessage::Emote { emote: *emote });
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::biome::{self, Biome};
+use crate::camera::FreeCamera;
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::danger;
+use crate::day_night::TimeOfDay;
+use crate::ron_asset::RonAssetPlugin;
+use crate::player::Player;
+use crate::population::{Creature, Population, PopulationLimits};
+use crate::projectile::{HitTarget, Hittable, ProjectileHit, ProjectileKind};
+use crate::quest::ItemCollected;
+use crate::streaming::ChunkCoords;
+use crate::water::WaterQuery;
+use crate::world_rng::{RngPurpose, WorldRng};
+
+// Water must be at least this deep for a school to spawn there
+const MIN_SCHOOL_DEPTH: f32 = 2.0;
+// Distance kept from the floor and from the surface
+const DEPTH_MARGIN: f32 = 0.4;
+const SPAWN_TABLE_PATH: &str = "creatures/default.spawns.ron";
+const SPAWN_TABLE: &str = "creature_spawns";
+const SPAWN_ATTEMPTS: u32 = 16;
+
+const FISH_SPEED: f32 = 2.0;
+const FLEE_SPEED: f32 = 5.0;
+const NEIGHBOR_RADIUS: f32 = 3.0;
+const SEPARATION_RADIUS: f32 = 0.8;
+const FLEE_RADIUS: f32 = 6.0;
+const SEPARATION_WEIGHT: f32 = 2.5;
+const ALIGNMENT_WEIGHT: f32 = 1.0;
+const COHESION_WEIGHT: f32 = 0.8;
+const FLEE_WEIGHT: f32 = 6.0;
+const BOUNDS_WEIGHT: f32 = 4.0;
+const MAX_STEER: f32 = 6.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct FishPlugin;
+
+impl Plugin for FishPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<CreatureSpawnTable>::new(&["spawns.ron"]))
+            .preload_asset::<CreatureSpawnTable>(SPAWN_TABLE, SPAWN_TABLE_PATH)
+            .init_resource::<FishSchools>()
+            .add_systems(Startup, setup_fish_assets)
+            .add_systems(Update, (
+                forget_despawned_fish,
+                reset_schools_on_changes,
+                stream_fish_schools,
+                update_fish,
+                catch_hit_fish,
+            ).chain());
+    }
+}
+
+// When a species can be found
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActiveTime {
+    #[default]
+    Always,
+    Day,
+    Night,
+}
+
+impl ActiveTime {
+    pub fn allows(&self, night: bool) -> bool {
+        match self {
+            ActiveTime::Always => true,
+            ActiveTime::Day => !night,
+            ActiveTime::Night => night,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnRule {
+    pub species: String,
+    // Groups per chunk with deep enough water
+    pub density: u32,
+    pub group_size: u32,
+    pub color: [f32; 3],
+    #[serde(default = "default_size")]
+    pub size: f32,
+    #[serde(default)]
+    pub active: ActiveTime,
+}
+
+fn default_size() -> f32 {
+    1.0
+}
+
+// Which creatures spawn in each biome, edited in `assets/creatures/*.spawns.ron`
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct CreatureSpawnTable {
+    pub biomes: HashMap<Biome, Vec<SpawnRule>>,
+    #[serde(default)]
+    pub limits: PopulationLimits,
+}
+
+#[derive(Component)]
+pub struct Fish {
+    pub velocity: Vec3,
+    pub school: u32,
+    // Bolder fish in dangerous regions let the player come closer
+    pub flee_radius: f32,
+}
+
+// Fish spawned for each loaded chunk within the simulation range, despawned when the chunk
+// unloads or falls out of range
+#[derive(Resource, Default)]
+pub struct FishSchools {
+    chunks: HashMap<ChunkCoords, Vec<Entity>>,
+    next_school: u32,
+    table: Handle<CreatureSpawnTable>,
+    // Time of day the current schools were spawned for
+    night: Option<bool>,
+}
+
+#[derive(Resource)]
+struct FishAssets {
+    mesh: Handle<Mesh>,
+    // One per species, made on first spawn
+    materials: HashMap<String, Handle<StandardMaterial>>,
+}
+
+fn setup_fish_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut schools: ResMut<FishSchools>,
+    registry: Res<AssetRegistry>,
+) {
+    schools.table = registry.get(SPAWN_TABLE).unwrap_or_default();
+    commands.insert_resource(FishAssets {
+        mesh: meshes.add(Sphere::new(0.5).mesh().uv(12, 8)),
+        materials: HashMap::new(),
+    });
+}
+
+impl FishAssets {
+    fn material(&mut self, rule: &SpawnRule, materials: &mut Assets<StandardMaterial>) -> Handle<StandardMaterial> {
+        self.materials.entry(rule.species.clone())
+            .or_insert_with(|| {
+                let [r, g, b] = rule.color;
+                materials.add(StandardMaterial {
+                    base_color: Color::srgb(r, g, b),
+                    perceptual_roughness: 0.4,
+                    ..default()
+                })
+            })
+            .clone()
+    }
+}
+
+// Vertical range a fish can swim in at a point, None when the water is too shallow
+fn swim_range(water: &WaterQuery, x: f32, z: f32) -> Option<(f32, f32)> {
+    let floor = water.floor_height(x, z) + DEPTH_MARGIN;
+    let surface = water.surface_height(Vec2::new(x, z))? - DEPTH_MARGIN;
+    (floor < surface).then_some((floor, surface))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_school(
+    commands: &mut Commands,
+    rule: &SpawnRule,
+    mesh: &Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    water: &WaterQuery,
+    chunk: ChunkCoords,
+    school_index: u32,
+    school: u32,
+    creature: Creature,
+    // Most fish the population caps leave room for
+    limit: u32,
+) -> Vec<Entity> {
+    let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+    // Seeded by the world and chunk so a chunk always gets the same schools
+    let mut rng = WorldRng::for_chunk(chunk, RngPurpose::Creatures).substream(school_index);
+
+    // Look for a spot deep enough inside the chunk
+    let spot = (0..SPAWN_ATTEMPTS).find_map(|_| {
+        let offset = Vec2::new(rng.unit(), rng.unit()) - 0.5;
+        let point = center + offset * CHUNK_SIZE;
+        (water.depth(point.x, point.y) > MIN_SCHOOL_DEPTH).then_some(point)
+    });
+    let Some(spot) = spot else {
+        return Vec::new();
+    };
+
+    // Dangerous waters hold larger, bolder schools
+    let danger = danger::danger_level(spot.x, spot.y);
+    let school_size = ((rule.group_size as f32 * danger::resource_richness(danger)).round() as u32).min(limit);
+    let flee_radius = FLEE_RADIUS / danger::wildlife_aggressiveness(danger);
+
+    let heading = rng.unit() * std::f32::consts::TAU;
+    let direction = Vec3::new(heading.cos(), 0.0, heading.sin());
+
+    (0..school_size)
+        .filter_map(|_| {
+            // Drawn up front so a fish skipped over shallow water doesn't shift the others
+            let (jitter, depth) = (Vec2::new(rng.unit(), rng.unit()) - 0.5, rng.unit());
+            let point = spot + jitter * 2.0;
+            let (floor, surface) = swim_range(water, point.x, point.y)?;
+            let y = floor.lerp(surface, depth);
+            let position = Vec3::new(point.x, y, point.y);
+            Some(commands.spawn((
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::from_translation(position)
+                    .looking_to(direction, Vec3::Y)
+                    .with_scale(Vec3::new(0.15, 0.12, 0.4) * rule.size),
+                Fish {
+                    velocity: direction * FISH_SPEED,
+                    school,
+                    flee_radius,
+                },
+                Hittable { radius: 0.25 * rule.size },
+                creature,
+            )).id())
+        })
+        .collect()
+}
+
+// Respawn every school when the spawn table is edited or day turns to night (and back),
+// so the new rules apply right away
+fn reset_schools_on_changes(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<CreatureSpawnTable>>,
+    mut schools: ResMut<FishSchools>,
+    mut limits: ResMut<PopulationLimits>,
+    tables: Res<Assets<CreatureSpawnTable>>,
+    time_of_day: Res<TimeOfDay>,
+) {
+    let mut reset = false;
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event
+            && *id == schools.table.id()
+        {
+            info!("Creature spawn table {} applied", SPAWN_TABLE_PATH);
+            if let Some(table) = tables.get(*id) {
+                *limits = table.limits.clone();
+            }
+            reset = true;
+        }
+    }
+    let night = time_of_day.is_night();
+    if schools.night.is_some_and(|spawned_at_night| spawned_at_night != night) {
+        reset = true;
+    }
+    if !reset {
+        return;
+    }
+
+    schools.night = None;
+    for (_, fish) in schools.chunks.drain() {
+        for entity in fish {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Spawn schools in the water of loaded chunks near the camera following the spawn table of
+// their biome, as far as the population caps allow, and despawn those of chunks unloaded or
+// left out of the simulation range
+#[allow(clippy::too_many_arguments)]
+fn stream_fish_schools(
+    mut commands: Commands,
+    mut schools: ResMut<FishSchools>,
+    mut population: ResMut<Population>,
+    mut camera_chunk: Local<Option<ChunkCoords>>,
+    chunk_manager: Res<ChunkManager>,
+    limits: Res<PopulationLimits>,
+    water: WaterQuery,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    tables: Res<Assets<CreatureSpawnTable>>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    assets: Option<ResMut<FishAssets>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let camera = camera.translation.xz();
+    let current_chunk = ((camera.x / CHUNK_SIZE).round() as i32, (camera.y / CHUNK_SIZE).round() as i32);
+    let moved = camera_chunk.replace(current_chunk) != Some(current_chunk);
+    if !chunk_manager.is_changed() && !moved && !limits.is_changed() && schools.night.is_some() {
+        return;
+    }
+    let (Some(mut assets), Some(table)) = (assets, tables.get(&schools.table)) else {
+        return;
+    };
+    let night = time_of_day.is_night();
+    schools.night = Some(night);
+
+    // Chunks are simulated while their center is in range; new schools keep a chunk's width
+    // inside it, so they don't despawn as soon as they swim off
+    let in_range = |chunk: &ChunkCoords, margin: f32| {
+        let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+        center.distance(camera) <= limits.simulation_range - margin
+    };
+    schools.chunks.retain(|chunk, fish| {
+        if chunk_manager.loaded_chunks.contains_key(chunk) && in_range(chunk, 0.0) {
+            return true;
+        }
+        for entity in fish.drain(..) {
+            commands.entity(entity).despawn_recursive();
+        }
+        false
+    });
+
+    let now = time.elapsed_secs();
+    for (&chunk, (_, water_entity)) in chunk_manager.loaded_chunks.iter() {
+        if water_entity.is_none() || schools.chunks.contains_key(&chunk) || !in_range(&chunk, CHUNK_SIZE) {
+            continue;
+        }
+        let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+        let biome = biome::biome_at(center.x, center.y);
+        // A chunk skipped for lack of room is tried again once creatures despawn
+        if population.room(biome, &limits) == 0 {
+            continue;
+        }
+        let rules = table.biomes.get(&biome).map(Vec::as_slice).unwrap_or_default();
+        let mut fish = Vec::new();
+        let mut school_index = 0;
+        for rule in rules.iter().filter(|rule| rule.active.allows(night)) {
+            let material = assets.material(rule, &mut materials);
+            for _ in 0..rule.density {
+                let school = schools.next_school;
+                schools.next_school = schools.next_school.wrapping_add(1);
+                let room = population.room(biome, &limits);
+                let spawned = spawn_school(
+                    &mut commands, rule, &assets.mesh, material.clone(), &water, chunk, school_index, school,
+                    Creature::new(biome, now), room,
+                );
+                population.add(biome, spawned.len() as u32);
+                fish.extend(spawned);
+                school_index += 1;
+            }
+        }
+        schools.chunks.insert(chunk, fish);
+    }
+}
+
+// Fish despawned elsewhere, caught or culled by the population caps, leave their school
+fn forget_despawned_fish(
+    mut removed: RemovedComponents<Fish>,
+    mut schools: ResMut<FishSchools>,
+) {
+    let removed: Vec<Entity> = removed.read().collect();
+    if removed.is_empty() {
+        return;
+    }
+    for fish in schools.chunks.values_mut() {
+        fish.retain(|entity| !removed.contains(entity));
+    }
+}
+
+// Boids: separation, alignment and cohesion within a school, fleeing from the player,
+// kept between the terrain floor and the water surface
+fn update_fish(
+    time: Res<Time>,
+    water: WaterQuery,
+    player_query: Query<&Transform, (With<Player>, Without<Fish>)>,
+    mut fish_query: Query<(&mut Fish, &mut Transform)>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    let player = player_query.get_single().ok().map(|transform| transform.translation);
+
+    let snapshot: Vec<(u32, Vec3, Vec3)> = fish_query.iter()
+        .map(|(fish, transform)| (fish.school, transform.translation, fish.velocity))
+        .collect();
+
+    for (mut fish, mut transform) in fish_query.iter_mut() {
+        let position = transform.translation;
+        let mut separation = Vec3::ZERO;
+        let mut alignment = Vec3::ZERO;
+        let mut center = Vec3::ZERO;
+        let mut neighbors = 0;
+
+        for &(school, other_position, other_velocity) in &snapshot {
+            if school != fish.school || other_position == position {
+                continue;
+            }
+            let offset = position - other_position;
+            let distance = offset.length();
+            if distance > NEIGHBOR_RADIUS {
+                continue;
+            }
+            if distance < SEPARATION_RADIUS {
+                separation += offset / distance.max(0.01);
+            }
+            alignment += other_velocity;
+            center += other_position;
+            neighbors += 1;
+        }
+
+        let mut steer = separation * SEPARATION_WEIGHT;
+        if neighbors > 0 {
+            let count = neighbors as f32;
+            steer += (alignment / count - fish.velocity) * ALIGNMENT_WEIGHT;
+            steer += (center / count - position) * COHESION_WEIGHT;
+        }
+
+        let mut max_speed = FISH_SPEED;
+        if let Some(player) = player {
+            let away = position - player;
+            let distance = away.length();
+            if distance < fish.flee_radius {
+                steer += away.normalize_or_zero() * (1.0 - distance / fish.flee_radius) * FLEE_WEIGHT;
+                max_speed = FLEE_SPEED;
+            }
+        }
+
+        // Turn back before reaching shallow water
+        let ahead = position + fish.velocity.normalize_or_zero() * 1.5;
+        match swim_range(&water, ahead.x, ahead.z) {
+            Some((floor, surface)) => {
+                if ahead.y < floor {
+                    steer.y += BOUNDS_WEIGHT;
+                } else if ahead.y > surface {
+                    steer.y -= BOUNDS_WEIGHT;
+                }
+            }
+            None => steer -= Vec3::new(fish.velocity.x, 0.0, fish.velocity.z) * BOUNDS_WEIGHT,
+        }
+
+        let velocity = fish.velocity + steer.clamp_length_max(MAX_STEER) * dt;
+        fish.velocity = velocity.clamp_length(FISH_SPEED * 0.5, max_speed);
+
+        let mut next = position + fish.velocity * dt;
+        match swim_range(&water, next.x, next.z) {
+            Some((floor, surface)) => next.y = next.y.clamp(floor, surface),
+            // Never leave the water, stop and let the steering turn the fish around
+            None => {
+                next = position;
+                fish.velocity = -fish.velocity;
+            }
+        }
+
+        transform.translation = next;
+        if fish.velocity.length_squared() > 0.0001 {
+            let target = next + fish.velocity;
+            transform.look_at(target, Vec3::Y);
+        }
+    }
+}
+
+// A fish hit by a projectile is caught
+fn catch_hit_fish(
+    mut commands: Commands,
+    mut hits: EventReader<ProjectileHit>,
+    mut collected: EventWriter<ItemCollected>,
+    fish_query: Query<(), With<Fish>>,
+) {
+    for hit in hits.read() {
+        // Only rocks stun fish, a bobber just bounces off
+        let (ProjectileKind::Rock, HitTarget::Entity(entity)) = (hit.kind, hit.target) else {
+            continue;
+        };
+        if !fish_query.contains(entity) {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        collected.send(ItemCollected { item: "fish".to_string(), count: 1 });
+        info!("Caught a fish");
+    }
+}
@@ -0,0 +1,331 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::accessibility::show_prompt;
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::danger;
+use crate::day_night::TimeOfDay;
+use crate::hotbar::{Hotbar, HAND_OFFSET};
+use crate::inventory::Inventory;
+use crate::placement::PlacementState;
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::projectile::{Projectile, ProjectileKind};
+use crate::quest::ItemCollected;
+use crate::rain::RAIN;
+use crate::settings::GameSettings;
+use crate::water::WaterQuery;
+use crate::world_events::{unit_random, ActiveWorldEvents};
+
+pub const FISHING_ROD: &str = "fishing_rod";
+// Rod held in hand, tilted forward from upright
+pub const ROD_LENGTH: f32 = 1.6;
+pub const ROD_TILT: f32 = -0.8;
+const CAST_SPEED: f32 = 12.0;
+// Cast upward of the aim so the bobber flies in an arc
+const CAST_LIFT: f32 = 0.35;
+pub const BOBBER_RADIUS: f32 = 0.08;
+// The line snaps when the bobber drifts further than this
+const MAX_LINE_LENGTH: f32 = 35.0;
+// Seconds until a bite at the normal bite rate, rolled per cast
+const BITE_SECONDS: (f32, f32) = (6.0, 18.0);
+// Time to react to a bite before the fish lets go
+const BITE_WINDOW: f32 = 0.9;
+// How fast the bobber is pulled under on a bite
+const BITE_PULL: f32 = 2.5;
+// Reeling: the marker sweeps the bar this many times per second, the catch zone is this wide
+const REEL_SWEEPS: f32 = 0.8;
+const REEL_ZONE_WIDTH: f32 = 0.2;
+const REEL_SECONDS: f32 = 5.0;
+
+// Fishing with the rod: left click casts the bobber onto the water, a fish bites after
+// a while (sooner in the rain and at dawn and dusk), clicking hooks it and a second
+// click in the right moment reels it in
+#[derive(Default, Clone, Debug)]
+pub struct FishingPlugin;
+
+impl Plugin for FishingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Fishing>()
+            .add_systems(Startup, setup_fishing_assets)
+            .add_systems(Update, (
+                use_fishing_rod,
+                update_fishing,
+                draw_fishing_line,
+                fishing_ui,
+            ).chain());
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FishingPhase {
+    #[default]
+    Idle,
+    // The bobber is in the air
+    Casting,
+    Waiting { bite_in: f32 },
+    Bite { remaining: f32 },
+    // Marker sweeping the bar, the fish is caught by clicking while it's in the zone
+    Reeling { marker: f32, zone: f32, speed: f32, remaining: f32 },
+}
+
+#[derive(Resource, Default)]
+pub struct Fishing {
+    pub phase: FishingPhase,
+    bobber: Option<Entity>,
+    // Seeds the random rolls of each cast
+    casts: u64,
+}
+
+impl Fishing {
+    fn roll(&self, salt: u32) -> f32 {
+        unit_random(self.casts, salt)
+    }
+
+    fn wait_for_bite(&mut self) {
+        self.phase = FishingPhase::Waiting { bite_in: BITE_SECONDS.0.lerp(BITE_SECONDS.1, self.roll(0)) };
+    }
+
+    fn reel_in(&mut self, commands: &mut Commands) {
+        if let Some(bobber) = self.bobber.take() {
+            commands.entity(bobber).despawn();
+        }
+        self.phase = FishingPhase::Idle;
+    }
+}
+
+#[derive(Resource)]
+struct FishingAssets {
+    bobber_mesh: Handle<Mesh>,
+    bobber_material: Handle<StandardMaterial>,
+}
+
+fn setup_fishing_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(FishingAssets {
+        bobber_mesh: meshes.add(Sphere::new(BOBBER_RADIUS).mesh().uv(12, 8)),
+        bobber_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.9, 0.15, 0.1),
+            perceptual_roughness: 0.5,
+            ..default()
+        }),
+    });
+}
+
+// Where the line leaves the rod, in world space
+pub fn rod_tip(player: &Transform) -> Vec3 {
+    player.transform_point(HAND_OFFSET + Quat::from_rotation_x(ROD_TILT) * Vec3::Y * ROD_LENGTH)
+}
+
+// Fish bite more often in the rain and around dawn and dusk, less at night
+fn bite_rate(rain: f32, time_of_day: &TimeOfDay) -> f32 {
+    let twilight = [time_of_day.sunrise(), time_of_day.sunset()].iter().any(|peak| (time_of_day.hour - peak).abs() < 1.5);
+    let time = if twilight {
+        1.6
+    } else if time_of_day.is_night() {
+        0.6
+    } else {
+        1.0
+    };
+    time * (1.0 + rain)
+}
+
+// Left click with the rod in hand: cast, hook the fish on a bite, catch it while reeling,
+// or reel the bobber back in
+#[allow(clippy::too_many_arguments)]
+fn use_fishing_rod(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    camera_settings: Res<CameraSettings>,
+    placement: Res<PlacementState>,
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    assets: Option<Res<FishingAssets>>,
+    mut fishing: ResMut<Fishing>,
+    mut collected: EventWriter<ItemCollected>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    player_query: Query<&Transform, (With<Player>, Without<FreeCamera>)>,
+    bobber_query: Query<&Transform, With<Projectile>>,
+) {
+    if !hotbar.is_holding(&inventory, FISHING_ROD) || camera_settings.camera_mode != CameraMode::Player {
+        if fishing.phase != FishingPhase::Idle {
+            fishing.reel_in(&mut commands);
+        }
+        return;
+    }
+    if placement.active.is_some()
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || contexts.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+
+    match fishing.phase {
+        FishingPhase::Idle => {
+            let (Some(assets), Ok(camera), Ok(player)) = (assets, camera_query.get_single(), player_query.get_single()) else {
+                return;
+            };
+            let feet = player.translation - Vec3::Y * PLAYER_HALF_HEIGHT;
+            let origin = feet + Vec3::Y * camera_settings.eye_height;
+            let direction = (*camera.forward() + Vec3::Y * CAST_LIFT).normalize();
+            fishing.casts += 1;
+            fishing.bobber = Some(commands.spawn((
+                Mesh3d(assets.bobber_mesh.clone()),
+                MeshMaterial3d(assets.bobber_material.clone()),
+                Transform::from_translation(origin + direction * 0.6),
+                Projectile {
+                    kind: ProjectileKind::Bobber,
+                    velocity: direction * CAST_SPEED,
+                    age: 0.0,
+                    resting: false,
+                },
+            )).id());
+            fishing.phase = FishingPhase::Casting;
+        }
+        FishingPhase::Casting | FishingPhase::Waiting { .. } => fishing.reel_in(&mut commands),
+        FishingPhase::Bite { .. } => {
+            // Fish in dangerous waters fight harder, the marker sweeps faster
+            let danger = fishing.bobber
+                .and_then(|bobber| bobber_query.get(bobber).ok())
+                .map_or(0.0, |bobber| danger::danger_level(bobber.translation.x, bobber.translation.z));
+            fishing.phase = FishingPhase::Reeling {
+                marker: 0.0,
+                zone: fishing.roll(1) * (1.0 - REEL_ZONE_WIDTH),
+                speed: 2.0 * REEL_SWEEPS * danger::wildlife_aggressiveness(danger),
+                remaining: REEL_SECONDS,
+            };
+        }
+        FishingPhase::Reeling { marker, zone, .. } => {
+            if (zone..zone + REEL_ZONE_WIDTH).contains(&marker) {
+                collected.send(ItemCollected { item: "fish".to_string(), count: 1 });
+                info!("Caught a fish");
+            } else {
+                info!("The fish got away");
+            }
+            fishing.reel_in(&mut commands);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_fishing(
+    mut commands: Commands,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    events: Res<ActiveWorldEvents>,
+    water: WaterQuery,
+    mut fishing: ResMut<Fishing>,
+    mut bobber_query: Query<(&Transform, &mut Projectile)>,
+    player_query: Query<&Transform, (With<Player>, Without<Projectile>)>,
+) {
+    let dt = time.delta_secs();
+    let Some(bobber) = fishing.bobber else {
+        return;
+    };
+    let Ok((transform, mut projectile)) = bobber_query.get_mut(bobber) else {
+        fishing.reel_in(&mut commands);
+        return;
+    };
+    let snapped = player_query.get_single()
+        .is_ok_and(|player| player.translation.distance(transform.translation) > MAX_LINE_LENGTH);
+    if projectile.resting || snapped {
+        info!("{}", if snapped { "The fishing line snapped" } else { "The bobber landed on dry ground" });
+        fishing.reel_in(&mut commands);
+        return;
+    }
+
+    match fishing.phase {
+        FishingPhase::Casting => {
+            if water.is_in_water(transform.translation) {
+                fishing.wait_for_bite();
+            }
+        }
+        FishingPhase::Waiting { bite_in } => {
+            let bite_in = bite_in - dt * bite_rate(events.intensity(RAIN), &time_of_day);
+            if bite_in <= 0.0 {
+                projectile.velocity.y = -BITE_PULL;
+                fishing.phase = FishingPhase::Bite { remaining: BITE_WINDOW };
+            } else {
+                fishing.phase = FishingPhase::Waiting { bite_in };
+            }
+        }
+        FishingPhase::Bite { remaining } => {
+            if remaining <= dt {
+                info!("The fish let go of the bait");
+                fishing.casts += 1;
+                fishing.wait_for_bite();
+            } else {
+                fishing.phase = FishingPhase::Bite { remaining: remaining - dt };
+            }
+        }
+        FishingPhase::Reeling { marker, zone, speed, remaining } => {
+            if remaining <= dt {
+                info!("The fish got away");
+                fishing.reel_in(&mut commands);
+                return;
+            }
+            // Bounce between the ends of the bar, the sign of the speed is the direction
+            let mut marker = marker + speed * dt;
+            let mut speed = speed;
+            if !(0.0..=1.0).contains(&marker) {
+                marker = marker.clamp(0.0, 1.0);
+                speed = -speed;
+            }
+            // The fish tugs on the bobber while it fights
+            projectile.velocity.y -= BITE_PULL * 2.0 * dt;
+            fishing.phase = FishingPhase::Reeling { marker, zone, speed, remaining: remaining - dt };
+        }
+        FishingPhase::Idle => {}
+    }
+}
+
+fn draw_fishing_line(
+    mut gizmos: Gizmos,
+    fishing: Res<Fishing>,
+    bobber_query: Query<&Transform, With<Projectile>>,
+    player_query: Query<&Transform, (With<Player>, Without<Projectile>)>,
+) {
+    let (Some(bobber), Ok(player)) = (fishing.bobber, player_query.get_single()) else {
+        return;
+    };
+    if let Ok(bobber) = bobber_query.get(bobber) {
+        gizmos.line(rod_tip(player), bobber.translation + Vec3::Y * BOBBER_RADIUS, Color::srgba(0.9, 0.9, 0.9, 0.6));
+    }
+}
+
+fn fishing_ui(
+    mut contexts: EguiContexts,
+    fishing: Res<Fishing>,
+    settings: Res<GameSettings>,
+) {
+    let text = match fishing.phase {
+        FishingPhase::Idle | FishingPhase::Casting => return,
+        FishingPhase::Waiting { .. } => "Waiting for a bite... (click to reel in)",
+        FishingPhase::Bite { .. } => "A bite! Click to hook it",
+        FishingPhase::Reeling { .. } => "Click while the marker is in the green",
+    };
+    show_prompt(contexts.ctx_mut(), "fishing", &settings.accessibility, egui::Align2::CENTER_BOTTOM, [0.0, -80.0], |ui| {
+        ui.vertical_centered(|ui| {
+            ui.colored_label(egui::Color32::WHITE, text);
+            let FishingPhase::Reeling { marker, zone, .. } = fishing.phase else {
+                return;
+            };
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(240.0, 16.0), egui::Sense::hover());
+            let painter = ui.painter();
+            let x = |value: f32| rect.left() + rect.width() * value;
+            painter.rect_filled(rect, 3.0, egui::Color32::from_black_alpha(180));
+            painter.rect_filled(
+                egui::Rect::from_x_y_ranges(x(zone)..=x(zone + REEL_ZONE_WIDTH), rect.y_range()),
+                0.0,
+                egui::Color32::from_rgb(60, 170, 80),
+            );
+            painter.line_segment(
+                [egui::pos2(x(marker), rect.top() - 3.0), egui::pos2(x(marker), rect.bottom() + 3.0)],
+                egui::Stroke::new(3.0, egui::Color32::WHITE),
+            );
+        });
+    });
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::day_night::TimeOfDay;
+use crate::world_events::{ActiveWorldEvents, WorldEventAppExt, WorldEventType};
+
+const FOG_BANK: &str = "fog_bank";
+// Visibility in meters with a light haze and in the thickest fog
+const CLEAR_VISIBILITY: f32 = 2000.0;
+const DENSE_VISIBILITY: f32 = 45.0;
+const FOG_COLOR_DAY: Color = Color::srgb(0.78, 0.8, 0.82);
+const FOG_COLOR_NIGHT: Color = Color::srgb(0.12, 0.13, 0.16);
+
+#[derive(Default, Clone, Debug)]
+pub struct FogBankPlugin;
+
+impl Plugin for FogBankPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_world_event(WorldEventType {
+                name: FOG_BANK,
+                start_window: (4.0, 7.0),
+                chance: 0.2,
+                duration_hours: 3.5,
+            })
+            .add_systems(Update, update_fog_bank);
+    }
+}
+
+// Dense fog on the main camera while the event runs, lit by the time of day
+fn update_fog_bank(
+    mut commands: Commands,
+    events: Res<ActiveWorldEvents>,
+    time_of_day: Res<TimeOfDay>,
+    mut camera_query: Query<(Entity, Option<&mut DistanceFog>), With<FreeCamera>>,
+) {
+    let intensity = events.intensity(FOG_BANK);
+    let Ok((camera, fog)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    if intensity <= 0.0 {
+        if fog.is_some() {
+            commands.entity(camera).remove::<DistanceFog>();
+        }
+        return;
+    }
+
+    // Fog thickens quickly so most of the event is spent in the dense part
+    let visibility = CLEAR_VISIBILITY.lerp(DENSE_VISIBILITY, intensity.sqrt());
+    let color = FOG_COLOR_NIGHT.mix(&FOG_COLOR_DAY, time_of_day.daylight());
+    let settings = DistanceFog {
+        color,
+        falloff: FogFalloff::from_visibility(visibility),
+        ..default()
+    };
+    match fog {
+        Some(mut fog) => *fog = settings,
+        None => {
+            commands.entity(camera).insert(settings);
+        }
+    }
+}
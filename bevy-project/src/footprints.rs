@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use crate::boat::Aboard;
+use crate::creative::CreativeMode;
+use crate::palette::ActiveTerrainPalette;
+use crate::player::{Player, PlayerStance, PLAYER_HALF_HEIGHT};
+use crate::terrain_edit::TerrainEdits;
+
+// Oldest footprints are removed first once this many exist
+const MAX_FOOTPRINTS: usize = 64;
+const STRIDE_LENGTH: f32 = 0.8;
+// Sideways distance of each foot from the walking line
+const FOOT_SPACING: f32 = 0.15;
+const FOOTPRINT_LIFETIME: f32 = 20.0;
+const FOOTPRINT_ALPHA: f32 = 0.45;
+// Small lift so footprints don't z-fight with the terrain
+const FOOTPRINT_OFFSET: f32 = 0.02;
+
+#[derive(Default, Clone, Debug)]
+pub struct FootprintPlugin;
+
+impl Plugin for FootprintPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Footprints>()
+            .add_systems(Startup, setup_footprint_mesh)
+            .add_systems(Update, (spawn_footprints, fade_footprints).chain());
+    }
+}
+
+#[derive(Component)]
+struct Footprint {
+    age: f32,
+    material: Handle<StandardMaterial>,
+}
+
+// Ring buffer of spawned footprints, oldest first
+#[derive(Resource, Default)]
+struct Footprints {
+    entities: VecDeque<Entity>,
+    last_step: Option<Vec3>,
+    left_foot: bool,
+}
+
+#[derive(Resource)]
+struct FootprintMesh(Handle<Mesh>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftGround {
+    Sand,
+    Snow,
+}
+
+impl SoftGround {
+    // Read from the terrain color so it follows biomes and palette swaps
+    pub fn from_color([r, g, b, _]: [f32; 4]) -> Option<Self> {
+        if r.min(g).min(b) > 0.8 {
+            Some(SoftGround::Snow)
+        } else if r > 0.65 && r >= g && g > b && r - b > 0.2 {
+            Some(SoftGround::Sand)
+        } else {
+            None
+        }
+    }
+
+    fn print_color(&self) -> Color {
+        match self {
+            SoftGround::Sand => Color::srgba(0.45, 0.35, 0.2, FOOTPRINT_ALPHA),
+            SoftGround::Snow => Color::srgba(0.55, 0.6, 0.7, FOOTPRINT_ALPHA),
+        }
+    }
+}
+
+fn setup_footprint_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    commands.insert_resource(FootprintMesh(meshes.add(Plane3d::new(Vec3::Y, Vec2::new(0.09, 0.15)))));
+}
+
+pub fn ground_normal(terrain_edits: &TerrainEdits, x: f32, z: f32) -> Vec3 {
+    let step = 0.25;
+    let dx = terrain_edits.height(x + step, z) - terrain_edits.height(x - step, z);
+    let dz = terrain_edits.height(x, z + step) - terrain_edits.height(x, z - step);
+    Vec3::new(-dx, 2.0 * step, -dz).normalize()
+}
+
+// Leave a footprint every stride while walking on sand or snow
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn spawn_footprints(
+    mut commands: Commands,
+    mut footprints: ResMut<Footprints>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mesh: Option<Res<FootprintMesh>>,
+    terrain_edits: Res<TerrainEdits>,
+    palette: Res<ActiveTerrainPalette>,
+    creative: Res<CreativeMode>,
+    player_query: Query<(&Transform, &PlayerStance), (With<Player>, Without<Aboard>)>,
+) {
+    let (Some(mesh), Ok((transform, stance))) = (mesh, player_query.get_single()) else {
+        footprints.last_step = None;
+        return;
+    };
+    if *stance == PlayerStance::Swimming || creative.flying() {
+        footprints.last_step = None;
+        return;
+    }
+
+    let feet = transform.translation - Vec3::Y * PLAYER_HALF_HEIGHT;
+    let Some(last_step) = footprints.last_step else {
+        footprints.last_step = Some(feet);
+        return;
+    };
+    let travel = (feet - last_step).with_y(0.0);
+    if travel.length() < STRIDE_LENGTH {
+        return;
+    }
+    footprints.last_step = Some(feet);
+    footprints.left_foot = !footprints.left_foot;
+
+    let direction = travel.normalize();
+    let side = if footprints.left_foot { -FOOT_SPACING } else { FOOT_SPACING };
+    let point = feet + direction.cross(Vec3::Y) * side;
+    let height = terrain_edits.height(point.x, point.z);
+    let Some(ground) = SoftGround::from_color(palette.0.color(point.x, point.z, height)) else {
+        return;
+    };
+
+    let normal = ground_normal(&terrain_edits, point.x, point.z);
+    let material = materials.add(StandardMaterial {
+        base_color: ground.print_color(),
+        alpha_mode: AlphaMode::Blend,
+        perceptual_roughness: 1.0,
+        ..default()
+    });
+    let entity = commands.spawn((
+        Mesh3d(mesh.0.clone()),
+        MeshMaterial3d(material.clone()),
+        Transform::from_translation(Vec3::new(point.x, height, point.z) + normal * FOOTPRINT_OFFSET)
+            .looking_to(direction, normal),
+        Footprint { age: 0.0, material },
+    )).id();
+
+    footprints.entities.push_back(entity);
+    if footprints.entities.len() > MAX_FOOTPRINTS
+        && let Some(oldest) = footprints.entities.pop_front()
+    {
+        commands.entity(oldest).despawn();
+    }
+}
+
+fn fade_footprints(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut footprints: ResMut<Footprints>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut footprint_query: Query<(Entity, &mut Footprint)>,
+) {
+    for (entity, mut footprint) in footprint_query.iter_mut() {
+        footprint.age += time.delta_secs();
+        if footprint.age >= FOOTPRINT_LIFETIME {
+            commands.entity(entity).despawn();
+            footprints.entities.retain(|&other| other != entity);
+            continue;
+        }
+        // Hold for a while, then fade out over the last third of the lifetime
+        let fade = (FOOTPRINT_LIFETIME - footprint.age) / (FOOTPRINT_LIFETIME / 3.0);
+        if fade < 1.0
+            && let Some(material) = materials.get_mut(&footprint.material) {
+            material.base_color.set_alpha(FOOTPRINT_ALPHA * fade);
+        }
+    }
+}
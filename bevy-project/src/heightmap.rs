@@ -0,0 +1,198 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+// Describes a heightmap made of one or more grayscale images laid out in world space.
+// A bare `.png` can be used instead, it becomes a single tile with the default scale.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HeightmapDescriptor {
+    pub tiles: Vec<HeightmapTileDescriptor>,
+    // Horizontal size of a pixel
+    pub meters_per_pixel: f32,
+    // Heights of the darkest and brightest pixel values
+    pub min_height: f32,
+    pub max_height: f32,
+    // Distance over which the heightmap fades into the noise terrain at its edges
+    pub edge_blend: f32,
+}
+
+impl Default for HeightmapDescriptor {
+    fn default() -> Self {
+        Self {
+            tiles: Vec::new(),
+            meters_per_pixel: 1.0,
+            min_height: -10.0,
+            max_height: 40.0,
+            edge_blend: 32.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HeightmapTileDescriptor {
+    // Relative to the descriptor file
+    pub image: PathBuf,
+    // World (x, z) of the image's top-left pixel; neighbouring tiles share their border pixels
+    #[serde(default)]
+    pub origin: (f32, f32),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HeightmapError {
+    #[error("could not read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("could not parse heightmap descriptor: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not decode {0}: {1}")]
+    Image(PathBuf, image::ImageError),
+    #[error("heightmap has no tiles")]
+    Empty,
+    #[error("{0} is smaller than 2x2 pixels")]
+    TooSmall(PathBuf),
+}
+
+struct HeightmapTile {
+    origin: Vec2,
+    width: u32,
+    height: u32,
+    // Normalized 0..1 pixel values, row by row
+    pixels: Vec<f32>,
+    // Sides (-x, -z, +x, +z) not joined to another tile, where the heightmap fades out
+    open_sides: [bool; 4],
+}
+
+impl HeightmapTile {
+    fn pixel(&self, x: u32, y: u32) -> f32 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // Bilinear value and distance to the nearest edge (in pixels), None outside the image
+    fn sample(&self, pixel: Vec2) -> Option<(f32, f32)> {
+        let max_x = (self.width - 1) as f32;
+        let max_y = (self.height - 1) as f32;
+        if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x > max_x || pixel.y > max_y {
+            return None;
+        }
+
+        let x0 = (pixel.x.floor() as u32).min(self.width.saturating_sub(2));
+        let y0 = (pixel.y.floor() as u32).min(self.height.saturating_sub(2));
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let tx = pixel.x - x0 as f32;
+        let ty = pixel.y - y0 as f32;
+
+        let top = self.pixel(x0, y0).lerp(self.pixel(x1, y0), tx);
+        let bottom = self.pixel(x0, y1).lerp(self.pixel(x1, y1), tx);
+        let distances = [pixel.x, pixel.y, max_x - pixel.x, max_y - pixel.y];
+        let edge = distances.iter()
+            .zip(self.open_sides)
+            .filter(|(_, open)| *open)
+            .map(|(distance, _)| *distance)
+            .fold(f32::MAX, f32::min);
+        Some((top.lerp(bottom, ty), edge))
+    }
+}
+
+// Terrain heights read from real-world elevation data (DEM) instead of noise
+pub struct Heightmap {
+    tiles: Vec<HeightmapTile>,
+    meters_per_pixel: f32,
+    min_height: f32,
+    max_height: f32,
+    edge_blend: f32,
+}
+
+impl Heightmap {
+    // Load a `.png` directly or a RON descriptor listing the tiles
+    pub fn load(path: &Path) -> Result<Self, HeightmapError> {
+        let is_image = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if is_image {
+            let descriptor = HeightmapDescriptor {
+                tiles: vec![HeightmapTileDescriptor {
+                    image: path.to_path_buf(),
+                    origin: (0.0, 0.0),
+                }],
+                ..default()
+            };
+            return Self::from_descriptor(&descriptor, Path::new(""));
+        }
+
+        let text = fs::read_to_string(path).map_err(|err| HeightmapError::Io(path.to_path_buf(), err))?;
+        let descriptor: HeightmapDescriptor = ron::from_str(&text)?;
+        Self::from_descriptor(&descriptor, path.parent().unwrap_or(Path::new("")))
+    }
+
+    pub fn from_descriptor(descriptor: &HeightmapDescriptor, base_dir: &Path) -> Result<Self, HeightmapError> {
+        if descriptor.tiles.is_empty() {
+            return Err(HeightmapError::Empty);
+        }
+
+        let mut tiles = Vec::with_capacity(descriptor.tiles.len());
+        for tile in &descriptor.tiles {
+            let path = base_dir.join(&tile.image);
+            let image = image::open(&path)
+                .map_err(|err| HeightmapError::Image(path.clone(), err))?
+                .into_luma16();
+            let (width, height) = image.dimensions();
+            if width < 2 || height < 2 {
+                return Err(HeightmapError::TooSmall(path));
+            }
+            let pixels = image.pixels().map(|pixel| pixel.0[0] as f32 / u16::MAX as f32).collect();
+            info!("Loaded heightmap tile {} ({}x{})", path.display(), width, height);
+            tiles.push(HeightmapTile {
+                origin: Vec2::new(tile.origin.0, tile.origin.1),
+                width,
+                height,
+                pixels,
+                open_sides: [true; 4],
+            });
+        }
+
+        let meters_per_pixel = descriptor.meters_per_pixel.max(f32::EPSILON);
+        let bounds: Vec<(Vec2, Vec2)> = tiles.iter()
+            .map(|tile| {
+                let size = Vec2::new((tile.width - 1) as f32, (tile.height - 1) as f32) * meters_per_pixel;
+                (tile.origin, tile.origin + size)
+            })
+            .collect();
+        let covered = |point: Vec2, skip: usize| bounds.iter().enumerate()
+            .any(|(i, (min, max))| i != skip && point.cmpge(*min).all() && point.cmple(*max).all());
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            // Probe just outside the middle of each side
+            let (min, max) = bounds[i];
+            let center = (min + max) / 2.0;
+            let probe = meters_per_pixel;
+            tile.open_sides = [
+                !covered(Vec2::new(min.x - probe, center.y), i),
+                !covered(Vec2::new(center.x, min.y - probe), i),
+                !covered(Vec2::new(max.x + probe, center.y), i),
+                !covered(Vec2::new(center.x, max.y + probe), i),
+            ];
+        }
+
+        Ok(Self {
+            tiles,
+            meters_per_pixel,
+            min_height: descriptor.min_height,
+            max_height: descriptor.max_height,
+            edge_blend: descriptor.edge_blend.max(0.0),
+        })
+    }
+
+    // Height at a world position and how much it should override the noise terrain (0..1)
+    pub fn sample(&self, world_x: f32, world_z: f32) -> Option<(f32, f32)> {
+        let world = Vec2::new(world_x, world_z);
+        self.tiles.iter().find_map(|tile| {
+            let (value, edge) = tile.sample((world - tile.origin) / self.meters_per_pixel)?;
+            let height = self.min_height.lerp(self.max_height, value);
+            let weight = if self.edge_blend > 0.0 {
+                (edge * self.meters_per_pixel / self.edge_blend).clamp(0.0, 1.0)
+            } else {
+                1.0
+            };
+            Some((height, weight))
+        })
+    }
+}
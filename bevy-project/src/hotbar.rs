@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use crate::fishing::{FISHING_ROD, ROD_LENGTH, ROD_TILT};
+use crate::inventory::Inventory;
+use crate::player::{Player, PLAYER_RADIUS};
+
+pub const HOTBAR_SLOTS: usize = 9;
+// Where the held item sits relative to the player's center, the torch is in the other hand
+pub const HAND_OFFSET: Vec3 = Vec3::new(-(PLAYER_RADIUS + 0.15), 0.1, -0.35);
+const SLOT_KEYS: [KeyCode; HOTBAR_SLOTS] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+    KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+    KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+
+#[derive(Default, Clone, Debug)]
+pub struct HotbarPlugin;
+
+impl Plugin for HotbarPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Hotbar>()
+            .add_systems(Startup, setup_held_item_assets)
+            .add_systems(Update, (
+                bind_inventory_items,
+                select_hotbar_slot,
+                update_held_item,
+            ).chain());
+    }
+}
+
+// Nine slots bound to inventory items, the selected one is held in hand.
+// Slots keep their item when it runs out so it comes back to the same key.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Hotbar {
+    pub slots: [Option<String>; HOTBAR_SLOTS],
+    pub selected: usize,
+}
+
+impl Hotbar {
+    // Item in the selected slot, if the player carries any
+    pub fn held<'a>(&'a self, inventory: &Inventory) -> Option<&'a str> {
+        self.slots[self.selected].as_deref()
+            .filter(|item| inventory.count(item) > 0)
+    }
+
+    pub fn is_holding(&self, inventory: &Inventory, item: &str) -> bool {
+        self.held(inventory) == Some(item)
+    }
+
+    // Put carried items that have no slot yet in the first free ones
+    fn bind_new_items(&mut self, inventory: &Inventory) {
+        let mut unbound: Vec<&String> = inventory.items.keys()
+            .filter(|item| !self.slots.iter().flatten().any(|slot| slot == *item))
+            .collect();
+        unbound.sort();
+        for item in unbound {
+            let Some(slot) = self.slots.iter_mut().find(|slot| slot.is_none()) else {
+                return;
+            };
+            *slot = Some(item.clone());
+        }
+    }
+}
+
+// Mesh shown in the player's hand for the held item
+#[derive(Component)]
+struct HeldItem {
+    item: String,
+}
+
+#[derive(Resource)]
+struct HeldItemAssets {
+    wood_mesh: Handle<Mesh>,
+    fish_mesh: Handle<Mesh>,
+    rod_mesh: Handle<Mesh>,
+    other_mesh: Handle<Mesh>,
+    wood_material: Handle<StandardMaterial>,
+    fish_material: Handle<StandardMaterial>,
+    cooked_material: Handle<StandardMaterial>,
+    other_material: Handle<StandardMaterial>,
+}
+
+impl HeldItemAssets {
+    fn for_item(&self, item: &str) -> (Handle<Mesh>, Handle<StandardMaterial>, Transform) {
+        match item {
+            "wood" => (
+                self.wood_mesh.clone(),
+                self.wood_material.clone(),
+                Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+            ),
+            "fish" | "cooked_fish" => (
+                self.fish_mesh.clone(),
+                if item == "fish" { self.fish_material.clone() } else { self.cooked_material.clone() },
+                Transform::from_scale(Vec3::new(0.5, 0.6, 1.4)),
+            ),
+            FISHING_ROD => {
+                // Held at the bottom end, leaning forward
+                let rotation = Quat::from_rotation_x(ROD_TILT);
+                (
+                    self.rod_mesh.clone(),
+                    self.wood_material.clone(),
+                    Transform::from_rotation(rotation).with_translation(rotation * Vec3::Y * ROD_LENGTH / 2.0),
+                )
+            }
+            _ => (self.other_mesh.clone(), self.other_material.clone(), Transform::default()),
+        }
+    }
+}
+
+fn setup_held_item_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut material = |color: Color| materials.add(StandardMaterial {
+        base_color: color,
+        perceptual_roughness: 0.85,
+        ..default()
+    });
+    commands.insert_resource(HeldItemAssets {
+        wood_mesh: meshes.add(Cylinder::new(0.06, 0.5)),
+        fish_mesh: meshes.add(Sphere::new(0.12).mesh().ico(1).unwrap()),
+        rod_mesh: meshes.add(Cylinder::new(0.015, ROD_LENGTH)),
+        other_mesh: meshes.add(Cuboid::from_length(0.15)),
+        wood_material: material(Color::srgb(0.35, 0.22, 0.1)),
+        fish_material: material(Color::srgb(0.55, 0.6, 0.65)),
+        cooked_material: material(Color::srgb(0.5, 0.3, 0.12)),
+        other_material: material(Color::srgb(0.6, 0.6, 0.6)),
+    });
+}
+
+fn bind_inventory_items(
+    inventory: Res<Inventory>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    if inventory.is_changed() {
+        hotbar.bind_new_items(&inventory);
+    }
+}
+
+// Number keys 1-9 pick the slot to hold
+fn select_hotbar_slot(
+    input: Res<ButtonInput<KeyCode>>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    if let Some(slot) = SLOT_KEYS.iter().position(|key| input.just_pressed(*key)) {
+        hotbar.selected = slot;
+    }
+}
+
+// Swap the mesh in the player's hand when the held item changes or runs out
+fn update_held_item(
+    mut commands: Commands,
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    assets: Res<HeldItemAssets>,
+    player_query: Query<Entity, With<Player>>,
+    held_query: Query<(Entity, &HeldItem)>,
+) {
+    if !hotbar.is_changed() && !inventory.is_changed() {
+        return;
+    }
+    let held = hotbar.held(&inventory);
+    let current = held_query.get_single().ok();
+    if current.map(|(_, held_item)| held_item.item.as_str()) == held {
+        return;
+    }
+
+    if let Some((entity, _)) = current {
+        commands.entity(entity).despawn_recursive();
+    }
+    let (Some(item), Ok(player)) = (held, player_query.get_single()) else {
+        return;
+    };
+    let (mesh, material, transform) = assets.for_item(item);
+    commands.entity(player).with_children(|parent| {
+        parent.spawn((
+            Mesh3d(mesh),
+            MeshMaterial3d(material),
+            Transform::from_translation(HAND_OFFSET) * transform,
+            HeldItem { item: item.to_string() },
+        ));
+    });
+}
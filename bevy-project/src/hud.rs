@@ -0,0 +1,272 @@
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::window::WindowRef;
+use crate::hotbar::{Hotbar, HOTBAR_SLOTS};
+use crate::inventory::Inventory;
+use crate::player::{Health, Oxygen, Player, Stamina, Thirst, Vital};
+use crate::settings::{ColorVision, GameSettings};
+
+const HIDE_HUD_KEY: KeyCode = KeyCode::F1;
+const BAR_WIDTH: f32 = 220.0;
+const BAR_HEIGHT: f32 = 12.0;
+const SLOT_SIZE: f32 = 56.0;
+const PANEL_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+const SLOT_BORDER: Color = Color::srgb(0.35, 0.35, 0.35);
+const SELECTED_BORDER: Color = Color::srgb(0.94, 0.78, 0.31);
+const EMPTY_TEXT: Color = Color::srgb(0.4, 0.4, 0.4);
+// The oxygen bar flashes this color when breath runs low
+const LOW_OXYGEN: f32 = 0.25;
+const WARNING_COLOR: Color = Color::srgb(0.95, 0.25, 0.2);
+// Yellow stands out from every bar for any color vision
+const SAFE_WARNING_COLOR: Color = Color::srgb(0.94, 0.89, 0.26);
+const WARNING_FLASHES_PER_SECOND: f32 = 2.0;
+
+// Gameplay HUD drawn with Bevy UI: health, stamina, thirst and oxygen bars over the hotbar.
+// F1 hides it, to take screenshots without it.
+#[derive(Default, Clone, Debug)]
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<HudVisible>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(Update, (
+                toggle_hud,
+                follow_window_camera,
+                update_stat_bars,
+                update_hotbar_slots,
+            ).chain());
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HudVisible(pub bool);
+
+impl Default for HudVisible {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stat {
+    Health,
+    Stamina,
+    Thirst,
+    // Only shown while some breath is missing
+    Oxygen,
+}
+
+impl Stat {
+    fn color(&self, vision: ColorVision) -> Color {
+        if vision != ColorVision::Normal {
+            // Okabe-Ito colors, told apart with any kind of color blindness
+            return match self {
+                Stat::Health => Color::srgb(0.9, 0.6, 0.0),
+                Stat::Stamina => Color::srgb(0.0, 0.62, 0.45),
+                Stat::Thirst => Color::srgb(0.34, 0.71, 0.91),
+                Stat::Oxygen => Color::srgb(0.0, 0.45, 0.7),
+            };
+        }
+        match self {
+            Stat::Health => Color::srgb(0.8, 0.2, 0.2),
+            Stat::Stamina => Color::srgb(0.35, 0.75, 0.3),
+            Stat::Thirst => Color::srgb(0.3, 0.8, 0.8),
+            Stat::Oxygen => Color::srgb(0.3, 0.6, 0.95),
+        }
+    }
+
+    fn warning_color(vision: ColorVision) -> Color {
+        if vision == ColorVision::Normal { WARNING_COLOR } else { SAFE_WARNING_COLOR }
+    }
+}
+
+#[derive(Component)]
+struct HudRoot;
+
+// Row holding a bar, hidden with it
+#[derive(Component)]
+struct StatRow(Stat);
+
+// Filled part of a bar, its width follows the stat
+#[derive(Component)]
+struct StatFill(Stat);
+
+#[derive(Component)]
+struct HotbarSlot(usize);
+
+#[derive(Component)]
+struct HotbarSlotLabel(usize);
+
+fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        HudRoot,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::FlexEnd,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(4.0),
+            padding: UiRect::bottom(Val::Px(8.0)),
+            ..default()
+        },
+        PickingBehavior::IGNORE,
+    )).with_children(|root| {
+        for stat in [Stat::Oxygen, Stat::Thirst, Stat::Stamina, Stat::Health] {
+            root.spawn((
+                StatRow(stat),
+                Node {
+                    width: Val::Px(BAR_WIDTH),
+                    height: Val::Px(BAR_HEIGHT),
+                    padding: UiRect::all(Val::Px(2.0)),
+                    ..default()
+                },
+                BackgroundColor(PANEL_COLOR),
+            )).with_child((
+                StatFill(stat),
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(stat.color(ColorVision::Normal)),
+            ));
+        }
+
+        root.spawn(Node {
+            column_gap: Val::Px(4.0),
+            margin: UiRect::top(Val::Px(4.0)),
+            ..default()
+        }).with_children(|bar| {
+            for index in 0..HOTBAR_SLOTS {
+                bar.spawn((
+                    HotbarSlot(index),
+                    Node {
+                        width: Val::Px(SLOT_SIZE),
+                        height: Val::Px(SLOT_SIZE),
+                        flex_direction: FlexDirection::Column,
+                        border: UiRect::all(Val::Px(1.0)),
+                        padding: UiRect::all(Val::Px(3.0)),
+                        ..default()
+                    },
+                    BackgroundColor(PANEL_COLOR),
+                    BorderColor(SLOT_BORDER),
+                )).with_children(|slot| {
+                    slot.spawn((
+                        Text::new(format!("{}", index + 1)),
+                        TextFont::from_font_size(10.0),
+                    ));
+                    slot.spawn((
+                        HotbarSlotLabel(index),
+                        Text::default(),
+                        TextFont::from_font_size(11.0),
+                    ));
+                });
+            }
+        });
+    });
+}
+
+fn toggle_hud(
+    input: Res<ButtonInput<KeyCode>>,
+    mut visible: ResMut<HudVisible>,
+    mut root_query: Query<&mut Visibility, With<HudRoot>>,
+) {
+    if input.just_pressed(HIDE_HUD_KEY) {
+        visible.0 = !visible.0;
+    }
+    if !visible.is_changed() {
+        return;
+    }
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if visible.0 { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+// Draw on whichever camera shows the window: the world camera, or the one stretching
+// it over the window while dynamic resolution is on
+fn follow_window_camera(
+    mut commands: Commands,
+    camera_query: Query<(Entity, &Camera)>,
+    root_query: Query<(Entity, Option<&TargetCamera>), With<HudRoot>>,
+) {
+    let window_camera = camera_query
+        .iter()
+        .filter(|(_, camera)| camera.is_active && matches!(camera.target, RenderTarget::Window(WindowRef::Primary)))
+        .max_by_key(|(_, camera)| camera.order)
+        .map(|(entity, _)| entity);
+    let Some(window_camera) = window_camera else {
+        return;
+    };
+    for (root, target) in root_query.iter() {
+        if target.map(|target| target.0) != Some(window_camera) {
+            commands.entity(root).insert(TargetCamera(window_camera));
+        }
+    }
+}
+
+fn update_stat_bars(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    player_query: Query<(&Health, &Stamina, &Thirst, &Oxygen), With<Player>>,
+    mut fill_query: Query<(&StatFill, &mut Node, &mut BackgroundColor)>,
+    mut row_query: Query<(&StatRow, &mut Visibility)>,
+) {
+    let Ok((health, stamina, thirst, oxygen)) = player_query.get_single() else {
+        return;
+    };
+    let vital = |stat: Stat| -> &Vital {
+        match stat {
+            Stat::Health => health,
+            Stat::Stamina => stamina,
+            Stat::Thirst => thirst,
+            Stat::Oxygen => oxygen,
+        }
+    };
+    let vision = settings.accessibility.color_vision;
+    for (fill, mut node, mut color) in fill_query.iter_mut() {
+        let width = Val::Percent(vital(fill.0).fraction() * 100.0);
+        if node.width != width {
+            node.width = width;
+        }
+        let fill_color = if fill.0 == Stat::Oxygen && oxygen.fraction() <= LOW_OXYGEN {
+            let flash = 0.5 + 0.5 * (time.elapsed_secs() * WARNING_FLASHES_PER_SECOND * std::f32::consts::TAU).sin();
+            fill.0.color(vision).mix(&Stat::warning_color(vision), flash)
+        } else {
+            fill.0.color(vision)
+        };
+        color.set_if_neq(BackgroundColor(fill_color));
+    }
+    for (row, mut visibility) in row_query.iter_mut() {
+        let shown = row.0 != Stat::Oxygen || !oxygen.is_full();
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+    }
+}
+
+fn update_hotbar_slots(
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    mut slot_query: Query<(&HotbarSlot, &mut BorderColor)>,
+    mut label_query: Query<(&HotbarSlotLabel, &mut Text, &mut TextColor)>,
+) {
+    if !hotbar.is_changed() && !inventory.is_changed() {
+        return;
+    }
+    for (slot, mut border) in slot_query.iter_mut() {
+        border.0 = if slot.0 == hotbar.selected { SELECTED_BORDER } else { SLOT_BORDER };
+    }
+    for (label, mut text, mut color) in label_query.iter_mut() {
+        let (content, count) = match &hotbar.slots[label.0] {
+            Some(item) => {
+                let count = inventory.count(item);
+                (format!("{}\n{}", item, count), count)
+            }
+            None => (String::new(), 0),
+        };
+        text.0 = content;
+        color.0 = if count > 0 { Color::WHITE } else { EMPTY_TEXT };
+    }
+}
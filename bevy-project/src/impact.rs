@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use bevy::audio::{SpatialScale, Volume};
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use crate::audio_mixer::{MixedAudio, EMITTER_SCALE};
+use crate::camera::FreeCamera;
+use crate::footprints::{ground_normal, SoftGround};
+use crate::palette::ActiveTerrainPalette;
+use crate::placement::{PlaceableKind, PlacedObject};
+use crate::pool::{EntityPool, EntityPoolPlugin, Pooled};
+use crate::projectile::{HitTarget, ProjectileHit, ProjectileKind};
+use crate::settings::GameSettings;
+use crate::terrain_edit::TerrainEdits;
+use crate::water::WaterQuery;
+
+// Oldest decals are removed first once this many exist
+const MAX_DECALS: usize = 48;
+const DECAL_LIFETIME: f32 = 30.0;
+const DECAL_SIZE: f32 = 0.5;
+// Small lift so decals don't z-fight with the terrain
+const DECAL_OFFSET: f32 = 0.02;
+const DECAL_TEXTURE_SIZE: u32 = 64;
+const PARTICLE_SIZE: f32 = 0.06;
+const PARTICLE_GRAVITY: f32 = 9.81;
+// Debris particles kept hidden for the next bursts instead of despawned
+const PARTICLE_POOL_CAPACITY: usize = 128;
+// Hits at this speed or faster make the full effect
+const FULL_STRENGTH_SPEED: f32 = 20.0;
+// Impacts further than this from the camera can't be heard
+const HEARING_DISTANCE: f32 = 40.0;
+
+// Feedback when something strikes the terrain or a prop: a mark left on the surface,
+// a burst of debris tinted like the ground and a sound, all depending on what was hit
+#[derive(Default, Clone, Debug)]
+pub struct ImpactPlugin;
+
+impl Plugin for ImpactPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<Impact>()
+            .init_resource::<ImpactDecals>()
+            .add_plugins(EntityPoolPlugin::<ImpactParticle>::new(PARTICLE_POOL_CAPACITY, reset_impact_particle))
+            .add_systems(Startup, setup_impact_assets)
+            .add_systems(Update, (
+                impacts_from_projectiles,
+                spawn_impacts,
+                update_impact_particles,
+                update_impact_decals,
+            ).chain());
+    }
+}
+
+// Something struck a surface; `strength` goes from 0 for a tap to 1 for a full blow
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Impact {
+    pub position: Vec3,
+    pub target: HitTarget,
+    pub strength: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImpactMaterial {
+    Sand,
+    Snow,
+    Dirt,
+    Rock,
+    Wood,
+    Water,
+}
+
+impl ImpactMaterial {
+    // Read from the terrain color and slope, like footprints, so it follows biomes and palettes
+    fn of_terrain(color: [f32; 4], normal: Vec3) -> Self {
+        match SoftGround::from_color(color) {
+            Some(SoftGround::Sand) => ImpactMaterial::Sand,
+            Some(SoftGround::Snow) => ImpactMaterial::Snow,
+            None if normal.y < 0.75 => ImpactMaterial::Rock,
+            None => ImpactMaterial::Dirt,
+        }
+    }
+
+    fn decal(&self) -> Option<DecalShape> {
+        match self {
+            ImpactMaterial::Sand | ImpactMaterial::Snow | ImpactMaterial::Dirt => Some(DecalShape::Crater),
+            ImpactMaterial::Rock => Some(DecalShape::Chip),
+            ImpactMaterial::Wood | ImpactMaterial::Water => None,
+        }
+    }
+
+    // Debris thrown up, and how fast
+    fn particles(&self) -> (u32, f32) {
+        match self {
+            ImpactMaterial::Sand | ImpactMaterial::Snow => (14, 2.5),
+            ImpactMaterial::Dirt => (10, 2.5),
+            ImpactMaterial::Rock => (8, 4.0),
+            ImpactMaterial::Wood => (6, 3.0),
+            ImpactMaterial::Water => (12, 3.5),
+        }
+    }
+
+    fn sound(&self) -> &'static str {
+        match self {
+            ImpactMaterial::Sand => "sounds/impact_sand.ogg",
+            ImpactMaterial::Snow => "sounds/impact_snow.ogg",
+            ImpactMaterial::Dirt => "sounds/impact_dirt.ogg",
+            ImpactMaterial::Rock => "sounds/impact_rock.ogg",
+            ImpactMaterial::Wood => "sounds/impact_wood.ogg",
+            ImpactMaterial::Water => "sounds/impact_water.ogg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecalShape {
+    // Dark dent with a raised rim, in soft ground
+    Crater,
+    // Pale scar with scattered flakes, on rock
+    Chip,
+}
+
+#[derive(Component)]
+struct ImpactDecal {
+    age: f32,
+    alpha: f32,
+    // Turn around the surface normal
+    spin: f32,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct ImpactParticle {
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+}
+
+// Ring buffer of spawned decals, oldest first
+#[derive(Resource, Default)]
+struct ImpactDecals {
+    entities: VecDeque<Entity>,
+    // Seeds the scatter of each burst
+    count: u32,
+}
+
+#[derive(Resource)]
+struct ImpactAssets {
+    decal_mesh: Handle<Mesh>,
+    particle_mesh: Handle<Mesh>,
+    crater: Handle<Image>,
+    chip: Handle<Image>,
+}
+
+fn setup_impact_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands.insert_resource(ImpactAssets {
+        decal_mesh: meshes.add(Plane3d::new(Vec3::Y, Vec2::splat(DECAL_SIZE / 2.0))),
+        particle_mesh: meshes.add(Cuboid::from_length(PARTICLE_SIZE)),
+        crater: images.add(decal_texture(DecalShape::Crater)),
+        chip: images.add(decal_texture(DecalShape::Chip)),
+    });
+}
+
+// Grayscale mark with alpha, tinted by the ground color through the material
+fn decal_texture(shape: DecalShape) -> Image {
+    let size = DECAL_TEXTURE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let offset = (Vec2::new(x as f32, y as f32) + 0.5) / size as f32 * 2.0 - 1.0;
+            let distance = offset.length();
+            let angle = offset.y.atan2(offset.x);
+            // Ragged outline rather than a perfect circle
+            let ragged = 1.0 + 0.12 * ((angle * 5.0).sin() + 0.5 * (angle * 11.0 + 1.0).sin());
+            let (shade, alpha) = match shape {
+                DecalShape::Crater => {
+                    let rim = (1.0 - ((distance * ragged - 0.7) / 0.15).abs()).clamp(0.0, 1.0);
+                    let pit = (1.0 - distance * ragged / 0.7).clamp(0.0, 1.0);
+                    (0.35 + 0.9 * rim, (pit * 0.9).max(rim * 0.6))
+                }
+                DecalShape::Chip => {
+                    let scar = (1.0 - distance * ragged / 0.45).clamp(0.0, 1.0);
+                    // Flakes scattered around in 4 pixel cells
+                    let flake = (unit(x / 4 + y / 4 * size, 3) > 0.8) as u32 as f32 * (1.0 - distance).max(0.0);
+                    (1.4, (scar * 0.8).max(flake * 0.6))
+                }
+            };
+            let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0) as u8;
+            let shade = byte(shade / 1.4);
+            data.extend_from_slice(&[shade, shade, shade, byte(alpha)]);
+        }
+    }
+    Image::new(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+// Cheap deterministic hash for scattering debris
+fn unit(seed: u32, salt: u32) -> f32 {
+    let mut value = seed.wrapping_mul(0x9E37_79B1) ^ salt.wrapping_mul(0x85EB_CA77);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x2C1B_3C6D);
+    value ^= value >> 12;
+    value as f32 / u32::MAX as f32
+}
+
+// Thrown rocks make impacts, fishing bobbers land quietly
+fn impacts_from_projectiles(
+    mut hits: EventReader<ProjectileHit>,
+    mut impacts: EventWriter<Impact>,
+) {
+    for hit in hits.read().filter(|hit| hit.kind == ProjectileKind::Rock) {
+        impacts.send(Impact {
+            position: hit.position,
+            target: hit.target,
+            strength: (hit.velocity.length() / FULL_STRENGTH_SPEED).min(1.0),
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_impacts(
+    mut commands: Commands,
+    mut impacts: EventReader<Impact>,
+    mut decals: ResMut<ImpactDecals>,
+    mut particle_pool: ResMut<EntityPool<ImpactParticle>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    assets: Option<Res<ImpactAssets>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<GameSettings>,
+    terrain_edits: Res<TerrainEdits>,
+    palette: Res<ActiveTerrainPalette>,
+    water: WaterQuery,
+    placed_query: Query<&PlacedObject>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    for impact in impacts.read() {
+        let position = impact.position;
+        let ground = terrain_edits.height(position.x, position.z);
+        let normal = ground_normal(&terrain_edits, position.x, position.z);
+        let ground_color = palette.0.color(position.x, position.z, ground);
+        let material = match impact.target {
+            _ if water.is_in_water(position) => ImpactMaterial::Water,
+            HitTarget::Terrain => ImpactMaterial::of_terrain(ground_color, normal),
+            HitTarget::Entity(entity) => match placed_query.get(entity).map(|placed| placed.kind) {
+                Ok(PlaceableKind::Campfire) => ImpactMaterial::Rock,
+                Ok(_) => ImpactMaterial::Wood,
+                // Creatures
+                Err(_) => continue,
+            },
+        };
+        let color = match material {
+            ImpactMaterial::Wood => Color::srgb(0.45, 0.32, 0.2),
+            ImpactMaterial::Water => Color::srgb(0.8, 0.88, 0.95),
+            _ => Color::srgb(ground_color[0], ground_color[1], ground_color[2]),
+        };
+        decals.count = decals.count.wrapping_add(1);
+        let seed = decals.count;
+
+        if let Some(shape) = material.decal()
+            && impact.target == HitTarget::Terrain
+        {
+            let alpha = 0.5 + 0.5 * impact.strength;
+            let decal_material = materials.add(StandardMaterial {
+                base_color: color.darker(0.25).with_alpha(alpha),
+                base_color_texture: Some(match shape {
+                    DecalShape::Crater => assets.crater.clone(),
+                    DecalShape::Chip => assets.chip.clone(),
+                }),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 1.0,
+                ..default()
+            });
+            let scale = 0.6 + 0.6 * impact.strength;
+            let spin = unit(seed, 7) * std::f32::consts::TAU;
+            let entity = commands.spawn((
+                Mesh3d(assets.decal_mesh.clone()),
+                MeshMaterial3d(decal_material.clone()),
+                Transform::from_translation(Vec3::new(position.x, ground, position.z) + normal * DECAL_OFFSET)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::Y, normal) * Quat::from_rotation_y(spin))
+                    .with_scale(Vec3::splat(scale)),
+                ImpactDecal { age: 0.0, alpha, spin, material: decal_material },
+            )).id();
+            decals.entities.push_back(entity);
+            if decals.entities.len() > MAX_DECALS
+                && let Some(oldest) = decals.entities.pop_front()
+            {
+                commands.entity(oldest).despawn();
+            }
+        }
+
+        // Debris shares one material per burst, dropped with the last particle
+        let (count, speed) = material.particles();
+        let count = (count as f32 * (0.4 + 0.6 * impact.strength)).ceil() as u32;
+        let particle_material = materials.add(StandardMaterial {
+            base_color: color,
+            perceptual_roughness: 1.0,
+            ..default()
+        });
+        let up = if impact.target == HitTarget::Terrain { normal } else { Vec3::Y };
+        for index in 0..count {
+            let angle = unit(seed, index * 3) * std::f32::consts::TAU;
+            let spread = 0.3 + 0.6 * unit(seed, index * 3 + 1);
+            let side = (Quat::from_rotation_arc(Vec3::Y, up) * Vec3::new(angle.cos(), 0.0, angle.sin())) * spread;
+            let velocity = (up + side).normalize() * speed * (0.5 + 0.5 * unit(seed, index * 3 + 2)) * (0.5 + 0.5 * impact.strength);
+            particle_pool.acquire(&mut commands, (
+                Mesh3d(assets.particle_mesh.clone()),
+                MeshMaterial3d(particle_material.clone()),
+                Transform::from_translation(position + up * PARTICLE_SIZE),
+                ImpactParticle { velocity, age: 0.0, lifetime: 0.5 + 0.4 * unit(seed, index + 97) },
+            ));
+        }
+
+        let distance = camera_query.get_single().map_or(0.0, |camera| camera.translation.distance(position));
+        let volume = settings.audio.master_volume * settings.audio.effects_volume * (0.3 + 0.7 * impact.strength);
+        if distance < HEARING_DISTANCE && volume > 0.0 {
+            commands.spawn((
+                AudioPlayer::<MixedAudio>(asset_server.load(material.sound())),
+                PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new(volume))
+                    .with_spatial(true)
+                    .with_spatial_scale(SpatialScale::new(EMITTER_SCALE)),
+                Transform::from_translation(position),
+            ));
+        }
+    }
+}
+
+// Particles go back to the pool rather than despawning, bursts reuse them
+fn reset_impact_particle(entity: &mut EntityCommands) {
+    entity.remove::<(Mesh3d, MeshMaterial3d<StandardMaterial>, ImpactParticle)>();
+}
+
+fn update_impact_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    terrain_edits: Res<TerrainEdits>,
+    mut particle_pool: ResMut<EntityPool<ImpactParticle>>,
+    mut particle_query: Query<(Entity, &mut ImpactParticle, &mut Transform), Without<Pooled>>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut particle, mut transform) in particle_query.iter_mut() {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            particle_pool.release(&mut commands, entity);
+            continue;
+        }
+        particle.velocity.y -= PARTICLE_GRAVITY * dt;
+        transform.translation += particle.velocity * dt;
+        // Debris settles on the ground rather than falling through it
+        let ground = terrain_edits.height(transform.translation.x, transform.translation.z) + PARTICLE_SIZE / 2.0;
+        if transform.translation.y < ground {
+            transform.translation.y = ground;
+            particle.velocity = Vec3::ZERO;
+        }
+        transform.scale = Vec3::splat(1.0 - particle.age / particle.lifetime);
+    }
+}
+
+// Fade decals out at the end of their life and keep them on the ground when it's edited
+fn update_impact_decals(
+    mut commands: Commands,
+    time: Res<Time>,
+    terrain_edits: Res<TerrainEdits>,
+    mut decals: ResMut<ImpactDecals>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut decal_query: Query<(Entity, &mut ImpactDecal, &mut Transform)>,
+) {
+    for (entity, mut decal, mut transform) in decal_query.iter_mut() {
+        decal.age += time.delta_secs();
+        if decal.age >= DECAL_LIFETIME {
+            commands.entity(entity).despawn();
+            decals.entities.retain(|&other| other != entity);
+            continue;
+        }
+        if terrain_edits.is_changed() {
+            let (x, z) = (transform.translation.x, transform.translation.z);
+            let normal = ground_normal(&terrain_edits, x, z);
+            transform.translation = Vec3::new(x, terrain_edits.height(x, z), z) + normal * DECAL_OFFSET;
+            transform.rotation = Quat::from_rotation_arc(Vec3::Y, normal) * Quat::from_rotation_y(decal.spin);
+        }
+        // Hold for a while, then fade out over the last third of the lifetime
+        let fade = (DECAL_LIFETIME - decal.age) / (DECAL_LIFETIME / 3.0);
+        if fade < 1.0
+            && let Some(material) = materials.get_mut(&decal.material)
+        {
+            material.base_color.set_alpha(decal.alpha * fade);
+        }
+    }
+}
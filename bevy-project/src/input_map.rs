@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, EguiPreUpdateSet};
+use serde::{Deserialize, Serialize};
+use crate::placement::PlacementState;
+use crate::settings::GameSettings;
+
+// Gameplay keys go through named actions, bound per input context in the `controls` section
+// of the settings so players can rebind them. Bindings shared by two actions are reported.
+#[derive(Default, Clone, Debug)]
+pub struct InputMapPlugin;
+
+impl Plugin for InputMapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ActiveInputContext>()
+            .add_systems(PreUpdate, update_input_context.after(EguiPreUpdateSet::InitContexts))
+            .add_systems(Update, warn_binding_conflicts);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Crouch,
+    Sprint,
+    Interact,
+    Torch,
+    EmoteWheel,
+    RaiseTerrain,
+    LowerTerrain,
+    ToggleBuild,
+    CyclePiece,
+    RotateLeft,
+    RotateRight,
+    CancelBuild,
+}
+
+// Which map the keys are looked up in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum InputContext {
+    #[default]
+    Gameplay,
+    // Placing a building piece or object, gameplay bindings still apply unless overridden
+    Build,
+    // A text field has the keyboard, nothing from gameplay fires
+    Menu,
+}
+
+impl InputContext {
+    // Context whose bindings apply to actions this one doesn't bind
+    fn fallback(&self) -> Option<InputContext> {
+        match self {
+            InputContext::Build => Some(InputContext::Gameplay),
+            InputContext::Gameplay | InputContext::Menu => None,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ActiveInputContext(pub InputContext);
+
+// A key, held together with modifiers for chords such as Ctrl+K
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Binding {
+    pub key: KeyCode,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ctrl: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shift: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub alt: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl Binding {
+    pub const fn key(key: KeyCode) -> Self {
+        Self { key, ctrl: false, shift: false, alt: false }
+    }
+
+    fn modifier_count(&self) -> usize {
+        [self.ctrl, self.shift, self.alt].into_iter().filter(|held| *held).count()
+    }
+
+    fn modifiers_held(&self, keys: &ButtonInput<KeyCode>) -> bool {
+        let held = |left, right| keys.any_pressed([left, right]);
+        (!self.ctrl || held(KeyCode::ControlLeft, KeyCode::ControlRight))
+            && (!self.shift || held(KeyCode::ShiftLeft, KeyCode::ShiftRight))
+            && (!self.alt || held(KeyCode::AltLeft, KeyCode::AltRight))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl+"), (self.shift, "Shift+"), (self.alt, "Alt+")] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        let name = format!("{:?}", self.key);
+        let name = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit")).unwrap_or(&name);
+        f.write_str(name)
+    }
+}
+
+// Two actions reachable from the same binding in one context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub context: InputContext,
+    pub binding: Binding,
+    pub actions: (Action, Action),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct InputMap {
+    pub gameplay: BTreeMap<Action, Vec<Binding>>,
+    pub build: BTreeMap<Action, Vec<Binding>>,
+    pub menu: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let map = |bindings: &[(Action, KeyCode)]| bindings
+            .iter()
+            .map(|(action, key)| (*action, vec![Binding::key(*key)]))
+            .collect();
+        Self {
+            gameplay: map(&[
+                (Action::MoveForward, KeyCode::KeyW),
+                (Action::MoveBack, KeyCode::KeyS),
+                (Action::MoveLeft, KeyCode::KeyA),
+                (Action::MoveRight, KeyCode::KeyD),
+                (Action::Jump, KeyCode::Space),
+                (Action::Crouch, KeyCode::ControlLeft),
+                (Action::Sprint, KeyCode::ShiftLeft),
+                (Action::Interact, KeyCode::KeyE),
+                (Action::Torch, KeyCode::KeyT),
+                (Action::EmoteWheel, KeyCode::KeyG),
+                (Action::RaiseTerrain, KeyCode::KeyR),
+                (Action::LowerTerrain, KeyCode::KeyF),
+                (Action::ToggleBuild, KeyCode::KeyB),
+            ]),
+            build: map(&[
+                (Action::CyclePiece, KeyCode::Tab),
+                (Action::RotateLeft, KeyCode::KeyZ),
+                (Action::RotateRight, KeyCode::KeyX),
+                (Action::CancelBuild, KeyCode::Escape),
+            ]),
+            menu: BTreeMap::new(),
+        }
+    }
+}
+
+impl InputMap {
+    fn context_map(&self, context: InputContext) -> &BTreeMap<Action, Vec<Binding>> {
+        match context {
+            InputContext::Gameplay => &self.gameplay,
+            InputContext::Build => &self.build,
+            InputContext::Menu => &self.menu,
+        }
+    }
+
+    // Bindings of an action in a context, its own or its fallback's
+    pub fn bindings(&self, context: InputContext, action: Action) -> &[Binding] {
+        match self.context_map(context).get(&action) {
+            Some(bindings) => bindings,
+            None => context.fallback().map_or(&[], |fallback| self.bindings(fallback, action)),
+        }
+    }
+
+    // Every binding in effect in a context
+    pub fn effective(&self, context: InputContext) -> Vec<(Action, Binding)> {
+        let own = self.context_map(context);
+        let inherited = context.fallback().map(|fallback| self.effective(fallback)).unwrap_or_default();
+        own.iter()
+            .flat_map(|(action, bindings)| bindings.iter().map(|binding| (*action, *binding)))
+            .chain(inherited.into_iter().filter(|(action, _)| !own.contains_key(action)))
+            .collect()
+    }
+
+    // Bindings that trigger more than one action, each reported once
+    pub fn conflicts(&self) -> Vec<BindingConflict> {
+        let mut conflicts: Vec<BindingConflict> = Vec::new();
+        for context in [InputContext::Gameplay, InputContext::Build, InputContext::Menu] {
+            let bindings = self.effective(context);
+            for (index, (first, binding)) in bindings.iter().enumerate() {
+                for (second, other) in &bindings[index + 1..] {
+                    let seen = conflicts.iter().any(|conflict| conflict.binding == *binding && conflict.actions == (*first, *second));
+                    if first != second && binding == other && !seen {
+                        conflicts.push(BindingConflict { context, binding: *binding, actions: (*first, *second) });
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+// Queries actions through the active context's bindings instead of raw keys
+#[derive(SystemParam)]
+pub struct Actions<'w> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    settings: Res<'w, GameSettings>,
+    context: Res<'w, ActiveInputContext>,
+}
+
+impl Actions<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.any_binding(action, |key| self.keys.pressed(key))
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.any_binding(action, |key| self.keys.just_pressed(key))
+    }
+
+    fn any_binding(&self, action: Action, key_state: impl Fn(KeyCode) -> bool) -> bool {
+        self.settings.controls
+            .bindings(self.context.0, action)
+            .iter()
+            .any(|binding| key_state(binding.key) && self.active(binding))
+    }
+
+    // Modifiers held and no longer chord on the same key held, so Ctrl+K doesn't also fire K
+    fn active(&self, binding: &Binding) -> bool {
+        binding.modifiers_held(&self.keys)
+            && !self.settings.controls.effective(self.context.0).iter().any(|(_, other)| {
+                other.key == binding.key
+                    && other.modifier_count() > binding.modifier_count()
+                    && other.modifiers_held(&self.keys)
+            })
+    }
+}
+
+fn update_input_context(
+    mut contexts: EguiContexts,
+    placement: Res<PlacementState>,
+    mut active: ResMut<ActiveInputContext>,
+) {
+    let typing = contexts.try_ctx_mut().is_some_and(|ctx| ctx.wants_keyboard_input());
+    let context = if typing {
+        InputContext::Menu
+    } else if placement.active.is_some() {
+        InputContext::Build
+    } else {
+        InputContext::Gameplay
+    };
+    active.set_if_neq(ActiveInputContext(context));
+}
+
+fn warn_binding_conflicts(
+    settings: Res<GameSettings>,
+    mut reported: Local<Vec<BindingConflict>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let conflicts = settings.controls.conflicts();
+    if conflicts == *reported {
+        return;
+    }
+    for conflict in conflicts.iter().filter(|conflict| !reported.contains(conflict)) {
+        warn!(
+            "{} is bound to both {:?} and {:?} in the {:?} context",
+            conflict.binding, conflict.actions.0, conflict.actions.1, conflict.context,
+        );
+    }
+    *reported = conflicts;
+}
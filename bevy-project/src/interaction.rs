@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::accessibility::show_prompt;
+use crate::player::Player;
+use crate::input_map::{Action, Actions};
+use crate::settings::GameSettings;
+
+#[derive(Default, Clone, Debug)]
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<InteractionFocus>()
+            .add_event::<InteractEvent>()
+            .add_systems(Update, (
+                update_interaction_focus,
+                send_interactions,
+                interaction_prompt_ui,
+            ).chain());
+    }
+}
+
+// Something the player can use with the interact key when close enough
+#[derive(Component, Clone, Debug)]
+pub struct Interactable {
+    pub prompt: String,
+    pub radius: f32,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct InteractEvent {
+    pub actor: Entity,
+    pub target: Entity,
+}
+
+// Closest interactable in range of the player
+#[derive(Resource, Default)]
+pub struct InteractionFocus {
+    pub target: Option<Entity>,
+}
+
+fn update_interaction_focus(
+    mut focus: ResMut<InteractionFocus>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    interactables: Query<(Entity, &GlobalTransform, &Interactable)>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        focus.target = None;
+        return;
+    };
+    let player_position = player_transform.translation();
+
+    focus.target = interactables.iter()
+        .map(|(entity, transform, interactable)| {
+            (entity, transform.translation().distance(player_position), interactable.radius)
+        })
+        .filter(|(_, distance, radius)| distance <= radius)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _, _)| entity);
+}
+
+fn send_interactions(
+    actions: Actions,
+    focus: Res<InteractionFocus>,
+    player_query: Query<Entity, With<Player>>,
+    mut events: EventWriter<InteractEvent>,
+) {
+    if !actions.just_pressed(Action::Interact) {
+        return;
+    }
+    if let (Some(target), Ok(actor)) = (focus.target, player_query.get_single()) {
+        events.send(InteractEvent { actor, target });
+    }
+}
+
+fn interaction_prompt_ui(
+    mut contexts: EguiContexts,
+    focus: Res<InteractionFocus>,
+    settings: Res<GameSettings>,
+    interactables: Query<&Interactable>,
+) {
+    let Some(interactable) = focus.target.and_then(|target| interactables.get(target).ok()) else {
+        return;
+    };
+
+    let prompt = format!("[E] {}", interactable.prompt);
+    show_prompt(contexts.ctx_mut(), "interaction_prompt", &settings.accessibility, egui::Align2::CENTER_BOTTOM, [0.0, -80.0], |ui| {
+        ui.label(egui::RichText::new(prompt).heading());
+    });
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use crate::fishing::FISHING_ROD;
+use crate::items::{item_label, ItemRegistry};
+use crate::net::{ClientMessage, ServerConnection, ServerEvent, ServerMessage};
+use crate::quest::ItemCollected;
+use crate::thirst::WATERSKIN;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+use crate::vendor::COIN;
+
+// What a new game starts with, enough for a couple of campfires, a rod to fish with, a
+// waterskin to carry water and a few coins for the village vendors
+const STARTING_ITEMS: [(&str, u32); 4] = [("wood", 6), (FISHING_ROD, 1), (WATERSKIN, 1), (COIN, 10)];
+const INVENTORY_WINDOW: &str = "inventory";
+
+#[derive(Default, Clone, Debug)]
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Inventory>()
+            .register_ui_window(UiWindow {
+                id: INVENTORY_WINDOW,
+                title: "Inventory",
+                shortcut: Some(KeyCode::KeyI),
+                open: false,
+            })
+            .add_systems(Update, (restore_account_inventory, collect_items, send_inventory, inventory_ui_system).chain());
+    }
+}
+
+// Items carried by the player, by item id
+#[derive(Resource, Debug, Clone)]
+pub struct Inventory {
+    pub items: HashMap<String, u32>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            items: STARTING_ITEMS.iter().map(|(item, count)| (item.to_string(), *count)).collect(),
+        }
+    }
+}
+
+impl Inventory {
+    pub fn count(&self, item: &str) -> u32 {
+        self.items.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn has_all(&self, items: &[(&str, u32)]) -> bool {
+        items.iter().all(|(item, count)| self.count(item) >= *count)
+    }
+
+    // Remove all the items or none of them
+    pub fn take_all(&mut self, items: &[(&str, u32)]) -> bool {
+        if !self.has_all(items) {
+            return false;
+        }
+        for (item, count) in items {
+            if let Some(held) = self.items.get_mut(*item) {
+                *held -= count;
+            }
+        }
+        self.items.retain(|_, count| *count > 0);
+        true
+    }
+}
+
+fn collect_items(
+    mut collected: EventReader<ItemCollected>,
+    mut inventory: ResMut<Inventory>,
+) {
+    for event in collected.read() {
+        *inventory.items.entry(event.item.clone()).or_insert(0) += event.count;
+    }
+}
+
+// The server keeps the inventory of players with an account, and is told what changed
+// while the connection was lost once a session starts again
+fn restore_account_inventory(
+    mut server_events: EventReader<ServerEvent>,
+    mut inventory: ResMut<Inventory>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        match message {
+            ServerMessage::AccountRestored { inventory: Some(items), .. } => inventory.items = items.clone(),
+            ServerMessage::Joined { .. } => inventory.set_changed(),
+            _ => {}
+        }
+    }
+}
+
+fn send_inventory(
+    inventory: Res<Inventory>,
+    mut connection: ResMut<ServerConnection>,
+) {
+    if inventory.is_changed() {
+        connection.send(&ClientMessage::InventoryState { items: inventory.items.clone() });
+    }
+}
+
+fn inventory_ui_system(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    inventory: Res<Inventory>,
+    registry: Res<ItemRegistry>,
+) {
+    let Some(window) = windows.window(INVENTORY_WINDOW) else {
+        return;
+    };
+    // Grouped by category, then by name
+    let mut items: Vec<(&String, &u32)> = inventory.items.iter().collect();
+    items.sort_by_key(|(item, _)| (registry.get(item).map(|definition| definition.category as u8), registry.name(item)));
+    window.resizable(false).show(contexts.ctx_mut(), |ui| {
+        if items.is_empty() {
+            ui.label("Empty");
+        }
+        for (item, count) in items {
+            item_label(ui, &registry, item, Some(*count));
+        }
+        ui.separator();
+        let slots: u32 = inventory.items.iter().map(|(item, count)| registry.stacks(item, *count)).sum();
+        ui.weak(format!("{:.1} kg, {} slots", registry.total_weight(&inventory.items), slots));
+    });
+}
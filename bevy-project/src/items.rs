@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::ron_asset::RonAssetPlugin;
+
+const ITEM_DATABASE_PATH: &str = "items/default.items.ron";
+const ITEM_DATABASE: &str = "item_database";
+// Shown for items missing from the database
+const UNKNOWN_ICON: &str = "❓";
+
+// Item names, icons, stack sizes, weights and categories, read from a RON item database so
+// new content needs no code. Items are still referred to by their id everywhere else.
+#[derive(Default, Clone, Debug)]
+pub struct ItemsPlugin;
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<ItemDatabase>::new(&["items.ron"]))
+            .preload_asset::<ItemDatabase>(ITEM_DATABASE, ITEM_DATABASE_PATH)
+            .init_resource::<ItemRegistry>()
+            .add_systems(Update, fill_item_registry);
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ItemCategory {
+    #[default]
+    Material,
+    Food,
+    Drink,
+    Tool,
+    Currency,
+}
+
+impl ItemCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ItemCategory::Material => "Material",
+            ItemCategory::Food => "Food",
+            ItemCategory::Drink => "Drink",
+            ItemCategory::Tool => "Tool",
+            ItemCategory::Currency => "Currency",
+        }
+    }
+}
+
+fn default_stack_size() -> u32 {
+    99
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // A glyph of the UI font
+    pub icon: String,
+    // Most of the item held in one inventory slot
+    #[serde(default = "default_stack_size")]
+    pub stack_size: u32,
+    // Kilograms, for one
+    #[serde(default)]
+    pub weight: f32,
+    #[serde(default)]
+    pub category: ItemCategory,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct ItemDatabase {
+    pub items: Vec<ItemDefinition>,
+}
+
+// The loaded item database by item id, empty until it loads
+#[derive(Resource, Default, Debug)]
+pub struct ItemRegistry {
+    items: HashMap<String, ItemDefinition>,
+}
+
+impl ItemRegistry {
+    pub fn from_database(database: &ItemDatabase) -> Self {
+        let mut items = HashMap::new();
+        for item in &database.items {
+            if items.insert(item.id.clone(), item.clone()).is_some() {
+                warn!("Item {} is defined twice, the last definition is used", item.id);
+            }
+        }
+        Self { items }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+
+    // Display name, the id itself for unknown items
+    pub fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.get(id).map_or(id, |item| item.name.as_str())
+    }
+
+    pub fn icon(&self, id: &str) -> &str {
+        self.get(id).map_or(UNKNOWN_ICON, |item| item.icon.as_str())
+    }
+
+    // Inventory slots taken by a number of an item, unknown items stacking without limit
+    pub fn stacks(&self, id: &str, count: u32) -> u32 {
+        let stack_size = self.get(id).map_or(u32::MAX, |item| item.stack_size.max(1));
+        count.div_ceil(stack_size)
+    }
+
+    // Kilograms carried in total, unknown items weighing nothing
+    pub fn total_weight(&self, items: &HashMap<String, u32>) -> f32 {
+        items.iter()
+            .map(|(id, count)| self.get(id).map_or(0.0, |item| item.weight) * *count as f32)
+            .sum()
+    }
+}
+
+// Icon and name of an item, and how many are held if given, with its details in a tooltip
+pub fn item_label(ui: &mut egui::Ui, registry: &ItemRegistry, id: &str, count: Option<u32>) -> egui::Response {
+    let text = match count {
+        Some(count) => format!("{} {} x{}", registry.icon(id), registry.name(id), count),
+        None => format!("{} {}", registry.icon(id), registry.name(id)),
+    };
+    ui.label(text).on_hover_ui(|ui| item_tooltip(ui, registry, id, count))
+}
+
+fn item_tooltip(ui: &mut egui::Ui, registry: &ItemRegistry, id: &str, count: Option<u32>) {
+    let Some(item) = registry.get(id) else {
+        ui.label(egui::RichText::new(id).strong());
+        ui.weak("Unknown item");
+        return;
+    };
+    ui.label(egui::RichText::new(format!("{} {}", item.icon, item.name)).strong().size(16.0));
+    ui.weak(item.category.label());
+    if !item.description.is_empty() {
+        ui.label(&item.description);
+    }
+    ui.separator();
+    match count {
+        Some(count) => {
+            ui.label(format!("Weight: {:.2} kg each, {:.1} kg held", item.weight, item.weight * count as f32));
+            ui.label(format!("Stacks of {}, {} slots", item.stack_size, registry.stacks(id, count)));
+        }
+        None => {
+            ui.label(format!("Weight: {:.2} kg", item.weight));
+            ui.label(format!("Stacks of {}", item.stack_size));
+        }
+    }
+}
+
+fn fill_item_registry(
+    mut events: EventReader<AssetEvent<ItemDatabase>>,
+    asset_registry: Res<AssetRegistry>,
+    databases: Res<Assets<ItemDatabase>>,
+    mut registry: ResMut<ItemRegistry>,
+) {
+    let Some(handle) = asset_registry.get::<ItemDatabase>(ITEM_DATABASE) else {
+        return;
+    };
+    let loaded = events.read().any(|event| {
+        matches!(event, AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } if *id == handle.id())
+    });
+    let Some(database) = loaded.then(|| databases.get(&handle)).flatten() else {
+        return;
+    };
+    *registry = ItemRegistry::from_database(database);
+    info!("Item database {} applied, {} items", ITEM_DATABASE_PATH, registry.items.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_parses_and_stacks() {
+        let database: ItemDatabase = ron::from_str(include_str!("../assets/items/default.items.ron")).unwrap();
+        let registry = ItemRegistry::from_database(&database);
+        assert_eq!(registry.name("fishing_rod"), "Fishing Rod");
+        assert_eq!(registry.name("mystery"), "mystery");
+        assert_eq!(registry.stacks("fishing_rod", 3), 3);
+        assert_eq!(registry.stacks("wood", 51), 2);
+        assert_eq!(registry.stacks("mystery", 1000), 1);
+
+        let carried = HashMap::from([("stone".to_string(), 2), ("mystery".to_string(), 5)]);
+        assert_eq!(registry.total_weight(&carried), 3.0);
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+use crate::biome::{self, Biome};
+use crate::building::Structures;
+use crate::camera::FreeCamera;
+use crate::console::{CommandResult, ConsoleAppExt, ConsoleCommand};
+use crate::placement::{PlaceableKind, PlacedObject};
+use crate::player::Player;
+use crate::terrain;
+
+// Biome searches sample rings this far apart, out to LOCATE_RADIUS
+const LOCATE_STEP: f32 = 48.0;
+const LOCATE_RADIUS: f32 = 6000.0;
+const BEACON_HEIGHT: f32 = 60.0;
+const BEACON_RADIUS: f32 = 2.0;
+const BEACON_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+
+// Debug commands finding the nearest biome or built structure of a kind, and an optional
+// waypoint beacon drawn over the result
+#[derive(Default, Clone, Debug)]
+pub struct LocatePlugin;
+
+impl Plugin for LocatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Waypoint>()
+            .register_console_command(ConsoleCommand {
+                name: "locate",
+                usage: "locate <biome|structure> <name> [waypoint]",
+                help: "find the nearest biome or built structure",
+                run: locate_command,
+            })
+            .register_console_command(ConsoleCommand {
+                name: "waypoint",
+                usage: "waypoint [<x> <z>|clear]",
+                help: "show, set or clear the waypoint",
+                run: waypoint_command,
+            })
+            .add_systems(Update, draw_waypoint);
+    }
+}
+
+#[derive(Resource, Default, Debug, Clone, Copy)]
+pub struct Waypoint(pub Option<Vec3>);
+
+// Points on rings of growing radius around `origin`, nearest ring first
+fn search_rings(origin: Vec2, step: f32, radius: f32) -> impl Iterator<Item = Vec<Vec2>> {
+    (0..=(radius / step) as u32).map(move |ring| {
+        if ring == 0 {
+            return vec![origin];
+        }
+        let ring_radius = ring as f32 * step;
+        let count = ((std::f32::consts::TAU * ring_radius / step).ceil() as u32).max(8);
+        (0..count)
+            .map(|index| origin + Vec2::from_angle(index as f32 / count as f32 * std::f32::consts::TAU) * ring_radius)
+            .collect()
+    })
+}
+
+fn find_biome(origin: Vec2, biome: Biome) -> Option<Vec2> {
+    search_rings(origin, LOCATE_STEP, LOCATE_RADIUS).find_map(|ring| {
+        ring.into_iter()
+            .filter(|point| biome::biome_at(point.x, point.y) == biome)
+            .min_by(|a, b| a.distance_squared(origin).total_cmp(&b.distance_squared(origin)))
+    })
+}
+
+fn parse_biome(name: &str) -> Result<Biome, String> {
+    Biome::ALL
+        .into_iter()
+        .find(|biome| biome.label().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!(
+            "unknown biome '{}', one of {}",
+            name,
+            Biome::ALL.map(|biome| biome.label().to_lowercase()).join(", "),
+        ))
+}
+
+fn parse_kind(name: &str) -> Result<PlaceableKind, String> {
+    PlaceableKind::ALL
+        .into_iter()
+        .find(|kind| kind.label().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!(
+            "unknown structure '{}', one of {}",
+            name,
+            PlaceableKind::ALL.map(|kind| kind.label().to_lowercase()).join(", "),
+        ))
+}
+
+// Where searches start: the player, or the camera without one
+fn search_origin(world: &mut World) -> Option<Vec3> {
+    let player = world.query_filtered::<&Transform, With<Player>>().get_single(world).ok().map(|transform| transform.translation);
+    player.or_else(|| world.query_filtered::<&Transform, With<FreeCamera>>().get_single(world).ok().map(|transform| transform.translation))
+}
+
+// Nearest built piece or placed object of a kind; pieces of unloaded chunks count too
+fn find_structure(world: &mut World, origin: Vec2, kind: PlaceableKind) -> Option<Vec3> {
+    let mut candidates: Vec<Vec3> = world
+        .query::<(&Transform, &PlacedObject)>()
+        .iter(world)
+        .filter(|(_, object)| object.kind == kind)
+        .map(|(transform, _)| transform.translation)
+        .collect();
+    if let Some(structures) = world.get_resource::<Structures>() {
+        candidates.extend(structures.chunks.values().flatten().filter(|piece| piece.kind == kind).map(|piece| piece.position));
+    }
+    candidates.into_iter().min_by(|a, b| a.xz().distance_squared(origin).total_cmp(&b.xz().distance_squared(origin)))
+}
+
+fn locate_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let (category, name, waypoint) = match args {
+        [category, name] => (*category, *name, false),
+        [category, name, "waypoint"] => (*category, *name, true),
+        _ => return Err("usage: locate <biome|structure> <name> [waypoint]".to_string()),
+    };
+    let origin = search_origin(world).ok_or("nothing to search from")?;
+    let found = match category {
+        "biome" => {
+            let biome = parse_biome(name)?;
+            find_biome(origin.xz(), biome)
+                .map(|point| Vec3::new(point.x, terrain::height(point.x, point.y), point.y))
+                .ok_or_else(|| format!("no {} within {:.0} m", biome.label(), LOCATE_RADIUS))?
+        }
+        "structure" => {
+            let kind = parse_kind(name)?;
+            find_structure(world, origin.xz(), kind).ok_or_else(|| format!("no {} has been built", kind.label()))?
+        }
+        _ => return Err(format!("can't locate '{}', try biome or structure", category)),
+    };
+    if waypoint {
+        world.resource_mut::<Waypoint>().0 = Some(found);
+    }
+    Ok(format!(
+        "{} at ({:.0}, {:.0}), {:.0} m away{}",
+        name,
+        found.x,
+        found.z,
+        found.xz().distance(origin.xz()),
+        if waypoint { ", waypoint set" } else { "" },
+    ))
+}
+
+fn waypoint_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let mut waypoint = world.resource_mut::<Waypoint>();
+    match args {
+        [] => Ok(match waypoint.0 {
+            Some(point) => format!("waypoint at ({:.0}, {:.0})", point.x, point.z),
+            None => "no waypoint".to_string(),
+        }),
+        ["clear"] => {
+            waypoint.0 = None;
+            Ok("waypoint cleared".to_string())
+        }
+        [x, z] => {
+            let parse = |value: &str| value.parse::<f32>().map_err(|_| format!("`{}` is not a coordinate", value));
+            let (x, z) = (parse(x)?, parse(z)?);
+            waypoint.0 = Some(Vec3::new(x, terrain::height(x, z), z));
+            Ok(format!("waypoint set at ({:.0}, {:.0})", x, z))
+        }
+        _ => Err("usage: waypoint [<x> <z>|clear]".to_string()),
+    }
+}
+
+// Tall beam with a ring at its foot, visible from afar
+fn draw_waypoint(mut gizmos: Gizmos, waypoint: Res<Waypoint>) {
+    let Some(point) = waypoint.0 else {
+        return;
+    };
+    gizmos.line(point, point + Vec3::Y * BEACON_HEIGHT, BEACON_COLOR);
+    gizmos.circle(
+        Isometry3d::new(point + Vec3::Y * 0.2, Quat::from_rotation_x(std::f32::consts::FRAC_PI_2)),
+        BEACON_RADIUS,
+        BEACON_COLOR,
+    );
+}
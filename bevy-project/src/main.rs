@@ -4,16 +4,170 @@ mod camera;
 use std::env;
 mod ground;
 mod water;
+mod ron_asset;
+mod quest;
+mod terrain;
+mod chunk_debug;
+mod pool;
+mod net;
+mod transport;
+mod server;
+mod terrain_edit;
+mod settings;
+mod capture;
+mod interaction;
+mod boat;
+mod terrain_material;
+mod streaming;
+mod diagnostics;
+mod fish;
+mod day_night;
+mod torch;
+mod biome;
+mod palette;
+mod display;
+mod render_scale;
+mod ambient_occlusion;
+mod heightmap;
+mod placement;
+mod save;
+mod campfire;
+mod inventory;
+mod creative;
+mod danger;
+mod map;
+mod footprints;
+mod map_bridge;
+mod projectile;
+mod collider;
+mod door;
+mod region;
+mod hotbar;
+mod world_events;
+mod aurora;
+mod meteors;
+mod fog_bank;
+mod chunk_diff;
+mod sleep;
+mod camera_shake;
+mod console;
+mod admin;
+mod rain;
+mod ui_window;
+mod asset_registry;
+mod navigation;
+mod fishing;
+mod season;
+mod reflection_probe;
+mod avatar;
+mod emote;
+mod building;
+mod input_map;
+mod music;
+mod server_store;
+mod impact;
+mod hud;
+mod audio_mixer;
+mod locate;
+mod snapshot;
+mod road;
+mod camera_fov;
+mod population;
+mod worldgen;
+mod drowning;
+mod movement;
+mod sky_light;
+mod thirst;
+mod dialogue;
+mod vendor;
+mod world_border;
+mod world_rng;
+mod terrain_displacement;
+mod accessibility;
+mod wolf;
+mod network_debug;
+mod race;
+mod ripple;
+mod items;
+mod reconnect;
+mod toast;
+mod save_migration;
+mod birds;
+mod ruins;
+mod vegetation;
 fn main() {
     let mut args = env::args();
-    args.next();
-    match args.next().as_ref().map(|s| s.as_str()) {
+    let program = args.next().unwrap_or_default();
+    match args.next().as_deref() {
         Some("client") => {
             println!("Running on client mode");
-            client::run();
+            let mut options = client::ClientOptions::default();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--trace" => options.trace = true,
+                    "--heightmap" => options.heightmap = args.next().map(Into::into),
+                    "--map-bridge" => match args.next().map(|address| address.parse()) {
+                        Some(Ok(address)) => options.map_bridge = Some(address),
+                        _ => println!("--map-bridge expects an address like 127.0.0.1:9001"),
+                    },
+                    "--connect" => match args.next().map(|address| address.parse()) {
+                        Some(Ok(address)) => options.connect = Some(address),
+                        _ => println!("--connect expects an address like 127.0.0.1:{}", transport::DEFAULT_PORT),
+                    },
+                    _ => println!("Ignoring unknown argument {}", arg),
+                }
+            }
+            if options.connect.is_some() && options.map_bridge.take().is_some() {
+                println!("--map-bridge serves the local world, not opening it when connecting to a server");
+            }
+            client::run(options);
         }
+        Some("server") => {
+            println!("Running on server mode");
+            let mut options = server::ServerOptions::default();
+            options.admin.stdin = true;
+            let mut admin_address = None;
+            let mut admin_password = env::var("ADMIN_PASSWORD").ok();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--admin" => match args.next().map(|address| address.parse()) {
+                        Some(Ok(address)) => admin_address = Some(address),
+                        _ => println!("--admin expects an address like 127.0.0.1:9002"),
+                    },
+                    "--admin-password" => admin_password = args.next(),
+                    "--listen" => match args.next().map(|address| address.parse()) {
+                        Some(Ok(address)) => options.listen = address,
+                        _ => println!("--listen expects an address like 0.0.0.0:{}", transport::DEFAULT_PORT),
+                    },
+                    "--no-movement-checks" => options.movement.enabled = false,
+                    "--world-border" => match args.next().map(|radius| radius.parse()) {
+                        Some(Ok(radius)) if radius >= 0 => options.world_border.radius_chunks = Some(radius),
+                        _ => println!("--world-border expects a radius in chunks, like 8"),
+                    },
+                    _ => println!("Ignoring unknown argument {}", arg),
+                }
+            }
+            match (admin_address, admin_password) {
+                (Some(address), Some(password)) if !password.is_empty() => {
+                    options.admin.remote = Some(admin::RemoteAdmin { address, password });
+                }
+                (Some(_), _) => println!("The admin port needs a password (--admin-password or ADMIN_PASSWORD), not opening it"),
+                _ => {}
+            }
+            server::run(options);
+        }
+        Some("verify-chunks") => {
+            let update_golden = args.any(|arg| arg == "--update-golden");
+            if !chunk_diff::run(update_golden) {
+                std::process::exit(1);
+            }
+        }
+        Some("bench-terrain") => terrain::run_benchmark(),
         _ => {
-            println!("Usage : {} [client]", args.next().unwrap());
+            println!("Usage : {} client [--trace] [--heightmap <file.png | file.heightmap.ron>] [--map-bridge <address>] [--connect <address>]", program);
+            println!("        {} server [--listen <address>] [--admin <address>] [--admin-password <password>] [--no-movement-checks] [--world-border <chunks>]", program);
+            println!("        {} verify-chunks [--update-golden]", program);
+            println!("        {} bench-terrain", program);
         }
     }
-}
\ No newline at end of file
+}
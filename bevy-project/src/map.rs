@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::danger;
+use crate::palette::ActiveTerrainPalette;
+use crate::player::Player;
+use crate::streaming::StreamingAnchor;
+use crate::terrain_edit::TerrainEdits;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+use crate::water::sea_level;
+
+// Pixels per side of the map image
+const MAP_RESOLUTION: usize = 128;
+const METERS_PER_PIXEL: f32 = 4.0;
+// Redraw once the player moved this far from the map center
+const REDRAW_DISTANCE: f32 = 16.0;
+const MAP_DISPLAY_SIZE: f32 = 256.0;
+const WATER_COLOR: [f32; 3] = [0.15, 0.35, 0.6];
+const DANGER_COLOR: [f32; 3] = [0.9, 0.1, 0.1];
+const MAP_WINDOW: &str = "map";
+// Chunks loaded in each direction around a place picked on the map
+const PREVIEW_RADIUS: i32 = 1;
+
+#[derive(Default, Clone, Debug)]
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MapState>()
+            .register_ui_window(UiWindow {
+                id: MAP_WINDOW,
+                title: "Map",
+                shortcut: Some(KeyCode::KeyM),
+                open: false,
+            })
+            .add_systems(Update, map_ui_system);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MapState {
+    // Tint regions by their danger level
+    pub show_danger: bool,
+    texture: Option<egui::TextureHandle>,
+    // World (x, z) at the middle of the current image
+    center: Option<Vec2>,
+    drawn_with_danger: bool,
+    // Place clicked on the map and the anchor loading its chunks, until the map closes
+    preview: Option<(Vec2, Entity)>,
+}
+
+fn map_pixel(
+    world_x: f32,
+    world_z: f32,
+    terrain_edits: &TerrainEdits,
+    palette: &ActiveTerrainPalette,
+    show_danger: bool,
+) -> egui::Color32 {
+    let height = terrain_edits.height(world_x, world_z);
+    let mut color = if height < sea_level(world_x, world_z) {
+        WATER_COLOR
+    } else {
+        let [r, g, b, _] = palette.0.color(world_x, world_z, height);
+        [r, g, b]
+    };
+    if show_danger {
+        let danger = danger::danger_level(world_x, world_z) * 0.6;
+        for (channel, tint) in color.iter_mut().zip(DANGER_COLOR) {
+            *channel = channel.lerp(tint, danger);
+        }
+    }
+    let [r, g, b] = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0) as u8);
+    egui::Color32::from_rgb(r, g, b)
+}
+
+fn draw_map(center: Vec2, terrain_edits: &TerrainEdits, palette: &ActiveTerrainPalette, show_danger: bool) -> egui::ColorImage {
+    let half = MAP_RESOLUTION as f32 / 2.0;
+    let pixels = (0..MAP_RESOLUTION * MAP_RESOLUTION)
+        .map(|i| {
+            let offset = Vec2::new((i % MAP_RESOLUTION) as f32, (i / MAP_RESOLUTION) as f32) - half;
+            let world = center + offset * METERS_PER_PIXEL;
+            map_pixel(world.x, world.y, terrain_edits, palette, show_danger)
+        })
+        .collect();
+    egui::ColorImage {
+        size: [MAP_RESOLUTION, MAP_RESOLUTION],
+        pixels,
+    }
+}
+
+// Top-down map around the player, north (-z) up
+fn map_ui_system(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut map: ResMut<MapState>,
+    terrain_edits: Res<TerrainEdits>,
+    palette: Res<ActiveTerrainPalette>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    // The map image is only drawn while the window is open
+    if !windows.is_open(MAP_WINDOW) {
+        if let Some((_, anchor)) = map.preview.take() {
+            commands.entity(anchor).despawn_recursive();
+        }
+        return;
+    }
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let position = player.translation.xz();
+    let map = map.bypass_change_detection();
+
+    let stale = match map.center {
+        Some(center) => center.distance(position) > REDRAW_DISTANCE || map.drawn_with_danger != map.show_danger,
+        None => true,
+    };
+    if stale || terrain_edits.is_changed() || palette.is_changed() {
+        let image = draw_map(position, &terrain_edits, &palette, map.show_danger);
+        match &mut map.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => map.texture = Some(contexts.ctx_mut().load_texture("world_map", image, egui::TextureOptions::NEAREST)),
+        }
+        map.center = Some(position);
+        map.drawn_with_danger = map.show_danger;
+    }
+    let (Some(texture), Some(center)) = (map.texture.clone(), map.center) else {
+        return;
+    };
+
+    let danger = danger::danger_level(position.x, position.y);
+    let Some(window) = windows.window(MAP_WINDOW) else {
+        return;
+    };
+    window
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let image = egui::Image::new((texture.id(), egui::vec2(MAP_DISPLAY_SIZE, MAP_DISPLAY_SIZE)));
+            let response = ui.add(image.sense(egui::Sense::click()));
+            // The map is only redrawn every few meters, place the markers relative to its center
+            let scale = MAP_DISPLAY_SIZE / (MAP_RESOLUTION as f32 * METERS_PER_PIXEL);
+            let to_screen = |world: Vec2| {
+                let offset = (world - center) * scale;
+                response.rect.center() + egui::vec2(offset.x, offset.y)
+            };
+            // Clicking a place loads its chunks, ready to look at or travel to
+            if response.clicked()
+                && let Some(pointer) = response.interact_pointer_pos()
+            {
+                let offset = pointer - response.rect.center();
+                let place = center + Vec2::new(offset.x, offset.y) / scale;
+                if let Some((_, anchor)) = map.preview.take() {
+                    commands.entity(anchor).despawn_recursive();
+                }
+                let anchor = commands
+                    .spawn((StreamingAnchor::with_radius(PREVIEW_RADIUS), Transform::from_xyz(place.x, 0.0, place.y)))
+                    .id();
+                map.preview = Some((place, anchor));
+            }
+            let marker = to_screen(position);
+            let forward = player.forward().xz() * 8.0;
+            let painter = ui.painter_at(response.rect);
+            if let Some((place, _)) = map.preview {
+                painter.circle_stroke(to_screen(place), 5.0, (2.0, egui::Color32::YELLOW));
+            }
+            painter.circle_filled(marker, 4.0, egui::Color32::WHITE);
+            painter.line_segment([marker, marker + egui::vec2(forward.x, forward.y)], (2.0, egui::Color32::WHITE));
+
+            ui.checkbox(&mut map.show_danger, "Danger overlay");
+            ui.label(format!("Danger here: {} ({:.0}%)", danger::danger_label(danger), danger * 100.0));
+        });
+}
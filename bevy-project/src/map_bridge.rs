@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use bevy::prelude::*;
+use bevy::utils::synccell::SyncCell;
+use tungstenite::{Message, WebSocket};
+use crate::biome::{self, Biome};
+use crate::client::CHUNK_SIZE;
+use crate::server::{ServerChunk, ServerChunks, ServerWorld};
+use crate::streaming::ChunkCoords;
+use crate::water::sea_level;
+
+// Bumped whenever the wire format below changes
+pub const MAP_PROTOCOL_VERSION: u16 = 1;
+const BROADCAST_INTERVAL: f32 = 0.5;
+
+// Streams chunk summaries and player positions of the server to external map viewers
+// over WebSocket. Enabled with `--map-bridge <address>`.
+#[derive(Default, Clone, Debug)]
+pub struct MapBridgePlugin;
+
+impl Plugin for MapBridgePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MapBridgeConfig>()
+            .insert_resource(BroadcastTimer(Timer::from_seconds(BROADCAST_INTERVAL, TimerMode::Repeating)))
+            .add_systems(Startup, start_map_bridge)
+            .add_systems(Update, broadcast_map_updates);
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct MapBridgeConfig {
+    pub address: SocketAddr,
+}
+
+impl Default for MapBridgeConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::from(([127, 0, 0, 1], 9001)),
+        }
+    }
+}
+
+// Messages sent to viewers, one per binary WebSocket frame. Every frame starts with the
+// message tag byte, numbers are little-endian:
+//
+// Hello          0x01  version: u16, chunk_size: f32
+// ChunkSummary   0x02  x: i32, z: i32, min_height: f32, max_height: f32, mean_height: f32,
+//                      water: u8 (share of the chunk under sea level, 0..255),
+//                      biome: u8 (0 temperate, 1 desert, 2 tundra, 3 savanna, 4 rainforest)
+// ChunkUnloaded  0x03  x: i32, z: i32
+// Players        0x04  count: u16, then per player id: u32, x: f32, y: f32, z: f32
+//
+// A new viewer gets Hello and a summary of every loaded chunk, then only changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapMessage {
+    Hello { version: u16, chunk_size: f32 },
+    ChunkSummary(ChunkSummary),
+    ChunkUnloaded { x: i32, z: i32 },
+    Players(Vec<MapPlayer>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkSummary {
+    pub x: i32,
+    pub z: i32,
+    pub min_height: f32,
+    pub max_height: f32,
+    pub mean_height: f32,
+    pub water: u8,
+    pub biome: Biome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapPlayer {
+    pub id: u32,
+    pub position: Vec3,
+}
+
+fn biome_id(biome: Biome) -> u8 {
+    match biome {
+        Biome::Temperate => 0,
+        Biome::Desert => 1,
+        Biome::Tundra => 2,
+        Biome::Savanna => 3,
+        Biome::Rainforest => 4,
+    }
+}
+
+impl MapMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            MapMessage::Hello { version, chunk_size } => {
+                bytes.push(0x01);
+                bytes.extend(version.to_le_bytes());
+                bytes.extend(chunk_size.to_le_bytes());
+            }
+            MapMessage::ChunkSummary(summary) => {
+                bytes.push(0x02);
+                bytes.extend(summary.x.to_le_bytes());
+                bytes.extend(summary.z.to_le_bytes());
+                bytes.extend(summary.min_height.to_le_bytes());
+                bytes.extend(summary.max_height.to_le_bytes());
+                bytes.extend(summary.mean_height.to_le_bytes());
+                bytes.push(summary.water);
+                bytes.push(biome_id(summary.biome));
+            }
+            MapMessage::ChunkUnloaded { x, z } => {
+                bytes.push(0x03);
+                bytes.extend(x.to_le_bytes());
+                bytes.extend(z.to_le_bytes());
+            }
+            MapMessage::Players(players) => {
+                bytes.push(0x04);
+                bytes.extend((players.len().min(u16::MAX as usize) as u16).to_le_bytes());
+                for player in players.iter().take(u16::MAX as usize) {
+                    bytes.extend(player.id.to_le_bytes());
+                    for value in player.position.to_array() {
+                        bytes.extend(value.to_le_bytes());
+                    }
+                }
+            }
+        }
+        bytes
+    }
+}
+
+impl ChunkSummary {
+    pub fn new(coords: ChunkCoords, chunk: &ServerChunk) -> Self {
+        let collider = &chunk.collider;
+        let side = collider.resolution + 1;
+        let mut total = 0.0;
+        let mut under_water = 0;
+        for (i, height) in collider.heights.iter().enumerate() {
+            let x = collider.origin.x + (i as u32 % side) as f32 * collider.cell_size;
+            let z = collider.origin.y + (i as u32 / side) as f32 * collider.cell_size;
+            total += height;
+            if *height < sea_level(x, z) {
+                under_water += 1;
+            }
+        }
+        let samples = collider.heights.len().max(1);
+        let center = Vec2::new(coords.0 as f32, coords.1 as f32) * CHUNK_SIZE;
+        Self {
+            x: coords.0,
+            z: coords.1,
+            min_height: collider.min_height,
+            max_height: collider.max_height,
+            mean_height: total / samples as f32,
+            water: (under_water * 255 / samples) as u8,
+            biome: biome::biome_at(center.x, center.y),
+        }
+    }
+}
+
+// A connected viewer; frames are written by its own thread so a slow browser never stalls a frame
+struct Viewer {
+    frames: Sender<Arc<[u8]>>,
+    // Still waiting for the full world snapshot
+    new: bool,
+}
+
+#[derive(Resource)]
+struct MapBridge {
+    // Viewers accepted by the listener thread, not yet picked up by the broadcast system
+    incoming: SyncCell<Receiver<Viewer>>,
+    viewers: Vec<Viewer>,
+    // Chunk summaries as every viewer last received them
+    sent_chunks: HashMap<ChunkCoords, ChunkSummary>,
+}
+
+#[derive(Resource)]
+struct BroadcastTimer(Timer);
+
+fn start_map_bridge(
+    mut commands: Commands,
+    config: Res<MapBridgeConfig>,
+) {
+    let listener = match TcpListener::bind(config.address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Could not start the map bridge on {}: {}", config.address, err);
+            return;
+        }
+    };
+    info!("Map bridge listening on ws://{}", config.address);
+
+    let (sender, incoming) = mpsc::channel();
+    thread::Builder::new()
+        .name("map-bridge".to_string())
+        .spawn(move || accept_viewers(listener, sender))
+        .expect("failed to spawn the map bridge thread");
+
+    commands.insert_resource(MapBridge {
+        incoming: SyncCell::new(incoming),
+        viewers: Vec::new(),
+        sent_chunks: HashMap::new(),
+    });
+}
+
+fn accept_viewers(listener: TcpListener, viewers: Sender<Viewer>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Map viewer connection failed: {}", err);
+                continue;
+            }
+        };
+        let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+        let socket = match tungstenite::accept(stream) {
+            Ok(socket) => socket,
+            Err(err) => {
+                warn!("Map viewer handshake with {} failed: {}", peer, err);
+                continue;
+            }
+        };
+        info!("Map viewer {} connected", peer);
+
+        let (frames, outgoing) = mpsc::channel();
+        let _ = thread::Builder::new()
+            .name(format!("map-viewer-{}", peer))
+            .spawn(move || write_frames(socket, outgoing, peer));
+        if viewers.send(Viewer { frames, new: true }).is_err() {
+            // The game is shutting down
+            return;
+        }
+    }
+}
+
+fn write_frames(mut socket: WebSocket<TcpStream>, frames: Receiver<Arc<[u8]>>, peer: String) {
+    for frame in frames {
+        if let Err(err) = socket.send(Message::Binary(frame.to_vec().into())) {
+            if !matches!(err, tungstenite::Error::Io(ref io) if io.kind() == io::ErrorKind::BrokenPipe) {
+                info!("Map viewer {} disconnected: {}", peer, err);
+            }
+            return;
+        }
+    }
+    let _ = socket.close(None);
+}
+
+fn broadcast_map_updates(
+    bridge: Option<ResMut<MapBridge>>,
+    mut timer: ResMut<BroadcastTimer>,
+    server_world: Res<ServerWorld>,
+    server_chunks: Res<ServerChunks>,
+    time: Res<Time>,
+) {
+    let Some(mut bridge) = bridge else {
+        return;
+    };
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let bridge = &mut *bridge;
+    while let Ok(viewer) = bridge.incoming.get().try_recv() {
+        bridge.viewers.push(viewer);
+    }
+    if bridge.viewers.is_empty() {
+        bridge.sent_chunks.clear();
+        return;
+    }
+    let _span = info_span!("map_bridge_broadcast", viewers = bridge.viewers.len()).entered();
+
+    // Changes since the last broadcast (streamed or edited chunks), for viewers that already have the world
+    let summaries: HashMap<ChunkCoords, ChunkSummary> = server_chunks.chunks.iter()
+        .map(|(coords, chunk)| (*coords, ChunkSummary::new(*coords, chunk)))
+        .collect();
+    let mut updates = Vec::new();
+    for coords in bridge.sent_chunks.keys().filter(|coords| !summaries.contains_key(coords)) {
+        updates.push(MapMessage::ChunkUnloaded { x: coords.0, z: coords.1 });
+    }
+    for (coords, summary) in &summaries {
+        if bridge.sent_chunks.get(coords) != Some(summary) {
+            updates.push(MapMessage::ChunkSummary(*summary));
+        }
+    }
+
+    let players = MapMessage::Players(server_world.players.iter()
+        .map(|(client, player)| MapPlayer { id: client.0, position: player.position })
+        .collect());
+    let updates: Vec<Arc<[u8]>> = updates.iter()
+        .chain(std::iter::once(&players))
+        .map(|message| message.encode().into())
+        .collect();
+
+    bridge.viewers.retain_mut(|viewer| {
+        let frames: Vec<Arc<[u8]>> = if viewer.new {
+            viewer.new = false;
+            let hello = MapMessage::Hello { version: MAP_PROTOCOL_VERSION, chunk_size: CHUNK_SIZE };
+            std::iter::once(hello)
+                .chain(summaries.values().map(|summary| MapMessage::ChunkSummary(*summary)))
+                .chain(std::iter::once(players.clone()))
+                .map(|message| message.encode().into())
+                .collect()
+        } else {
+            updates.clone()
+        };
+        // A failed send means the writer thread ended, the viewer is gone
+        frames.into_iter().all(|frame| viewer.frames.send(frame).is_ok())
+    });
+    bridge.sent_chunks = summaries;
+}
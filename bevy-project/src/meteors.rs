@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::world_events::{unit_random, ActiveWorldEvents, WorldEventAppExt, WorldEventType};
+
+const METEOR_SHOWER: &str = "meteor_shower";
+// Streaks per second at full intensity
+const METEOR_RATE: f32 = 1.2;
+const METEOR_SPEED: f32 = 350.0;
+const METEOR_LIFETIME: f32 = 0.9;
+const METEOR_LENGTH: f32 = 40.0;
+// Where streaks start, relative to the camera
+const SKY_DISTANCE: (f32, f32) = (200.0, 600.0);
+const SKY_HEIGHT: (f32, f32) = (250.0, 400.0);
+
+#[derive(Default, Clone, Debug)]
+pub struct MeteorPlugin;
+
+impl Plugin for MeteorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_world_event(WorldEventType {
+                name: METEOR_SHOWER,
+                start_window: (21.0, 3.0),
+                chance: 0.08,
+                duration_hours: 1.5,
+            })
+            .init_resource::<MeteorSpawner>()
+            .add_systems(Startup, setup_meteor_assets)
+            .add_systems(Update, (spawn_meteors, move_meteors).chain());
+    }
+}
+
+#[derive(Component)]
+struct Meteor {
+    velocity: Vec3,
+    age: f32,
+}
+
+#[derive(Resource, Default)]
+struct MeteorSpawner {
+    // Fractional meteors carried over between frames
+    pending: f32,
+    count: u64,
+}
+
+#[derive(Resource)]
+struct MeteorAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_meteor_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(MeteorAssets {
+        // Streak along its local -Z, the direction it flies to
+        mesh: meshes.add(Cuboid::new(0.5, 0.5, METEOR_LENGTH)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.8, 0.9, 1.0),
+            emissive: LinearRgba::rgb(30.0, 34.0, 40.0),
+            alpha_mode: AlphaMode::Add,
+            unlit: true,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_meteors(
+    mut commands: Commands,
+    time: Res<Time>,
+    events: Res<ActiveWorldEvents>,
+    assets: Res<MeteorAssets>,
+    mut spawner: ResMut<MeteorSpawner>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+) {
+    let intensity = events.intensity(METEOR_SHOWER);
+    if intensity <= 0.0 {
+        spawner.pending = 0.0;
+        return;
+    }
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+
+    spawner.pending += METEOR_RATE * intensity * time.delta_secs();
+    while spawner.pending >= 1.0 {
+        spawner.pending -= 1.0;
+        spawner.count += 1;
+        let random = |salt| unit_random(spawner.count, salt);
+
+        let angle = random(0) * std::f32::consts::TAU;
+        let distance = SKY_DISTANCE.0.lerp(SKY_DISTANCE.1, random(1));
+        let height = SKY_HEIGHT.0.lerp(SKY_HEIGHT.1, random(2));
+        let start = camera.translation.with_y(0.0)
+            + Vec3::new(angle.cos() * distance, height, angle.sin() * distance);
+        let heading = random(3) * std::f32::consts::TAU;
+        let direction = Vec3::new(heading.cos(), -0.35 - random(4) * 0.3, heading.sin()).normalize();
+
+        commands.spawn((
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(assets.material.clone()),
+            Transform::from_translation(start).looking_to(direction, Vec3::Y),
+            Meteor {
+                velocity: direction * METEOR_SPEED,
+                age: 0.0,
+            },
+        ));
+    }
+}
+
+// Streaks fly straight and thin out before burning up
+fn move_meteors(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meteor_query: Query<(Entity, &mut Meteor, &mut Transform)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut meteor, mut transform) in meteor_query.iter_mut() {
+        meteor.age += dt;
+        if meteor.age >= METEOR_LIFETIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.translation += meteor.velocity * dt;
+        let life = 1.0 - meteor.age / METEOR_LIFETIME;
+        transform.scale = Vec3::new(life, life, 0.3 + 0.7 * life);
+    }
+}
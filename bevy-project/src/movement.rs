@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::player::PLAYER_HALF_HEIGHT;
+
+// Corrections within this many seconds count toward flagging a player
+const SUSPICION_WINDOW: f64 = 10.0;
+const SUSPICIOUS_CORRECTIONS: usize = 5;
+// Shortest time between two respawns the server lets a player teleport for
+const RESPAWN_COOLDOWN: f64 = 3.0;
+
+// How far the server lets reported player movement go. Players over the limits are put back
+// where they could have been; creative flying breaks them, so creative servers turn them off.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct MovementLimits {
+    pub enabled: bool,
+    // Fastest horizontal speed, sprinting and boats included, in meters per second
+    pub max_speed: f32,
+    pub max_climb_speed: f32,
+    // Extra distance allowed per report, for states arriving bunched up
+    pub slack: f32,
+    // Longer moves are teleports, only allowed right after a respawn
+    pub teleport_distance: f32,
+    // How far below the terrain the feet may be reported before being lifted out
+    pub ground_tolerance: f32,
+}
+
+impl Default for MovementLimits {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_speed: 12.0,
+            max_climb_speed: 12.0,
+            slack: 1.5,
+            teleport_distance: 40.0,
+            ground_tolerance: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveVerdict {
+    Accepted,
+    // Where the player is put instead
+    Corrected(Vec3),
+}
+
+impl MovementLimits {
+    // Check a move reported `elapsed` seconds after the last one against the limits and the
+    // terrain height `ground` where it ends
+    pub fn check(&self, from: Vec3, to: Vec3, elapsed: f32, ground: f32) -> MoveVerdict {
+        let delta = to - from;
+        if delta.length() > self.teleport_distance {
+            return MoveVerdict::Corrected(from);
+        }
+        let mut position = to;
+        let reach = self.max_speed * elapsed + self.slack;
+        let horizontal = delta.xz().length();
+        if horizontal > reach {
+            let allowed = delta * (reach / horizontal);
+            position = from + allowed;
+        }
+        let climb = self.max_climb_speed * elapsed + self.slack;
+        if position.y - from.y > climb {
+            position.y = from.y + climb;
+        }
+        if position.y - PLAYER_HALF_HEIGHT < ground - self.ground_tolerance {
+            position.y = ground + PLAYER_HALF_HEIGHT;
+        }
+        if position == to { MoveVerdict::Accepted } else { MoveVerdict::Corrected(position) }
+    }
+}
+
+// What the server remembers of a player's movement between reports
+#[derive(Debug, Clone, Default)]
+pub struct MovementCheck {
+    // Server time of the last report
+    last_report: Option<f64>,
+    corrections: VecDeque<f64>,
+    last_respawn: Option<f64>,
+    // The next report may be anywhere, after a respawn
+    teleport_allowed: bool,
+}
+
+impl MovementCheck {
+    // Seconds since the last report, and start timing from `now`
+    pub fn elapsed(&mut self, now: f64) -> f32 {
+        let elapsed = self.last_report.map_or(0.0, |last| (now - last) as f32);
+        self.last_report = Some(now);
+        elapsed
+    }
+
+    // Let the next report teleport, false if the player respawned too recently
+    pub fn allow_respawn(&mut self, now: f64) -> bool {
+        if self.last_respawn.is_some_and(|last| now - last < RESPAWN_COOLDOWN) {
+            return false;
+        }
+        self.last_respawn = Some(now);
+        self.teleport_allowed = true;
+        true
+    }
+
+    pub fn take_teleport(&mut self) -> bool {
+        std::mem::take(&mut self.teleport_allowed)
+    }
+
+    // Count a correction, true once the player has been corrected too often lately
+    pub fn record_correction(&mut self, now: f64) -> bool {
+        self.corrections.push_back(now);
+        while self.corrections.front().is_some_and(|time| now - time > SUSPICION_WINDOW) {
+            self.corrections.pop_front();
+        }
+        self.corrections.len() >= SUSPICIOUS_CORRECTIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUND: f32 = 0.0;
+    const STANDING: f32 = GROUND + PLAYER_HALF_HEIGHT;
+
+    #[test]
+    fn walking_is_accepted() {
+        let limits = MovementLimits::default();
+        let from = Vec3::new(0.0, STANDING, 0.0);
+        let to = Vec3::new(0.5, STANDING, 0.0);
+        assert_eq!(limits.check(from, to, 0.05, GROUND), MoveVerdict::Accepted);
+    }
+
+    #[test]
+    fn speeding_is_cut_short() {
+        let limits = MovementLimits::default();
+        let from = Vec3::new(0.0, STANDING, 0.0);
+        let to = Vec3::new(20.0, STANDING, 0.0);
+        let MoveVerdict::Corrected(position) = limits.check(from, to, 0.5, GROUND) else {
+            panic!("a move at 40 m/s was accepted");
+        };
+        assert!((position.x - (limits.max_speed * 0.5 + limits.slack)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn teleports_and_tunneling_are_undone() {
+        let limits = MovementLimits::default();
+        let from = Vec3::new(0.0, STANDING, 0.0);
+        assert_eq!(limits.check(from, Vec3::new(500.0, STANDING, 0.0), 60.0, GROUND), MoveVerdict::Corrected(from));
+        let underground = Vec3::new(0.5, STANDING - 3.0, 0.0);
+        assert_eq!(limits.check(from, underground, 0.05, GROUND), MoveVerdict::Corrected(Vec3::new(0.5, STANDING, 0.0)));
+    }
+}
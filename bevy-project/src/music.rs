@@ -0,0 +1,228 @@
+use bevy::audio::{PlaybackMode, Volume};
+use bevy::prelude::*;
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::audio_mixer::MixedAudio;
+use crate::biome::{self, Biome};
+use crate::camera::FreeCamera;
+use crate::danger;
+use crate::day_night::TimeOfDay;
+use crate::fish::ActiveTime;
+use crate::projectile::{HitTarget, ProjectileHit, ProjectileKind};
+use crate::ron_asset::RonAssetPlugin;
+use crate::settings::GameSettings;
+use crate::sleep::Hostile;
+
+const SOUNDTRACK_PATH: &str = "music/default.soundtrack.ron";
+const SOUNDTRACK: &str = "soundtrack";
+// Hostile creatures this close put the music in combat
+const COMBAT_RADIUS: f32 = 25.0;
+// Seconds the combat layers keep playing after the last hit
+const COMBAT_LINGER: f32 = 8.0;
+
+// Looping soundtrack layers faded in and out to fit where the player is: biome, danger,
+// time of day and combat. Layers are defined in `assets/music/*.soundtrack.ron`.
+#[derive(Default, Clone, Debug)]
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<Soundtrack>::new(&["soundtrack.ron"]))
+            .preload_asset::<Soundtrack>(SOUNDTRACK, SOUNDTRACK_PATH)
+            .init_resource::<MusicState>()
+            .add_systems(Startup, setup_music)
+            .add_systems(Update, (
+                spawn_music_layers,
+                track_combat,
+                crossfade_music,
+            ).chain());
+    }
+}
+
+// Whether a layer plays in or out of combat
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CombatState {
+    #[default]
+    Any,
+    Calm,
+    Fighting,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MusicLayer {
+    // Audio asset path, every layer loops for as long as the soundtrack is loaded
+    pub track: String,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    // Biomes the layer plays in, all of them when empty
+    #[serde(default)]
+    pub biomes: Vec<Biome>,
+    #[serde(default)]
+    pub min_danger: f32,
+    #[serde(default = "default_max_danger")]
+    pub max_danger: f32,
+    #[serde(default)]
+    pub time: ActiveTime,
+    #[serde(default)]
+    pub combat: CombatState,
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_max_danger() -> f32 {
+    1.0
+}
+
+fn default_crossfade() -> f32 {
+    4.0
+}
+
+impl MusicLayer {
+    fn plays_in(&self, mood: &MusicMood) -> bool {
+        (self.biomes.is_empty() || self.biomes.contains(&mood.biome))
+            && (self.min_danger..=self.max_danger).contains(&mood.danger)
+            && self.time.allows(mood.night)
+            && match self.combat {
+                CombatState::Any => true,
+                CombatState::Calm => !mood.combat,
+                CombatState::Fighting => mood.combat,
+            }
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct Soundtrack {
+    // Seconds for a layer to fade all the way in or out
+    #[serde(default = "default_crossfade")]
+    pub crossfade_seconds: f32,
+    pub layers: Vec<MusicLayer>,
+}
+
+// What the music reacts to, around the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MusicMood {
+    biome: Biome,
+    danger: f32,
+    night: bool,
+    combat: bool,
+}
+
+#[derive(Resource, Default)]
+struct MusicState {
+    soundtrack: Handle<Soundtrack>,
+    // Seconds since the last projectile hit
+    since_hit: f32,
+    combat: bool,
+}
+
+// Plays one layer of the soundtrack, `fade` being how far it is faded in
+#[derive(Component)]
+struct MusicLayerPlayer {
+    index: usize,
+    fade: f32,
+}
+
+fn setup_music(mut state: ResMut<MusicState>, registry: Res<AssetRegistry>) {
+    state.soundtrack = registry.get(SOUNDTRACK).unwrap_or_default();
+    state.since_hit = f32::MAX;
+}
+
+// Start every layer silent once the soundtrack is loaded, again when it's edited on disk
+fn spawn_music_layers(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Soundtrack>>,
+    state: Res<MusicState>,
+    soundtracks: Res<Assets<Soundtrack>>,
+    asset_server: Res<AssetServer>,
+    layer_query: Query<Entity, With<MusicLayerPlayer>>,
+) {
+    let loaded = events.read().any(|event| match event {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => *id == state.soundtrack.id(),
+        _ => false,
+    });
+    if !loaded {
+        return;
+    }
+    let Some(soundtrack) = soundtracks.get(&state.soundtrack) else {
+        return;
+    };
+
+    for entity in layer_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (index, layer) in soundtrack.layers.iter().enumerate() {
+        commands.spawn((
+            AudioPlayer::<MixedAudio>(asset_server.load(&layer.track)),
+            PlaybackSettings {
+                mode: PlaybackMode::Loop,
+                volume: Volume::new(0.0),
+                ..default()
+            },
+            MusicLayerPlayer { index, fade: 0.0 },
+        ));
+    }
+    info!("Soundtrack loaded with {} layers", soundtrack.layers.len());
+}
+
+// Fighting while hostile creatures are close or shortly after hitting a creature with a rock
+fn track_combat(
+    time: Res<Time>,
+    mut hits: EventReader<ProjectileHit>,
+    mut state: ResMut<MusicState>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    hostile_query: Query<&GlobalTransform, With<Hostile>>,
+) {
+    let struck = hits.read()
+        .filter(|hit| hit.kind == ProjectileKind::Rock && matches!(hit.target, HitTarget::Entity(_)))
+        .count();
+    if struck > 0 {
+        state.since_hit = 0.0;
+    } else {
+        state.since_hit += time.delta_secs();
+    }
+    let hostile_nearby = camera_query.get_single().is_ok_and(|camera| {
+        hostile_query.iter()
+            .any(|hostile| hostile.translation().distance(camera.translation) < COMBAT_RADIUS)
+    });
+    let combat = hostile_nearby || state.since_hit < COMBAT_LINGER;
+    if state.combat != combat {
+        state.combat = combat;
+    }
+}
+
+fn crossfade_music(
+    time: Res<Time>,
+    state: Res<MusicState>,
+    soundtracks: Res<Assets<Soundtrack>>,
+    settings: Res<GameSettings>,
+    time_of_day: Res<TimeOfDay>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut layer_query: Query<(&mut MusicLayerPlayer, Option<&AudioSink>)>,
+) {
+    let (Some(soundtrack), Ok(camera)) = (soundtracks.get(&state.soundtrack), camera_query.get_single()) else {
+        return;
+    };
+    let position = camera.translation;
+    let mood = MusicMood {
+        biome: biome::biome_at(position.x, position.z),
+        danger: danger::danger_level(position.x, position.z),
+        night: time_of_day.is_night(),
+        combat: state.combat,
+    };
+    let step = time.delta_secs() / soundtrack.crossfade_seconds.max(f32::EPSILON);
+    let volume = settings.audio.master_volume * settings.audio.music_volume;
+
+    for (mut player, sink) in layer_query.iter_mut() {
+        let Some(layer) = soundtrack.layers.get(player.index) else {
+            continue;
+        };
+        let target = if layer.plays_in(&mood) { 1.0 } else { 0.0 };
+        player.fade += (target - player.fade).clamp(-step, step);
+        if let Some(sink) = sink {
+            sink.set_volume(player.fade * layer.volume * volume);
+        }
+    }
+}
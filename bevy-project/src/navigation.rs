@@ -0,0 +1,275 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::chunk_debug::{ChunkDebugGizmos, ChunkDebugSettings};
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::console::{CommandResult, ConsoleAppExt, ConsoleCommand};
+use crate::player::{max_slope, MAX_STEP_HEIGHT};
+use crate::streaming::ChunkCoords;
+use crate::terrain_edit::{rebuild_dirty_chunks, TerrainEdits};
+use crate::water::sea_level;
+
+// Navigation cells per chunk side, 2 m cells
+const NAV_CELLS: i32 = 25;
+const CELL_SIZE: f32 = CHUNK_SIZE / NAV_CELLS as f32;
+// Chunks whose grid is built per frame, so streaming in many chunks doesn't stall a frame
+const BUILDS_PER_FRAME: usize = 4;
+// Cells deeper under the sea than this are not walkable
+const MAX_WADE_DEPTH: f32 = 0.3;
+// Searches give up after expanding this many cells, roughly a 140 m square
+const MAX_SEARCH_CELLS: usize = 5_000;
+// Path costs in centimeters
+const STRAIGHT_COST: u32 = (CELL_SIZE * 100.0) as u32;
+const DIAGONAL_COST: u32 = (CELL_SIZE * 141.4) as u32;
+// Extra cost per centimeter climbed, so paths prefer going around hills
+const CLIMB_COST_FACTOR: u32 = 2;
+
+// Coarse walkability grid over the loaded terrain, for creatures to path over.
+// Each loaded chunk gets its own grid; cells are addressed globally so paths cross
+// chunk borders as if the grid was one.
+#[derive(Default, Clone, Debug)]
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NavGrid>()
+            .init_resource::<PathPreview>()
+            .register_console_command(ConsoleCommand {
+                name: "path",
+                usage: "path <x> <z>",
+                help: "find a walking path from the camera and show it with the navigation grid",
+                run: path_command,
+            })
+            .add_systems(Update, (
+                sync_nav_grid.before(rebuild_dirty_chunks),
+                draw_nav_grid,
+            ));
+    }
+}
+
+// Global cell coordinates, (0, 0) is the cell at the min corner of chunk (0, 0)
+pub type NavCell = (i32, i32);
+
+#[derive(Debug, Clone)]
+pub struct NavChunk {
+    // Terrain height at each cell center, row by row along x
+    heights: Vec<f32>,
+    walkable: Vec<bool>,
+}
+
+#[derive(Resource, Default)]
+pub struct NavGrid {
+    chunks: HashMap<ChunkCoords, NavChunk>,
+}
+
+pub fn cell_at(position: Vec2) -> NavCell {
+    let offset = position + Vec2::splat(CHUNK_SIZE / 2.0);
+    ((offset.x / CELL_SIZE).floor() as i32, (offset.y / CELL_SIZE).floor() as i32)
+}
+
+pub fn cell_center(cell: NavCell) -> Vec2 {
+    (Vec2::new(cell.0 as f32, cell.1 as f32) + 0.5) * CELL_SIZE - Vec2::splat(CHUNK_SIZE / 2.0)
+}
+
+fn split_cell(cell: NavCell) -> (ChunkCoords, usize) {
+    let chunk = (cell.0.div_euclid(NAV_CELLS), cell.1.div_euclid(NAV_CELLS));
+    let local = (cell.0.rem_euclid(NAV_CELLS), cell.1.rem_euclid(NAV_CELLS));
+    (chunk, (local.1 * NAV_CELLS + local.0) as usize)
+}
+
+impl NavChunk {
+    pub fn build(coords: ChunkCoords, terrain_edits: &TerrainEdits) -> Self {
+        let cells = (NAV_CELLS * NAV_CELLS) as usize;
+        let mut heights = Vec::with_capacity(cells);
+        let mut walkable = Vec::with_capacity(cells);
+        for z in 0..NAV_CELLS {
+            for x in 0..NAV_CELLS {
+                let center = cell_center((coords.0 * NAV_CELLS + x, coords.1 * NAV_CELLS + z));
+                let height = terrain_edits.height(center.x, center.y);
+                let d = CELL_SIZE / 2.0;
+                let gradient = Vec2::new(
+                    terrain_edits.height(center.x + d, center.y) - terrain_edits.height(center.x - d, center.y),
+                    terrain_edits.height(center.x, center.y + d) - terrain_edits.height(center.x, center.y - d),
+                ) / CELL_SIZE;
+                heights.push(height);
+                walkable.push(height > sea_level(center.x, center.y) - MAX_WADE_DEPTH && gradient.length() <= max_slope());
+            }
+        }
+        Self { heights, walkable }
+    }
+}
+
+impl NavGrid {
+    // Height of a walkable cell, None when blocked or not loaded
+    pub fn walkable_height(&self, cell: NavCell) -> Option<f32> {
+        let (chunk, index) = split_cell(cell);
+        let chunk = self.chunks.get(&chunk)?;
+        chunk.walkable[index].then(|| chunk.heights[index])
+    }
+
+    // Cost of stepping between two neighbouring cells, None if the step is too high or it
+    // cuts the corner of a blocked cell
+    fn step_cost(&self, from: NavCell, to: NavCell, from_height: f32) -> Option<u32> {
+        let height = self.walkable_height(to)?;
+        let diagonal = from.0 != to.0 && from.1 != to.1;
+        if diagonal
+            && (self.walkable_height((to.0, from.1)).is_none() || self.walkable_height((from.0, to.1)).is_none())
+        {
+            return None;
+        }
+        let (distance, cost) = if diagonal {
+            (CELL_SIZE * std::f32::consts::SQRT_2, DIAGONAL_COST)
+        } else {
+            (CELL_SIZE, STRAIGHT_COST)
+        };
+        let rise = height - from_height;
+        if rise.abs() > MAX_STEP_HEIGHT + distance * max_slope() {
+            return None;
+        }
+        Some(cost + (rise.max(0.0) * 100.0) as u32 * CLIMB_COST_FACTOR)
+    }
+
+    // Shortest walkable path between two points with A*, as cell centers on the ground from
+    // the start to the goal. None when either end is blocked, outside the loaded grid, or too
+    // far for the search.
+    pub fn find_path(&self, from: Vec3, to: Vec3) -> Option<Vec<Vec3>> {
+        let (start, goal) = (cell_at(from.xz()), cell_at(to.xz()));
+        self.walkable_height(start)?;
+        self.walkable_height(goal)?;
+
+        // Octile distance, never more than the real cost
+        let estimate = |cell: NavCell| {
+            let (dx, dz) = ((cell.0 - goal.0).unsigned_abs(), (cell.1 - goal.1).unsigned_abs());
+            STRAIGHT_COST * dx.max(dz) + (DIAGONAL_COST - STRAIGHT_COST) * dx.min(dz)
+        };
+        let mut open = BinaryHeap::from([Reverse((estimate(start), start))]);
+        let mut costs = HashMap::from([(start, 0u32)]);
+        let mut came_from: HashMap<NavCell, NavCell> = HashMap::new();
+
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if cell == goal {
+                let mut path = vec![goal];
+                while let Some(previous) = came_from.get(path.last()?) {
+                    path.push(*previous);
+                }
+                path.reverse();
+                return Some(path.into_iter()
+                    .filter_map(|cell| {
+                        let center = cell_center(cell);
+                        Some(Vec3::new(center.x, self.walkable_height(cell)?, center.y))
+                    })
+                    .collect());
+            }
+            if costs.len() > MAX_SEARCH_CELLS {
+                return None;
+            }
+
+            let cost = costs[&cell];
+            let height = self.walkable_height(cell)?;
+            for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let next = (cell.0 + dx, cell.1 + dz);
+                let Some(step) = self.step_cost(cell, next, height) else {
+                    continue;
+                };
+                let next_cost = cost + step;
+                if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                    continue;
+                }
+                costs.insert(next, next_cost);
+                came_from.insert(next, cell);
+                open.push(Reverse((next_cost + estimate(next), next)));
+            }
+        }
+        None
+    }
+}
+
+// Last path found with the `path` command, drawn with the navigation grid
+#[derive(Resource, Default)]
+struct PathPreview(Vec<Vec3>);
+
+fn path_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let target = match args {
+        [x, z] => match (x.parse::<f32>(), z.parse::<f32>()) {
+            (Ok(x), Ok(z)) => Vec3::new(x, 0.0, z),
+            _ => return Err("coordinates are numbers".to_string()),
+        },
+        _ => return Err("usage: path <x> <z>".to_string()),
+    };
+    let start = world.query_filtered::<&Transform, With<FreeCamera>>()
+        .iter(world)
+        .next()
+        .ok_or("no camera to start from")?
+        .translation;
+    let path = world.get_resource::<NavGrid>().ok_or("no navigation grid")?
+        .find_path(start, target)
+        .ok_or_else(|| format!("no walkable path to ({}, {})", target.x, target.z))?;
+    let length: f32 = path.windows(2).map(|pair| pair[0].distance(pair[1])).sum();
+    let reply = format!("{} waypoints, {:.0} m", path.len(), length);
+    world.resource_mut::<PathPreview>().0 = path;
+    Ok(reply)
+}
+
+// Follow the streamed chunks and rebuild grids touched by terrain edits. Runs before the
+// edited meshes are rebuilt, which clears the dirty chunks and areas.
+fn sync_nav_grid(
+    mut nav_grid: ResMut<NavGrid>,
+    chunk_manager: Res<ChunkManager>,
+    terrain_edits: Res<TerrainEdits>,
+) {
+    nav_grid.chunks.retain(|coords, _| chunk_manager.loaded_chunks.contains_key(coords));
+    for coords in terrain_edits.dirty() {
+        if nav_grid.chunks.contains_key(coords) {
+            nav_grid.chunks.insert(*coords, NavChunk::build(*coords, &terrain_edits));
+        }
+    }
+
+    let missing: Vec<ChunkCoords> = chunk_manager.loaded_chunks.keys()
+        .filter(|coords| !nav_grid.chunks.contains_key(coords))
+        .copied()
+        .take(BUILDS_PER_FRAME)
+        .collect();
+    for coords in missing {
+        nav_grid.chunks.insert(coords, NavChunk::build(coords, &terrain_edits));
+    }
+}
+
+// Blocked cells of the chunks around the camera as red crosses on the ground, and the
+// previewed path
+fn draw_nav_grid(
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    settings: Res<ChunkDebugSettings>,
+    nav_grid: Res<NavGrid>,
+    preview: Res<PathPreview>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+) {
+    if !settings.show_navigation {
+        return;
+    }
+    gizmos.linestrip(preview.0.iter().map(|point| *point + Vec3::Y * 0.3), Color::srgb(0.2, 0.6, 1.0));
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let center = cell_at(camera.translation.xz());
+    let radius = NAV_CELLS;
+    let color = Color::srgb(1.0, 0.2, 0.2);
+    for z in center.1 - radius..=center.1 + radius {
+        for x in center.0 - radius..=center.0 + radius {
+            let cell = (x, z);
+            let (chunk, index) = split_cell(cell);
+            let Some(nav_chunk) = nav_grid.chunks.get(&chunk) else {
+                continue;
+            };
+            if nav_chunk.walkable[index] {
+                continue;
+            }
+            let point = cell_center(cell);
+            let base = Vec3::new(point.x, nav_chunk.heights[index] + 0.1, point.y);
+            let half = CELL_SIZE * 0.35;
+            gizmos.line(base + Vec3::new(-half, 0.0, -half), base + Vec3::new(half, 0.0, half), color);
+            gizmos.line(base + Vec3::new(-half, 0.0, half), base + Vec3::new(half, 0.0, -half), color);
+        }
+    }
+}
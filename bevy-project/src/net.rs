@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::emote::Emote;
+use crate::player::Player;
+use crate::region::ProtectedRegion;
+use crate::road::Road;
+use crate::settings::GameSettings;
+use crate::snapshot::{NetworkSettings, ServerClock};
+use crate::terrain::WorldGenSettings;
+use crate::terrain_edit::TerrainEdit;
+use crate::transport::{Connector, Transport, TransportError};
+use crate::world_border::WorldBorder;
+
+// How often the client reports its player to the server
+const PLAYER_STATE_INTERVAL: f32 = 0.05;
+
+#[derive(Default, Clone, Debug)]
+pub struct NetClientPlugin;
+
+impl Plugin for NetClientPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<ServerEvent>()
+            .init_resource::<NetworkSettings>()
+            .init_resource::<ServerClock>()
+            .insert_resource(PlayerStateTimer(Timer::from_seconds(PLAYER_STATE_INTERVAL, TimerMode::Repeating)))
+            .add_systems(Startup, assign_account_key)
+            .add_systems(PreUpdate, (apply_network_settings, receive_server_messages))
+            .add_systems(PostUpdate, send_player_state);
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(pub u32);
+
+// Id of the client living in the same process as the server
+pub const LOCAL_CLIENT: ClientId = ClientId(0);
+
+// Who the player is across sessions. Client ids only last as long as a connection, the
+// server keeps accounts under this key instead. Generated once and kept in the settings.
+// Nothing proves a client owns the key it sends: accounts are trust on first use, whoever
+// presents a key gets its account, and the server only refuses a second live session with
+// it. Anyone who learns a key can play that account while its owner is away.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AccountKey(pub u64);
+
+impl AccountKey {
+    // Seeded by the standard library's per-process random hasher keys and the clock
+    pub fn generate() -> Self {
+        use std::hash::{BuildHasher, Hasher};
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos()));
+        Self(hasher.finish())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ClientMessage {
+    // First message on every link, before the player's state
+    Hello { account: AccountKey },
+    // Yaw around the vertical axis, in radians
+    PlayerState { position: Vec3, yaw: f32 },
+    // `seq` lets the client match the server answer with its predicted edit
+    TerrainEdit { seq: u32, edit: TerrainEdit },
+    Emote { emote: Emote },
+    // Items carried, sent whenever they change
+    InventoryState { items: HashMap<String, u32> },
+    // Rock thrown while remote players were shown as they were at `view_time` on the server's clock
+    RockThrown { origin: Vec3, velocity: Vec3, view_time: f64 },
+    // The player died and is about to report its respawn position
+    Respawned,
+    // Regenerate the world from other noise settings, only the host's client may
+    SetWorldGen { settings: WorldGenSettings },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditRejection {
+    ClaimedLand,
+    ProtectedRegion,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ServerMessage {
+    EditAccepted { seq: u32 },
+    // The edit was applied, but not exactly as the client predicted it
+    EditAdjusted { seq: u32, edit: TerrainEdit },
+    EditRejected { seq: u32, reason: EditRejection },
+    // Edit made by another client
+    RemoteEdit { edit: TerrainEdit },
+    // Protected regions and roads of the world, sent when joining and when they change
+    Regions { regions: Vec<ProtectedRegion>, roads: Vec<Road> },
+    // State of another client's player, relayed as it reports it, stamped with the server's clock
+    PlayerMoved { client: ClientId, position: Vec3, yaw: f32, time: f64 },
+    PlayerLeft { client: ClientId },
+    RemoteEmote { client: ClientId, emote: Emote },
+    // Where the player left off last session, sent when joining a server that remembers it
+    AccountRestored { position: Vec3, inventory: Option<HashMap<String, u32>> },
+    // A rock thrown by another player hit this one
+    RockHit { by: ClientId, position: Vec3 },
+    // First message of a session, `resumed` when the server held the player since the
+    // connection dropped. The terrain settings, regions, the border and edits follow, only
+    // the missed edits if resumed.
+    Joined { resumed: bool },
+    // The player moved further than the server allows and is put back here
+    PositionCorrected { position: Vec3 },
+    // Edge of a bounded world, sent when joining and when it changes
+    WorldBorder { border: WorldBorder },
+    // Noise the terrain is generated from, sent when joining and when it changes
+    WorldGen { settings: WorldGenSettings },
+    // Answer to Hello, with the id the server knows this link by. Players are only let in
+    // after introducing themselves, anything else sent first closes the link.
+    Welcome { client: ClientId },
+    // Answer to Hello when the account already plays on another link, this one is closed
+    AccountInUse,
+}
+
+// Every message received from the server, re-emitted as an event for gameplay systems
+#[derive(Event, Debug, Clone)]
+pub struct ServerEvent(pub ServerMessage);
+
+// Messages travel as RON frames over any transport
+pub fn encode_message(message: &impl Serialize) -> Vec<u8> {
+    ron::to_string(message).expect("network messages always serialize").into_bytes()
+}
+
+pub fn decode_message<T: DeserializeOwned>(frame: &[u8]) -> Option<T> {
+    let text = std::str::from_utf8(frame).ok()?;
+    ron::from_str(text).ok()
+}
+
+// The client's link to the server: a loopback in single player, a socket otherwise
+#[derive(Resource)]
+pub struct ServerConnection {
+    transport: Box<dyn Transport>,
+    connected: bool,
+    // Whether this link introduced the player yet
+    greeted: bool,
+    // Opens a new link when this one is lost, None if the client can't reconnect
+    connector: Option<Box<dyn Connector>>,
+    // Id the server welcomed this link with
+    client: Option<ClientId>,
+}
+
+impl ServerConnection {
+    pub fn new(transport: impl Transport) -> Self {
+        Self {
+            transport: Box::new(transport),
+            connected: true,
+            greeted: false,
+            connector: None,
+            client: None,
+        }
+    }
+
+    pub fn with_connector(mut self, connector: impl Connector) -> Self {
+        self.connector = Some(Box::new(connector));
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+
+    pub fn can_reconnect(&self) -> bool {
+        self.connector.is_some()
+    }
+
+    // Replace a lost link with a new one; the server answers on it once it takes the player back
+    pub fn reconnect(&mut self) -> Result<(), TransportError> {
+        let connector = self.connector.as_mut().ok_or(TransportError::Disconnected)?;
+        self.transport = connector.connect()?;
+        self.connected = true;
+        self.greeted = false;
+        self.client = None;
+        Ok(())
+    }
+
+    // Give up on a link the server never answered on
+    pub fn drop_link(&mut self) {
+        self.connected = false;
+    }
+
+    // Introduce the player once per link, so the server finds their account
+    pub fn greet(&mut self, account: AccountKey) {
+        if self.connected && !self.greeted {
+            self.send(&ClientMessage::Hello { account });
+            self.greeted = self.connected;
+        }
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        if !self.connected {
+            return;
+        }
+        if let Err(err) = self.transport.send(&encode_message(message)) {
+            self.handle_error(err);
+        }
+    }
+
+    // Every message received since the last call
+    pub fn receive(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        while self.connected {
+            match self.transport.recv() {
+                Ok(Some(frame)) => match decode_message(&frame) {
+                    Some(ServerMessage::Welcome { client }) => self.client = Some(client),
+                    Some(ServerMessage::AccountInUse) => {
+                        warn!("The server refused the link, this account is already playing there");
+                        self.connected = false;
+                    }
+                    Some(message) => messages.push(message),
+                    None => warn!("Dropped malformed message from the server"),
+                },
+                Ok(None) => break,
+                Err(err) => self.handle_error(err),
+            }
+        }
+        messages
+    }
+
+    fn handle_error(&mut self, err: TransportError) {
+        if matches!(err, TransportError::Disconnected) {
+            warn!("Lost connection to the server");
+            self.connected = false;
+        } else {
+            warn!("Network error: {}", err);
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PlayerStateTimer(Timer);
+
+// The settings resource is what the client's systems and the local server read
+fn apply_network_settings(
+    settings: Res<GameSettings>,
+    mut network: ResMut<NetworkSettings>,
+) {
+    if settings.is_changed() {
+        *network = settings.network;
+    }
+}
+
+// The first run picks the key every later session is known by
+fn assign_account_key(mut settings: ResMut<GameSettings>) {
+    if settings.account.is_none() {
+        settings.account = Some(AccountKey::generate());
+        settings.save();
+    }
+}
+
+fn receive_server_messages(
+    mut connection: ResMut<ServerConnection>,
+    mut events: EventWriter<ServerEvent>,
+) {
+    let messages = connection.receive();
+    let _span = info_span!("net_receive", messages = messages.len()).entered();
+    events.send_batch(messages.into_iter().map(ServerEvent));
+}
+
+fn send_player_state(
+    mut connection: ResMut<ServerConnection>,
+    mut timer: ResMut<PlayerStateTimer>,
+    player_query: Query<&Transform, With<Player>>,
+    settings: Res<GameSettings>,
+    time: Res<Time>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    if let Ok(transform) = player_query.get_single() {
+        if let Some(account) = settings.account {
+            connection.greet(account);
+        }
+        let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        connection.send(&ClientMessage::PlayerState { position: transform.translation, yaw });
+    }
+}
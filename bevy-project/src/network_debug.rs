@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::avatar::RemotePlayer;
+use crate::camera::FreeCamera;
+use crate::chunk_debug::{ChunkDebugGizmos, ChunkDebugSettings};
+use crate::net::{ServerConnection, ServerEvent, ServerMessage};
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::projectile::{Projectile, ProjectileKind};
+use crate::settings::GameSettings;
+use crate::snapshot::{NetworkSettings, ServerClock};
+
+// Labels float this far above the entity's origin
+const PLAYER_LABEL_HEIGHT: f32 = PLAYER_HALF_HEIGHT + 0.8;
+const ROCK_LABEL_HEIGHT: f32 = 0.4;
+// Labels farther from the camera than this are left out
+const LABEL_RANGE: f32 = 80.0;
+// A position correction keeps the local player's label red this long
+const CORRECTION_HIGHLIGHT: f32 = 2.0;
+const LABEL_BACKDROP: egui::Color32 = egui::Color32::from_black_alpha(170);
+
+// Floating labels over networked entities with their entity id, who owns them and how
+// their replication is doing, toggled from the debug window or with F2, to spot desyncs
+#[derive(Default, Clone, Debug)]
+pub struct NetworkDebugPlugin;
+
+impl Plugin for NetworkDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<LastCorrection>()
+            .add_systems(Update, (
+                track_corrections,
+                draw_network_labels,
+            ).chain());
+    }
+}
+
+// Elapsed seconds when the server last corrected the local player
+#[derive(Resource, Default)]
+struct LastCorrection(Option<f32>);
+
+// How a remote entity's shown state relates to the snapshots received for it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplicationStatus {
+    Empty,
+    Interpolating,
+    // Seconds shown past the newest snapshot
+    Extrapolating(f32),
+    // Seconds past the newest snapshot, beyond what is extrapolated
+    Stale(f32),
+}
+
+impl ReplicationStatus {
+    pub fn of(latest: Option<f64>, view_time: f64, max_extrapolation: f32) -> Self {
+        let Some(latest) = latest else {
+            return ReplicationStatus::Empty;
+        };
+        let ahead = (view_time - latest) as f32;
+        if ahead <= 0.0 {
+            ReplicationStatus::Interpolating
+        } else if ahead <= max_extrapolation {
+            ReplicationStatus::Extrapolating(ahead)
+        } else {
+            ReplicationStatus::Stale(ahead)
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            ReplicationStatus::Empty => "no snapshots".to_string(),
+            ReplicationStatus::Interpolating => "interpolating".to_string(),
+            ReplicationStatus::Extrapolating(ahead) => format!("extrapolating +{:.0} ms", ahead * 1000.0),
+            ReplicationStatus::Stale(ahead) => format!("stale {:.1} s", ahead),
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            ReplicationStatus::Interpolating => Color::srgb(0.3, 0.9, 0.4),
+            ReplicationStatus::Extrapolating(_) => Color::srgb(0.95, 0.8, 0.2),
+            ReplicationStatus::Empty | ReplicationStatus::Stale(_) => Color::srgb(0.95, 0.3, 0.25),
+        }
+    }
+}
+
+struct NetworkLabel {
+    origin: Vec3,
+    height: f32,
+    lines: Vec<String>,
+    color: Color,
+}
+
+fn track_corrections(
+    time: Res<Time>,
+    mut server_events: EventReader<ServerEvent>,
+    mut last_correction: ResMut<LastCorrection>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::PositionCorrected { .. } = message {
+            last_correction.0 = Some(time.elapsed_secs());
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_network_labels(
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos<ChunkDebugGizmos>,
+    debug_settings: Res<ChunkDebugSettings>,
+    settings: Res<GameSettings>,
+    network: Res<NetworkSettings>,
+    clock: Res<ServerClock>,
+    connection: Res<ServerConnection>,
+    last_correction: Res<LastCorrection>,
+    time: Res<Time>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<FreeCamera>>,
+    player_query: Query<(Entity, &GlobalTransform), With<Player>>,
+    remote_query: Query<(Entity, &RemotePlayer, &GlobalTransform)>,
+    projectile_query: Query<(Entity, &Projectile, &GlobalTransform)>,
+) {
+    if !debug_settings.show_network {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+
+    let mut labels = Vec::new();
+    let now = time.elapsed_secs();
+    let link = if connection.is_connected() { "connected" } else { "offline" };
+    let local_id = connection.client().map_or("not welcomed".to_string(), |client| format!("player {}", client.0));
+    for (entity, transform) in player_query.iter() {
+        let corrected = last_correction.0.map(|at| now - at);
+        let status = match corrected {
+            Some(ago) => format!("predicted, corrected {:.1} s ago", ago),
+            None => "predicted, never corrected".to_string(),
+        };
+        let recent = corrected.is_some_and(|ago| ago < CORRECTION_HIGHLIGHT);
+        labels.push(NetworkLabel {
+            origin: transform.translation(),
+            height: PLAYER_LABEL_HEIGHT,
+            lines: vec![format!("{} local {}", entity, local_id), format!("client-owned, {}", link), status],
+            color: if recent { Color::srgb(0.95, 0.3, 0.25) } else { Color::srgb(0.4, 0.7, 1.0) },
+        });
+    }
+
+    let view_time = clock.view_time(time.elapsed_secs_f64(), &network);
+    for (entity, remote, transform) in remote_query.iter() {
+        let snapshots = remote.snapshots();
+        let status = ReplicationStatus::of(snapshots.latest().map(|snapshot| snapshot.time), view_time, network.max_extrapolation);
+        labels.push(NetworkLabel {
+            origin: transform.translation(),
+            height: PLAYER_LABEL_HEIGHT,
+            lines: vec![
+                format!("{} player {}", entity, remote.client.0),
+                format!("server-owned, {} snapshots", snapshots.len()),
+                status.label(),
+            ],
+            color: status.color(),
+        });
+    }
+
+    // Rocks fly on the client, the server replays them to decide what they hit
+    for (entity, projectile, transform) in projectile_query.iter() {
+        if projectile.kind != ProjectileKind::Rock || projectile.resting {
+            continue;
+        }
+        labels.push(NetworkLabel {
+            origin: transform.translation(),
+            height: ROCK_LABEL_HEIGHT,
+            lines: vec![
+                format!("{} rock", entity),
+                if connection.is_connected() { "client-owned, hits decided by server" } else { "local only" }.to_string(),
+            ],
+            color: Color::srgb(0.8, 0.8, 0.8),
+        });
+    }
+
+    let ctx = contexts.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new("network_labels")));
+    let font = egui::FontId::monospace(12.0);
+    for label in labels {
+        let anchor = label.origin + Vec3::Y * label.height;
+        if anchor.distance(camera_transform.translation()) > LABEL_RANGE {
+            continue;
+        }
+        let Ok(viewport) = camera.world_to_viewport(camera_transform, anchor) else {
+            continue;
+        };
+        gizmos.line(label.origin, anchor, label.color);
+
+        // Viewport coordinates are logical pixels, egui's points are scaled by the UI scale
+        let position = egui::pos2(viewport.x, viewport.y) / settings.accessibility.ui_scale;
+        let [r, g, b, _] = label.color.to_srgba().to_u8_array();
+        let galley = painter.layout_no_wrap(label.lines.join("\n"), font.clone(), egui::Color32::from_rgb(r, g, b));
+        let rect = egui::Align2::CENTER_BOTTOM.anchor_size(position, galley.size()).expand(3.0);
+        painter.rect_filled(rect, 3.0, LABEL_BACKDROP);
+        painter.galley(rect.shrink(3.0).min, galley, egui::Color32::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_follows_the_newest_snapshot() {
+        assert_eq!(ReplicationStatus::of(None, 10.0, 0.25), ReplicationStatus::Empty);
+        assert_eq!(ReplicationStatus::of(Some(10.1), 10.0, 0.25), ReplicationStatus::Interpolating);
+        assert_eq!(ReplicationStatus::of(Some(10.0), 10.0, 0.25), ReplicationStatus::Interpolating);
+        assert!(matches!(ReplicationStatus::of(Some(9.9), 10.0, 0.25), ReplicationStatus::Extrapolating(_)));
+        assert!(matches!(ReplicationStatus::of(Some(9.0), 10.0, 0.25), ReplicationStatus::Stale(_)));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::biome::{self, Biome};
+use crate::client::ChunkManager;
+use crate::ron_asset::RonAssetPlugin;
+use crate::accessibility::daltonize;
+use crate::settings::{ColorVision, GameSettings};
+use crate::terrain_edit::TerrainEdits;
+
+// Color multipliers for grass in the driest and wettest places
+const DRY_GRASS_TINT: Vec3 = Vec3::new(1.6, 1.1, 0.6);
+const LUSH_GRASS_TINT: Vec3 = Vec3::new(0.7, 1.0, 0.75);
+
+#[derive(Default, Clone, Debug)]
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<TerrainPalette>::new(&["palette.ron"]))
+            .init_resource::<ActiveTerrainPalette>()
+            .init_resource::<TerrainPaletteHandle>()
+            .add_systems(Update, (
+                load_selected_palette,
+                apply_loaded_palette,
+            ).chain());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ColorStop {
+    pub height: f32,
+    pub color: [f32; 4],
+}
+
+// Height to color gradient; stops are sorted by height when the palette is loaded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColorRamp {
+    pub stops: Vec<ColorStop>,
+}
+
+impl ColorRamp {
+    pub fn color_at(&self, height: f32) -> [f32; 4] {
+        let Some(first) = self.stops.first() else {
+            return [1.0, 0.0, 1.0, 1.0];
+        };
+        if height <= first.height {
+            return first.color;
+        }
+
+        for pair in self.stops.windows(2) {
+            let (low, high) = (pair[0], pair[1]);
+            if height < high.height {
+                let t = (height - low.height) / (high.height - low.height).max(f32::EPSILON);
+                return lerp_color(low.color, high.color, t);
+            }
+        }
+        self.stops[self.stops.len() - 1].color
+    }
+
+    fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.height.total_cmp(&b.height));
+    }
+}
+
+// Terrain vertex colors: a default ramp plus optional per-biome overrides
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone)]
+pub struct TerrainPalette {
+    pub default: ColorRamp,
+    #[serde(default)]
+    pub biomes: HashMap<Biome, ColorRamp>,
+    // Colors are adjusted for this color vision, from the accessibility settings
+    #[serde(skip)]
+    pub color_vision: ColorVision,
+}
+
+impl TerrainPalette {
+    pub fn ramp(&self, biome: Biome) -> &ColorRamp {
+        self.biomes.get(&biome).unwrap_or(&self.default)
+    }
+
+    // Color of the terrain at a world position, fading between biomes near their borders
+    pub fn color(&self, world_x: f32, world_z: f32, height: f32) -> [f32; 4] {
+        let sample = biome::sample_at(world_x, world_z, height);
+        let mut color = self.ramp(sample.biome).color_at(height);
+        if sample.blend > 0.0 {
+            color = lerp_color(color, self.ramp(sample.neighbor).color_at(height), sample.blend);
+        }
+        daltonize(moisture_tint(color, sample.moisture), self.color_vision)
+    }
+}
+
+// Built-in colors used until the palette asset is loaded
+impl Default for TerrainPalette {
+    fn default() -> Self {
+        Self {
+            default: ColorRamp {
+                stops: vec![
+                    ColorStop { height: 0.3, color: [0.8, 0.7, 0.4, 1.0] },
+                    ColorStop { height: 1.5, color: [0.3, 0.6, 0.2, 1.0] },
+                    ColorStop { height: 3.0, color: [0.5, 0.4, 0.3, 1.0] },
+                    ColorStop { height: 4.0, color: [0.9, 0.9, 0.9, 1.0] },
+                ],
+            },
+            biomes: HashMap::new(),
+            color_vision: ColorVision::Normal,
+        }
+    }
+}
+
+// Palette used to color chunk meshes
+#[derive(Resource, Default)]
+pub struct ActiveTerrainPalette(pub TerrainPalette);
+
+impl ActiveTerrainPalette {
+    // Use a palette from now on; chunks have to be remeshed to show it
+    pub fn set(&mut self, mut palette: TerrainPalette) {
+        palette.color_vision = self.0.color_vision;
+        palette.default.sort();
+        for ramp in palette.biomes.values_mut() {
+            ramp.sort();
+        }
+        self.0 = palette;
+    }
+}
+
+#[derive(Resource, Default)]
+struct TerrainPaletteHandle {
+    path: String,
+    handle: Handle<TerrainPalette>,
+}
+
+// Lush grass where it's wet, dry yellow grass where it's dry. Only green colors are
+// tinted so sand, rock and snow keep their palette colors.
+fn moisture_tint(color: [f32; 4], moisture: f32) -> [f32; 4] {
+    let greenness = ((color[1] - color[0].max(color[2])) * 4.0).clamp(0.0, 1.0);
+    if greenness <= 0.0 {
+        return color;
+    }
+    let tint = if moisture < 0.0 {
+        Vec3::ONE.lerp(DRY_GRASS_TINT, -moisture)
+    } else {
+        Vec3::ONE.lerp(LUSH_GRASS_TINT, moisture)
+    };
+    let tint = Vec3::ONE.lerp(tint, greenness);
+    [color[0] * tint.x, color[1] * tint.y, color[2] * tint.z, color[3]]
+}
+
+// Linear interpolation between two colors
+pub fn lerp_color(color1: [f32; 4], color2: [f32; 4], t: f32) -> [f32; 4] {
+    let t = t.clamp(0.0, 1.0);
+    [
+        color1[0] + (color2[0] - color1[0]) * t,
+        color1[1] + (color2[1] - color1[1]) * t,
+        color1[2] + (color2[2] - color1[2]) * t,
+        color1[3] + (color2[3] - color1[3]) * t,
+    ]
+}
+
+// (Re)load the palette chosen in the settings
+fn load_selected_palette(
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+    mut palette_handle: ResMut<TerrainPaletteHandle>,
+) {
+    let path = &settings.graphics.terrain_palette;
+    if palette_handle.path == *path {
+        return;
+    }
+    info!("Loading terrain palette {}", path);
+    palette_handle.path = path.clone();
+    palette_handle.handle = asset_server.load(path.clone());
+}
+
+// Swap in the palette once loaded (or edited on disk) and recolor every loaded chunk
+fn apply_loaded_palette(
+    mut events: EventReader<AssetEvent<TerrainPalette>>,
+    palette_handle: Res<TerrainPaletteHandle>,
+    palettes: Res<Assets<TerrainPalette>>,
+    mut active: ResMut<ActiveTerrainPalette>,
+    chunk_manager: Res<ChunkManager>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+) {
+    let mut updated = false;
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event
+            && *id == palette_handle.handle.id()
+        {
+            updated = true;
+        }
+    }
+    if !updated {
+        return;
+    }
+    let Some(palette) = palettes.get(&palette_handle.handle) else {
+        return;
+    };
+
+    active.set(palette.clone());
+    terrain_edits.remesh_all(&chunk_manager);
+    info!("Terrain palette {} applied", palette_handle.path);
+}
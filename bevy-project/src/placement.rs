@@ -0,0 +1,440 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use crate::accessibility::show_prompt;
+use crate::building::{piece_mesh, place_piece, PIECE_SIZE};
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::creative::CreativeMode;
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::player::Player;
+use crate::settings::GameSettings;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::water::WaterQuery;
+use crate::input_map::{Action, Actions};
+
+// Distance in front of the player where objects are placed in Player mode
+const PLACE_DISTANCE: f32 = 3.0;
+// Reach of the camera ray in Free mode
+const PLACE_REACH: f32 = 60.0;
+const ROTATE_STEP: f32 = std::f32::consts::FRAC_PI_8;
+
+#[derive(Default, Clone, Debug)]
+pub struct PlacementPlugin;
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PlacementState>()
+            .add_event::<ObjectPlaced>()
+            .add_systems(Startup, setup_ghost_materials)
+            .add_systems(Update, (
+                toggle_placement,
+                update_placement_ghost,
+                confirm_placement,
+                placement_hint_ui,
+            ).chain());
+    }
+}
+
+// Objects the player can put down in the world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaceableKind {
+    Campfire,
+    Door,
+    Gate,
+    Floor,
+    Wall,
+    Ramp,
+}
+
+impl PlaceableKind {
+    pub const ALL: [PlaceableKind; 6] = [
+        PlaceableKind::Campfire,
+        PlaceableKind::Door,
+        PlaceableKind::Gate,
+        PlaceableKind::Floor,
+        PlaceableKind::Wall,
+        PlaceableKind::Ramp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlaceableKind::Campfire => "Campfire",
+            PlaceableKind::Door => "Door",
+            PlaceableKind::Gate => "Gate",
+            PlaceableKind::Floor => "Floor",
+            PlaceableKind::Wall => "Wall",
+            PlaceableKind::Ramp => "Ramp",
+        }
+    }
+
+    // Building pieces snap together, see `building`
+    pub fn is_structure(&self) -> bool {
+        matches!(self, PlaceableKind::Floor | PlaceableKind::Wall | PlaceableKind::Ramp)
+    }
+
+    // Radius of the ground the object covers
+    pub fn footprint(&self) -> f32 {
+        match self {
+            PlaceableKind::Campfire => 0.8,
+            PlaceableKind::Door => 0.6,
+            PlaceableKind::Gate => 1.4,
+            PlaceableKind::Floor | PlaceableKind::Wall | PlaceableKind::Ramp => PIECE_SIZE / 2.0,
+        }
+    }
+
+    // Items used up when placing the object
+    pub fn cost(&self) -> &'static [(&'static str, u32)] {
+        match self {
+            PlaceableKind::Campfire => &[("wood", 3)],
+            PlaceableKind::Door => &[("wood", 4)],
+            PlaceableKind::Gate => &[("wood", 6)],
+            PlaceableKind::Floor => &[("wood", 3)],
+            PlaceableKind::Wall => &[("wood", 3)],
+            PlaceableKind::Ramp => &[("wood", 2)],
+        }
+    }
+
+    // Largest height difference allowed under the footprint
+    pub fn max_unevenness(&self) -> f32 {
+        match self {
+            PlaceableKind::Campfire => 0.5,
+            PlaceableKind::Door => 0.3,
+            PlaceableKind::Gate => 0.6,
+            // Floors hide the gap under their thickness, walls sink into the ground
+            PlaceableKind::Floor => 0.4,
+            PlaceableKind::Wall => 0.6,
+            // A ramp lies along the slope it climbs
+            PlaceableKind::Ramp => 1.5,
+        }
+    }
+
+    fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|kind| kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // Building needs one of the materials in hand
+    fn held_material(&self, hotbar: &Hotbar, inventory: &Inventory) -> bool {
+        self.cost().iter().any(|(item, _)| hotbar.is_holding(inventory, item))
+    }
+
+    // Rough shape shown while placing, standing on the ground
+    fn ghost_mesh(&self) -> Mesh {
+        match self {
+            PlaceableKind::Campfire => Cylinder::new(self.footprint(), 0.3).into(),
+            PlaceableKind::Door | PlaceableKind::Gate => {
+                let size = door_size(*self);
+                Mesh::from(Cuboid::from_size(size)).translated_by(Vec3::Y * size.y / 2.0)
+            }
+            PlaceableKind::Floor | PlaceableKind::Wall | PlaceableKind::Ramp => piece_mesh(*self),
+        }
+    }
+}
+
+// Width, height and thickness of the door or gate panel
+pub fn door_size(kind: PlaceableKind) -> Vec3 {
+    match kind {
+        PlaceableKind::Gate => Vec3::new(2.6, 1.3, 0.1),
+        _ => Vec3::new(1.1, 2.2, 0.12),
+    }
+}
+
+// Sent once the player confirms a placement; the owning feature spawns the actual object
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ObjectPlaced {
+    pub kind: PlaceableKind,
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+// Put on placed objects so new placements don't overlap them
+#[derive(Component)]
+pub struct PlacedObject {
+    pub kind: PlaceableKind,
+}
+
+#[derive(Resource)]
+pub struct PlacementState {
+    pub active: Option<PlaceableKind>,
+    // Kind picked when placing starts again
+    last_kind: PlaceableKind,
+    ghost: Option<Entity>,
+    rotation: f32,
+    target: Option<Transform>,
+    valid: bool,
+    affordable: bool,
+    // Building piece attached to another one
+    snapped: bool,
+}
+
+impl Default for PlacementState {
+    fn default() -> Self {
+        Self {
+            active: None,
+            last_kind: PlaceableKind::Campfire,
+            ghost: None,
+            rotation: 0.0,
+            target: None,
+            valid: false,
+            affordable: false,
+            snapped: false,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct GhostMaterials {
+    valid: Handle<StandardMaterial>,
+    invalid: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct PlacementGhost;
+
+fn setup_ghost_materials(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let ghost = |color: Color| StandardMaterial {
+        base_color: color,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    };
+    commands.insert_resource(GhostMaterials {
+        valid: materials.add(ghost(Color::srgba(0.3, 1.0, 0.4, 0.4))),
+        invalid: materials.add(ghost(Color::srgba(1.0, 0.25, 0.2, 0.4))),
+    });
+}
+
+// B starts or cancels placing an object while holding its material, Tab switches to the next kind.
+// Escape, or putting the material away, cancels too.
+#[allow(clippy::too_many_arguments)]
+fn toggle_placement(
+    mut commands: Commands,
+    actions: Actions,
+    mut state: ResMut<PlacementState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    ghost_materials: Option<Res<GhostMaterials>>,
+    mut ghost_query: Query<&mut Mesh3d, With<PlacementGhost>>,
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    creative: Res<CreativeMode>,
+) {
+    let holding = |kind: PlaceableKind| creative.free_building() || kind.held_material(&hotbar, &inventory);
+
+    if let (Some(kind), Some(ghost)) = (state.active, state.ghost)
+        && actions.just_pressed(Action::CyclePiece)
+    {
+        let kind = kind.next();
+        state.active = Some(kind);
+        state.last_kind = kind;
+        if let Ok(mut mesh) = ghost_query.get_mut(ghost) {
+            mesh.0 = meshes.add(kind.ghost_mesh());
+        }
+        return;
+    }
+
+    let cancel = state.active
+        .is_some_and(|kind| actions.just_pressed(Action::CancelBuild) || !holding(kind));
+    if !actions.just_pressed(Action::ToggleBuild) && !cancel {
+        return;
+    }
+
+    if let Some(ghost) = state.ghost.take() {
+        commands.entity(ghost).despawn_recursive();
+    }
+    if state.active.take().is_some() {
+        return;
+    }
+    let Some(ghost_materials) = ghost_materials else {
+        return;
+    };
+
+    let kind = state.last_kind;
+    if !holding(kind) {
+        info!("Hold {} to build", kind.cost().iter().map(|(item, _)| *item).collect::<Vec<_>>().join(" or "));
+        return;
+    }
+    state.active = Some(kind);
+    state.ghost = Some(commands.spawn((
+        Mesh3d(meshes.add(kind.ghost_mesh())),
+        MeshMaterial3d(ghost_materials.invalid.clone()),
+        Transform::default(),
+        Visibility::Hidden,
+        PlacementGhost,
+    )).id());
+}
+
+// Spot the object would be placed at: in front of the player, or where the free camera looks
+fn placement_target(
+    camera_settings: &CameraSettings,
+    camera: &Transform,
+    player: Option<&Transform>,
+    terrain_edits: &TerrainEdits,
+) -> Option<Vec3> {
+    match camera_settings.camera_mode {
+        CameraMode::Player => {
+            let player = player?;
+            let forward = player.forward().with_y(0.0).normalize_or_zero();
+            let point = player.translation + forward * PLACE_DISTANCE;
+            Some(Vec3::new(point.x, terrain_edits.height(point.x, point.z), point.z))
+        }
+        CameraMode::Free => terrain::raycast(
+            camera.translation,
+            *camera.forward(),
+            PLACE_REACH,
+            |x, z| terrain_edits.height(x, z),
+        ),
+    }
+}
+
+// Ground must be dry, flat enough and free of other placed objects
+fn is_valid_placement(
+    kind: PlaceableKind,
+    position: Vec3,
+    water: &WaterQuery,
+    placed: &Query<(&Transform, &PlacedObject)>,
+) -> bool {
+    let footprint = kind.footprint();
+    let samples = [Vec2::ZERO, Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y]
+        .map(|offset| Vec2::new(position.x, position.z) + offset * footprint);
+    if samples.iter().any(|point| water.has_water(point.x, point.y)) {
+        return false;
+    }
+    let heights = samples.map(|point| water.floor_height(point.x, point.y));
+    let lowest = heights.iter().copied().fold(f32::MAX, f32::min);
+    let highest = heights.iter().copied().fold(f32::MIN, f32::max);
+    if highest - lowest > kind.max_unevenness() {
+        return false;
+    }
+
+    placed.iter().all(|(transform, object)| {
+        let distance = transform.translation.xz().distance(position.xz());
+        distance > footprint + object.kind.footprint()
+    })
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_placement_ghost(
+    actions: Actions,
+    mut state: ResMut<PlacementState>,
+    camera_settings: Res<CameraSettings>,
+    terrain_edits: Res<TerrainEdits>,
+    water: WaterQuery,
+    inventory: Res<Inventory>,
+    creative: Res<CreativeMode>,
+    ghost_materials: Option<Res<GhostMaterials>>,
+    camera_query: Query<&Transform, (With<FreeCamera>, Without<PlacementGhost>)>,
+    player_query: Query<&Transform, (With<Player>, Without<PlacementGhost>)>,
+    placed: Query<(&Transform, &PlacedObject)>,
+    mut ghost_query: Query<(&mut Transform, &mut Visibility, &mut MeshMaterial3d<StandardMaterial>), (With<PlacementGhost>, Without<FreeCamera>, Without<Player>, Without<PlacedObject>)>,
+) {
+    let (Some(kind), Some(ghost), Some(ghost_materials)) = (state.active, state.ghost, ghost_materials) else {
+        return;
+    };
+    let Ok((mut transform, mut visibility, mut material)) = ghost_query.get_mut(ghost) else {
+        return;
+    };
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+
+    // Z and X turn the object
+    if actions.just_pressed(Action::RotateLeft) {
+        state.rotation += ROTATE_STEP;
+    }
+    if actions.just_pressed(Action::RotateRight) {
+        state.rotation -= ROTATE_STEP;
+    }
+
+    let Some(target) = placement_target(&camera_settings, camera, player_query.get_single().ok(), &terrain_edits) else {
+        state.target = None;
+        *visibility = Visibility::Hidden;
+        state.valid = false;
+        return;
+    };
+
+    let (placement, valid) = if kind.is_structure() {
+        let piece = place_piece(kind, target, state.rotation, &placed.iter().collect::<Vec<_>>(), &water);
+        state.snapped = piece.snapped;
+        (piece.transform, piece.valid)
+    } else {
+        state.snapped = false;
+        let transform = Transform::from_translation(target).with_rotation(Quat::from_rotation_y(state.rotation));
+        (transform, is_valid_placement(kind, target, &water, &placed))
+    };
+    state.target = Some(placement);
+    state.affordable = creative.free_building() || inventory.has_all(kind.cost());
+    state.valid = state.affordable && valid;
+    *transform = placement;
+    *visibility = Visibility::Inherited;
+    material.0 = if state.valid { ghost_materials.valid.clone() } else { ghost_materials.invalid.clone() };
+}
+
+// Left click places the object when the spot is valid, paying its cost unless building for free
+fn confirm_placement(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut state: ResMut<PlacementState>,
+    mut inventory: ResMut<Inventory>,
+    creative: Res<CreativeMode>,
+    mut events: EventWriter<ObjectPlaced>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) || !state.valid {
+        return;
+    }
+    let (Some(kind), Some(target)) = (state.active, state.target) else {
+        return;
+    };
+    if !creative.free_building() && !inventory.take_all(kind.cost()) {
+        return;
+    }
+
+    let position = target.translation;
+    events.send(ObjectPlaced {
+        kind,
+        position,
+        rotation: target.rotation,
+    });
+    info!("Placed {} at ({:.1}, {:.1}, {:.1})", kind.label(), position.x, position.y, position.z);
+
+    state.active = None;
+    state.valid = false;
+    if let Some(ghost) = state.ghost.take() {
+        commands.entity(ghost).despawn_recursive();
+    }
+}
+
+fn placement_hint_ui(
+    mut contexts: EguiContexts,
+    state: Res<PlacementState>,
+    creative: Res<CreativeMode>,
+    settings: Res<GameSettings>,
+) {
+    let Some(kind) = state.active else {
+        return;
+    };
+
+    show_prompt(contexts.ctx_mut(), "placement_hint", &settings.accessibility, egui::Align2::CENTER_BOTTOM, [0.0, -120.0], |ui| {
+        ui.label(format!(
+            "Placing {}{}: [Left click] place  [Z/X] rotate  [Tab] next  [B/Esc] cancel",
+            kind.label(),
+            if state.snapped { " (snapped)" } else { "" },
+        ));
+        if !creative.free_building() {
+            let cost: Vec<String> = kind.cost().iter()
+                .map(|(item, count)| format!("{} {}", count, item))
+                .collect();
+            let label = format!("Costs {}", cost.join(", "));
+            if state.affordable {
+                ui.label(label);
+            } else {
+                ui.colored_label(egui::Color32::LIGHT_RED, format!("{} (not enough)", label));
+            }
+        }
+    });
+}
@@ -1,4 +1,63 @@
+use bevy::app::RunFixedMainLoopSystem;
 use bevy::prelude::*;
+use crate::asset_registry::GameState;
+use crate::avatar::Avatar;
+use crate::boat::Aboard;
+use crate::net::{ClientMessage, ServerConnection, ServerEvent, ServerMessage};
+use crate::camera::{CameraMode, CameraSettings};
+use crate::collider::{self, BoxCollider, WalkableSurface};
+use crate::creative::CreativeMode;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::water::sea_level;
+use crate::input_map::{Action, Actions};
+use crate::streaming::StreamingAnchor;
+
+// Capsule3d::new(PLAYER_RADIUS, PLAYER_BODY_LENGTH)
+pub const PLAYER_RADIUS: f32 = 0.5;
+pub const PLAYER_BODY_LENGTH: f32 = 1.8;
+pub const PLAYER_HALF_HEIGHT: f32 = PLAYER_RADIUS + PLAYER_BODY_LENGTH / 2.0;
+
+const SPAWN_ORIGIN: Vec2 = Vec2::ZERO;
+const SPAWN_SEARCH_RADIUS: f32 = 400.0;
+const SPAWN_CLEARANCE: f32 = 0.2;
+const DRY_LAND_MARGIN: f32 = 0.3;
+// Chunks around a teleport destination kept loaded while the camera catches up
+const TELEPORT_PRELOAD_RADIUS: i32 = 2;
+const TELEPORT_PRELOAD_SECONDS: f32 = 5.0;
+
+pub const WALK_SPEED: f32 = 6.0;
+pub const SPRINT_SPEED: f32 = 10.0;
+const CROUCH_SPEED: f32 = 3.0;
+const SWIM_SPEED: f32 = 3.5;
+const FLY_SPEED: f32 = 15.0;
+const FLY_SPRINT_SPEED: f32 = 40.0;
+// Water deeper than this makes the player swim instead of walking on the bottom
+const SWIM_DEPTH: f32 = 1.3;
+// How deep the feet hang under the surface while swimming
+const SWIM_FEET_DEPTH: f32 = 1.4;
+// Vertical speeds when diving or swimming up, and floating back up otherwise
+const DIVE_SPEED: f32 = 2.0;
+const BUOYANCY: f32 = 1.0;
+// Steepest ground the player can walk up, steeper ground makes them slide down
+pub const MAX_SLOPE_DEGREES: f32 = 45.0;
+// Ledges up to this height are stepped onto whatever their slope
+pub const MAX_STEP_HEIGHT: f32 = 0.4;
+// Distance ahead the ground is checked for ledges
+const STEP_PROBE: f32 = PLAYER_RADIUS;
+const SLIDE_SPEED: f32 = 5.0;
+// Vertical speed when climbing onto a ledge instead of snapping up
+const STEP_UP_SPEED: f32 = 3.0;
+// Player simulation rate, the one a server would tick at
+const PLAYER_TICK_HZ: f64 = 60.0;
+const MAX_HEALTH: f32 = 100.0;
+const MAX_STAMINA: f32 = 100.0;
+// Seconds of breath under water
+const MAX_OXYGEN: f32 = 30.0;
+const MAX_THIRST: f32 = 100.0;
+// Stamina per second spent sprinting and recovered otherwise
+const SPRINT_STAMINA_COST: f32 = 20.0;
+const STAMINA_RECOVERY: f32 = 12.0;
 
 #[derive(Default, Clone, Debug)]
 pub struct PlayerPlugin;
@@ -6,30 +65,395 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Startup, spawn_player);
+            .insert_resource(Time::<Fixed>::from_hz(PLAYER_TICK_HZ))
+            .init_resource::<RespawnPoint>()
+            .init_resource::<PlayerInput>()
+            .add_event::<RespawnPlayer>()
+            .add_systems(Startup, spawn_player)
+            .add_systems(RunFixedMainLoop, buffer_player_input.in_set(RunFixedMainLoopSystem::BeforeFixedMainLoop))
+            .add_systems(FixedUpdate, move_player.run_if(in_state(GameState::Playing)))
+            .add_systems(Update, (take_server_positions, respawn_player));
     }
 }
 
+// Put the player back where the server last saw them, or where it corrected them to
+fn take_server_positions(
+    mut commands: Commands,
+    mut server_events: EventReader<ServerEvent>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        let position = match message {
+            ServerMessage::AccountRestored { position, .. } => {
+                preload_destination(&mut commands, *position);
+                position
+            }
+            ServerMessage::PositionCorrected { position } => {
+                warn!("Server corrected the player's position to {}", position);
+                position
+            }
+            _ => continue,
+        };
+        if let Ok(mut transform) = player_query.get_single_mut() {
+            transform.translation = *position;
+        }
+    }
+}
+
+// Where the player comes back after dying; the world spawn when None
+#[derive(Resource, Default)]
+pub struct RespawnPoint(pub Option<Vec3>);
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct RespawnPlayer;
+
 #[derive(Component)]
 pub struct Player {
+    #[allow(dead_code)]
     pub id : i32,
 }
 
-fn spawn_player(
-    mut commands : Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+// Keys driving the player, true while held
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveKeys {
+    pub forward: bool,
+    pub back: bool,
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub sprint: bool,
+}
+
+impl MoveKeys {
+    fn read(pressed: impl Fn(Action) -> bool) -> Self {
+        Self {
+            forward: pressed(Action::MoveForward),
+            back: pressed(Action::MoveBack),
+            left: pressed(Action::MoveLeft),
+            right: pressed(Action::MoveRight),
+            up: pressed(Action::Jump),
+            down: pressed(Action::Crouch),
+            sprint: pressed(Action::Sprint),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self {
+            forward: self.forward || other.forward,
+            back: self.back || other.back,
+            left: self.left || other.left,
+            right: self.right || other.right,
+            up: self.up || other.up,
+            down: self.down || other.down,
+            sprint: self.sprint || other.sprint,
+        }
+    }
+
+    // x to the right, y forward
+    fn planar(&self) -> Vec2 {
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        Vec2::new(axis(self.right, self.left), axis(self.forward, self.back))
+    }
+}
+
+// Input gathered every frame and consumed by the fixed player ticks.
+// Keys tapped between two ticks still count for the next one, and mouse look
+// is accumulated so the result doesn't depend on the frame rate.
+#[derive(Resource, Default, Debug)]
+pub struct PlayerInput {
+    held: MoveKeys,
+    tapped: MoveKeys,
+    // Yaw turned by the mouse since the last tick, in radians
+    pub yaw_delta: f32,
+}
+
+impl PlayerInput {
+    // Keys for one tick; taps and mouse look are only given to the first tick of a frame
+    fn take(&mut self) -> (MoveKeys, f32) {
+        let keys = self.held.or(std::mem::take(&mut self.tapped));
+        (keys, std::mem::take(&mut self.yaw_delta))
+    }
+}
+
+// A stat going from 0 up to its maximum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vital {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Vital {
+    pub fn full(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.max > 0.0 { (self.current / self.max).clamp(0.0, 1.0) } else { 0.0 }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.current >= self.max
+    }
+
+    // Add or remove, staying within 0 and the maximum
+    pub fn change(&mut self, amount: f32) {
+        self.current = (self.current + amount).clamp(0.0, self.max);
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct Health(pub Vital);
+
+// Spent by sprinting, sprinting stops when it runs out
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct Stamina(pub Vital);
+
+// Breath left while the head is under water
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct Oxygen(pub Vital);
+
+// Water left in the body, drained over time and refilled by drinking
+#[derive(Component, Clone, Copy, Debug, Deref, DerefMut)]
+pub struct Thirst(pub Vital);
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlayerStance {
+    #[default]
+    Standing,
+    Crouching,
+    Swimming,
+}
+
+fn spawn_player(mut commands : Commands) {
+    let spawn_point = find_spawn_point();
+
+    commands.spawn((
+        Avatar { color: Color::srgb(0.3, 0.6, 0.9) },
+        Transform::from_translation(spawn_point),
+        Player { id: 1 },
+        PlayerStance::default(),
+        Health(Vital::full(MAX_HEALTH)),
+        Stamina(Vital::full(MAX_STAMINA)),
+        Oxygen(Vital::full(MAX_OXYGEN)),
+        Thirst(Vital::full(MAX_THIRST)),
+    ));
+}
+
+// Sample the terrain around the spawn origin and pick a spot on dry land
+pub fn find_spawn_point() -> Vec3 {
+    let ground = terrain::find_dry_land(SPAWN_ORIGIN, sea_level(SPAWN_ORIGIN.x, SPAWN_ORIGIN.y) + DRY_LAND_MARGIN, SPAWN_SEARCH_RADIUS)
+        .unwrap_or_else(|| {
+            warn!("No dry land found near spawn, spawning above the water");
+            SPAWN_ORIGIN
+        });
+
+    let ground_height = terrain::height(ground.x, ground.y).max(sea_level(ground.x, ground.y));
+    info!("Spawning player at ({:.1}, {:.1}), ground height {:.2}", ground.x, ground.y, ground_height);
+
+    Vec3::new(ground.x, ground_height + PLAYER_HALF_HEIGHT + SPAWN_CLEARANCE, ground.y)
+}
+
+
+
+fn buffer_player_input(
+    actions: Actions,
+    camera_settings: Res<CameraSettings>,
+    mut input: ResMut<PlayerInput>,
 ) {
+    if camera_settings.camera_mode != CameraMode::Player {
+        *input = PlayerInput::default();
+        return;
+    }
+    // Typing in a text field (the console) has no movement bindings, so it doesn't walk
+    input.held = MoveKeys::read(|action| actions.pressed(action));
+    let tapped = MoveKeys::read(|action| actions.just_pressed(action));
+    input.tapped = input.tapped.or(tapped);
+}
+
+// Fixed tick: WASD walking relative to the player's facing, kept on the terrain surface.
+// Left Ctrl crouches, deep water makes the player swim: Left Ctrl dives, Space swims up
+// and the player floats back to the surface otherwise.
+// In creative fly mode Space / Left Ctrl move up and down instead.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn move_player(
+    mut player_query: Query<(&mut Transform, &mut PlayerStance, &mut Stamina), (With<Player>, Without<Aboard>)>,
+    mut input: ResMut<PlayerInput>,
+    camera_settings: Res<CameraSettings>,
+    terrain_edits: Res<TerrainEdits>,
+    creative: Res<CreativeMode>,
+    colliders: Query<(&GlobalTransform, &BoxCollider)>,
+    surfaces: Query<(&GlobalTransform, &WalkableSurface)>,
+    time: Res<Time>,
+) {
+    if camera_settings.camera_mode != CameraMode::Player {
+        return;
+    }
+    let Ok((mut transform, mut stance, mut stamina)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let (keys, yaw_delta) = input.take();
+    transform.rotate_y(yaw_delta);
+
+    let forward = transform.forward().with_y(0.0).normalize_or_zero();
+    let right = transform.right().with_y(0.0).normalize_or_zero();
+    let planar = keys.planar();
+    let mut direction = forward * planar.y + right * planar.x;
+
+    if creative.flying() {
+        direction.y = keys.up as i32 as f32 - keys.down as i32 as f32;
+        let speed = if keys.sprint { FLY_SPRINT_SPEED } else { FLY_SPEED };
+        transform.translation += direction.normalize_or_zero() * speed * time.delta_secs();
+        stance.set_if_neq(PlayerStance::Standing);
+        if !creative.noclip() {
+            let ground = terrain_edits.height(transform.translation.x, transform.translation.z);
+            transform.translation.y = transform.translation.y.max(ground + PLAYER_HALF_HEIGHT);
+        }
+        return;
+    }
+
+    let dt = time.delta_secs();
+    // Holding sprint while out of stamina walks without recovering any
+    let sprinting = *stance == PlayerStance::Standing && keys.sprint && direction != Vec3::ZERO;
+    if !sprinting {
+        stamina.change(STAMINA_RECOVERY * dt);
+    } else if stamina.current > 0.0 {
+        stamina.change(-SPRINT_STAMINA_COST * dt);
+    }
+    let speed = match *stance {
+        PlayerStance::Swimming => SWIM_SPEED,
+        PlayerStance::Crouching => CROUCH_SPEED,
+        PlayerStance::Standing if sprinting && stamina.current > 0.0 => SPRINT_SPEED,
+        PlayerStance::Standing => WALK_SPEED,
+    };
+
+    let mut step = direction.normalize_or_zero() * speed * dt;
+    // Built floors and ramps are flat enough to walk anywhere on them
+    let standing_on = |position: Vec3| {
+        collider::surface_height(position - Vec3::Y * PLAYER_HALF_HEIGHT, MAX_STEP_HEIGHT, surfaces.iter())
+    };
+    let on_structure = standing_on(transform.translation)
+        .is_some_and(|height| height >= terrain_edits.height(transform.translation.x, transform.translation.z));
+    if *stance != PlayerStance::Swimming && !on_structure {
+        step = limit_slope(&terrain_edits, transform.translation, step) + slide_down(&terrain_edits, transform.translation, dt);
+    }
+    transform.translation += step;
+    let (bottom, top) = (transform.translation.y - PLAYER_HALF_HEIGHT, transform.translation.y + PLAYER_HALF_HEIGHT);
+    transform.translation = collider::push_out(transform.translation, PLAYER_RADIUS, bottom, top, colliders.iter());
+    let (x, z) = (transform.translation.x, transform.translation.z);
+    let ground = terrain_edits.height(x, z).max(standing_on(transform.translation).unwrap_or(f32::MIN));
+
+    let new_stance = if sea_level(x, z) - ground > SWIM_DEPTH {
+        PlayerStance::Swimming
+    } else if keys.down {
+        PlayerStance::Crouching
+    } else {
+        PlayerStance::Standing
+    };
+    stance.set_if_neq(new_stance);
+
+    let feet = match *stance {
+        PlayerStance::Swimming => {
+            let rise = match (keys.down, keys.up) {
+                (true, false) => -DIVE_SPEED,
+                (false, true) => DIVE_SPEED,
+                _ => BUOYANCY,
+            };
+            let feet = (transform.translation.y - PLAYER_HALF_HEIGHT + rise * dt).min(sea_level(x, z) - SWIM_FEET_DEPTH);
+            ground.max(feet)
+        }
+        _ => ground,
+    };
+    // Walking up a slope follows the ground, a ledge is climbed over a few ticks
+    let target = feet + PLAYER_HALF_HEIGHT;
+    let walkable_rise = speed * dt * max_slope() + 0.01;
+    transform.translation.y = if target - transform.translation.y > walkable_rise {
+        (transform.translation.y + STEP_UP_SPEED * dt).min(target)
+    } else {
+        target
+    };
+}
+
+pub fn max_slope() -> f32 {
+    MAX_SLOPE_DEGREES.to_radians().tan()
+}
+
+// Height change per meter along x and z
+fn ground_gradient(terrain_edits: &TerrainEdits, x: f32, z: f32) -> Vec2 {
+    const D: f32 = 0.5;
+    Vec2::new(
+        terrain_edits.height(x + D, z) - terrain_edits.height(x - D, z),
+        terrain_edits.height(x, z + D) - terrain_edits.height(x, z - D),
+    ) / (2.0 * D)
+}
+
+// Drop the uphill part of a move onto ground steeper than the slope limit, unless it
+// only climbs a low ledge. Moving across or down a steep slope stays possible.
+fn limit_slope(terrain_edits: &TerrainEdits, position: Vec3, step: Vec3) -> Vec3 {
+    let Some(heading) = step.xz().try_normalize() else {
+        return step;
+    };
+    let target = position.xz() + step.xz();
+    let gradient = ground_gradient(terrain_edits, target.x, target.y);
+    if gradient.length() <= max_slope() || gradient.dot(heading) <= 0.0 {
+        return step;
+    }
+
+    let ahead = target + heading * STEP_PROBE;
+    let rise = terrain_edits.height(ahead.x, ahead.y) - terrain_edits.height(position.x, position.z);
+    if rise <= MAX_STEP_HEIGHT {
+        return step;
+    }
+    let uphill = gradient.normalize();
+    let along = step.xz() - uphill * step.xz().dot(uphill);
+    Vec3::new(along.x, 0.0, along.y)
+}
+
+// Standing on ground too steep to hold, slide down it faster the steeper it is
+fn slide_down(terrain_edits: &TerrainEdits, position: Vec3, dt: f32) -> Vec3 {
+    let gradient = ground_gradient(terrain_edits, position.x, position.z);
+    let steepness = gradient.length();
+    if steepness <= max_slope() {
+        return Vec3::ZERO;
+    }
+    let excess = ((steepness - max_slope()) / max_slope()).min(1.0);
+    let downhill = -gradient / steepness;
+    Vec3::new(downhill.x, 0.0, downhill.y) * SLIDE_SPEED * (0.5 + 0.5 * excess) * dt
+}
+
+// Start loading the chunks where the player is teleported to, ahead of the camera
+fn preload_destination(commands: &mut Commands, destination: Vec3) {
     commands.spawn((
-        Mesh3d(meshes.add(Capsule3d::new(0.5, 1.8))),
-        MeshMaterial3d(materials.add(StandardMaterial {
-            base_color: Color::srgb(0.3, 0.6, 0.9),
-            metallic: 0.1,
-            perceptual_roughness: 0.8,
-            ..default()
-        })),
-        Player { id: 01 }
+        StreamingAnchor::preload(TELEPORT_PRELOAD_RADIUS, TELEPORT_PRELOAD_SECONDS),
+        Transform::from_translation(destination),
     ));
 }
 
+fn respawn_player(
+    mut commands: Commands,
+    mut events: EventReader<RespawnPlayer>,
+    respawn_point: Res<RespawnPoint>,
+    terrain_edits: Res<TerrainEdits>,
+    mut connection: ResMut<ServerConnection>,
+    mut player_query: Query<(&mut Transform, &mut Health, &mut Stamina, &mut Oxygen, &mut Thirst), With<Player>>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    let Ok((mut transform, mut health, mut stamina, mut oxygen, mut thirst)) = player_query.get_single_mut() else {
+        return;
+    };
+    health.current = health.max;
+    stamina.current = stamina.max;
+    oxygen.current = oxygen.max;
+    thirst.current = thirst.max;
+    connection.send(&ClientMessage::Respawned);
 
+    transform.translation = match respawn_point.0 {
+        Some(point) => Vec3::new(point.x, terrain_edits.height(point.x, point.z) + PLAYER_HALF_HEIGHT, point.z),
+        None => find_spawn_point(),
+    };
+    preload_destination(&mut commands, transform.translation);
+    info!("Player respawned at ({:.1}, {:.1})", transform.translation.x, transform.translation.z);
+}
@@ -0,0 +1,88 @@
+use std::marker::PhantomData;
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+
+// Marks an entity parked in a pool; gameplay queries can filter it out with `Without<Pooled>`
+#[derive(Component)]
+pub struct Pooled;
+
+// Registers an `EntityPool<T>` resource. `T` is the marker component identifying the pool
+pub struct EntityPoolPlugin<T> {
+    capacity: usize,
+    reset: fn(&mut EntityCommands),
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> EntityPoolPlugin<T> {
+    pub fn new(capacity: usize, reset: fn(&mut EntityCommands)) -> Self {
+        Self {
+            capacity,
+            reset,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> Plugin for EntityPoolPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EntityPool::<T>::new(self.capacity, self.reset));
+    }
+}
+
+// Recycles entities instead of despawning and respawning them, for water chunks as they
+// stream and impact debris
+#[derive(Resource)]
+pub struct EntityPool<T> {
+    free: Vec<Entity>,
+    capacity: usize,
+    // Strips per-use components when an entity goes back to the pool
+    reset: fn(&mut EntityCommands),
+    pub spawned: usize,
+    pub reused: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> EntityPool<T> {
+    pub fn new(capacity: usize, reset: fn(&mut EntityCommands)) -> Self {
+        Self {
+            free: Vec::with_capacity(capacity),
+            capacity,
+            reset,
+            spawned: 0,
+            reused: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    // Take an entity from the pool (or spawn one) and give it `bundle`
+    pub fn acquire(&mut self, commands: &mut Commands, bundle: impl Bundle) -> Entity {
+        match self.free.pop() {
+            Some(entity) => {
+                self.reused += 1;
+                commands.entity(entity)
+                    .remove::<Pooled>()
+                    .insert((bundle, Visibility::Inherited));
+                entity
+            }
+            None => {
+                self.spawned += 1;
+                commands.spawn((bundle, Visibility::Inherited)).id()
+            }
+        }
+    }
+
+    // Hand an entity back; it is hidden and reset, or despawned when the pool is full
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        if self.free.len() >= self.capacity {
+            commands.entity(entity).despawn_recursive();
+            return;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands
+            .remove_parent()
+            .insert((Pooled, Visibility::Hidden));
+        (self.reset)(&mut entity_commands);
+        self.free.push(entity);
+    }
+}
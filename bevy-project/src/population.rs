@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::render::view::VisibilitySystems;
+use serde::Deserialize;
+use crate::biome::Biome;
+use crate::camera::FreeCamera;
+
+// Keeps the number of live creatures bounded as the player explores: spawners ask for room
+// under the global and per-biome caps, and creatures wandering out of the simulation range
+// or left unseen for a while are despawned
+#[derive(Default, Clone, Debug)]
+pub struct PopulationPlugin;
+
+impl Plugin for PopulationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PopulationLimits>()
+            .init_resource::<Population>()
+            .add_systems(PreUpdate, count_creatures)
+            .add_systems(PostUpdate, (
+                track_seen_creatures.after(VisibilitySystems::CheckVisibility),
+                despawn_stray_creatures,
+            ).chain());
+    }
+}
+
+// Caps and despawn rules, read from the creature spawn table
+#[derive(Resource, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PopulationLimits {
+    pub global: u32,
+    // Cap of biomes missing from `biomes`
+    pub per_biome: u32,
+    pub biomes: HashMap<Biome, u32>,
+    // Creatures farther than this from the camera are despawned, and don't spawn there
+    pub simulation_range: f32,
+    pub unseen_minutes: f32,
+}
+
+impl Default for PopulationLimits {
+    fn default() -> Self {
+        Self {
+            global: 600,
+            per_biome: 300,
+            biomes: HashMap::new(),
+            simulation_range: 220.0,
+            unseen_minutes: 3.0,
+        }
+    }
+}
+
+impl PopulationLimits {
+    pub fn biome_cap(&self, biome: Biome) -> u32 {
+        self.biomes.get(&biome).copied().unwrap_or(self.per_biome)
+    }
+}
+
+// A creature counted against the caps of the biome it spawned in
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Creature {
+    pub biome: Biome,
+    // Elapsed seconds when it was last on screen
+    last_seen: f32,
+}
+
+impl Creature {
+    pub fn new(biome: Biome, now: f32) -> Self {
+        Self { biome, last_seen: now }
+    }
+}
+
+// Live creatures, counted at the start of each frame and kept up to date by spawners
+#[derive(Resource, Default, Debug)]
+pub struct Population {
+    total: u32,
+    biomes: HashMap<Biome, u32>,
+}
+
+impl Population {
+    pub fn count(&self, biome: Biome) -> u32 {
+        self.biomes.get(&biome).copied().unwrap_or(0)
+    }
+
+    // How many more creatures may spawn in a biome
+    pub fn room(&self, biome: Biome, limits: &PopulationLimits) -> u32 {
+        let global = limits.global.saturating_sub(self.total);
+        global.min(limits.biome_cap(biome).saturating_sub(self.count(biome)))
+    }
+
+    // Count creatures spawned this frame, before the next recount sees them
+    pub fn add(&mut self, biome: Biome, count: u32) {
+        self.total += count;
+        *self.biomes.entry(biome).or_default() += count;
+    }
+}
+
+fn count_creatures(
+    mut population: ResMut<Population>,
+    creature_query: Query<&Creature>,
+) {
+    let population = population.bypass_change_detection();
+    population.total = 0;
+    population.biomes.clear();
+    for creature in creature_query.iter() {
+        population.add(creature.biome, 1);
+    }
+}
+
+fn track_seen_creatures(
+    time: Res<Time>,
+    mut creature_query: Query<(&mut Creature, &ViewVisibility)>,
+) {
+    let now = time.elapsed_secs();
+    for (mut creature, visibility) in creature_query.iter_mut() {
+        if visibility.get() {
+            creature.last_seen = now;
+        }
+    }
+}
+
+fn despawn_stray_creatures(
+    mut commands: Commands,
+    time: Res<Time>,
+    limits: Res<PopulationLimits>,
+    camera_query: Query<&GlobalTransform, With<FreeCamera>>,
+    creature_query: Query<(Entity, &Creature, &GlobalTransform)>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let now = time.elapsed_secs();
+    let range_squared = limits.simulation_range * limits.simulation_range;
+    let mut despawned = 0;
+    for (entity, creature, transform) in creature_query.iter() {
+        let far = transform.translation().distance_squared(camera.translation()) > range_squared;
+        let forgotten = now - creature.last_seen > limits.unseen_minutes * 60.0;
+        if far || forgotten {
+            commands.entity(entity).despawn_recursive();
+            despawned += 1;
+        }
+    }
+    if despawned > 0 {
+        debug!("Despawned {} creatures out of range or unseen", despawned);
+    }
+}
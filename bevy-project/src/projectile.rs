@@ -0,0 +1,311 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::fishing::{BOBBER_RADIUS, FISHING_ROD};
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::net::{ClientMessage, ServerConnection, ServerEvent, ServerMessage};
+use crate::placement::PlacementState;
+use crate::region::ProtectedRegions;
+use crate::player::{Health, Player, RespawnPlayer, PLAYER_HALF_HEIGHT};
+use crate::snapshot::{NetworkSettings, ServerClock};
+use crate::terrain_edit::TerrainEdits;
+use crate::toast::{Toast, ToastKind};
+use crate::water::WaterQuery;
+
+pub const GRAVITY: f32 = 9.81;
+pub const THROW_SPEED: f32 = 20.0;
+// Thrown slightly above the aim point to make up for the drop
+const THROW_LIFT: f32 = 0.08;
+const THROW_COOLDOWN: f32 = 0.6;
+pub const ROCK_RADIUS: f32 = 0.12;
+// Health lost when another player's rock hits
+const ROCK_DAMAGE: f32 = 15.0;
+// Velocity kept per second while under water
+const WATER_DRAG: f32 = 0.1;
+// Floating projectiles settle with half their height under the surface
+const BUOYANCY: f32 = GRAVITY * 2.0;
+// Rocks lie on the ground for a while before disappearing
+const RESTING_LIFETIME: f32 = 8.0;
+const MAX_FLIGHT_TIME: f32 = 10.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ThrowCooldown>()
+            .add_event::<ProjectileHit>()
+            .add_systems(Startup, setup_projectile_assets)
+            .add_systems(Update, (
+                throw_rock,
+                move_projectiles,
+                expire_projectiles,
+                aim_reticle_ui,
+            ).chain())
+            .add_systems(Update, take_rock_hits);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileKind {
+    Rock,
+    // Fishing bobber, floats on the water and stays out until reeled in
+    Bobber,
+}
+
+impl ProjectileKind {
+    pub fn radius(&self) -> f32 {
+        match self {
+            ProjectileKind::Rock => ROCK_RADIUS,
+            ProjectileKind::Bobber => BOBBER_RADIUS,
+        }
+    }
+}
+
+// Flying projectile; `resting` once it landed
+#[derive(Component)]
+pub struct Projectile {
+    pub kind: ProjectileKind,
+    pub velocity: Vec3,
+    pub age: f32,
+    pub resting: bool,
+}
+
+// Something projectiles can hit, as a sphere around its origin
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Hittable {
+    pub radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HitTarget {
+    Terrain,
+    Entity(Entity),
+}
+
+// Sent when a projectile lands on the terrain or hits a hittable entity
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ProjectileHit {
+    pub kind: ProjectileKind,
+    pub target: HitTarget,
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+#[derive(Resource, Default)]
+struct ThrowCooldown(f32);
+
+#[derive(Resource)]
+struct ProjectileAssets {
+    rock_mesh: Handle<Mesh>,
+    rock_material: Handle<StandardMaterial>,
+}
+
+fn setup_projectile_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(ProjectileAssets {
+        rock_mesh: meshes.add(Sphere::new(ROCK_RADIUS).mesh().ico(1).unwrap()),
+        rock_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.5, 0.48, 0.45),
+            perceptual_roughness: 0.95,
+            ..default()
+        }),
+    });
+}
+
+// Left click throws a rock where the camera aims, in Player mode and when not placing
+// anything or fishing
+#[allow(clippy::too_many_arguments)]
+fn throw_rock(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    camera_settings: Res<CameraSettings>,
+    placement: Res<PlacementState>,
+    hotbar: Res<Hotbar>,
+    inventory: Res<Inventory>,
+    mut cooldown: ResMut<ThrowCooldown>,
+    assets: Option<Res<ProjectileAssets>>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    player_query: Query<&Transform, (With<Player>, Without<FreeCamera>)>,
+    mut connection: ResMut<ServerConnection>,
+    clock: Res<ServerClock>,
+    network: Res<NetworkSettings>,
+    time: Res<Time>,
+) {
+    cooldown.0 = (cooldown.0 - time.delta_secs()).max(0.0);
+    if camera_settings.camera_mode != CameraMode::Player
+        || placement.active.is_some()
+        || hotbar.is_holding(&inventory, FISHING_ROD)
+        || cooldown.0 > 0.0
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || contexts.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    let (Some(assets), Ok(camera), Ok(player)) = (assets, camera_query.get_single(), player_query.get_single()) else {
+        return;
+    };
+
+    // Thrown from the player's eyes, along the line of sight through the reticle
+    let feet = player.translation - Vec3::Y * PLAYER_HALF_HEIGHT;
+    let origin = feet + Vec3::Y * camera_settings.eye_height;
+    let direction = (*camera.forward() + Vec3::Y * THROW_LIFT).normalize();
+    let (origin, velocity) = (origin + direction * 0.6, direction * THROW_SPEED);
+    commands.spawn((
+        Mesh3d(assets.rock_mesh.clone()),
+        MeshMaterial3d(assets.rock_material.clone()),
+        Transform::from_translation(origin),
+        Projectile {
+            kind: ProjectileKind::Rock,
+            velocity,
+            age: 0.0,
+            resting: false,
+        },
+    ));
+    // The server decides whether it hit another player, seen where they were shown here
+    let view_time = clock.view_time(time.elapsed_secs_f64(), &network);
+    connection.send(&ClientMessage::RockThrown { origin, velocity, view_time });
+    cooldown.0 = THROW_COOLDOWN;
+}
+
+fn take_rock_hits(
+    mut server_events: EventReader<ServerEvent>,
+    mut respawn_events: EventWriter<RespawnPlayer>,
+    mut toasts: EventWriter<Toast>,
+    mut player_query: Query<&mut Health, With<Player>>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        let ServerMessage::RockHit { by, .. } = message else {
+            continue;
+        };
+        let Ok(mut health) = player_query.get_single_mut() else {
+            continue;
+        };
+        health.change(-ROCK_DAMAGE);
+        info!("Hit by a rock from {:?}", by);
+        if health.current <= 0.0 {
+            toasts.send(Toast::new(ToastKind::Combat, format!("You were knocked out by player {}", by.0)));
+            respawn_events.send(RespawnPlayer);
+        }
+    }
+}
+
+// Closest point of a segment to a sphere, when they touch
+fn segment_hits_sphere(from: Vec3, to: Vec3, center: Vec3, radius: f32) -> bool {
+    let segment = to - from;
+    let length_squared = segment.length_squared();
+    let t = if length_squared > 0.0 {
+        ((center - from).dot(segment) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (from + segment * t).distance_squared(center) <= radius * radius
+}
+
+// Ballistic flight, stopped by hittable entities and the terrain
+fn move_projectiles(
+    time: Res<Time>,
+    terrain_edits: Res<TerrainEdits>,
+    water: WaterQuery,
+    mut hits: EventWriter<ProjectileHit>,
+    mut projectile_query: Query<(&mut Projectile, &mut Transform), Without<Hittable>>,
+    targets: Query<(Entity, &GlobalTransform, &Hittable)>,
+    regions: Res<ProtectedRegions>,
+) {
+    let dt = time.delta_secs();
+    for (mut projectile, mut transform) in projectile_query.iter_mut() {
+        if projectile.resting {
+            continue;
+        }
+        let from = transform.translation;
+        let radius = projectile.kind.radius();
+        projectile.velocity.y -= GRAVITY * dt;
+        if water.is_in_water(from) {
+            projectile.velocity *= WATER_DRAG.powf(dt);
+        }
+        // Floating projectiles are pushed up by the share of them under the surface
+        if projectile.kind == ProjectileKind::Bobber
+            && let Some(surface) = water.surface_height(from.xz())
+        {
+            let submerged = ((surface - from.y) / radius + 0.5).clamp(0.0, 1.0);
+            projectile.velocity.y += BUOYANCY * submerged * dt;
+        }
+        let to = from + projectile.velocity * dt;
+
+        let hit = targets.iter()
+            .filter(|(_, target, hittable)| segment_hits_sphere(from, to, target.translation(), hittable.radius + radius))
+            .min_by(|a, b| a.1.translation().distance_squared(from).total_cmp(&b.1.translation().distance_squared(from)));
+        if let Some((entity, target, _)) = hit {
+            // Nothing gets hurt inside protected regions, the rock just bounces off
+            if !regions.no_damage_at(target.translation()) {
+                hits.send(ProjectileHit {
+                    kind: projectile.kind,
+                    target: HitTarget::Entity(entity),
+                    position: to,
+                    velocity: projectile.velocity,
+                });
+            }
+            // Bounce off and fall to the ground
+            projectile.velocity = -projectile.velocity * 0.2;
+            transform.translation = from;
+            continue;
+        }
+
+        let ground = terrain_edits.height(to.x, to.z) + radius;
+        if to.y <= ground {
+            transform.translation = Vec3::new(to.x, ground, to.z);
+            hits.send(ProjectileHit {
+                kind: projectile.kind,
+                target: HitTarget::Terrain,
+                position: transform.translation,
+                velocity: projectile.velocity,
+            });
+            projectile.velocity = Vec3::ZERO;
+            projectile.resting = true;
+            projectile.age = 0.0;
+            continue;
+        }
+        transform.translation = to;
+    }
+}
+
+fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut projectile_query: Query<(Entity, &mut Projectile)>,
+) {
+    for (entity, mut projectile) in projectile_query.iter_mut() {
+        if projectile.kind == ProjectileKind::Bobber {
+            continue;
+        }
+        projectile.age += time.delta_secs();
+        let lifetime = if projectile.resting { RESTING_LIFETIME } else { MAX_FLIGHT_TIME };
+        if projectile.age > lifetime {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Crosshair in the middle of the screen, where the camera looks at the player's eyes
+fn aim_reticle_ui(
+    mut contexts: EguiContexts,
+    camera_settings: Res<CameraSettings>,
+    placement: Res<PlacementState>,
+) {
+    if camera_settings.camera_mode != CameraMode::Player || placement.active.is_some() {
+        return;
+    }
+    let ctx = contexts.ctx_mut();
+    let center = ctx.screen_rect().center();
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new("aim_reticle")));
+    let stroke = egui::Stroke::new(2.0, egui::Color32::from_white_alpha(200));
+    for direction in [egui::vec2(1.0, 0.0), egui::vec2(-1.0, 0.0), egui::vec2(0.0, 1.0), egui::vec2(0.0, -1.0)] {
+        painter.line_segment([center + direction * 4.0, center + direction * 10.0], stroke);
+    }
+}
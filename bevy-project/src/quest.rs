@@ -0,0 +1,310 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::camera::FreeCamera;
+use crate::ron_asset::RonAssetPlugin;
+
+const QUEST_BOOK_PATH: &str = "quests/main.quests.ron";
+const QUEST_BOOK: &str = "quest_book";
+
+#[derive(Default, Clone, Debug)]
+pub struct QuestPlugin;
+
+impl Plugin for QuestPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<QuestBook>::new(&["quests.ron"]))
+            .preload_asset::<QuestBook>(QUEST_BOOK, QUEST_BOOK_PATH)
+            .init_resource::<QuestLog>()
+            .add_event::<ItemCollected>()
+            .add_event::<ObjectiveCompleted>()
+            .add_event::<QuestCompleted>()
+            .add_systems(Startup, (load_quest_book, spawn_objective_marker))
+            .add_systems(Update, (
+                start_next_quest,
+                track_goto_objective,
+                track_collect_objective,
+                update_objective_marker,
+                quest_hud_system,
+            ).chain());
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Objective {
+    // Reach a point on the map, given as world (x, z)
+    GoTo { position: (f32, f32), radius: f32 },
+    // Collect `count` items with the given id
+    Collect { item: String, count: u32 },
+}
+
+impl Objective {
+    fn describe(&self) -> String {
+        match self {
+            Objective::GoTo { position, .. } => format!("Go to ({:.0}, {:.0})", position.0, position.1),
+            Objective::Collect { item, count } => format!("Collect {} x {}", count, item),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct QuestDefinition {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub objectives: Vec<Objective>,
+    // NPC offering the quest in a dialogue, it only starts once accepted there
+    #[serde(default)]
+    pub giver: Option<String>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct QuestBook {
+    pub quests: Vec<QuestDefinition>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveQuest {
+    pub definition: QuestDefinition,
+    pub objective: usize,
+    pub progress: u32,
+    pub distance: Option<f32>,
+}
+
+impl ActiveQuest {
+    pub fn current_objective(&self) -> Option<&Objective> {
+        self.definition.objectives.get(self.objective)
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct QuestLog {
+    pub book: Handle<QuestBook>,
+    pub active: Option<ActiveQuest>,
+    pub completed: Vec<String>,
+    // Quests accepted from NPCs, started in turn with the others
+    pub accepted: Vec<String>,
+}
+
+impl QuestLog {
+    // False if the quest was already accepted or done
+    pub fn accept(&mut self, quest_id: &str) -> bool {
+        if self.accepted.iter().chain(&self.completed).any(|id| id == quest_id) {
+            return false;
+        }
+        self.accepted.push(quest_id.to_string());
+        true
+    }
+}
+
+// Sent by gameplay systems whenever the player picks something up
+#[derive(Event, Debug, Clone)]
+pub struct ItemCollected {
+    pub item: String,
+    pub count: u32,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct ObjectiveCompleted {
+    pub quest_id: String,
+    pub objective: usize,
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct QuestCompleted {
+    pub quest_id: String,
+}
+
+#[derive(Component)]
+pub struct ObjectiveMarker;
+
+fn load_quest_book(
+    registry: Res<AssetRegistry>,
+    mut quest_log: ResMut<QuestLog>,
+) {
+    quest_log.book = registry.get(QUEST_BOOK).unwrap_or_default();
+}
+
+fn start_next_quest(
+    mut quest_log: ResMut<QuestLog>,
+    books: Res<Assets<QuestBook>>,
+) {
+    if quest_log.active.is_some() {
+        return;
+    }
+    let Some(book) = books.get(&quest_log.book) else {
+        return;
+    };
+
+    let next = book.quests.iter()
+        .filter(|quest| quest.giver.is_none() || quest_log.accepted.contains(&quest.id))
+        .find(|quest| !quest_log.completed.contains(&quest.id))
+        .cloned();
+
+    if let Some(definition) = next {
+        info!("Quest started: {}", definition.title);
+        quest_log.active = Some(ActiveQuest {
+            definition,
+            objective: 0,
+            progress: 0,
+            distance: None,
+        });
+    }
+}
+
+// Move the active quest to its next objective, finishing the quest after the last one
+fn advance_objective(
+    quest_log: &mut QuestLog,
+    objective_events: &mut EventWriter<ObjectiveCompleted>,
+    quest_events: &mut EventWriter<QuestCompleted>,
+) {
+    let Some(active) = quest_log.active.as_mut() else {
+        return;
+    };
+
+    objective_events.send(ObjectiveCompleted {
+        quest_id: active.definition.id.clone(),
+        objective: active.objective,
+    });
+    active.objective += 1;
+    active.progress = 0;
+    active.distance = None;
+
+    if active.objective >= active.definition.objectives.len() {
+        let quest_id = active.definition.id.clone();
+        info!("Quest completed: {}", active.definition.title);
+        quest_log.completed.push(quest_id.clone());
+        quest_log.active = None;
+        quest_events.send(QuestCompleted { quest_id });
+    }
+}
+
+fn track_goto_objective(
+    mut quest_log: ResMut<QuestLog>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut objective_events: EventWriter<ObjectiveCompleted>,
+    mut quest_events: EventWriter<QuestCompleted>,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let Some(active) = quest_log.active.as_mut() else {
+        return;
+    };
+    let Some(Objective::GoTo { position, radius }) = active.current_objective().cloned() else {
+        return;
+    };
+
+    let target = Vec2::new(position.0, position.1);
+    let current = Vec2::new(camera_transform.translation.x, camera_transform.translation.z);
+    let distance = current.distance(target);
+    active.distance = Some(distance);
+
+    if distance <= radius {
+        advance_objective(&mut quest_log, &mut objective_events, &mut quest_events);
+    }
+}
+
+fn track_collect_objective(
+    mut quest_log: ResMut<QuestLog>,
+    mut collected: EventReader<ItemCollected>,
+    mut objective_events: EventWriter<ObjectiveCompleted>,
+    mut quest_events: EventWriter<QuestCompleted>,
+) {
+    for event in collected.read() {
+        let Some(active) = quest_log.active.as_mut() else {
+            continue;
+        };
+        let Some(Objective::Collect { item, count }) = active.current_objective().cloned() else {
+            continue;
+        };
+        if event.item != item {
+            continue;
+        }
+
+        active.progress += event.count;
+        if active.progress >= count {
+            advance_objective(&mut quest_log, &mut objective_events, &mut quest_events);
+        }
+    }
+}
+
+fn spawn_objective_marker(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Tall beam so the marker stays visible whatever the terrain height is
+    commands.spawn((
+        Mesh3d(meshes.add(Cylinder::new(0.4, 60.0))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgba(1.0, 0.8, 0.2, 0.5),
+            emissive: LinearRgba::rgb(4.0, 3.0, 0.5),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        })),
+        Transform::from_xyz(0.0, 20.0, 0.0),
+        Visibility::Hidden,
+        ObjectiveMarker,
+    ));
+}
+
+fn update_objective_marker(
+    quest_log: Res<QuestLog>,
+    mut marker_query: Query<(&mut Transform, &mut Visibility), With<ObjectiveMarker>>,
+    time: Res<Time>,
+) {
+    let Ok((mut transform, mut visibility)) = marker_query.get_single_mut() else {
+        return;
+    };
+
+    match quest_log.active.as_ref().and_then(|active| active.current_objective()) {
+        Some(Objective::GoTo { position, .. }) => {
+            transform.translation.x = position.0;
+            transform.translation.z = position.1;
+            transform.rotate_y(time.delta_secs());
+            *visibility = Visibility::Visible;
+        }
+        _ => {
+            *visibility = Visibility::Hidden;
+        }
+    }
+}
+
+fn quest_hud_system(
+    mut contexts: EguiContexts,
+    quest_log: Res<QuestLog>,
+) {
+    let Some(active) = quest_log.active.as_ref() else {
+        return;
+    };
+
+    egui::Window::new("Quest")
+        .anchor(egui::Align2::RIGHT_TOP, [-10.0, 10.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(&active.definition.title);
+            ui.label(&active.definition.description);
+            ui.separator();
+            if let Some(objective) = active.current_objective() {
+                ui.label(objective.describe());
+                match objective {
+                    Objective::GoTo { .. } => {
+                        if let Some(distance) = active.distance {
+                            ui.label(format!("Distance: {:.0} m", distance));
+                        }
+                    }
+                    Objective::Collect { count, .. } => {
+                        ui.label(format!("Progress: {}/{}", active.progress, count));
+                    }
+                }
+            }
+            ui.label(format!(
+                "Objective {}/{}",
+                active.objective + 1,
+                active.definition.objectives.len()
+            ));
+        });
+}
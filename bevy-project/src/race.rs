@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use crate::camera::{CameraMode, CameraSettings};
+use crate::console::{CommandResult, ConsoleAppExt, ConsoleCommand};
+use crate::player::Player;
+use crate::save::{LoadedSave, SaveRequested};
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+
+// Rings stand on the terrain, passed by coming within RING_RADIUS of their center
+const RING_RADIUS: f32 = 3.0;
+const RING_THICKNESS: f32 = 0.2;
+const RING_COLOR: Color = Color::srgb(0.9, 0.9, 0.9);
+const NEXT_RING_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+const PASSED_RING_COLOR: Color = Color::srgba(0.4, 0.8, 0.4, 0.35);
+// Seconds the result of a finished race stays on screen
+const RESULT_SECONDS: f32 = 6.0;
+
+// Time trials: checkpoint rings placed on the terrain from the console, raced through in
+// order in Player mode, with the course and best time of each world seed kept in the save
+#[derive(Default, Clone, Debug)]
+pub struct RacePlugin;
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<RaceCourses>()
+            .init_resource::<Race>()
+            .register_console_command(ConsoleCommand {
+                name: "race",
+                usage: "race <add|undo|clear|start|stop|best>",
+                help: "place checkpoints at the player and run time trials",
+                run: race_command,
+            })
+            .add_systems(Startup, setup_ring_assets)
+            .add_systems(PostStartup, restore_race_courses)
+            .add_systems(Update, (
+                spawn_checkpoint_rings,
+                update_race,
+                color_checkpoint_rings,
+                race_ui,
+            ).chain());
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Checkpoint {
+    // Center of the ring
+    pub position: Vec3,
+    // Direction the ring faces, the player's heading when placed
+    pub yaw: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct RaceCourse {
+    pub checkpoints: Vec<Checkpoint>,
+    // Seconds, None until the course is finished once
+    pub best_time: Option<f32>,
+}
+
+impl RaceCourse {
+    // Keep a finishing time, true when it beats the best one
+    pub fn record(&mut self, time: f32) -> bool {
+        if self.best_time.is_some_and(|best| best <= time) {
+            return false;
+        }
+        self.best_time = Some(time);
+        true
+    }
+}
+
+// Courses of every world seed played, the current seed's one being raced
+#[derive(Resource, Default, Debug, Clone)]
+pub struct RaceCourses {
+    pub seeds: HashMap<u32, RaceCourse>,
+}
+
+impl RaceCourses {
+    pub fn current(&self) -> Option<&RaceCourse> {
+        self.seeds.get(&terrain::world_gen().seed)
+    }
+
+    pub fn current_mut(&mut self) -> &mut RaceCourse {
+        self.seeds.entry(terrain::world_gen().seed).or_default()
+    }
+}
+
+// A race in progress: armed until the first ring is passed, then timed
+#[derive(Debug, Clone, Copy)]
+struct RunningRace {
+    next: usize,
+    elapsed: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+struct Race {
+    running: Option<RunningRace>,
+    // Last finishing time, whether it was a record, and seconds it has been shown
+    result: Option<(f32, bool, f32)>,
+}
+
+#[derive(Component)]
+struct CheckpointRing(usize);
+
+#[derive(Resource)]
+struct RingAssets {
+    mesh: Handle<Mesh>,
+    idle: Handle<StandardMaterial>,
+    next: Handle<StandardMaterial>,
+    passed: Handle<StandardMaterial>,
+}
+
+// 1:02.35
+pub fn format_race_time(seconds: f32) -> String {
+    let hundredths = (seconds * 100.0).round() as u32;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
+
+fn setup_ring_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = |color: Color| StandardMaterial {
+        base_color: color,
+        emissive: color.to_linear() * 0.5,
+        alpha_mode: if color.alpha() < 1.0 { AlphaMode::Blend } else { AlphaMode::Opaque },
+        unlit: true,
+        ..default()
+    };
+    commands.insert_resource(RingAssets {
+        mesh: meshes.add(Torus::new(RING_RADIUS - RING_THICKNESS, RING_RADIUS + RING_THICKNESS)),
+        idle: materials.add(material(RING_COLOR)),
+        next: materials.add(material(NEXT_RING_COLOR)),
+        passed: materials.add(material(PASSED_RING_COLOR)),
+    });
+}
+
+fn restore_race_courses(
+    save: Res<LoadedSave>,
+    mut courses: ResMut<RaceCourses>,
+) {
+    courses.seeds = save.0.races.clone();
+}
+
+fn race_command(world: &mut World, args: &[&str]) -> CommandResult {
+    let player = world.query_filtered::<&Transform, With<Player>>().get_single(world).ok().copied();
+    let reply = match args {
+        ["add"] => {
+            let player = player.ok_or("no player to place the checkpoint at")?;
+            let (x, z) = (player.translation.x, player.translation.z);
+            let ground = world.resource::<TerrainEdits>().height(x, z);
+            let forward = player.forward();
+            let checkpoint = Checkpoint {
+                position: Vec3::new(x, ground + RING_RADIUS, z),
+                yaw: forward.x.atan2(forward.z),
+            };
+            let course = world.resource_mut::<RaceCourses>().into_inner().current_mut();
+            course.checkpoints.push(checkpoint);
+            // A different course makes the old time meaningless
+            course.best_time = None;
+            format!("checkpoint {} placed at ({:.0}, {:.0})", course.checkpoints.len(), x, z)
+        }
+        ["undo"] => {
+            let course = world.resource_mut::<RaceCourses>().into_inner().current_mut();
+            course.checkpoints.pop().ok_or("the course has no checkpoints")?;
+            course.best_time = None;
+            format!("{} checkpoints left", course.checkpoints.len())
+        }
+        ["clear"] => {
+            *world.resource_mut::<RaceCourses>().current_mut() = RaceCourse::default();
+            "course cleared".to_string()
+        }
+        ["start"] => {
+            let count = world.resource::<RaceCourses>().current().map_or(0, |course| course.checkpoints.len());
+            if count < 2 {
+                return Err("place at least two checkpoints with `race add`".to_string());
+            }
+            if world.resource::<CameraSettings>().camera_mode != CameraMode::Player {
+                return Err("races are run in Player mode".to_string());
+            }
+            world.resource_mut::<Race>().running = Some(RunningRace { next: 0, elapsed: 0.0 });
+            return Ok("race armed, the clock starts at the first checkpoint".to_string());
+        }
+        ["stop"] => {
+            world.resource_mut::<Race>().running.take().ok_or("no race is running")?;
+            return Ok("race stopped".to_string());
+        }
+        ["best"] => {
+            let course = world.resource::<RaceCourses>().current().cloned().unwrap_or_default();
+            return Ok(match course.best_time {
+                Some(best) => format!("best time {} over {} checkpoints", format_race_time(best), course.checkpoints.len()),
+                None => format!("no time set over {} checkpoints", course.checkpoints.len()),
+            });
+        }
+        _ => return Err("usage: race <add|undo|clear|start|stop|best>".to_string()),
+    };
+    // Editing the course ends the race on it
+    world.resource_mut::<Race>().running = None;
+    world.send_event(SaveRequested { reason: "race course" });
+    Ok(reply)
+}
+
+// Put the rings up again whenever the course or the world seed changes
+fn spawn_checkpoint_rings(
+    mut commands: Commands,
+    courses: Res<RaceCourses>,
+    assets: Option<Res<RingAssets>>,
+    mut shown_seed: Local<Option<u32>>,
+    ring_query: Query<Entity, With<CheckpointRing>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    let seed = terrain::world_gen().seed;
+    if !courses.is_changed() && *shown_seed == Some(seed) {
+        return;
+    }
+    *shown_seed = Some(seed);
+    for entity in ring_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let Some(course) = courses.current() else {
+        return;
+    };
+    for (index, checkpoint) in course.checkpoints.iter().enumerate() {
+        // The torus lies flat, it is stood up across the heading
+        let rotation = Quat::from_rotation_y(checkpoint.yaw) * Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        commands.spawn((
+            CheckpointRing(index),
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(assets.idle.clone()),
+            Transform::from_translation(checkpoint.position).with_rotation(rotation),
+        ));
+    }
+}
+
+fn update_race(
+    time: Res<Time>,
+    camera_settings: Res<CameraSettings>,
+    mut race: ResMut<Race>,
+    mut courses: ResMut<RaceCourses>,
+    mut save_requests: EventWriter<SaveRequested>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let dt = time.delta_secs();
+    if let Some((_, _, shown)) = &mut race.result {
+        *shown += dt;
+        if *shown > RESULT_SECONDS {
+            race.result = None;
+        }
+    }
+    let Some(mut running) = race.running else {
+        return;
+    };
+    if camera_settings.camera_mode != CameraMode::Player {
+        info!("Race stopped, left Player mode");
+        race.running = None;
+        return;
+    }
+    let (Ok(player), Some(course)) = (player_query.get_single(), courses.current()) else {
+        return;
+    };
+    if running.next > 0 {
+        running.elapsed += dt;
+    }
+    let Some(checkpoint) = course.checkpoints.get(running.next) else {
+        race.running = None;
+        return;
+    };
+    if player.translation.distance(checkpoint.position) <= RING_RADIUS {
+        running.next += 1;
+        if running.next == course.checkpoints.len() {
+            let record = courses.current_mut().record(running.elapsed);
+            info!("Race finished in {}{}", format_race_time(running.elapsed), if record { ", new best" } else { "" });
+            if record {
+                save_requests.send(SaveRequested { reason: "race record" });
+            }
+            race.result = Some((running.elapsed, record, 0.0));
+            race.running = None;
+            return;
+        }
+    }
+    race.running = Some(running);
+}
+
+fn color_checkpoint_rings(
+    race: Res<Race>,
+    assets: Option<Res<RingAssets>>,
+    mut ring_query: Query<(&CheckpointRing, &mut MeshMaterial3d<StandardMaterial>)>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    let next = race.running.map(|running| running.next);
+    for (ring, mut material) in ring_query.iter_mut() {
+        let wanted = match next {
+            Some(next) if ring.0 < next => &assets.passed,
+            Some(next) if ring.0 == next => &assets.next,
+            _ => &assets.idle,
+        };
+        if material.0 != *wanted {
+            material.0 = wanted.clone();
+        }
+    }
+}
+
+// Clock and progress at the top of the screen while racing, then the result
+fn race_ui(
+    mut contexts: EguiContexts,
+    race: Res<Race>,
+    courses: Res<RaceCourses>,
+) {
+    let course = courses.current();
+    let best = course.and_then(|course| course.best_time);
+    let lines = match (race.running, race.result) {
+        (Some(running), _) => {
+            let count = course.map_or(0, |course| course.checkpoints.len());
+            let status = if running.next == 0 {
+                "Go through the first checkpoint to start".to_string()
+            } else {
+                format!("{}   checkpoint {}/{}", format_race_time(running.elapsed), running.next, count)
+            };
+            vec![status, best.map_or("No best time yet".to_string(), |best| format!("Best {}", format_race_time(best)))]
+        }
+        (None, Some((time, record, _))) => vec![format!(
+            "Finished in {}{}",
+            format_race_time(time),
+            if record { ", new best!" } else { "" },
+        )],
+        (None, None) => return,
+    };
+    egui::Area::new(egui::Id::new("race"))
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 40.0])
+        .show(contexts.ctx_mut(), |ui| {
+            ui.vertical_centered(|ui| {
+                for line in lines {
+                    ui.label(egui::RichText::new(line).size(20.0).color(egui::Color32::WHITE));
+                }
+            });
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_faster_times_are_records() {
+        let mut course = RaceCourse::default();
+        assert!(course.record(42.5));
+        assert!(!course.record(50.0));
+        assert!(!course.record(42.5));
+        assert!(course.record(40.0));
+        assert_eq!(course.best_time, Some(40.0));
+        assert_eq!(format_race_time(62.349), "1:02.35");
+    }
+}
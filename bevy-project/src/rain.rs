@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::terrain_edit::TerrainEdits;
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle};
+use crate::water::sea_level;
+use crate::world_events::{unit_random, ActiveWorldEvents, WorldEventAppExt, WorldEventType};
+
+pub const RAIN: &str = "rain";
+// Drops per second at full intensity
+const RAIN_RATE: f32 = 500.0;
+const DROP_SPEED: f32 = 22.0;
+// Drops start in this radius around the camera, this high above it
+const DROP_AREA_RADIUS: f32 = 30.0;
+const DROP_START_HEIGHT: f32 = 20.0;
+// Seconds of full rain to soak the ground, and of dry weather to dry it again
+const SOAK_SECONDS: f32 = 60.0;
+const DRY_SECONDS: f32 = 240.0;
+// Wetness the material changes by before it is updated again
+const WETNESS_STEP: f32 = 0.01;
+// Puddles form above this wetness and grow to full size when soaked
+const PUDDLE_WETNESS: f32 = 0.35;
+// Local minima are searched on a grid this fine, this far around the player
+const PUDDLE_GRID: f32 = 4.0;
+const PUDDLE_SCAN_RADIUS: f32 = 48.0;
+// Moving this far from the last search looks for puddles again
+const PUDDLE_RESCAN_DISTANCE: f32 = 20.0;
+// A hollow deeper than this around its grid neighbours is a pit, not a puddle spot
+const PUDDLE_MAX_DEPTH: f32 = 0.8;
+const PUDDLE_MIN_DEPTH: f32 = 0.02;
+const PUDDLE_RADIUS: (f32, f32) = (1.2, 2.6);
+// Lift above the lowest point so the water surface meets the hollow's sides
+const PUDDLE_LIFT: f32 = 0.04;
+
+#[derive(Default, Clone, Debug)]
+pub struct RainPlugin;
+
+impl Plugin for RainPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_world_event(WorldEventType {
+                name: RAIN,
+                start_window: (0.0, 24.0),
+                chance: 0.05,
+                duration_hours: 3.0,
+            })
+            .init_resource::<Wetness>()
+            .init_resource::<RainSpawner>()
+            .init_resource::<PuddleScan>()
+            .add_systems(Startup, setup_rain_assets)
+            .add_systems(Update, (
+                (spawn_rain_drops, move_rain_drops).chain(),
+                (update_wetness, apply_terrain_wetness, update_puddles).chain(),
+            ));
+    }
+}
+
+// How wet the ground is, 0 dry, 1 soaked. Rises while it rains and dries slowly after.
+#[derive(Resource, Default, Debug)]
+pub struct Wetness(pub f32);
+
+#[derive(Component)]
+struct RainDrop;
+
+#[derive(Component)]
+struct Puddle {
+    radius: f32,
+}
+
+#[derive(Resource, Default)]
+struct RainSpawner {
+    // Fractional drops carried over between frames
+    pending: f32,
+    count: u64,
+}
+
+#[derive(Resource, Default)]
+struct PuddleScan {
+    // Where puddles were last searched for, None once everything dried
+    center: Option<Vec2>,
+}
+
+#[derive(Resource)]
+struct RainAssets {
+    drop_mesh: Handle<Mesh>,
+    drop_material: Handle<StandardMaterial>,
+    puddle_mesh: Handle<Mesh>,
+    puddle_material: Handle<StandardMaterial>,
+}
+
+fn setup_rain_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(RainAssets {
+        drop_mesh: meshes.add(Cuboid::new(0.015, 0.5, 0.015)),
+        drop_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.75, 0.8, 0.9, 0.35),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        // Unit disc facing up, scaled to each puddle's size
+        puddle_mesh: meshes.add(Circle::new(1.0).mesh().resolution(24).build().rotated_by(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2))),
+        puddle_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.2, 0.24, 0.28, 0.7),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.05,
+            reflectance: 0.8,
+            ..default()
+        }),
+    });
+}
+
+fn spawn_rain_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    events: Res<ActiveWorldEvents>,
+    assets: Res<RainAssets>,
+    mut spawner: ResMut<RainSpawner>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+) {
+    let intensity = events.intensity(RAIN);
+    if intensity <= 0.0 {
+        spawner.pending = 0.0;
+        return;
+    }
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+
+    spawner.pending += RAIN_RATE * intensity * time.delta_secs();
+    while spawner.pending >= 1.0 {
+        spawner.pending -= 1.0;
+        spawner.count += 1;
+        let random = |salt| unit_random(spawner.count, salt);
+        // Uniform over the disc around the camera
+        let angle = random(0) * std::f32::consts::TAU;
+        let distance = random(1).sqrt() * DROP_AREA_RADIUS;
+        let start = camera.translation
+            + Vec3::new(angle.cos() * distance, DROP_START_HEIGHT * (0.5 + random(2)), angle.sin() * distance);
+        commands.spawn((
+            Mesh3d(assets.drop_mesh.clone()),
+            MeshMaterial3d(assets.drop_material.clone()),
+            Transform::from_translation(start),
+            RainDrop,
+        ));
+    }
+}
+
+// Drops fall straight down and vanish when they reach the ground or the sea
+fn move_rain_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    terrain_edits: Res<TerrainEdits>,
+    mut drop_query: Query<(Entity, &mut Transform), With<RainDrop>>,
+) {
+    let fall = DROP_SPEED * time.delta_secs();
+    for (entity, mut transform) in drop_query.iter_mut() {
+        transform.translation.y -= fall;
+        let (x, z) = (transform.translation.x, transform.translation.z);
+        if transform.translation.y < terrain_edits.height(x, z).max(sea_level(x, z)) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn update_wetness(
+    time: Res<Time>,
+    events: Res<ActiveWorldEvents>,
+    mut wetness: ResMut<Wetness>,
+) {
+    let intensity = events.intensity(RAIN);
+    let change = if intensity > 0.0 {
+        intensity / SOAK_SECONDS
+    } else {
+        -1.0 / DRY_SECONDS
+    };
+    let value = (wetness.0 + change * time.delta_secs()).clamp(0.0, 1.0);
+    if value != wetness.0 {
+        wetness.0 = value;
+    }
+}
+
+// The terrain darkens and turns glossy with the wetness, updated in small steps
+// so the material isn't re-uploaded every frame
+fn apply_terrain_wetness(
+    wetness: Res<Wetness>,
+    handle: Res<TerrainMaterialHandle>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    mut applied: Local<f32>,
+) {
+    let reached_end = (wetness.0 == 0.0 || wetness.0 == 1.0) && *applied != wetness.0;
+    if (wetness.0 - *applied).abs() < WETNESS_STEP && !reached_end {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.extension.params.wetness = wetness.0;
+        *applied = wetness.0;
+    }
+}
+
+// Lowest point of a shallow hollow: below all 8 grid neighbours, but not by much
+fn puddle_spot(terrain_edits: &TerrainEdits, x: f32, z: f32) -> Option<f32> {
+    let height = terrain_edits.height(x, z);
+    if height < sea_level(x, z) + 0.2 {
+        return None;
+    }
+    let mut deepest = 0.0f32;
+    for (dx, dz) in [(-1.0, -1.0), (0.0, -1.0), (1.0, -1.0), (-1.0, 0.0), (1.0, 0.0), (-1.0, 1.0), (0.0, 1.0), (1.0, 1.0)] {
+        let depth = terrain_edits.height(x + dx * PUDDLE_GRID, z + dz * PUDDLE_GRID) - height;
+        if depth <= 0.0 {
+            return None;
+        }
+        deepest = deepest.max(depth);
+    }
+    (PUDDLE_MIN_DEPTH..PUDDLE_MAX_DEPTH).contains(&deepest).then_some(height)
+}
+
+// Puddles fill flat hollows around the player once the ground is wet enough,
+// grow while it keeps raining and shrink away as it dries
+fn update_puddles(
+    mut commands: Commands,
+    wetness: Res<Wetness>,
+    terrain_edits: Res<TerrainEdits>,
+    assets: Res<RainAssets>,
+    mut scan: ResMut<PuddleScan>,
+    camera_query: Query<&Transform, (With<FreeCamera>, Without<Puddle>)>,
+    mut puddle_query: Query<(Entity, &Puddle, &mut Transform)>,
+) {
+    let fill = ((wetness.0 - PUDDLE_WETNESS) / (1.0 - PUDDLE_WETNESS)).clamp(0.0, 1.0);
+    if fill <= 0.0 {
+        if scan.center.take().is_some() {
+            for (entity, _, _) in puddle_query.iter() {
+                commands.entity(entity).despawn();
+            }
+        }
+        return;
+    }
+
+    for (_, puddle, mut transform) in puddle_query.iter_mut() {
+        transform.scale = Vec3::new(puddle.radius * fill, 1.0, puddle.radius * fill);
+    }
+
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let center = camera.translation.xz();
+    if scan.center.is_some_and(|last| last.distance(center) < PUDDLE_RESCAN_DISTANCE) {
+        return;
+    }
+    scan.center = Some(center);
+
+    // Keep the puddles still in range, look for new ones on the grid
+    let mut existing = Vec::new();
+    for (entity, _, transform) in puddle_query.iter() {
+        if transform.translation.xz().distance(center) > PUDDLE_SCAN_RADIUS {
+            commands.entity(entity).despawn();
+        } else {
+            existing.push(transform.translation.xz());
+        }
+    }
+    let origin = (center / PUDDLE_GRID).floor() * PUDDLE_GRID;
+    let cells = (PUDDLE_SCAN_RADIUS / PUDDLE_GRID) as i32;
+    for gz in -cells..=cells {
+        for gx in -cells..=cells {
+            let point = origin + Vec2::new(gx as f32, gz as f32) * PUDDLE_GRID;
+            if point.distance(center) > PUDDLE_SCAN_RADIUS || existing.iter().any(|other| other.distance(point) < PUDDLE_GRID) {
+                continue;
+            }
+            let Some(height) = puddle_spot(&terrain_edits, point.x, point.y) else {
+                continue;
+            };
+            let seed = (point.x as i32 as u32 as u64) << 32 | point.y as i32 as u32 as u64;
+            let radius = PUDDLE_RADIUS.0.lerp(PUDDLE_RADIUS.1, unit_random(seed, 0));
+            commands.spawn((
+                Mesh3d(assets.puddle_mesh.clone()),
+                MeshMaterial3d(assets.puddle_material.clone()),
+                Transform::from_xyz(point.x, height + PUDDLE_LIFT, point.y)
+                    .with_scale(Vec3::new(radius * fill, 1.0, radius * fill)),
+                Puddle { radius },
+            ));
+        }
+    }
+}
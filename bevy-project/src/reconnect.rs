@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::net::{ServerConnection, ServerEvent, ServerMessage};
+
+// Seconds before the first attempt, doubled after each failed one up to RETRY_MAX_DELAY
+const RETRY_FIRST_DELAY: f32 = 1.0;
+const RETRY_MAX_DELAY: f32 = 16.0;
+// A new link the server hasn't answered on by then counts as a failed attempt
+const JOIN_TIMEOUT: f32 = 5.0;
+// Seconds the outcome stays on screen once connected again
+const RESULT_SECONDS: f32 = 4.0;
+const OVERLAY_BACKDROP: egui::Color32 = egui::Color32::from_black_alpha(200);
+
+// Reconnects to the server when the connection drops, retrying with backoff while the world
+// stays loaded and playable. The server holds the player for a while, so coming back in time
+// resumes the session where it was.
+#[derive(Default, Clone, Debug)]
+pub struct ReconnectPlugin;
+
+impl Plugin for ReconnectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Reconnect>()
+            .add_systems(Update, (
+                watch_connection,
+                retry_connection,
+                reconnect_overlay,
+            ).chain());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkState {
+    Connected,
+    // Seconds left before attempt number `attempt`, counted from 0
+    Waiting { attempt: u32, wait: f32 },
+    // A new link is open, waiting for the server to take the player back
+    Joining { attempt: u32, elapsed: f32 },
+}
+
+#[derive(Resource, Debug)]
+pub struct Reconnect {
+    pub state: LinkState,
+    // Seconds since the connection was lost
+    pub offline: f32,
+    // Whether the last reconnection resumed the session, and seconds it has been shown
+    result: Option<(bool, f32)>,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Self { state: LinkState::Connected, offline: 0.0, result: None }
+    }
+}
+
+pub fn retry_delay(attempt: u32) -> f32 {
+    (RETRY_FIRST_DELAY * 2f32.powi(attempt.min(16) as i32)).min(RETRY_MAX_DELAY)
+}
+
+fn watch_connection(
+    mut server_events: EventReader<ServerEvent>,
+    connection: Res<ServerConnection>,
+    mut reconnect: ResMut<Reconnect>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::Joined { resumed } = message
+            && reconnect.state != LinkState::Connected
+        {
+            info!("Reconnected after {:.1} s, session {}", reconnect.offline, if *resumed { "resumed" } else { "started again" });
+            reconnect.state = LinkState::Connected;
+            reconnect.result = Some((*resumed, 0.0));
+        }
+    }
+    if reconnect.state == LinkState::Connected && !connection.is_connected() && connection.can_reconnect() {
+        warn!("Connection lost, reconnecting");
+        reconnect.state = LinkState::Waiting { attempt: 0, wait: retry_delay(0) };
+        reconnect.offline = 0.0;
+        reconnect.result = None;
+    }
+}
+
+// Real time, the game may be paused meanwhile
+fn retry_connection(
+    time: Res<Time<Real>>,
+    mut connection: ResMut<ServerConnection>,
+    mut reconnect: ResMut<Reconnect>,
+) {
+    let dt = time.delta_secs();
+    if let Some((_, shown)) = &mut reconnect.result {
+        *shown += dt;
+        if *shown > RESULT_SECONDS {
+            reconnect.result = None;
+        }
+    }
+    if reconnect.state == LinkState::Connected {
+        return;
+    }
+    reconnect.offline += dt;
+    reconnect.state = match reconnect.state {
+        LinkState::Waiting { attempt, wait } if wait > dt => LinkState::Waiting { attempt, wait: wait - dt },
+        LinkState::Waiting { attempt, .. } => match connection.reconnect() {
+            Ok(()) => LinkState::Joining { attempt, elapsed: 0.0 },
+            Err(err) => {
+                warn!("Reconnection attempt {} failed: {}", attempt + 1, err);
+                LinkState::Waiting { attempt: attempt + 1, wait: retry_delay(attempt + 1) }
+            }
+        },
+        LinkState::Joining { attempt, elapsed } if connection.is_connected() && elapsed + dt <= JOIN_TIMEOUT => {
+            LinkState::Joining { attempt, elapsed: elapsed + dt }
+        }
+        LinkState::Joining { attempt, .. } => {
+            warn!("Reconnection attempt {} got no answer from the server", attempt + 1);
+            connection.drop_link();
+            LinkState::Waiting { attempt: attempt + 1, wait: retry_delay(attempt + 1) }
+        }
+        LinkState::Connected => LinkState::Connected,
+    };
+}
+
+fn reconnect_overlay(
+    mut contexts: EguiContexts,
+    mut reconnect: ResMut<Reconnect>,
+) {
+    let (title, status) = match (reconnect.state, reconnect.result) {
+        (LinkState::Waiting { attempt, wait }, _) => (
+            "Connection to the server lost",
+            format!("Reconnecting in {:.0} s, attempt {}", wait.ceil(), attempt + 1),
+        ),
+        (LinkState::Joining { attempt, .. }, _) => (
+            "Connection to the server lost",
+            format!("Reconnecting, attempt {}", attempt + 1),
+        ),
+        (LinkState::Connected, Some((true, _))) => ("Reconnected", "Session resumed".to_string()),
+        (LinkState::Connected, Some((false, _))) => ("Reconnected", "The server had let you go, joined again".to_string()),
+        (LinkState::Connected, None) => return,
+    };
+    let offline = reconnect.offline;
+    let mut retry_now = false;
+    egui::Area::new(egui::Id::new("reconnect"))
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 80.0])
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::NONE
+                .fill(OVERLAY_BACKDROP)
+                .corner_radius(6.0)
+                .inner_margin(12.0)
+                .show(ui, |ui| {
+                    ui.vertical_centered(|ui| {
+                        ui.label(egui::RichText::new(title).size(20.0).color(egui::Color32::WHITE));
+                        ui.label(egui::RichText::new(status).color(egui::Color32::LIGHT_GRAY));
+                        if matches!(reconnect.state, LinkState::Connected) {
+                            return;
+                        }
+                        ui.label(egui::RichText::new(format!("Offline for {:.0} s, the world keeps going", offline)).weak());
+                        let waiting = matches!(reconnect.state, LinkState::Waiting { .. });
+                        retry_now = ui.add_enabled(waiting, egui::Button::new("Retry now")).clicked();
+                    });
+                });
+        });
+    if retry_now && let LinkState::Waiting { attempt, .. } = reconnect.state {
+        reconnect.state = LinkState::Waiting { attempt, wait: 0.0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_back_off_up_to_a_limit() {
+        assert_eq!(retry_delay(0), 1.0);
+        assert_eq!(retry_delay(1), 2.0);
+        assert_eq!(retry_delay(3), 8.0);
+        assert_eq!(retry_delay(10), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), RETRY_MAX_DELAY);
+    }
+}
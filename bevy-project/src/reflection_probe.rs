@@ -0,0 +1,292 @@
+use bevy::{
+    pbr::{environment_map::EnvironmentMapLight, LightProbe},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureViewDescriptor, TextureViewDimension},
+    },
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use crate::camera::FreeCamera;
+use crate::day_night::TimeOfDay;
+use crate::settings::GameSettings;
+use crate::terrain;
+use crate::water::sea_level;
+
+// Cubemap sampled by the water shader and lighting the terrain around the camera
+pub const REFLECTION_MAP: Handle<Image> = Handle::weak_from_u128(0x5f1c_23a8_9be4_4d07_8a6e_1b9c_40d2_e713);
+// Region the probe lights, centered on the camera at sea level
+const PROBE_SIZE: Vec3 = Vec3::new(600.0, 200.0, 600.0);
+// Moving this far from the probe's center moves it and rescans the horizon
+const RECENTER_DISTANCE: f32 = 150.0;
+// Directions the horizon is scanned in, and how far
+const HORIZON_DIRECTIONS: usize = 64;
+const HORIZON_RANGE: f32 = 800.0;
+// Re-render when the sun moved more than this many degrees, or the daylight changed this much
+const SUN_STEP_DEGREES: f32 = 3.0;
+const DAYLIGHT_STEP: f32 = 0.05;
+const ENVIRONMENT_INTENSITY: f32 = 600.0;
+
+const DAY_ZENITH: Vec3 = Vec3::new(0.25, 0.45, 0.8);
+const DAY_HORIZON: Vec3 = Vec3::new(0.65, 0.75, 0.88);
+const NIGHT_ZENITH: Vec3 = Vec3::new(0.01, 0.015, 0.04);
+const NIGHT_HORIZON: Vec3 = Vec3::new(0.03, 0.04, 0.07);
+const DUSK_HORIZON: Vec3 = Vec3::new(0.9, 0.5, 0.3);
+const SUN_GLOW: Vec3 = Vec3::new(1.0, 0.9, 0.7);
+const MOUNTAIN_COLOR: Vec3 = Vec3::new(0.28, 0.3, 0.26);
+const SEA_COLOR: Vec3 = Vec3::new(0.05, 0.12, 0.18);
+// Rings of directions the sky is averaged over for its light, and directions per ring
+const IRRADIANCE_RINGS: usize = 4;
+const IRRADIANCE_AZIMUTHS: usize = 12;
+
+// Reflections without rendering the scene again: a small cubemap generated on the CPU
+// from a sky model and the terrain's silhouette around the camera. The water reflects it
+// and wet or glossy ground within the probe picks it up as environment lighting. It is
+// regenerated as the sun moves and when the camera travels far enough.
+#[derive(Default, Clone, Debug)]
+pub struct ReflectionProbePlugin;
+
+impl Plugin for ReflectionProbePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ReflectionProbeState>()
+            .add_systems(PreStartup, spawn_reflection_probe)
+            .add_systems(Update, (
+                scan_horizon,
+                render_reflection_map,
+            ).chain());
+    }
+}
+
+#[derive(Component)]
+struct ReflectionProbe;
+
+// Terrain silhouette seen from the probe's center: per direction around it, the highest
+// elevation angle (as a slope) of the terrain and how far away that ridge is
+#[derive(Debug, Clone, Default)]
+struct Horizon {
+    slopes: Vec<f32>,
+    distances: Vec<f32>,
+}
+
+impl Horizon {
+    fn scan(center: Vec2, eye_height: f32) -> Self {
+        let mut horizon = Self::default();
+        for index in 0..HORIZON_DIRECTIONS {
+            let angle = index as f32 / HORIZON_DIRECTIONS as f32 * std::f32::consts::TAU;
+            let direction = Vec2::new(angle.cos(), angle.sin());
+            let (mut slope, mut ridge) = (f32::MIN, HORIZON_RANGE);
+            // Steps grow with the distance, far terrain only matters for its big shapes
+            let mut distance = 4.0;
+            while distance < HORIZON_RANGE {
+                let point = center + direction * distance;
+                let point_slope = (terrain::height(point.x, point.y) - eye_height) / distance;
+                if point_slope > slope {
+                    slope = point_slope;
+                    ridge = distance;
+                }
+                distance *= 1.08;
+            }
+            horizon.slopes.push(slope);
+            horizon.distances.push(ridge);
+        }
+        horizon
+    }
+
+    // Highest terrain slope and its distance in a horizontal direction, between scanned ones
+    fn at(&self, direction: Vec2) -> (f32, f32) {
+        if self.slopes.is_empty() {
+            return (f32::MIN, HORIZON_RANGE);
+        }
+        let position = direction.y.atan2(direction.x).rem_euclid(std::f32::consts::TAU)
+            / std::f32::consts::TAU * HORIZON_DIRECTIONS as f32;
+        let (index, t) = (position.floor() as usize % HORIZON_DIRECTIONS, position.fract());
+        let next = (index + 1) % HORIZON_DIRECTIONS;
+        (self.slopes[index].lerp(self.slopes[next], t), self.distances[index].lerp(self.distances[next], t))
+    }
+}
+
+#[derive(Resource, Default)]
+struct ReflectionProbeState {
+    center: Option<Vec2>,
+    horizon: Horizon,
+    scanning: Option<Task<(Vec2, Horizon)>>,
+    // What the current map was rendered for
+    rendered: Option<RenderedSky>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RenderedSky {
+    sun: Vec3,
+    daylight: f32,
+    size: u32,
+}
+
+fn spawn_reflection_probe(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    settings: Res<GameSettings>,
+) {
+    // Plain sky until the first real map is rendered, the water needs the image to draw
+    let size = settings.graphics.quality.reflection_probe_size();
+    images.insert(REFLECTION_MAP.id(), cubemap(size, |_| DAY_HORIZON));
+    commands.spawn((
+        LightProbe,
+        EnvironmentMapLight {
+            diffuse_map: REFLECTION_MAP,
+            specular_map: REFLECTION_MAP,
+            intensity: ENVIRONMENT_INTENSITY,
+            ..default()
+        },
+        Transform::from_scale(PROBE_SIZE),
+        ReflectionProbe,
+    ));
+}
+
+// Scan the horizon in the background when the camera leaves the probe's region, then
+// move the probe there
+fn scan_horizon(
+    mut state: ResMut<ReflectionProbeState>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut probe_query: Query<&mut Transform, (With<ReflectionProbe>, Without<FreeCamera>)>,
+) {
+    if let Some(task) = state.scanning.as_mut() {
+        let Some((center, horizon)) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        state.scanning = None;
+        state.center = Some(center);
+        state.horizon = horizon;
+        state.rendered = None;
+        for mut transform in probe_query.iter_mut() {
+            transform.translation = Vec3::new(center.x, sea_level(center.x, center.y), center.y);
+        }
+        return;
+    }
+
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let center = camera.translation.xz();
+    if state.center.is_some_and(|last| last.distance(center) < RECENTER_DISTANCE) {
+        return;
+    }
+    // Seen from the water surface, where the reflections are
+    let eye_height = sea_level(center.x, center.y) + 1.0;
+    state.scanning = Some(AsyncComputeTaskPool::get().spawn(async move {
+        (center, Horizon::scan(center, eye_height))
+    }));
+}
+
+fn render_reflection_map(
+    time_of_day: Res<TimeOfDay>,
+    settings: Res<GameSettings>,
+    mut state: ResMut<ReflectionProbeState>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let sky = RenderedSky {
+        sun: time_of_day.sun_direction(),
+        daylight: time_of_day.daylight(),
+        size: settings.graphics.quality.reflection_probe_size(),
+    };
+    if state.center.is_none() {
+        return;
+    }
+    if let Some(rendered) = state.rendered
+        && rendered.size == sky.size
+        && rendered.sun.angle_between(sky.sun) < SUN_STEP_DEGREES.to_radians()
+        && (rendered.daylight - sky.daylight).abs() < DAYLIGHT_STEP
+    {
+        return;
+    }
+
+    let _span = info_span!("render_reflection_map", size = sky.size).entered();
+    let horizon = &state.horizon;
+    images.insert(REFLECTION_MAP.id(), cubemap(sky.size, |direction| environment_color(direction, &sky, horizon)));
+    state.rendered = Some(sky);
+}
+
+fn sky_color(direction: Vec3, sky: &RenderedSky) -> Vec3 {
+    let zenith = NIGHT_ZENITH.lerp(DAY_ZENITH, sky.daylight);
+    let mut horizon = NIGHT_HORIZON.lerp(DAY_HORIZON, sky.daylight);
+    // Warm horizon toward a low sun
+    let dusk = (1.0 - sky.sun.y.abs() * 4.0).clamp(0.0, 1.0);
+    let facing_sun = direction.xz().normalize_or_zero().dot(sky.sun.xz().normalize_or_zero()).max(0.0);
+    horizon = horizon.lerp(DUSK_HORIZON, dusk * facing_sun);
+    let color = horizon.lerp(zenith, direction.y.max(0.0).sqrt());
+    color + SUN_GLOW * direction.dot(sky.sun).max(0.0).powf(64.0) * sky.daylight.max(dusk * 0.5)
+}
+
+// Light the whole sky sheds on ground facing straight up: the sky model averaged over the
+// upper hemisphere, weighted toward the zenith like a cosine lobe
+pub fn sky_irradiance(sun: Vec3, daylight: f32) -> Vec3 {
+    let sky = RenderedSky { sun, daylight, size: 0 };
+    let (mut total, mut weights) = (Vec3::ZERO, 0.0);
+    for ring in 0..IRRADIANCE_RINGS {
+        let elevation = (ring as f32 + 0.5) / IRRADIANCE_RINGS as f32 * std::f32::consts::FRAC_PI_2;
+        let weight = elevation.sin() * elevation.cos();
+        for index in 0..IRRADIANCE_AZIMUTHS {
+            let azimuth = index as f32 / IRRADIANCE_AZIMUTHS as f32 * std::f32::consts::TAU;
+            let direction = Vec3::new(elevation.cos() * azimuth.cos(), elevation.sin(), elevation.cos() * azimuth.sin());
+            total += sky_color(direction, &sky) * weight;
+            weights += weight;
+        }
+    }
+    total / weights
+}
+
+// What a reflected ray sees: sky, the mountains around, or the sea below the horizon
+fn environment_color(direction: Vec3, sky: &RenderedSky, horizon: &Horizon) -> Vec3 {
+    let horizontal = direction.xz();
+    let slope = direction.y / horizontal.length().max(0.0001);
+    let (ridge_slope, ridge_distance) = horizon.at(horizontal.normalize_or_zero());
+    if slope < ridge_slope && ridge_slope > 0.0 {
+        // Distant mountains fade into the sky's haze
+        let haze = (ridge_distance / HORIZON_RANGE).powf(0.7);
+        let lit = MOUNTAIN_COLOR * (0.1 + 0.9 * sky.daylight);
+        return lit.lerp(sky_color(Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero(), sky), haze);
+    }
+    if direction.y < 0.0 {
+        return SEA_COLOR * (0.2 + 0.8 * sky.daylight);
+    }
+    sky_color(direction, sky)
+}
+
+// Cube texture filled face by face, with the color of each texel's world direction
+fn cubemap(size: u32, color: impl Fn(Vec3) -> Vec3) -> Image {
+    let mut data = Vec::with_capacity((size * size * 6 * 4) as usize);
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                // Faces in +X, -X, +Y, -Y, +Z, -Z order
+                let cube = match face {
+                    0 => Vec3::new(1.0, -v, -u),
+                    1 => Vec3::new(-1.0, -v, u),
+                    2 => Vec3::new(u, 1.0, v),
+                    3 => Vec3::new(u, -1.0, -v),
+                    4 => Vec3::new(u, -v, 1.0),
+                    _ => Vec3::new(-u, -v, -1.0),
+                };
+                // Cubemaps are left-handed, world z is flipped
+                let direction = Vec3::new(cube.x, cube.y, -cube.z).normalize();
+                let rgb = color(direction).min(Vec3::ONE);
+                data.extend_from_slice(&Srgba::from(LinearRgba::rgb(rgb.x, rgb.y, rgb.z)).to_u8_array());
+            }
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d { width: size, height: size, depth_or_array_layers: 6 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..default()
+    });
+    image
+}
@@ -0,0 +1,161 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::net::{ServerEvent, ServerMessage};
+use crate::terrain;
+use crate::terrain_edit::{TerrainEdit, TerrainEdits, EDIT_RADIUS, EDIT_REACH};
+
+// Boundaries are drawn when the edit cursor comes this close to a region
+const BOUNDARY_SHOW_DISTANCE: f32 = 25.0;
+const BOUNDARY_SEGMENTS: usize = 64;
+// Lift above the ground so the boundary isn't hidden in the terrain
+const BOUNDARY_LIFT: f32 = 0.3;
+const SPAWN_PROTECTION_RADIUS: f32 = 12.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct RegionPlugin;
+
+impl Plugin for RegionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ProtectedRegions>()
+            .add_systems(Update, (receive_regions, draw_region_boundaries).chain());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RegionShape {
+    // Axis aligned box between two corners
+    Box { min: Vec3, max: Vec3 },
+    Sphere { center: Vec3, radius: f32 },
+}
+
+// Part of the world where some actions are forbidden for everyone, stored with the world
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProtectedRegion {
+    pub name: String,
+    pub shape: RegionShape,
+    pub no_edit: bool,
+    pub no_damage: bool,
+}
+
+impl ProtectedRegion {
+    pub fn contains(&self, point: Vec3) -> bool {
+        match self.shape {
+            RegionShape::Box { min, max } => point.cmpge(min).all() && point.cmple(max).all(),
+            RegionShape::Sphere { center, radius } => center.distance_squared(point) <= radius * radius,
+        }
+    }
+
+    // Horizontal distance from a point to the region, zero inside.
+    // Terrain edits change whole columns so the height is ignored.
+    pub fn ground_distance(&self, point: Vec2) -> f32 {
+        match self.shape {
+            RegionShape::Box { min, max } => point.distance(point.clamp(min.xz(), max.xz())),
+            RegionShape::Sphere { center, radius } => (center.xz().distance(point) - radius).max(0.0),
+        }
+    }
+
+    pub fn blocks_edit(&self, edit: &TerrainEdit) -> bool {
+        self.no_edit && self.ground_distance(edit.center) < edit.radius
+    }
+
+    // Outline of the region on the ground
+    fn boundary(&self) -> Vec<Vec2> {
+        match self.shape {
+            RegionShape::Box { min, max } => {
+                let sides = [
+                    (Vec2::new(min.x, min.z), Vec2::new(max.x, min.z)),
+                    (Vec2::new(max.x, min.z), Vec2::new(max.x, max.z)),
+                    (Vec2::new(max.x, max.z), Vec2::new(min.x, max.z)),
+                    (Vec2::new(min.x, max.z), Vec2::new(min.x, min.z)),
+                ];
+                let steps = BOUNDARY_SEGMENTS / 4;
+                let mut points: Vec<Vec2> = sides.iter()
+                    .flat_map(|(from, to)| (0..steps).map(move |i| from.lerp(*to, i as f32 / steps as f32)))
+                    .collect();
+                points.push(points[0]);
+                points
+            }
+            RegionShape::Sphere { center, radius } => (0..=BOUNDARY_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / BOUNDARY_SEGMENTS as f32 * std::f32::consts::TAU;
+                    center.xz() + Vec2::from_angle(angle) * radius
+                })
+                .collect(),
+        }
+    }
+}
+
+// Regions of a new world: the area around the world spawn
+pub fn default_regions() -> Vec<ProtectedRegion> {
+    vec![ProtectedRegion {
+        name: "Spawn".to_string(),
+        shape: RegionShape::Sphere { center: Vec3::ZERO, radius: SPAWN_PROTECTION_RADIUS },
+        no_edit: true,
+        no_damage: true,
+    }]
+}
+
+// Regions as last sent by the server
+#[derive(Resource, Default)]
+pub struct ProtectedRegions(pub Vec<ProtectedRegion>);
+
+impl ProtectedRegions {
+    pub fn no_damage_at(&self, point: Vec3) -> bool {
+        self.0.iter().any(|region| region.no_damage && region.contains(point))
+    }
+}
+
+fn receive_regions(
+    mut server_events: EventReader<ServerEvent>,
+    mut regions: ResMut<ProtectedRegions>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::Regions { regions: received, .. } = message {
+            info!("Received {} protected regions", received.len());
+            regions.0 = received.clone();
+        }
+    }
+}
+
+// While editing terrain, outline protected regions near the edit cursor.
+// Red when the next edit would be refused.
+fn draw_region_boundaries(
+    mut gizmos: Gizmos,
+    regions: Res<ProtectedRegions>,
+    camera_settings: Res<CameraSettings>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    terrain_edits: Res<TerrainEdits>,
+) {
+    if camera_settings.camera_mode != CameraMode::Free || regions.0.is_empty() {
+        return;
+    }
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let Some(hit) = terrain::raycast(
+        camera_transform.translation,
+        *camera_transform.forward(),
+        EDIT_REACH,
+        |x, z| terrain_edits.height(x, z),
+    ) else {
+        return;
+    };
+
+    let cursor = hit.xz();
+    let edit = TerrainEdit { center: cursor, radius: EDIT_RADIUS, delta: 0.0 };
+    for region in regions.0.iter().filter(|region| region.no_edit) {
+        if region.ground_distance(cursor) > BOUNDARY_SHOW_DISTANCE {
+            continue;
+        }
+        let color = if region.blocks_edit(&edit) {
+            Color::srgb(1.0, 0.2, 0.15)
+        } else {
+            Color::srgb(1.0, 0.85, 0.2)
+        };
+        let points = region.boundary().into_iter()
+            .map(|point| Vec3::new(point.x, terrain_edits.height(point.x, point.y) + BOUNDARY_LIFT, point.y));
+        gizmos.linestrip(points, color);
+    }
+}
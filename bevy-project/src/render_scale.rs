@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::render_resource::Extent3d;
+use bevy::render::view::RenderLayers;
+use bevy::window::PrimaryWindow;
+use crate::camera::{render_target_image, FreeCamera};
+use crate::settings::GameSettings;
+
+pub const MIN_RENDER_SCALE: f32 = 0.7;
+pub const MAX_RENDER_SCALE: f32 = 1.0;
+const SCALE_STEP: f32 = 0.05;
+// Seconds between two scale changes, so the frame time can settle
+const ADJUST_INTERVAL: f32 = 0.5;
+// Weight of the newest frame in the smoothed frame time
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+// Lower the scale above this fraction of the frame budget, raise it below the other
+const DOWNSCALE_THRESHOLD: f32 = 1.05;
+const UPSCALE_THRESHOLD: f32 = 0.85;
+// Keeps the upscaling sprite away from the world cameras and gizmos
+const DISPLAY_LAYER: usize = 1;
+
+#[derive(Default, Clone, Debug)]
+pub struct RenderScalePlugin;
+
+impl Plugin for RenderScalePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, setup_render_scale)
+            .add_systems(Update, (
+                adjust_render_scale,
+                apply_render_scale,
+            ).chain());
+    }
+}
+
+// While dynamic resolution is on, the world camera renders into `target` at `scale` times
+// the window resolution, and a 2D camera stretches it over the window
+#[derive(Resource)]
+pub struct RenderScale {
+    pub scale: f32,
+    target: Handle<Image>,
+    display_camera: Entity,
+    display_sprite: Entity,
+    frame_time: f32,
+    since_adjust: f32,
+}
+
+fn setup_render_scale(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let target = images.add(render_target_image(UVec2::ONE));
+
+    let display_camera = commands.spawn((
+        Camera2d,
+        Camera {
+            order: 1,
+            is_active: false,
+            ..default()
+        },
+        RenderLayers::layer(DISPLAY_LAYER),
+    )).id();
+    let display_sprite = commands.spawn((
+        Sprite::from_image(target.clone()),
+        RenderLayers::layer(DISPLAY_LAYER),
+        Visibility::Hidden,
+    )).id();
+
+    commands.insert_resource(RenderScale {
+        scale: MAX_RENDER_SCALE,
+        target,
+        display_camera,
+        display_sprite,
+        frame_time: 0.0,
+        since_adjust: 0.0,
+    });
+}
+
+// Step the scale down when frames take longer than the target, back up when there is headroom
+fn adjust_render_scale(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    mut render_scale: ResMut<RenderScale>,
+) {
+    let graphics = &settings.graphics;
+    if !graphics.dynamic_resolution {
+        if render_scale.scale != MAX_RENDER_SCALE {
+            render_scale.scale = MAX_RENDER_SCALE;
+        }
+        return;
+    }
+
+    let render_scale = render_scale.bypass_change_detection();
+    let delta = time.delta_secs();
+    render_scale.frame_time = render_scale.frame_time.lerp(delta, FRAME_TIME_SMOOTHING);
+    render_scale.since_adjust += delta;
+    if render_scale.since_adjust < ADJUST_INTERVAL {
+        return;
+    }
+    render_scale.since_adjust = 0.0;
+
+    let budget = 1.0 / graphics.target_fps.max(1) as f32;
+    let load = render_scale.frame_time / budget;
+    let scale = if load > DOWNSCALE_THRESHOLD {
+        render_scale.scale - SCALE_STEP
+    } else if load < UPSCALE_THRESHOLD {
+        render_scale.scale + SCALE_STEP
+    } else {
+        return;
+    };
+    let scale = scale.clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE);
+    if scale != render_scale.scale {
+        render_scale.scale = scale;
+        debug!("Render scale {:.2} (frame time {:.1} ms)", scale, render_scale.frame_time * 1000.0);
+    }
+}
+
+// Point the world camera at the scaled image or straight at the window, and keep the image
+// sized to the window
+fn apply_render_scale(
+    settings: Res<GameSettings>,
+    render_scale: Res<RenderScale>,
+    mut images: ResMut<Assets<Image>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut world_camera_query: Query<&mut Camera, With<FreeCamera>>,
+    mut display_camera_query: Query<&mut Camera, Without<FreeCamera>>,
+    mut sprite_query: Query<(&mut Sprite, &mut Visibility)>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok(mut world_camera) = world_camera_query.get_single_mut() else {
+        return;
+    };
+    let enabled = settings.graphics.dynamic_resolution;
+
+    if enabled {
+        let size = (window.physical_size().as_vec2() * render_scale.scale).round().as_uvec2().max(UVec2::ONE);
+        if let Some(image) = images.get(&render_scale.target)
+            && image.size() != size
+            && let Some(image) = images.get_mut(&render_scale.target)
+        {
+            image.resize(Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 });
+        }
+        if let Ok((mut sprite, _)) = sprite_query.get_mut(render_scale.display_sprite)
+            && sprite.custom_size != Some(window.size())
+        {
+            sprite.custom_size = Some(window.size());
+        }
+    }
+
+    let rendering_to_image = matches!(world_camera.target, RenderTarget::Image(_));
+    if rendering_to_image != enabled {
+        world_camera.target = if enabled {
+            RenderTarget::Image(render_scale.target.clone())
+        } else {
+            RenderTarget::default()
+        };
+        if let Ok(mut display_camera) = display_camera_query.get_mut(render_scale.display_camera) {
+            display_camera.is_active = enabled;
+        }
+        if let Ok((_, mut visibility)) = sprite_query.get_mut(render_scale.display_sprite) {
+            *visibility = if enabled { Visibility::Inherited } else { Visibility::Hidden };
+        }
+    }
+}
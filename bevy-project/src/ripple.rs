@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use crate::player::{Player, PLAYER_HALF_HEIGHT};
+use crate::projectile::{Projectile, ProjectileKind};
+use crate::water::{WaterQuery, WaterRipples};
+
+// Going through the surface slower than this, like floating on the waves, leaves no ring
+const SPLASH_MIN_SPEED: f32 = 1.0;
+// Vertical speed making the strongest splash
+const SPLASH_FULL_SPEED: f32 = 8.0;
+// Wading or swimming faster than this leaves weaker rings behind every WAKE_INTERVAL seconds
+const WAKE_MIN_SPEED: f32 = 0.8;
+const WAKE_INTERVAL: f32 = 0.35;
+const WAKE_STRENGTH: f32 = 0.35;
+
+// Rings spreading on the water where the player and projectiles go through its surface, or
+// where the player wades, written to the water material for its shader to draw
+#[derive(Default, Clone, Debug)]
+pub struct RipplePlugin;
+
+impl Plugin for RipplePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (add_ripple_emitters, emit_ripples).chain());
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct RippleEmitter {
+    pub strength: f32,
+    // From the origin down to the lowest point, which makes the splash, and as much above it.
+    // Wakes are left while the surface is between the two.
+    pub half_height: f32,
+    last_position: Option<Vec3>,
+    wake_timer: f32,
+}
+
+impl RippleEmitter {
+    pub fn new(strength: f32, half_height: f32) -> Self {
+        Self { strength, half_height, last_position: None, wake_timer: 0.0 }
+    }
+}
+
+// Splash strength for going through the surface at a vertical speed, None when too slow
+pub fn splash_strength(strength: f32, vertical_speed: f32) -> Option<f32> {
+    let speed = vertical_speed.abs();
+    (speed >= SPLASH_MIN_SPEED).then(|| strength * (speed / SPLASH_FULL_SPEED).clamp(0.3, 1.0))
+}
+
+fn add_ripple_emitters(
+    mut commands: Commands,
+    player_query: Query<Entity, Added<Player>>,
+    projectile_query: Query<(Entity, &Projectile), Added<Projectile>>,
+) {
+    for entity in player_query.iter() {
+        commands.entity(entity).insert(RippleEmitter::new(1.0, PLAYER_HALF_HEIGHT));
+    }
+    for (entity, projectile) in projectile_query.iter() {
+        let strength = match projectile.kind {
+            ProjectileKind::Rock => 0.6,
+            ProjectileKind::Bobber => 0.3,
+        };
+        commands.entity(entity).insert(RippleEmitter::new(strength, 0.0));
+    }
+}
+
+fn emit_ripples(
+    time: Res<Time>,
+    water: WaterQuery,
+    mut ripples: ResMut<WaterRipples>,
+    mut emitter_query: Query<(&GlobalTransform, &mut RippleEmitter)>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    let now = time.elapsed_secs();
+    for (transform, mut emitter) in emitter_query.iter_mut() {
+        let position = transform.translation();
+        let Some(last) = emitter.last_position.replace(position) else {
+            continue;
+        };
+        let Some(surface) = water.surface_height(position.xz()) else {
+            continue;
+        };
+        let (bottom, last_bottom) = (position.y - emitter.half_height, last.y - emitter.half_height);
+        if (bottom < surface) != (last_bottom < surface) {
+            if let Some(strength) = splash_strength(emitter.strength, (position.y - last.y) / dt) {
+                ripples.push(position.xz(), now, strength);
+            }
+            continue;
+        }
+
+        let wading = bottom < surface && position.y + emitter.half_height > surface;
+        let speed = (position.xz() - last.xz()).length() / dt;
+        emitter.wake_timer -= dt;
+        if wading && speed > WAKE_MIN_SPEED && emitter.wake_timer <= 0.0 {
+            emitter.wake_timer = WAKE_INTERVAL;
+            ripples.push(position.xz(), now, emitter.strength * WAKE_STRENGTH);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ripples_replace_the_oldest() {
+        let mut ripples = WaterRipples::default();
+        for i in 0..crate::water::MAX_RIPPLES {
+            ripples.push(Vec2::ZERO, 1.0 + i as f32, 1.0);
+        }
+        assert!(ripples.ripples.iter().all(|ripple| ripple.strength > 0.0));
+        ripples.push(Vec2::ONE, 100.0, 0.5);
+        assert!(ripples.ripples.iter().all(|ripple| ripple.start_time != 1.0));
+        assert!(ripples.ripples.iter().any(|ripple| ripple.center == Vec2::ONE));
+
+        assert_eq!(splash_strength(1.0, 0.2), None);
+        assert_eq!(splash_strength(1.0, -20.0), Some(1.0));
+    }
+}
@@ -0,0 +1,261 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::client::CHUNK_SIZE;
+use crate::streaming::mesh_chunk_coords;
+use crate::terrain;
+use crate::water::sea_level;
+
+pub const ROAD_WIDTH: f32 = 3.0;
+// Beyond the road's edge the ground blends back to the natural terrain over this distance
+const SHOULDER: f32 = 2.5;
+const DIRT_COLOR: [f32; 4] = [0.45, 0.36, 0.24, 1.0];
+// Routes are searched on a grid of 4 m cells, giving up after expanding MAX_ROUTE_CELLS
+const ROUTE_CELL: f32 = 4.0;
+const MAX_ROUTE_CELLS: usize = 60_000;
+// Extra cost of a step per unit of grade squared, so routes follow the contour lines
+const GRADE_COST: f32 = 40.0;
+// Steeper steps are never taken
+const MAX_GRADE: f32 = 0.6;
+// Shallows are crossed on a causeway this high above the sea, at WADE_COST times the cost;
+// deeper water is never crossed
+const CAUSEWAY_HEIGHT: f32 = 0.4;
+const WADE_COST: f32 = 4.0;
+const MAX_FORD_DEPTH: f32 = 2.5;
+// Road heights are averaged over this many points on each side, twice, for a gentle grade
+const SMOOTHING_POINTS: usize = 3;
+// Built pieces within this distance of each other form one site, sites this close get a road
+const SITE_RADIUS: f32 = 24.0;
+pub const LINK_DISTANCE: f32 = 250.0;
+
+// A dirt path carved into the terrain: the ground along the center line is leveled to the
+// road's heights and painted, fading back to the natural terrain past the edges.
+// Part of the world data, the server sends it to every client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Road {
+    // Center line, with the height of the road surface at each point
+    pub points: Vec<Vec3>,
+    pub width: f32,
+}
+
+impl Road {
+    // Horizontal distance to the center line and the road height at the nearest point
+    fn nearest(&self, point: Vec2) -> Option<(f32, f32)> {
+        self.points
+            .windows(2)
+            .map(|pair| {
+                let (a, b) = (pair[0], pair[1]);
+                let segment = b.xz() - a.xz();
+                let length_squared = segment.length_squared();
+                let t = if length_squared > 0.0 {
+                    ((point - a.xz()).dot(segment) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                ((a.xz() + segment * t).distance(point), a.y + (b.y - a.y) * t)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    // 1 on the road, easing to 0 across the shoulder
+    fn weight(&self, distance: f32) -> f32 {
+        let t = ((distance - self.width / 2.0) / SHOULDER).clamp(0.0, 1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    }
+
+    // Whether the road runs between two points, either way
+    pub fn joins(&self, a: Vec2, b: Vec2) -> bool {
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return false;
+        };
+        let near = |point: &Vec3, end: Vec2| point.xz().distance(end) < ROAD_WIDTH * 2.0;
+        (near(first, a) && near(last, b)) || (near(first, b) && near(last, a))
+    }
+
+    // Ground the road changes, shoulders included
+    pub fn bounds(&self) -> Rect {
+        let Some(first) = self.points.first() else {
+            return Rect::default();
+        };
+        let bounds = self.points.iter().fold(Rect::from_center_size(first.xz(), Vec2::ZERO), |bounds, point| {
+            bounds.union_point(point.xz())
+        });
+        bounds.inflate(self.width / 2.0 + SHOULDER)
+    }
+
+    // Chunks whose mesh has at least one vertex the road changes
+    pub fn affected_chunks(&self) -> impl Iterator<Item = (i32, i32)> {
+        let bounds = self.bounds().inflate(1.0);
+        let (min_x, min_z) = mesh_chunk_coords(bounds.min.x, bounds.min.y, CHUNK_SIZE);
+        let (max_x, max_z) = mesh_chunk_coords(bounds.max.x, bounds.max.y, CHUNK_SIZE);
+        (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+    }
+
+    // Height and weight of the road surface at a point, None away from it
+    fn surface(&self, point: Vec2) -> Option<(f32, f32)> {
+        if !self.bounds().contains(point) {
+            return None;
+        }
+        let (distance, height) = self.nearest(point)?;
+        let weight = self.weight(distance);
+        (weight > 0.0).then_some((height, weight))
+    }
+}
+
+// Height change the roads make to the natural terrain; where roads cross, the one nearest wins
+pub fn roads_height_offset(roads: &[Road], world_x: f32, world_z: f32) -> f32 {
+    let point = Vec2::new(world_x, world_z);
+    let Some((height, weight)) = roads
+        .iter()
+        .filter_map(|road| road.surface(point))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    else {
+        return 0.0;
+    };
+    (height - terrain::height(world_x, world_z)) * weight
+}
+
+// Whether a point is on a road or its shoulders
+pub fn near_road(roads: &[Road], world_x: f32, world_z: f32) -> bool {
+    let point = Vec2::new(world_x, world_z);
+    roads.iter().any(|road| road.surface(point).is_some())
+}
+
+// Terrain color with the road's dirt painted over it
+pub fn paint_roads(roads: &[Road], world_x: f32, world_z: f32, color: [f32; 4]) -> [f32; 4] {
+    let point = Vec2::new(world_x, world_z);
+    let dirt = roads.iter().filter_map(|road| road.surface(point)).map(|(_, weight)| weight).fold(0.0, f32::max);
+    if dirt <= 0.0 {
+        return color;
+    }
+    std::array::from_fn(|channel| color[channel] + (DIRT_COLOR[channel] - color[channel]) * dirt)
+}
+
+type RouteCell = (i32, i32);
+
+fn route_cell_center(cell: RouteCell) -> Vec2 {
+    Vec2::new(cell.0 as f32, cell.1 as f32) * ROUTE_CELL
+}
+
+// Cheapest route between two points with A* over the heightfield, avoiding deep water and
+// steep slopes, as a road with smoothed heights. None when no route is found within the search.
+pub fn find_road(from: Vec2, to: Vec2, height: impl Fn(f32, f32) -> f32) -> Option<Road> {
+    let cell_of = |point: Vec2| ((point.x / ROUTE_CELL).round() as i32, (point.y / ROUTE_CELL).round() as i32);
+    let (start, goal) = (cell_of(from), cell_of(to));
+    // Height of the road surface over a cell, raised over shallow water
+    let ground = |cell: RouteCell| {
+        let center = route_cell_center(cell);
+        let (ground, sea) = (height(center.x, center.y), sea_level(center.x, center.y));
+        (ground > sea - MAX_FORD_DEPTH).then_some(ground.max(sea + CAUSEWAY_HEIGHT))
+    };
+    let wet = |cell: RouteCell| {
+        let center = route_cell_center(cell);
+        height(center.x, center.y) < sea_level(center.x, center.y)
+    };
+    ground(start)?;
+    ground(goal)?;
+
+    // Plain distance, never more than the real cost
+    let estimate = |cell: RouteCell| route_cell_center(cell).distance(route_cell_center(goal));
+    let key = |cost: f32| (cost * 100.0) as u32;
+    let mut open = BinaryHeap::from([Reverse((key(estimate(start)), start))]);
+    let mut costs = HashMap::from([(start, 0.0f32)]);
+    let mut heights = HashMap::new();
+    let mut came_from: HashMap<RouteCell, RouteCell> = HashMap::new();
+
+    while let Some(Reverse((_, cell))) = open.pop() {
+        if cell == goal {
+            let mut cells = vec![goal];
+            while let Some(previous) = came_from.get(cells.last()?) {
+                cells.push(*previous);
+            }
+            cells.reverse();
+            return Some(smooth_road(&cells, |cell| heights.get(cell).copied().flatten().unwrap_or(0.0)));
+        }
+        if costs.len() > MAX_ROUTE_CELLS {
+            return None;
+        }
+
+        let cost = costs[&cell];
+        let Some(cell_height) = *heights.entry(cell).or_insert_with(|| ground(cell)) else {
+            continue;
+        };
+        for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let next = (cell.0 + dx, cell.1 + dz);
+            let Some(next_height) = *heights.entry(next).or_insert_with(|| ground(next)) else {
+                continue;
+            };
+            let distance = Vec2::new(dx as f32, dz as f32).length() * ROUTE_CELL;
+            let grade = (next_height - cell_height).abs() / distance;
+            if grade > MAX_GRADE {
+                continue;
+            }
+            let wading = if wet(next) { WADE_COST } else { 1.0 };
+            let next_cost = cost + distance * (1.0 + GRADE_COST * grade * grade) * wading;
+            if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, cell);
+            open.push(Reverse((key(next_cost + estimate(next)), next)));
+        }
+    }
+    None
+}
+
+// Road along route cells, its heights averaged so it climbs evenly
+fn smooth_road(cells: &[RouteCell], height: impl Fn(&RouteCell) -> f32) -> Road {
+    let mut heights: Vec<f32> = cells.iter().map(height).collect();
+    for _ in 0..2 {
+        heights = (0..heights.len())
+            .map(|index| {
+                let window = &heights[index.saturating_sub(SMOOTHING_POINTS)..(index + SMOOTHING_POINTS + 1).min(heights.len())];
+                window.iter().sum::<f32>() / window.len() as f32
+            })
+            .collect();
+    }
+    Road {
+        points: cells
+            .iter()
+            .zip(heights)
+            .map(|(cell, height)| {
+                let center = route_cell_center(*cell);
+                Vec3::new(center.x, height, center.y)
+            })
+            .collect(),
+        width: ROAD_WIDTH,
+    }
+}
+
+// Groups positions of built pieces into sites, and pairs each site with its nearest
+// neighbour within LINK_DISTANCE, as the ends of the roads to carve between them
+pub fn link_sites(pieces: impl IntoIterator<Item = Vec2>) -> Vec<(Vec2, Vec2)> {
+    let mut sites: Vec<(Vec2, u32)> = Vec::new();
+    for piece in pieces {
+        match sites.iter_mut().find(|(center, _)| center.distance(piece) < SITE_RADIUS) {
+            Some((center, count)) => {
+                *count += 1;
+                *center += (piece - *center) / *count as f32;
+            }
+            None => sites.push((piece, 1)),
+        }
+    }
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    for (index, (site, _)) in sites.iter().enumerate() {
+        let nearest = sites
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(other, (center, _))| (other, center.distance(*site)))
+            .filter(|(_, distance)| *distance <= LINK_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((other, _)) = nearest {
+            let link = (index.min(other), index.max(other));
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    links.into_iter().map(|(a, b)| (sites[a].0, sites[b].0)).collect()
+}
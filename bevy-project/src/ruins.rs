@@ -0,0 +1,118 @@
+use std::f32::consts::FRAC_PI_2;
+use bevy::prelude::*;
+use crate::building::{PIECE_SIZE, SIDES};
+use crate::client::CHUNK_SIZE;
+use crate::placement::PlaceableKind;
+use crate::save::SavedPiece;
+use crate::streaming::ChunkCoords;
+use crate::terrain;
+use crate::water::sea_level;
+use crate::world_rng::{RngPurpose, WorldRng};
+
+// Share of chunks holding the remains of an old building
+const RUIN_CHANCE: f32 = 0.1;
+// Floor pieces along each side of a ruin
+const MIN_SIDE: usize = 2;
+const MAX_SIDE: usize = 4;
+// Share of the outer walls still standing
+const STANDING_WALLS: f32 = 0.55;
+// Ruins stand on dry land, on ground no more uneven than this under their floor
+const MIN_SHORE_HEIGHT: f32 = 0.3;
+const MAX_UNEVENNESS: f32 = 4.0;
+// Each floor piece settled on the ground under it, sunk this far into it
+const FLOOR_SINK: f32 = 0.1;
+
+// An abandoned building made of the same floors and walls players build, each floor piece
+// settled on its own ground as it crumbled. Placed from the world seed and the chunk on the
+// natural terrain, so every client and the server find the same ruins without sending them.
+// Never saved, they come back with the world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ruin {
+    // Middle of the floor, on the ground
+    pub center: Vec3,
+    // Everything of the ruin is within this horizontal distance of the center
+    pub radius: f32,
+    pub pieces: Vec<SavedPiece>,
+}
+
+// The ruin of a chunk, if it has one
+pub fn ruin_in_chunk(chunk: ChunkCoords) -> Option<Ruin> {
+    let mut rng = WorldRng::for_chunk(chunk, RngPurpose::Structures);
+    if !rng.chance(RUIN_CHANCE) {
+        return None;
+    }
+    let chunk_center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+    let center = chunk_center + (Vec2::new(rng.unit(), rng.unit()) - 0.5) * CHUNK_SIZE * 0.5;
+    let width = MIN_SIDE + rng.index(MAX_SIDE - MIN_SIDE + 1);
+    let depth = MIN_SIDE + rng.index(MAX_SIDE - MIN_SIDE + 1);
+    // Mostly square to the world, a little askew
+    let rotation = Quat::from_rotation_y(rng.index(4) as f32 * FRAC_PI_2 + rng.range(-0.2, 0.2));
+
+    let cells: Vec<(usize, usize, Vec2)> = (0..width)
+        .flat_map(|x| (0..depth).map(move |z| (x, z)))
+        .map(|(x, z)| {
+            let local = Vec3::new(x as f32 - (width - 1) as f32 / 2.0, 0.0, z as f32 - (depth - 1) as f32 / 2.0) * PIECE_SIZE;
+            (x, z, center + (rotation * local).xz())
+        })
+        .collect();
+    let ground: Vec<f32> = cells.iter().map(|(_, _, point)| terrain::height(point.x, point.y)).collect();
+    let (lowest, highest) = ground.iter().fold((f32::MAX, f32::MIN), |(low, high), height| (low.min(*height), high.max(*height)));
+    if lowest < sea_level(center.x, center.y) + MIN_SHORE_HEIGHT || highest - lowest > MAX_UNEVENNESS {
+        return None;
+    }
+
+    let mut pieces = Vec::new();
+    for ((x, z, point), ground) in cells.into_iter().zip(&ground) {
+        let floor = Vec3::new(point.x, ground - FLOOR_SINK, point.y);
+        pieces.push(SavedPiece { kind: PlaceableKind::Floor, position: floor, rotation });
+        let outer = [z == 0, z == depth - 1, x == 0, x == width - 1];
+        for ((side, yaw), outer) in SIDES.iter().zip(outer) {
+            if outer && rng.chance(STANDING_WALLS) {
+                pieces.push(SavedPiece {
+                    kind: PlaceableKind::Wall,
+                    position: floor + rotation * (*side * PIECE_SIZE / 2.0),
+                    rotation: rotation * Quat::from_rotation_y(*yaw),
+                });
+            }
+        }
+    }
+    Some(Ruin {
+        center: Vec3::new(center.x, terrain::height(center.x, center.y), center.y),
+        radius: Vec2::new(width as f32, depth as f32).length() * PIECE_SIZE / 2.0,
+        pieces,
+    })
+}
+
+// The other ruin closest to `ruin`, of chunk `chunk`, within `max_distance`, with its chunk
+pub fn nearest_ruin(chunk: ChunkCoords, ruin: &Ruin, max_distance: f32) -> Option<(ChunkCoords, Ruin)> {
+    let reach = (max_distance / CHUNK_SIZE).ceil() as i32 + 1;
+    let distance = |other: &Ruin| other.center.xz().distance(ruin.center.xz());
+    (-reach..=reach)
+        .flat_map(|dx| (-reach..=reach).map(move |dz| (chunk.0 + dx, chunk.1 + dz)))
+        .filter(|other| *other != chunk)
+        .filter_map(|other| ruin_in_chunk(other).map(|ruin| (other, ruin)))
+        .filter(|(_, other)| distance(other) <= max_distance)
+        .min_by(|a, b| distance(&a.1).total_cmp(&distance(&b.1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ruins_are_the_same_every_time_and_stay_in_their_chunk() {
+        let ruins: Vec<(ChunkCoords, Ruin)> = (-20..20)
+            .flat_map(|x| (-20..20).map(move |z| (x, z)))
+            .filter_map(|chunk| ruin_in_chunk(chunk).map(|ruin| (chunk, ruin)))
+            .collect();
+        assert!(!ruins.is_empty());
+        for (chunk, ruin) in ruins {
+            assert_eq!(ruin_in_chunk(chunk), Some(ruin.clone()));
+            let chunk_center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+            for piece in &ruin.pieces {
+                assert!(piece.position.xz().distance(ruin.center.xz()) <= ruin.radius + 1e-3);
+                assert!((piece.position.xz() - chunk_center).abs().max_element() < CHUNK_SIZE / 2.0);
+            }
+        }
+    }
+}
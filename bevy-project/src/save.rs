@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::building::Structures;
+use crate::campfire::Campfire;
+use crate::creative::CreativeMode;
+use crate::day_night::TimeOfDay;
+use crate::door::Door;
+use crate::inventory::Inventory;
+use crate::placement::PlaceableKind;
+use crate::player::{Player, RespawnPoint};
+use crate::race::{RaceCourse, RaceCourses};
+use crate::region::ProtectedRegion;
+use crate::road::Road;
+use crate::save_migration::{parse_save, SaveError, SAVE_VERSION};
+use crate::server::ServerWorld;
+use crate::settings::GameSettings;
+use crate::streaming::ChunkCoords;
+use crate::terrain::{self, WorldGenSettings};
+use crate::terrain_edit::TerrainEdit;
+
+const SAVE_PATH: &str = "saves/world.ron";
+// Most backups kept whatever the settings, and looked for when loading
+const MAX_BACKUPS: usize = 10;
+
+#[derive(Default, Clone, Debug)]
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<LoadedSave>() {
+            app.insert_resource(LoadedSave(SaveGame::load_or_exit()));
+        }
+        app
+            .add_event::<SaveRequested>()
+            .init_resource::<AutosaveTimer>()
+            .add_systems(PostStartup, restore_save)
+            .add_systems(Update, request_autosave)
+            .add_systems(Last, write_save_on_request);
+    }
+}
+
+// Ask for the game to be written to disk at the end of the frame
+#[derive(Event, Debug, Clone)]
+pub struct SaveRequested {
+    pub reason: &'static str,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedTime {
+    pub hour: f32,
+    pub day: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedCampfire {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub lit: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SavedDoor {
+    pub kind: PlaceableKind,
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub open: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SavedPiece {
+    pub kind: PlaceableKind,
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+// Everything persisted in `saves/world.ron`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct SaveGame {
+    // Format the save was written in, 0 for saves from before formats were numbered
+    pub version: u32,
+    pub player_position: Option<Vec3>,
+    pub respawn_point: Option<Vec3>,
+    pub time_of_day: Option<SavedTime>,
+    pub campfires: Vec<SavedCampfire>,
+    pub doors: Vec<SavedDoor>,
+    // None in saves made before the inventory existed, they keep the starting items
+    pub inventory: Option<HashMap<String, u32>>,
+    // Creative mode was used in this world
+    pub creative: bool,
+    // None in saves made before regions existed, they get the default spawn protection
+    pub regions: Option<Vec<ProtectedRegion>>,
+    // Dirt roads carved between structures, stored with the regions
+    pub roads: Vec<Road>,
+    // Floors, walls and ramps, by the chunk they stand in
+    pub structures: HashMap<ChunkCoords, Vec<SavedPiece>>,
+    // Race checkpoints and best time, by world seed
+    pub races: HashMap<u32, RaceCourse>,
+    // Noise the terrain is generated from, the edits and structures above sit on it
+    pub worldgen: WorldGenSettings,
+    // Terrain raised and dug in single player, in the order they were made. A dedicated
+    // server keeps its own in the server store.
+    pub terrain_edits: Vec<TerrainEdit>,
+}
+
+// Save read at startup, each feature restores its own part from it
+#[derive(Resource, Default)]
+pub struct LoadedSave(pub SaveGame);
+
+impl SaveGame {
+    // Read the save file, falling back to the newest readable backup, and starting a new
+    // game when there is none. A save from a newer build is an error: falling back or
+    // starting over would overwrite it on the next save.
+    pub fn load() -> Result<Self, (PathBuf, SaveError)> {
+        let candidates = std::iter::once(PathBuf::from(SAVE_PATH)).chain((1..=MAX_BACKUPS).map(backup_path));
+        for path in candidates.filter(|path| path.exists()) {
+            match fs::read_to_string(&path).map_err(SaveError::from).and_then(|text| parse_save(&text)) {
+                Ok(save) => {
+                    info!("Loaded save {}", path.display());
+                    return Ok(save);
+                }
+                Err(err @ SaveError::TooNew { .. }) => return Err((path, err)),
+                Err(err) => warn!("Could not read {}: {}", path.display(), err),
+            }
+        }
+        if Path::new(SAVE_PATH).exists() {
+            warn!("No readable save or backup, starting a new game");
+        }
+        Ok(Self::default())
+    }
+
+    // The game can't run on a world it can't save
+    pub fn load_or_exit() -> Self {
+        Self::load().unwrap_or_else(|(path, err)| {
+            eprintln!("Could not load {}: {}. Move it out of the way to start a new world.", path.display(), err);
+            std::process::exit(1);
+        })
+    }
+
+    // Write the save next to the current one and swap it in, so a crash while saving leaves
+    // the previous save whole. The previous save becomes the newest of `backups` backups.
+    pub fn write(&self, backups: usize) {
+        let save = SaveGame { version: SAVE_VERSION, ..self.clone() };
+        let text = match ron::ser::to_string_pretty(&save, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("Could not serialize save: {}", err);
+                return;
+            }
+        };
+        if let Some(parent) = Path::new(SAVE_PATH).parent()
+            && let Err(err) = fs::create_dir_all(parent)
+        {
+            warn!("Could not create {}: {}", parent.display(), err);
+            return;
+        }
+        if let Err(err) = write_atomically(Path::new(SAVE_PATH), &text, backups.min(MAX_BACKUPS)) {
+            warn!("Could not write {}: {}", SAVE_PATH, err);
+        }
+    }
+}
+
+// `saves/world.<index>.ron`, 1 being the newest
+fn backup_path(index: usize) -> PathBuf {
+    Path::new(SAVE_PATH).with_extension(format!("{}.ron", index))
+}
+
+fn write_atomically(path: &Path, text: &str, backups: usize) -> std::io::Result<()> {
+    let temporary = path.with_extension("ron.tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+
+    // Shift the backups down, dropping the oldest
+    if backups > 0 && path.exists() {
+        for index in (1..backups).rev() {
+            let from = backup_path(index);
+            if from.exists() {
+                fs::rename(&from, backup_path(index + 1))?;
+            }
+        }
+        fs::rename(path, backup_path(1))?;
+    }
+    fs::rename(&temporary, path)
+}
+
+fn restore_save(
+    save: Res<LoadedSave>,
+    mut respawn_point: ResMut<RespawnPoint>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut inventory: ResMut<Inventory>,
+    mut creative: ResMut<CreativeMode>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    let save = &save.0;
+    if let Some(position) = save.player_position
+        && let Ok(mut transform) = player_query.get_single_mut()
+    {
+        transform.translation = position;
+    }
+    respawn_point.0 = save.respawn_point;
+    if let Some(time) = save.time_of_day {
+        time_of_day.hour = time.hour;
+        time_of_day.day = time.day;
+    }
+    if let Some(items) = &save.inventory {
+        inventory.items = items.clone();
+    }
+    creative.used = save.creative;
+}
+
+#[derive(Resource, Default)]
+struct AutosaveTimer {
+    elapsed: f32,
+    // In-game day of the last autosave
+    day: Option<u32>,
+}
+
+// Save every few minutes and at the start of every in-game day
+fn request_autosave(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    time_of_day: Res<TimeOfDay>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut save_requests: EventWriter<SaveRequested>,
+) {
+    let new_day = timer.day.is_some_and(|day| day != time_of_day.day);
+    timer.day = Some(time_of_day.day);
+    if !settings.saving.autosave {
+        timer.elapsed = 0.0;
+        return;
+    }
+    timer.elapsed += time.delta_secs();
+    if new_day || timer.elapsed >= settings.saving.autosave_minutes * 60.0 {
+        timer.elapsed = 0.0;
+        save_requests.send(SaveRequested { reason: if new_day { "new day" } else { "autosave" } });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_save_on_request(
+    mut requests: EventReader<SaveRequested>,
+    settings: Res<GameSettings>,
+    respawn_point: Res<RespawnPoint>,
+    time_of_day: Res<TimeOfDay>,
+    inventory: Res<Inventory>,
+    creative: Res<CreativeMode>,
+    structures: Res<Structures>,
+    race_courses: Res<RaceCourses>,
+    player_query: Query<&Transform, With<Player>>,
+    campfire_query: Query<(&Transform, &Campfire)>,
+    door_query: Query<(&Transform, &Door)>,
+    server_world: Option<Res<ServerWorld>>,
+) {
+    let Some(request) = requests.read().last() else {
+        return;
+    };
+
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        player_position: player_query.get_single().ok().map(|transform| transform.translation),
+        respawn_point: respawn_point.0,
+        time_of_day: Some(SavedTime {
+            hour: time_of_day.hour,
+            day: time_of_day.day,
+        }),
+        campfires: campfire_query.iter()
+            .map(|(transform, campfire)| SavedCampfire {
+                position: transform.translation,
+                rotation: transform.rotation,
+                lit: campfire.lit,
+            })
+            .collect(),
+        doors: door_query.iter()
+            .map(|(transform, door)| SavedDoor {
+                kind: door.kind,
+                position: transform.translation,
+                rotation: transform.rotation,
+                open: door.open,
+            })
+            .collect(),
+        inventory: Some(inventory.items.clone()),
+        creative: creative.used,
+        regions: server_world.as_ref().map(|world| world.regions.clone()),
+        roads: server_world.as_ref().map(|world| world.roads.clone()).unwrap_or_default(),
+        structures: structures.chunks.clone(),
+        races: race_courses.seeds.clone(),
+        worldgen: server_world.as_ref().map_or_else(terrain::world_gen, |world| world.worldgen),
+        terrain_edits: server_world.as_ref().map(|world| world.edits.clone()).unwrap_or_default(),
+    };
+    save.write(settings.saving.backups);
+    info!("Game saved ({})", request.reason);
+}
+
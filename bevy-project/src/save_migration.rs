@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use bevy::math::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::race::RaceCourse;
+use crate::region::ProtectedRegion;
+use crate::road::Road;
+use crate::save::{SaveGame, SavedCampfire, SavedDoor, SavedPiece, SavedTime};
+use crate::streaming::ChunkCoords;
+
+// Format of the saves this build writes. Changing what a save holds in a way the serde
+// defaults can't absorb (a field renamed, moved or changing type) bumps it and adds a step
+// to MIGRATIONS.
+pub const SAVE_VERSION: u32 = 3;
+
+// MIGRATIONS[n] rewrites a save of format n as format n + 1. A step reads the old format with
+// its own frozen copy of the structs that changed, kept in here, and writes the next one.
+// Fields a step carries over as they are keep their current types, so a later step changing
+// one of those freezes its old layout for the earlier steps too.
+type Migration = fn(&str) -> Result<String, SaveError>;
+const MIGRATIONS: [Migration; SAVE_VERSION as usize] = [from_unversioned, from_v1, from_v2];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveError {
+    #[error("could not read the save: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse the save: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not rewrite the save: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("save format {version} is newer than this build reads (up to {supported}), update the game to load it", supported = SAVE_VERSION)]
+    TooNew { version: u32 },
+}
+
+// Only the format of a save, every other field is skipped
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SaveHeader {
+    version: u32,
+}
+
+// Read a save of any format up to this build's, upgrading older ones one step at a time
+pub fn parse_save(text: &str) -> Result<SaveGame, SaveError> {
+    let version = ron::from_str::<SaveHeader>(text)?.version;
+    if version > SAVE_VERSION {
+        return Err(SaveError::TooNew { version });
+    }
+    let mut text = text.to_string();
+    for migration in &MIGRATIONS[version as usize..] {
+        text = migration(&text)?;
+    }
+    let mut save: SaveGame = ron::from_str(&text)?;
+    save.version = SAVE_VERSION;
+    Ok(save)
+}
+
+// Saves from before formats were numbered have no version field. Everything added until then
+// has a default, so they read as format 1 as they are.
+fn from_unversioned(text: &str) -> Result<String, SaveError> {
+    Ok(text.to_string())
+}
+
+// Noise settings as of format 1, which didn't record them: every world came back with these
+// defaults on load. Frozen here so changing the defaults later doesn't reshape old worlds.
+#[derive(Serialize, Clone, Copy)]
+struct WorldGenV1 {
+    seed: u32,
+    octaves: usize,
+    frequency: f64,
+    persistence: f64,
+    lacunarity: f64,
+    amplitude: f64,
+    detail_frequency: f64,
+    detail_amplitude: f64,
+}
+
+const WORLD_GEN_V1: WorldGenV1 = WorldGenV1 {
+    seed: 1,
+    octaves: 8,
+    frequency: 0.05,
+    persistence: 0.6,
+    lacunarity: 2.0,
+    amplitude: 22.0,
+    detail_frequency: 0.03,
+    detail_amplitude: 3.0,
+};
+
+impl Default for WorldGenV1 {
+    fn default() -> Self {
+        WORLD_GEN_V1
+    }
+}
+
+// A format 1 save, written back with the terrain settings format 2 adds
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct SaveV1 {
+    version: u32,
+    player_position: Option<Vec3>,
+    respawn_point: Option<Vec3>,
+    time_of_day: Option<SavedTime>,
+    campfires: Vec<SavedCampfire>,
+    doors: Vec<SavedDoor>,
+    inventory: Option<HashMap<String, u32>>,
+    creative: bool,
+    regions: Option<Vec<ProtectedRegion>>,
+    roads: Vec<Road>,
+    structures: HashMap<ChunkCoords, Vec<SavedPiece>>,
+    races: HashMap<u32, RaceCourse>,
+    #[serde(skip_deserializing)]
+    worldgen: WorldGenV1,
+}
+
+// Format 2 keeps the terrain settings, format 1 saves get the ones they were played with
+fn from_v1(text: &str) -> Result<String, SaveError> {
+    let save = SaveV1 { version: 2, ..ron::from_str(text)? };
+    Ok(ron::to_string(&save)?)
+}
+
+// Format 3 adds the terrain edits, which format 2 saves have none of and read as they are.
+// The bump keeps builds that would drop the edits from loading, and then overwriting, them.
+fn from_v2(text: &str) -> Result<String, SaveError> {
+    Ok(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_saves_upgrade_and_newer_ones_are_refused() {
+        let save = parse_save("(creative: true, inventory: Some({\"wood\": 3}))").unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        assert!(save.creative);
+        assert_eq!(save.inventory.unwrap()["wood"], 3);
+        assert_eq!(save.worldgen.seed, WORLD_GEN_V1.seed);
+
+        // Comments and a nested tuple last don't get in the way
+        let v1 = "(\n    version: 1,\n    races: {7: (checkpoints: [], best_time: None)},\n    respawn_point: Some((4.0, 5.0, 6.0)),\n) // saved on day 3\n";
+        let save = parse_save(v1).unwrap();
+        assert_eq!((save.worldgen.seed, save.worldgen.amplitude), (1, 22.0));
+        assert!(save.races.contains_key(&7));
+        assert_eq!(save.respawn_point, Some(Vec3::new(4.0, 5.0, 6.0)));
+
+        let current = format!("(version: {}, doors: [(kind: Gate, position: (1.0, 2.0, 3.0), rotation: (0.0, 0.0, 0.0, 1.0), open: true)])", SAVE_VERSION);
+        assert_eq!(parse_save(&current).unwrap().doors.len(), 1);
+        let seeded = format!("(version: {}, worldgen: (seed: 42))", SAVE_VERSION);
+        assert_eq!(parse_save(&seeded).unwrap().worldgen.seed, 42);
+        let edited = format!("(version: {}, terrain_edits: [(center: (3.0, -4.0), radius: 2.0, delta: 0.5)])", SAVE_VERSION);
+        assert_eq!(parse_save(&edited).unwrap().terrain_edits.len(), 1);
+        assert!(parse_save("(version: 2, creative: true)").unwrap().terrain_edits.is_empty());
+
+        let newer = format!("(version: {}, some_new_field: [])", SAVE_VERSION + 1);
+        assert!(matches!(parse_save(&newer), Err(SaveError::TooNew { version }) if version == SAVE_VERSION + 1));
+        assert!(matches!(parse_save("(creative: nope"), Err(SaveError::Parse(_))));
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use bevy_atmosphere::prelude::*;
+use crate::day_night::{advance_time_of_day, TimeOfDay};
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle};
+
+// In-game days per season, a full year is four times this
+const SEASON_DAYS: f32 = 7.0;
+// Changes smaller than these don't re-upload the terrain material or redraw the sky
+const SNOW_LINE_STEP: f32 = 0.05;
+const TINT_STEP: f32 = 0.005;
+const HAZE_STEP: f32 = 0.02;
+// Mie scattering of the default sky, scaled by the season's haze
+const MIE_COEFFICIENT: f32 = 21e-6;
+
+// Seasons cycling from spring to winter over the in-game days. Each one sets how long
+// the days are, how high the sun climbs, where snow starts, the color of the grass and
+// how hazy the sky is, fading into the next season halfway through.
+#[derive(Default, Clone, Debug)]
+pub struct SeasonPlugin;
+
+impl Plugin for SeasonPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Season>()
+            .add_systems(Update, (
+                update_season,
+                apply_season_to_terrain,
+                apply_season_to_sky,
+            ).chain().after(advance_time_of_day));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SeasonKind {
+    #[default]
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl SeasonKind {
+    const ALL: [SeasonKind; 4] = [SeasonKind::Spring, SeasonKind::Summer, SeasonKind::Autumn, SeasonKind::Winter];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SeasonKind::Spring => "Spring",
+            SeasonKind::Summer => "Summer",
+            SeasonKind::Autumn => "Autumn",
+            SeasonKind::Winter => "Winter",
+        }
+    }
+
+    // Look of the season at its middle
+    fn params(&self) -> SeasonParams {
+        match self {
+            SeasonKind::Spring => SeasonParams {
+                daylight_hours: 12.5,
+                sun_elevation_degrees: 55.0,
+                snow_line: 12.0,
+                foliage_tint: Vec3::new(0.85, 1.1, 0.8),
+                haze: 1.0,
+            },
+            SeasonKind::Summer => SeasonParams {
+                daylight_hours: 15.0,
+                sun_elevation_degrees: 72.0,
+                snow_line: 40.0,
+                foliage_tint: Vec3::ONE,
+                haze: 1.4,
+            },
+            SeasonKind::Autumn => SeasonParams {
+                daylight_hours: 11.5,
+                sun_elevation_degrees: 48.0,
+                snow_line: 10.0,
+                foliage_tint: Vec3::new(1.45, 0.95, 0.5),
+                haze: 1.1,
+            },
+            SeasonKind::Winter => SeasonParams {
+                daylight_hours: 9.0,
+                sun_elevation_degrees: 28.0,
+                snow_line: 2.5,
+                foliage_tint: Vec3::new(0.95, 0.95, 0.9),
+                haze: 0.7,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SeasonParams {
+    pub daylight_hours: f32,
+    pub sun_elevation_degrees: f32,
+    // Height snow covers flat ground above
+    pub snow_line: f32,
+    // Color multiplier for grass
+    pub foliage_tint: Vec3,
+    // Multiplier on the sky's haze, crisp winter air to hazy summer days
+    pub haze: f32,
+}
+
+impl SeasonParams {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            daylight_hours: self.daylight_hours.lerp(other.daylight_hours, t),
+            sun_elevation_degrees: self.sun_elevation_degrees.lerp(other.sun_elevation_degrees, t),
+            snow_line: self.snow_line.lerp(other.snow_line, t),
+            foliage_tint: self.foliage_tint.lerp(other.foliage_tint, t),
+            haze: self.haze.lerp(other.haze, t),
+        }
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Season {
+    pub kind: SeasonKind,
+    pub params: SeasonParams,
+}
+
+impl Default for Season {
+    fn default() -> Self {
+        Self::at(&TimeOfDay::default())
+    }
+}
+
+impl Season {
+    pub fn at(time_of_day: &TimeOfDay) -> Self {
+        let seasons = ((time_of_day.day.max(1) - 1) as f32 + time_of_day.hour / 24.0) / SEASON_DAYS;
+        let index = |season: f32| SeasonKind::ALL[(season.floor() as i64).rem_euclid(4) as usize];
+        // Blend from the middle of one season to the middle of the next
+        let from_middle = seasons - 0.5;
+        let params = index(from_middle).params().lerp(&index(from_middle + 1.0).params(), from_middle - from_middle.floor());
+        Self {
+            kind: index(seasons),
+            params,
+        }
+    }
+}
+
+fn update_season(
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut season: ResMut<Season>,
+) {
+    let current = Season::at(&time_of_day);
+    if current.kind != season.kind {
+        info!("{} begins", current.kind.name());
+    }
+    *season = current;
+    time_of_day.daylight_hours = current.params.daylight_hours;
+    time_of_day.sun_elevation = current.params.sun_elevation_degrees.to_radians();
+}
+
+fn apply_season_to_terrain(
+    season: Res<Season>,
+    handle: Res<TerrainMaterialHandle>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    let Some(material) = materials.get(&handle.0) else {
+        return;
+    };
+    let params = &material.extension.params;
+    if (params.snow_line - season.params.snow_line).abs() < SNOW_LINE_STEP
+        && params.foliage_tint.abs_diff_eq(season.params.foliage_tint, TINT_STEP)
+    {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.extension.params.snow_line = season.params.snow_line;
+        material.extension.params.foliage_tint = season.params.foliage_tint;
+    }
+}
+
+fn apply_season_to_sky(
+    season: Res<Season>,
+    mut atmosphere: ResMut<AtmosphereModel>,
+    mut applied: Local<f32>,
+) {
+    if (season.params.haze - *applied).abs() < HAZE_STEP {
+        return;
+    }
+    if let Some(nishita) = atmosphere.to_mut::<Nishita>() {
+        nishita.mie_coefficient = MIE_COEFFICIENT * season.params.haze;
+        *applied = season.params.haze;
+    }
+}
@@ -0,0 +1,996 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use crate::admin::{AdminConfig, AdminPlugin};
+use crate::client::{chunk_roughness, chunk_subdivisions, CHUNK_SIZE};
+use crate::console::ConsolePlugin;
+use crate::day_night::{advance_time_of_day, TimeOfDay};
+use crate::region::{default_regions, ProtectedRegion};
+use crate::road::{find_road, roads_height_offset, Road, LINK_DISTANCE};
+use crate::ruins::{nearest_ruin, ruin_in_chunk};
+use crate::movement::{MovementCheck, MovementLimits, MoveVerdict};
+use crate::player::{PLAYER_BODY_LENGTH, PLAYER_RADIUS};
+use crate::projectile::{GRAVITY, ROCK_RADIUS, THROW_SPEED};
+use crate::save::{LoadedSave, SaveGame};
+use crate::server_store::{ServerStore, ServerStorePlugin};
+use crate::snapshot::{NetworkSettings, Snapshot, SnapshotBuffer};
+use crate::net::{decode_message, encode_message, AccountKey, ClientId, ClientMessage, EditRejection, ServerConnection, ServerMessage, LOCAL_CLIENT};
+use crate::streaming::{chunk_coords, mesh_chunk_coords, AnchorId, ChunkCoords, ChunkStreamer};
+use crate::terrain::{self, Heightfield, WorldGenSettings};
+use crate::terrain_edit::{ChunkEdits, TerrainEdit};
+use crate::transport::{loopback_pair, Connector, LoopbackTransport, Transport, TransportError, UdpListener, DEFAULT_PORT};
+use crate::world_border::WorldBorder;
+
+const MAX_EDIT_RADIUS: f32 = 8.0;
+const MAX_EDIT_DELTA: f32 = 2.0;
+// Chunks kept loaded around each connected player
+const SERVER_VIEW_RADIUS: i32 = 2;
+// Simulation rate of the dedicated server
+const SERVER_TICK_HZ: f64 = 30.0;
+// Rewound rock flights are simulated in steps of this many seconds, for at most ROCK_CHECK_SECONDS
+const REWIND_STEP: f32 = 1.0 / 60.0;
+const ROCK_CHECK_SECONDS: f32 = 3.0;
+// Rocks thrown farther than this from where the server has the thrower are ignored
+const MAX_THROW_REACH: f32 = 3.0;
+// Seconds a player whose connection dropped is kept in the world, resumed if they come back
+const RECONNECT_GRACE: f64 = 60.0;
+
+// Runs the authoritative server logic inside the client app (single player),
+// the local client is connected to it through a loopback transport
+#[derive(Default, Clone, Debug)]
+pub struct LocalServerPlugin;
+
+impl Plugin for LocalServerPlugin {
+    fn build(&self, app: &mut App) {
+        let (client_end, server_end) = loopback_pair();
+        let mut connections = ClientConnections::default();
+        connections.connect(LOCAL_CLIENT, server_end);
+        connections.host = Some(LOCAL_CLIENT);
+        let reconnects = LocalReconnects::default();
+
+        app
+            .insert_resource(ServerConnection::new(client_end).with_connector(reconnects.clone()))
+            .insert_resource(connections)
+            .insert_resource(reconnects)
+            .add_plugins(ServerPlugin)
+            .add_systems(Update, accept_local_reconnects.before(process_client_messages));
+    }
+}
+
+// Server ends of the loopbacks the local client opened to reconnect, not accepted yet
+#[derive(Resource, Default, Clone)]
+struct LocalReconnects(Arc<Mutex<Vec<LoopbackTransport>>>);
+
+impl Connector for LocalReconnects {
+    fn connect(&mut self) -> Result<Box<dyn Transport>, TransportError> {
+        let (client_end, server_end) = loopback_pair();
+        self.0.lock().unwrap().push(server_end);
+        Ok(Box::new(client_end))
+    }
+}
+
+// The dedicated server's socket for remote clients, and the id the next one gets
+#[derive(Resource)]
+struct ClientListener {
+    listener: UdpListener,
+    next_client: u32,
+}
+
+fn accept_remote_clients(
+    mut listener: ResMut<ClientListener>,
+    mut connections: ResMut<ClientConnections>,
+) {
+    let accepted = match listener.listener.accept() {
+        Ok(accepted) => accepted,
+        Err(err) => {
+            warn!("Could not read from clients: {}", err);
+            return;
+        }
+    };
+    for (address, transport) in accepted {
+        let client = ClientId(listener.next_client);
+        listener.next_client += 1;
+        info!("Client {} connected from {}", client.0, address);
+        connections.connect(client, transport);
+    }
+}
+
+fn accept_local_reconnects(
+    reconnects: Res<LocalReconnects>,
+    mut connections: ResMut<ClientConnections>,
+) {
+    for transport in reconnects.0.lock().unwrap().drain(..) {
+        connections.connect(LOCAL_CLIENT, transport);
+    }
+}
+
+// The authoritative world and its systems, shared by single player and the dedicated server
+#[derive(Default, Clone, Debug)]
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ClientConnections>()
+            .init_resource::<ServerWorld>()
+            .init_resource::<ServerChunks>()
+            .init_resource::<NetworkSettings>()
+            .init_resource::<MovementLimits>()
+            .init_resource::<WorldBorder>()
+            // Before anything samples the terrain
+            .add_systems(PreStartup, load_world)
+            .add_systems(Update, (process_client_messages, release_held_players, stream_server_chunks, carve_ruin_roads, send_world_border).chain());
+    }
+}
+
+// A circular area owned by a player, where nobody else may edit terrain
+#[derive(Debug, Clone)]
+pub struct LandClaim {
+    pub owner: Option<ClientId>,
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl LandClaim {
+    fn blocks(&self, client: ClientId, edit: &TerrainEdit) -> bool {
+        self.owner != Some(client) && self.center.distance(edit.center) < self.radius + edit.radius
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerPlayer {
+    pub position: Vec3,
+    // None until the client reports it
+    pub inventory: Option<HashMap<String, u32>>,
+    // Recent positions on the server's clock, to check shots against where the shooter saw them
+    pub history: SnapshotBuffer,
+    pub movement: MovementCheck,
+    // Set while the player's connection is lost, until they come back or the grace period ends
+    pub held: Option<HeldSession>,
+    // Where the player's account is stored, None if their client never said who they are
+    pub account: Option<AccountKey>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeldSession {
+    pub since: f64,
+    // Edits made before the connection dropped, the later ones are sent when resuming
+    pub edits: usize,
+}
+
+#[derive(Resource)]
+pub struct ServerWorld {
+    pub players: HashMap<ClientId, ServerPlayer>,
+    // Every accepted edit in order, added through `add_edit` so `edits_by_chunk` follows
+    pub edits: Vec<TerrainEdit>,
+    edits_by_chunk: ChunkEdits,
+    // Accounts announced by clients whose player hasn't joined yet
+    pending_accounts: HashMap<ClientId, AccountKey>,
+    pub claims: Vec<LandClaim>,
+    pub regions: Vec<ProtectedRegion>,
+    pub roads: Vec<Road>,
+    // Noise the terrain is generated from, changed through `set_world_gen`
+    pub worldgen: WorldGenSettings,
+}
+
+impl Default for ServerWorld {
+    fn default() -> Self {
+        Self {
+            players: HashMap::new(),
+            edits: Vec::new(),
+            edits_by_chunk: ChunkEdits::default(),
+            pending_accounts: HashMap::new(),
+            claims: Vec::new(),
+            regions: default_regions(),
+            roads: Vec::new(),
+            worldgen: WorldGenSettings::default(),
+        }
+    }
+}
+
+impl ServerWorld {
+    // Generate the terrain from other settings, loaded chunks have to be rebuilt after
+    pub fn set_world_gen(&mut self, settings: WorldGenSettings) {
+        self.worldgen = settings;
+        terrain::set_world_gen(settings);
+    }
+
+    // Authoritative terrain height, roads and edits included
+    pub fn height(&self, world_x: f32, world_z: f32) -> f32 {
+        terrain::height(world_x, world_z)
+            + roads_height_offset(&self.roads, world_x, world_z)
+            + self.edits_by_chunk.height_offset(world_x, world_z)
+    }
+
+    pub fn add_edit(&mut self, edit: TerrainEdit) {
+        self.edits.push(edit);
+        self.edits_by_chunk.insert(edit);
+    }
+
+    // What clients are sent about the regions and roads of the world
+    pub fn regions_message(&self) -> ServerMessage {
+        ServerMessage::Regions { regions: self.regions.clone(), roads: self.roads.clone() }
+    }
+
+    pub fn disconnect(&mut self, client: ClientId) {
+        self.players.remove(&client);
+    }
+
+    // Keep a player whose connection dropped in the world for a while, so they can resume
+    pub fn hold(&mut self, client: ClientId, now: f64) {
+        let edits = self.edits.len();
+        if let Some(player) = self.players.get_mut(&client) {
+            player.held.get_or_insert(HeldSession { since: now, edits });
+        }
+    }
+
+    // Whether `account` is in use on a link other than `client`'s, by a player or one saying hello
+    pub fn plays_elsewhere(&self, account: AccountKey, client: ClientId) -> bool {
+        self.players.iter().any(|(other, player)| *other != client && player.held.is_none() && player.account == Some(account))
+            || self.pending_accounts.iter().any(|(other, pending)| *other != client && *pending == account)
+    }
+
+    // Move the held player of `account` over to a new link's id, returning its old id
+    pub fn resume_as(&mut self, account: AccountKey, client: ClientId) -> Option<ClientId> {
+        let previous = self.players.iter()
+            .find(|(_, player)| player.held.is_some() && player.account == Some(account))
+            .map(|(previous, _)| *previous)?;
+        let player = self.players.remove(&previous)?;
+        self.players.insert(client, player);
+        Some(previous)
+    }
+
+    // Held players whose grace period is over
+    pub fn expired_sessions(&self, now: f64) -> Vec<ClientId> {
+        self.players.iter()
+            .filter(|(_, player)| player.held.is_some_and(|held| now - held.since > RECONNECT_GRACE))
+            .map(|(client, _)| *client)
+            .collect()
+    }
+
+    // Player a rock hits and where, checked against the other players where the thrower saw
+    // them: as they were at `view_time`, but no more than `max_rewind` seconds back
+    pub fn rewind_rock_hit(
+        &self,
+        thrower: ClientId,
+        origin: Vec3,
+        velocity: Vec3,
+        view_time: f64,
+        now: f64,
+        settings: &NetworkSettings,
+    ) -> Option<(ClientId, Vec3)> {
+        let start = view_time.clamp(now - settings.max_rewind as f64, now);
+        let (mut position, mut velocity) = (origin, velocity);
+        let mut elapsed = 0.0;
+        while elapsed < ROCK_CHECK_SECONDS {
+            velocity.y -= GRAVITY * REWIND_STEP;
+            position += velocity * REWIND_STEP;
+            elapsed += REWIND_STEP;
+            // Past the present players stay where they were last reported
+            let time = (start + elapsed as f64).min(now);
+            for (client, player) in &self.players {
+                if *client == thrower {
+                    continue;
+                }
+                if let Some(snapshot) = player.history.sample(time, 0.0)
+                    && touches_player(snapshot.position, position, ROCK_RADIUS)
+                {
+                    return Some((*client, position));
+                }
+            }
+            if position.y < self.height(position.x, position.z) {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Validate an edit request; the edit is stored if accepted or adjusted
+    pub fn handle_edit(&mut self, client: ClientId, seq: u32, edit: TerrainEdit) -> ServerMessage {
+        if self.regions.iter().any(|region| region.blocks_edit(&edit)) {
+            return ServerMessage::EditRejected { seq, reason: EditRejection::ProtectedRegion };
+        }
+        if self.claims.iter().any(|claim| claim.blocks(client, &edit)) {
+            return ServerMessage::EditRejected { seq, reason: EditRejection::ClaimedLand };
+        }
+
+        let clamped = TerrainEdit {
+            center: edit.center,
+            radius: edit.radius.clamp(0.5, MAX_EDIT_RADIUS),
+            delta: edit.delta.clamp(-MAX_EDIT_DELTA, MAX_EDIT_DELTA),
+        };
+        self.add_edit(clamped);
+
+        if clamped == edit {
+            ServerMessage::EditAccepted { seq }
+        } else {
+            ServerMessage::EditAdjusted { seq, edit: clamped }
+        }
+    }
+}
+
+// Whether a sphere touches a player's body, a capsule around `center`
+fn touches_player(center: Vec3, point: Vec3, radius: f32) -> bool {
+    let half_length = PLAYER_BODY_LENGTH / 2.0;
+    let axis = center + Vec3::Y * (point.y - center.y).clamp(-half_length, half_length);
+    axis.distance_squared(point) <= (PLAYER_RADIUS + radius).powi(2)
+}
+
+// The server's links to its clients, one transport each
+#[derive(Resource, Default)]
+pub struct ClientConnections {
+    clients: HashMap<ClientId, Box<dyn Transport>>,
+    // Clients who opened a new link while the server still had an old one, reported as
+    // disconnected by the next receive so they resume like after any dropped connection
+    replaced: Vec<ClientId>,
+    // The client whose app runs this server, trusted to change the world's settings and to
+    // move as it likes, since it could change the server itself. A dedicated server has none.
+    pub host: Option<ClientId>,
+}
+
+impl ClientConnections {
+    pub fn is_host(&self, client: ClientId) -> bool {
+        self.host == Some(client)
+    }
+
+    pub fn connect(&mut self, client: ClientId, transport: impl Transport) {
+        if self.clients.insert(client, Box::new(transport)).is_some() {
+            self.replaced.push(client);
+        }
+    }
+
+    // Drop the link to a client, false if it wasn't connected
+    pub fn disconnect(&mut self, client: ClientId) -> bool {
+        self.clients.remove(&client).is_some()
+    }
+
+    pub fn send(&mut self, client: ClientId, message: &ServerMessage) {
+        if let Some(transport) = self.clients.get_mut(&client)
+            && let Err(err) = transport.send(&encode_message(message))
+        {
+            warn!("Could not send to {:?}: {}", client, err);
+        }
+    }
+
+    pub fn broadcast(&mut self, message: &ServerMessage) {
+        let frame = encode_message(message);
+        for (client, transport) in &mut self.clients {
+            if let Err(err) = transport.send(&frame) {
+                warn!("Could not send to {:?}: {}", client, err);
+            }
+        }
+    }
+
+    // Send to every client but one
+    pub fn broadcast_except(&mut self, except: ClientId, message: &ServerMessage) {
+        let frame = encode_message(message);
+        for (client, transport) in &mut self.clients {
+            if *client != except
+                && let Err(err) = transport.send(&frame)
+            {
+                warn!("Could not send to {:?}: {}", client, err);
+            }
+        }
+    }
+
+    // Messages received from every client, and the clients that went away
+    pub fn receive(&mut self) -> (Vec<(ClientId, ClientMessage)>, Vec<ClientId>) {
+        let mut messages = Vec::new();
+        let mut disconnected = Vec::new();
+        for (client, transport) in &mut self.clients {
+            loop {
+                match transport.recv() {
+                    Ok(Some(frame)) => match decode_message(&frame) {
+                        Some(message) => messages.push((*client, message)),
+                        None => warn!("Dropped malformed message from {:?}", client),
+                    },
+                    Ok(None) => break,
+                    Err(TransportError::Disconnected) => {
+                        disconnected.push(*client);
+                        break;
+                    }
+                    Err(err) => {
+                        warn!("Network error from {:?}: {}", client, err);
+                        break;
+                    }
+                }
+            }
+        }
+        for client in &disconnected {
+            self.clients.remove(client);
+        }
+        disconnected.append(&mut self.replaced);
+        (messages, disconnected)
+    }
+}
+
+// Server side chunk: heights and collision only, the server never builds meshes
+#[derive(Debug)]
+pub struct ServerChunk {
+    pub collider: Heightfield,
+}
+
+#[derive(Resource, Default)]
+pub struct ServerChunks {
+    pub streamer: ChunkStreamer,
+    pub chunks: HashMap<ChunkCoords, ServerChunk>,
+    dirty: HashSet<ChunkCoords>,
+}
+
+impl ServerChunks {
+    // Collision height at a world position, if its chunk is loaded
+    pub fn height_at(&self, world_x: f32, world_z: f32) -> Option<f32> {
+        let coords = mesh_chunk_coords(world_x, world_z, CHUNK_SIZE);
+        self.chunks.get(&coords)?.collider.height_at(world_x, world_z)
+    }
+
+    // Rebuild a loaded chunk from the world data on the next update, false if it isn't loaded
+    pub fn regenerate(&mut self, coords: ChunkCoords) -> bool {
+        let loaded = self.chunks.contains_key(&coords);
+        if loaded {
+            self.dirty.insert(coords);
+        }
+        loaded
+    }
+}
+
+// Same grid as the client's full resolution mesh, so flat chunks get a lighter collider too
+pub fn generate_server_chunk(server_world: &ServerWorld, coords: ChunkCoords) -> ServerChunk {
+    let height = |x, z| server_world.height(x, z);
+    let cells = chunk_subdivisions(chunk_roughness(coords.0, coords.1, height), 0);
+    ServerChunk {
+        collider: Heightfield::sample(coords.0, coords.1, CHUNK_SIZE, cells, height),
+    }
+}
+
+// The terrain settings, regions and roads are part of the world data, worlds saved before
+// regions existed get the default ones. The save's terrain edits are single player's, a
+// dedicated server loads its own from the server store.
+fn load_world(
+    save: Res<LoadedSave>,
+    store: Option<Res<ServerStore>>,
+    mut server_world: ResMut<ServerWorld>,
+) {
+    server_world.set_world_gen(save.0.worldgen);
+    if let Some(regions) = &save.0.regions {
+        server_world.regions = regions.clone();
+    }
+    server_world.roads = save.0.roads.clone();
+    if store.is_none() {
+        for edit in &save.0.terrain_edits {
+            server_world.add_edit(*edit);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn process_client_messages(
+    mut connections: ResMut<ClientConnections>,
+    mut server_world: ResMut<ServerWorld>,
+    mut server_chunks: ResMut<ServerChunks>,
+    mut store: Option<ResMut<ServerStore>>,
+    settings: Res<NetworkSettings>,
+    limits: Res<MovementLimits>,
+    border: Res<WorldBorder>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_secs_f64();
+    let (messages, disconnected) = connections.receive();
+    let _span = info_span!("server_process_messages", pending = messages.len()).entered();
+    for client in disconnected {
+        info!("Lost connection to player {:?}, holding them for {} s", client, RECONNECT_GRACE);
+        server_world.hold(client, now);
+        server_world.pending_accounts.remove(&client);
+    }
+    for (client, message) in messages {
+        let introduced = server_world.players.contains_key(&client) || server_world.pending_accounts.contains_key(&client);
+        if !introduced && !matches!(message, ClientMessage::Hello { .. }) {
+            // Left over from a link the server dropped, the client reconnects and says hello
+            if connections.disconnect(client) {
+                info!("Closed the link to {:?}, it didn't say hello first", client);
+            }
+            continue;
+        }
+        // A held player is back: catch them up on what they missed
+        if let Some(player) = server_world.players.get_mut(&client)
+            && let Some(held) = player.held.take()
+        {
+            info!("Player {:?} resumed their session after {:.1} s", client, now - held.since);
+            connections.send(client, &ServerMessage::Joined { resumed: true });
+            connections.send(client, &ServerMessage::WorldGen { settings: server_world.worldgen });
+            connections.send(client, &server_world.regions_message());
+            connections.send(client, &ServerMessage::WorldBorder { border: *border });
+            for edit in &server_world.edits[held.edits.min(server_world.edits.len())..] {
+                connections.send(client, &ServerMessage::RemoteEdit { edit: *edit });
+            }
+        }
+        match message {
+            ClientMessage::Hello { account } => {
+                // Keys are taken on trust, so at least an account's live session keeps it
+                if server_world.plays_elsewhere(account, client) {
+                    warn!("Refused {:?}, its account is already playing", client);
+                    connections.send(client, &ServerMessage::AccountInUse);
+                    connections.disconnect(client);
+                    continue;
+                }
+                connections.send(client, &ServerMessage::Welcome { client });
+                if server_world.players.contains_key(&client) {
+                    continue;
+                }
+                // Remote clients come back on a new link, and so with a new id
+                if let Some(previous) = server_world.resume_as(account, client) {
+                    info!("Player {:?} is back as {:?}", previous, client);
+                    connections.broadcast_except(client, &ServerMessage::PlayerLeft { client: previous });
+                    if let Some(store) = store.as_mut() {
+                        store.mark_player(client);
+                    }
+                } else {
+                    server_world.pending_accounts.insert(client, account);
+                }
+            }
+            ClientMessage::PlayerState { mut position, yaw } => {
+                // The collision grid where the player's chunk is loaded, the exact height otherwise
+                let ground = server_chunks.height_at(position.x, position.z)
+                    .unwrap_or_else(|| server_world.height(position.x, position.z));
+                if let Some(player) = server_world.players.get_mut(&client) {
+                    let elapsed = player.movement.elapsed(now);
+                    let teleport = player.movement.take_teleport();
+                    let trusted = connections.is_host(client);
+                    let checked = limits.enabled && !trusted && !teleport;
+                    let mut corrected = false;
+                    if checked && let MoveVerdict::Corrected(allowed) = limits.check(player.position, position, elapsed, ground) {
+                        debug!("Corrected {:?} from {} to {}", client, position, allowed);
+                        if player.movement.record_correction(now) {
+                            warn!("Player {:?} keeps moving faster than allowed, near {}", client, allowed);
+                        }
+                        position = allowed;
+                        corrected = true;
+                    }
+                    // Nobody walks past the world border, the host's client keeps its player in
+                    if !trusted && let Some(inside) = border.push_inside(position, PLAYER_RADIUS) {
+                        position = inside;
+                        corrected = true;
+                    }
+                    if corrected {
+                        connections.send(client, &ServerMessage::PositionCorrected { position });
+                    }
+                    player.position = position;
+                    player.history.push(Snapshot { time: now, position, yaw });
+                } else {
+                    info!("Player {:?} joined", client);
+                    let mut player = ServerPlayer {
+                        position,
+                        inventory: None,
+                        history: SnapshotBuffer::default(),
+                        movement: MovementCheck::default(),
+                        held: None,
+                        account: server_world.pending_accounts.remove(&client),
+                    };
+                    player.movement.elapsed(now);
+                    connections.send(client, &ServerMessage::Joined { resumed: false });
+                    connections.send(client, &ServerMessage::WorldGen { settings: server_world.worldgen });
+                    let stored = store.as_ref().zip(player.account).map(|(store, account)| store.load_account(account));
+                    match stored.transpose() {
+                        Ok(Some(Some(account))) => {
+                            player.position = account.position;
+                            player.inventory = account.inventory.clone();
+                            connections.send(client, &ServerMessage::AccountRestored {
+                                position: account.position,
+                                inventory: account.inventory,
+                            });
+                        }
+                        Ok(_) => {}
+                        Err(err) => warn!("Could not load player {:?}: {}", client, err),
+                    }
+                    player.history.push(Snapshot { time: now, position: player.position, yaw });
+                    server_world.players.insert(client, player);
+                    connections.send(client, &server_world.regions_message());
+                    connections.send(client, &ServerMessage::WorldBorder { border: *border });
+                    // Edits made before the player joined, or kept from previous runs
+                    for edit in &server_world.edits {
+                        connections.send(client, &ServerMessage::RemoteEdit { edit: *edit });
+                    }
+                }
+                connections.broadcast_except(client, &ServerMessage::PlayerMoved { client, position, yaw, time: now });
+            }
+            ClientMessage::Respawned => {
+                if let Some(player) = server_world.players.get_mut(&client)
+                    && !player.movement.allow_respawn(now)
+                {
+                    warn!("Player {:?} respawns too often, not letting it teleport", client);
+                }
+            }
+            ClientMessage::InventoryState { items } => {
+                if let Some(player) = server_world.players.get_mut(&client) {
+                    player.inventory = Some(items);
+                    if let Some(store) = store.as_mut() {
+                        store.mark_player(client);
+                    }
+                }
+            }
+            ClientMessage::RockThrown { origin, velocity, view_time } => {
+                let Some(thrower) = server_world.players.get(&client) else {
+                    continue;
+                };
+                if origin.distance(thrower.position) > MAX_THROW_REACH || velocity.length() > THROW_SPEED * 1.05 {
+                    info!("Ignored rock thrown by {:?} from out of reach", client);
+                    continue;
+                }
+                if let Some((target, position)) = server_world.rewind_rock_hit(client, origin, velocity, view_time, now, &settings) {
+                    connections.send(target, &ServerMessage::RockHit { by: client, position });
+                }
+            }
+            ClientMessage::SetWorldGen { settings } => {
+                // Everyone's terrain changes, so only the host may do it
+                if !connections.is_host(client) {
+                    info!("Ignored new terrain settings from {:?}", client);
+                    continue;
+                }
+                info!("Regenerating the world with seed {}", settings.seed);
+                server_world.set_world_gen(settings);
+                let loaded: Vec<ChunkCoords> = server_chunks.chunks.keys().copied().collect();
+                server_chunks.dirty.extend(loaded);
+                connections.broadcast(&ServerMessage::WorldGen { settings });
+            }
+            ClientMessage::Emote { emote } => {
+                connections.broadcast_except(client, &ServerMessage::RemoteEmote { client, emote });
+            }
+            ClientMessage::TerrainEdit { seq, edit } => {
+                let response = server_world.handle_edit(client, seq, edit);
+                let applied = match &response {
+                    ServerMessage::EditRejected { reason, .. } => {
+                        info!("Rejected terrain edit {} from {:?}: {:?}", seq, client, reason);
+                        None
+                    }
+                    ServerMessage::EditAdjusted { edit, .. } => Some(*edit),
+                    _ => Some(edit),
+                };
+                connections.send(client, &response);
+                // Other clients see the edit as the server applied it
+                if let Some(edit) = applied {
+                    if let Some(store) = store.as_mut() {
+                        store.mark_edit(&edit);
+                    }
+                    server_chunks.dirty.extend(edit.affected_chunks());
+                    connections.broadcast_except(client, &ServerMessage::RemoteEdit { edit });
+                }
+            }
+        }
+    }
+}
+
+// Players who didn't come back in time leave for good
+fn release_held_players(
+    mut connections: ResMut<ClientConnections>,
+    mut server_world: ResMut<ServerWorld>,
+    mut store: Option<ResMut<ServerStore>>,
+    time: Res<Time>,
+) {
+    for client in server_world.expired_sessions(time.elapsed_secs_f64()) {
+        info!("Player {:?} left", client);
+        if let (Some(store), Some(player)) = (store.as_mut(), server_world.players.get(&client))
+            && let Err(err) = store.save_account(player)
+        {
+            warn!("Could not save player {:?}: {}", client, err);
+        }
+        server_world.disconnect(client);
+        connections.broadcast_except(client, &ServerMessage::PlayerLeft { client });
+    }
+}
+
+// Join each ruin the server loads to the nearest other ruin with a road, once per ruin and
+// world. The roads are world data like those carved from the console, so the pair is
+// skipped once a road joins it, in this run or a saved one.
+fn carve_ruin_roads(
+    mut server_world: ResMut<ServerWorld>,
+    mut server_chunks: ResMut<ServerChunks>,
+    mut connections: ResMut<ClientConnections>,
+    mut visited: Local<(Option<WorldGenSettings>, HashSet<ChunkCoords>)>,
+    border: Res<WorldBorder>,
+) {
+    // Other terrain, other ruins
+    if visited.0 != Some(server_world.worldgen) {
+        *visited = (Some(server_world.worldgen), HashSet::new());
+    }
+    let new: Vec<ChunkCoords> = server_chunks.chunks.keys().filter(|coords| !visited.1.contains(*coords)).copied().collect();
+    if new.is_empty() {
+        return;
+    }
+    visited.1.extend(new.iter().copied());
+    let _span = info_span!("server_carve_ruin_roads").entered();
+
+    let mut carved = Vec::new();
+    for coords in new {
+        let Some(ruin) = ruin_in_chunk(coords) else {
+            continue;
+        };
+        let Some((other_coords, other)) = nearest_ruin(coords, &ruin, LINK_DISTANCE).filter(|(other, _)| border.contains_chunk(*other)) else {
+            continue;
+        };
+        // From one ruin's edge to the other's, always from the same end so both find one road
+        let (a, b) = if coords < other_coords { (&ruin, &other) } else { (&other, &ruin) };
+        let direction = (b.center.xz() - a.center.xz()).normalize_or_zero();
+        let (from, to) = (a.center.xz() + direction * a.radius, b.center.xz() - direction * b.radius);
+        if server_world.roads.iter().chain(&carved).any(|road| road.joins(from, to)) {
+            continue;
+        }
+        // Routes follow the natural terrain, like the console's
+        match find_road(from, to, terrain::height) {
+            Some(road) => carved.push(road),
+            None => debug!("No route between the ruins of {:?} and {:?}", coords, other_coords),
+        }
+    }
+    if carved.is_empty() {
+        return;
+    }
+    info!("Carved {} roads between ruins", carved.len());
+    for coords in carved.iter().flat_map(|road| road.affected_chunks()) {
+        server_chunks.regenerate(coords);
+    }
+    server_world.roads.extend(carved);
+    connections.broadcast(&server_world.regions_message());
+}
+
+// Keep chunks loaded around every connected player, sharing chunks between nearby players
+fn stream_server_chunks(
+    server_world: Res<ServerWorld>,
+    border: Res<WorldBorder>,
+    mut server_chunks: ResMut<ServerChunks>,
+) {
+    let _span = info_span!("server_stream_chunks").entered();
+    let server_chunks = &mut *server_chunks;
+
+    let departed: Vec<AnchorId> = server_chunks.streamer.anchor_ids()
+        .filter(|anchor| match anchor {
+            AnchorId::Player(client) => !server_world.players.contains_key(client),
+            _ => false,
+        })
+        .collect();
+    for anchor in departed {
+        let changes = server_chunks.streamer.remove_anchor(anchor);
+        for coords in changes.unload {
+            server_chunks.chunks.remove(&coords);
+        }
+    }
+
+    for (client, player) in &server_world.players {
+        let center = chunk_coords(player.position, CHUNK_SIZE);
+        let changes = server_chunks.streamer.update_anchor(AnchorId::Player(*client), center, SERVER_VIEW_RADIUS);
+        for coords in changes.unload {
+            server_chunks.chunks.remove(&coords);
+        }
+        for coords in changes.load.into_iter().filter(|coords| border.contains_chunk(*coords)) {
+            server_chunks.chunks.insert(coords, generate_server_chunk(&server_world, coords));
+        }
+    }
+
+    // Chunks past a new border are dropped, those inside a wider one generated
+    if border.is_changed() {
+        server_chunks.chunks.retain(|coords, _| border.contains_chunk(*coords));
+        let missing: Vec<ChunkCoords> = server_chunks.streamer.required()
+            .filter(|coords| border.contains_chunk(*coords) && !server_chunks.chunks.contains_key(coords))
+            .collect();
+        for coords in missing {
+            server_chunks.chunks.insert(coords, generate_server_chunk(&server_world, coords));
+        }
+    }
+
+    // Regenerate loaded chunks touched by accepted edits
+    for coords in server_chunks.dirty.drain() {
+        if let Some(chunk) = server_chunks.chunks.get_mut(&coords) {
+            *chunk = generate_server_chunk(&server_world, coords);
+        }
+    }
+}
+
+// Tell every client when the world border changes
+fn send_world_border(
+    border: Res<WorldBorder>,
+    mut connections: ResMut<ClientConnections>,
+) {
+    if border.is_changed() && !border.is_added() {
+        connections.broadcast(&ServerMessage::WorldBorder { border: *border });
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    // Where remote clients send to
+    pub listen: SocketAddr,
+    pub admin: AdminConfig,
+    pub network: NetworkSettings,
+    pub movement: MovementLimits,
+    pub world_border: WorldBorder,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            listen: (Ipv4Addr::UNSPECIFIED, DEFAULT_PORT).into(),
+            admin: default(),
+            network: default(),
+            movement: default(),
+            world_border: default(),
+        }
+    }
+}
+
+// Dedicated server: the world without rendering or a local player, administered from
+// the terminal and the admin port
+pub fn run(options: ServerOptions) {
+    let listener = match UdpListener::bind(options.listen) {
+        Ok(listener) => {
+            if let Ok(address) = listener.local_addr() {
+                println!("Listening for clients on {}", address);
+            }
+            ClientListener { listener, next_client: LOCAL_CLIENT.0 + 1 }
+        }
+        Err(err) => {
+            eprintln!("Could not listen for clients on {}: {}", options.listen, err);
+            std::process::exit(1);
+        }
+    };
+    let save = SaveGame::load_or_exit();
+    let mut time_of_day = TimeOfDay::default();
+    if let Some(saved) = save.time_of_day {
+        time_of_day.hour = saved.hour;
+        time_of_day.day = saved.day;
+    }
+
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / SERVER_TICK_HZ))));
+    app.add_plugins(LogPlugin::default());
+    app.insert_resource(LoadedSave(save));
+    app.insert_resource(time_of_day);
+    app.insert_resource(options.admin);
+    app.insert_resource(options.network);
+    app.insert_resource(options.movement);
+    app.insert_resource(options.world_border);
+    app.insert_resource(listener);
+    app.add_plugins(ServerPlugin);
+    app.add_plugins(ServerStorePlugin);
+    app.add_systems(Update, accept_remote_clients.before(process_client_messages));
+    app.add_plugins(ConsolePlugin);
+    app.add_plugins(AdminPlugin);
+    app.add_systems(Update, advance_time_of_day);
+    app.run();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    // The resources process_client_messages works on, without chunk streaming or a clock
+    fn server() -> World {
+        let mut world = World::new();
+        world.init_resource::<ClientConnections>();
+        world.init_resource::<ServerWorld>();
+        world.init_resource::<ServerChunks>();
+        world.init_resource::<NetworkSettings>();
+        world.init_resource::<MovementLimits>();
+        world.init_resource::<WorldBorder>();
+        world.init_resource::<Time>();
+        world
+    }
+
+    fn connect(world: &mut World, client: ClientId) -> LoopbackTransport {
+        let (client_end, server_end) = loopback_pair();
+        world.resource_mut::<ClientConnections>().connect(client, server_end);
+        client_end
+    }
+
+    // Send `messages` on `link`, run one server tick and return what came back
+    fn exchange(world: &mut World, link: &mut LoopbackTransport, messages: &[ClientMessage]) -> Vec<ServerMessage> {
+        for message in messages {
+            link.send(&encode_message(message)).unwrap();
+        }
+        world.run_system_once(process_client_messages).unwrap();
+        let mut replies = Vec::new();
+        while let Ok(Some(frame)) = link.recv() {
+            replies.push(decode_message(&frame).unwrap());
+        }
+        replies
+    }
+
+    #[test]
+    fn clients_say_hello_before_joining_and_resume_on_a_new_link() {
+        let mut world = server();
+        let account = AccountKey::generate();
+        let hello = ClientMessage::Hello { account };
+        let state = ClientMessage::PlayerState { position: Vec3::new(4.0, 30.0, 4.0), yaw: 0.0 };
+
+        let mut stranger = connect(&mut world, ClientId(1));
+        assert!(exchange(&mut world, &mut stranger, std::slice::from_ref(&state)).is_empty());
+        assert!(matches!(stranger.recv(), Err(TransportError::Disconnected)));
+
+        let mut first = connect(&mut world, ClientId(2));
+        let replies = exchange(&mut world, &mut first, &[hello.clone(), state.clone()]);
+        assert!(matches!(replies[..2], [ServerMessage::Welcome { client: ClientId(2) }, ServerMessage::Joined { resumed: false }]));
+        assert_eq!(world.resource::<ServerWorld>().players[&ClientId(2)].account, Some(account));
+
+        // The link drops and the client comes back on another one, under another id
+        drop(first);
+        world.run_system_once(process_client_messages).unwrap();
+        let mut second = connect(&mut world, ClientId(3));
+        let replies = exchange(&mut world, &mut second, &[hello, state]);
+        assert!(matches!(replies[..2], [ServerMessage::Welcome { client: ClientId(3) }, ServerMessage::Joined { resumed: true }]));
+        let players = &world.resource::<ServerWorld>().players;
+        assert!(players.contains_key(&ClientId(3)) && !players.contains_key(&ClientId(2)));
+    }
+
+    #[test]
+    fn movement_past_the_limits_is_corrected_unless_from_the_host() {
+        let mut world = server();
+        let ground = world.resource::<ServerWorld>().height(4.0, 4.0);
+        let start = Vec3::new(4.0, ground + 2.0, 4.0);
+        let dash = ClientMessage::PlayerState { position: start + Vec3::X * 30.0, yaw: 0.0 };
+        let mut links = Vec::new();
+        for client in [ClientId(1), ClientId(2)] {
+            let mut link = connect(&mut world, client);
+            let hello = ClientMessage::Hello { account: AccountKey::generate() };
+            exchange(&mut world, &mut link, &[hello, ClientMessage::PlayerState { position: start, yaw: 0.0 }]);
+            links.push(link);
+        }
+        world.resource_mut::<ClientConnections>().host = Some(ClientId(2));
+
+        // Thirty metres in no time at all
+        let replies = exchange(&mut world, &mut links[0], std::slice::from_ref(&dash));
+        let corrected = replies.iter().find_map(|reply| match reply {
+            ServerMessage::PositionCorrected { position } => Some(*position),
+            _ => None,
+        });
+        let corrected = corrected.expect("the server puts the player back");
+        assert!(corrected.distance(start) <= MovementLimits::default().slack + 1e-3);
+        assert_eq!(world.resource::<ServerWorld>().players[&ClientId(1)].position, corrected);
+
+        let replies = exchange(&mut world, &mut links[1], &[dash]);
+        assert!(!replies.iter().any(|reply| matches!(reply, ServerMessage::PositionCorrected { .. })));
+    }
+
+    #[test]
+    fn an_account_plays_on_one_link_at_a_time() {
+        let mut world = server();
+        let hello = ClientMessage::Hello { account: AccountKey::generate() };
+        let state = ClientMessage::PlayerState { position: Vec3::new(4.0, 30.0, 4.0), yaw: 0.0 };
+        let mut first = connect(&mut world, ClientId(1));
+        exchange(&mut world, &mut first, &[hello.clone(), state.clone()]);
+
+        let mut second = connect(&mut world, ClientId(2));
+        let replies = exchange(&mut world, &mut second, &[hello, state]);
+        assert!(matches!(replies[..], [ServerMessage::AccountInUse]));
+        assert!(matches!(second.recv(), Err(TransportError::Disconnected)));
+        let players = &world.resource::<ServerWorld>().players;
+        assert!(players.contains_key(&ClientId(1)) && !players.contains_key(&ClientId(2)));
+    }
+
+    #[test]
+    fn ruins_are_joined_to_their_nearest_neighbour_once() {
+        let pairs = (-20..20)
+            .flat_map(|x| (-20..20).map(move |z| (x, z)))
+            .filter_map(|chunk| nearest_ruin(chunk, &ruin_in_chunk(chunk)?, LINK_DISTANCE).map(|(other, _)| (chunk, other)));
+        // Routes fail over deep water, the first pair with one will do
+        let mut carved = None;
+        for (chunk, other) in pairs.take(10) {
+            let mut world = server();
+            for coords in [chunk, other] {
+                let generated = generate_server_chunk(world.resource::<ServerWorld>(), coords);
+                world.resource_mut::<ServerChunks>().chunks.insert(coords, generated);
+            }
+            world.run_system_once(carve_ruin_roads).unwrap();
+            if !world.resource::<ServerWorld>().roads.is_empty() {
+                carved = Some(world);
+                break;
+            }
+        }
+        let mut world = carved.expect("some ruins are joined by a road");
+        let roads = world.resource::<ServerWorld>().roads.clone();
+        // A new run, as after a restart, finds the ruins joined already
+        world.run_system_once(carve_ruin_roads).unwrap();
+        assert_eq!(world.resource::<ServerWorld>().roads, roads);
+    }
+}
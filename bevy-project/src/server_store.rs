@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use crate::client::CHUNK_SIZE;
+use crate::net::{AccountKey, ClientId};
+use crate::server::{ServerPlayer, ServerWorld};
+use crate::streaming::{mesh_chunk_coords, ChunkCoords};
+use crate::terrain_edit::TerrainEdit;
+
+const STORE_DIR: &str = "saves/server";
+const CHUNKS_TREE: &str = "chunks";
+const PLAYERS_TREE: &str = "players";
+const AUTOSAVE_SECONDS: f32 = 60.0;
+
+// Dedicated server persistence: player accounts and edited chunks are kept in an embedded
+// database, written back periodically and flushed once more when the server shuts down
+#[derive(Default, Clone, Debug)]
+pub struct ServerStorePlugin;
+
+impl Plugin for ServerStorePlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<ServerStore>() {
+            app.insert_resource(ServerStore::open(STORE_DIR).unwrap_or_else(|err| {
+                eprintln!("Could not open the world store in {}: {}", STORE_DIR, err);
+                std::process::exit(1);
+            }));
+        }
+        app
+            .insert_resource(AutosaveTimer(Timer::from_seconds(AUTOSAVE_SECONDS, TimerMode::Repeating)))
+            .add_systems(Startup, (load_stored_world, watch_for_shutdown))
+            .add_systems(Update, (exit_on_shutdown_signal, autosave_store))
+            .add_systems(Last, flush_store_on_exit);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("could not access the store: {0}")]
+    Database(#[from] sled::Error),
+    #[error("could not decode a record: {0}")]
+    Encoding(#[from] std::str::Utf8Error),
+    #[error("could not parse a record: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize a record: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+// What a player keeps between sessions
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerAccount {
+    pub position: Vec3,
+    // None until the player's client reported it, the client keeps its starting items then
+    pub inventory: Option<HashMap<String, u32>>,
+}
+
+// A sled database with one tree of edits per chunk and one of accounts per player, so an
+// autosave only rewrites what changed. Records stay RON like the save game, keyed by the
+// chunk's coordinates or the player's account key.
+#[derive(Resource)]
+pub struct ServerStore {
+    root: PathBuf,
+    chunks: sled::Tree,
+    players: sled::Tree,
+    db: sled::Db,
+    dirty_chunks: HashSet<ChunkCoords>,
+    // Connected players to write back, under their account
+    dirty_players: HashSet<ClientId>,
+}
+
+impl ServerStore {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let root = root.into();
+        let db = sled::open(&root)?;
+        Ok(Self {
+            root,
+            chunks: db.open_tree(CHUNKS_TREE)?,
+            players: db.open_tree(PLAYERS_TREE)?,
+            db,
+            dirty_chunks: HashSet::new(),
+            dirty_players: HashSet::new(),
+        })
+    }
+
+    // Every stored terrain edit
+    pub fn load_edits(&self) -> Result<Vec<TerrainEdit>, StoreError> {
+        let mut edits = Vec::new();
+        for record in self.chunks.iter() {
+            let (_, value) = record?;
+            edits.extend(decode_record::<Vec<TerrainEdit>>(&value)?);
+        }
+        Ok(edits)
+    }
+
+    pub fn load_account(&self, account: AccountKey) -> Result<Option<PlayerAccount>, StoreError> {
+        self.players.get(account.0.to_be_bytes())?
+            .map(|value| decode_record(&value))
+            .transpose()
+    }
+
+    // Write a leaving player's account right away, players nobody knows the account of are not kept
+    pub fn save_account(&mut self, player: &ServerPlayer) -> Result<(), StoreError> {
+        if let Some(key) = player.account {
+            self.players.insert(key.0.to_be_bytes(), encode_record(&account(player))?)?;
+            self.db.flush()?;
+        }
+        Ok(())
+    }
+
+    // The chunk holding an edit's center is written on the next flush
+    pub fn mark_edit(&mut self, edit: &TerrainEdit) {
+        self.dirty_chunks.insert(mesh_chunk_coords(edit.center.x, edit.center.y, CHUNK_SIZE));
+    }
+
+    pub fn mark_player(&mut self, client: ClientId) {
+        self.dirty_players.insert(client);
+    }
+
+    // Write every changed record and wait for them to reach the disk, returns how many were written
+    pub fn flush(&mut self, world: &ServerWorld) -> Result<usize, StoreError> {
+        let mut written = 0;
+        for coords in std::mem::take(&mut self.dirty_chunks) {
+            let edits: Vec<&TerrainEdit> = world.edits
+                .iter()
+                .filter(|edit| mesh_chunk_coords(edit.center.x, edit.center.y, CHUNK_SIZE) == coords)
+                .collect();
+            self.chunks.insert(chunk_key(coords), encode_record(&edits)?)?;
+            written += 1;
+        }
+        for client in std::mem::take(&mut self.dirty_players) {
+            if let Some(player) = world.players.get(&client)
+                && let Some(key) = player.account
+            {
+                self.players.insert(key.0.to_be_bytes(), encode_record(&account(player))?)?;
+                written += 1;
+            }
+        }
+        self.db.flush()?;
+        Ok(written)
+    }
+}
+
+fn chunk_key(coords: ChunkCoords) -> [u8; 8] {
+    let mut key = [0; 8];
+    key[..4].copy_from_slice(&coords.0.to_be_bytes());
+    key[4..].copy_from_slice(&coords.1.to_be_bytes());
+    key
+}
+
+fn account(player: &ServerPlayer) -> PlayerAccount {
+    PlayerAccount {
+        position: player.position,
+        inventory: player.inventory.clone(),
+    }
+}
+
+fn decode_record<T: DeserializeOwned>(value: &[u8]) -> Result<T, StoreError> {
+    Ok(ron::from_str(std::str::from_utf8(value)?)?)
+}
+
+fn encode_record(value: &impl Serialize) -> Result<Vec<u8>, StoreError> {
+    Ok(ron::to_string(value)?.into_bytes())
+}
+
+#[derive(Resource)]
+struct AutosaveTimer(Timer);
+
+// Set from the Ctrl+C handler, which runs on its own thread
+#[derive(Resource, Default)]
+struct ShutdownSignal(Arc<AtomicBool>);
+
+fn load_stored_world(
+    store: Res<ServerStore>,
+    mut server_world: ResMut<ServerWorld>,
+) {
+    match store.load_edits() {
+        Ok(edits) => {
+            info!("Loaded {} terrain edits from {}", edits.len(), store.root.display());
+            for edit in edits {
+                server_world.add_edit(edit);
+            }
+        }
+        Err(err) => warn!("Could not load terrain edits from {}: {}", store.root.display(), err),
+    }
+}
+
+fn watch_for_shutdown(mut commands: Commands) {
+    let signal = ShutdownSignal::default();
+    let flag = signal.0.clone();
+    if let Err(err) = ctrlc::set_handler(move || flag.store(true, Ordering::Relaxed)) {
+        warn!("Could not catch Ctrl+C, the store is only saved periodically: {}", err);
+    }
+    commands.insert_resource(signal);
+}
+
+fn exit_on_shutdown_signal(
+    signal: Option<Res<ShutdownSignal>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if signal.is_some_and(|signal| signal.0.swap(false, Ordering::Relaxed)) {
+        info!("Shutting down");
+        exit.send(AppExit::Success);
+    }
+}
+
+fn autosave_store(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    mut store: ResMut<ServerStore>,
+    server_world: Res<ServerWorld>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    // Connected players move all the time, save where they are
+    for client in server_world.players.keys() {
+        store.mark_player(*client);
+    }
+    match store.flush(&server_world) {
+        Ok(written) => debug!("Autosaved {} records", written),
+        Err(err) => warn!("Autosave failed: {}", err),
+    }
+}
+
+fn flush_store_on_exit(
+    mut exits: EventReader<AppExit>,
+    mut store: ResMut<ServerStore>,
+    server_world: Res<ServerWorld>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    for client in server_world.players.keys() {
+        store.mark_player(*client);
+    }
+    match store.flush(&server_world) {
+        Ok(written) => info!("Saved {} records before exiting", written),
+        Err(err) => warn!("Could not save the world before exiting: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::MovementCheck;
+    use crate::snapshot::SnapshotBuffer;
+
+    fn player(account: Option<AccountKey>, position: Vec3) -> ServerPlayer {
+        ServerPlayer {
+            position,
+            inventory: Some(HashMap::from([("stone".to_string(), 3)])),
+            history: SnapshotBuffer::default(),
+            movement: MovementCheck::default(),
+            held: None,
+            account,
+        }
+    }
+
+    // Accounts come back under the player's key whatever connection they join on
+    #[test]
+    fn accounts_are_kept_under_the_player_key() {
+        let root = std::env::temp_dir().join(format!("bevy_game_store_{}", std::process::id()));
+        let (key, position) = (AccountKey(0xfeed), Vec3::new(12.0, 3.0, -40.0));
+        let mut world = ServerWorld::default();
+        world.players.insert(ClientId(7), player(Some(key), position));
+        world.players.insert(ClientId(8), player(None, Vec3::ZERO));
+        let mut store = ServerStore::open(&root).unwrap();
+        store.mark_player(ClientId(7));
+        store.mark_player(ClientId(8));
+        assert_eq!(store.flush(&world).unwrap(), 1);
+        let account = store.load_account(key).unwrap().expect("stored account");
+        assert_eq!(account.position, position);
+        assert_eq!(account.inventory, world.players[&ClientId(7)].inventory);
+        assert_eq!(store.load_account(AccountKey(7)).unwrap(), None);
+        drop(store);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,600 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use bevy::pbr::ScreenSpaceAmbientOcclusionQualityLevel;
+use bevy::prelude::*;
+use bevy::render::renderer::{RenderAdapterInfo, RenderInstance, WgpuWrapper};
+use bevy::render::settings::{Backends, PowerPreference, WgpuSettings};
+use bevy::window::{Monitor, PrimaryMonitor};
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use crate::input_map::InputMap;
+use crate::net::AccountKey;
+use crate::snapshot::NetworkSettings;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+
+const SETTINGS_PATH: &str = "settings.ron";
+
+#[derive(Default, Clone, Debug)]
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world().contains_resource::<GameSettings>() {
+            app.insert_resource(GameSettings::load());
+        }
+        app
+            .register_ui_window(UiWindow {
+                id: SETTINGS_WINDOW,
+                title: "Settings",
+                shortcut: None,
+                open: false,
+            })
+            .add_systems(Update, settings_ui_system)
+            .add_systems(Last, save_settings_on_change);
+    }
+}
+
+// User settings persisted to `settings.ron` in the working directory
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct GameSettings {
+    pub graphics: GraphicsSettings,
+    pub display: DisplaySettings,
+    pub capture: CaptureSettings,
+    pub audio: AudioSettings,
+    // Key bindings per input context
+    pub controls: InputMap,
+    pub network: NetworkSettings,
+    pub saving: SaveSettings,
+    pub accessibility: AccessibilitySettings,
+    pub notifications: NotificationSettings,
+    // Identifies the player to servers, set on the first run
+    pub account: Option<AccountKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QualityPreset {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl QualityPreset {
+    pub const ALL: [QualityPreset; 3] = [QualityPreset::Low, QualityPreset::Medium, QualityPreset::High];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QualityPreset::Low => "Low",
+            QualityPreset::Medium => "Medium",
+            QualityPreset::High => "High",
+        }
+    }
+
+    // Triplanar sampling costs three texture fetches per layer on steep terrain
+    pub fn triplanar_terrain(&self) -> bool {
+        *self != QualityPreset::Low
+    }
+
+    // Edge size of the generated reflection cubemap
+    pub fn reflection_probe_size(&self) -> u32 {
+        match self {
+            QualityPreset::Low => 16,
+            QualityPreset::Medium => 32,
+            QualityPreset::High => 64,
+        }
+    }
+
+    // SSAO sample count; Low skips it, it needs prepasses and turns MSAA off
+    pub fn ambient_occlusion(&self) -> Option<ScreenSpaceAmbientOcclusionQualityLevel> {
+        match self {
+            QualityPreset::Low => None,
+            QualityPreset::Medium => Some(ScreenSpaceAmbientOcclusionQualityLevel::Medium),
+            QualityPreset::High => Some(ScreenSpaceAmbientOcclusionQualityLevel::High),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub quality: QualityPreset,
+    // Asset path of the terrain color palette, swap it to re-theme the world
+    pub terrain_palette: String,
+    // Lower the render resolution when frames get slower than `target_fps`
+    pub dynamic_resolution: bool,
+    pub target_fps: u32,
+    // Screen space ambient occlusion, when the quality preset allows it
+    pub ambient_occlusion: bool,
+    // Vertical field of view in degrees
+    pub fov: f32,
+    // Widen the view while sprinting or falling fast
+    pub fov_kick: bool,
+    // Lift flat chunk grids from height maps in the vertex shader instead of building meshes
+    pub gpu_terrain: bool,
+    // Graphics API and GPU to render with, read once at startup
+    pub backend: GpuBackend,
+    pub gpu_preference: GpuPreference,
+}
+
+impl GraphicsSettings {
+    pub fn ambient_occlusion_level(&self) -> Option<ScreenSpaceAmbientOcclusionQualityLevel> {
+        self.quality.ambient_occlusion().filter(|_| self.ambient_occlusion)
+    }
+
+    // Renderer setup for the chosen backend and GPU. Auto keeps wgpu's choice, which the
+    // WGPU_BACKEND and WGPU_POWER_PREF environment variables still override.
+    pub fn wgpu_settings(&self, available: &AvailableBackends) -> WgpuSettings {
+        let mut wgpu = WgpuSettings::default();
+        if let Some(backends) = self.backend.backends().filter(|_| available.has(self.backend)) {
+            wgpu.backends = Some(backends);
+        }
+        if let Some(power_preference) = self.gpu_preference.power_preference() {
+            wgpu.power_preference = power_preference;
+        }
+        wgpu
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            quality: QualityPreset::default(),
+            terrain_palette: "palettes/default.palette.ron".to_string(),
+            dynamic_resolution: false,
+            target_fps: 60,
+            ambient_occlusion: true,
+            fov: 45.0,
+            fov_kick: true,
+            gpu_terrain: false,
+            backend: GpuBackend::default(),
+            gpu_preference: GpuPreference::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GpuBackend {
+    #[default]
+    Auto,
+    Vulkan,
+    Dx12,
+    Metal,
+    Gl,
+}
+
+impl GpuBackend {
+    pub const ALL: [GpuBackend; 5] = [GpuBackend::Auto, GpuBackend::Vulkan, GpuBackend::Dx12, GpuBackend::Metal, GpuBackend::Gl];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GpuBackend::Auto => "Auto",
+            GpuBackend::Vulkan => "Vulkan",
+            GpuBackend::Dx12 => "DirectX 12",
+            GpuBackend::Metal => "Metal",
+            GpuBackend::Gl => "OpenGL",
+        }
+    }
+
+    fn backends(&self) -> Option<Backends> {
+        match self {
+            GpuBackend::Auto => None,
+            GpuBackend::Vulkan => Some(Backends::VULKAN),
+            GpuBackend::Dx12 => Some(Backends::DX12),
+            GpuBackend::Metal => Some(Backends::METAL),
+            GpuBackend::Gl => Some(Backends::GL),
+        }
+    }
+}
+
+// Graphics APIs with at least one GPU on this machine. A backend the platform supports
+// can still have no adapter, missing drivers or a GPU too old for it, and the renderer
+// would fail to start on it. Detected by client::run, which inserts it.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct AvailableBackends(pub Backends);
+
+impl AvailableBackends {
+    // Loads every graphics driver, done once before the renderer starts
+    pub fn detect() -> Self {
+        // An instance on every backend, like the one the renderer makes for Auto
+        let instance = RenderInstance(Arc::new(WgpuWrapper::new(default())));
+        let backends = instance.enumerate_adapters(Backends::all())
+            .iter()
+            .fold(Backends::empty(), |found, adapter| found | adapter.get_info().backend.into());
+        Self(backends)
+    }
+
+    // Auto is always there, wgpu then picks from what it finds
+    pub fn has(&self, backend: GpuBackend) -> bool {
+        backend.backends().is_none_or(|backends| self.0.contains(backends))
+    }
+}
+
+// GPU asked for when there are several, like a laptop's integrated and discrete ones
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GpuPreference {
+    #[default]
+    Auto,
+    HighPerformance,
+    LowPower,
+}
+
+impl GpuPreference {
+    pub const ALL: [GpuPreference; 3] = [GpuPreference::Auto, GpuPreference::HighPerformance, GpuPreference::LowPower];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GpuPreference::Auto => "Auto",
+            GpuPreference::HighPerformance => "High performance (discrete)",
+            GpuPreference::LowPower => "Low power (integrated)",
+        }
+    }
+
+    fn power_preference(&self) -> Option<PowerPreference> {
+        match self {
+            GpuPreference::Auto => None,
+            GpuPreference::HighPerformance => Some(PowerPreference::HighPerformance),
+            GpuPreference::LowPower => Some(PowerPreference::LowPower),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [DisplayMode::Windowed, DisplayMode::Borderless, DisplayMode::Fullscreen];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Borderless => "Borderless",
+            DisplayMode::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub mode: DisplayMode,
+    // Physical size of the window, or the video mode in fullscreen
+    pub resolution: (u32, u32),
+    pub vsync: bool,
+    // Frames per second limit, 0 means unlimited
+    pub fps_cap: u32,
+    // Stop the game and free the cursor while the window is in the background
+    pub pause_on_unfocus: bool,
+    // Frames per second while the window is in the background, 0 means unlimited
+    pub background_fps: u32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            mode: DisplayMode::Windowed,
+            resolution: (1280, 720),
+            vsync: true,
+            fps_cap: 0,
+            pause_on_unfocus: true,
+            background_fps: 10,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct CaptureSettings {
+    pub output_dir: String,
+    pub clip_seconds: f32,
+    pub clip_fps: u32,
+    // Clip frames are downscaled to this width to keep GIFs small
+    pub clip_max_width: u32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            output_dir: "captures".to_string(),
+            clip_seconds: 5.0,
+            clip_fps: 10,
+            clip_max_width: 480,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AudioSettings {
+    // 0 to 1, the other volumes are scaled by the master volume
+    pub master_volume: f32,
+    pub music_volume: f32,
+    // Impacts and other sound effects
+    pub effects_volume: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.6,
+            effects_volume: 0.8,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct SaveSettings {
+    // Save every `autosave_minutes`, and when a new in-game day starts
+    pub autosave: bool,
+    pub autosave_minutes: f32,
+    // Previous saves kept next to the world, newest first
+    pub backups: usize,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            autosave: true,
+            autosave_minutes: 5.0,
+            backups: 3,
+        }
+    }
+}
+
+// Color vision deficiency the colors shown are adjusted for
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorVision {
+    #[default]
+    Normal,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorVision {
+    pub const ALL: [ColorVision; 4] = [ColorVision::Normal, ColorVision::Protanopia, ColorVision::Deuteranopia, ColorVision::Tritanopia];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ColorVision::Normal => "Normal",
+            ColorVision::Protanopia => "Protanopia (red)",
+            ColorVision::Deuteranopia => "Deuteranopia (green)",
+            ColorVision::Tritanopia => "Tritanopia (blue)",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct NotificationSettings {
+    // Toasts in the corner of the screen, the log window keeps them either way
+    pub toasts: bool,
+    // Seconds a toast stays up before fading out
+    pub toast_seconds: f32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            toasts: true,
+            toast_seconds: 5.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    // Size of every menu, window and HUD element, 1 being the default
+    pub ui_scale: f32,
+    pub color_vision: ColorVision,
+    // Show gameplay prompts in large text at the center of the screen
+    pub large_prompts: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.0,
+            color_vision: ColorVision::Normal,
+            large_prompts: false,
+        }
+    }
+}
+
+impl GameSettings {
+    // Read the settings file, falling back to defaults when missing or invalid
+    pub fn load() -> Self {
+        let path = Path::new(SETTINGS_PATH);
+        if !path.exists() {
+            return Self::default();
+        }
+        match fs::read_to_string(path).map_err(|e| e.to_string())
+            .and_then(|text| ron::from_str(&text).map_err(|e| e.to_string()))
+        {
+            Ok(settings) => settings,
+            Err(err) => {
+                warn!("Could not read {}: {}, using default settings", SETTINGS_PATH, err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("Could not serialize settings: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = fs::write(SETTINGS_PATH, text) {
+            warn!("Could not write {}: {}", SETTINGS_PATH, err);
+        }
+    }
+}
+
+fn save_settings_on_change(settings: Res<GameSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        settings.save();
+    }
+}
+
+// Resolutions offered when the monitor doesn't report its video modes
+const FALLBACK_RESOLUTIONS: [(u32, u32); 5] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440), (3840, 2160)];
+const FPS_CAPS: [u32; 5] = [0, 30, 60, 120, 144];
+const BACKGROUND_FPS_CAPS: [u32; 4] = [0, 5, 10, 30];
+const SETTINGS_WINDOW: &str = "settings";
+
+// Resolutions supported by the primary monitor, largest first
+fn available_resolutions(monitor: Option<&Monitor>) -> Vec<(u32, u32)> {
+    let mut resolutions: Vec<(u32, u32)> = monitor
+        .map(|monitor| monitor.video_modes.iter()
+            .map(|mode| (mode.physical_size.x, mode.physical_size.y))
+            .collect())
+        .unwrap_or_default();
+    if resolutions.is_empty() {
+        resolutions.extend(FALLBACK_RESOLUTIONS);
+    }
+    resolutions.sort_unstable_by(|a, b| b.cmp(a));
+    resolutions.dedup();
+    resolutions
+}
+
+fn settings_ui_system(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut settings: ResMut<GameSettings>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    available: Res<AvailableBackends>,
+    monitor_query: Query<&Monitor, With<PrimaryMonitor>>,
+) {
+    let Some(window) = windows.window(SETTINGS_WINDOW) else {
+        return;
+    };
+    // Only flag the resource as changed on real edits, so the file isn't rewritten every frame
+    let mut changed = false;
+    let current = settings.bypass_change_detection();
+
+    window.show(contexts.ctx_mut(), |ui| {
+        ui.heading("Graphics");
+        ui.horizontal(|ui| {
+            ui.label("Quality");
+            for preset in QualityPreset::ALL {
+                changed |= ui.radio_value(&mut current.graphics.quality, preset, preset.label()).changed();
+            }
+        });
+        ui.add_enabled_ui(current.graphics.quality.ambient_occlusion().is_some(), |ui| {
+            changed |= ui.checkbox(&mut current.graphics.ambient_occlusion, "Ambient occlusion").changed();
+        });
+        changed |= ui.checkbox(&mut current.graphics.dynamic_resolution, "Dynamic resolution").changed();
+        ui.add_enabled_ui(current.graphics.dynamic_resolution, |ui| {
+            changed |= ui.add(egui::Slider::new(&mut current.graphics.target_fps, 30..=144).text("Target FPS")).changed();
+        });
+        changed |= ui.add(egui::Slider::new(&mut current.graphics.fov, 30.0..=100.0).text("Field of view")).changed();
+        changed |= ui.checkbox(&mut current.graphics.fov_kick, "Widen view when sprinting").changed();
+        changed |= ui.checkbox(&mut current.graphics.gpu_terrain, "GPU terrain displacement").changed();
+        egui::ComboBox::from_label("Graphics API")
+            .selected_text(current.graphics.backend.label())
+            .show_ui(ui, |ui| {
+                for backend in GpuBackend::ALL.into_iter().filter(|backend| available.has(*backend)) {
+                    changed |= ui.selectable_value(&mut current.graphics.backend, backend, backend.label()).changed();
+                }
+            });
+        egui::ComboBox::from_label("GPU")
+            .selected_text(current.graphics.gpu_preference.label())
+            .show_ui(ui, |ui| {
+                for preference in GpuPreference::ALL {
+                    changed |= ui.selectable_value(&mut current.graphics.gpu_preference, preference, preference.label()).changed();
+                }
+            });
+        if let Some(adapter) = &adapter {
+            ui.weak(format!("Rendering on {} ({:?}), changes apply after a restart", adapter.name, adapter.backend));
+        }
+        ui.separator();
+        ui.heading("Display");
+        let display = &mut current.display;
+        ui.horizontal(|ui| {
+            ui.label("Mode");
+            for mode in DisplayMode::ALL {
+                changed |= ui.radio_value(&mut display.mode, mode, mode.label()).changed();
+            }
+        });
+        let resolution_label = |(width, height): (u32, u32)| format!("{} x {}", width, height);
+        egui::ComboBox::from_label("Resolution")
+            .selected_text(resolution_label(display.resolution))
+            .show_ui(ui, |ui| {
+                for resolution in available_resolutions(monitor_query.get_single().ok()) {
+                    changed |= ui.selectable_value(&mut display.resolution, resolution, resolution_label(resolution)).changed();
+                }
+            });
+        changed |= ui.checkbox(&mut display.vsync, "VSync").changed();
+        let fps_label = |cap: u32| if cap == 0 { "Unlimited".to_string() } else { cap.to_string() };
+        egui::ComboBox::from_label("FPS cap")
+            .selected_text(fps_label(display.fps_cap))
+            .show_ui(ui, |ui| {
+                for cap in FPS_CAPS {
+                    changed |= ui.selectable_value(&mut display.fps_cap, cap, fps_label(cap)).changed();
+                }
+            });
+        egui::ComboBox::from_label("Background FPS")
+            .selected_text(fps_label(display.background_fps))
+            .show_ui(ui, |ui| {
+                for cap in BACKGROUND_FPS_CAPS {
+                    changed |= ui.selectable_value(&mut display.background_fps, cap, fps_label(cap)).changed();
+                }
+            });
+        changed |= ui.checkbox(&mut display.pause_on_unfocus, "Pause when the window loses focus").changed();
+        ui.separator();
+        ui.heading("Audio");
+        changed |= ui.add(egui::Slider::new(&mut current.audio.master_volume, 0.0..=1.0).text("Master volume")).changed();
+        changed |= ui.add(egui::Slider::new(&mut current.audio.music_volume, 0.0..=1.0).text("Music volume")).changed();
+        changed |= ui.add(egui::Slider::new(&mut current.audio.effects_volume, 0.0..=1.0).text("Effects volume")).changed();
+        ui.separator();
+        ui.heading("Capture");
+        ui.horizontal(|ui| {
+            ui.label("Output directory");
+            changed |= ui.text_edit_singleline(&mut current.capture.output_dir).changed();
+        });
+        changed |= ui.add(egui::Slider::new(&mut current.capture.clip_seconds, 1.0..=15.0).text("Clip length (s)")).changed();
+        ui.separator();
+        ui.heading("Saving");
+        changed |= ui.checkbox(&mut current.saving.autosave, "Autosave").changed();
+        ui.add_enabled_ui(current.saving.autosave, |ui| {
+            changed |= ui.add(egui::Slider::new(&mut current.saving.autosave_minutes, 1.0..=60.0).text("Autosave every (min)")).changed();
+        });
+        changed |= ui.add(egui::Slider::new(&mut current.saving.backups, 0..=10).text("Backups kept")).changed();
+        ui.separator();
+        ui.heading("Notifications");
+        changed |= ui.checkbox(&mut current.notifications.toasts, "Show notification toasts").changed();
+        ui.add_enabled_ui(current.notifications.toasts, |ui| {
+            changed |= ui.add(egui::Slider::new(&mut current.notifications.toast_seconds, 1.0..=15.0).text("Shown for (s)")).changed();
+        });
+        ui.separator();
+        ui.heading("Accessibility");
+        let accessibility = &mut current.accessibility;
+        // Applied once the slider is let go, the window would otherwise resize under the pointer
+        let scale = ui.add(egui::Slider::new(&mut accessibility.ui_scale, 0.75..=2.0).text("UI scale"));
+        changed |= scale.drag_stopped() || (scale.changed() && !scale.dragged());
+        egui::ComboBox::from_label("Color vision")
+            .selected_text(accessibility.color_vision.label())
+            .show_ui(ui, |ui| {
+                for vision in ColorVision::ALL {
+                    changed |= ui.selectable_value(&mut accessibility.color_vision, vision, vision.label()).changed();
+                }
+            });
+        changed |= ui.checkbox(&mut accessibility.large_prompts, "Large center-screen prompts").changed();
+    });
+
+    if changed {
+        settings.set_changed();
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use crate::camera::FreeCamera;
+use crate::day_night::{advance_time_of_day, TimeOfDay};
+use crate::palette::ActiveTerrainPalette;
+use crate::reflection_probe;
+use crate::terrain;
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle};
+
+const UPDATE_INTERVAL: f32 = 0.25;
+// Brightness of the sky's light, and of sunlight bounced off the ground at noon, in the
+// ambient light's units
+const SKY_BRIGHTNESS: f32 = 250.0;
+const BOUNCE_BRIGHTNESS: f32 = 120.0;
+// Ground sampled around the camera for the bounce color: rings of this many points
+const GROUND_RINGS: [f32; 3] = [8.0, 24.0, 48.0];
+const GROUND_POINTS: usize = 8;
+// Moving this far resamples the ground around the camera
+const RESAMPLE_DISTANCE: f32 = 16.0;
+// Changes smaller than this don't re-upload the terrain material
+const LIGHT_STEP: f32 = 0.5;
+
+// Cheap global illumination for the terrain: a hemisphere light, with the sky's color from
+// the sky model above and the sunlight bounced off the surrounding terrain's dominant color
+// below. Shaded slopes keep some color at sunrise and sunset, when the ambient light is low.
+#[derive(Default, Clone, Debug)]
+pub struct SkyLightPlugin;
+
+impl Plugin for SkyLightPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<SkyLight>()
+            .add_systems(Update, (
+                sample_ground_color,
+                update_sky_light,
+            ).chain().after(advance_time_of_day));
+    }
+}
+
+#[derive(Resource, Debug)]
+struct SkyLight {
+    timer: Timer,
+    // Average terrain color around the camera and where it was sampled
+    ground_color: Vec3,
+    sampled_at: Option<Vec2>,
+}
+
+impl Default for SkyLight {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(UPDATE_INTERVAL, TimerMode::Repeating),
+            ground_color: Vec3::splat(0.3),
+            sampled_at: None,
+        }
+    }
+}
+
+fn sample_ground_color(
+    palette: Res<ActiveTerrainPalette>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut sky_light: ResMut<SkyLight>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let center = camera.translation.xz();
+    if !palette.is_changed() && sky_light.sampled_at.is_some_and(|last| last.distance(center) < RESAMPLE_DISTANCE) {
+        return;
+    }
+
+    let mut total = Vec3::ZERO;
+    let mut count = 0;
+    for radius in GROUND_RINGS {
+        for index in 0..GROUND_POINTS {
+            let angle = index as f32 / GROUND_POINTS as f32 * std::f32::consts::TAU;
+            let point = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+            let [r, g, b, _] = palette.0.color(point.x, point.y, terrain::height(point.x, point.y));
+            total += Vec3::new(r, g, b);
+            count += 1;
+        }
+    }
+    sky_light.ground_color = total / count as f32;
+    sky_light.sampled_at = Some(center);
+}
+
+fn update_sky_light(
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    handle: Res<TerrainMaterialHandle>,
+    mut sky_light: ResMut<SkyLight>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    sky_light.timer.tick(time.delta());
+    if !sky_light.timer.finished() {
+        return;
+    }
+
+    let sun = time_of_day.sun_direction();
+    let daylight = time_of_day.daylight();
+    let sky = reflection_probe::sky_irradiance(sun, daylight) * SKY_BRIGHTNESS;
+    // Ground lit by the sky and the sun, like the sun light's illuminance
+    let sunlit = daylight * daylight * sun.y.max(0.0);
+    let ground = sky_light.ground_color * (sky + Vec3::splat(BOUNCE_BRIGHTNESS * sunlit));
+
+    let Some(material) = materials.get(&handle.0) else {
+        return;
+    };
+    let params = &material.extension.params;
+    if params.sky_light.abs_diff_eq(sky, LIGHT_STEP) && params.ground_light.abs_diff_eq(ground, LIGHT_STEP) {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.extension.params.sky_light = sky;
+        material.extension.params.ground_light = ground;
+    }
+}
@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::campfire::Campfire;
+use crate::day_night::TimeOfDay;
+use crate::interaction::InteractEvent;
+use crate::player::Player;
+use crate::save::SaveRequested;
+
+// Waking up this long after sunrise
+const WAKE_AFTER_SUNRISE: f32 = 0.5;
+// No sleeping with hostile creatures this close
+const HOSTILE_RADIUS: f32 = 30.0;
+const FADE_SECONDS: f32 = 1.2;
+// Time spent on a black screen while the night passes
+const SLEEP_SECONDS: f32 = 1.0;
+const MESSAGE_SECONDS: f32 = 3.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct SleepPlugin;
+
+impl Plugin for SleepPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Sleep>()
+            .add_systems(Update, (
+                start_sleeping,
+                advance_sleep,
+                sleep_overlay_ui,
+            ).chain());
+    }
+}
+
+// Creatures that keep the player from sleeping when close
+#[derive(Component)]
+pub struct Hostile;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum SleepPhase {
+    #[default]
+    Awake,
+    FallingAsleep,
+    Sleeping,
+    WakingUp,
+}
+
+#[derive(Resource, Default)]
+pub struct Sleep {
+    phase: SleepPhase,
+    // Seconds spent in the current phase
+    timer: f32,
+    // Shown for a moment when sleeping isn't possible
+    message: Option<(String, f32)>,
+}
+
+impl Sleep {
+    pub fn is_sleeping(&self) -> bool {
+        self.phase != SleepPhase::Awake
+    }
+
+    // 0 with a clear view, 1 on a black screen
+    fn darkness(&self) -> f32 {
+        let t = (self.timer / FADE_SECONDS).clamp(0.0, 1.0);
+        match self.phase {
+            SleepPhase::Awake => 0.0,
+            SleepPhase::FallingAsleep => t,
+            SleepPhase::Sleeping => 1.0,
+            SleepPhase::WakingUp => 1.0 - t,
+        }
+    }
+
+    fn refuse(&mut self, reason: &str) {
+        info!("Can't sleep: {}", reason);
+        self.message = Some((format!("You can't sleep now, {}", reason), MESSAGE_SECONDS));
+    }
+}
+
+// Using a lit campfire at night goes to sleep until the morning
+fn start_sleeping(
+    mut interactions: EventReader<InteractEvent>,
+    mut sleep: ResMut<Sleep>,
+    time_of_day: Res<TimeOfDay>,
+    campfire_query: Query<&Campfire>,
+    player_query: Query<&GlobalTransform, With<Player>>,
+    hostile_query: Query<&GlobalTransform, With<Hostile>>,
+) {
+    for event in interactions.read() {
+        let Ok(campfire) = campfire_query.get(event.target) else {
+            continue;
+        };
+        if sleep.is_sleeping() || !campfire.lit || !time_of_day.is_night() {
+            continue;
+        }
+        let Ok(player) = player_query.get(event.actor) else {
+            continue;
+        };
+        let hostile_nearby = hostile_query.iter()
+            .any(|hostile| hostile.translation().distance(player.translation()) < HOSTILE_RADIUS);
+        if hostile_nearby {
+            sleep.refuse("there are hostile creatures nearby");
+            continue;
+        }
+
+        info!("Going to sleep");
+        sleep.phase = SleepPhase::FallingAsleep;
+        sleep.timer = 0.0;
+    }
+}
+
+// Fade out, skip the night while the screen is black, fade back in and autosave
+fn advance_sleep(
+    time: Res<Time>,
+    mut sleep: ResMut<Sleep>,
+    mut time_of_day: ResMut<TimeOfDay>,
+    mut save_requests: EventWriter<SaveRequested>,
+) {
+    let dt = time.delta_secs();
+    if let Some((_, remaining)) = &mut sleep.message {
+        *remaining -= dt;
+        if *remaining <= 0.0 {
+            sleep.message = None;
+        }
+    }
+    if !sleep.is_sleeping() {
+        return;
+    }
+
+    sleep.timer += dt;
+    match sleep.phase {
+        SleepPhase::FallingAsleep if sleep.timer >= FADE_SECONDS => {
+            // Morning of the next day, or of this one when sleeping after midnight
+            let hours = (time_of_day.sunrise() + WAKE_AFTER_SUNRISE - time_of_day.hour).rem_euclid(24.0);
+            time_of_day.advance_hours(hours);
+            sleep.phase = SleepPhase::Sleeping;
+            sleep.timer = 0.0;
+        }
+        SleepPhase::Sleeping if sleep.timer >= SLEEP_SECONDS => {
+            sleep.phase = SleepPhase::WakingUp;
+            sleep.timer = 0.0;
+        }
+        SleepPhase::WakingUp if sleep.timer >= FADE_SECONDS => {
+            sleep.phase = SleepPhase::Awake;
+            info!("Woke up on day {}", time_of_day.day);
+            save_requests.send(SaveRequested { reason: "sleep" });
+        }
+        _ => {}
+    }
+}
+
+fn sleep_overlay_ui(
+    mut contexts: EguiContexts,
+    sleep: Res<Sleep>,
+) {
+    let ctx = contexts.ctx_mut();
+    let darkness = sleep.darkness();
+    if darkness > 0.0 {
+        let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("sleep_fade")));
+        painter.rect_filled(ctx.screen_rect(), 0.0, egui::Color32::from_black_alpha((darkness * 255.0) as u8));
+    }
+    if let Some((message, _)) = &sleep.message {
+        egui::Area::new(egui::Id::new("sleep_message"))
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, -60.0])
+            .show(ctx, |ui| {
+                ui.colored_label(egui::Color32::LIGHT_RED, message);
+            });
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Snapshots further back than this are dropped, whatever the settings
+const MAX_HISTORY_SECONDS: f64 = 2.0;
+// Share of the gap to a new clock estimate closed per sample, jumps over a second are taken at once
+const CLOCK_SMOOTHING: f64 = 0.05;
+const CLOCK_RESYNC: f64 = 1.0;
+
+// Timing of remote player movement, on the client and the server, in seconds
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct NetworkSettings {
+    // Remote players are shown this far in the past, so there are snapshots on both sides
+    pub interpolation_delay: f32,
+    // Longest a remote player keeps moving past its last snapshot before stopping
+    pub max_extrapolation: f32,
+    // Furthest back the server rewinds players to check what a shot hit
+    pub max_rewind: f32,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            interpolation_delay: 0.1,
+            max_extrapolation: 0.25,
+            max_rewind: 0.3,
+        }
+    }
+}
+
+// A player's state at a point of the server's clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub time: f64,
+    pub position: Vec3,
+    pub yaw: f32,
+}
+
+// Recent snapshots of a player, oldest first
+#[derive(Component, Debug, Clone, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    // Snapshots arriving late, behind the newest one, are dropped
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.latest().is_some_and(|latest| snapshot.time <= latest.time) {
+            return;
+        }
+        self.snapshots.push_back(snapshot);
+        let oldest = snapshot.time - MAX_HISTORY_SECONDS;
+        self.trim(oldest);
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    // Drop snapshots not needed to sample `time` or later
+    pub fn trim(&mut self, time: f64) {
+        while self.snapshots.get(1).is_some_and(|next| next.time <= time) {
+            self.snapshots.pop_front();
+        }
+    }
+
+    // State at `time`: interpolated between the snapshots around it, or extrapolated from the
+    // last two for at most `max_extrapolation` seconds past the newest
+    pub fn sample(&self, time: f64, max_extrapolation: f32) -> Option<Snapshot> {
+        let first = self.snapshots.front()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        let after = self.snapshots.iter().position(|snapshot| snapshot.time > time);
+        let Some(after) = after else {
+            let last = *self.latest()?;
+            let Some(previous) = self.snapshots.len().checked_sub(2).and_then(|index| self.snapshots.get(index)) else {
+                return Some(last);
+            };
+            let velocity = (last.position - previous.position) / (last.time - previous.time) as f32;
+            let ahead = ((time - last.time) as f32).min(max_extrapolation);
+            return Some(Snapshot { time, position: last.position + velocity * ahead, yaw: last.yaw });
+        };
+        let (from, to) = (self.snapshots[after - 1], self.snapshots[after]);
+        let t = ((time - from.time) / (to.time - from.time)) as f32;
+        Some(Snapshot {
+            time,
+            position: from.position.lerp(to.position, t),
+            yaw: lerp_angle(from.yaw, to.yaw, t),
+        })
+    }
+}
+
+// Turns the short way around
+fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    let delta = (to - from + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI;
+    from + delta * t
+}
+
+// The client's estimate of the server's clock, from the times stamped on snapshots
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct ServerClock {
+    // Server time minus local time
+    offset: Option<f64>,
+}
+
+impl ServerClock {
+    pub fn observe(&mut self, server_time: f64, local_time: f64) {
+        let sample = server_time - local_time;
+        self.offset = Some(match self.offset {
+            Some(offset) if (sample - offset).abs() < CLOCK_RESYNC => offset + (sample - offset) * CLOCK_SMOOTHING,
+            _ => sample,
+        });
+    }
+
+    // Server time now, the local time until a snapshot arrived
+    pub fn now(&self, local_time: f64) -> f64 {
+        local_time + self.offset.unwrap_or(0.0)
+    }
+
+    // Server time remote players are shown at
+    pub fn view_time(&self, local_time: f64, settings: &NetworkSettings) -> f64 {
+        self.now(local_time) - settings.interpolation_delay as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(points: &[(f64, f32)]) -> SnapshotBuffer {
+        let mut buffer = SnapshotBuffer::default();
+        for (time, x) in points {
+            buffer.push(Snapshot { time: *time, position: Vec3::new(*x, 0.0, 0.0), yaw: 0.0 });
+        }
+        buffer
+    }
+
+    #[test]
+    fn interpolates_between_snapshots() {
+        let buffer = buffer(&[(1.0, 0.0), (1.1, 1.0), (1.2, 3.0)]);
+        let sample = buffer.sample(1.15, 0.25).unwrap();
+        assert!((sample.position.x - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn extrapolation_is_capped() {
+        let buffer = buffer(&[(1.0, 0.0), (1.1, 1.0)]);
+        // 10 m/s, extrapolated 0.1 s then capped at 0.25 s
+        assert!((buffer.sample(1.2, 0.25).unwrap().position.x - 2.0).abs() < 1e-4);
+        assert!((buffer.sample(5.0, 0.25).unwrap().position.x - 3.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn late_snapshots_are_dropped() {
+        let buffer = buffer(&[(1.0, 0.0), (1.2, 2.0), (1.1, 9.0)]);
+        assert!((buffer.sample(1.1, 0.0).unwrap().position.x - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn yaw_turns_the_short_way() {
+        let turned = lerp_angle(3.0, -3.0, 0.5);
+        assert!(turned.cos() < -0.99);
+    }
+}
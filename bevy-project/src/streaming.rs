@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use bevy::prelude::*;
+use crate::net::ClientId;
+
+pub type ChunkCoords = (i32, i32);
+
+// Something that keeps chunks loaded around itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnchorId {
+    // An entity with a StreamingAnchor on the local client, like the camera
+    Entity(Entity),
+    // A player connected to the server
+    Player(ClientId),
+}
+
+// Keeps the chunks around an entity loaded on the client, the camera's out to the render
+// distance. Teleport destinations and places picked on the map add their own, and chunks
+// needed by several anchors are loaded once.
+#[derive(Component, Debug, Clone, Default)]
+pub struct StreamingAnchor {
+    // Chunks kept loaded in each direction, the render distance if unset
+    pub radius: Option<i32>,
+    // Anchors that only preload count down and are despawned with their entity
+    pub lifetime: Option<Timer>,
+}
+
+impl StreamingAnchor {
+    pub fn with_radius(radius: i32) -> Self {
+        Self { radius: Some(radius), lifetime: None }
+    }
+
+    // Load the chunks around a place for a few seconds, ahead of arriving there
+    pub fn preload(radius: i32, seconds: f32) -> Self {
+        Self { radius: Some(radius), lifetime: Some(Timer::from_seconds(seconds, TimerMode::Once)) }
+    }
+}
+
+// Chunks that started or stopped being needed after an anchor update
+#[derive(Debug, Default)]
+pub struct ChunkChanges {
+    pub load: Vec<ChunkCoords>,
+    pub unload: Vec<ChunkCoords>,
+}
+
+impl ChunkChanges {
+    pub fn is_empty(&self) -> bool {
+        self.load.is_empty() && self.unload.is_empty()
+    }
+
+    // Add the changes of another anchor update; a chunk one anchor let go of and another
+    // picked up in the meantime neither loads nor unloads
+    pub fn merge(&mut self, other: ChunkChanges) {
+        for chunk in other.load {
+            match self.unload.iter().position(|unloaded| *unloaded == chunk) {
+                Some(index) => { self.unload.swap_remove(index); }
+                None => self.load.push(chunk),
+            }
+        }
+        for chunk in other.unload {
+            match self.load.iter().position(|loaded| *loaded == chunk) {
+                Some(index) => { self.load.swap_remove(index); }
+                None => self.unload.push(chunk),
+            }
+        }
+    }
+}
+
+// Where an anchor is and the chunks it requires
+#[derive(Debug, Default)]
+struct AnchorChunks {
+    center: ChunkCoords,
+    radius: i32,
+    chunks: HashSet<ChunkCoords>,
+}
+
+// Chunk index containing a world position
+pub fn chunk_coords(position: Vec3, chunk_size: f32) -> ChunkCoords {
+    (
+        (position.x / chunk_size).floor() as i32,
+        (position.z / chunk_size).floor() as i32,
+    )
+}
+
+// Chunk whose mesh covers a world position; meshes are centered on their chunk offset
+pub fn mesh_chunk_coords(world_x: f32, world_z: f32, chunk_size: f32) -> ChunkCoords {
+    (
+        ((world_x + chunk_size / 2.0) / chunk_size).floor() as i32,
+        ((world_z + chunk_size / 2.0) / chunk_size).floor() as i32,
+    )
+}
+
+// Reference counts chunks over every streaming anchor: a chunk stays loaded while any anchor needs it
+#[derive(Debug, Default)]
+pub struct ChunkStreamer {
+    anchors: HashMap<AnchorId, AnchorChunks>,
+    ref_counts: HashMap<ChunkCoords, u32>,
+}
+
+impl ChunkStreamer {
+    // Move an anchor (or add it) so it requires the square of chunks within `radius` of `center`
+    pub fn update_anchor(&mut self, id: AnchorId, center: ChunkCoords, radius: i32) -> ChunkChanges {
+        if self.anchors.get(&id).is_some_and(|anchor| anchor.center == center && anchor.radius == radius) {
+            return ChunkChanges::default();
+        }
+        let mut required = HashSet::new();
+        for x in (center.0 - radius)..=(center.0 + radius) {
+            for z in (center.1 - radius)..=(center.1 + radius) {
+                required.insert((x, z));
+            }
+        }
+
+        let previous = self.anchors.remove(&id).unwrap_or_default().chunks;
+        let mut changes = ChunkChanges::default();
+
+        for chunk in previous.difference(&required) {
+            if self.release(*chunk) {
+                changes.unload.push(*chunk);
+            }
+        }
+        for chunk in required.difference(&previous) {
+            if self.retain(*chunk) {
+                changes.load.push(*chunk);
+            }
+        }
+
+        self.anchors.insert(id, AnchorChunks { center, radius, chunks: required });
+        changes
+    }
+
+    pub fn remove_anchor(&mut self, id: AnchorId) -> ChunkChanges {
+        let mut changes = ChunkChanges::default();
+        for chunk in self.anchors.remove(&id).unwrap_or_default().chunks {
+            if self.release(chunk) {
+                changes.unload.push(chunk);
+            }
+        }
+        changes
+    }
+
+    pub fn anchor_ids(&self) -> impl Iterator<Item = AnchorId> + '_ {
+        self.anchors.keys().copied()
+    }
+
+    // Chunks the anchors are in
+    pub fn centers(&self) -> impl Iterator<Item = ChunkCoords> + '_ {
+        self.anchors.values().map(|anchor| anchor.center)
+    }
+
+    // Chunks some anchor needs
+    pub fn required(&self) -> impl Iterator<Item = ChunkCoords> + '_ {
+        self.ref_counts.keys().copied()
+    }
+
+    pub fn is_required(&self, chunk: ChunkCoords) -> bool {
+        self.ref_counts.contains_key(&chunk)
+    }
+
+    // Returns true when the chunk was not needed before
+    fn retain(&mut self, chunk: ChunkCoords) -> bool {
+        let count = self.ref_counts.entry(chunk).or_insert(0);
+        *count += 1;
+        *count == 1
+    }
+
+    // Returns true when nothing needs the chunk anymore
+    fn release(&mut self, chunk: ChunkCoords) -> bool {
+        match self.ref_counts.get_mut(&chunk) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            Some(_) => {
+                self.ref_counts.remove(&chunk);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, OnceLock, PoisonError, RwLock};
+use std::time::Instant;
+use bevy::prelude::*;
+use noise::{BasicMulti, MultiFractal, NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use crate::client::{build_terrain_mesh, lod_subdivisions, MAX_TERRAIN_LOD};
+use crate::heightmap::Heightmap;
+use crate::palette::TerrainPalette;
+use crate::terrain_edit::TerrainEdits;
+
+// Spacing of the lattice of cached heights, finer than any chunk mesh so interpolating it
+// stays closer to the noise than the drawn ground is
+const HEIGHT_CACHE_STEP: f32 = 0.25;
+// Lattice points kept, about a 30 m square around each place sampled every frame
+const HEIGHT_CACHE_CAPACITY: usize = 16_384;
+
+// Parameters of the noise terrain, the same settings always give the same terrain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct WorldGenSettings {
+    pub seed: u32,
+    pub octaves: usize,
+    pub frequency: f64,
+    pub persistence: f64,
+    pub lacunarity: f64,
+    // Height of the main layer's peaks, in meters
+    pub amplitude: f64,
+    pub detail_frequency: f64,
+    pub detail_amplitude: f64,
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            octaves: 8,
+            frequency: 0.05,
+            persistence: 0.6,
+            lacunarity: 2.0,
+            amplitude: 22.0,
+            detail_frequency: 0.03,
+            detail_amplitude: 3.0,
+        }
+    }
+}
+
+// Noise layers shared by every system that needs the terrain height
+pub struct TerrainNoise {
+    settings: WorldGenSettings,
+    main: BasicMulti<Perlin>,
+    detail: BasicMulti<Perlin>,
+}
+
+impl TerrainNoise {
+    pub fn new() -> Self {
+        Self::with_settings(WorldGenSettings::default())
+    }
+
+    #[cfg(test)]
+    pub fn with_seed(seed: u32) -> Self {
+        Self::with_settings(WorldGenSettings { seed, ..default() })
+    }
+
+    pub fn with_settings(settings: WorldGenSettings) -> Self {
+        let main = BasicMulti::<Perlin>::new(settings.seed)
+            .set_octaves(settings.octaves)
+            .set_frequency(settings.frequency)
+            .set_persistence(settings.persistence)
+            .set_lacunarity(settings.lacunarity);
+
+        let detail = BasicMulti::<Perlin>::new(settings.seed.wrapping_add(1))
+            .set_octaves(3)
+            .set_frequency(settings.detail_frequency)
+            .set_persistence(0.4)
+            .set_lacunarity(2.0);
+
+        Self { settings, main, detail }
+    }
+
+    pub fn settings(&self) -> WorldGenSettings {
+        self.settings
+    }
+
+    pub fn height(&self, world_x: f32, world_z: f32) -> f32 {
+        let main_val = self.main.get([world_x as f64, world_z as f64, 42.0]) * self.settings.amplitude;
+        let detail_val = self.detail.get([world_x as f64, world_z as f64, 100.0]) * self.settings.detail_amplitude;
+        (main_val + detail_val) as f32
+    }
+}
+
+impl Default for TerrainNoise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Heights of lattice points recently sampled. Two generations stand in for least recently
+// used eviction: samples found in the older one move to the recent one, which becomes the
+// older one once full, dropping whatever wasn't used meanwhile.
+#[derive(Default)]
+pub struct HeightCache {
+    recent: HashMap<(i32, i32), f32>,
+    older: HashMap<(i32, i32), f32>,
+    // Bumped when the terrain changes, so heights sampled before aren't stored after
+    generation: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl HeightCache {
+    fn get(&mut self, point: (i32, i32)) -> Option<f32> {
+        if let Some(height) = self.recent.get(&point) {
+            self.hits += 1;
+            return Some(*height);
+        }
+        let height = self.older.remove(&point)?;
+        self.hits += 1;
+        self.insert(point, height);
+        Some(height)
+    }
+
+    fn insert(&mut self, point: (i32, i32), height: f32) {
+        if self.recent.len() >= HEIGHT_CACHE_CAPACITY / 2 {
+            self.older = std::mem::take(&mut self.recent);
+        }
+        self.recent.insert(point, height);
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.older.len()
+    }
+
+    fn clear(&mut self) {
+        self.recent.clear();
+        self.older.clear();
+        self.generation += 1;
+    }
+}
+
+static TERRAIN_NOISE: LazyLock<RwLock<TerrainNoise>> = LazyLock::new(|| RwLock::new(TerrainNoise::new()));
+// Optional real-world elevation data replacing the noise where it has coverage
+static HEIGHTMAP: OnceLock<Heightmap> = OnceLock::new();
+static HEIGHT_CACHE: LazyLock<Mutex<HeightCache>> = LazyLock::new(Mutex::default);
+
+// Must be called before anything samples the terrain; returns false if a heightmap was already set
+pub fn use_heightmap(heightmap: Heightmap) -> bool {
+    let set = HEIGHTMAP.set(heightmap).is_ok();
+    HEIGHT_CACHE.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    set
+}
+
+pub fn world_gen() -> WorldGenSettings {
+    TERRAIN_NOISE.read().unwrap_or_else(PoisonError::into_inner).settings()
+}
+
+// Swap the noise terrain; loaded chunks and anything caching heights must be rebuilt after
+pub fn set_world_gen(settings: WorldGenSettings) {
+    *TERRAIN_NOISE.write().unwrap_or_else(PoisonError::into_inner) = TerrainNoise::with_settings(settings);
+    HEIGHT_CACHE.lock().unwrap_or_else(PoisonError::into_inner).clear();
+}
+
+// Terrain height at a world position
+pub fn height(world_x: f32, world_z: f32) -> f32 {
+    let noise = TERRAIN_NOISE.read().unwrap_or_else(PoisonError::into_inner).height(world_x, world_z);
+    match HEIGHTMAP.get().and_then(|heightmap| heightmap.sample(world_x, world_z)) {
+        Some((height, weight)) => noise.lerp(height, weight),
+        None => noise,
+    }
+}
+
+// Terrain height at a world position, interpolated between cached samples of a fine lattice.
+// For gameplay sampling the same places every frame; chunk meshes and the server's checks
+// use the exact `height`.
+pub fn cached_height(world_x: f32, world_z: f32) -> f32 {
+    let lattice = Vec2::new(world_x, world_z) / HEIGHT_CACHE_STEP;
+    let corner = lattice.floor();
+    let t = lattice - corner;
+    let (x, z) = (corner.x as i32, corner.y as i32);
+    let points = [(x, z), (x + 1, z), (x, z + 1), (x + 1, z + 1)];
+
+    let mut heights = [None; 4];
+    let generation = {
+        let mut cache = HEIGHT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        for (height, point) in heights.iter_mut().zip(points) {
+            *height = cache.get(point);
+        }
+        cache.generation
+    };
+    // The noise is sampled without holding the lock, other threads keep using the cache
+    let mut sampled = Vec::new();
+    let heights = std::array::from_fn::<f32, 4, _>(|i| {
+        heights[i].unwrap_or_else(|| {
+            let (x, z) = points[i];
+            let height = height(x as f32 * HEIGHT_CACHE_STEP, z as f32 * HEIGHT_CACHE_STEP);
+            sampled.push((points[i], height));
+            height
+        })
+    });
+    if !sampled.is_empty() {
+        let mut cache = HEIGHT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.misses += sampled.len() as u64;
+        if cache.generation == generation {
+            for (point, height) in sampled {
+                cache.insert(point, height);
+            }
+        }
+    }
+
+    let top = heights[0].lerp(heights[1], t.x);
+    let bottom = heights[2].lerp(heights[3], t.x);
+    top.lerp(bottom, t.y)
+}
+
+// Hits, misses and lattice points held by the height cache
+pub fn height_cache_stats() -> (u64, u64, usize) {
+    let cache = HEIGHT_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    (cache.hits, cache.misses, cache.len())
+}
+
+// Search rings around `origin` for the closest point whose height is above `min_height`
+pub fn find_dry_land(origin: Vec2, min_height: f32, max_radius: f32) -> Option<Vec2> {
+    find_nearest(origin, max_radius, 4.0, |x, z| height(x, z) > min_height)
+}
+
+// Search rings of increasing radius around `origin` for the first point matching `predicate`
+pub fn find_nearest(
+    origin: Vec2,
+    max_radius: f32,
+    ring_step: f32,
+    predicate: impl Fn(f32, f32) -> bool,
+) -> Option<Vec2> {
+    let samples_per_ring = 24;
+
+    if predicate(origin.x, origin.y) {
+        return Some(origin);
+    }
+
+    let mut radius = ring_step;
+    while radius <= max_radius {
+        for i in 0..samples_per_ring {
+            let angle = i as f32 / samples_per_ring as f32 * std::f32::consts::TAU;
+            let point = origin + Vec2::new(angle.cos(), angle.sin()) * radius;
+            if predicate(point.x, point.y) {
+                return Some(point);
+            }
+        }
+        radius += ring_step;
+    }
+
+    None
+}
+
+// March along a ray until it goes below the surface given by `height_at`
+pub fn raycast(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    height_at: impl Fn(f32, f32) -> f32,
+) -> Option<Vec3> {
+    let step = 0.5;
+    let direction = direction.normalize_or_zero();
+    let mut previous = origin;
+    let mut distance = step;
+
+    while distance <= max_distance {
+        let point = origin + direction * distance;
+        if point.y <= height_at(point.x, point.z) {
+            // Refine between the last point above ground and this one
+            let mut above = previous;
+            let mut below = point;
+            for _ in 0..8 {
+                let middle = (above + below) / 2.0;
+                if middle.y <= height_at(middle.x, middle.z) {
+                    below = middle;
+                } else {
+                    above = middle;
+                }
+            }
+            return Some(below);
+        }
+        previous = point;
+        distance += step;
+    }
+
+    None
+}
+
+// Heights sampled on the same lattice as a chunk mesh; doubles as the chunk's collision shape
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    // World position of the (-x, -z) corner
+    pub origin: Vec2,
+    pub cell_size: f32,
+    // Cells per side, there are `resolution + 1` samples per side
+    pub resolution: u32,
+    pub heights: Vec<f32>,
+    pub min_height: f32,
+    pub max_height: f32,
+}
+
+impl Heightfield {
+    pub fn sample(
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk_size: f32,
+        resolution: u32,
+        height_at: impl Fn(f32, f32) -> f32,
+    ) -> Self {
+        // Chunk meshes are centered on their offset
+        let origin = Vec2::new(chunk_x as f32, chunk_z as f32) * chunk_size - Vec2::splat(chunk_size / 2.0);
+        let cell_size = chunk_size / resolution as f32;
+        let side = resolution + 1;
+        let mut heights = Vec::with_capacity((side * side) as usize);
+        let mut min_height = f32::MAX;
+        let mut max_height = f32::MIN;
+
+        for z in 0..side {
+            for x in 0..side {
+                let height = height_at(origin.x + x as f32 * cell_size, origin.y + z as f32 * cell_size);
+                min_height = min_height.min(height);
+                max_height = max_height.max(height);
+                heights.push(height);
+            }
+        }
+
+        Self { origin, cell_size, resolution, heights, min_height, max_height }
+    }
+
+    fn sample_at(&self, x: u32, z: u32) -> f32 {
+        self.heights[(z * (self.resolution + 1) + x) as usize]
+    }
+
+    // Bilinear height at a world position, None outside the chunk
+    pub fn height_at(&self, world_x: f32, world_z: f32) -> Option<f32> {
+        let local = (Vec2::new(world_x, world_z) - self.origin) / self.cell_size;
+        let max = self.resolution as f32;
+        if local.x < 0.0 || local.y < 0.0 || local.x > max || local.y > max {
+            return None;
+        }
+
+        let x0 = (local.x.floor() as u32).min(self.resolution - 1);
+        let z0 = (local.y.floor() as u32).min(self.resolution - 1);
+        let tx = local.x - x0 as f32;
+        let tz = local.y - z0 as f32;
+
+        let top = self.sample_at(x0, z0).lerp(self.sample_at(x0 + 1, z0), tx);
+        let bottom = self.sample_at(x0, z0 + 1).lerp(self.sample_at(x0 + 1, z0 + 1), tx);
+        Some(top.lerp(bottom, tz))
+    }
+}
+
+// Time the height function and chunk mesh builds, to compare generator changes.
+// Meant for release builds: `cargo run --release -- bench-terrain`
+pub fn run_benchmark() {
+    const SAMPLES: u32 = 1_000_000;
+    const CHUNKS: i32 = 8;
+
+    let start = Instant::now();
+    let mut checksum = 0.0;
+    for i in 0..SAMPLES {
+        checksum += height((i % 1000) as f32 * 0.37, (i / 1000) as f32 * 0.37);
+    }
+    let elapsed = start.elapsed();
+    println!("height: {:.1} ns per sample ({} samples, checksum {})", elapsed.as_nanos() as f64 / SAMPLES as f64, SAMPLES, checksum);
+
+    // A player walking the same ground every frame
+    let start = Instant::now();
+    let mut checksum = 0.0;
+    for i in 0..SAMPLES {
+        checksum += cached_height((i % 1000) as f32 * 0.01, (i / 1000 % 10) as f32 * 0.01);
+    }
+    let elapsed = start.elapsed();
+    let (hits, misses, _) = height_cache_stats();
+    println!(
+        "cached height: {:.1} ns per sample ({} hits, {} misses, checksum {})",
+        elapsed.as_nanos() as f64 / SAMPLES as f64, hits, misses, checksum,
+    );
+
+    let edits = TerrainEdits::default();
+    let palette = TerrainPalette::default();
+    for lod in 0..=MAX_TERRAIN_LOD {
+        let start = Instant::now();
+        let mut vertices = 0;
+        for chunk_z in 0..CHUNKS {
+            for chunk_x in 0..CHUNKS {
+                let (_, bounds) = build_terrain_mesh(chunk_x, chunk_z, lod, &edits, &palette);
+                vertices += (bounds.cells + 1).pow(2);
+            }
+        }
+        let chunks = (CHUNKS * CHUNKS) as u32;
+        let per_chunk = start.elapsed().as_secs_f64() * 1000.0 / chunks as f64;
+        // Against every chunk at the base grid, before roughness picked their resolution
+        let uniform = (lod_subdivisions(lod) + 1).pow(2);
+        println!(
+            "chunk mesh, lod {}: {:.2} ms per chunk ({} vertices per chunk, {} with a uniform grid)",
+            lod, per_chunk, vertices / chunks, uniform,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+    use crate::client::CHUNK_SIZE;
+
+    // Steepest height change per meter along an axis. The default world measures about 6.5
+    // over 300k random samples, a jump in the noise would break this at once.
+    const MAX_GRADIENT: f32 = 8.0;
+    // Rounding allowance of f32 heights far from the origin
+    const TOLERANCE: f32 = 0.001;
+
+    proptest! {
+        #[test]
+        fn height_is_deterministic_for_a_seed(seed in 0u32..1000, x in -1.0e5f32..1.0e5, z in -1.0e5f32..1.0e5) {
+            let first = TerrainNoise::with_seed(seed).height(x, z);
+            let second = TerrainNoise::with_seed(seed).height(x, z);
+            prop_assert_eq!(first.to_bits(), second.to_bits());
+        }
+
+        #[test]
+        fn height_is_continuous_across_chunk_borders(
+            border in -2000i32..2000,
+            along in -2000.0f32..2000.0,
+            offset in 0.01f32..0.5,
+            across_x: bool,
+        ) {
+            // Chunk meshes are centered on their offset, borders fall halfway between
+            let border = (border as f32 + 0.5) * CHUNK_SIZE;
+            let along = along * CHUNK_SIZE / 40.0;
+            let (near, far) = (border - offset, border + offset);
+            let (before, after) = if across_x {
+                (height(near, along), height(far, along))
+            } else {
+                (height(along, near), height(along, far))
+            };
+            // Positions round to the f32 grid, which is coarse this far out
+            let span = far - near;
+            prop_assert!(
+                (before - after).abs() <= MAX_GRADIENT * span + TOLERANCE,
+                "{} then {} over {} m across the border at {}", before, after, span, border,
+            );
+        }
+    }
+
+    #[test]
+    fn cached_heights_follow_the_noise() {
+        for i in 0..200 {
+            let (x, z) = (i as f32 * 3.17 - 300.0, i as f32 * -1.93 + 50.0);
+            let (cached, exact) = (cached_height(x, z), height(x, z));
+            assert!((cached - exact).abs() < 0.15, "{} against {} at ({}, {})", cached, exact, x, z);
+            // Lattice points are exact
+            let (x, z) = ((x / HEIGHT_CACHE_STEP).round() * HEIGHT_CACHE_STEP, (z / HEIGHT_CACHE_STEP).round() * HEIGHT_CACHE_STEP);
+            assert_eq!(cached_height(x, z), height(x, z));
+        }
+        assert!(height_cache_stats().0 > 0);
+    }
+
+    #[test]
+    fn seeds_give_different_terrain() {
+        let (first, second) = (TerrainNoise::with_seed(1), TerrainNoise::with_seed(2));
+        let differs = (0..16).any(|i| {
+            let (x, z) = (i as f32 * 13.7, i as f32 * -7.3);
+            first.height(x, z) != second.height(x, z)
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn default_noise_uses_the_first_seed() {
+        assert_eq!(TerrainNoise::new().height(12.5, -40.0), TerrainNoise::with_seed(1).height(12.5, -40.0));
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use bevy::{
+    ecs::system::SystemParam,
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, Extent3d, ShaderRef, ShaderType, TextureDimension, TextureFormat},
+    },
+};
+use crate::asset_registry::AssetRegistryAppExt;
+use crate::client::{
+    build_grid_mesh, chunk_roughness, chunk_subdivisions, skirt_depth, ChunkBounds, ChunkManager, TerrainChunk,
+    CHUNK_SIZE, LOD_RING_STEP, MAX_TERRAIN_LOD,
+};
+use crate::palette::TerrainPalette;
+use crate::road::paint_roads;
+use crate::settings::GameSettings;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::terrain_material::{TerrainExtension, TerrainMaterial, TerrainMaterialHandle, TerrainParams, TERRAIN_SHADER_PATH};
+
+const DISPLACEMENT_SHADER_PATH: &str = "shaders/terrain_displacement.wgsl";
+// Vertices morph into the next LOD's grid over the last this many meters before its ring
+const MORPH_DISTANCE: f32 = CHUNK_SIZE;
+// Morph distances of chunks with no coarser LOD to morph into
+const NO_MORPH: (f32, f32) = (1.0e9, 2.0e9);
+
+pub type DisplacedTerrainMaterial = ExtendedMaterial<StandardMaterial, DisplacedTerrainExtension>;
+
+// GPU driven terrain, picked in the graphics settings: chunks draw a shared flat grid per
+// LOD that the vertex shader lifts from the chunk's height map, so building a chunk only
+// samples heights and colors, and changing its LOD only swaps the grid. Vertices close to
+// the next LOD ring morph into the coarser grid so the switch doesn't pop.
+#[derive(Default, Clone, Debug)]
+pub struct TerrainDisplacementPlugin;
+
+impl Plugin for TerrainDisplacementPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<DisplacedTerrainMaterial>::default())
+            .preload_asset::<Shader>("terrain_displacement_shader", DISPLACEMENT_SHADER_PATH)
+            .init_resource::<GridMeshes>()
+            .add_systems(Update, (
+                apply_gpu_terrain_setting,
+                apply_displaced_lods,
+                sync_displaced_materials,
+            ));
+    }
+}
+
+// Kept apart for the per-field `check` fns `ShaderType` generates, which rustc counts as
+// unused and which attributes on the struct don't cover
+#[allow(dead_code)]
+mod uniforms {
+    use super::*;
+
+    #[derive(ShaderType, Reflect, Debug, Clone, Copy)]
+    pub struct DisplacementParams {
+        // Cells along each side of the chunk's maps, and of the grid drawn
+        pub map_cells: u32,
+        pub mesh_cells: u32,
+        // Distance from the camera along x or z where vertices start and finish morphing into
+        // the grid of the next LOD
+        pub morph_start: f32,
+        pub morph_end: f32,
+    }
+}
+
+pub use uniforms::DisplacementParams;
+
+impl DisplacementParams {
+    fn new(map_cells: u32, lod: u32) -> Self {
+        let mesh_cells = chunk_cells(map_cells, lod);
+        // LOD rings are counted in chunks from the camera's chunk, whose center is half a
+        // chunk from its edge
+        let (morph_start, morph_end) = if lod < MAX_TERRAIN_LOD && mesh_cells > 2 {
+            let ring = ((lod as i32 + 1) * LOD_RING_STEP) as f32 - 0.5;
+            (ring * CHUNK_SIZE - MORPH_DISTANCE, ring * CHUNK_SIZE)
+        } else {
+            NO_MORPH
+        };
+        Self { map_cells, mesh_cells, morph_start, morph_end }
+    }
+}
+
+// The terrain material plus a chunk's maps, one per chunk. Bindings 100 to 104 mirror
+// `TerrainExtension` so both share the fragment shader.
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct DisplacedTerrainExtension {
+    #[uniform(100)]
+    pub params: TerrainParams,
+    #[texture(101)]
+    #[sampler(102)]
+    pub ground_texture: Handle<Image>,
+    #[texture(103)]
+    #[sampler(104)]
+    pub rock_texture: Handle<Image>,
+    #[uniform(105)]
+    pub displacement: DisplacementParams,
+    // Heights in meters, with one extra cell around the chunk for the normals at its edges
+    #[texture(106, sample_type = "float", filterable = false, visibility(vertex))]
+    pub height_map: Handle<Image>,
+    #[texture(107, sample_type = "float", filterable = false, visibility(vertex))]
+    pub color_map: Handle<Image>,
+}
+
+impl DisplacedTerrainExtension {
+    // Take the shared terrain material's look, keeping this chunk's maps
+    fn copy_look(&mut self, terrain: &TerrainExtension) {
+        self.params = terrain.params;
+        self.ground_texture = terrain.ground_texture.clone();
+        self.rock_texture = terrain.rock_texture.clone();
+    }
+}
+
+impl MaterialExtension for DisplacedTerrainExtension {
+    fn vertex_shader() -> ShaderRef {
+        DISPLACEMENT_SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        TERRAIN_SHADER_PATH.into()
+    }
+
+    fn prepass_vertex_shader() -> ShaderRef {
+        DISPLACEMENT_SHADER_PATH.into()
+    }
+
+    fn deferred_vertex_shader() -> ShaderRef {
+        DISPLACEMENT_SHADER_PATH.into()
+    }
+}
+
+// Grid cells a side of a chunk drawn at a LOD, from the cells of its maps
+fn chunk_cells(map_cells: u32, lod: u32) -> u32 {
+    (map_cells >> lod).max(2)
+}
+
+// Height and color maps of a chunk, built off the main thread instead of a mesh
+pub struct DisplacementMaps {
+    height: Image,
+    color: Image,
+    cells: u32,
+    min_height: f32,
+    max_height: f32,
+}
+
+// Sample the edited terrain of a chunk into maps, at the resolution its full detail mesh
+// would have
+pub fn build_displacement_maps(
+    chunk_x: i32,
+    chunk_z: i32,
+    terrain_edits: &TerrainEdits,
+    palette: &TerrainPalette,
+) -> DisplacementMaps {
+    let _span = info_span!("build_displacement_maps", chunk_x, chunk_z).entered();
+    let roughness = chunk_roughness(chunk_x, chunk_z, |x, z| terrain_edits.height(x, z));
+    let cells = chunk_subdivisions(roughness, 0);
+    let step = CHUNK_SIZE / cells as f32;
+    let origin = Vec2::new(chunk_x as f32, chunk_z as f32) * CHUNK_SIZE - CHUNK_SIZE / 2.0;
+
+    let border_side = cells + 3;
+    let mut heights = Vec::with_capacity((border_side * border_side * 4) as usize);
+    let mut colors = Vec::with_capacity(((cells + 1) * (cells + 1) * 4) as usize);
+    let mut min_height = f32::MAX;
+    let mut max_height = f32::MIN;
+    for z in -1..=cells as i32 + 1 {
+        for x in -1..=cells as i32 + 1 {
+            let world = origin + Vec2::new(x as f32, z as f32) * step;
+            let height = terrain::height(world.x, world.y) + terrain_edits.height_offset(world.x, world.y);
+            heights.extend_from_slice(&height.to_le_bytes());
+            let inside = (0..=cells as i32).contains(&x) && (0..=cells as i32).contains(&z);
+            if inside {
+                min_height = min_height.min(height);
+                max_height = max_height.max(height);
+                let [r, g, b, a] = paint_roads(&terrain_edits.roads, world.x, world.y, palette.color(world.x, world.y, height));
+                colors.extend_from_slice(&Srgba::from(LinearRgba::new(r, g, b, a)).to_u8_array());
+            }
+        }
+    }
+
+    let map = |side: u32, data: Vec<u8>, format: TextureFormat| Image::new(
+        Extent3d { width: side, height: side, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        format,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    DisplacementMaps {
+        height: map(border_side, heights, TextureFormat::R32Float),
+        color: map(cells + 1, colors, TextureFormat::Rgba8UnormSrgb),
+        cells,
+        min_height,
+        max_height,
+    }
+}
+
+// A terrain chunk drawn from maps, and what it was last set up with
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DisplacedChunk {
+    pub lod: u32,
+    pub map_cells: u32,
+    min_height: f32,
+    max_height: f32,
+}
+
+impl DisplacedChunk {
+    pub fn bounds(&self) -> ChunkBounds {
+        let cells = chunk_cells(self.map_cells, self.lod);
+        ChunkBounds {
+            min_height: self.min_height - skirt_depth(cells),
+            max_height: self.max_height,
+            cells,
+        }
+    }
+}
+
+// One flat grid mesh per size, shared by every displaced chunk drawn with it
+#[derive(Resource, Default, Debug)]
+pub struct GridMeshes(HashMap<u32, Handle<Mesh>>);
+
+impl GridMeshes {
+    pub fn get(&mut self, cells: u32, meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+        self.0.entry(cells).or_insert_with(|| meshes.add(build_grid_mesh(cells))).clone()
+    }
+}
+
+// What setting up and updating displaced chunks needs, besides the meshes
+#[derive(SystemParam)]
+pub struct DisplacedTerrain<'w> {
+    grids: ResMut<'w, GridMeshes>,
+    materials: ResMut<'w, Assets<DisplacedTerrainMaterial>>,
+    images: ResMut<'w, Assets<Image>>,
+    terrain_materials: Res<'w, Assets<TerrainMaterial>>,
+    terrain_material: Res<'w, TerrainMaterialHandle>,
+}
+
+impl DisplacedTerrain<'_> {
+    // Components drawing a chunk from its maps: its LOD's grid and its own material
+    pub fn chunk(
+        &mut self,
+        meshes: &mut Assets<Mesh>,
+        maps: DisplacementMaps,
+        lod: u32,
+    ) -> (Mesh3d, MeshMaterial3d<DisplacedTerrainMaterial>, DisplacedChunk, ChunkBounds, Aabb) {
+        let displaced = DisplacedChunk { lod, map_cells: maps.cells, min_height: maps.min_height, max_height: maps.max_height };
+        let shared = self.terrain_materials.get(&self.terrain_material.0);
+        let mut material = DisplacedTerrainMaterial {
+            base: shared.map(|shared| shared.base.clone()).unwrap_or_default(),
+            extension: DisplacedTerrainExtension {
+                params: TerrainParams::default(),
+                ground_texture: Handle::default(),
+                rock_texture: Handle::default(),
+                displacement: DisplacementParams::new(maps.cells, lod),
+                height_map: self.images.add(maps.height),
+                color_map: self.images.add(maps.color),
+            },
+        };
+        if let Some(shared) = shared {
+            material.extension.copy_look(&shared.extension);
+        }
+        let bounds = displaced.bounds();
+        (
+            Mesh3d(self.grids.get(chunk_cells(maps.cells, lod), meshes)),
+            MeshMaterial3d(self.materials.add(material)),
+            displaced,
+            bounds,
+            bounds.aabb(),
+        )
+    }
+
+    // Swap the maps of a displaced chunk for new ones, returning its new bounds and grid,
+    // which change with the maps' resolution
+    pub fn update(
+        &mut self,
+        meshes: &mut Assets<Mesh>,
+        material: &Handle<DisplacedTerrainMaterial>,
+        displaced: &mut DisplacedChunk,
+        maps: DisplacementMaps,
+    ) -> Option<(ChunkBounds, Handle<Mesh>)> {
+        // Touching the material rebinds it to the new textures
+        let material = self.materials.get_mut(material)?;
+        self.images.insert(&material.extension.height_map, maps.height);
+        self.images.insert(&material.extension.color_map, maps.color);
+        material.extension.displacement = DisplacementParams::new(maps.cells, displaced.lod);
+        displaced.map_cells = maps.cells;
+        displaced.min_height = maps.min_height;
+        displaced.max_height = maps.max_height;
+        Some((displaced.bounds(), self.grids.get(chunk_cells(maps.cells, displaced.lod), meshes)))
+    }
+}
+
+// Turning GPU terrain on or off meshes every chunk again the other way
+fn apply_gpu_terrain_setting(
+    settings: Res<GameSettings>,
+    chunk_manager: Res<ChunkManager>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    mut last: Local<Option<bool>>,
+) {
+    let enabled = settings.graphics.gpu_terrain;
+    if last.replace(enabled).is_some_and(|was| was != enabled) {
+        info!("GPU terrain {}", if enabled { "enabled" } else { "disabled" });
+        terrain_edits.remesh_all(&chunk_manager);
+    }
+}
+
+// Give displaced chunks whose LOD changed the grid of their new LOD; their maps stay
+#[allow(clippy::type_complexity)]
+fn apply_displaced_lods(
+    chunk_manager: Res<ChunkManager>,
+    mut grids: ResMut<GridMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<DisplacedTerrainMaterial>>,
+    mut chunk_query: Query<(
+        &TerrainChunk,
+        &mut DisplacedChunk,
+        &mut Mesh3d,
+        &MeshMaterial3d<DisplacedTerrainMaterial>,
+        &mut ChunkBounds,
+        &mut Aabb,
+    )>,
+) {
+    if !chunk_manager.is_changed() {
+        return;
+    }
+    for (chunk, mut displaced, mut mesh, material, mut bounds, mut aabb) in chunk_query.iter_mut() {
+        let Some(&lod) = chunk_manager.chunk_lods.get(&(chunk.chunk_x, chunk.chunk_z)) else {
+            continue;
+        };
+        if displaced.lod == lod {
+            continue;
+        }
+        displaced.lod = lod;
+        mesh.0 = grids.get(chunk_cells(displaced.map_cells, lod), &mut meshes);
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.extension.displacement = DisplacementParams::new(displaced.map_cells, lod);
+        }
+        *bounds = displaced.bounds();
+        *aabb = bounds.aabb();
+    }
+}
+
+// Weather, seasons and lighting change the shared terrain material, copy them over
+fn sync_displaced_materials(
+    mut events: EventReader<AssetEvent<TerrainMaterial>>,
+    handle: Res<TerrainMaterialHandle>,
+    terrain_materials: Res<Assets<TerrainMaterial>>,
+    mut materials: ResMut<Assets<DisplacedTerrainMaterial>>,
+) {
+    let changed = events.read().any(|event| {
+        matches!(event, AssetEvent::Modified { id } | AssetEvent::LoadedWithDependencies { id } if *id == handle.0.id())
+    });
+    let Some(shared) = changed.then(|| terrain_materials.get(&handle.0)).flatten() else {
+        return;
+    };
+    for (_, material) in materials.iter_mut() {
+        material.base = shared.base.clone();
+        material.extension.copy_look(&shared.extension);
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use bevy::render::primitives::Aabb;
+use crate::client::{build_terrain_mesh, update_terrain_mesh, ChunkBounds, ChunkManager, CHUNK_SIZE};
+use crate::impact::Impact;
+use crate::projectile::HitTarget;
+use crate::input_map::{Action, Actions};
+use crate::palette::ActiveTerrainPalette;
+use crate::road::{roads_height_offset, Road};
+use crate::net::{ClientMessage, ServerConnection, ServerEvent, ServerMessage};
+use crate::streaming::mesh_chunk_coords;
+use crate::settings::GameSettings;
+use crate::terrain;
+use crate::terrain_displacement::{build_displacement_maps, DisplacedChunk, DisplacedTerrain, DisplacedTerrainMaterial};
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle};
+
+pub const EDIT_REACH: f32 = 150.0;
+pub const EDIT_RADIUS: f32 = 4.0;
+const EDIT_DELTA: f32 = 1.0;
+
+#[derive(Default, Clone, Debug)]
+pub struct TerrainEditPlugin;
+
+impl Plugin for TerrainEditPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TerrainEdits>()
+            .add_systems(Update, (
+                terrain_edit_input,
+                receive_edit_results,
+                rebuild_dirty_chunks,
+            ).chain());
+    }
+}
+
+// Circular brush raising (positive delta) or lowering the terrain with a smooth falloff
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TerrainEdit {
+    pub center: Vec2,
+    pub radius: f32,
+    pub delta: f32,
+}
+
+impl TerrainEdit {
+    pub fn offset_at(&self, world_x: f32, world_z: f32) -> f32 {
+        let distance = self.center.distance(Vec2::new(world_x, world_z));
+        if distance >= self.radius {
+            return 0.0;
+        }
+        let t = 1.0 - distance / self.radius;
+        self.delta * t * t * (3.0 - 2.0 * t)
+    }
+
+    // Chunks whose mesh contains at least one vertex touched by this edit
+    pub fn affected_chunks(&self) -> impl Iterator<Item = (i32, i32)> {
+        let reach = self.radius + 1.0;
+        let (min_x, min_z) = mesh_chunk_coords(self.center.x - reach, self.center.y - reach, CHUNK_SIZE);
+        let (max_x, max_z) = mesh_chunk_coords(self.center.x + reach, self.center.y + reach, CHUNK_SIZE);
+        (min_x..=max_x).flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+    }
+}
+
+fn edits_height_offset<'a>(edits: impl IntoIterator<Item = &'a TerrainEdit>, world_x: f32, world_z: f32) -> f32 {
+    edits.into_iter()
+        .map(|edit| edit.offset_at(world_x, world_z))
+        .sum()
+}
+
+// Edits listed under every chunk they reach, so a height sample only goes through the few
+// edits of its own chunk instead of every edit ever made
+#[derive(Default, Clone, Debug)]
+pub struct ChunkEdits {
+    chunks: HashMap<(i32, i32), Vec<TerrainEdit>>,
+}
+
+impl ChunkEdits {
+    pub fn insert(&mut self, edit: TerrainEdit) {
+        for chunk_pos in edit.affected_chunks() {
+            self.chunks.entry(chunk_pos).or_default().push(edit);
+        }
+    }
+
+    // Remove one copy of an edit, the same edit may have been made more than once
+    pub fn remove(&mut self, edit: &TerrainEdit) {
+        for chunk_pos in edit.affected_chunks() {
+            if let Some(edits) = self.chunks.get_mut(&chunk_pos) {
+                if let Some(index) = edits.iter().position(|other| other == edit) {
+                    edits.remove(index);
+                }
+                if edits.is_empty() {
+                    self.chunks.remove(&chunk_pos);
+                }
+            }
+        }
+    }
+
+    pub fn height_offset(&self, world_x: f32, world_z: f32) -> f32 {
+        self.chunks
+            .get(&mesh_chunk_coords(world_x, world_z, CHUNK_SIZE))
+            .map_or(0.0, |edits| edits_height_offset(edits, world_x, world_z))
+    }
+}
+
+// Heightfield delta applied on top of the noise terrain.
+// Edits confirmed by the server are kept apart from the ones only predicted locally,
+// so a rejected prediction can be rolled back.
+#[derive(Resource, Default, Clone)]
+pub struct TerrainEdits {
+    confirmed: Vec<TerrainEdit>,
+    predicted: BTreeMap<u32, TerrainEdit>,
+    // Confirmed and predicted edits together, by chunk, for height samples
+    by_chunk: ChunkEdits,
+    // Chunks to mesh again from scratch
+    pub dirty_chunks: HashSet<(i32, i32)>,
+    // World area of each chunk whose vertices edits moved, only those are updated
+    pub dirty_areas: HashMap<(i32, i32), Rect>,
+    // Roads carved into the terrain, as last sent by the server; edits apply on top of them
+    pub roads: Vec<Road>,
+    next_seq: u32,
+}
+
+impl TerrainEdits {
+    pub fn height_offset(&self, world_x: f32, world_z: f32) -> f32 {
+        roads_height_offset(&self.roads, world_x, world_z)
+            + self.by_chunk.height_offset(world_x, world_z)
+    }
+
+    // Mesh every loaded chunk again, and those still generating once they're spawned
+    pub fn remesh_all(&mut self, chunk_manager: &ChunkManager) {
+        self.dirty_chunks.extend(chunk_manager.loaded_chunks.keys().chain(chunk_manager.pending_chunks.keys()).copied());
+    }
+
+    // Terrain height including every edit, sampled through the height cache
+    pub fn height(&self, world_x: f32, world_z: f32) -> f32 {
+        terrain::cached_height(world_x, world_z) + self.height_offset(world_x, world_z)
+    }
+
+    fn mark_dirty(&mut self, edit: &TerrainEdit) {
+        let area = Rect::from_center_half_size(edit.center, Vec2::splat(edit.radius));
+        self.mark_area(area, edit.affected_chunks());
+    }
+
+    fn mark_area(&mut self, area: Rect, chunks: impl Iterator<Item = (i32, i32)>) {
+        for chunk_pos in chunks {
+            self.dirty_areas
+                .entry(chunk_pos)
+                .and_modify(|dirty| *dirty = dirty.union(area))
+                .or_insert(area);
+        }
+    }
+
+    // Replace the roads, re-meshing the ground under the ones that changed
+    pub fn set_roads(&mut self, roads: Vec<Road>) {
+        let old = std::mem::take(&mut self.roads);
+        for road in old.iter().filter(|road| !roads.contains(road)).chain(roads.iter().filter(|road| !old.contains(road))) {
+            self.mark_area(road.bounds(), road.affected_chunks());
+        }
+        self.roads = roads;
+    }
+
+    // Chunks whose terrain changed since they were last meshed
+    pub fn dirty(&self) -> impl Iterator<Item = &(i32, i32)> {
+        self.dirty_chunks.iter()
+            .chain(self.dirty_areas.keys().filter(|chunk_pos| !self.dirty_chunks.contains(chunk_pos)))
+    }
+
+    // Apply an edit locally right away and return the sequence number to send to the server
+    pub fn predict(&mut self, edit: TerrainEdit) -> u32 {
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.predicted.insert(seq, edit);
+        self.by_chunk.insert(edit);
+        self.mark_dirty(&edit);
+        seq
+    }
+
+    pub fn confirm(&mut self, seq: u32) {
+        if let Some(edit) = self.predicted.remove(&seq) {
+            self.confirmed.push(edit);
+        }
+    }
+
+    // Replace the predicted edit with the one the server actually applied
+    pub fn adjust(&mut self, seq: u32, edit: TerrainEdit) {
+        if let Some(predicted) = self.predicted.remove(&seq) {
+            self.by_chunk.remove(&predicted);
+            self.mark_dirty(&predicted);
+        }
+        self.confirmed.push(edit);
+        self.by_chunk.insert(edit);
+        self.mark_dirty(&edit);
+    }
+
+    pub fn rollback(&mut self, seq: u32) {
+        if let Some(predicted) = self.predicted.remove(&seq) {
+            self.by_chunk.remove(&predicted);
+            self.mark_dirty(&predicted);
+        }
+    }
+
+    // A new session replays every edit of the world, those already known are dropped first
+    pub fn forget_confirmed(&mut self) {
+        for edit in std::mem::take(&mut self.confirmed) {
+            self.by_chunk.remove(&edit);
+            self.mark_dirty(&edit);
+        }
+    }
+
+    pub fn apply_remote(&mut self, edit: TerrainEdit) {
+        self.confirmed.push(edit);
+        self.by_chunk.insert(edit);
+        self.mark_dirty(&edit);
+    }
+}
+
+// R raises and F lowers the terrain where the free camera is looking
+fn terrain_edit_input(
+    actions: Actions,
+    mut impacts: EventWriter<Impact>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    camera_settings: Res<CameraSettings>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    mut connection: ResMut<ServerConnection>,
+) {
+    if camera_settings.camera_mode != CameraMode::Free {
+        return;
+    }
+
+    let delta = if actions.just_pressed(Action::RaiseTerrain) {
+        EDIT_DELTA
+    } else if actions.just_pressed(Action::LowerTerrain) {
+        -EDIT_DELTA
+    } else {
+        return;
+    };
+
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let Some(hit) = terrain::raycast(
+        camera_transform.translation,
+        *camera_transform.forward(),
+        EDIT_REACH,
+        |x, z| terrain_edits.height(x, z),
+    ) else {
+        return;
+    };
+
+    let edit = TerrainEdit {
+        center: Vec2::new(hit.x, hit.z),
+        radius: EDIT_RADIUS,
+        delta,
+    };
+    impacts.send(Impact { position: hit, target: HitTarget::Terrain, strength: 1.0 });
+    let seq = terrain_edits.predict(edit);
+    connection.send(&ClientMessage::TerrainEdit { seq, edit });
+}
+
+fn receive_edit_results(
+    mut server_events: EventReader<ServerEvent>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    mut connection: ResMut<ServerConnection>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        match message {
+            // Predictions made while the connection was lost never reached the server
+            ServerMessage::Joined { resumed } => {
+                if !*resumed {
+                    terrain_edits.forget_confirmed();
+                }
+                for (seq, edit) in &terrain_edits.predicted {
+                    connection.send(&ClientMessage::TerrainEdit { seq: *seq, edit: *edit });
+                }
+            }
+            ServerMessage::EditAccepted { seq } => terrain_edits.confirm(*seq),
+            ServerMessage::EditAdjusted { seq, edit } => {
+                info!("Terrain edit {} adjusted by the server", seq);
+                terrain_edits.adjust(*seq, *edit);
+            }
+            ServerMessage::EditRejected { seq, reason } => {
+                warn!("Terrain edit {} rejected: {:?}", seq, reason);
+                terrain_edits.rollback(*seq);
+            }
+            ServerMessage::RemoteEdit { edit } => terrain_edits.apply_remote(*edit),
+            ServerMessage::Regions { roads, .. } => terrain_edits.set_roads(roads.clone()),
+            ServerMessage::PlayerMoved { .. }
+            | ServerMessage::PlayerLeft { .. }
+            | ServerMessage::RemoteEmote { .. }
+            | ServerMessage::AccountRestored { .. }
+            | ServerMessage::RockHit { .. }
+            | ServerMessage::PositionCorrected { .. }
+            | ServerMessage::WorldBorder { .. }
+            | ServerMessage::WorldGen { .. }
+            | ServerMessage::Welcome { .. }
+            | ServerMessage::AccountInUse => {}
+        }
+    }
+}
+
+// Re-mesh loaded chunks touched by edits, predictions or rollbacks. An edit only moves the
+// vertices under it, which are updated in place unless the chunk's grid has to change.
+// With GPU terrain the chunk's maps are sampled again instead, and chunks built the other
+// way are switched over.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn rebuild_dirty_chunks(
+    mut commands: Commands,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    chunk_manager: Res<ChunkManager>,
+    palette: Res<ActiveTerrainPalette>,
+    settings: Res<GameSettings>,
+    terrain_material: Res<TerrainMaterialHandle>,
+    mut chunk_query: Query<(
+        &mut Mesh3d,
+        &mut ChunkBounds,
+        &mut Aabb,
+        Option<(&MeshMaterial3d<DisplacedTerrainMaterial>, &mut DisplacedChunk)>,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut displaced: DisplacedTerrain,
+) {
+    if terrain_edits.dirty_chunks.is_empty() && terrain_edits.dirty_areas.is_empty() {
+        return;
+    }
+
+    let _span = info_span!(
+        "rebuild_dirty_chunks",
+        chunks = terrain_edits.dirty_chunks.len(),
+        areas = terrain_edits.dirty_areas.len(),
+    ).entered();
+    // Chunks still generating are rebuilt once spawned, their task may have missed the edit
+    let ready = |chunk_pos: &(i32, i32)| !chunk_manager.pending_chunks.contains_key(chunk_pos);
+    let rebuilt: Vec<(i32, i32)> = terrain_edits.dirty_chunks.iter().filter(|chunk_pos| ready(chunk_pos)).copied().collect();
+    let updated: Vec<((i32, i32), Rect)> = terrain_edits.dirty_areas
+        .iter()
+        .filter(|(chunk_pos, _)| ready(chunk_pos) && !terrain_edits.dirty_chunks.contains(chunk_pos))
+        .map(|(chunk_pos, area)| (*chunk_pos, *area))
+        .collect();
+    for chunk_pos in &rebuilt {
+        terrain_edits.dirty_chunks.remove(chunk_pos);
+        terrain_edits.dirty_areas.remove(chunk_pos);
+    }
+    for (chunk_pos, _) in &updated {
+        terrain_edits.dirty_areas.remove(chunk_pos);
+    }
+
+    let chunks = rebuilt.into_iter().map(|chunk_pos| (chunk_pos, None))
+        .chain(updated.into_iter().map(|(chunk_pos, area)| (chunk_pos, Some(area))));
+    for (chunk_pos, area) in chunks {
+        let Some((terrain_entity, _)) = chunk_manager.loaded_chunks.get(&chunk_pos) else {
+            continue;
+        };
+        let lod = chunk_manager.chunk_lods.get(&chunk_pos).copied().unwrap_or(0);
+        let Ok((mut mesh, mut bounds, mut aabb, displaced_chunk)) = chunk_query.get_mut(*terrain_entity) else {
+            continue;
+        };
+        match (settings.graphics.gpu_terrain, displaced_chunk) {
+            (true, Some((material, mut displaced_chunk))) => {
+                let maps = build_displacement_maps(chunk_pos.0, chunk_pos.1, &terrain_edits, &palette.0);
+                if let Some((new_bounds, grid)) = displaced.update(&mut meshes, &material.0, &mut displaced_chunk, maps) {
+                    mesh.0 = grid;
+                    *bounds = new_bounds;
+                    *aabb = new_bounds.aabb();
+                }
+                continue;
+            }
+            (true, None) => {
+                let maps = build_displacement_maps(chunk_pos.0, chunk_pos.1, &terrain_edits, &palette.0);
+                commands.entity(*terrain_entity)
+                    .remove::<MeshMaterial3d<TerrainMaterial>>()
+                    .insert(displaced.chunk(&mut meshes, maps, lod));
+                continue;
+            }
+            (false, Some(_)) => {
+                // The grid is shared, the chunk gets a mesh of its own again
+                let (terrain, new_bounds) = build_terrain_mesh(chunk_pos.0, chunk_pos.1, lod, &terrain_edits, &palette.0);
+                commands.entity(*terrain_entity)
+                    .remove::<(MeshMaterial3d<DisplacedTerrainMaterial>, DisplacedChunk)>()
+                    .insert((Mesh3d(meshes.add(terrain)), MeshMaterial3d(terrain_material.0.clone()), new_bounds, new_bounds.aabb()));
+                continue;
+            }
+            (false, None) => {}
+        }
+        let updated_bounds = area.zip(meshes.get_mut(&mesh.0)).and_then(|(area, terrain)| {
+            update_terrain_mesh(terrain, chunk_pos.0, chunk_pos.1, lod, bounds.cells, area, &terrain_edits, &palette.0)
+        });
+        let new_bounds = updated_bounds.unwrap_or_else(|| {
+            let (terrain, new_bounds) = build_terrain_mesh(chunk_pos.0, chunk_pos.1, lod, &terrain_edits, &palette.0);
+            meshes.insert(&mesh.0, terrain);
+            new_bounds
+        });
+        *bounds = new_bounds;
+        *aabb = new_bounds.aabb();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Bucketed sums match summing every edit, on chunk borders too
+    #[test]
+    fn chunk_edits_match_the_full_sum() {
+        let half = CHUNK_SIZE / 2.0;
+        let mut edits = vec![
+            TerrainEdit { center: Vec2::new(half, 0.0), radius: 6.0, delta: 1.5 },
+            TerrainEdit { center: Vec2::new(half, half), radius: 8.0, delta: -2.0 },
+            TerrainEdit { center: Vec2::new(-40.0, 12.0), radius: 3.0, delta: 0.5 },
+            TerrainEdit { center: Vec2::new(half, 0.0), radius: 6.0, delta: 1.5 },
+        ];
+        let mut by_chunk = ChunkEdits::default();
+        for edit in &edits {
+            by_chunk.insert(*edit);
+        }
+        by_chunk.remove(&edits.pop().unwrap());
+
+        for z in -40..=40 {
+            for x in -60..=60 {
+                let (world_x, world_z) = (x as f32 * 0.75, z as f32 * 0.75);
+                let expected = edits_height_offset(&edits, world_x, world_z);
+                assert!((by_chunk.height_offset(world_x, world_z) - expected).abs() < 1e-5, "at {} {}", world_x, world_z);
+            }
+        }
+    }
+}
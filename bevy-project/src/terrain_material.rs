@@ -0,0 +1,179 @@
+use std::f64::consts::TAU;
+use bevy::{
+    image::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, Extent3d, ShaderRef, ShaderType, TextureDimension, TextureFormat},
+    },
+};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use crate::asset_registry::AssetRegistryAppExt;
+use crate::settings::GameSettings;
+
+const DETAIL_TEXTURE_SIZE: u32 = 256;
+pub const TERRAIN_SHADER_PATH: &str = "shaders/terrain.wgsl";
+
+pub type TerrainMaterial = ExtendedMaterial<StandardMaterial, TerrainExtension>;
+
+#[derive(Default, Clone, Debug)]
+pub struct TerrainMaterialPlugin;
+
+impl Plugin for TerrainMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(MaterialPlugin::<TerrainMaterial>::default())
+            .preload_asset::<Shader>("terrain_shader", TERRAIN_SHADER_PATH)
+            .add_systems(PreStartup, create_terrain_material)
+            .add_systems(Update, apply_terrain_quality);
+    }
+}
+
+// Shared by every terrain chunk so quality changes apply everywhere at once
+#[derive(Resource)]
+pub struct TerrainMaterialHandle(pub Handle<TerrainMaterial>);
+
+// Uniform block of the terrain shader, in its own module so the allow below stays on it:
+// `ShaderType` adds a never called `check` fn per field beside the struct.
+#[allow(dead_code)]
+mod uniforms {
+    use super::*;
+
+    #[derive(ShaderType, Reflect, Debug, Clone, Copy, Default)]
+    pub struct TerrainParams {
+        // Texture repeats per world unit
+        pub texture_scale: f32,
+        // Slope (1 - normal.y) where rock starts and fully replaces ground
+        pub slope_start: f32,
+        pub slope_end: f32,
+        // 0: planar mapping only, 1: triplanar rock on steep slopes
+        pub triplanar: u32,
+        // 0 dry, 1 soaked by rain: darker and glossier ground
+        pub wetness: f32,
+        // Flat ground above this height is covered in snow
+        pub snow_line: f32,
+        // Color multiplier for grass, changing with the seasons
+        pub foliage_tint: Vec3,
+        // Still water height and how far above it the shore is wet sand
+        pub water_level: f32,
+        pub shore_band: f32,
+        // Height of the rock layers drawn on cliffs and how strongly they show, 0 to 1
+        pub strata_spacing: f32,
+        pub strata_contrast: f32,
+        // Light from the sky above and bounced off the ground around, lighting surfaces the sun
+        // doesn't reach. Premultiplied by their brightness, like the ambient light.
+        pub sky_light: Vec3,
+        pub ground_light: Vec3,
+    }
+}
+
+pub use uniforms::TerrainParams;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TerrainExtension {
+    #[uniform(100)]
+    pub params: TerrainParams,
+    #[texture(101)]
+    #[sampler(102)]
+    pub ground_texture: Handle<Image>,
+    #[texture(103)]
+    #[sampler(104)]
+    pub rock_texture: Handle<Image>,
+}
+
+impl MaterialExtension for TerrainExtension {
+    fn fragment_shader() -> ShaderRef {
+        TERRAIN_SHADER_PATH.into()
+    }
+}
+
+// Tileable grayscale detail texture: noise sampled on a 4D torus wraps on both axes
+fn detail_texture(seed: u32, frequency: f64, stretch: f64, contrast: f32) -> Image {
+    let fbm = Fbm::<Perlin>::new(seed).set_octaves(5);
+    let size = DETAIL_TEXTURE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            let u = x as f64 / size as f64 * TAU;
+            let v = y as f64 / size as f64 * TAU;
+            let value = fbm.get([
+                u.cos() * frequency,
+                u.sin() * frequency,
+                v.cos() * frequency * stretch,
+                v.sin() * frequency * stretch,
+            ]) as f32;
+            let shade = ((1.0 - contrast) + contrast * (value * 0.5 + 0.5)).clamp(0.0, 1.0);
+            let byte = (shade * 255.0) as u8;
+            data.extend_from_slice(&[byte, byte, byte, 255]);
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+fn create_terrain_material(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+    settings: Res<GameSettings>,
+) {
+    let ground_texture = images.add(detail_texture(7, 1.5, 1.0, 0.35));
+    // Rock is stretched along one axis to give a layered look
+    let rock_texture = images.add(detail_texture(11, 1.0, 3.0, 0.6));
+
+    let material = materials.add(TerrainMaterial {
+        base: StandardMaterial {
+            base_color: Color::WHITE,
+            perceptual_roughness: 0.9,
+            ..default()
+        },
+        extension: TerrainExtension {
+            params: TerrainParams {
+                texture_scale: 0.15,
+                slope_start: 0.25,
+                slope_end: 0.5,
+                triplanar: settings.graphics.quality.triplanar_terrain() as u32,
+                wetness: 0.0,
+                snow_line: f32::MAX,
+                foliage_tint: Vec3::ONE,
+                water_level: f32::MIN,
+                shore_band: 0.0,
+                strata_spacing: 1.4,
+                strata_contrast: 0.8,
+                sky_light: Vec3::ZERO,
+                ground_light: Vec3::ZERO,
+            },
+            ground_texture,
+            rock_texture,
+        },
+    });
+    commands.insert_resource(TerrainMaterialHandle(material));
+}
+
+fn apply_terrain_quality(
+    settings: Res<GameSettings>,
+    handle: Res<TerrainMaterialHandle>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let triplanar = settings.graphics.quality.triplanar_terrain() as u32;
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.extension.params.triplanar = triplanar;
+    }
+}
@@ -0,0 +1,191 @@
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+use crate::audio_mixer::MixedAudio;
+use crate::biome::{self, Biome};
+use crate::camera::{CameraMode, CameraSettings, FreeCamera};
+use crate::creative::CreativeMode;
+use crate::hotbar::Hotbar;
+use crate::inventory::Inventory;
+use crate::placement::PlacementState;
+use crate::player::{Health, Player, RespawnPlayer, Thirst, PLAYER_HALF_HEIGHT};
+use crate::settings::GameSettings;
+use crate::terrain;
+use crate::water::WaterQuery;
+
+pub const WATERSKIN: &str = "waterskin";
+pub const CLEAN_WATERSKIN: &str = "waterskin_clean";
+pub const DIRTY_WATERSKIN: &str = "waterskin_dirty";
+// Thirst lost per second, a full body of water lasts a day and a half
+const THIRST_PER_SECOND: f32 = 0.11;
+// Health lost each DEHYDRATION_INTERVAL seconds once out of water
+const DEHYDRATION_DAMAGE: f32 = 2.0;
+const DEHYDRATION_INTERVAL: f32 = 4.0;
+// How far away a water surface can be filled from
+const FILL_REACH: f32 = 4.0;
+// Thirst a waterskin restores; dirty water quenches less and makes the player sick
+const CLEAN_DRINK: f32 = 40.0;
+const DIRTY_DRINK: f32 = 25.0;
+const DIRTY_WATER_DAMAGE: f32 = 8.0;
+const FILL_SOUND: &str = "sounds/fill_water.ogg";
+const DRINK_SOUND: &str = "sounds/drink.ogg";
+
+// A first survival loop: thirst drains over time, faster in hot biomes, and hurts once it
+// runs out. Left click with an empty waterskin while aiming at water fills it, clean or
+// dirty depending on the biome, and left click with a full one drinks it.
+#[derive(Default, Clone, Debug)]
+pub struct ThirstPlugin;
+
+impl Plugin for ThirstPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Dehydration>()
+            .add_systems(Update, (use_waterskin, update_thirst).chain());
+    }
+}
+
+// Seconds until the next dehydration damage
+#[derive(Resource, Default, Debug)]
+struct Dehydration(f32);
+
+// Standing water there is safe to drink: snowmelt and temperate lakes. Warm biomes keep it
+// stagnant.
+fn water_is_clean(biome: Biome) -> bool {
+    matches!(biome, Biome::Temperate | Biome::Tundra)
+}
+
+// How much faster thirst drains in a biome
+fn thirst_rate(biome: Biome) -> f32 {
+    match biome {
+        Biome::Desert => 2.0,
+        Biome::Savanna => 1.5,
+        _ => 1.0,
+    }
+}
+
+fn play_cue(commands: &mut Commands, asset_server: &AssetServer, settings: &GameSettings, sound: &'static str) {
+    let volume = settings.audio.master_volume * settings.audio.effects_volume;
+    if volume > 0.0 {
+        commands.spawn((
+            AudioPlayer::<MixedAudio>(asset_server.load(sound)),
+            PlaybackSettings::DESPAWN.with_volume(Volume::new(volume)),
+        ));
+    }
+}
+
+// Trade one held item for another; the slot follows the new one once the stack runs out
+fn swap_held(hotbar: &mut Hotbar, inventory: &mut Inventory, from: &str, to: &str) {
+    if !inventory.take_all(&[(from, 1)]) {
+        return;
+    }
+    *inventory.items.entry(to.to_string()).or_insert(0) += 1;
+    if inventory.count(from) == 0 && !hotbar.slots.iter().flatten().any(|slot| slot == to) {
+        let selected = hotbar.selected;
+        hotbar.slots[selected] = Some(to.to_string());
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn use_waterskin(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    camera_settings: Res<CameraSettings>,
+    placement: Res<PlacementState>,
+    water: WaterQuery,
+    settings: Res<GameSettings>,
+    asset_server: Res<AssetServer>,
+    mut hotbar: ResMut<Hotbar>,
+    mut inventory: ResMut<Inventory>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut player_query: Query<(&Transform, &mut Thirst, &mut Health), (With<Player>, Without<FreeCamera>)>,
+) {
+    if camera_settings.camera_mode != CameraMode::Player
+        || placement.active.is_some()
+        || !mouse_input.just_pressed(MouseButton::Left)
+        || contexts.ctx_mut().wants_pointer_input()
+    {
+        return;
+    }
+    let Some(held) = hotbar.held(&inventory).map(str::to_string) else {
+        return;
+    };
+    let (Ok(camera), Ok((player, mut thirst, mut health))) = (camera_query.get_single(), player_query.get_single_mut()) else {
+        return;
+    };
+
+    match held.as_str() {
+        WATERSKIN => {
+            let feet = player.translation - Vec3::Y * PLAYER_HALF_HEIGHT;
+            let eye = feet + Vec3::Y * camera_settings.eye_height;
+            // The aim stops on whichever comes first, the ground or the water above it
+            let surface = |x: f32, z: f32| {
+                let floor = water.floor_height(x, z);
+                water.surface_height(Vec2::new(x, z)).map_or(floor, |surface| surface.max(floor))
+            };
+            let Some(hit) = terrain::raycast(eye, *camera.forward(), FILL_REACH, surface) else {
+                return;
+            };
+            if !water.has_water(hit.x, hit.z) || hit.y <= water.floor_height(hit.x, hit.z) {
+                return;
+            }
+            let biome = biome::sample_at(hit.x, hit.z, hit.y).biome;
+            let filled = if water_is_clean(biome) { CLEAN_WATERSKIN } else { DIRTY_WATERSKIN };
+            swap_held(&mut hotbar, &mut inventory, WATERSKIN, filled);
+            play_cue(&mut commands, &asset_server, &settings, FILL_SOUND);
+            info!("Filled a waterskin with {} water", if filled == CLEAN_WATERSKIN { "clean" } else { "dirty" });
+        }
+        CLEAN_WATERSKIN | DIRTY_WATERSKIN => {
+            if thirst.is_full() {
+                return;
+            }
+            if held == CLEAN_WATERSKIN {
+                thirst.change(CLEAN_DRINK);
+            } else {
+                thirst.change(DIRTY_DRINK);
+                // Sickening, but never deadly on its own
+                let damage = DIRTY_WATER_DAMAGE.min(health.current - 1.0).max(0.0);
+                health.change(-damage);
+                info!("The water was dirty");
+            }
+            swap_held(&mut hotbar, &mut inventory, &held, WATERSKIN);
+            play_cue(&mut commands, &asset_server, &settings, DRINK_SOUND);
+        }
+        _ => {}
+    }
+}
+
+fn update_thirst(
+    time: Res<Time>,
+    creative: Res<CreativeMode>,
+    mut dehydration: ResMut<Dehydration>,
+    mut respawn_events: EventWriter<RespawnPlayer>,
+    mut player_query: Query<(&Transform, &mut Thirst, &mut Health), With<Player>>,
+) {
+    let Ok((transform, mut thirst, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    if creative.enabled {
+        return;
+    }
+    let dt = time.delta_secs();
+    let position = transform.translation;
+    let biome = biome::sample_at(position.x, position.z, position.y - PLAYER_HALF_HEIGHT).biome;
+    thirst.change(-THIRST_PER_SECOND * thirst_rate(biome) * dt);
+    if thirst.current > 0.0 {
+        dehydration.0 = 0.0;
+        return;
+    }
+
+    dehydration.0 -= dt;
+    if dehydration.0 > 0.0 {
+        return;
+    }
+    dehydration.0 = DEHYDRATION_INTERVAL;
+    health.change(-DEHYDRATION_DAMAGE);
+    if health.current <= 0.0 {
+        info!("Player died of thirst");
+        dehydration.0 = 0.0;
+        respawn_events.send(RespawnPlayer);
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashSet, VecDeque};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::avatar::RemotePlayer;
+use crate::day_night::TimeOfDay;
+use crate::items::ItemRegistry;
+use crate::net::{ClientId, ServerEvent, ServerMessage};
+use crate::quest::{ItemCollected, ObjectiveCompleted, QuestBook, QuestCompleted, QuestLog};
+use crate::settings::GameSettings;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+use crate::world_events::{WorldEventEnded, WorldEventStarted};
+
+const NOTIFICATIONS_WINDOW: &str = "notifications";
+// Toasts on screen at once, older ones make room for new ones
+const MAX_TOASTS: usize = 5;
+// Notifications kept in the log window
+const HISTORY_SIZE: usize = 100;
+// Seconds a toast takes to fade out at the end of its time
+const FADE_SECONDS: f32 = 0.5;
+const TOAST_WIDTH: f32 = 260.0;
+
+// Short notifications stacked in the bottom right corner for things happening in the game:
+// items picked up, kills, quest progress, players coming and going, world events, chunks
+// failing to generate.
+// Features send a `Toast` event; every notification is also kept in a log window.
+#[derive(Default, Clone, Debug)]
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<Toast>()
+            .init_resource::<Notifications>()
+            .register_ui_window(UiWindow {
+                id: NOTIFICATIONS_WINDOW,
+                title: "Notifications",
+                shortcut: Some(KeyCode::KeyN),
+                open: false,
+            })
+            .add_systems(Update, (
+                (toast_items, toast_quests, toast_players, toast_world_events),
+                collect_toasts,
+                toasts_ui,
+                notifications_window_ui,
+            ).chain());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastKind {
+    Item,
+    Combat,
+    Quest,
+    Player,
+    World,
+    Error,
+}
+
+impl ToastKind {
+    fn color(&self) -> egui::Color32 {
+        match self {
+            ToastKind::Combat => egui::Color32::from_rgb(240, 140, 70),
+            ToastKind::Item => egui::Color32::from_rgb(140, 210, 120),
+            ToastKind::Quest => egui::Color32::from_rgb(240, 200, 80),
+            ToastKind::Player => egui::Color32::from_rgb(110, 170, 240),
+            ToastKind::World => egui::Color32::from_rgb(190, 150, 240),
+            ToastKind::Error => egui::Color32::from_rgb(235, 90, 80),
+        }
+    }
+}
+
+#[derive(Event, Debug, Clone)]
+pub struct Toast {
+    pub kind: ToastKind,
+    pub text: String,
+}
+
+impl Toast {
+    pub fn new(kind: ToastKind, text: impl Into<String>) -> Self {
+        Self { kind, text: text.into() }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Notification {
+    toast: Toast,
+    // In-game day and hour it arrived at, for the log
+    day: u32,
+    hour: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+struct Notifications {
+    // Toasts on screen and the seconds each has been up
+    shown: VecDeque<(Toast, f32)>,
+    history: VecDeque<Notification>,
+}
+
+// Fully opaque until the last FADE_SECONDS of its time on screen
+pub fn toast_opacity(seconds: f32, age: f32) -> f32 {
+    ((seconds - age) / FADE_SECONDS).clamp(0.0, 1.0)
+}
+
+fn toast_items(
+    mut collected: EventReader<ItemCollected>,
+    registry: Res<ItemRegistry>,
+    mut toasts: EventWriter<Toast>,
+) {
+    for event in collected.read() {
+        let text = format!("+{} {} {}", event.count, registry.icon(&event.item), registry.name(&event.item));
+        toasts.send(Toast::new(ToastKind::Item, text));
+    }
+}
+
+fn toast_quests(
+    quest_log: Res<QuestLog>,
+    books: Res<Assets<QuestBook>>,
+    mut objectives: EventReader<ObjectiveCompleted>,
+    mut completed: EventReader<QuestCompleted>,
+    mut started: Local<Option<String>>,
+    mut toasts: EventWriter<Toast>,
+) {
+    let title = |quest_id: &str| {
+        books.get(&quest_log.book)
+            .and_then(|book| book.quests.iter().find(|quest| quest.id == quest_id))
+            .map_or(quest_id.to_string(), |quest| quest.title.clone())
+    };
+    for event in completed.read() {
+        toasts.send(Toast::new(ToastKind::Quest, format!("Quest completed: {}", title(&event.quest_id))));
+    }
+    for event in objectives.read() {
+        let total = books.get(&quest_log.book)
+            .and_then(|book| book.quests.iter().find(|quest| quest.id == event.quest_id))
+            .map_or(0, |quest| quest.objectives.len());
+        // The last objective's toast is the quest's own
+        if event.objective + 1 < total {
+            toasts.send(Toast::new(ToastKind::Quest, format!("{}: objective {}/{} done", title(&event.quest_id), event.objective + 1, total)));
+        }
+    }
+    let active = quest_log.active.as_ref().map(|active| &active.definition);
+    if active.map(|quest| &quest.id) != started.as_ref() {
+        if let Some(quest) = active {
+            toasts.send(Toast::new(ToastKind::Quest, format!("New quest: {}", quest.title)));
+        }
+        *started = active.map(|quest| quest.id.clone());
+    }
+}
+
+// Players already seen are shown again after reconnecting without having joined
+fn toast_players(
+    mut server_events: EventReader<ServerEvent>,
+    mut seen: Local<HashSet<ClientId>>,
+    mut toasts: EventWriter<Toast>,
+    added_query: Query<&RemotePlayer, Added<RemotePlayer>>,
+) {
+    for remote in added_query.iter() {
+        if seen.insert(remote.client) {
+            toasts.send(Toast::new(ToastKind::Player, format!("Player {} joined", remote.client.0)));
+        }
+    }
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::PlayerLeft { client } = message
+            && seen.remove(client)
+        {
+            toasts.send(Toast::new(ToastKind::Player, format!("Player {} left", client.0)));
+        }
+    }
+}
+
+fn toast_world_events(
+    mut started: EventReader<WorldEventStarted>,
+    mut ended: EventReader<WorldEventEnded>,
+    mut toasts: EventWriter<Toast>,
+) {
+    for event in started.read() {
+        toasts.send(Toast::new(ToastKind::World, format!("{} started", event_title(event.name))));
+    }
+    for event in ended.read() {
+        toasts.send(Toast::new(ToastKind::World, format!("{} ended", event_title(event.name))));
+    }
+}
+
+// "meteor_shower" as "Meteor shower"
+fn event_title(name: &str) -> String {
+    let name = name.replace('_', " ");
+    let mut chars = name.chars();
+    chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+}
+
+fn collect_toasts(
+    mut events: EventReader<Toast>,
+    time: Res<Time<Real>>,
+    settings: Res<GameSettings>,
+    time_of_day: Res<TimeOfDay>,
+    mut notifications: ResMut<Notifications>,
+) {
+    let seconds = settings.notifications.toast_seconds;
+    let dt = time.delta_secs();
+    for (_, age) in notifications.shown.iter_mut() {
+        *age += dt;
+    }
+    notifications.shown.retain(|(_, age)| *age < seconds);
+    for toast in events.read() {
+        if settings.notifications.toasts {
+            notifications.shown.push_back((toast.clone(), 0.0));
+        }
+        notifications.history.push_front(Notification { toast: toast.clone(), day: time_of_day.day, hour: time_of_day.hour });
+    }
+    while notifications.shown.len() > MAX_TOASTS {
+        notifications.shown.pop_front();
+    }
+    notifications.history.truncate(HISTORY_SIZE);
+}
+
+fn toasts_ui(
+    mut contexts: EguiContexts,
+    settings: Res<GameSettings>,
+    notifications: Res<Notifications>,
+) {
+    if notifications.shown.is_empty() {
+        return;
+    }
+    let seconds = settings.notifications.toast_seconds;
+    egui::Area::new(egui::Id::new("toasts"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.set_width(TOAST_WIDTH);
+            for (toast, age) in &notifications.shown {
+                ui.scope(|ui| {
+                    ui.set_opacity(toast_opacity(seconds, *age));
+                    egui::Frame::NONE
+                        .fill(egui::Color32::from_black_alpha(190))
+                        .stroke(egui::Stroke::new(1.0, toast.kind.color()))
+                        .corner_radius(4.0)
+                        .inner_margin(8.0)
+                        .show(ui, |ui| {
+                            ui.set_width(TOAST_WIDTH - 16.0);
+                            ui.label(egui::RichText::new(&toast.text).color(toast.kind.color()));
+                        });
+                });
+                ui.add_space(4.0);
+            }
+        });
+}
+
+fn notifications_window_ui(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut notifications: ResMut<Notifications>,
+) {
+    let Some(window) = windows.window(NOTIFICATIONS_WINDOW) else {
+        return;
+    };
+    let mut clear = false;
+    window.default_width(320.0).show(contexts.ctx_mut(), |ui| {
+        if notifications.history.is_empty() {
+            ui.label("Nothing yet");
+        }
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for notification in &notifications.history {
+                ui.horizontal(|ui| {
+                    let (hours, minutes) = (notification.hour as u32, (notification.hour.fract() * 60.0) as u32);
+                    ui.weak(format!("Day {} {:02}:{:02}", notification.day, hours, minutes));
+                    ui.label(egui::RichText::new(&notification.toast.text).color(notification.toast.kind.color()));
+                });
+            }
+        });
+        ui.separator();
+        clear = ui.button("Clear").clicked();
+    });
+    if clear {
+        notifications.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toasts_fade_at_the_end() {
+        assert_eq!(toast_opacity(5.0, 0.0), 1.0);
+        assert_eq!(toast_opacity(5.0, 4.5), 1.0);
+        assert_eq!(toast_opacity(5.0, 4.75), 0.5);
+        assert_eq!(toast_opacity(5.0, 6.0), 0.0);
+    }
+}
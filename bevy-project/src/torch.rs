@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::day_night::TimeOfDay;
+use crate::player::{Player, PLAYER_RADIUS};
+use crate::quest::ItemCollected;
+use crate::input_map::{Action, Actions};
+
+// Seconds of light from a full torch
+const TORCH_FUEL_CAPACITY: f32 = 300.0;
+// Fuel given by one collected item
+const FUEL_PER_ITEM: f32 = 60.0;
+const FUEL_ITEM: &str = "wood";
+const TORCH_INTENSITY: f32 = 200_000.0;
+const TORCH_RANGE: f32 = 25.0;
+// Where the flame sits relative to the player's center
+const TORCH_OFFSET: Vec3 = Vec3::new(PLAYER_RADIUS + 0.2, 0.6, -0.3);
+
+#[derive(Default, Clone, Debug)]
+pub struct TorchPlugin;
+
+impl Plugin for TorchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<TorchFuel>()
+            .add_systems(Update, (
+                equip_torch,
+                toggle_torch,
+                refuel_torch,
+                burn_torch,
+                flicker_torch,
+                torch_hud_system,
+            ).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct Torch {
+    pub lit: bool,
+}
+
+// Fuel carried by the player, burnt while the torch is lit
+#[derive(Resource)]
+pub struct TorchFuel {
+    pub seconds: f32,
+    pub capacity: f32,
+}
+
+impl Default for TorchFuel {
+    fn default() -> Self {
+        Self {
+            seconds: TORCH_FUEL_CAPACITY,
+            capacity: TORCH_FUEL_CAPACITY,
+        }
+    }
+}
+
+impl TorchFuel {
+    pub fn is_empty(&self) -> bool {
+        self.seconds <= 0.0
+    }
+
+    pub fn fraction(&self) -> f32 {
+        (self.seconds / self.capacity).clamp(0.0, 1.0)
+    }
+}
+
+// Give the player a torch once it is spawned; it starts unlit
+fn equip_torch(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    player_query: Query<Entity, Added<Player>>,
+) {
+    for player in player_query.iter() {
+        let handle = materials.add(StandardMaterial {
+            base_color: Color::srgb(0.35, 0.22, 0.1),
+            perceptual_roughness: 0.9,
+            ..default()
+        });
+        let flame = materials.add(StandardMaterial {
+            base_color: Color::srgb(1.0, 0.6, 0.2),
+            emissive: LinearRgba::rgb(8.0, 3.0, 0.6),
+            ..default()
+        });
+
+        commands.entity(player).with_children(|parent| {
+            parent.spawn((
+                Mesh3d(meshes.add(Cylinder::new(0.04, 0.6))),
+                MeshMaterial3d(handle),
+                Transform::from_translation(TORCH_OFFSET - Vec3::Y * 0.3),
+            ));
+            parent.spawn((
+                Mesh3d(meshes.add(Sphere::new(0.08))),
+                MeshMaterial3d(flame),
+                PointLight {
+                    color: Color::srgb(1.0, 0.65, 0.3),
+                    intensity: 0.0,
+                    range: TORCH_RANGE,
+                    shadows_enabled: true,
+                    ..default()
+                },
+                Transform::from_translation(TORCH_OFFSET),
+                Visibility::Hidden,
+                Torch { lit: false },
+            ));
+        });
+    }
+}
+
+// T lights or puts out the torch
+fn toggle_torch(
+    actions: Actions,
+    fuel: Res<TorchFuel>,
+    mut torch_query: Query<(&mut Torch, &mut Visibility)>,
+) {
+    if !actions.just_pressed(Action::Torch) {
+        return;
+    }
+
+    for (mut torch, mut visibility) in torch_query.iter_mut() {
+        if !torch.lit && fuel.is_empty() {
+            info!("Torch has no fuel");
+            continue;
+        }
+        torch.lit = !torch.lit;
+        *visibility = if torch.lit { Visibility::Inherited } else { Visibility::Hidden };
+        info!("Torch {}", if torch.lit { "lit" } else { "put out" });
+    }
+}
+
+fn refuel_torch(
+    mut collected: EventReader<ItemCollected>,
+    mut fuel: ResMut<TorchFuel>,
+) {
+    for event in collected.read() {
+        if event.item == FUEL_ITEM {
+            fuel.seconds = (fuel.seconds + FUEL_PER_ITEM * event.count as f32).min(fuel.capacity);
+        }
+    }
+}
+
+// Lit torches consume fuel and go out when it runs dry
+fn burn_torch(
+    time: Res<Time>,
+    mut fuel: ResMut<TorchFuel>,
+    mut torch_query: Query<(&mut Torch, &mut Visibility)>,
+) {
+    let mut burning = false;
+    for (torch, _) in torch_query.iter() {
+        burning |= torch.lit;
+    }
+    if !burning {
+        return;
+    }
+
+    fuel.seconds = (fuel.seconds - time.delta_secs()).max(0.0);
+    if !fuel.is_empty() {
+        return;
+    }
+
+    for (mut torch, mut visibility) in torch_query.iter_mut() {
+        if torch.lit {
+            torch.lit = false;
+            *visibility = Visibility::Hidden;
+            info!("Torch burnt out");
+        }
+    }
+}
+
+// Layered sines give an irregular flicker; the flame dims as fuel runs low
+fn flicker_torch(
+    time: Res<Time>,
+    fuel: Res<TorchFuel>,
+    mut torch_query: Query<(&Torch, &mut PointLight, &mut Transform)>,
+) {
+    let t = time.elapsed_secs();
+    let flicker = 0.85
+        + 0.08 * (t * 13.0).sin()
+        + 0.05 * (t * 23.7 + 1.3).sin()
+        + 0.02 * (t * 41.0 + 0.7).sin();
+    let strength = 0.5 + 0.5 * fuel.fraction().sqrt();
+
+    for (torch, mut light, mut transform) in torch_query.iter_mut() {
+        light.intensity = if torch.lit { TORCH_INTENSITY * flicker * strength } else { 0.0 };
+        transform.scale = Vec3::splat(0.9 + (flicker - 0.85) * 1.5);
+    }
+}
+
+fn torch_hud_system(
+    mut contexts: EguiContexts,
+    fuel: Res<TorchFuel>,
+    time_of_day: Res<TimeOfDay>,
+    torch_query: Query<&Torch>,
+) {
+    let lit = torch_query.iter().any(|torch| torch.lit);
+    // Only shown when the torch matters
+    if !lit && !time_of_day.is_night() {
+        return;
+    }
+
+    egui::Area::new(egui::Id::new("torch_hud"))
+        .anchor(egui::Align2::LEFT_BOTTOM, [10.0, -10.0])
+        .show(contexts.ctx_mut(), |ui| {
+            let label = if lit { "Torch (lit)" } else { "[T] Light torch" };
+            ui.label(label);
+            ui.add(egui::ProgressBar::new(fuel.fraction())
+                .desired_width(150.0)
+                .text(format!("{:.0} s", fuel.seconds)));
+        });
+}
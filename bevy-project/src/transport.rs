@@ -0,0 +1,382 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Port the dedicated server listens on for clients unless told otherwise
+pub const DEFAULT_PORT: u16 = 7777;
+
+// Largest payload a single UDP datagram can carry
+pub const MAX_FRAME_SIZE: usize = 65_507;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("socket error: {0}")]
+    Io(#[from] io::Error),
+    #[error("the other end of the connection is gone")]
+    Disconnected,
+    #[error("frame of {0} bytes is larger than {MAX_FRAME_SIZE}")]
+    FrameTooLarge(usize),
+}
+
+// A link to one peer carrying whole messages (frames); never blocks
+pub trait Transport: Send + Sync + 'static {
+    fn send(&mut self, frame: &[u8]) -> Result<(), TransportError>;
+    // Next frame received from the peer, None when nothing is waiting
+    fn recv(&mut self) -> Result<Option<Vec<u8>>, TransportError>;
+}
+
+// Opens new links to the same peer, to reconnect after one is lost
+pub trait Connector: Send + Sync + 'static {
+    fn connect(&mut self) -> Result<Box<dyn Transport>, TransportError>;
+}
+
+type FrameQueue = Arc<Mutex<VecDeque<Vec<u8>>>>;
+
+// In-process transport: frames go through shared queues, in order and without loss.
+// Used for single player and for deterministic network scenarios without sockets.
+pub struct LoopbackTransport {
+    outgoing: FrameQueue,
+    incoming: FrameQueue,
+}
+
+// Two connected ends, whatever one sends the other receives
+pub fn loopback_pair() -> (LoopbackTransport, LoopbackTransport) {
+    let a_to_b = FrameQueue::default();
+    let b_to_a = FrameQueue::default();
+    (
+        LoopbackTransport { outgoing: a_to_b.clone(), incoming: b_to_a.clone() },
+        LoopbackTransport { outgoing: b_to_a, incoming: a_to_b },
+    )
+}
+
+impl LoopbackTransport {
+    // The peer dropped its end, each queue is then only held by this end
+    fn peer_dropped(&self) -> bool {
+        Arc::strong_count(&self.outgoing) == 1
+    }
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if self.peer_dropped() {
+            return Err(TransportError::Disconnected);
+        }
+        self.outgoing.lock().unwrap().push_back(frame.to_vec());
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        // Frames sent before the peer left are still delivered
+        match self.incoming.lock().unwrap().pop_front() {
+            Some(frame) => Ok(Some(frame)),
+            None if self.peer_dropped() => Err(TransportError::Disconnected),
+            None => Ok(None),
+        }
+    }
+}
+
+// UDP has no connection to lose, so both ends send an empty datagram when they have been
+// quiet for HEARTBEAT_INTERVAL and give the link up once nothing came for PEER_TIMEOUT.
+// Empty frames are never handed out, every message encodes to at least one byte.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The local address to send to `peer` from, any interface and port of its family
+fn any_local_addr(peer: SocketAddr) -> SocketAddr {
+    match peer {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
+
+// One datagram per frame to a single peer. Like UDP itself, frames may be lost or reordered.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    last_heard: Instant,
+    last_sent: Instant,
+}
+
+impl UdpTransport {
+    pub fn connect(local: impl ToSocketAddrs, peer: impl ToSocketAddrs) -> Result<Self, TransportError> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        socket.set_nonblocking(true)?;
+        let now = Instant::now();
+        Ok(Self {
+            socket,
+            buffer: vec![0; MAX_FRAME_SIZE],
+            last_heard: now,
+            last_sent: now,
+        })
+    }
+
+    // A link to a server, from whichever local port the system picks
+    pub fn to_server(server: SocketAddr) -> Result<Self, TransportError> {
+        Self::connect(any_local_addr(server), server)
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.socket.local_addr()?)
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TransportError::FrameTooLarge(frame.len()));
+        }
+        match self.socket.send(frame) {
+            Ok(_) => {}
+            // Full send buffer, drop the frame as the network would
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        if self.last_sent.elapsed() >= HEARTBEAT_INTERVAL {
+            self.send(&[])?;
+        }
+        loop {
+            match self.socket.recv(&mut self.buffer) {
+                Ok(size) => {
+                    self.last_heard = Instant::now();
+                    if size > 0 {
+                        return Ok(Some(self.buffer[..size].to_vec()));
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    return if self.last_heard.elapsed() >= PEER_TIMEOUT {
+                        Err(TransportError::Disconnected)
+                    } else {
+                        Ok(None)
+                    };
+                }
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => return Err(TransportError::Disconnected),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+// Reopens a UDP link to the same server
+pub struct UdpConnector {
+    pub server: SocketAddr,
+}
+
+impl Connector for UdpConnector {
+    fn connect(&mut self) -> Result<Box<dyn Transport>, TransportError> {
+        Ok(Box::new(UdpTransport::to_server(self.server)?))
+    }
+}
+
+// Frames the listener read for one peer, waiting for its transport
+struct PeerInbox {
+    frames: VecDeque<Vec<u8>>,
+    last_heard: Instant,
+}
+
+// The server's single socket, split into a transport per client address
+pub struct UdpListener {
+    socket: Arc<UdpSocket>,
+    buffer: Vec<u8>,
+    peers: HashMap<SocketAddr, Arc<Mutex<PeerInbox>>>,
+}
+
+impl UdpListener {
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self, TransportError> {
+        let socket = UdpSocket::bind(address)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket: Arc::new(socket),
+            buffer: vec![0; MAX_FRAME_SIZE],
+            peers: HashMap::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    // Hand every waiting datagram to its peer's transport, and return a transport for each
+    // address heard from for the first time. Dropping one forgets the peer, so its next
+    // frame comes back here as a new peer.
+    pub fn accept(&mut self) -> Result<Vec<(SocketAddr, UdpPeer)>, TransportError> {
+        self.peers.retain(|_, inbox| Arc::strong_count(inbox) > 1);
+        let mut accepted = Vec::new();
+        loop {
+            let (size, address) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // An earlier send to a closed port, reported on the next read on some systems
+                Err(err) if matches!(err.kind(), io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionRefused) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let frame = &self.buffer[..size];
+            let now = Instant::now();
+            if let Some(inbox) = self.peers.get(&address) {
+                let mut inbox = inbox.lock().unwrap();
+                inbox.last_heard = now;
+                if !frame.is_empty() {
+                    inbox.frames.push_back(frame.to_vec());
+                }
+            } else if !frame.is_empty() {
+                // A heartbeat alone doesn't open a link, it is left over from one the server dropped
+                let inbox = Arc::new(Mutex::new(PeerInbox {
+                    frames: VecDeque::from([frame.to_vec()]),
+                    last_heard: now,
+                }));
+                self.peers.insert(address, inbox.clone());
+                accepted.push((address, UdpPeer {
+                    socket: self.socket.clone(),
+                    address,
+                    inbox,
+                    last_sent: now,
+                }));
+            }
+        }
+        Ok(accepted)
+    }
+}
+
+// The server's end of one client's link through a `UdpListener`, fed by its `accept`
+pub struct UdpPeer {
+    socket: Arc<UdpSocket>,
+    address: SocketAddr,
+    inbox: Arc<Mutex<PeerInbox>>,
+    last_sent: Instant,
+}
+
+impl Transport for UdpPeer {
+    fn send(&mut self, frame: &[u8]) -> Result<(), TransportError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TransportError::FrameTooLarge(frame.len()));
+        }
+        match self.socket.send_to(frame, self.address) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err.into()),
+        }
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Option<Vec<u8>>, TransportError> {
+        if self.last_sent.elapsed() >= HEARTBEAT_INTERVAL {
+            self.send(&[])?;
+        }
+        let mut inbox = self.inbox.lock().unwrap();
+        match inbox.frames.pop_front() {
+            Some(frame) => Ok(Some(frame)),
+            None if inbox.last_heard.elapsed() >= PEER_TIMEOUT => Err(TransportError::Disconnected),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{decode_message, encode_message, AccountKey, ClientMessage};
+    use bevy::math::Vec3;
+
+    // A join and some movement, encoded as the client sends them
+    fn client_frames() -> Vec<Vec<u8>> {
+        let mut messages = vec![ClientMessage::Hello { account: AccountKey::generate() }];
+        messages.extend((0..20).map(|step| ClientMessage::PlayerState {
+            position: Vec3::new(step as f32, 1.0, 0.0),
+            yaw: 0.0,
+        }));
+        messages.iter().map(encode_message).collect()
+    }
+
+    // Every frame waiting on `transport`, each one a whole message
+    fn drain(transport: &mut impl Transport) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while let Some(frame) = transport.recv().unwrap() {
+            assert!(decode_message::<ClientMessage>(&frame).is_some(), "frame is not one whole message");
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn loopback_delivers_whole_frames_in_order() {
+        let (mut client, mut server) = loopback_pair();
+        let sent = client_frames();
+        for frame in &sent {
+            client.send(frame).unwrap();
+        }
+        assert_eq!(drain(&mut server), sent);
+
+        server.send(b"welcome").unwrap();
+        assert_eq!(client.recv().unwrap(), Some(b"welcome".to_vec()));
+        client.send(&sent[0]).unwrap();
+        drop(client);
+        // Frames sent before the client left still arrive, then the link reports it gone
+        assert_eq!(server.recv().unwrap(), Some(sent[0].clone()));
+        assert!(matches!(server.recv(), Err(TransportError::Disconnected)));
+        assert!(matches!(server.send(b"anyone?"), Err(TransportError::Disconnected)));
+    }
+
+    // Read from the listener until `count` frames came from the one client, UDP delivers
+    // asynchronously even on the loopback interface
+    fn accept_frames(listener: &mut UdpListener, count: usize) -> (UdpPeer, Vec<Vec<u8>>) {
+        let mut peer = None;
+        let mut frames = Vec::new();
+        for _ in 0..200 {
+            for (_, accepted) in listener.accept().unwrap() {
+                assert!(peer.is_none(), "one client shows up as one peer");
+                peer = Some(accepted);
+            }
+            if let Some(peer) = &mut peer {
+                frames.extend(drain(peer));
+            }
+            if frames.len() >= count {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        (peer.expect("the client was accepted"), frames)
+    }
+
+    #[test]
+    fn udp_listener_splits_out_a_transport_per_client() {
+        let mut listener = UdpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = UdpTransport::to_server(listener.local_addr().unwrap()).unwrap();
+        let sent = client_frames();
+        for frame in &sent {
+            client.send(frame).unwrap();
+            // Paced, a burst could overflow the receive buffer and lose frames
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        client.send(&[]).unwrap();
+
+        let (mut peer, received) = accept_frames(&mut listener, sent.len());
+        assert_eq!(received, sent);
+        assert_eq!(peer.address, client.local_addr().unwrap());
+
+        peer.send(b"welcome").unwrap();
+        let mut reply = None;
+        for _ in 0..200 {
+            reply = client.recv().unwrap();
+            if reply.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(reply, Some(b"welcome".to_vec()));
+
+        // Once the server drops the peer, the same address is accepted anew
+        drop(peer);
+        client.send(&sent[1]).unwrap();
+        let (_, received) = accept_frames(&mut listener, 1);
+        assert_eq!(received, [sent[1].clone()]);
+    }
+}
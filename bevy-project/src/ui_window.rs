@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+// Window manager for the egui windows: each feature registers its window with
+// `register_ui_window` and draws it through `UiWindows::window`, which is None
+// while it's closed. A menu bar at the top of the screen opens and closes them.
+#[derive(Default, Clone, Debug)]
+pub struct UiWindowPlugin;
+
+impl Plugin for UiWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<UiWindows>()
+            .add_systems(Update, (toggle_ui_windows, menu_bar_ui).chain());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UiWindow {
+    pub id: &'static str,
+    pub title: &'static str,
+    // Key opening and closing the window besides the menu bar
+    pub shortcut: Option<KeyCode>,
+    // Open when the game starts
+    pub open: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct UiWindows {
+    windows: Vec<UiWindow>,
+}
+
+impl UiWindows {
+    fn get_mut(&mut self, id: &str) -> Option<&mut UiWindow> {
+        self.windows.iter_mut().find(|window| window.id == id)
+    }
+
+    pub fn is_open(&self, id: &str) -> bool {
+        self.windows.iter().any(|window| window.id == id && window.open)
+    }
+
+    // The egui window to show this frame, wired to the open flag so its close button works.
+    // None while the window is closed or was never registered.
+    pub fn window(&mut self, id: &str) -> Option<egui::Window<'_>> {
+        let window = self.get_mut(id).filter(|window| window.open)?;
+        Some(egui::Window::new(window.title).open(&mut window.open))
+    }
+}
+
+pub trait UiWindowAppExt {
+    fn register_ui_window(&mut self, window: UiWindow) -> &mut Self;
+}
+
+impl UiWindowAppExt for App {
+    fn register_ui_window(&mut self, window: UiWindow) -> &mut Self {
+        self.world_mut().get_resource_or_insert_with(UiWindows::default).windows.push(window);
+        self
+    }
+}
+
+fn toggle_ui_windows(
+    input: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+) {
+    // Typing in a text field never toggles windows
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    for window in windows.windows.iter_mut() {
+        if window.shortcut.is_some_and(|key| input.just_pressed(key)) {
+            window.open = !window.open;
+        }
+    }
+}
+
+fn shortcut_label(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(letter) => letter.to_string(),
+        None if key == KeyCode::Backquote => "`".to_string(),
+        None => name,
+    }
+}
+
+fn menu_bar_ui(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+) {
+    egui::TopBottomPanel::top("menu_bar").show(contexts.ctx_mut(), |ui| {
+        egui::menu::bar(ui, |ui| {
+            for window in windows.windows.iter_mut() {
+                let response = ui.toggle_value(&mut window.open, window.title);
+                if let Some(key) = window.shortcut {
+                    response.on_hover_text(format!("Shortcut: {}", shortcut_label(key)));
+                }
+            }
+        });
+    });
+}
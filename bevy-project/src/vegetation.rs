@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::biome::{self, Biome};
+use crate::birds::is_forest;
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::collider::BoxCollider;
+use crate::road::{near_road, Road};
+use crate::ruins::ruin_in_chunk;
+use crate::streaming::ChunkCoords;
+use crate::terrain;
+use crate::terrain_edit::TerrainEdits;
+use crate::water::sea_level;
+use crate::world_rng::{RngPurpose, WorldRng};
+
+// Spots tried for a tree in each chunk, by how wooded the land is; only suitable ground gets one
+const FOREST_TRIES: u32 = 18;
+const WOODLAND_TRIES: u32 = 4;
+const TUNDRA_TRIES: u32 = 2;
+// Trees keep off ground steeper than this, and stay this far above the waterline
+const MAX_SLOPE: f32 = 0.6;
+const SHORE_CLEARANCE: f32 = 0.8;
+// Ruins keep this much room around them clear
+const RUIN_CLEARANCE: f32 = 2.0;
+const TRUNK_HEIGHT: f32 = 3.0;
+const TRUNK_RADIUS: f32 = 0.25;
+const CROWN_RADIUS: f32 = 1.8;
+const CONE_HEIGHT: f32 = 5.0;
+
+// Trees over the terrain, thick in forests and sparse elsewhere. Where they grow comes from
+// WorldRng, so a chunk always gets the same trees, the same ones on every client.
+// They come and go with the terrain chunks.
+#[derive(Default, Clone, Debug)]
+pub struct VegetationPlugin;
+
+impl Plugin for VegetationPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Vegetation>()
+            .add_systems(Startup, setup_vegetation_assets)
+            .add_systems(Update, stream_vegetation);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeSpot {
+    pub position: Vec2,
+    pub scale: f32,
+    pub yaw: f32,
+    // Cone crowned, for cold land
+    pub conifer: bool,
+}
+
+// Where a chunk's trees stand, picked on the natural terrain
+pub fn scatter_trees(chunk: ChunkCoords) -> Vec<TreeSpot> {
+    let rng = WorldRng::for_chunk(chunk, RngPurpose::Vegetation);
+    let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+    let sample = biome::sample(center.x, center.y);
+    let tries = match sample.biome {
+        _ if is_forest(&sample) => FOREST_TRIES,
+        Biome::Rainforest => FOREST_TRIES,
+        Biome::Temperate | Biome::Savanna => WOODLAND_TRIES,
+        Biome::Tundra => TUNDRA_TRIES,
+        Biome::Desert => 0,
+    };
+    let ruin = ruin_in_chunk(chunk);
+    // Each tree draws from its own stream, so a spot turned down doesn't move the others
+    (0..tries)
+        .filter_map(|index| {
+            let mut rng = rng.substream(index);
+            let position = center + (Vec2::new(rng.unit(), rng.unit()) - 0.5) * CHUNK_SIZE;
+            let (scale, yaw) = (rng.range(0.7, 1.3), rng.range(0.0, std::f32::consts::TAU));
+            let height = |dx: f32, dz: f32| terrain::height(position.x + dx, position.y + dz);
+            let slope = Vec2::new(height(1.0, 0.0) - height(-1.0, 0.0), height(0.0, 1.0) - height(0.0, -1.0)).length() / 2.0;
+            let dry = height(0.0, 0.0) > sea_level(position.x, position.y) + SHORE_CLEARANCE;
+            let clear = ruin.as_ref().is_none_or(|ruin| ruin.center.xz().distance(position) > ruin.radius + RUIN_CLEARANCE);
+            let conifer = sample.biome == Biome::Tundra || biome::temperature(position.x, position.y) < 0.0;
+            (dry && clear && slope <= MAX_SLOPE).then_some(TreeSpot { position, scale, yaw, conifer })
+        })
+        .collect()
+}
+
+#[derive(Resource, Default)]
+struct Vegetation {
+    // Tree entities of the loaded chunks
+    spawned: HashMap<ChunkCoords, Vec<Entity>>,
+}
+
+#[derive(Resource)]
+struct VegetationAssets {
+    trunk_mesh: Handle<Mesh>,
+    crown_mesh: Handle<Mesh>,
+    cone_mesh: Handle<Mesh>,
+    bark: Handle<StandardMaterial>,
+    leaves: Handle<StandardMaterial>,
+    needles: Handle<StandardMaterial>,
+}
+
+fn setup_vegetation_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let foliage = |color| StandardMaterial { base_color: color, perceptual_roughness: 0.9, ..default() };
+    commands.insert_resource(VegetationAssets {
+        trunk_mesh: meshes.add(Cylinder::new(TRUNK_RADIUS, TRUNK_HEIGHT)),
+        crown_mesh: meshes.add(Sphere::new(CROWN_RADIUS).mesh().ico(2).unwrap()),
+        cone_mesh: meshes.add(Cone { radius: CROWN_RADIUS, height: CONE_HEIGHT }),
+        bark: materials.add(foliage(Color::srgb(0.33, 0.24, 0.16))),
+        leaves: materials.add(foliage(Color::srgb(0.22, 0.42, 0.16))),
+        needles: materials.add(foliage(Color::srgb(0.13, 0.3, 0.18))),
+    });
+}
+
+fn spawn_tree(commands: &mut Commands, assets: &VegetationAssets, tree: &TreeSpot, ground: f32) -> Entity {
+    let (crown, material, crown_height) = if tree.conifer {
+        (assets.cone_mesh.clone(), assets.needles.clone(), TRUNK_HEIGHT * 0.6 + CONE_HEIGHT / 2.0)
+    } else {
+        (assets.crown_mesh.clone(), assets.leaves.clone(), TRUNK_HEIGHT + CROWN_RADIUS * 0.6)
+    };
+    commands
+        .spawn((
+            Transform::from_xyz(tree.position.x, ground, tree.position.y)
+                .with_rotation(Quat::from_rotation_y(tree.yaw))
+                .with_scale(Vec3::splat(tree.scale)),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            // Colliders don't scale with their transform, the trunk's is sized here
+            parent.spawn((
+                Mesh3d(assets.trunk_mesh.clone()),
+                MeshMaterial3d(assets.bark.clone()),
+                Transform::from_xyz(0.0, TRUNK_HEIGHT / 2.0, 0.0),
+                BoxCollider { half_size: Vec3::new(TRUNK_RADIUS, TRUNK_HEIGHT / 2.0, TRUNK_RADIUS) * tree.scale },
+            ));
+            parent.spawn((
+                Mesh3d(crown),
+                MeshMaterial3d(material),
+                Transform::from_xyz(0.0, crown_height, 0.0),
+            ));
+        })
+        .id()
+}
+
+// Trees grow with the chunks loaded, on the ground as edited, and not on the roads
+fn stream_vegetation(
+    mut commands: Commands,
+    mut vegetation: ResMut<Vegetation>,
+    mut roads: Local<Vec<Road>>,
+    chunk_manager: Res<ChunkManager>,
+    terrain_edits: Res<TerrainEdits>,
+    assets: Option<Res<VegetationAssets>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    // New roads clear their way, every chunk's trees are planted again
+    let roads_changed = terrain_edits.is_changed() && *roads != terrain_edits.roads;
+    if roads_changed {
+        roads.clone_from(&terrain_edits.roads);
+    }
+    if !chunk_manager.is_changed() && !roads_changed {
+        return;
+    }
+    vegetation.spawned.retain(|coords, entities| {
+        let keep = chunk_manager.loaded_chunks.contains_key(coords) && !roads_changed;
+        if !keep {
+            for entity in entities.drain(..) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        keep
+    });
+    for coords in chunk_manager.loaded_chunks.keys() {
+        if vegetation.spawned.contains_key(coords) {
+            continue;
+        }
+        let entities = scatter_trees(*coords)
+            .iter()
+            .filter(|tree| !near_road(&roads, tree.position.x, tree.position.y))
+            .map(|tree| spawn_tree(&mut commands, &assets, tree, terrain_edits.height(tree.position.x, tree.position.y)))
+            .collect();
+        vegetation.spawned.insert(*coords, entities);
+    }
+}
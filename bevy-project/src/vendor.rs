@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::Deserialize;
+use crate::asset_registry::{AssetRegistry, AssetRegistryAppExt};
+use crate::biome::Biome;
+use crate::day_night::TimeOfDay;
+use crate::dialogue::{DialogueAction, DialogueActionTaken, Npc};
+use crate::inventory::Inventory;
+use crate::items::{item_label, ItemRegistry};
+use crate::player::Player;
+use crate::ron_asset::RonAssetPlugin;
+
+pub const COIN: &str = "coin";
+const STOCK_TABLES_PATH: &str = "npcs/default.stock.ron";
+const STOCK_TABLES: &str = "stock_tables";
+// Walking further than this from the vendor closes the shop
+const LEAVE_DISTANCE: f32 = 5.0;
+
+// Village vendors selling and buying goods for coins. What they trade, at which prices and
+// how much they hold come from the stock table of their village's biome, and their stock
+// fills back up at the start of each day. Their dialogue opens the shop.
+#[derive(Default, Clone, Debug)]
+pub struct VendorPlugin;
+
+impl Plugin for VendorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_plugins(RonAssetPlugin::<StockTables>::new(&["stock.ron"]))
+            .preload_asset::<StockTables>(STOCK_TABLES, STOCK_TABLES_PATH)
+            .init_resource::<Shop>()
+            .add_systems(Update, (
+                restock_vendors,
+                open_shops,
+                close_distant_shop,
+                shop_ui,
+            ).chain());
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StockEntry {
+    pub item: String,
+    // Coins the player pays for one, None if the vendor doesn't sell it
+    #[serde(default)]
+    pub buy_price: Option<u32>,
+    // Coins the vendor pays for one, None if they don't buy it
+    #[serde(default)]
+    pub sell_price: Option<u32>,
+    // How many the vendor holds after restocking
+    #[serde(default)]
+    pub stock: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct StockTable {
+    pub entries: Vec<StockEntry>,
+}
+
+impl StockTable {
+    fn entry(&self, item: &str) -> Option<&StockEntry> {
+        self.entries.iter().find(|entry| entry.item == item)
+    }
+}
+
+// Goods traded by vendors: a default table plus optional per-biome overrides
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct StockTables {
+    pub default: StockTable,
+    #[serde(default)]
+    pub biomes: HashMap<Biome, StockTable>,
+}
+
+impl StockTables {
+    pub fn table(&self, biome: Biome) -> &StockTable {
+        self.biomes.get(&biome).unwrap_or(&self.default)
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Vendor {
+    // Biome of the vendor's village, picking their stock table
+    pub biome: Biome,
+    pub stock: HashMap<String, u32>,
+    // Day the stock was last filled up
+    restocked_day: Option<u32>,
+}
+
+impl Vendor {
+    pub fn new(biome: Biome) -> Self {
+        Self { biome, stock: HashMap::new(), restocked_day: None }
+    }
+}
+
+// The vendor whose shop window is open
+#[derive(Resource, Default, Debug)]
+struct Shop {
+    vendor: Option<Entity>,
+}
+
+fn stock_tables<'a>(registry: &AssetRegistry, tables: &'a Assets<StockTables>) -> Option<&'a StockTables> {
+    tables.get(&registry.get::<StockTables>(STOCK_TABLES)?)
+}
+
+fn restock_vendors(
+    time_of_day: Res<TimeOfDay>,
+    registry: Res<AssetRegistry>,
+    tables: Res<Assets<StockTables>>,
+    mut vendor_query: Query<&mut Vendor>,
+) {
+    let Some(tables) = stock_tables(&registry, &tables) else {
+        return;
+    };
+    for mut vendor in vendor_query.iter_mut() {
+        if vendor.restocked_day == Some(time_of_day.day) {
+            continue;
+        }
+        let table = tables.table(vendor.biome);
+        vendor.stock = table.entries.iter()
+            .filter(|entry| entry.buy_price.is_some())
+            .map(|entry| (entry.item.clone(), entry.stock))
+            .collect();
+        vendor.restocked_day = Some(time_of_day.day);
+    }
+}
+
+fn open_shops(
+    mut actions: EventReader<DialogueActionTaken>,
+    mut shop: ResMut<Shop>,
+    vendor_query: Query<(), With<Vendor>>,
+) {
+    for event in actions.read() {
+        if event.action == DialogueAction::OpenShop && vendor_query.contains(event.npc) {
+            shop.vendor = Some(event.npc);
+        }
+    }
+}
+
+fn close_distant_shop(
+    mut shop: ResMut<Shop>,
+    player_query: Query<&Transform, With<Player>>,
+    vendor_query: Query<&Transform, With<Vendor>>,
+) {
+    let Some(vendor) = shop.vendor else {
+        return;
+    };
+    let near = match (player_query.get_single(), vendor_query.get(vendor)) {
+        (Ok(player), Ok(vendor)) => player.translation.distance(vendor.translation) <= LEAVE_DISTANCE,
+        _ => false,
+    };
+    if !near {
+        shop.vendor = None;
+    }
+}
+
+enum ShopAction {
+    Buy(String, u32),
+    Sell(String, u32),
+    Close,
+}
+
+fn shop_ui(
+    mut contexts: EguiContexts,
+    mut shop: ResMut<Shop>,
+    mut inventory: ResMut<Inventory>,
+    registry: Res<AssetRegistry>,
+    items: Res<ItemRegistry>,
+    tables: Res<Assets<StockTables>>,
+    mut vendor_query: Query<(&Npc, &mut Vendor)>,
+) {
+    let Some(entity) = shop.vendor else {
+        return;
+    };
+    let Ok((npc, mut vendor)) = vendor_query.get_mut(entity) else {
+        shop.vendor = None;
+        return;
+    };
+    let Some(tables) = stock_tables(&registry, &tables) else {
+        return;
+    };
+    let table = tables.table(vendor.biome);
+    let coins = inventory.count(COIN);
+    let mut action = None;
+
+    egui::Window::new(format!("{}'s shop", npc.name))
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Coins: {}", coins));
+            ui.separator();
+            ui.heading("Buy");
+            egui::Grid::new("shop_buy").striped(true).show(ui, |ui| {
+                for entry in &table.entries {
+                    let Some(price) = entry.buy_price else {
+                        continue;
+                    };
+                    let left = vendor.stock.get(&entry.item).copied().unwrap_or(0);
+                    item_label(ui, &items, &entry.item, None);
+                    ui.label(format!("{} coins", price));
+                    ui.label(format!("{} left", left));
+                    if ui.add_enabled(left > 0 && coins >= price, egui::Button::new("Buy")).clicked() {
+                        action = Some(ShopAction::Buy(entry.item.clone(), price));
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.heading("Sell");
+            let mut carried: Vec<(&String, &u32)> = inventory.items.iter()
+                .filter(|(item, _)| table.entry(item).is_some_and(|entry| entry.sell_price.is_some()))
+                .collect();
+            carried.sort();
+            if carried.is_empty() {
+                ui.label("Nothing they would buy");
+            }
+            egui::Grid::new("shop_sell").striped(true).show(ui, |ui| {
+                for (item, count) in carried {
+                    let price = table.entry(item).and_then(|entry| entry.sell_price).unwrap_or(0);
+                    item_label(ui, &items, item, Some(*count));
+                    ui.label(format!("{} coins", price));
+                    if ui.button("Sell").clicked() {
+                        action = Some(ShopAction::Sell(item.clone(), price));
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            if ui.button("Close").clicked() {
+                action = Some(ShopAction::Close);
+            }
+        });
+
+    match action {
+        Some(ShopAction::Buy(item, price)) => {
+            if !inventory.take_all(&[(COIN, price)]) {
+                return;
+            }
+            if let Some(left) = vendor.stock.get_mut(&item) {
+                *left = left.saturating_sub(1);
+            }
+            *inventory.items.entry(item.clone()).or_insert(0) += 1;
+            info!("Bought {} for {} coins from {}", item, price, npc.name);
+        }
+        Some(ShopAction::Sell(item, price)) => {
+            if !inventory.take_all(&[(&item, 1)]) {
+                return;
+            }
+            *inventory.items.entry(COIN.to_string()).or_insert(0) += price;
+            info!("Sold {} for {} coins to {}", item, price, npc.name);
+        }
+        Some(ShopAction::Close) => shop.vendor = None,
+        None => {}
+    }
+}
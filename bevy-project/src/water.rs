@@ -1,26 +1,197 @@
+use std::collections::HashMap;
 use bevy::{
     prelude::*,
+    ecs::system::SystemParam,
     reflect::TypePath,
-    render::render_resource::{AsBindGroup, ShaderRef},
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
     pbr::{MaterialPlugin, Material},
 };
+use crate::asset_registry::AssetRegistryAppExt;
+use crate::biome::Biome;
+use crate::camera::FreeCamera;
+use crate::client::CHUNK_SIZE;
+use crate::pool::Pooled;
+use crate::reflection_probe::REFLECTION_MAP;
+use crate::terrain_edit::TerrainEdits;
+use crate::terrain_material::{TerrainMaterial, TerrainMaterialHandle};
+
+pub const WATER_LEVEL: f32 = 1.0; // Niveau de l'eau (remonté pour une meilleure visibilité)
 
 #[derive(Component)]
 pub struct Water;
 
+const WATER_SHADER_PATH: &str = "shaders/water.wgsl";
+// Sand this far above the highest waves still shows as wet
+const SHORE_BAND: f32 = 0.6;
+// Shoreline changes smaller than this don't re-upload the terrain material
+const SHORE_STEP: f32 = 0.01;
+// Must match the array size in shaders/water.wgsl
+pub const MAX_WAVES: usize = 4;
+// Ripples drawn at once, the oldest replaced first. Must match shaders/water.wgsl
+pub const MAX_RIPPLES: usize = 16;
+// Grid cells a side of the water plane at each LOD, fine near the camera where the waves
+// displace the vertices. Each divides the finer ones so edge vertices line up.
+const WATER_LOD_SUBDIVISIONS: [u32; 3] = [32, 16, 4];
+// Distance from the camera to a chunk's center where each coarser LOD starts
+const WATER_LOD_DISTANCES: [f32; 2] = [80.0, 140.0];
+// A chunk changes LOD only this far past a boundary, so one on the edge doesn't flicker
+const WATER_LOD_HYSTERESIS: f32 = 10.0;
+
+// Uniforms shared with the water shader. The `ShaderType` derive emits a `check` fn per
+// field next to each struct, unused as far as rustc can tell; an allow on a struct doesn't
+// reach them, the one on this module only covers the derived structs.
+#[allow(dead_code)]
+mod uniforms {
+    use super::*;
+
+    // A single Gerstner wave. Padded to 32 bytes to respect uniform array stride rules
+    #[derive(ShaderType, Debug, Clone, Copy, Default)]
+    pub struct GerstnerWave {
+        pub direction: Vec2,
+        pub amplitude: f32,
+        pub steepness: f32,
+        pub wavelength: f32,
+        pub speed: f32,
+        pub _padding: Vec2,
+    }
+
+    // Wave setup shared by the water materials and CPU-side queries
+    #[derive(Resource, ShaderType, Debug, Clone, Copy)]
+    pub struct WaterWaves {
+        pub waves: [GerstnerWave; MAX_WAVES],
+        pub wave_count: u32,
+    }
+
+    // Ring spreading from where something went through the surface, at the material's time
+    #[derive(ShaderType, Debug, Clone, Copy, Default, PartialEq)]
+    pub struct Ripple {
+        pub center: Vec2,
+        pub start_time: f32,
+        // 0 for an unused slot
+        pub strength: f32,
+    }
+
+    #[derive(Resource, ShaderType, Debug, Clone, Copy, Default)]
+    pub struct WaterRipples {
+        pub ripples: [Ripple; MAX_RIPPLES],
+    }
+
+    // Look of the water in a biome. The waves' displacement is the same everywhere so chunks of
+    // different biomes meet without cracks and gameplay queries stay right; the intensity only
+    // steepens or flattens the surface's normals.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct WaterTint {
+        pub deep_color: Vec3,
+        // Alpha looking straight down, reflections making it more opaque at grazing angles
+        pub opacity: f32,
+        pub shallow_color: Vec3,
+        pub wave_intensity: f32,
+    }
+}
+
+pub use uniforms::{GerstnerWave, WaterWaves, Ripple, WaterRipples, WaterTint};
+
+impl GerstnerWave {
+    pub fn new(direction: Vec2, amplitude: f32, steepness: f32, wavelength: f32, speed: f32) -> Self {
+        Self {
+            direction: direction.normalize_or_zero(),
+            amplitude,
+            steepness,
+            wavelength,
+            speed,
+            _padding: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for WaterWaves {
+    fn default() -> Self {
+        Self {
+            waves: [
+                GerstnerWave::new(Vec2::new(1.0, 0.3), 0.25, 0.5, 18.0, 2.0),
+                GerstnerWave::new(Vec2::new(-0.4, 1.0), 0.15, 0.4, 11.0, 1.6),
+                GerstnerWave::new(Vec2::new(0.7, -0.8), 0.08, 0.3, 6.0, 1.2),
+                GerstnerWave::new(Vec2::new(-1.0, -0.2), 0.04, 0.25, 3.5, 0.9),
+            ],
+            wave_count: MAX_WAVES as u32,
+        }
+    }
+}
+
+impl WaterWaves {
+    // Highest the summed waves can rise above the still surface
+    pub fn max_amplitude(&self) -> f32 {
+        let count = (self.wave_count as usize).min(MAX_WAVES);
+        self.waves[..count].iter().map(|wave| wave.amplitude).sum()
+    }
+
+    // Surface height offset at a world position, mirroring the vertex shader.
+    // The small horizontal Gerstner displacement is ignored.
+    pub fn height_at(&self, position: Vec2, time: f32) -> f32 {
+        let count = (self.wave_count as usize).min(MAX_WAVES);
+        self.waves[..count].iter()
+            .map(|wave| {
+                let k = std::f32::consts::TAU / wave.wavelength;
+                let f = k * wave.direction.dot(position) - wave.speed * k * time;
+                wave.amplitude * f.sin()
+            })
+            .sum()
+    }
+}
+
+impl WaterRipples {
+    // Takes the place of the oldest ripple, unused slots starting at time 0
+    pub fn push(&mut self, center: Vec2, start_time: f32, strength: f32) {
+        if let Some(oldest) = self.ripples.iter_mut().min_by(|a, b| a.start_time.total_cmp(&b.start_time)) {
+            *oldest = Ripple { center, start_time, strength };
+        }
+    }
+}
+
+impl WaterTint {
+    pub fn for_biome(biome: Biome) -> Self {
+        let (deep_color, shallow_color, opacity, wave_intensity) = match biome {
+            Biome::Temperate => (Vec3::new(0.02, 0.15, 0.3), Vec3::new(0.1, 0.45, 0.55), 0.75, 1.0),
+            // Clear turquoise
+            Biome::Desert | Biome::Savanna => (Vec3::new(0.0, 0.22, 0.32), Vec3::new(0.1, 0.65, 0.62), 0.6, 0.8),
+            // Cold gray and choppy
+            Biome::Tundra => (Vec3::new(0.06, 0.1, 0.14), Vec3::new(0.3, 0.38, 0.42), 0.85, 1.4),
+            // Murky swamp green and still
+            Biome::Rainforest => (Vec3::new(0.04, 0.1, 0.04), Vec3::new(0.2, 0.32, 0.12), 0.92, 0.45),
+        };
+        Self { deep_color, opacity, shallow_color, wave_intensity }
+    }
+}
+
+impl Default for WaterTint {
+    fn default() -> Self {
+        Self::for_biome(Biome::Temperate)
+    }
+}
+
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct WaterMaterial {
     #[uniform(0)]
     pub time: f32,
+    #[uniform(1)]
+    pub waves: WaterWaves,
+    // Sky and surroundings reflected by the surface
+    #[texture(2, dimension = "cube")]
+    #[sampler(3)]
+    pub reflection: Handle<Image>,
+    #[uniform(4)]
+    pub ripples: WaterRipples,
+    #[uniform(5)]
+    pub tint: WaterTint,
 }
 
 impl Material for WaterMaterial {
     fn fragment_shader() -> ShaderRef {
-        "shaders/water.wgsl".into()
+        WATER_SHADER_PATH.into()
     }
 
     fn vertex_shader() -> ShaderRef {
-        "shaders/water.wgsl".into()
+        WATER_SHADER_PATH.into()
     }
 
     fn alpha_mode(&self) -> AlphaMode {
@@ -32,26 +203,201 @@ impl Default for WaterMaterial {
     fn default() -> Self {
         Self {
             time: 0.0,
+            waves: WaterWaves::default(),
+            reflection: REFLECTION_MAP,
+            ripples: WaterRipples::default(),
+            tint: WaterTint::default(),
         }
     }
 }
 
+// One material per biome, given to a water chunk when it spawns. Chunks of a biome share it,
+// so there are few materials to animate and chunks using the same bind group draw one after
+// another without switching it.
+#[derive(Resource)]
+pub struct WaterMaterials(HashMap<Biome, Handle<WaterMaterial>>);
+
+impl FromWorld for WaterMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<WaterMaterial>>();
+        Self(Biome::ALL
+            .iter()
+            .map(|biome| (*biome, materials.add(WaterMaterial { tint: WaterTint::for_biome(*biome), ..default() })))
+            .collect())
+    }
+}
+
+impl WaterMaterials {
+    pub fn get(&self, biome: Biome) -> Handle<WaterMaterial> {
+        self.0[&biome].clone()
+    }
+}
+
+// One plane mesh per water LOD, shared by every water chunk since they are all the same size
+#[derive(Resource)]
+pub struct WaterMeshes(Vec<Handle<Mesh>>);
+
+impl FromWorld for WaterMeshes {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        Self(WATER_LOD_SUBDIVISIONS
+            .iter()
+            .map(|subdivisions| meshes.add(Plane3d::default().mesh().size(CHUNK_SIZE, CHUNK_SIZE).subdivisions(*subdivisions)))
+            .collect())
+    }
+}
+
+impl WaterMeshes {
+    pub fn get(&self, lod: u32) -> Handle<Mesh> {
+        self.0[(lod as usize).min(self.0.len() - 1)].clone()
+    }
+}
+
+// LOD of a water chunk's mesh, 0 being the finest
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WaterLod(pub u32);
+
+// LOD for a water chunk this far from the camera, kept at `current` until the distance is
+// clearly past a boundary
+pub fn water_lod(distance: f32, current: Option<u32>) -> u32 {
+    let lod_at = |distance: f32| WATER_LOD_DISTANCES.iter().filter(|boundary| distance > **boundary).count() as u32;
+    let Some(current) = current else {
+        return lod_at(distance);
+    };
+    let coarser = lod_at(distance - WATER_LOD_HYSTERESIS);
+    let finer = lod_at(distance + WATER_LOD_HYSTERESIS);
+    if coarser > current {
+        coarser
+    } else if finer < current {
+        finer
+    } else {
+        current
+    }
+}
+
 pub struct WaterPlugin;
 
 impl Plugin for WaterPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(MaterialPlugin::<WaterMaterial>::default())
-           .add_systems(Update, update_water_time);
+           .preload_asset::<Shader>("water_shader", WATER_SHADER_PATH)
+           .init_resource::<WaterWaves>()
+           .init_resource::<WaterRipples>()
+           .init_resource::<WaterMaterials>()
+           .init_resource::<WaterMeshes>()
+           .add_systems(Update, (update_water_time, update_water_lods, apply_shoreline_to_terrain));
+    }
+}
+
+// Height of the still water surface; the single place to change for lakes or basins at other levels
+pub fn sea_level(_world_x: f32, _world_z: f32) -> f32 {
+    WATER_LEVEL
+}
+
+// Answers water questions for gameplay code (AI, buoyancy, audio, rendering),
+// taking terrain edits and the current waves into account
+#[derive(SystemParam)]
+pub struct WaterQuery<'w> {
+    time: Res<'w, Time>,
+    waves: Res<'w, WaterWaves>,
+    terrain_edits: Res<'w, TerrainEdits>,
+}
+
+impl WaterQuery<'_> {
+    pub fn floor_height(&self, world_x: f32, world_z: f32) -> f32 {
+        self.terrain_edits.height(world_x, world_z)
+    }
+
+    // Still water depth, 0 on land
+    pub fn depth(&self, world_x: f32, world_z: f32) -> f32 {
+        (sea_level(world_x, world_z) - self.floor_height(world_x, world_z)).max(0.0)
+    }
+
+    pub fn has_water(&self, world_x: f32, world_z: f32) -> bool {
+        self.depth(world_x, world_z) > 0.0
+    }
+
+    // Wavy surface height at a point, whether or not there is water there
+    pub fn wave_surface_height(&self, position: Vec2) -> f32 {
+        sea_level(position.x, position.y) + self.waves.height_at(position, self.time.elapsed_secs())
+    }
+
+    // Surface height, None where the terrain is above the water
+    pub fn surface_height(&self, position: Vec2) -> Option<f32> {
+        self.has_water(position.x, position.y).then(|| self.wave_surface_height(position))
+    }
+
+    // Between the floor and the wavy surface
+    pub fn is_in_water(&self, position: Vec3) -> bool {
+        self.surface_height(position.xz())
+            .is_some_and(|surface| position.y < surface && position.y > self.floor_height(position.x, position.z))
+    }
+}
+
+// Swap water chunk meshes for finer ones as the camera comes near and coarser ones as it leaves
+#[allow(clippy::type_complexity)]
+fn update_water_lods(
+    meshes: Res<WaterMeshes>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    mut water_query: Query<(&Transform, &mut WaterLod, &mut Mesh3d), (With<Water>, Without<FreeCamera>, Without<Pooled>)>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    for (transform, mut lod, mut mesh) in water_query.iter_mut() {
+        let distance = transform.translation.xz().distance(camera.translation.xz());
+        let wanted = water_lod(distance, Some(lod.0));
+        if wanted != lod.0 {
+            lod.0 = wanted;
+            mesh.0 = meshes.get(wanted);
+        }
     }
 }
 
 fn update_water_time(
     time: Res<Time>,
+    waves: Res<WaterWaves>,
+    ripples: Res<WaterRipples>,
+    handles: Res<WaterMaterials>,
     mut water_materials: ResMut<Assets<WaterMaterial>>,
 ) {
-    let current_time = time.elapsed_secs();
-    
-    for (_handle, material) in water_materials.iter_mut() {
-        material.time = current_time;
+    let _span = info_span!("update_water_time").entered();
+    for handle in handles.0.values() {
+        let Some(material) = water_materials.get_mut(handle) else {
+            continue;
+        };
+        material.time = time.elapsed_secs();
+        if waves.is_changed() {
+            material.waves = *waves;
+        }
+        if ripples.is_changed() {
+            material.ripples = *ripples;
+        }
     }
-} 
\ No newline at end of file
+}
+
+// The terrain shader draws wet sand up to where the waves reach, from the water level
+// around the camera
+fn apply_shoreline_to_terrain(
+    waves: Res<WaterWaves>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    handle: Res<TerrainMaterialHandle>,
+    mut materials: ResMut<Assets<TerrainMaterial>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let water_level = sea_level(camera.translation.x, camera.translation.z);
+    let shore_band = waves.max_amplitude() + SHORE_BAND;
+    let Some(material) = materials.get(&handle.0) else {
+        return;
+    };
+    let params = &material.extension.params;
+    if (params.water_level - water_level).abs() < SHORE_STEP && (params.shore_band - shore_band).abs() < SHORE_STEP {
+        return;
+    }
+    if let Some(material) = materials.get_mut(&handle.0) {
+        material.extension.params.water_level = water_level;
+        material.extension.params.shore_band = shore_band;
+    }
+}
@@ -0,0 +1,453 @@
+use std::collections::HashSet;
+use bevy::prelude::*;
+use crate::biome::{self, Biome};
+use crate::camera::FreeCamera;
+use crate::client::{ChunkManager, CHUNK_SIZE};
+use crate::creative::CreativeMode;
+use crate::danger;
+use crate::day_night::TimeOfDay;
+use crate::player::{Health, Player, RespawnPlayer, Vital};
+use crate::population::{Creature, Population, PopulationLimits};
+use crate::projectile::{HitTarget, Hittable, ProjectileHit, ProjectileKind};
+use crate::quest::ItemCollected;
+use crate::region::ProtectedRegions;
+use crate::streaming::ChunkCoords;
+use crate::terrain_edit::TerrainEdits;
+use crate::toast::{Toast, ToastKind};
+use crate::water::WaterQuery;
+use crate::world_rng::{RngPurpose, WorldRng};
+
+// Packs roam these biomes at night, one chunk in PACK_CHANCE having one
+const WOLF_BIOMES: [Biome; 2] = [Biome::Temperate, Biome::Tundra];
+const PACK_CHANCE: f32 = 0.3;
+const MAX_PACK_SIZE: u32 = 3;
+// Packs appear at least this far from the player, on dry land
+const MIN_SPAWN_DISTANCE: f32 = 35.0;
+const SPAWN_ATTEMPTS: u32 = 8;
+
+const WOLF_HEALTH: f32 = 60.0;
+const WOLF_HALF_HEIGHT: f32 = 0.4;
+const WOLF_RADIUS: f32 = 0.6;
+// The player is noticed this close, more in dangerous regions, and let go past GIVE_UP_RADIUS
+const DETECTION_RADIUS: f32 = 14.0;
+const GIVE_UP_RADIUS: f32 = 35.0;
+const PROWL_SPEED: f32 = 1.5;
+// A little slower than a sprinting player, so running away works
+const CHASE_SPEED: f32 = 8.0;
+const RETREAT_SPEED: f32 = 9.0;
+// Prowling wolves wander this far around where their pack spawned
+const PROWL_RADIUS: f32 = 12.0;
+const ATTACK_RANGE: f32 = 1.6;
+const ATTACK_DAMAGE: f32 = 12.0;
+const ATTACK_COOLDOWN: f32 = 1.5;
+// Health lost to a rock, and share of health left when a wolf gives up and runs
+const ROCK_DAMAGE: f32 = 20.0;
+const RETREAT_HEALTH: f32 = 0.3;
+// Retreating wolves are gone once this far from the player
+const ESCAPE_DISTANCE: f32 = 60.0;
+
+// Item, least and most dropped, and the chance of dropping any
+const LOOT_TABLE: [(&str, u32, u32, f32); 3] = [
+    ("hide", 1, 2, 1.0),
+    ("raw_meat", 1, 2, 0.8),
+    ("fang", 1, 1, 0.25),
+];
+const LOOT_PICKUP_RADIUS: f32 = 1.5;
+const LOOT_LIFETIME: f32 = 120.0;
+
+// Hostile wolves: packs spawn at night in some biomes, prowl until the player comes close,
+// chase and bite them, run off when badly hurt or at sunrise, and drop loot when killed by
+// thrown rocks
+#[derive(Default, Clone, Debug)]
+pub struct WolfPlugin;
+
+impl Plugin for WolfPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WolfPacks>()
+            .add_systems(Startup, setup_wolf_assets)
+            .add_systems(Update, (
+                spawn_wolf_packs,
+                hurt_hit_wolves,
+                update_wolves,
+                collect_loot,
+            ).chain());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WolfState {
+    // Wandering toward a point near the pack's spawn
+    Prowl { target: Vec2 },
+    Chase,
+    Retreat,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Wolf {
+    pub health: Vital,
+    pub state: WolfState,
+    // Numbers the wolf's streams
+    index: u32,
+    home: Vec2,
+    detection_radius: f32,
+    // Seconds until the next bite
+    cooldown: f32,
+    rng: WorldRng,
+}
+
+impl Wolf {
+    fn hurt(&mut self, damage: f32) {
+        self.health.change(-damage);
+        self.state = if self.health.fraction() <= RETREAT_HEALTH { WolfState::Retreat } else { WolfState::Chase };
+    }
+
+    fn next_prowl_target(&mut self) -> WolfState {
+        let offset = Vec2::new(self.rng.unit(), self.rng.unit()) * 2.0 - 1.0;
+        WolfState::Prowl { target: self.home + offset * PROWL_RADIUS }
+    }
+}
+
+// Items left by a dead wolf, picked up by walking over them
+#[derive(Component, Debug, Clone)]
+pub struct LootDrop {
+    pub items: Vec<(String, u32)>,
+    age: f32,
+}
+
+// Chunks that already rolled for a pack tonight
+#[derive(Resource, Default)]
+struct WolfPacks {
+    night: Option<u32>,
+    rolled: HashSet<ChunkCoords>,
+    next_wolf: u32,
+}
+
+#[derive(Resource)]
+struct WolfAssets {
+    body: Handle<Mesh>,
+    head: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    loot_mesh: Handle<Mesh>,
+    loot_material: Handle<StandardMaterial>,
+}
+
+fn setup_wolf_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(WolfAssets {
+        body: meshes.add(Cuboid::new(0.45, 0.5, 1.1)),
+        head: meshes.add(Cuboid::new(0.3, 0.3, 0.4)),
+        material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.35, 0.34, 0.33),
+            perceptual_roughness: 0.9,
+            ..default()
+        }),
+        loot_mesh: meshes.add(Cuboid::new(0.35, 0.25, 0.35)),
+        loot_material: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.55, 0.4, 0.25),
+            perceptual_roughness: 0.8,
+            ..default()
+        }),
+    });
+}
+
+// Nights are counted from the day they start on, so the one running past midnight keeps its number
+fn night_number(time_of_day: &TimeOfDay) -> Option<u32> {
+    if !time_of_day.is_night() {
+        return None;
+    }
+    Some(if time_of_day.hour < time_of_day.sunrise() { time_of_day.day.saturating_sub(1) } else { time_of_day.day })
+}
+
+// Items a dead wolf leaves, each rolled for its chance then its count
+pub fn roll_loot(rng: &mut WorldRng) -> Vec<(String, u32)> {
+    LOOT_TABLE.iter()
+        .filter_map(|&(item, min, max, chance)| {
+            let count = min + rng.index((max - min + 1) as usize) as u32;
+            rng.chance(chance).then(|| (item.to_string(), count))
+        })
+        .collect()
+}
+
+fn ground_position(terrain_edits: &TerrainEdits, point: Vec2) -> Vec3 {
+    Vec3::new(point.x, terrain_edits.height(point.x, point.y) + WOLF_HALF_HEIGHT, point.y)
+}
+
+// Each night every loaded chunk of a wolf biome within the simulation range rolls once for
+// a pack, seeded by the world, chunk and night so it is the same on every machine
+#[allow(clippy::too_many_arguments)]
+fn spawn_wolf_packs(
+    mut commands: Commands,
+    mut packs: ResMut<WolfPacks>,
+    mut population: ResMut<Population>,
+    limits: Res<PopulationLimits>,
+    chunk_manager: Res<ChunkManager>,
+    terrain_edits: Res<TerrainEdits>,
+    water: WaterQuery,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    assets: Option<Res<WolfAssets>>,
+    camera_query: Query<&Transform, With<FreeCamera>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Some(night) = night_number(&time_of_day) else {
+        packs.night = None;
+        return;
+    };
+    if packs.night != Some(night) {
+        packs.night = Some(night);
+        packs.rolled.clear();
+    }
+    let (Some(assets), Ok(camera), Ok(player)) = (assets, camera_query.get_single(), player_query.get_single()) else {
+        return;
+    };
+    let camera = camera.translation.xz();
+    let player = player.translation.xz();
+
+    let now = time.elapsed_secs();
+    for &chunk in chunk_manager.loaded_chunks.keys() {
+        let center = Vec2::new(chunk.0 as f32, chunk.1 as f32) * CHUNK_SIZE;
+        if packs.rolled.contains(&chunk) || center.distance(camera) > limits.simulation_range - CHUNK_SIZE {
+            continue;
+        }
+        let biome = biome::biome_at(center.x, center.y);
+        if !WOLF_BIOMES.contains(&biome) {
+            packs.rolled.insert(chunk);
+            continue;
+        }
+        // A chunk skipped for lack of room tries again once creatures despawn
+        let room = population.room(biome, &limits);
+        if room == 0 {
+            continue;
+        }
+        packs.rolled.insert(chunk);
+
+        let mut rng = WorldRng::for_chunk(chunk, RngPurpose::Predators).substream(night);
+        if !rng.chance(PACK_CHANCE) {
+            continue;
+        }
+        let spot = (0..SPAWN_ATTEMPTS).find_map(|_| {
+            let point = center + (Vec2::new(rng.unit(), rng.unit()) - 0.5) * CHUNK_SIZE;
+            (!water.has_water(point.x, point.y) && point.distance(player) >= MIN_SPAWN_DISTANCE).then_some(point)
+        });
+        let Some(spot) = spot else {
+            continue;
+        };
+
+        // Dangerous regions have bigger packs, noticing the player from farther away
+        let danger = danger::danger_level(spot.x, spot.y);
+        let size = (1 + rng.index(MAX_PACK_SIZE as usize) as u32).min(room);
+        let detection_radius = DETECTION_RADIUS * danger::wildlife_aggressiveness(danger);
+        for _ in 0..size {
+            let index = packs.next_wolf;
+            packs.next_wolf = packs.next_wolf.wrapping_add(1);
+            let mut wolf = Wolf {
+                health: Vital::full(WOLF_HEALTH),
+                state: WolfState::Chase,
+                index,
+                home: spot,
+                detection_radius,
+                cooldown: 0.0,
+                rng: rng.substream(index),
+            };
+            wolf.state = wolf.next_prowl_target();
+            let offset = Vec2::new(rng.unit(), rng.unit()) * 4.0 - 2.0;
+            commands.spawn((
+                Mesh3d(assets.body.clone()),
+                MeshMaterial3d(assets.material.clone()),
+                Transform::from_translation(ground_position(&terrain_edits, spot + offset)),
+                wolf,
+                Hittable { radius: WOLF_RADIUS },
+                Creature::new(biome, now),
+            )).with_child((
+                Mesh3d(assets.head.clone()),
+                MeshMaterial3d(assets.material.clone()),
+                Transform::from_xyz(0.0, 0.2, -0.65),
+            ));
+        }
+        population.add(biome, size);
+        debug!("Wolf pack of {} in chunk {:?}", size, chunk);
+    }
+}
+
+// Rocks hurt wolves, which turn on the thrower or run once badly hurt, and die leaving loot
+fn hurt_hit_wolves(
+    mut commands: Commands,
+    mut hits: EventReader<ProjectileHit>,
+    assets: Option<Res<WolfAssets>>,
+    mut toasts: EventWriter<Toast>,
+    mut wolf_query: Query<(&mut Wolf, &Transform)>,
+) {
+    for hit in hits.read() {
+        let (ProjectileKind::Rock, HitTarget::Entity(entity)) = (hit.kind, hit.target) else {
+            continue;
+        };
+        let Ok((mut wolf, transform)) = wolf_query.get_mut(entity) else {
+            continue;
+        };
+        wolf.hurt(ROCK_DAMAGE);
+        if wolf.health.current > 0.0 {
+            continue;
+        }
+        commands.entity(entity).despawn_recursive();
+        let chunk = ((transform.translation.x / CHUNK_SIZE).round() as i32, (transform.translation.z / CHUNK_SIZE).round() as i32);
+        let items = roll_loot(&mut WorldRng::for_chunk(chunk, RngPurpose::Loot).substream(wolf.index));
+        info!("Killed a wolf");
+        toasts.send(Toast::new(ToastKind::Combat, "You killed a wolf"));
+        if let Some(assets) = &assets
+            && !items.is_empty()
+        {
+            commands.spawn((
+                Mesh3d(assets.loot_mesh.clone()),
+                MeshMaterial3d(assets.loot_material.clone()),
+                Transform::from_translation(transform.translation - Vec3::Y * (WOLF_HALF_HEIGHT - 0.125)),
+                LootDrop { items, age: 0.0 },
+            ));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_wolves(
+    mut commands: Commands,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    terrain_edits: Res<TerrainEdits>,
+    water: WaterQuery,
+    creative: Res<CreativeMode>,
+    regions: Res<ProtectedRegions>,
+    mut respawn_events: EventWriter<RespawnPlayer>,
+    mut toasts: EventWriter<Toast>,
+    mut player_query: Query<(&Transform, &mut Health), With<Player>>,
+    mut wolf_query: Query<(Entity, &mut Wolf, &mut Transform), Without<Player>>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    let Ok((player_transform, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    let player = player_transform.translation;
+    let day = !time_of_day.is_night();
+
+    for (entity, mut wolf, mut transform) in wolf_query.iter_mut() {
+        let position = transform.translation.xz();
+        let distance = position.distance(player.xz());
+        wolf.cooldown = (wolf.cooldown - dt).max(0.0);
+
+        // Sunrise sends every pack away; players in creative mode or safe regions are left alone
+        let safe = creative.enabled || regions.no_damage_at(player);
+        if day && wolf.state != WolfState::Retreat {
+            wolf.state = WolfState::Retreat;
+        }
+        match wolf.state {
+            WolfState::Prowl { .. } if !safe && distance <= wolf.detection_radius => wolf.state = WolfState::Chase,
+            WolfState::Chase if safe || distance > GIVE_UP_RADIUS => wolf.state = wolf.next_prowl_target(),
+            WolfState::Retreat if distance > ESCAPE_DISTANCE => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+            _ => {}
+        }
+
+        let (goal, speed) = match wolf.state {
+            WolfState::Prowl { target } => (target, PROWL_SPEED),
+            WolfState::Chase => (player.xz(), CHASE_SPEED),
+            WolfState::Retreat => (position + (position - player.xz()).normalize_or(Vec2::X), RETREAT_SPEED),
+        };
+
+        if wolf.state == WolfState::Chase && distance <= ATTACK_RANGE {
+            if wolf.cooldown <= 0.0 {
+                wolf.cooldown = ATTACK_COOLDOWN;
+                health.change(-ATTACK_DAMAGE);
+                debug!("Bitten by a wolf, {:.0} health left", health.current);
+                if health.current <= 0.0 {
+                    info!("Player killed by a wolf");
+                    toasts.send(Toast::new(ToastKind::Combat, "You were killed by a wolf"));
+                    respawn_events.send(RespawnPlayer);
+                }
+            }
+            transform.look_to(Vec3::new(player.x - position.x, 0.0, player.z - position.y), Vec3::Y);
+            continue;
+        }
+
+        let to_goal = goal - position;
+        if let WolfState::Prowl { .. } = wolf.state
+            && to_goal.length() < 1.0
+        {
+            wolf.state = wolf.next_prowl_target();
+            continue;
+        }
+        let direction = to_goal.normalize_or_zero();
+        let next = position + direction * (speed * dt).min(to_goal.length());
+        // Wolves don't swim: they stop at the shore and prowl somewhere else
+        if water.has_water(next.x, next.y) {
+            if let WolfState::Prowl { .. } = wolf.state {
+                wolf.state = wolf.next_prowl_target();
+            }
+            continue;
+        }
+        transform.translation = ground_position(&terrain_edits, next);
+        if direction != Vec2::ZERO {
+            transform.look_to(Vec3::new(direction.x, 0.0, direction.y), Vec3::Y);
+        }
+    }
+}
+
+fn collect_loot(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut collected: EventWriter<ItemCollected>,
+    player_query: Query<&Transform, With<Player>>,
+    mut loot_query: Query<(Entity, &mut LootDrop, &Transform), Without<Player>>,
+) {
+    let player = player_query.get_single().ok().map(|transform| transform.translation);
+    for (entity, mut loot, transform) in loot_query.iter_mut() {
+        loot.age += time.delta_secs();
+        let picked_up = player.is_some_and(|player| player.distance(transform.translation) <= LOOT_PICKUP_RADIUS + WOLF_HALF_HEIGHT);
+        if picked_up {
+            for (item, count) in loot.items.drain(..) {
+                info!("Picked up {} {}", count, item);
+                collected.send(ItemCollected { item, count });
+            }
+        }
+        if picked_up || loot.age > LOOT_LIFETIME {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loot_is_deterministic_and_within_the_table() {
+        let rng = WorldRng::new(9, (2, 5), RngPurpose::Loot);
+        assert_eq!(roll_loot(&mut rng.clone()), roll_loot(&mut rng.clone()));
+        let mut rng = rng.substream(1);
+        for _ in 0..200 {
+            let loot = roll_loot(&mut rng);
+            // Hides always drop
+            assert!(loot.iter().any(|(item, _)| item == "hide"));
+            for (item, count) in loot {
+                let &(_, min, max, _) = LOOT_TABLE.iter().find(|entry| entry.0 == item).unwrap();
+                assert!((min..=max).contains(&count), "{} {}", item, count);
+            }
+        }
+    }
+
+    #[test]
+    fn nights_keep_their_number_past_midnight() {
+        let mut time_of_day = TimeOfDay { day: 4, hour: 12.0, ..default() };
+        assert_eq!(night_number(&time_of_day), None);
+        time_of_day.hour = 22.0;
+        assert_eq!(night_number(&time_of_day), Some(4));
+        time_of_day.advance_hours(4.0);
+        assert_eq!(night_number(&time_of_day), Some(4));
+    }
+}
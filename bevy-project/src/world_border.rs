@@ -0,0 +1,248 @@
+use bevy::{
+    image::{ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    math::Affine2,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use serde::{Deserialize, Serialize};
+use crate::client::CHUNK_SIZE;
+use crate::net::{ServerEvent, ServerMessage};
+use crate::player::{Player, PLAYER_RADIUS};
+use crate::projectile::Projectile;
+use crate::streaming::ChunkCoords;
+
+// The wall reaches from below the sea floor to well above the mountains
+const WALL_BOTTOM: f32 = -40.0;
+const WALL_HEIGHT: f32 = 160.0;
+const WALL_COLOR: Color = Color::srgba(0.45, 0.75, 1.0, 0.35);
+// Diagonal stripes this many meters apart, drifting up the wall
+const STRIPE_SPACING: f32 = 6.0;
+const STRIPE_SPEED: f32 = 0.15;
+const STRIPE_TEXTURE_SIZE: u32 = 64;
+// The wall's opacity breathes between these shares of WALL_COLOR's alpha
+const PULSE: (f32, f32) = (0.7, 1.0);
+const PULSES_PER_SECOND: f32 = 0.4;
+// Projectiles hitting the wall bounce back with this share of their speed
+const WALL_BOUNCE: f32 = 0.4;
+
+// Optional square world border for bounded arenas: chunks beyond it are never generated, a
+// translucent striped wall stands at its edge, and the player and projectiles are pushed
+// back inside. The server decides it and sends it to clients.
+#[derive(Default, Clone, Debug)]
+pub struct WorldBorderPlugin;
+
+impl Plugin for WorldBorderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldBorder>()
+            .add_systems(Startup, setup_border_wall)
+            .add_systems(Update, (
+                take_server_border,
+                spawn_border_wall,
+                animate_border_wall,
+                keep_inside_border,
+            ).chain());
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorldBorder {
+    // Chunks kept on each side of the center chunk, None for an endless world
+    pub radius_chunks: Option<i32>,
+}
+
+impl WorldBorder {
+    pub fn contains_chunk(&self, chunk: ChunkCoords) -> bool {
+        self.radius_chunks.is_none_or(|radius| chunk.0.abs() <= radius && chunk.1.abs() <= radius)
+    }
+
+    // Distance from the world's center to the wall along x and z, chunk meshes being
+    // centered on their chunk offset
+    pub fn half_extent(&self) -> Option<f32> {
+        self.radius_chunks.map(|radius| (radius as f32 + 0.5) * CHUNK_SIZE)
+    }
+
+    // The position moved back at least `margin` inside the wall, None if it already is
+    pub fn push_inside(&self, position: Vec3, margin: f32) -> Option<Vec3> {
+        let limit = (self.half_extent()? - margin).max(0.0);
+        let inside = Vec3::new(position.x.clamp(-limit, limit), position.y, position.z.clamp(-limit, limit));
+        (inside != position).then_some(inside)
+    }
+}
+
+#[derive(Resource)]
+struct BorderWallAssets {
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Component)]
+struct BorderWall;
+
+// Transparent texture with a bright diagonal band, tiling into stripes
+fn stripe_texture() -> Image {
+    let size = STRIPE_TEXTURE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let diagonal = ((x + y) % size) as f32 / size as f32;
+            let band = (1.0 - (diagonal - 0.5).abs() * 4.0).clamp(0.0, 1.0);
+            let alpha = (0.35 + 0.65 * band) * 255.0;
+            data.extend_from_slice(&[255, 255, 255, alpha as u8]);
+        }
+    }
+    let mut image = Image::new(
+        Extent3d { width: size, height: size, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        address_mode_u: ImageAddressMode::Repeat,
+        address_mode_v: ImageAddressMode::Repeat,
+        ..ImageSamplerDescriptor::linear()
+    });
+    image
+}
+
+fn setup_border_wall(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = materials.add(StandardMaterial {
+        base_color: WALL_COLOR,
+        base_color_texture: Some(images.add(stripe_texture())),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        double_sided: true,
+        cull_mode: None,
+        ..default()
+    });
+    commands.insert_resource(BorderWallAssets { material });
+}
+
+fn take_server_border(
+    mut server_events: EventReader<ServerEvent>,
+    mut border: ResMut<WorldBorder>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::WorldBorder { border: received } = message {
+            border.set_if_neq(*received);
+        }
+    }
+}
+
+// Put the four walls up again whenever the border changes
+fn spawn_border_wall(
+    mut commands: Commands,
+    border: Res<WorldBorder>,
+    assets: Option<Res<BorderWallAssets>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    wall_query: Query<Entity, With<BorderWall>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    if !border.is_changed() {
+        return;
+    }
+    for entity in wall_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    let Some(extent) = border.half_extent() else {
+        return;
+    };
+
+    let length = extent * 2.0;
+    if let Some(material) = materials.get_mut(&assets.material) {
+        material.uv_transform = Affine2::from_scale(Vec2::new(length, WALL_HEIGHT) / STRIPE_SPACING);
+    }
+    let mesh = meshes.add(Rectangle::new(length, WALL_HEIGHT));
+    let center_y = WALL_BOTTOM + WALL_HEIGHT / 2.0;
+    for side in 0..4 {
+        let facing = Quat::from_rotation_y(side as f32 * std::f32::consts::FRAC_PI_2);
+        commands.spawn((
+            BorderWall,
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(assets.material.clone()),
+            Transform::from_translation(facing * Vec3::new(0.0, 0.0, -extent) + Vec3::Y * center_y).with_rotation(facing),
+        ));
+    }
+    info!("World border {} m from the center", extent);
+}
+
+fn animate_border_wall(
+    time: Res<Time>,
+    border: Res<WorldBorder>,
+    assets: Option<Res<BorderWallAssets>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    if border.radius_chunks.is_none() {
+        return;
+    }
+    let Some(material) = materials.get_mut(&assets.material) else {
+        return;
+    };
+    let elapsed = time.elapsed_secs();
+    material.uv_transform.translation = Vec2::new(0.0, -(elapsed * STRIPE_SPEED).fract());
+    let pulse = 0.5 + 0.5 * (elapsed * PULSES_PER_SECOND * std::f32::consts::TAU).sin();
+    material.base_color = WALL_COLOR.with_alpha(WALL_COLOR.alpha() * PULSE.0.lerp(PULSE.1, pulse));
+}
+
+fn keep_inside_border(
+    border: Res<WorldBorder>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+    mut projectile_query: Query<(&mut Transform, &mut Projectile), Without<Player>>,
+) {
+    if border.radius_chunks.is_none() {
+        return;
+    }
+    for mut transform in player_query.iter_mut() {
+        if let Some(inside) = border.push_inside(transform.translation, PLAYER_RADIUS) {
+            transform.translation = inside;
+        }
+    }
+    for (mut transform, mut projectile) in projectile_query.iter_mut() {
+        let Some(inside) = border.push_inside(transform.translation, 0.0) else {
+            continue;
+        };
+        if inside.x != transform.translation.x {
+            projectile.velocity.x *= -WALL_BOUNCE;
+        }
+        if inside.z != transform.translation.z {
+            projectile.velocity.z *= -WALL_BOUNCE;
+        }
+        transform.translation = inside;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endless_world_has_no_border() {
+        let border = WorldBorder::default();
+        assert!(border.contains_chunk((10_000, -10_000)));
+        assert_eq!(border.push_inside(Vec3::splat(1e6), 1.0), None);
+    }
+
+    #[test]
+    fn positions_past_the_wall_are_pushed_back() {
+        let border = WorldBorder { radius_chunks: Some(2) };
+        assert!(border.contains_chunk((2, -2)));
+        assert!(!border.contains_chunk((3, 0)));
+        let extent = border.half_extent().unwrap();
+        assert_eq!(border.push_inside(Vec3::new(0.0, 5.0, 0.0), 1.0), None);
+        let pushed = border.push_inside(Vec3::new(extent + 10.0, 5.0, -extent - 3.0), 1.0);
+        assert_eq!(pushed, Some(Vec3::new(extent - 1.0, 5.0, -extent + 1.0)));
+    }
+}
@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use crate::day_night::TimeOfDay;
+
+// Share of an event's duration spent fading in and out, capped in hours
+const FADE_SHARE: f32 = 0.2;
+const MAX_FADE_HOURS: f32 = 0.5;
+
+// Starts rare timed events (auroras, meteor showers, fog banks...) at given times of day.
+// Each event type lives in its own plugin, registered with `register_world_event`,
+// and reads its intensity from `ActiveWorldEvents` to drive its visuals.
+#[derive(Default, Clone, Debug)]
+pub struct WorldEventsPlugin;
+
+impl Plugin for WorldEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldEventRegistry>()
+            .init_resource::<ActiveWorldEvents>()
+            .add_event::<WorldEventStarted>()
+            .add_event::<WorldEventEnded>()
+            .add_systems(Update, schedule_world_events);
+    }
+}
+
+// An event type the scheduler can start
+#[derive(Debug, Clone)]
+pub struct WorldEventType {
+    pub name: &'static str,
+    // Hours of the day when it may start, the window can wrap past midnight
+    pub start_window: (f32, f32),
+    // Chance to start at each in-game hour inside the window
+    pub chance: f32,
+    pub duration_hours: f32,
+}
+
+impl WorldEventType {
+    fn can_start_at(&self, hour: f32) -> bool {
+        let (from, to) = self.start_window;
+        if from <= to {
+            hour >= from && hour < to
+        } else {
+            hour >= from || hour < to
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct WorldEventRegistry {
+    types: Vec<WorldEventType>,
+    // In-game hour the chances were last rolled for
+    last_roll: Option<u64>,
+}
+
+pub trait WorldEventAppExt {
+    fn register_world_event(&mut self, event: WorldEventType) -> &mut Self;
+}
+
+impl WorldEventAppExt for App {
+    fn register_world_event(&mut self, event: WorldEventType) -> &mut Self {
+        self.world_mut().get_resource_or_insert_with(WorldEventRegistry::default).types.push(event);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveWorldEvent {
+    pub name: &'static str,
+    // Hours since the start of day 0
+    started: f32,
+    duration: f32,
+    // 0..1, eased in at the start and out at the end
+    pub intensity: f32,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct ActiveWorldEvents {
+    pub events: Vec<ActiveWorldEvent>,
+}
+
+impl ActiveWorldEvents {
+    pub fn intensity(&self, name: &str) -> f32 {
+        self.events.iter()
+            .find(|event| event.name == name)
+            .map_or(0.0, |event| event.intensity)
+    }
+
+    pub fn is_active(&self, name: &str) -> bool {
+        self.events.iter().any(|event| event.name == name)
+    }
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WorldEventStarted {
+    pub name: &'static str,
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct WorldEventEnded {
+    pub name: &'static str,
+}
+
+// Deterministic 0..1 value, the same world hour always rolls the same events
+pub fn unit_random(seed: u64, salt: u32) -> f32 {
+    let mut value = (seed as u32).wrapping_mul(0x9E37_79B1)
+        ^ ((seed >> 32) as u32).wrapping_mul(0x85EB_CA77)
+        ^ salt.wrapping_mul(0xC2B2_AE3D);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x2C1B_3C6D);
+    value ^= value >> 12;
+    value as f32 / u32::MAX as f32
+}
+
+fn absolute_hours(time_of_day: &TimeOfDay) -> f32 {
+    time_of_day.day as f32 * 24.0 + time_of_day.hour
+}
+
+fn schedule_world_events(
+    time_of_day: Res<TimeOfDay>,
+    mut registry: ResMut<WorldEventRegistry>,
+    mut active: ResMut<ActiveWorldEvents>,
+    mut started: EventWriter<WorldEventStarted>,
+    mut ended: EventWriter<WorldEventEnded>,
+) {
+    let now = absolute_hours(&time_of_day);
+
+    active.events.retain(|event| {
+        let running = now < event.started + event.duration;
+        if !running {
+            info!("World event {} ended", event.name);
+            ended.send(WorldEventEnded { name: event.name });
+        }
+        running
+    });
+    for event in active.events.iter_mut() {
+        let fade = (event.duration * FADE_SHARE).min(MAX_FADE_HOURS);
+        let elapsed = now - event.started;
+        let remaining = event.duration - elapsed;
+        event.intensity = (elapsed / fade).min(remaining / fade).clamp(0.0, 1.0);
+    }
+
+    // Roll once per in-game hour
+    let hour_index = now.floor() as u64;
+    if registry.last_roll == Some(hour_index) {
+        return;
+    }
+    registry.last_roll = Some(hour_index);
+
+    for (salt, event_type) in registry.types.iter().enumerate() {
+        if active.is_active(event_type.name)
+            || !event_type.can_start_at(time_of_day.hour)
+            || unit_random(hour_index, salt as u32) >= event_type.chance
+        {
+            continue;
+        }
+        info!("World event {} started for {:.1} hours", event_type.name, event_type.duration_hours);
+        active.events.push(ActiveWorldEvent {
+            name: event_type.name,
+            started: now,
+            duration: event_type.duration_hours,
+            intensity: 0.0,
+        });
+        started.send(WorldEventStarted { name: event_type.name });
+    }
+}
@@ -0,0 +1,125 @@
+use crate::streaming::ChunkCoords;
+use crate::terrain;
+
+// What a stream is drawn for, so adding draws to one never shifts another's placements
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RngPurpose {
+    Vegetation,
+    Structures,
+    Creatures,
+    Predators,
+    Loot,
+    Birds,
+}
+
+impl RngPurpose {
+    fn tag(self) -> u64 {
+        match self {
+            RngPurpose::Vegetation => 0x7665_6765,
+            RngPurpose::Structures => 0x7374_7275,
+            RngPurpose::Creatures => 0x6372_6561,
+            RngPurpose::Predators => 0x7072_6564,
+            RngPurpose::Loot => 0x6c6f_6f74,
+            RngPurpose::Birds => 0x6269_7264,
+        }
+    }
+}
+
+// SplitMix64 finalizer, spreading every input bit over the whole output
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+// Deterministic random stream for procedural placement. The same world seed, chunk and
+// purpose always give the same numbers, on every machine, so the client and the server
+// place the same things without sending them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorldRng {
+    state: u64,
+}
+
+impl WorldRng {
+    pub fn new(seed: u32, chunk: ChunkCoords, purpose: RngPurpose) -> Self {
+        let coords = (chunk.0 as u32 as u64) | ((chunk.1 as u32 as u64) << 32);
+        let state = mix(mix(mix(seed as u64 ^ GOLDEN_GAMMA) ^ coords) ^ purpose.tag());
+        Self { state }
+    }
+
+    // Stream for a chunk of the current world
+    pub fn for_chunk(chunk: ChunkCoords, purpose: RngPurpose) -> Self {
+        Self::new(terrain::world_gen().seed, chunk, purpose)
+    }
+
+    // Independent stream for the index-th thing in the chunk, like its second school of fish,
+    // so how many numbers one of them draws doesn't change the others
+    pub fn substream(&self, index: u32) -> Self {
+        Self { state: mix(self.state ^ mix((index as u64).wrapping_add(GOLDEN_GAMMA))) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    // In [0, 1)
+    pub fn unit(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // In [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.unit()
+    }
+
+    // Index below `count`, which must not be zero
+    pub fn index(&mut self, count: usize) -> usize {
+        (self.next_u64() % count as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.unit() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(mut rng: WorldRng) -> Vec<u64> {
+        (0..8).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn same_inputs_give_the_same_stream() {
+        assert_eq!(draws(WorldRng::new(42, (3, -7), RngPurpose::Loot)), draws(WorldRng::new(42, (3, -7), RngPurpose::Loot)));
+        assert_eq!(
+            draws(WorldRng::new(42, (3, -7), RngPurpose::Loot).substream(2)),
+            draws(WorldRng::new(42, (3, -7), RngPurpose::Loot).substream(2)),
+        );
+    }
+
+    #[test]
+    fn streams_differ_by_seed_chunk_purpose_and_index() {
+        let base = draws(WorldRng::new(42, (3, -7), RngPurpose::Loot));
+        assert_ne!(base, draws(WorldRng::new(43, (3, -7), RngPurpose::Loot)));
+        assert_ne!(base, draws(WorldRng::new(42, (-7, 3), RngPurpose::Loot)));
+        assert_ne!(base, draws(WorldRng::new(42, (3, -7), RngPurpose::Creatures)));
+        let rng = WorldRng::new(42, (3, -7), RngPurpose::Loot);
+        assert_ne!(draws(rng.substream(0)), draws(rng.substream(1)));
+    }
+
+    #[test]
+    fn units_stay_in_range() {
+        let mut rng = WorldRng::new(7, (0, 0), RngPurpose::Birds);
+        for _ in 0..1000 {
+            let value = rng.unit();
+            assert!((0.0..1.0).contains(&value));
+            assert!((2.0..5.0).contains(&rng.range(2.0, 5.0)));
+            assert!(rng.index(3) < 3);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use crate::client::ChunkManager;
+use crate::net::{ClientMessage, ServerConnection, ServerEvent, ServerMessage};
+use crate::palette::{ActiveTerrainPalette, TerrainPalette};
+use crate::terrain::{self, WorldGenSettings};
+use crate::terrain_edit::TerrainEdits;
+use crate::ui_window::{UiWindow, UiWindowAppExt, UiWindows};
+
+const WORLD_GEN_WINDOW: &str = "world_gen";
+const PRESET_DIR: &str = "presets";
+const PRESET_EXTENSION: &str = ".preset.ron";
+
+// World Gen window: tweak the noise terrain and regenerate it, and export the current noise
+// settings and palette as named presets to share terrain recipes. The server owns the noise
+// settings: the window asks it to switch, and every client rebuilds its terrain when the
+// server sends them. Palettes only color this client's terrain.
+#[derive(Default, Clone, Debug)]
+pub struct WorldGenPlugin;
+
+impl Plugin for WorldGenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<WorldGenPresets>()
+            .register_ui_window(UiWindow {
+                id: WORLD_GEN_WINDOW,
+                title: "World Gen",
+                shortcut: None,
+                open: false,
+            })
+            .add_systems(Startup, list_presets_on_startup)
+            .add_systems(Update, (apply_server_world_gen, world_gen_ui_system));
+    }
+}
+
+// A terrain recipe, saved as `presets/<name>.preset.ron`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WorldGenPreset {
+    pub worldgen: WorldGenSettings,
+    pub palette: TerrainPalette,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PresetError {
+    #[error("preset names may only use letters, digits, '-' and '_'")]
+    InvalidName,
+    #[error("could not access {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("could not parse preset: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not serialize preset: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+fn preset_path(name: &str) -> Result<PathBuf, PresetError> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(PresetError::InvalidName);
+    }
+    Ok(Path::new(PRESET_DIR).join(format!("{}{}", name, PRESET_EXTENSION)))
+}
+
+impl WorldGenPreset {
+    pub fn read(name: &str) -> Result<Self, PresetError> {
+        let path = preset_path(name)?;
+        let text = fs::read_to_string(&path).map_err(|err| PresetError::Io(path, err))?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn write(&self, name: &str) -> Result<(), PresetError> {
+        let path = preset_path(name)?;
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::create_dir_all(PRESET_DIR).map_err(|err| PresetError::Io(PRESET_DIR.into(), err))?;
+        fs::write(&path, text).map_err(|err| PresetError::Io(path, err))
+    }
+}
+
+// Names of the saved presets, sorted
+pub fn list_presets() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(PRESET_DIR) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(PRESET_EXTENSION).map(str::to_string))
+        .collect();
+    names.sort();
+    names
+}
+
+// State of the World Gen window
+#[derive(Resource, Default)]
+struct WorldGenPresets {
+    names: Vec<String>,
+    // Noise settings being edited, applied with Regenerate
+    draft: Option<WorldGenSettings>,
+    // Name typed for the next export
+    name: String,
+    status: String,
+}
+
+enum WorldGenAction {
+    Regenerate(WorldGenSettings),
+    Export(String),
+    Apply(String),
+    Refresh,
+}
+
+fn list_presets_on_startup(mut presets: ResMut<WorldGenPresets>) {
+    presets.names = list_presets();
+}
+
+// Terrain settings from the server, when joining and whenever the host changes them
+fn apply_server_world_gen(
+    mut server_events: EventReader<ServerEvent>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    chunk_manager: Res<ChunkManager>,
+    // Settings the loaded chunks were meshed with, None until the server sent any
+    mut meshed: Local<Option<WorldGenSettings>>,
+) {
+    for ServerEvent(message) in server_events.read() {
+        if let ServerMessage::WorldGen { settings } = message
+            && *meshed != Some(*settings)
+        {
+            // The local server already switched the shared noise, a remote one didn't
+            terrain::set_world_gen(*settings);
+            terrain_edits.remesh_all(&chunk_manager);
+            *meshed = Some(*settings);
+            info!("Regenerating terrain with seed {}", settings.seed);
+        }
+    }
+}
+
+fn world_gen_ui_system(
+    mut contexts: EguiContexts,
+    mut windows: ResMut<UiWindows>,
+    mut presets: ResMut<WorldGenPresets>,
+    mut active_palette: ResMut<ActiveTerrainPalette>,
+    mut terrain_edits: ResMut<TerrainEdits>,
+    chunk_manager: Res<ChunkManager>,
+    mut connection: ResMut<ServerConnection>,
+) {
+    let Some(window) = windows.window(WORLD_GEN_WINDOW) else {
+        return;
+    };
+    let presets = presets.as_mut();
+    let mut action = None;
+
+    window.show(contexts.ctx_mut(), |ui| {
+        ui.heading("Noise");
+        let draft = presets.draft.get_or_insert_with(terrain::world_gen);
+        ui.add(egui::DragValue::new(&mut draft.seed).prefix("Seed "));
+        ui.add(egui::Slider::new(&mut draft.octaves, 1..=10).text("Octaves"));
+        ui.add(egui::Slider::new(&mut draft.frequency, 0.005..=0.2).logarithmic(true).text("Frequency"));
+        ui.add(egui::Slider::new(&mut draft.persistence, 0.2..=0.9).text("Persistence"));
+        ui.add(egui::Slider::new(&mut draft.lacunarity, 1.5..=3.0).text("Lacunarity"));
+        ui.add(egui::Slider::new(&mut draft.amplitude, 2.0..=60.0).text("Amplitude"));
+        ui.add(egui::Slider::new(&mut draft.detail_frequency, 0.005..=0.2).logarithmic(true).text("Detail frequency"));
+        ui.add(egui::Slider::new(&mut draft.detail_amplitude, 0.0..=10.0).text("Detail amplitude"));
+        ui.horizontal(|ui| {
+            if ui.button("Regenerate").clicked() {
+                action = Some(WorldGenAction::Regenerate(*draft));
+            }
+            if ui.button("Defaults").clicked() {
+                *draft = WorldGenSettings::default();
+            }
+        });
+
+        ui.separator();
+        ui.heading("Presets");
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut presets.name);
+            if ui.button("Export").on_hover_text("Save the current terrain and palette").clicked() {
+                action = Some(WorldGenAction::Export(presets.name.trim().to_string()));
+            }
+        });
+        if presets.names.is_empty() {
+            ui.label(format!("No presets in {}/", PRESET_DIR));
+        }
+        for name in &presets.names {
+            ui.horizontal(|ui| {
+                ui.label(name);
+                if ui.button("Apply").clicked() {
+                    action = Some(WorldGenAction::Apply(name.clone()));
+                }
+            });
+        }
+        if ui.button("Refresh").clicked() {
+            action = Some(WorldGenAction::Refresh);
+        }
+        if !presets.status.is_empty() {
+            ui.label(&presets.status);
+        }
+    });
+
+    let Some(action) = action else {
+        return;
+    };
+    presets.status = match action {
+        WorldGenAction::Regenerate(settings) => {
+            connection.send(&ClientMessage::SetWorldGen { settings });
+            "Asked the server to regenerate the terrain".to_string()
+        }
+        WorldGenAction::Export(name) => {
+            let preset = WorldGenPreset { worldgen: terrain::world_gen(), palette: active_palette.0.clone() };
+            match preset.write(&name) {
+                Ok(()) => {
+                    presets.names = list_presets();
+                    format!("Exported preset {}", name)
+                }
+                Err(err) => format!("Could not export {}: {}", name, err),
+            }
+        }
+        WorldGenAction::Apply(name) => match WorldGenPreset::read(&name) {
+            Ok(preset) => {
+                presets.draft = Some(preset.worldgen);
+                active_palette.set(preset.palette);
+                terrain_edits.remesh_all(&chunk_manager);
+                connection.send(&ClientMessage::SetWorldGen { settings: preset.worldgen });
+                format!("Applied preset {}, the server regenerates the terrain", name)
+            }
+            Err(err) => format!("Could not apply {}: {}", name, err),
+        },
+        WorldGenAction::Refresh => {
+            presets.names = list_presets();
+            String::new()
+        }
+    };
+    if !presets.status.is_empty() {
+        info!("{}", presets.status);
+    }
+}